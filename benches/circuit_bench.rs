@@ -0,0 +1,58 @@
+//! Compiles the corpus in `benches/corpus` once for its sizes, failing if a circuit grew
+//! past `benches/baseline.json` by more than the regression threshold, then times each
+//! compilation with criterion.
+//!
+//! The first run, or any run with `FCMC_UPDATE_BASELINE=1`, writes the baseline instead;
+//! commit it with the change that moved the sizes.
+
+use criterion::{black_box, criterion_group, Criterion};
+use fcmc_compiler::bench::{self, Baseline, PeakAllocator, CORPUS, DEFAULT_REGRESSION_THRESHOLD, UPDATE_BASELINE_ENV};
+use fcmc_compiler::FCMC;
+use std::path::Path;
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator::new();
+
+const BASELINE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/baseline.json");
+
+fn check_sizes() {
+    let current = bench::run(&FCMC::new(), CORPUS, Some(&ALLOCATOR)).unwrap_or_else(|e| panic!("{}", e));
+    let path = Path::new(BASELINE);
+    if std::env::var_os(UPDATE_BASELINE_ENV).is_some() || !path.exists() {
+        current.save(path).unwrap_or_else(|e| panic!("{}", e));
+        println!("wrote {}", path.display());
+        return;
+    }
+    let baseline = Baseline::load(path).unwrap_or_else(|e| panic!("{}", e));
+    for line in bench::summary(&baseline, &current) {
+        println!("{}", line);
+    }
+    let regressions = bench::compare(&baseline, &current, DEFAULT_REGRESSION_THRESHOLD);
+    if !regressions.is_empty() {
+        for regression in &regressions {
+            eprintln!("regression: {}", regression.describe());
+        }
+        panic!(
+            "{} circuit size regression(s); rerun with {}=1 to accept them",
+            regressions.len(),
+            UPDATE_BASELINE_ENV
+        );
+    }
+}
+
+fn compile_corpus(c: &mut Criterion) {
+    let compiler = FCMC::new();
+    for (name, source) in CORPUS {
+        c.bench_function(&format!("compile {}", name), |b| {
+            b.iter(|| compiler.compile(black_box(source)).expect("corpus compiles"))
+        });
+    }
+}
+
+criterion_group!(benches, compile_corpus);
+
+fn main() {
+    check_sizes();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
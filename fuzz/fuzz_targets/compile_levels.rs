@@ -0,0 +1,14 @@
+//! Random well-typed programs must compute the same outputs at every optimization level
+//! as the interpreter does on the unoptimized program.
+
+#![no_main]
+
+use fcmc_compiler::fuzz::{check_program, program_from_bytes, DEFAULT_FUZZ_SAMPLES};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = program_from_bytes(data);
+    if let Err(e) = check_program(&source, DEFAULT_FUZZ_SAMPLES) {
+        panic!("{}\n\n{}", e, source);
+    }
+});
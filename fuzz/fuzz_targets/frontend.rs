@@ -0,0 +1,12 @@
+//! Arbitrary source text may be rejected with an error, but must never panic the compiler.
+
+#![no_main]
+
+use fcmc_compiler::FCMC;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        let _ = FCMC::new().compile(source);
+    }
+});
@@ -0,0 +1,994 @@
+//! Versioned binary format for compiled circuits, so a circuit compiled once, in CI say,
+//! is loaded by proving services without running the compiler again.
+//!
+//! A file is the magic `FCMC`, the format version and a list of sections, each a kind
+//! byte and a `u64` byte length: the header (target and field), the signature, the
+//! optimized IR (nodes with their attributes, which hold the source map, then edges,
+//! loops and tables), the R1CS constraints, the stats, the outline of the source's
+//! functions and loops, which profiles read and which older files may lack, and the
+//! audit boundaries, written only for circuits with some. Integers are little-endian and
+//! field elements are length-prefixed little-endian bytes. Readers skip sections of
+//! unknown kinds. Circuits for targets other than R1CS are lowered again from the stored
+//! IR on load, which costs little next to parsing and optimizing.
+//!
+//! Nothing time-dependent is stored, timings included, so compiling the same source with
+//! the same options gives the same bytes.
+
+use crate::backend::custom_gate::CustomGate;
+use crate::backend::lowering::{BlackBoxCall, CustomGateCall, FunctionTable, LinearCombination, TableLookupCall, WireSource};
+use crate::backend::public_inputs::{packed_inputs, public_order, PackedInputs};
+use crate::backend::tags::node_tags;
+use crate::backend::{self, CircuitBackend, R1CSCircuit, R1CSConstraint, SanityReport, TargetCircuit, TargetSystem};
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNode, IRNodeType, LookupTable, LoopRegion};
+use crate::language::ast::Type;
+use crate::optimization::field::FieldConfig;
+use crate::optimization::{InlineReport, OptimizationStats};
+use crate::profile::{Region, RegionKind, SourceOutline};
+use crate::memory::MemoryReport;
+use crate::report::{CompilationReport, TargetMetrics};
+use crate::{AuditBoundary, CompilationStats, CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use std::collections::{HashMap, HashSet};
+
+const MAGIC: &[u8; 4] = b"FCMC";
+
+/// Version written to new files; files of any other version are rejected
+pub const FORMAT_VERSION: u32 = 2;
+
+const HEADER_SECTION: u8 = 1;
+const SIGNATURE_SECTION: u8 = 2;
+const IR_SECTION: u8 = 3;
+const R1CS_SECTION: u8 = 4;
+const STATS_SECTION: u8 = 5;
+const OUTLINE_SECTION: u8 = 6;
+const AUDIT_SECTION: u8 = 7;
+
+/// Names of the values a prover supplies and a verifier reads, in wire order
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CircuitSignature {
+    pub public_outputs: Vec<String>,
+    pub public_inputs: Vec<String>,
+    pub private_inputs: Vec<String>,
+    /// Outputs packing inputs made private, and what each packs
+    pub packed: Vec<PackedInputs>,
+}
+
+impl CircuitSignature {
+    pub fn of(graph: &IRGraph) -> Self {
+        let names = |nodes: Vec<usize>| -> Vec<String> {
+            nodes
+                .into_iter()
+                .filter_map(|node_id| match &graph.get_node(node_id)?.node_type {
+                    IRNodeType::Output(name) | IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => {
+                        Some(name.clone())
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        Self {
+            public_outputs: names(public_order(graph, |node_type| matches!(node_type, IRNodeType::Output(_)))),
+            public_inputs: names(public_order(graph, |node_type| matches!(node_type, IRNodeType::Input(_)))),
+            private_inputs: names(
+                graph
+                    .live_nodes()
+                    .filter(|node| matches!(node.node_type, IRNodeType::PrivateInput(_)))
+                    .map(|node| node.id)
+                    .collect(),
+            ),
+            packed: packed_inputs(graph),
+        }
+    }
+}
+
+/// The file contents for `circuit`
+pub fn encode(circuit: &CompiledCircuit) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+    let mut header = Encoder::default();
+    header.target(&circuit.circuit.target());
+    header.biguint(&circuit.field.modulus());
+    section(&mut out, HEADER_SECTION, header);
+
+    let mut signature = Encoder::default();
+    signature.signature(&circuit.signature());
+    section(&mut out, SIGNATURE_SECTION, signature);
+
+    let mut ir = Encoder::default();
+    ir.graph(&circuit.ir);
+    section(&mut out, IR_SECTION, ir);
+
+    if let Some(r1cs) = circuit.circuit.as_r1cs() {
+        let mut constraints = Encoder::default();
+        constraints.r1cs(r1cs);
+        section(&mut out, R1CS_SECTION, constraints);
+    }
+
+    let mut stats = Encoder::default();
+    stats.stats(&circuit.stats);
+    section(&mut out, STATS_SECTION, stats);
+
+    let mut outline = Encoder::default();
+    outline.outline(&circuit.stats.outline);
+    section(&mut out, OUTLINE_SECTION, outline);
+
+    if !circuit.stats.audit.is_empty() {
+        let mut audit = Encoder::default();
+        audit.audit(&circuit.stats.audit);
+        section(&mut out, AUDIT_SECTION, audit);
+    }
+    out
+}
+
+fn section(out: &mut Vec<u8>, kind: u8, contents: Encoder) {
+    out.push(kind);
+    out.extend_from_slice(&(contents.bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(&contents.bytes);
+}
+
+/// Sections of a file, by kind, after its magic and version are checked
+fn sections(bytes: &[u8]) -> Result<HashMap<u8, &[u8]>, FCMCError> {
+    let mut reader = Decoder::new(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(corrupt("not an FCMC circuit file"));
+    }
+    let version = reader.u32()?;
+    if version != FORMAT_VERSION {
+        return Err(FCMCError::BackendError(format!(
+            "Circuit file has format version {}, this compiler reads version {}",
+            version, FORMAT_VERSION
+        )));
+    }
+    let mut sections = HashMap::new();
+    while !reader.is_empty() {
+        let kind = reader.u8()?;
+        let length = reader.usize()?;
+        sections.insert(kind, reader.take(length)?);
+    }
+    Ok(sections)
+}
+
+fn required<'a>(sections: &HashMap<u8, &'a [u8]>, kind: u8, name: &str) -> Result<Decoder<'a>, FCMCError> {
+    sections
+        .get(&kind)
+        .copied()
+        .map(Decoder::new)
+        .ok_or_else(|| corrupt(&format!("the {} section is missing", name)))
+}
+
+/// Only the signature of a stored circuit, without rebuilding it
+pub fn decode_signature(bytes: &[u8]) -> Result<CircuitSignature, FCMCError> {
+    required(&sections(bytes)?, SIGNATURE_SECTION, "signature")?.signature()
+}
+
+/// The circuit stored in `bytes`
+pub fn decode(bytes: &[u8]) -> Result<CompiledCircuit, FCMCError> {
+    let sections = sections(bytes)?;
+    let mut header = required(&sections, HEADER_SECTION, "header")?;
+    let target = header.target()?;
+    let field = FieldConfig::from_modulus(header.biguint()?)?;
+
+    let ir = required(&sections, IR_SECTION, "IR")?.graph()?;
+    if required(&sections, SIGNATURE_SECTION, "signature")?.signature()? != CircuitSignature::of(&ir) {
+        return Err(corrupt("the signature does not match the IR"));
+    }
+    let mut stats = required(&sections, STATS_SECTION, "stats")?.stats()?;
+    if let Some(&outline) = sections.get(&OUTLINE_SECTION) {
+        stats.outline = Decoder::new(outline).outline()?;
+    }
+    if let Some(&audit) = sections.get(&AUDIT_SECTION) {
+        stats.audit = Decoder::new(audit).audit()?;
+    }
+
+    let circuit = if target == TargetSystem::R1CS {
+        let mut r1cs = required(&sections, R1CS_SECTION, "R1CS")?.r1cs(field.clone())?;
+        r1cs.tags = node_tags(&ir, r1cs.origin_nodes());
+        TargetCircuit::R1CS(r1cs)
+    } else {
+        backend::compile_to_target_in(&ir, target, &field)?
+    };
+    if circuit.constraint_count() != stats.constraint_count {
+        return Err(corrupt("the constraint count does not match the stats"));
+    }
+    let rows = circuit.constraint_count();
+    if stats.audit.iter().flat_map(|boundary| &boundary.rows).any(|range| range.end > rows) {
+        return Err(corrupt("an audit boundary's rows are past the last row"));
+    }
+    let signature = CircuitSignature::of(&ir);
+    stats.witness_size = circuit.wire_count();
+    stats.public_inputs = signature.public_inputs.len();
+    stats.public_outputs = signature.public_outputs.len();
+    stats.target = TargetMetrics::of(&circuit);
+    stats.memory = MemoryReport::of(&ir, &circuit);
+    Ok(CompiledCircuit { ir, circuit, field, stats })
+}
+
+fn corrupt(reason: &str) -> FCMCError {
+    FCMCError::BackendError(format!("Corrupt circuit file: {}", reason))
+}
+
+#[derive(Default)]
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.usize(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    fn option_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.str(value);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn strings(&mut self, values: &[String]) {
+        self.usize(values.len());
+        for value in values {
+            self.str(value);
+        }
+    }
+
+    fn usizes(&mut self, values: &[usize]) {
+        self.usize(values.len());
+        for &value in values {
+            self.usize(value);
+        }
+    }
+
+    fn u32s(&mut self, values: &[u32]) {
+        self.usize(values.len());
+        for &value in values {
+            self.u32(value);
+        }
+    }
+
+    fn biguint(&mut self, value: &BigUint) {
+        self.bytes(&value.to_bytes_le());
+    }
+
+    fn lc(&mut self, lc: &LinearCombination) {
+        self.usize(lc.len());
+        for (&wire, coefficient) in lc {
+            self.usize(wire);
+            self.biguint(coefficient);
+        }
+    }
+
+    fn lcs(&mut self, lcs: &[LinearCombination]) {
+        self.usize(lcs.len());
+        for lc in lcs {
+            self.lc(lc);
+        }
+    }
+
+    fn target(&mut self, target: &TargetSystem) {
+        match target {
+            TargetSystem::R1CS => self.u8(0),
+            TargetSystem::Plonk => self.u8(1),
+            TargetSystem::Halo2 => self.u8(2),
+            TargetSystem::AIR => self.u8(3),
+            TargetSystem::ACIR => self.u8(4),
+            TargetSystem::Plonky2 => self.u8(5),
+            TargetSystem::CCS => self.u8(6),
+            TargetSystem::Custom(name) => {
+                self.u8(7);
+                self.str(name);
+            }
+        }
+    }
+
+    fn signature(&mut self, signature: &CircuitSignature) {
+        self.strings(&signature.public_outputs);
+        self.strings(&signature.public_inputs);
+        self.strings(&signature.private_inputs);
+        self.usize(signature.packed.len());
+        for pack in &signature.packed {
+            self.str(&pack.name);
+            self.usize(pack.inputs.len());
+            for (name, bits) in &pack.inputs {
+                self.str(name);
+                self.u32(*bits);
+            }
+        }
+    }
+
+    fn data_type(&mut self, data_type: &Type) {
+        match data_type {
+            Type::Field => self.u8(0),
+            Type::Bool => self.u8(1),
+            Type::U32 => self.u8(2),
+            Type::Unit => self.u8(3),
+            Type::Array(element, size) => {
+                self.u8(4);
+                self.data_type(element);
+                self.usize(*size);
+            }
+        }
+    }
+
+    fn node_type(&mut self, node_type: &IRNodeType) {
+        self.u8(match node_type {
+            IRNodeType::Add => 0,
+            IRNodeType::Sub => 1,
+            IRNodeType::Mul => 2,
+            IRNodeType::Div => 3,
+            IRNodeType::Neg => 4,
+            IRNodeType::And => 5,
+            IRNodeType::Or => 6,
+            IRNodeType::Xor => 7,
+            IRNodeType::Not => 8,
+            IRNodeType::Eq => 9,
+            IRNodeType::Ne => 10,
+            IRNodeType::Lt => 11,
+            IRNodeType::Le => 12,
+            IRNodeType::Gt => 13,
+            IRNodeType::Ge => 14,
+            IRNodeType::Select => 15,
+            IRNodeType::Phi => 16,
+            IRNodeType::RangeCheck => 17,
+            IRNodeType::BitDecomposition => 18,
+            IRNodeType::Lookup => 19,
+            IRNodeType::Constant(_) => 20,
+            IRNodeType::Input(_) => 21,
+            IRNodeType::PrivateInput(_) => 22,
+            IRNodeType::Output(_) => 23,
+            IRNodeType::Constraint(_) => 24,
+        });
+        match node_type {
+            IRNodeType::Constant(value)
+            | IRNodeType::Input(value)
+            | IRNodeType::PrivateInput(value)
+            | IRNodeType::Output(value) => self.str(value),
+            IRNodeType::Constraint(constraint) => self.constraint_type(constraint),
+            _ => {}
+        }
+    }
+
+    fn constraint_type(&mut self, constraint: &ConstraintType) {
+        match constraint {
+            ConstraintType::Equality => self.u8(0),
+            ConstraintType::Inequality => self.u8(1),
+            ConstraintType::Range { bits } => {
+                self.u8(2);
+                self.u32(*bits);
+            }
+            ConstraintType::Polynomial { coefficients } => {
+                self.u8(3);
+                self.strings(coefficients);
+            }
+        }
+    }
+
+    fn graph(&mut self, graph: &IRGraph) {
+        self.usize(graph.nodes().len());
+        for node in graph.nodes() {
+            self.node_type(&node.node_type);
+            self.data_type(&node.data_type);
+            self.option_str(node.label.as_deref());
+            // Sorted, so the same graph always gives the same bytes
+            let mut attributes: Vec<_> = node.attributes.iter().collect();
+            attributes.sort();
+            self.usize(attributes.len());
+            for (key, value) in attributes {
+                self.str(key);
+                self.str(value);
+            }
+        }
+        let removed: Vec<usize> = (0..graph.nodes().len()).filter(|&id| graph.is_removed(id)).collect();
+        self.usizes(&removed);
+
+        self.usize(graph.edges().len());
+        for (from, to, edge_type) in graph.edges() {
+            self.usize(*from);
+            self.usize(*to);
+            self.u8(match edge_type {
+                EdgeType::DataFlow => 0,
+                EdgeType::ControlFlow => 1,
+                EdgeType::Constraint => 2,
+            });
+        }
+        self.usizes(graph.inputs());
+        self.usizes(graph.outputs());
+
+        self.usize(graph.loops().len());
+        for region in graph.loops() {
+            self.str(&region.induction_var);
+            self.u64(region.start);
+            self.u64(region.end);
+            self.usizes(&region.hoisted);
+        }
+        self.usize(graph.tables().len());
+        for table in graph.tables() {
+            self.str(&table.name);
+            self.u32s(&table.input_bits);
+            self.strings(&table.outputs);
+        }
+    }
+
+    fn wire_source(&mut self, source: &WireSource) {
+        match source {
+            WireSource::One => self.u8(0),
+            WireSource::Node(node_id) => {
+                self.u8(1);
+                self.usize(*node_id);
+            }
+            WireSource::Linear(lc) => {
+                self.u8(2);
+                self.lc(lc);
+            }
+            WireSource::Product(a, b) => {
+                self.u8(3);
+                self.lc(a);
+                self.lc(b);
+            }
+            WireSource::Inverse(lc) => {
+                self.u8(4);
+                self.lc(lc);
+            }
+            WireSource::Bit(lc, index) => {
+                self.u8(5);
+                self.lc(lc);
+                self.u32(*index);
+            }
+            WireSource::Byte(lc, index) => {
+                self.u8(6);
+                self.lc(lc);
+                self.u32(*index);
+            }
+            WireSource::IsZero(lc) => {
+                self.u8(7);
+                self.lc(lc);
+            }
+        }
+    }
+
+    fn r1cs(&mut self, circuit: &R1CSCircuit) {
+        self.usize(circuit.constraints.len());
+        for constraint in &circuit.constraints {
+            self.lc(&constraint.a);
+            self.lc(&constraint.b);
+            self.lc(&constraint.c);
+        }
+        self.usize(circuit.wires.len());
+        for source in &circuit.wires {
+            self.wire_source(source);
+        }
+        self.strings(&circuit.wire_names);
+        self.usize(circuit.public_outputs);
+        self.usize(circuit.public_inputs);
+        self.usize(circuit.private_inputs);
+
+        self.usize(circuit.ranges.len());
+        for (lc, bits) in &circuit.ranges {
+            self.lc(lc);
+            self.u32(*bits);
+        }
+        self.usize(circuit.black_boxes.len());
+        for call in &circuit.black_boxes {
+            self.str(&call.function);
+            self.lcs(&call.inputs);
+            self.usize(call.output);
+        }
+        self.usize(circuit.custom_gates.len());
+        for call in &circuit.custom_gates {
+            self.str(call.gate.name());
+            self.lcs(&call.inputs);
+            self.usize(call.output);
+        }
+        self.usize(circuit.tables.len());
+        for table in &circuit.tables {
+            self.str(&table.name);
+            self.u32s(&table.input_bits);
+            self.usize(table.values.len());
+            for value in &table.values {
+                self.biguint(value);
+            }
+        }
+        self.usize(circuit.table_lookups.len());
+        for call in &circuit.table_lookups {
+            self.usize(call.table);
+            self.lcs(&call.inputs);
+            self.usize(call.output);
+        }
+
+        self.usizes(&circuit.origins);
+        self.usizes(&circuit.range_origins);
+        self.usizes(&circuit.custom_gate_origins);
+        self.usizes(&circuit.table_lookup_origins);
+    }
+
+    fn stats(&mut self, stats: &CompilationStats) {
+        self.usize(stats.original_nodes);
+        self.usize(stats.optimized_nodes);
+        self.usize(stats.constraint_count);
+        self.usize(stats.duplicate_constraints);
+        self.usize(stats.optimization.iterations);
+        self.option_str(stats.optimization.stopped_early.as_deref());
+    }
+
+    fn outline(&mut self, outline: &SourceOutline) {
+        self.usize(outline.regions.len());
+        for region in &outline.regions {
+            let name = match &region.kind {
+                RegionKind::Function(name) => {
+                    self.u8(0);
+                    name
+                }
+                RegionKind::Loop(variable) => {
+                    self.u8(1);
+                    variable
+                }
+            };
+            self.str(name);
+            self.usize(region.start);
+            self.usize(region.end);
+        }
+    }
+
+    fn audit(&mut self, boundaries: &[AuditBoundary]) {
+        self.usize(boundaries.len());
+        for boundary in boundaries {
+            self.str(&boundary.name);
+            self.str(&boundary.function);
+            self.str(&boundary.content_hash);
+            self.usize(boundary.lines.0);
+            self.usize(boundary.lines.1);
+            self.usize(boundary.rows.len());
+            for range in &boundary.rows {
+                self.usize(range.start);
+                self.usize(range.end);
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], FCMCError> {
+        if length > self.bytes.len() {
+            return Err(corrupt("unexpected end of data"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, FCMCError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, FCMCError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, FCMCError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> Result<usize, FCMCError> {
+        usize::try_from(self.u64()?).map_err(|_| corrupt("length out of range"))
+    }
+
+    /// A count of items each at least `item_size` bytes long, checked against the data
+    /// left so a corrupt count cannot allocate more than the file holds
+    fn count(&mut self, item_size: usize) -> Result<usize, FCMCError> {
+        let count = self.usize()?;
+        if count.saturating_mul(item_size) > self.bytes.len() {
+            return Err(corrupt("unexpected end of data"));
+        }
+        Ok(count)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], FCMCError> {
+        let length = self.usize()?;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, FCMCError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| corrupt("invalid UTF-8 in a name"))
+    }
+
+    fn option_string(&mut self) -> Result<Option<String>, FCMCError> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.string()?)),
+        }
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, FCMCError> {
+        (0..self.count(8)?).map(|_| self.string()).collect()
+    }
+
+    fn usizes(&mut self) -> Result<Vec<usize>, FCMCError> {
+        (0..self.count(8)?).map(|_| self.usize()).collect()
+    }
+
+    fn u32s(&mut self) -> Result<Vec<u32>, FCMCError> {
+        (0..self.count(4)?).map(|_| self.u32()).collect()
+    }
+
+    fn biguint(&mut self) -> Result<BigUint, FCMCError> {
+        Ok(BigUint::from_bytes_le(self.bytes()?))
+    }
+
+    fn lc(&mut self) -> Result<LinearCombination, FCMCError> {
+        (0..self.count(16)?).map(|_| Ok((self.usize()?, self.biguint()?))).collect()
+    }
+
+    fn lcs(&mut self) -> Result<Vec<LinearCombination>, FCMCError> {
+        (0..self.count(8)?).map(|_| self.lc()).collect()
+    }
+
+    fn target(&mut self) -> Result<TargetSystem, FCMCError> {
+        Ok(match self.u8()? {
+            0 => TargetSystem::R1CS,
+            1 => TargetSystem::Plonk,
+            2 => TargetSystem::Halo2,
+            3 => TargetSystem::AIR,
+            4 => TargetSystem::ACIR,
+            5 => TargetSystem::Plonky2,
+            6 => TargetSystem::CCS,
+            7 => TargetSystem::Custom(self.string()?),
+            tag => return Err(corrupt(&format!("unknown target {}", tag))),
+        })
+    }
+
+    fn signature(&mut self) -> Result<CircuitSignature, FCMCError> {
+        Ok(CircuitSignature {
+            public_outputs: self.strings()?,
+            public_inputs: self.strings()?,
+            private_inputs: self.strings()?,
+            packed: (0..self.count(16)?)
+                .map(|_| {
+                    Ok(PackedInputs {
+                        name: self.string()?,
+                        inputs: (0..self.count(12)?)
+                            .map(|_| Ok((self.string()?, self.u32()?)))
+                            .collect::<Result<_, FCMCError>>()?,
+                    })
+                })
+                .collect::<Result<_, FCMCError>>()?,
+        })
+    }
+
+    fn data_type(&mut self) -> Result<Type, FCMCError> {
+        Ok(match self.u8()? {
+            0 => Type::Field,
+            1 => Type::Bool,
+            2 => Type::U32,
+            3 => Type::Unit,
+            4 => {
+                let element = self.data_type()?;
+                Type::Array(Box::new(element), self.usize()?)
+            }
+            tag => return Err(corrupt(&format!("unknown data type {}", tag))),
+        })
+    }
+
+    fn node_type(&mut self) -> Result<IRNodeType, FCMCError> {
+        Ok(match self.u8()? {
+            0 => IRNodeType::Add,
+            1 => IRNodeType::Sub,
+            2 => IRNodeType::Mul,
+            3 => IRNodeType::Div,
+            4 => IRNodeType::Neg,
+            5 => IRNodeType::And,
+            6 => IRNodeType::Or,
+            7 => IRNodeType::Xor,
+            8 => IRNodeType::Not,
+            9 => IRNodeType::Eq,
+            10 => IRNodeType::Ne,
+            11 => IRNodeType::Lt,
+            12 => IRNodeType::Le,
+            13 => IRNodeType::Gt,
+            14 => IRNodeType::Ge,
+            15 => IRNodeType::Select,
+            16 => IRNodeType::Phi,
+            17 => IRNodeType::RangeCheck,
+            18 => IRNodeType::BitDecomposition,
+            19 => IRNodeType::Lookup,
+            20 => IRNodeType::Constant(self.string()?),
+            21 => IRNodeType::Input(self.string()?),
+            22 => IRNodeType::PrivateInput(self.string()?),
+            23 => IRNodeType::Output(self.string()?),
+            24 => IRNodeType::Constraint(match self.u8()? {
+                0 => ConstraintType::Equality,
+                1 => ConstraintType::Inequality,
+                2 => ConstraintType::Range { bits: self.u32()? },
+                3 => ConstraintType::Polynomial {
+                    coefficients: self.strings()?,
+                },
+                tag => return Err(corrupt(&format!("unknown constraint type {}", tag))),
+            }),
+            tag => return Err(corrupt(&format!("unknown node type {}", tag))),
+        })
+    }
+
+    fn graph(&mut self) -> Result<IRGraph, FCMCError> {
+        let node_count = self.count(3)?;
+        let mut nodes = Vec::with_capacity(node_count);
+        for id in 0..node_count {
+            let node_type = self.node_type()?;
+            let data_type = self.data_type()?;
+            let label = self.option_string()?;
+            let attributes = (0..self.count(16)?)
+                .map(|_| Ok((self.string()?, self.string()?)))
+                .collect::<Result<HashMap<_, _>, FCMCError>>()?;
+            nodes.push(IRNode {
+                id,
+                node_type,
+                data_type,
+                label,
+                attributes,
+            });
+        }
+        let node = |id: usize| {
+            if id < node_count {
+                Ok(id)
+            } else {
+                Err(corrupt(&format!("reference to missing node {}", id)))
+            }
+        };
+        let nodes_of = |ids: Vec<usize>| ids.into_iter().map(&node).collect::<Result<Vec<_>, _>>();
+
+        let removed: HashSet<usize> = nodes_of(self.usizes()?)?.into_iter().collect();
+        let edge_count = self.count(17)?;
+        let mut edges = Vec::with_capacity(edge_count);
+        for _ in 0..edge_count {
+            let from = node(self.usize()?)?;
+            let to = node(self.usize()?)?;
+            let edge_type = match self.u8()? {
+                0 => EdgeType::DataFlow,
+                1 => EdgeType::ControlFlow,
+                2 => EdgeType::Constraint,
+                tag => return Err(corrupt(&format!("unknown edge type {}", tag))),
+            };
+            edges.push((from, to, edge_type));
+        }
+        let inputs = nodes_of(self.usizes()?)?;
+        let outputs = nodes_of(self.usizes()?)?;
+
+        let loops = (0..self.count(32)?)
+            .map(|_| {
+                Ok(LoopRegion {
+                    induction_var: self.string()?,
+                    start: self.u64()?,
+                    end: self.u64()?,
+                    hoisted: nodes_of(self.usizes()?)?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let tables = (0..self.count(24)?)
+            .map(|_| {
+                Ok(LookupTable {
+                    name: self.string()?,
+                    input_bits: self.u32s()?,
+                    outputs: self.strings()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+
+        Ok(IRGraph::from_parts(nodes, edges, inputs, outputs, removed, loops, tables))
+    }
+
+    fn wire_source(&mut self) -> Result<WireSource, FCMCError> {
+        Ok(match self.u8()? {
+            0 => WireSource::One,
+            1 => WireSource::Node(self.usize()?),
+            2 => WireSource::Linear(self.lc()?),
+            3 => WireSource::Product(self.lc()?, self.lc()?),
+            4 => WireSource::Inverse(self.lc()?),
+            5 => WireSource::Bit(self.lc()?, self.u32()?),
+            6 => WireSource::Byte(self.lc()?, self.u32()?),
+            7 => WireSource::IsZero(self.lc()?),
+            tag => return Err(corrupt(&format!("unknown wire source {}", tag))),
+        })
+    }
+
+    fn r1cs(&mut self, field: FieldConfig) -> Result<R1CSCircuit, FCMCError> {
+        let constraints = (0..self.count(24)?)
+            .map(|_| {
+                Ok(R1CSConstraint {
+                    a: self.lc()?,
+                    b: self.lc()?,
+                    c: self.lc()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let wires = (0..self.count(1)?).map(|_| self.wire_source()).collect::<Result<Vec<_>, _>>()?;
+        let wire_names = self.strings()?;
+        if wire_names.len() != wires.len() {
+            return Err(corrupt("every wire needs a name"));
+        }
+        let public_outputs = self.usize()?;
+        let public_inputs = self.usize()?;
+        let private_inputs = self.usize()?;
+
+        let ranges = (0..self.count(12)?)
+            .map(|_| Ok((self.lc()?, self.u32()?)))
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let black_boxes = (0..self.count(24)?)
+            .map(|_| {
+                Ok(BlackBoxCall {
+                    function: self.string()?,
+                    inputs: self.lcs()?,
+                    output: self.usize()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let custom_gates = (0..self.count(24)?)
+            .map(|_| {
+                let name = self.string()?;
+                let gate = CustomGate::from_name(&name)
+                    .ok_or_else(|| corrupt(&format!("unknown custom gate '{}'", name)))?;
+                Ok(CustomGateCall {
+                    gate,
+                    inputs: self.lcs()?,
+                    output: self.usize()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let tables = (0..self.count(24)?)
+            .map(|_| {
+                Ok(FunctionTable {
+                    name: self.string()?,
+                    input_bits: self.u32s()?,
+                    values: (0..self.count(8)?).map(|_| self.biguint()).collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        let table_lookups = (0..self.count(24)?)
+            .map(|_| {
+                Ok(TableLookupCall {
+                    table: self.usize()?,
+                    inputs: self.lcs()?,
+                    output: self.usize()?,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+
+        Ok(R1CSCircuit {
+            constraints,
+            wires,
+            wire_names,
+            public_outputs,
+            public_inputs,
+            private_inputs,
+            ranges,
+            black_boxes,
+            custom_gates,
+            tables,
+            table_lookups,
+            origins: self.usizes()?,
+            range_origins: self.usizes()?,
+            custom_gate_origins: self.usizes()?,
+            table_lookup_origins: self.usizes()?,
+            // Rebuilt from the IR by `decode`
+            tags: HashMap::new(),
+            field,
+        })
+    }
+
+    fn stats(&mut self) -> Result<CompilationStats, FCMCError> {
+        Ok(CompilationStats {
+            original_nodes: self.usize()?,
+            optimized_nodes: self.usize()?,
+            constraint_count: self.usize()?,
+            // Measured on the loaded circuit by `decode`
+            witness_size: 0,
+            public_inputs: 0,
+            public_outputs: 0,
+            target: TargetMetrics::Custom { rows: 0 },
+            duplicate_constraints: self.usize()?,
+            // Per-call, per-check and per-pass detail, timings and verification findings are not stored
+            inlining: InlineReport::default(),
+            sanity: SanityReport::default(),
+            equivalence: None,
+            suspicious_constraints: Vec::new(),
+            properties: None,
+            hints: None,
+            // Read from their own sections by `decode`
+            outline: SourceOutline::default(),
+            audit: Vec::new(),
+            report: CompilationReport::default(),
+            // Measured on the loaded circuit by `decode`
+            memory: MemoryReport::default(),
+            optimization: OptimizationStats {
+                iterations: self.usize()?,
+                stopped_early: self.option_string()?,
+                ..OptimizationStats::default()
+            },
+        })
+    }
+
+    fn outline(&mut self) -> Result<SourceOutline, FCMCError> {
+        let regions = (0..self.count(25)?)
+            .map(|_| {
+                let kind = self.u8()?;
+                let name = self.string()?;
+                let kind = match kind {
+                    0 => RegionKind::Function(name),
+                    1 => RegionKind::Loop(name),
+                    tag => return Err(corrupt(&format!("unknown source region {}", tag))),
+                };
+                Ok(Region {
+                    kind,
+                    start: self.usize()?,
+                    end: self.usize()?,
+                })
+            })
+            .collect::<Result<_, FCMCError>>()?;
+        Ok(SourceOutline { regions })
+    }
+
+    fn audit(&mut self) -> Result<Vec<AuditBoundary>, FCMCError> {
+        (0..self.count(48)?)
+            .map(|_| {
+                let name = self.string()?;
+                let function = self.string()?;
+                let content_hash = self.string()?;
+                let lines = (self.usize()?, self.usize()?);
+                let rows = (0..self.count(16)?)
+                    .map(|_| {
+                        let (start, end) = (self.usize()?, self.usize()?);
+                        if start > end {
+                            return Err(corrupt("an audit boundary's rows end before they start"));
+                        }
+                        Ok(start..end)
+                    })
+                    .collect::<Result<_, FCMCError>>()?;
+                Ok(AuditBoundary {
+                    name,
+                    function,
+                    content_hash,
+                    lines,
+                    rows,
+                })
+            })
+            .collect()
+    }
+}
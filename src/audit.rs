@@ -0,0 +1,181 @@
+//! Audit boundaries: which constraint rows of a circuit come from which audited gadget,
+//! and the hash of the source that was audited.
+
+use crate::backend::{CircuitBackend, TargetCircuit};
+use crate::optimization::properties::{attribute_end, line_of};
+use crate::profile::{RegionKind, SourceOutline};
+use crate::stdlib::sha256;
+use crate::FCMCError;
+use serde::Serialize;
+use std::ops::Range;
+
+pub const AUDIT_ATTRIBUTE: &str = "audit_boundary";
+
+/// An audited function and the rows it was lowered to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditBoundary {
+    /// The name given in the attribute, or the function's
+    pub name: String,
+    pub function: String,
+    /// Hex SHA-256 of the function's trimmed source lines
+    pub content_hash: String,
+    /// First and last source line of the function
+    pub lines: (usize, usize),
+    /// Rows lowered from the function, as ascending half-open ranges; empty if nothing
+    /// calls it or the target keeps no source positions
+    pub rows: Vec<Range<usize>>,
+}
+
+impl AuditBoundary {
+    /// Rows in all of the boundary's ranges
+    pub fn row_count(&self) -> usize {
+        self.rows.iter().map(|range| range.len()).sum()
+    }
+}
+
+/// Remove the `audit_boundary` attributes from `source`, returning the source the parser
+/// should see, with every other character in place, and the functions they mark, without
+/// rows yet
+pub fn extract_boundaries(source: &str) -> Result<(String, Vec<AuditBoundary>), FCMCError> {
+    let mut stripped = source.as_bytes().to_vec();
+    // Each marked function's name in the attribute, if given, and the function's name
+    let mut marked: Vec<(Option<String>, String)> = Vec::new();
+    // The attribute waiting for the item it marks, with its name and line
+    let mut pending: Option<(Option<String>, usize)> = None;
+    let mut position = 0;
+    let bytes = source.as_bytes();
+    while position < bytes.len() {
+        let rest = &source[position..];
+        if rest.starts_with("//") {
+            position += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("#[") {
+            let line = line_of(source, position);
+            let end = attribute_end(rest)
+                .ok_or_else(|| FCMCError::ParseError(format!("Unterminated attribute at line {}", line)))?;
+            if let Some(name) = boundary_name(&rest[2..end], line)? {
+                if pending.is_some() {
+                    return Err(FCMCError::ParseError(format!(
+                        "Function marked #[{}] twice at line {}",
+                        AUDIT_ATTRIBUTE, line
+                    )));
+                }
+                for byte in &mut stripped[position..=position + end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                pending = Some((name, line));
+            }
+            position += end + 1;
+            continue;
+        }
+        if let Some((name, line)) = &pending {
+            if !bytes[position].is_ascii_whitespace() {
+                let item: Vec<&str> = rest
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .filter(|word| !word.is_empty())
+                    .take(3)
+                    .collect();
+                let function = match item.as_slice() {
+                    ["fn", function, ..] | ["pub", "fn", function] => function.to_string(),
+                    _ => {
+                        return Err(FCMCError::ParseError(format!(
+                            "#[{}] at line {} must mark a function",
+                            AUDIT_ATTRIBUTE, line
+                        )))
+                    }
+                };
+                if marked.iter().any(|(_, other)| *other == function) {
+                    return Err(FCMCError::ParseError(format!(
+                        "Function '{}' has more than one #[{}]",
+                        function, AUDIT_ATTRIBUTE
+                    )));
+                }
+                marked.push((name.clone(), function));
+                pending = None;
+            }
+        }
+        position += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    if let Some((_, line)) = pending {
+        return Err(FCMCError::ParseError(format!(
+            "#[{}] at line {} marks nothing",
+            AUDIT_ATTRIBUTE, line
+        )));
+    }
+
+    let stripped = String::from_utf8(stripped)
+        .map_err(|e| FCMCError::ParseError(format!("Invalid source after removing audit boundaries: {}", e)))?;
+    let outline = SourceOutline::of(&stripped);
+    let lines: Vec<&str> = stripped.lines().collect();
+    let boundaries = marked
+        .into_iter()
+        .map(|(name, function)| {
+            let region = outline
+                .regions
+                .iter()
+                .find(|region| region.kind == RegionKind::Function(function.clone()))
+                .ok_or_else(|| FCMCError::ParseError(format!("Function '{}' has no body to audit", function)))?;
+            let body: Vec<&str> = lines[region.start - 1..region.end.min(lines.len())]
+                .iter()
+                .map(|line| line.trim())
+                .collect();
+            Ok(AuditBoundary {
+                name: name.unwrap_or_else(|| function.clone()),
+                function,
+                content_hash: hex::encode(sha256(body.join("\n").as_bytes())),
+                lines: (region.start, region.end),
+                rows: Vec::new(),
+            })
+        })
+        .collect::<Result<_, FCMCError>>()?;
+    Ok((stripped, boundaries))
+}
+
+/// The name an attribute such as `audit_boundary(sha-gadget)` gives, `Some(None)` for a
+/// bare `audit_boundary`, and `None` for other attributes
+fn boundary_name(attribute: &str, line: usize) -> Result<Option<Option<String>>, FCMCError> {
+    let Some(rest) = attribute.trim().strip_prefix(AUDIT_ATTRIBUTE) else {
+        return Ok(None);
+    };
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Ok(Some(None));
+    }
+    let name = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .map(|name| name.trim().trim_matches('"').trim())
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| {
+            FCMCError::ParseError(format!(
+                "Invalid #[{}] at line {}: expected a name in parentheses",
+                AUDIT_ATTRIBUTE, line
+            ))
+        })?;
+    Ok(Some(Some(name.to_string())))
+}
+
+/// Fill in each boundary's rows from the source positions of `circuit`'s rows
+pub fn assign_rows(boundaries: &mut [AuditBoundary], circuit: &TargetCircuit) {
+    let lines: Vec<Option<usize>> = circuit
+        .constraint_tags()
+        .into_iter()
+        .map(|tag| tag.and_then(|tag| tag.span).map(|span| span.line))
+        .collect();
+    for boundary in boundaries {
+        let (start, end) = boundary.lines;
+        boundary.rows.clear();
+        for (row, line) in lines.iter().enumerate() {
+            if !line.is_some_and(|line| start <= line && line <= end) {
+                continue;
+            }
+            match boundary.rows.last_mut() {
+                Some(range) if range.end == row => range.end = row + 1,
+                _ => boundary.rows.push(row..row + 1),
+            }
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! ACIR, the intermediate representation Noir compiles to and Barretenberg proves.
+//!
+//! Each R1CS row becomes one `AssertZero` opcode over a degree-2 expression; the constant
+//! one wire disappears into the expressions' constant terms, so wire `w` is witness
+//! `w - 1`. Range checks stay native `RANGE` black-box calls, and gadgets marked with a
+//! `blackbox` attribute become calls to Barretenberg's hash implementations, Poseidon2
+//! among them.
+
+use crate::backend::lowering::{
+    assign_wires, lc_wire, BlackBoxCall, LinearCombination, WireSource, ONE_WIRE, POSEIDON_BLACK_BOX,
+};
+use crate::backend::r1cs::{R1CSCircuit, R1CSConstraint};
+use crate::backend::{CircuitBackend, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+
+/// Bytes in the digest of the byte-oriented hashes
+pub const DIGEST_BYTES: u32 = 32;
+
+/// `Σ q·w_i·w_j + Σ q·w + q_c = 0`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AcirExpression {
+    pub mul_terms: Vec<(BigUint, usize, usize)>,
+    pub linear_combinations: Vec<(BigUint, usize)>,
+    pub q_c: BigUint,
+}
+
+impl AcirExpression {
+    /// `a * b - c` for an R1CS row, over witness indices
+    fn from_row(row: &R1CSConstraint) -> Self {
+        let mut quadratic: BTreeMap<(usize, usize), BigUint> = BTreeMap::new();
+        let mut linear: BTreeMap<usize, BigUint> = BTreeMap::new();
+        let mut constant = BigUint::zero();
+        let mut accumulate = |key: (usize, usize), coefficient: BigUint| match key {
+            (ONE_WIRE, ONE_WIRE) => constant = field::add(&constant, &coefficient),
+            (ONE_WIRE, wire) | (wire, ONE_WIRE) => {
+                let entry = linear.entry(witness(wire)).or_insert_with(BigUint::zero);
+                *entry = field::add(entry, &coefficient);
+            }
+            (x, y) => {
+                let entry = quadratic
+                    .entry((witness(x.min(y)), witness(x.max(y))))
+                    .or_insert_with(BigUint::zero);
+                *entry = field::add(entry, &coefficient);
+            }
+        };
+        for (&x, a) in &row.a {
+            for (&y, b) in &row.b {
+                accumulate((x, y), field::mul(a, b));
+            }
+        }
+        for (&wire, c) in &row.c {
+            accumulate((ONE_WIRE, wire), field::neg(c));
+        }
+
+        Self {
+            mul_terms: quadratic
+                .into_iter()
+                .filter(|(_, q)| !q.is_zero())
+                .map(|((x, y), q)| (q, x, y))
+                .collect(),
+            linear_combinations: linear
+                .into_iter()
+                .filter(|(_, q)| !q.is_zero())
+                .map(|(w, q)| (q, w))
+                .collect(),
+            q_c: constant,
+        }
+    }
+
+    /// `lc - w = 0`
+    fn binding(lc: &LinearCombination, wire: usize) -> Self {
+        let one = LinearCombination::from([(ONE_WIRE, BigUint::one())]);
+        Self::from_row(&R1CSConstraint {
+            a: lc.clone(),
+            b: one,
+            c: lc_wire(wire),
+        })
+    }
+
+    pub fn evaluate(&self, witnesses: &[BigUint]) -> BigUint {
+        let value = |index: usize| witnesses.get(index).cloned().unwrap_or_else(BigUint::zero);
+        let mut sum = self.q_c.clone();
+        for (q, x, y) in &self.mul_terms {
+            sum = field::add(&sum, &field::mul(q, &field::mul(&value(*x), &value(*y))));
+        }
+        for (q, w) in &self.linear_combinations {
+            sum = field::add(&sum, &field::mul(q, &value(*w)));
+        }
+        sum
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "mul_terms": self
+                .mul_terms
+                .iter()
+                .map(|(q, x, y)| json!([q.to_string(), x, y]))
+                .collect::<Vec<_>>(),
+            "linear_combinations": self
+                .linear_combinations
+                .iter()
+                .map(|(q, w)| json!([q.to_string(), w]))
+                .collect::<Vec<_>>(),
+            "q_c": self.q_c.to_string(),
+        })
+    }
+}
+
+/// Wire index to ACIR witness index
+fn witness(wire: usize) -> usize {
+    wire - 1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BlackBoxFunction {
+    Range,
+    Sha256,
+    Blake2s,
+    Blake3,
+    Keccak256,
+    PedersenHash,
+    /// Poseidon2 hash of field elements, as the stdlib's Poseidon gadget computes it
+    Poseidon2Hash,
+}
+
+impl BlackBoxFunction {
+    /// Every `blackbox` attribute value with a native function
+    pub const NAMES: [&'static str; 7] = [
+        "sha256",
+        "blake2s",
+        "blake3",
+        "keccak256",
+        "pedersen",
+        "pedersen_hash",
+        POSEIDON_BLACK_BOX,
+    ];
+
+    /// The hash a `blackbox` attribute names
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(BlackBoxFunction::Sha256),
+            "blake2s" => Some(BlackBoxFunction::Blake2s),
+            "blake3" => Some(BlackBoxFunction::Blake3),
+            "keccak256" => Some(BlackBoxFunction::Keccak256),
+            "pedersen" | "pedersen_hash" => Some(BlackBoxFunction::PedersenHash),
+            POSEIDON_BLACK_BOX => Some(BlackBoxFunction::Poseidon2Hash),
+            _ => None,
+        }
+    }
+
+    /// Hashes over bytes return a 32-byte digest; the rest return one field element
+    pub fn hashes_bytes(&self) -> bool {
+        matches!(
+            self,
+            BlackBoxFunction::Sha256 | BlackBoxFunction::Blake2s | BlackBoxFunction::Blake3 | BlackBoxFunction::Keccak256
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FunctionInput {
+    pub witness: usize,
+    pub num_bits: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Opcode {
+    AssertZero(AcirExpression),
+    BlackBoxFuncCall {
+        function: BlackBoxFunction,
+        inputs: Vec<FunctionInput>,
+        outputs: Vec<usize>,
+    },
+}
+
+impl Opcode {
+    pub fn to_json(&self) -> Value {
+        match self {
+            Opcode::AssertZero(expression) => json!({ "AssertZero": expression.to_json() }),
+            Opcode::BlackBoxFuncCall {
+                function,
+                inputs,
+                outputs,
+            } => json!({
+                "BlackBoxFuncCall": { "function": function, "inputs": inputs, "outputs": outputs }
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AcirCircuit {
+    pub opcodes: Vec<Opcode>,
+    pub private_parameters: Vec<usize>,
+    pub public_parameters: Vec<usize>,
+    pub return_values: Vec<usize>,
+    /// How the prover computes each wire; witness `i` is wire `i + 1`
+    pub wires: Vec<WireSource>,
+    pub wire_names: Vec<String>,
+}
+
+impl AcirCircuit {
+    pub fn compile(graph: &IRGraph) -> Result<Self, FCMCError> {
+        let r1cs = R1CSCircuit::compile_with(graph, TargetSystem::ACIR.capabilities().lowering_options())?;
+        Self::from_r1cs(&r1cs)
+    }
+
+    pub fn from_r1cs(r1cs: &R1CSCircuit) -> Result<Self, FCMCError> {
+        let outputs = 1..=r1cs.public_outputs;
+        let public = outputs.end() + 1..=outputs.end() + r1cs.public_inputs;
+        let private = public.end() + 1..=public.end() + r1cs.private_inputs;
+        let mut circuit = Self {
+            opcodes: Vec::new(),
+            return_values: outputs.map(witness).collect(),
+            public_parameters: public.map(witness).collect(),
+            private_parameters: private.map(witness).collect(),
+            wires: r1cs.wires.clone(),
+            wire_names: r1cs.wire_names.clone(),
+        };
+
+        for row in &r1cs.constraints {
+            let expression = AcirExpression::from_row(row);
+            if expression != AcirExpression::default() {
+                circuit.opcodes.push(Opcode::AssertZero(expression));
+            }
+        }
+        for (value, bits) in &r1cs.ranges {
+            let wire = circuit.materialize(value, "range");
+            circuit.opcodes.push(Opcode::BlackBoxFuncCall {
+                function: BlackBoxFunction::Range,
+                inputs: vec![FunctionInput {
+                    witness: witness(wire),
+                    num_bits: *bits,
+                }],
+                outputs: Vec::new(),
+            });
+        }
+        for call in &r1cs.black_boxes {
+            circuit.black_box(call)?;
+        }
+        Ok(circuit)
+    }
+
+    fn allocate(&mut self, source: WireSource, name: String) -> usize {
+        self.wires.push(source);
+        self.wire_names.push(name);
+        self.wires.len() - 1
+    }
+
+    /// A wire holding `lc`, bound by an opcode unless `lc` already is one
+    fn materialize(&mut self, lc: &LinearCombination, name: &str) -> usize {
+        if let [(&wire, coefficient)] = lc.iter().collect::<Vec<_>>().as_slice() {
+            if wire != ONE_WIRE && coefficient.is_one() {
+                return wire;
+            }
+        }
+        let wire = self.allocate(WireSource::Linear(lc.clone()), name.to_string());
+        self.opcodes.push(Opcode::AssertZero(AcirExpression::binding(lc, wire)));
+        wire
+    }
+
+    fn black_box(&mut self, call: &BlackBoxCall) -> Result<(), FCMCError> {
+        let function = BlackBoxFunction::from_name(&call.function).ok_or_else(|| {
+            FCMCError::BackendError(format!("ACIR has no black-box function '{}'", call.function))
+        })?;
+        let num_bits = if function.hashes_bytes() {
+            8
+        } else {
+            field::modulus().bits() as u32
+        };
+        let name = self.wire_names[call.output].clone();
+        let inputs = call
+            .inputs
+            .iter()
+            .map(|input| FunctionInput {
+                witness: witness(self.materialize(input, &format!("{}.input", name))),
+                num_bits,
+            })
+            .collect();
+
+        let outputs = if function.hashes_bytes() {
+            // The gadget's value is the big-endian digest reduced into the field
+            let output = lc_wire(call.output);
+            let bytes: Vec<usize> = (0..DIGEST_BYTES)
+                .map(|index| {
+                    let source = WireSource::Byte(output.clone(), index);
+                    self.allocate(source, format!("{}.byte{}", name, index))
+                })
+                .collect();
+            let mut packed = LinearCombination::new();
+            let mut weight = BigUint::one();
+            for &byte in bytes.iter().rev() {
+                packed.insert(byte, weight.clone());
+                weight = field::mul(&weight, &BigUint::from(256u32));
+            }
+            self.opcodes.push(Opcode::AssertZero(AcirExpression::binding(&packed, call.output)));
+            bytes
+        } else {
+            vec![call.output]
+        };
+
+        self.opcodes.push(Opcode::BlackBoxFuncCall {
+            function,
+            inputs,
+            outputs: outputs.into_iter().map(witness).collect(),
+        });
+        Ok(())
+    }
+}
+
+impl CircuitBackend for AcirCircuit {
+    fn target(&self) -> TargetSystem {
+        TargetSystem::ACIR
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.opcodes.len()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.wires.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.wires
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.wire_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        assign_wires(&self.wires, values)
+    }
+
+    /// Checks arithmetic and range opcodes; hashes are left to Barretenberg's solver
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        // ACIR witness `i` is wire `i + 1`
+        let witnesses = witness.get(1..).unwrap_or_default();
+        self.opcodes.iter().all(|opcode| match opcode {
+            Opcode::AssertZero(expression) => expression.evaluate(witnesses).is_zero(),
+            Opcode::BlackBoxFuncCall {
+                function: BlackBoxFunction::Range,
+                inputs,
+                ..
+            } => inputs.iter().all(|input| {
+                witnesses
+                    .get(input.witness)
+                    .map_or(false, |value| value.bits() <= input.num_bits as u64)
+            }),
+            Opcode::BlackBoxFuncCall { .. } => true,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "system": "acir",
+            "current_witness_index": self.wires.len().saturating_sub(2),
+            "opcodes": self.opcodes.iter().map(Opcode::to_json).collect::<Vec<_>>(),
+            "private_parameters": self.private_parameters,
+            "public_parameters": self.public_parameters,
+            "return_values": self.return_values,
+            "witness_names": self.wire_names.get(1..).unwrap_or_default(),
+            "witness_sources": self.wires.iter().skip(1).map(WireSource::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
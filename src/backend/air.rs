@@ -0,0 +1,297 @@
+//! Algebraic intermediate representation for STARK provers.
+//!
+//! The trace has one row per gate of the plonkish lowering: main columns `a`, `b`, `c`
+//! hold the gate's operands and preprocessed columns hold its selectors, so one transition
+//! constraint, `q_l·a + q_r·b + q_o·c + q_m·a·b + q_c = 0`, holds on every row. Loops are
+//! unrolled in topological order, so each iteration fills a contiguous region of rows.
+//! Public values become boundary constraints on the rows that expose them, and cells that
+//! share a variable are listed for the prover's permutation argument. The trace is padded
+//! with all-zero rows to a power-of-two length.
+
+use crate::backend::lowering::{assign_wires, WireSource};
+use crate::backend::plonk::{Cell, CopyConstraint, PlonkCircuit};
+use crate::backend::{CircuitBackend, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Main columns, then preprocessed selector columns
+pub const COLUMNS: [&str; 8] = ["a", "b", "c", "q_l", "q_r", "q_o", "q_m", "q_c"];
+
+/// Columns the prover fills from the witness
+pub const MAIN_COLUMNS: usize = 3;
+
+/// Polynomial over the current and next rows of the trace
+#[derive(Debug, Clone, PartialEq)]
+pub enum AirExpression {
+    Current(usize),
+    Next(usize),
+    Constant(BigUint),
+    Add(Box<AirExpression>, Box<AirExpression>),
+    Sub(Box<AirExpression>, Box<AirExpression>),
+    Mul(Box<AirExpression>, Box<AirExpression>),
+}
+
+impl AirExpression {
+    pub fn column(name: &str) -> Self {
+        AirExpression::Current(COLUMNS.iter().position(|column| *column == name).unwrap_or_default())
+    }
+
+    pub fn add(self, other: Self) -> Self {
+        AirExpression::Add(Box::new(self), Box::new(other))
+    }
+
+    pub fn sub(self, other: Self) -> Self {
+        AirExpression::Sub(Box::new(self), Box::new(other))
+    }
+
+    pub fn mul(self, other: Self) -> Self {
+        AirExpression::Mul(Box::new(self), Box::new(other))
+    }
+
+    pub fn degree(&self) -> usize {
+        match self {
+            AirExpression::Current(_) | AirExpression::Next(_) => 1,
+            AirExpression::Constant(_) => 0,
+            AirExpression::Add(a, b) | AirExpression::Sub(a, b) => a.degree().max(b.degree()),
+            AirExpression::Mul(a, b) => a.degree() + b.degree(),
+        }
+    }
+
+    pub fn evaluate(&self, current: &[BigUint], next: &[BigUint]) -> BigUint {
+        let cell = |row: &[BigUint], column: usize| row.get(column).cloned().unwrap_or_else(BigUint::zero);
+        match self {
+            AirExpression::Current(column) => cell(current, *column),
+            AirExpression::Next(column) => cell(next, *column),
+            AirExpression::Constant(value) => value.clone(),
+            AirExpression::Add(a, b) => field::add(&a.evaluate(current, next), &b.evaluate(current, next)),
+            AirExpression::Sub(a, b) => field::sub(&a.evaluate(current, next), &b.evaluate(current, next)),
+            AirExpression::Mul(a, b) => field::mul(&a.evaluate(current, next), &b.evaluate(current, next)),
+        }
+    }
+}
+
+impl std::fmt::Display for AirExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |column: usize| COLUMNS.get(column).copied().unwrap_or("?");
+        match self {
+            AirExpression::Current(column) => write!(f, "{}", name(*column)),
+            AirExpression::Next(column) => write!(f, "{}'", name(*column)),
+            AirExpression::Constant(value) => write!(f, "{}", value),
+            AirExpression::Add(a, b) => write!(f, "({} + {})", a, b),
+            AirExpression::Sub(a, b) => write!(f, "({} - {})", a, b),
+            AirExpression::Mul(a, b) => write!(f, "{} * {}", a, b),
+        }
+    }
+}
+
+/// A main-trace cell fixed to a public value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BoundaryConstraint {
+    pub column: usize,
+    pub row: usize,
+    /// Index into the public inputs
+    pub public_input: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AirCircuit {
+    /// Variables in the main columns of each gate row, before padding
+    pub rows: Vec<[usize; 3]>,
+    /// Selector values of each gate row, in `COLUMNS` order after the main columns
+    pub selectors: Vec<[BigUint; 5]>,
+    pub transition_constraints: Vec<AirExpression>,
+    pub boundary_constraints: Vec<BoundaryConstraint>,
+    /// Cells the permutation argument must prove equal
+    pub copy_constraints: Vec<CopyConstraint>,
+    pub variables: Vec<WireSource>,
+    pub variable_names: Vec<String>,
+}
+
+impl AirCircuit {
+    pub fn compile(graph: &IRGraph) -> Result<Self, FCMCError> {
+        let plonk = PlonkCircuit::compile(graph, TargetSystem::AIR)?;
+        Ok(Self::from_plonk(&plonk))
+    }
+
+    pub fn from_plonk(plonk: &PlonkCircuit) -> Self {
+        let mut rows = Vec::with_capacity(plonk.gates.len());
+        let mut selectors = Vec::with_capacity(plonk.gates.len());
+        let mut boundary_constraints = Vec::new();
+        for (row, gate) in plonk.gates.iter().enumerate() {
+            rows.push(gate.wires);
+            if gate.public {
+                // Checked against the public input instead of the gate polynomial
+                boundary_constraints.push(BoundaryConstraint {
+                    column: 0,
+                    row,
+                    public_input: boundary_constraints.len(),
+                });
+                selectors.push(Default::default());
+            } else {
+                selectors.push([
+                    gate.q_l.clone(),
+                    gate.q_r.clone(),
+                    gate.q_o.clone(),
+                    gate.q_m.clone(),
+                    gate.q_c.clone(),
+                ]);
+            }
+        }
+
+        let gate = AirExpression::column("q_l")
+            .mul(AirExpression::column("a"))
+            .add(AirExpression::column("q_r").mul(AirExpression::column("b")))
+            .add(AirExpression::column("q_o").mul(AirExpression::column("c")))
+            .add(
+                AirExpression::column("q_m")
+                    .mul(AirExpression::column("a"))
+                    .mul(AirExpression::column("b")),
+            )
+            .add(AirExpression::column("q_c"));
+
+        Self {
+            rows,
+            selectors,
+            transition_constraints: vec![gate],
+            boundary_constraints,
+            copy_constraints: plonk.copy_constraints(),
+            variables: plonk.variables.clone(),
+            variable_names: plonk.variable_names.clone(),
+        }
+    }
+
+    /// Rows in the padded trace
+    pub fn trace_length(&self) -> usize {
+        self.rows.len().max(2).next_power_of_two()
+    }
+
+    pub fn trace_width(&self) -> usize {
+        COLUMNS.len()
+    }
+
+    /// Highest degree among the transition constraints
+    pub fn constraint_degree(&self) -> usize {
+        self.transition_constraints.iter().map(AirExpression::degree).max().unwrap_or(0)
+    }
+
+    /// Every row of the padded trace, main columns first
+    pub fn trace(&self, witness: &[BigUint]) -> Vec<Vec<BigUint>> {
+        let value = |variable: usize| witness.get(variable).cloned().unwrap_or_else(BigUint::zero);
+        let mut trace: Vec<Vec<BigUint>> = self
+            .rows
+            .iter()
+            .zip(&self.selectors)
+            .map(|(row, selectors)| {
+                row.iter()
+                    .map(|&variable| value(variable))
+                    .chain(selectors.iter().cloned())
+                    .collect()
+            })
+            .collect();
+        trace.resize(self.trace_length(), vec![BigUint::zero(); COLUMNS.len()]);
+        trace
+    }
+
+    /// Public inputs in boundary constraint order
+    pub fn public_inputs(&self, witness: &[BigUint]) -> Vec<BigUint> {
+        self.boundary_constraints
+            .iter()
+            .map(|boundary| {
+                let variable = self.rows[boundary.row][boundary.column];
+                witness.get(variable).cloned().unwrap_or_else(BigUint::zero)
+            })
+            .collect()
+    }
+
+    fn cell_json(cell: &Cell) -> Value {
+        json!({ "column": cell.column.name(), "row": cell.row })
+    }
+}
+
+impl CircuitBackend for AirCircuit {
+    fn target(&self) -> TargetSystem {
+        TargetSystem::AIR
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.transition_constraints.len() + self.boundary_constraints.len()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.variables.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.variables
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.variable_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        assign_wires(&self.variables, values)
+    }
+
+    /// Transition constraints on every row; boundary and copy constraints hold by construction
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        let trace = self.trace(witness);
+        trace.iter().enumerate().all(|(row, current)| {
+            let next = &trace[(row + 1) % trace.len()];
+            self.transition_constraints
+                .iter()
+                .all(|constraint| constraint.evaluate(current, next).is_zero())
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let columns: Vec<Value> = COLUMNS
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let kind = if index < MAIN_COLUMNS { "main" } else { "preprocessed" };
+                json!({ "name": name, "kind": kind })
+            })
+            .collect();
+        let preprocessed: Vec<Vec<String>> = self
+            .selectors
+            .iter()
+            .map(|row| row.iter().map(|value| value.to_string()).collect())
+            .collect();
+        let transitions: Vec<Value> = self
+            .transition_constraints
+            .iter()
+            .map(|constraint| json!({ "expression": constraint.to_string(), "degree": constraint.degree() }))
+            .collect();
+        let copies: Vec<Value> = self
+            .copy_constraints
+            .iter()
+            .map(|copy| json!([Self::cell_json(&copy.left), Self::cell_json(&copy.right)]))
+            .collect();
+
+        json!({
+            "system": "air",
+            "trace_width": self.trace_width(),
+            "trace_length": self.trace_length(),
+            "columns": columns,
+            "preprocessed": preprocessed,
+            "transition_constraints": transitions,
+            "boundary_constraints": self.boundary_constraints,
+            "copy_constraints": copies,
+            "rows": self.rows,
+            "variable_names": self.variable_names,
+            "variable_sources": self.variables.iter().map(WireSource::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
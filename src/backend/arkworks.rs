@@ -0,0 +1,109 @@
+//! `ConstraintSynthesizer` adapter, so compiled R1CS circuits plug straight into arkworks
+//! provers such as ark-groth16 and ark-marlin.
+//!
+//! The scalar field `F` must be the field the circuit was compiled over; a circuit is
+//! rejected as unsatisfiable otherwise.
+
+use crate::backend::lowering::{LinearCombination, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::CircuitBackend;
+use crate::optimization::evaluate;
+use crate::{CompiledCircuit, FCMCError};
+use ark_ff::{BigInteger, PrimeField};
+use ark_relations::r1cs::{self, ConstraintSynthesizer, ConstraintSystemRef, SynthesisError, Variable};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+/// A compiled R1CS circuit, with a witness when proving
+#[derive(Debug, Clone)]
+pub struct ArkworksCircuit<'a> {
+    circuit: &'a R1CSCircuit,
+    witness: Option<Vec<BigUint>>,
+}
+
+impl<'a> ArkworksCircuit<'a> {
+    /// Without a witness, for key generation
+    pub fn setup(compiled: &'a CompiledCircuit) -> Result<Self, FCMCError> {
+        Ok(Self {
+            circuit: r1cs_of(compiled)?,
+            witness: None,
+        })
+    }
+
+    /// With the witness computed from `inputs`, for proving
+    pub fn prove(compiled: &'a CompiledCircuit, inputs: &HashMap<String, BigUint>) -> Result<Self, FCMCError> {
+        let circuit = r1cs_of(compiled)?;
+        let evaluation = circuit.field.enter(|| evaluate(&compiled.ir, inputs))?;
+        let witness = circuit.assign(&evaluation.values)?;
+        Ok(Self {
+            circuit,
+            witness: Some(witness),
+        })
+    }
+
+    /// Public values in the order the verifier expects them: outputs, then public inputs
+    pub fn public_inputs<F: PrimeField>(&self) -> Option<Vec<F>> {
+        let witness = self.witness.as_ref()?;
+        Some(witness[1..=self.circuit.public_count()].iter().map(to_field).collect())
+    }
+}
+
+fn r1cs_of(compiled: &CompiledCircuit) -> Result<&R1CSCircuit, FCMCError> {
+    compiled.circuit.as_r1cs().ok_or_else(|| {
+        FCMCError::BackendError(format!("arkworks needs an R1CS circuit, not {:?}", compiled.circuit.target()))
+    })
+}
+
+fn to_field<F: PrimeField>(value: &BigUint) -> F {
+    F::from_le_bytes_mod_order(&value.to_bytes_le())
+}
+
+fn to_lc<F: PrimeField>(lc: &LinearCombination, variables: &[Variable]) -> r1cs::LinearCombination<F> {
+    let mut result = r1cs::LinearCombination::zero();
+    for (&wire, coefficient) in lc {
+        result += (to_field::<F>(coefficient), variables[wire]);
+    }
+    result
+}
+
+impl<'a, F: PrimeField> ConstraintSynthesizer<F> for ArkworksCircuit<'a> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        if BigUint::from_bytes_le(&F::MODULUS.to_bytes_le()) != self.circuit.field.modulus() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let value = |wire: usize| -> Result<F, SynthesisError> {
+            match &self.witness {
+                Some(witness) => Ok(to_field(&witness[wire])),
+                None => Err(SynthesisError::AssignmentMissing),
+            }
+        };
+        let mut variables = Vec::with_capacity(self.circuit.wires.len());
+        for wire in 0..self.circuit.wires.len() {
+            let variable = if wire == ONE_WIRE {
+                Variable::One
+            } else if wire <= self.circuit.public_count() {
+                cs.new_input_variable(|| value(wire))?
+            } else {
+                cs.new_witness_variable(|| value(wire))?
+            };
+            variables.push(variable);
+        }
+
+        for constraint in &self.circuit.constraints {
+            cs.enforce_constraint(
+                to_lc(&constraint.a, &variables),
+                to_lc(&constraint.b, &variables),
+                to_lc(&constraint.c, &variables),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, F: PrimeField> ConstraintSynthesizer<F> for &'a CompiledCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<F>) -> Result<(), SynthesisError> {
+        let circuit = ArkworksCircuit::setup(self).map_err(|_| SynthesisError::Unsatisfiable)?;
+        circuit.generate_constraints(cs)
+    }
+}
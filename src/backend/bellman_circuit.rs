@@ -0,0 +1,115 @@
+//! bellman `Circuit` adapter, so bellman and bellperson provers consume compiled R1CS
+//! circuits natively.
+//!
+//! The scalar field must be the BLS12-381 scalar field the circuit was compiled over;
+//! synthesis fails with `Unsatisfiable` otherwise.
+
+use crate::backend::lowering::{LinearCombination, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::CircuitBackend;
+use crate::optimization::evaluate;
+use crate::optimization::field::FieldConfig;
+use crate::{CompiledCircuit, FCMCError};
+use bellman::{Circuit, ConstraintSystem, SynthesisError, Variable};
+use ff::PrimeField;
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+/// A compiled R1CS circuit, with a witness when proving
+#[derive(Debug, Clone)]
+pub struct BellmanCircuit<'a> {
+    circuit: &'a R1CSCircuit,
+    witness: Option<Vec<BigUint>>,
+}
+
+impl<'a> BellmanCircuit<'a> {
+    /// Without a witness, for parameter generation
+    pub fn setup(compiled: &'a CompiledCircuit) -> Result<Self, FCMCError> {
+        Ok(Self {
+            circuit: r1cs_of(compiled)?,
+            witness: None,
+        })
+    }
+
+    /// With the witness computed from `inputs`, for proving
+    pub fn prove(compiled: &'a CompiledCircuit, inputs: &HashMap<String, BigUint>) -> Result<Self, FCMCError> {
+        let circuit = r1cs_of(compiled)?;
+        let evaluation = circuit.field.enter(|| evaluate(&compiled.ir, inputs))?;
+        let witness = circuit.assign(&evaluation.values)?;
+        Ok(Self {
+            circuit,
+            witness: Some(witness),
+        })
+    }
+
+    /// Public values in the order `verify_proof` expects them: outputs, then public inputs
+    pub fn public_inputs<Scalar: PrimeField>(&self) -> Option<Vec<Scalar>> {
+        let witness = self.witness.as_ref()?;
+        witness[1..=self.circuit.public_count()]
+            .iter()
+            .map(to_scalar)
+            .collect()
+    }
+}
+
+fn r1cs_of(compiled: &CompiledCircuit) -> Result<&R1CSCircuit, FCMCError> {
+    compiled.circuit.as_r1cs().ok_or_else(|| {
+        FCMCError::BackendError(format!("bellman needs an R1CS circuit, not {:?}", compiled.circuit.target()))
+    })
+}
+
+fn to_scalar<Scalar: PrimeField>(value: &BigUint) -> Option<Scalar> {
+    Scalar::from_str_vartime(&value.to_string())
+}
+
+fn same_field<Scalar: PrimeField>(field: &FieldConfig) -> bool {
+    let modulus = Scalar::MODULUS.trim_start_matches("0x");
+    BigUint::parse_bytes(modulus.as_bytes(), 16).map_or(false, |modulus| modulus == field.modulus())
+}
+
+fn to_lc<Scalar: PrimeField>(
+    lc: &LinearCombination,
+    variables: &[Variable],
+    mut result: bellman::LinearCombination<Scalar>,
+) -> bellman::LinearCombination<Scalar> {
+    for (&wire, coefficient) in lc {
+        // Coefficients are reduced, so they always convert
+        let coefficient = to_scalar(coefficient).unwrap_or(Scalar::ZERO);
+        result = result + (coefficient, variables[wire]);
+    }
+    result
+}
+
+impl<'a, Scalar: PrimeField> Circuit<Scalar> for BellmanCircuit<'a> {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        if !same_field::<Scalar>(&self.circuit.field) {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        let value = |wire: usize| -> Result<Scalar, SynthesisError> {
+            let witness = self.witness.as_ref().ok_or(SynthesisError::AssignmentMissing)?;
+            to_scalar(&witness[wire]).ok_or(SynthesisError::Unsatisfiable)
+        };
+        let mut variables = Vec::with_capacity(self.circuit.wires.len());
+        for (wire, name) in self.circuit.wire_names.iter().enumerate() {
+            let variable = if wire == ONE_WIRE {
+                CS::one()
+            } else if wire <= self.circuit.public_count() {
+                cs.alloc_input(|| name.as_str(), || value(wire))?
+            } else {
+                cs.alloc(|| name.as_str(), || value(wire))?
+            };
+            variables.push(variable);
+        }
+
+        for (index, constraint) in self.circuit.constraints.iter().enumerate() {
+            cs.enforce(
+                || format!("constraint {}", index),
+                |lc| to_lc(&constraint.a, &variables, lc),
+                |lc| to_lc(&constraint.b, &variables, lc),
+                |lc| to_lc(&constraint.c, &variables, lc),
+            );
+        }
+        Ok(())
+    }
+}
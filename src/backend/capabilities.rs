@@ -0,0 +1,111 @@
+//! What each backend can prove natively. The optimizer, the legalization step before
+//! lowering and the shared R1CS lowering all consult these, so high-level IR operations
+//! are only expanded into arithmetic on targets that need it.
+
+use crate::backend::acir::BlackBoxFunction;
+use crate::backend::r1cs::LoweringOptions;
+use crate::backend::{registry, TargetSystem};
+use crate::optimization::field::FieldConfig;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendCapabilities {
+    /// Lookup arguments are available, so the optimizer may merge range checks into tables
+    pub lookups: bool,
+    /// Lookups into generated function tables are emitted as such; otherwise they are
+    /// interpolated into arithmetic before lowering
+    pub function_tables: bool,
+    /// Custom gates or reusable regions are available for repeated gadgets
+    pub custom_gates: bool,
+    /// Range checks are proved natively instead of decomposed into bits
+    pub native_ranges: bool,
+    /// Gadgets marked with a `blackbox` attribute are handed to the prover as native calls
+    pub black_boxes: bool,
+    /// Black-box functions the prover calls natively; legalization lowers other marked
+    /// gadgets to arithmetic
+    pub black_box_functions: Vec<String>,
+    /// Highest degree of a single constraint the backend emits
+    pub max_degree: u32,
+    /// The only field the prover works over, if it is fixed
+    pub native_field: Option<FieldConfig>,
+}
+
+impl BackendCapabilities {
+    pub fn for_target(target: TargetSystem) -> Self {
+        let mut capabilities = Self::none();
+        match target {
+            TargetSystem::R1CS | TargetSystem::CCS | TargetSystem::AIR => {}
+            TargetSystem::Plonk | TargetSystem::Halo2 => {
+                capabilities.lookups = true;
+                capabilities.function_tables = true;
+                capabilities.custom_gates = true;
+                capabilities.native_ranges = true;
+            }
+            TargetSystem::Plonky2 => {
+                capabilities.native_ranges = true;
+                capabilities.native_field = Some(FieldConfig::Goldilocks);
+            }
+            // Range checks are native black-box calls, but there are no function tables;
+            // Barretenberg only proves over BN254
+            TargetSystem::ACIR => {
+                capabilities.native_ranges = true;
+                capabilities.black_boxes = true;
+                capabilities.black_box_functions = BlackBoxFunction::NAMES.iter().map(ToString::to_string).collect();
+                capabilities.native_field = Some(FieldConfig::Bn254);
+            }
+            TargetSystem::Custom(name) => {
+                if let Some(factory) = registry::factory(&name) {
+                    capabilities = factory.capabilities();
+                }
+            }
+        }
+        capabilities
+    }
+
+    /// Quadratic constraints and nothing native, as in R1CS
+    pub fn none() -> Self {
+        Self {
+            lookups: false,
+            function_tables: false,
+            custom_gates: false,
+            native_ranges: false,
+            black_boxes: false,
+            black_box_functions: Vec::new(),
+            max_degree: 2,
+            native_field: None,
+        }
+    }
+
+    /// Whether a gadget marked as black-box `function` is handed to the prover natively
+    pub fn supports_black_box(&self, function: &str) -> bool {
+        self.black_boxes && self.black_box_functions.iter().any(|name| name == function)
+    }
+
+    pub fn supports_field(&self, field: &FieldConfig) -> bool {
+        self.native_field.as_ref().map_or(true, |native| native == field)
+    }
+
+    /// How the shared R1CS lowering treats range checks, black-box gadgets, custom gates
+    /// and function tables
+    pub fn lowering_options(&self) -> LoweringOptions {
+        LoweringOptions {
+            native_ranges: self.native_ranges,
+            black_boxes: self.black_boxes,
+            custom_gates: self.custom_gates,
+            function_tables: self.function_tables,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "lookups": self.lookups,
+            "function_tables": self.function_tables,
+            "custom_gates": self.custom_gates,
+            "native_ranges": self.native_ranges,
+            "black_boxes": self.black_boxes,
+            "black_box_functions": self.black_box_functions,
+            "max_degree": self.max_degree,
+            "native_field": self.native_field.as_ref().map(ToString::to_string),
+        })
+    }
+}
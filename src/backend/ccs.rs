@@ -0,0 +1,379 @@
+//! Customizable constraint systems, the arithmetization of Nova, HyperNova and
+//! SuperSpartan-style folding provers.
+//!
+//! A CCS is satisfied by `z = (1, x, w)` when `Σ_i c_i · ∘_{j ∈ S_i} M_j·z = 0`, where `∘`
+//! is the Hadamard product. Circuits are lowered through R1CS, which is the CCS with
+//! matrices `A, B, C`, multisets `{0, 1}, {2}` and constants `1, -1`.
+//!
+//! Folding provers prove a step function `F(z_i, w_i) = z_{i+1}` applied repeatedly.
+//! `IvcSteps::split` cuts an unrolled circuit at the state values it carries from one
+//! iteration to the next and checks every step lowers to the same CCS.
+
+use crate::backend::lowering::{assign_wires, LinearCombination, WireSource};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::{CircuitBackend, TargetSystem};
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::optimization::field::{self, FieldConfig};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Matrix of `rows × columns` field elements, stored as `(row, column, value)` entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMatrix {
+    pub rows: usize,
+    pub columns: usize,
+    pub entries: Vec<(usize, usize, BigUint)>,
+}
+
+impl SparseMatrix {
+    fn from_rows(rows: &[&LinearCombination], columns: usize) -> Self {
+        let entries = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, lc)| lc.iter().map(move |(&column, value)| (row, column, value.clone())))
+            .collect();
+        Self {
+            rows: rows.len(),
+            columns,
+            entries,
+        }
+    }
+
+    pub fn multiply(&self, z: &[BigUint]) -> Vec<BigUint> {
+        let mut result = vec![BigUint::zero(); self.rows];
+        for (row, column, value) in &self.entries {
+            result[*row] = field::add(&result[*row], &field::mul(value, &z[*column]));
+        }
+        result
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.entries
+                .iter()
+                .map(|(row, column, value)| json!([row, column, value.to_string()]))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CcsCircuit {
+    /// `M_1 … M_t`, each `m × n`
+    pub matrices: Vec<SparseMatrix>,
+    /// `S_1 … S_q`, indices into `matrices`
+    pub multisets: Vec<Vec<usize>>,
+    /// `c_1 … c_q`
+    pub constants: Vec<BigUint>,
+    /// `l`, the length of `x`
+    pub public_count: usize,
+    pub wires: Vec<WireSource>,
+    pub wire_names: Vec<String>,
+    pub field: FieldConfig,
+}
+
+impl CcsCircuit {
+    pub fn compile(graph: &IRGraph) -> Result<Self, FCMCError> {
+        Ok(Self::from_r1cs(&R1CSCircuit::compile(graph)?))
+    }
+
+    pub fn from_r1cs(r1cs: &R1CSCircuit) -> Self {
+        let columns = r1cs.wires.len();
+        let a: Vec<&LinearCombination> = r1cs.constraints.iter().map(|row| &row.a).collect();
+        let b: Vec<&LinearCombination> = r1cs.constraints.iter().map(|row| &row.b).collect();
+        let c: Vec<&LinearCombination> = r1cs.constraints.iter().map(|row| &row.c).collect();
+        Self {
+            matrices: vec![
+                SparseMatrix::from_rows(&a, columns),
+                SparseMatrix::from_rows(&b, columns),
+                SparseMatrix::from_rows(&c, columns),
+            ],
+            multisets: vec![vec![0, 1], vec![2]],
+            constants: vec![BigUint::one(), field::neg(&BigUint::one())],
+            public_count: r1cs.public_count(),
+            wires: r1cs.wires.clone(),
+            wire_names: r1cs.wire_names.clone(),
+            field: r1cs.field.clone(),
+        }
+    }
+
+    /// `m`, the number of rows of every matrix
+    pub fn rows(&self) -> usize {
+        self.matrices.first().map_or(0, |matrix| matrix.rows)
+    }
+
+    /// `d`, the largest multiset and so the degree of the constraints
+    pub fn degree(&self) -> usize {
+        self.multisets.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Same matrices, multisets and constants, so one folding instance fits both
+    pub fn same_structure(&self, other: &CcsCircuit) -> bool {
+        self.matrices == other.matrices
+            && self.multisets == other.multisets
+            && self.constants == other.constants
+            && self.public_count == other.public_count
+    }
+}
+
+impl CircuitBackend for CcsCircuit {
+    fn target(&self) -> TargetSystem {
+        TargetSystem::CCS
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.rows()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.wires.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.wires
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.wire_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        self.field.enter(|| assign_wires(&self.wires, values))
+    }
+
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        if witness.len() != self.wires.len() {
+            return false;
+        }
+        self.field.enter(|| {
+            let products: Vec<Vec<BigUint>> = self.matrices.iter().map(|matrix| matrix.multiply(witness)).collect();
+            (0..self.rows()).all(|row| {
+                let mut sum = BigUint::zero();
+                for (multiset, constant) in self.multisets.iter().zip(&self.constants) {
+                    let term = multiset
+                        .iter()
+                        .fold(constant.clone(), |acc, &matrix| field::mul(&acc, &products[matrix][row]));
+                    sum = field::add(&sum, &term);
+                }
+                sum.is_zero()
+            })
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "system": "ccs",
+            "field": self.field.to_string(),
+            "m": self.rows(),
+            "n": self.wires.len(),
+            "l": self.public_count,
+            "t": self.matrices.len(),
+            "q": self.multisets.len(),
+            "d": self.degree(),
+            "matrices": self.matrices.iter().map(SparseMatrix::to_json).collect::<Vec<_>>(),
+            "multisets": self.multisets,
+            "constants": self.constants.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            "wire_names": self.wire_names,
+            "wire_sources": self.wires.iter().map(WireSource::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// One application of the step function, as a graph of its own
+#[derive(Debug, Clone)]
+pub struct StepInstance {
+    pub graph: IRGraph,
+    /// Id in the unrolled graph of each node of `graph`
+    pub origin: Vec<usize>,
+}
+
+/// An unrolled circuit split into identical steps `F(z_i, w_i) = z_{i+1}`.
+///
+/// Each step's public inputs `state.in.k` are the previous state and its outputs
+/// `state.out.k` the next one. Circuit inputs a step reads become its private advice
+/// `advice.k`, so a value read by two steps is not tied between them.
+#[derive(Debug, Clone)]
+pub struct IvcSteps {
+    pub step: CcsCircuit,
+    pub instances: Vec<StepInstance>,
+}
+
+impl IvcSteps {
+    /// Split `graph` at `states`: `states[0]` is the initial state `z_0` and `states[i]`
+    /// the nodes holding `z_i`, all of the same width. A node belongs to the first step
+    /// whose state depends on it; nodes no state depends on, such as assertions, belong
+    /// to the last step they read from.
+    pub fn split(graph: &IRGraph, states: &[Vec<usize>]) -> Result<Self, FCMCError> {
+        if states.len() < 2 {
+            return Err(FCMCError::BackendError("IVC needs an initial state and at least one step".to_string()));
+        }
+        let width = states[0].len();
+        if states.iter().any(|state| state.len() != width) {
+            return Err(FCMCError::BackendError("every IVC state must have the same width".to_string()));
+        }
+
+        let steps = assign_steps(graph, states);
+        let mut instances = Vec::with_capacity(states.len() - 1);
+        for step in 1..states.len() {
+            let nodes: Vec<usize> = graph
+                .live_nodes()
+                .filter(|node| steps.get(&node.id) == Some(&step))
+                .map(|node| node.id)
+                .collect();
+            instances.push(extract_step(graph, &nodes, &states[step - 1], &states[step])?);
+        }
+
+        let circuits = instances
+            .iter()
+            .map(|instance| CcsCircuit::compile(&instance.graph))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(index) = circuits
+            .iter()
+            .position(|circuit| !circuit.same_structure(&circuits[0]) || circuit.wires != circuits[0].wires)
+        {
+            return Err(FCMCError::BackendError(format!(
+                "step {} does not lower to the same constraint system as step 1",
+                index + 1
+            )));
+        }
+        let step = circuits.into_iter().next().expect("at least one step");
+        Ok(Self { step, instances })
+    }
+
+    pub fn step_count(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Witness `z` of step `step`, 0-based, from the values of the unrolled graph's nodes
+    pub fn step_witness(&self, step: usize, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        let instance = self
+            .instances
+            .get(step)
+            .ok_or_else(|| FCMCError::BackendError(format!("no IVC step {}", step)))?;
+        let local = instance
+            .origin
+            .iter()
+            .enumerate()
+            .filter_map(|(id, origin)| values.get(origin).map(|value| (id, value.clone())))
+            .collect();
+        self.step.assign(&local)
+    }
+}
+
+/// Step of every node that is not an input or constant; state `0` and what it depends on
+/// are step 0, before the first step
+fn assign_steps(graph: &IRGraph, states: &[Vec<usize>]) -> HashMap<usize, usize> {
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    for (step, state) in states.iter().enumerate() {
+        let mut stack = state.clone();
+        while let Some(node_id) = stack.pop() {
+            if steps.contains_key(&node_id) || is_leaf(graph, node_id) {
+                continue;
+            }
+            steps.insert(node_id, step);
+            stack.extend(graph.get_predecessors(node_id));
+        }
+    }
+    let last = states.len() - 1;
+    for node_id in graph.topological_sort() {
+        if steps.contains_key(&node_id) || is_leaf(graph, node_id) {
+            continue;
+        }
+        if matches!(graph.get_node(node_id).map(|node| &node.node_type), Some(IRNodeType::Output(_))) {
+            continue;
+        }
+        let step = graph
+            .get_predecessors(node_id)
+            .iter()
+            .filter_map(|operand| steps.get(operand))
+            .max()
+            .copied()
+            .unwrap_or(1);
+        steps.insert(node_id, step.clamp(1, last));
+    }
+    steps
+}
+
+fn is_leaf(graph: &IRGraph, node_id: usize) -> bool {
+    matches!(
+        graph.get_node(node_id).map(|node| &node.node_type),
+        Some(IRNodeType::Input(_)) | Some(IRNodeType::PrivateInput(_)) | Some(IRNodeType::Constant(_))
+    )
+}
+
+fn extract_step(graph: &IRGraph, nodes: &[usize], previous: &[usize], next: &[usize]) -> Result<StepInstance, FCMCError> {
+    let mut step = IRGraph::new();
+    let mut origin = Vec::new();
+    let mut local: HashMap<usize, usize> = HashMap::new();
+
+    for (k, &node_id) in previous.iter().enumerate() {
+        let node = graph
+            .get_node(node_id)
+            .ok_or_else(|| FCMCError::BackendError(format!("state node {} does not exist", node_id)))?;
+        let id = step.add_node(IRNodeType::Input(format!("state.in.{}", k)), node.data_type.clone(), None);
+        local.insert(node_id, id);
+        origin.push(node_id);
+    }
+    for &node_id in nodes {
+        if local.contains_key(&node_id) {
+            continue;
+        }
+        let node = graph.get_node(node_id).expect("live node");
+        let id = step.add_node(node.node_type.clone(), node.data_type.clone(), node.label.clone());
+        if let Some(copy) = step.get_node_mut(id) {
+            copy.attributes = node.attributes.clone();
+        }
+        local.insert(node_id, id);
+        origin.push(node_id);
+    }
+
+    let mut advice = 0;
+    for &node_id in nodes {
+        for operand in graph.get_predecessors(node_id) {
+            let from = match local.get(&operand) {
+                Some(&from) => from,
+                None => {
+                    let node = graph.get_node(operand).expect("operand exists");
+                    let node_type = match &node.node_type {
+                        IRNodeType::Constant(value) => IRNodeType::Constant(value.clone()),
+                        IRNodeType::Input(_) | IRNodeType::PrivateInput(_) => {
+                            advice += 1;
+                            IRNodeType::PrivateInput(format!("advice.{}", advice - 1))
+                        }
+                        _ => {
+                            return Err(FCMCError::BackendError(format!(
+                                "node {} reads node {} of an earlier step that is not part of the state",
+                                node_id, operand
+                            )));
+                        }
+                    };
+                    let from = step.add_node(node_type, node.data_type.clone(), None);
+                    local.insert(operand, from);
+                    origin.push(operand);
+                    from
+                }
+            };
+            step.add_edge(from, local[&node_id], EdgeType::DataFlow);
+        }
+    }
+
+    for (k, &node_id) in next.iter().enumerate() {
+        let from = *local.get(&node_id).ok_or_else(|| {
+            FCMCError::BackendError(format!("state node {} is not computed by its step", node_id))
+        })?;
+        let data_type = graph.get_node(node_id).expect("state node exists").data_type.clone();
+        let id = step.add_node(IRNodeType::Output(format!("state.out.{}", k)), data_type, None);
+        step.add_edge(from, id, EdgeType::DataFlow);
+        origin.push(node_id);
+    }
+
+    Ok(StepInstance { graph: step, origin })
+}
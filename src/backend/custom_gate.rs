@@ -0,0 +1,97 @@
+//! Custom gates the plonkish backends can enable for small, frequent IR patterns.
+//!
+//! A gate spans two rows: its inputs and then its output fill the advice cells `a`, `b`,
+//! `c` of the row its selector is enabled on and continue on the next row, and the gate's
+//! constraint relates those cells. The next row's own selectors are all zero.
+
+use crate::optimization::field;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use serde_json::{json, Value};
+
+/// Advice cells a custom gate reads: the current row's, then the next row's
+pub const CELLS: usize = 6;
+
+const CELL_NAMES: [&str; CELLS] = ["a", "b", "c", "a_next", "b_next", "c_next"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CustomGate {
+    /// `out = a * b + c`
+    MulAdd,
+    /// `out = a * b + c * d`
+    DoubleMulAdd,
+    /// `out = s ? x : y` for a boolean `s`, as `s * (x - y) + y`
+    Select,
+}
+
+impl CustomGate {
+    pub const ALL: [CustomGate; 3] = [CustomGate::MulAdd, CustomGate::DoubleMulAdd, CustomGate::Select];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CustomGate::MulAdd => "mul_add",
+            CustomGate::DoubleMulAdd => "double_mul_add",
+            CustomGate::Select => "select",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|gate| gate.name() == name)
+    }
+
+    /// Name of the fixed column enabling the gate
+    pub fn selector(&self) -> String {
+        format!("q_{}", self.name())
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            CustomGate::MulAdd | CustomGate::Select => 3,
+            CustomGate::DoubleMulAdd => 4,
+        }
+    }
+
+    /// The gate's output for `inputs`
+    pub fn evaluate(&self, inputs: &[BigUint]) -> BigUint {
+        let input = |index: usize| inputs.get(index).cloned().unwrap_or_else(BigUint::zero);
+        match self {
+            CustomGate::MulAdd => field::add(&field::mul(&input(0), &input(1)), &input(2)),
+            CustomGate::DoubleMulAdd => field::add(
+                &field::mul(&input(0), &input(1)),
+                &field::mul(&input(2), &input(3)),
+            ),
+            CustomGate::Select => {
+                let difference = field::sub(&input(1), &input(2));
+                field::add(&field::mul(&input(0), &difference), &input(2))
+            }
+        }
+    }
+
+    /// The constraint over the gate's cells, which is zero when the output cell holds
+    /// the gate's value
+    pub fn constraint(&self, cells: &[BigUint]) -> BigUint {
+        let arity = self.arity();
+        let output = cells.get(arity).cloned().unwrap_or_else(BigUint::zero);
+        field::sub(&self.evaluate(&cells[..arity.min(cells.len())]), &output)
+    }
+
+    /// The constraint as an expression over `cells`, named in cell order
+    pub fn expression(&self, cells: &[&str; CELLS]) -> String {
+        let [a, b, c, d, e, _] = cells;
+        match self {
+            CustomGate::MulAdd => format!("{a} * {b} + {c} - {d}"),
+            CustomGate::DoubleMulAdd => format!("{a} * {b} + {c} * {d} - {e}"),
+            CustomGate::Select => format!("{a} * ({b} - {c}) + {c} - {d}"),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name(),
+            "selector": self.selector(),
+            "inputs": self.arity(),
+            "rows": 2,
+            "constraint": self.expression(&CELL_NAMES),
+        })
+    }
+}
@@ -0,0 +1,549 @@
+//! Generation of a standalone C witness calculator.
+//!
+//! The calculator is one `.c` file and its header, with no dependency beyond the C
+//! standard library and `fcmc_field.h`, which provides the field element type and its
+//! arithmetic. `field_header` writes a portable reference implementation of that header
+//! for the circuit's field; embedded and mobile provers can swap in their own, backed by
+//! whatever big-integer code the platform already has, as long as it keeps the same
+//! names and lets outputs alias inputs.
+
+use crate::backend::export::plan::{NodeStep, WitnessPlan};
+use crate::backend::lowering::{LinearCombination, WireSource};
+use crate::ir::IRNodeType;
+use crate::optimization::field::FieldConfig;
+use crate::{CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Name of the header the calculator includes for field arithmetic
+pub const FIELD_HEADER: &str = "fcmc_field.h";
+
+/// Write `name.h` and `name.c` for `compiled` into `dir`. The field header is not
+/// written, so a replacement already there is kept; see `write_field_header`.
+pub fn write_witness_c(compiled: &CompiledCircuit, name: &str, dir: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let dir = dir.as_ref();
+    let plan = WitnessPlan::new(compiled)?;
+    let prefix = identifier(name);
+    write_file(&dir.join(format!("{}.h", prefix)), &witness_header(&plan, &prefix))?;
+    write_file(&dir.join(format!("{}.c", prefix)), &witness_source(&plan, &prefix))
+}
+
+/// Write the reference `fcmc_field.h` for `field` to `path`
+pub fn write_field_header(field: &FieldConfig, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    write_file(path.as_ref(), &field_header(&field.modulus()))
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), FCMCError> {
+    std::fs::write(path, contents)
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// C identifier for `name`: lowercase letters, digits and underscores
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_lowercase()) {
+        ident.insert_str(0, "circuit_");
+    }
+    ident
+}
+
+/// Decimal constants the calculator parses once per call, by value
+#[derive(Default)]
+struct Constants {
+    values: Vec<BigUint>,
+    index: HashMap<BigUint, usize>,
+}
+
+impl Constants {
+    fn get(&mut self, value: &BigUint) -> usize {
+        let values = &mut self.values;
+        *self.index.entry(value.clone()).or_insert_with(|| {
+            values.push(value.clone());
+            values.len() - 1
+        })
+    }
+}
+
+/// `lc(&out, ...)` computing `lc` into `out`
+fn lc_call(out: &str, lc: &LinearCombination, constants: &mut Constants) -> String {
+    if lc.is_empty() {
+        return format!("lc(&{}, w, k, 0, NULL, NULL);", out);
+    }
+    let wires: Vec<String> = lc.keys().map(ToString::to_string).collect();
+    let coefficients: Vec<String> = lc.values().map(|c| constants.get(c).to_string()).collect();
+    format!(
+        "lc(&{}, w, k, {}, (const uint32_t[]){{{}}}, (const uint32_t[]){{{}}});",
+        out,
+        lc.len(),
+        wires.join(", "),
+        coefficients.join(", ")
+    )
+}
+
+/// Statements storing `node_type` applied to `operands` in `out`
+fn operation(out: &str, node_type: &IRNodeType, operands: &[String]) -> String {
+    let arg = |index: usize| operands.get(index).cloned().unwrap_or_else(|| "zero".to_string());
+    let (a, b) = (arg(0), arg(1));
+    match node_type {
+        IRNodeType::Add => format!("fcmc_fe_add(&{}, &{}, &{});", out, a, b),
+        IRNodeType::Sub => format!("fcmc_fe_sub(&{}, &{}, &{});", out, a, b),
+        IRNodeType::Mul | IRNodeType::And => format!("fcmc_fe_mul(&{}, &{}, &{});", out, a, b),
+        IRNodeType::Neg => format!("fcmc_fe_neg(&{}, &{});", out, a),
+        // A zero divisor leaves the circuit's nonzero check unsatisfied
+        IRNodeType::Div => format!("fcmc_fe_inv(&x, &{}); fcmc_fe_mul(&{}, &{}, &x);", b, out, a),
+        IRNodeType::Or => format!(
+            "fcmc_fe_mul(&x, &{a}, &{b}); fcmc_fe_add(&{o}, &{a}, &{b}); fcmc_fe_sub(&{o}, &{o}, &x);",
+            o = out,
+            a = a,
+            b = b
+        ),
+        IRNodeType::Xor => format!(
+            "fcmc_fe_mul(&x, &{a}, &{b}); fcmc_fe_add(&x, &x, &x); fcmc_fe_add(&{o}, &{a}, &{b}); fcmc_fe_sub(&{o}, &{o}, &x);",
+            o = out,
+            a = a,
+            b = b
+        ),
+        IRNodeType::Not => format!("fcmc_fe_one(&x); fcmc_fe_sub(&{}, &x, &{});", out, a),
+        IRNodeType::Eq => format!("boolean(&{}, fcmc_fe_eq(&{}, &{}));", out, a, b),
+        IRNodeType::Ne => format!("boolean(&{}, !fcmc_fe_eq(&{}, &{}));", out, a, b),
+        IRNodeType::Lt => format!("boolean(&{}, fcmc_fe_lt(&{}, &{}));", out, a, b),
+        IRNodeType::Le => format!("boolean(&{}, !fcmc_fe_lt(&{}, &{}));", out, b, a),
+        IRNodeType::Gt => format!("boolean(&{}, fcmc_fe_lt(&{}, &{}));", out, b, a),
+        IRNodeType::Ge => format!("boolean(&{}, !fcmc_fe_lt(&{}, &{}));", out, a, b),
+        IRNodeType::Select => format!("{} = fcmc_fe_is_zero(&{}) ? {} : {};", out, a, arg(2), b),
+        _ => format!("{} = {};", out, a),
+    }
+}
+
+/// Statements computing wire `wire` from `source`
+fn wire_statement(wire: usize, source: &WireSource, slots: &HashMap<usize, usize>, constants: &mut Constants) -> String {
+    let out = format!("w[{}]", wire);
+    match source {
+        WireSource::One => format!("fcmc_fe_one(&{});", out),
+        WireSource::Node(node_id) => format!("{} = v[{}];", out, slots[node_id]),
+        WireSource::Linear(lc) => lc_call(&out, lc, constants),
+        WireSource::Product(a, b) => format!(
+            "{} {} fcmc_fe_mul(&{}, &x, &y);",
+            lc_call("x", a, constants),
+            lc_call("y", b, constants),
+            out
+        ),
+        WireSource::Inverse(lc) => format!("{} fcmc_fe_inv(&{}, &x);", lc_call("x", lc, constants), out),
+        WireSource::Bit(lc, index) => format!(
+            "{} boolean(&{}, fcmc_fe_bit(&x, {}));",
+            lc_call("x", lc, constants),
+            out,
+            index
+        ),
+        WireSource::Byte(lc, index) => format!("{} byte(&{}, &x, {});", lc_call("x", lc, constants), out, index),
+        WireSource::IsZero(lc) => format!(
+            "{} boolean(&{}, fcmc_fe_is_zero(&x));",
+            lc_call("x", lc, constants),
+            out
+        ),
+    }
+}
+
+/// Source of the generated header, `prefix.h`
+pub fn witness_header(plan: &WitnessPlan, prefix: &str) -> String {
+    let guard = prefix.to_ascii_uppercase();
+    let mut out = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "/* Witness calculator for `{}`, generated by FCMC. Do not edit. */", prefix);
+    let _ = writeln!(out, "#ifndef {}_H\n#define {}_H\n", guard, guard);
+    let _ = writeln!(out, "#include \"{}\"\n", FIELD_HEADER);
+    let _ = writeln!(out, "/* Every wire, the constant one included */");
+    let _ = writeln!(out, "#define {}_WIRES {}", guard, plan.wires.len());
+    let _ = writeln!(out, "/* Every input, public and private */");
+    let _ = writeln!(out, "#define {}_INPUTS {}\n", guard, plan.inputs.len());
+    out.push_str("/* Inputs, by index:\n");
+    for (index, input) in plan.inputs.iter().enumerate() {
+        let _ = writeln!(out, " *   {}: {}", index, input);
+    }
+    out.push_str(" */\n\n");
+    let _ = writeln!(
+        out,
+        "/* Compute the full witness, one value per wire starting with the constant one, into\n * `witness`, from the {}_INPUTS values in `inputs`. Returns 0, or -1 if out of memory. */",
+        guard
+    );
+    let _ = writeln!(out, "int {}_compute_witness(const fcmc_fe *inputs, fcmc_fe *witness);", prefix);
+    let _ = writeln!(out, "\n#endif");
+    out
+}
+
+/// Source of the generated calculator, `prefix.c`
+pub fn witness_source(plan: &WitnessPlan, prefix: &str) -> String {
+    let mut constants = Constants::default();
+    let slots: HashMap<usize, usize> = plan.nodes.iter().enumerate().map(|(slot, (node_id, _))| (*node_id, slot)).collect();
+    let slot = |node_id: &usize| format!("v[{}]", slots[node_id]);
+
+    let mut body = String::new();
+    for (index, (_, step)) in plan.nodes.iter().enumerate() {
+        let out = format!("v[{}]", index);
+        let statement = match step {
+            NodeStep::Input(index) => format!("{} = inputs[{}];", out, index),
+            NodeStep::Constant(value) => format!("{} = k[{}];", out, constants.get(value)),
+            NodeStep::Operation(node_type, operands) => {
+                let operands: Vec<String> = operands.iter().map(slot).collect();
+                operation(&out, node_type, &operands)
+            }
+            NodeStep::Lookup(table, operands) => {
+                let mut statement = "fcmc_fe_zero(&x);".to_string();
+                for (operand, weight) in operands.iter().zip(plan.tables[*table].weights()) {
+                    let _ = write!(
+                        statement,
+                        " fcmc_fe_mul(&y, &{}, &k[{}]); fcmc_fe_add(&x, &x, &y);",
+                        slot(operand),
+                        constants.get(&weight)
+                    );
+                }
+                let _ = write!(
+                    statement,
+                    " lookup(&{}, t{}, {}, &x);",
+                    out,
+                    table,
+                    plan.tables[*table].values.len()
+                );
+                statement
+            }
+            NodeStep::Bit(operand, index) => {
+                format!("boolean(&{}, fcmc_fe_bit(&{}, {}));", out, slot(operand), index)
+            }
+        };
+        let _ = writeln!(body, "    {}", statement);
+    }
+    for (wire, source) in plan.wires.iter().enumerate() {
+        let wire_name = plan.wire_names.get(wire).map_or("", String::as_str);
+        let _ = writeln!(body, "    /* {} */", wire_name);
+        let _ = writeln!(body, "    {}", wire_statement(wire, source, &slots, &mut constants));
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Witness calculator for `{}`, generated by FCMC. Do not edit.", prefix);
+    let _ = writeln!(out, " *");
+    let _ = writeln!(out, " * {} must implement the field of modulus", FIELD_HEADER);
+    let _ = writeln!(out, " * {}. */\n", plan.modulus);
+    let _ = writeln!(out, "#include \"{}.h\"\n\n#include <stdint.h>\n#include <stdlib.h>\n", prefix);
+    let _ = writeln!(out, "#define NODES {}", plan.nodes.len());
+    let _ = writeln!(out, "#define CONSTANTS {}\n", constants.values.len());
+    out.push_str("static const char *const constants[] = {\n");
+    for value in &constants.values {
+        let _ = writeln!(out, "    \"{}\",", value);
+    }
+    out.push_str("    NULL,\n};\n");
+    for (index, table) in plan.tables.iter().enumerate() {
+        let _ = writeln!(out, "\n/* Rows of table `{}` */", table.name);
+        let _ = writeln!(out, "static const char *const table_{}[] = {{", index);
+        for value in &table.values {
+            let _ = writeln!(out, "    \"{}\",", value);
+        }
+        out.push_str("    NULL,\n};\n");
+    }
+    out.push_str(HELPERS);
+
+    let rows: usize = plan.tables.iter().map(|table| table.values.len()).sum();
+    let _ = writeln!(out, "\nint {}_compute_witness(const fcmc_fe *inputs, fcmc_fe *w)\n{{", prefix);
+    let _ = writeln!(out, "    fcmc_fe *memory = calloc(NODES + CONSTANTS + {} + 1, sizeof(fcmc_fe));", rows);
+    out.push_str("    fcmc_fe *v = memory;\n    fcmc_fe *k = v + NODES;\n");
+    let mut previous = ("k".to_string(), "CONSTANTS".to_string());
+    for (index, table) in plan.tables.iter().enumerate() {
+        let _ = writeln!(out, "    fcmc_fe *t{} = {} + {};", index, previous.0, previous.1);
+        previous = (format!("t{}", index), table.values.len().to_string());
+    }
+    out.push_str("    fcmc_fe zero, x, y;\n\n    if (memory == NULL) {\n        return -1;\n    }\n");
+    out.push_str("    for (size_t i = 0; i < CONSTANTS; i++) {\n        fcmc_fe_from_dec(&k[i], constants[i]);\n    }\n");
+    for (index, table) in plan.tables.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "    for (size_t i = 0; i < {}; i++) {{\n        fcmc_fe_from_dec(&t{}[i], table_{}[i]);\n    }}",
+            table.values.len(),
+            index,
+            index
+        );
+    }
+    // Not every circuit reads the scratch values
+    out.push_str("    fcmc_fe_zero(&zero);\n    (void)zero;\n    (void)x;\n    (void)y;\n\n");
+    out.push_str(&body);
+    out.push_str("\n    free(memory);\n    return 0;\n}\n");
+    out
+}
+
+const HELPERS: &str = r#"
+/* out = sum of k[coefficients[i]] * w[wires[i]] */
+static inline void lc(fcmc_fe *out, const fcmc_fe *w, const fcmc_fe *k, size_t n, const uint32_t *wires,
+                      const uint32_t *coefficients)
+{
+    fcmc_fe term;
+    fcmc_fe_zero(out);
+    for (size_t i = 0; i < n; i++) {
+        fcmc_fe_mul(&term, &w[wires[i]], &k[coefficients[i]]);
+        fcmc_fe_add(out, out, &term);
+    }
+}
+
+static inline void boolean(fcmc_fe *out, int value)
+{
+    if (value) {
+        fcmc_fe_one(out);
+    } else {
+        fcmc_fe_zero(out);
+    }
+}
+
+/* Row of `table` at the canonical value of `index`, zero past its end */
+static inline void lookup(fcmc_fe *out, const fcmc_fe *table, uint64_t rows, const fcmc_fe *index)
+{
+    uint64_t row;
+    if (fcmc_fe_to_u64(index, &row) && row < rows) {
+        *out = table[row];
+    } else {
+        fcmc_fe_zero(out);
+    }
+}
+
+/* Byte `index` of the value as 32 big-endian bytes */
+static inline void byte(fcmc_fe *out, const fcmc_fe *value, uint32_t index)
+{
+    uint64_t byte = 0;
+    for (uint32_t bit = 0; bit < 8; bit++) {
+        byte |= (uint64_t)fcmc_fe_bit(value, 8 * (31 - index) + bit) << bit;
+    }
+    fcmc_fe_from_u64(out, byte);
+}
+"#;
+
+/// Source of the reference `fcmc_field.h` for the field of `modulus`
+pub fn field_header(modulus: &BigUint) -> String {
+    let limbs = modulus.to_u64_digits();
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Reference field arithmetic for FCMC witness calculators, generated by FCMC.");
+    out.push_str(FIELD_HEADER_DOC);
+    let _ = writeln!(out, "#define FCMC_LIMBS {}", limbs.len());
+    let _ = writeln!(out, "#define FCMC_MODULUS_DECIMAL \"{}\"\n", modulus);
+    out.push_str("typedef struct {\n    uint64_t limbs[FCMC_LIMBS];\n} fcmc_fe;\n\n");
+    let limbs: Vec<String> = limbs.iter().map(|limb| format!("0x{:016x}ull", limb)).collect();
+    let _ = writeln!(out, "static const uint64_t fcmc_modulus[FCMC_LIMBS] = {{{}}};", limbs.join(", "));
+    out.push_str(FIELD_FUNCTIONS);
+    out
+}
+
+const FIELD_HEADER_DOC: &str = r#" *
+ * An element is its canonical value in FCMC_LIMBS little-endian 64-bit limbs. Any header
+ * defining the same type and functions can replace this one; outputs may alias inputs.
+ * Needs `unsigned __int128`, as GCC and Clang provide. */
+#ifndef FCMC_FIELD_H
+#define FCMC_FIELD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+"#;
+
+const FIELD_FUNCTIONS: &str = r#"
+typedef unsigned __int128 fcmc_wide;
+
+static inline void fcmc_fe_zero(fcmc_fe *out)
+{
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        out->limbs[i] = 0;
+    }
+}
+
+static inline int fcmc_fe_is_zero(const fcmc_fe *a)
+{
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        if (a->limbs[i] != 0) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+static inline int fcmc_fe_eq(const fcmc_fe *a, const fcmc_fe *b)
+{
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        if (a->limbs[i] != b->limbs[i]) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+/* a < b as canonical integers */
+static inline int fcmc_fe_lt(const fcmc_fe *a, const fcmc_fe *b)
+{
+    for (size_t i = FCMC_LIMBS; i-- > 0;) {
+        if (a->limbs[i] != b->limbs[i]) {
+            return a->limbs[i] < b->limbs[i];
+        }
+    }
+    return 0;
+}
+
+static inline int fcmc_geq_modulus(const uint64_t *limbs)
+{
+    for (size_t i = FCMC_LIMBS; i-- > 0;) {
+        if (limbs[i] != fcmc_modulus[i]) {
+            return limbs[i] > fcmc_modulus[i];
+        }
+    }
+    return 1;
+}
+
+/* Subtract the modulus, dropping the borrow: right for values below twice the modulus
+   that overflowed the limbs */
+static inline void fcmc_sub_modulus(uint64_t *limbs)
+{
+    uint64_t borrow = 0;
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        fcmc_wide d = (fcmc_wide)limbs[i] - fcmc_modulus[i] - borrow;
+        limbs[i] = (uint64_t)d;
+        borrow = (uint64_t)(d >> 64) & 1;
+    }
+}
+
+/* Reduce the little-endian value of `len` limbs, one bit at a time from the top */
+static inline void fcmc_reduce(fcmc_fe *out, const uint64_t *wide, size_t len)
+{
+    fcmc_fe r;
+    fcmc_fe_zero(&r);
+    for (size_t bit = 64 * len; bit-- > 0;) {
+        uint64_t carry = (wide[bit / 64] >> (bit % 64)) & 1;
+        for (size_t i = 0; i < FCMC_LIMBS; i++) {
+            uint64_t top = r.limbs[i] >> 63;
+            r.limbs[i] = (r.limbs[i] << 1) | carry;
+            carry = top;
+        }
+        if (carry || fcmc_geq_modulus(r.limbs)) {
+            fcmc_sub_modulus(r.limbs);
+        }
+    }
+    *out = r;
+}
+
+static inline void fcmc_fe_from_u64(fcmc_fe *out, uint64_t value)
+{
+    fcmc_reduce(out, &value, 1);
+}
+
+static inline void fcmc_fe_one(fcmc_fe *out)
+{
+    fcmc_fe_from_u64(out, 1);
+}
+
+static inline void fcmc_fe_add(fcmc_fe *out, const fcmc_fe *a, const fcmc_fe *b)
+{
+    uint64_t carry = 0;
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        fcmc_wide sum = (fcmc_wide)a->limbs[i] + b->limbs[i] + carry;
+        out->limbs[i] = (uint64_t)sum;
+        carry = (uint64_t)(sum >> 64);
+    }
+    if (carry || fcmc_geq_modulus(out->limbs)) {
+        fcmc_sub_modulus(out->limbs);
+    }
+}
+
+static inline void fcmc_fe_sub(fcmc_fe *out, const fcmc_fe *a, const fcmc_fe *b)
+{
+    uint64_t borrow = 0;
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        fcmc_wide d = (fcmc_wide)a->limbs[i] - b->limbs[i] - borrow;
+        out->limbs[i] = (uint64_t)d;
+        borrow = (uint64_t)(d >> 64) & 1;
+    }
+    if (borrow) {
+        uint64_t carry = 0;
+        for (size_t i = 0; i < FCMC_LIMBS; i++) {
+            fcmc_wide sum = (fcmc_wide)out->limbs[i] + fcmc_modulus[i] + carry;
+            out->limbs[i] = (uint64_t)sum;
+            carry = (uint64_t)(sum >> 64);
+        }
+    }
+}
+
+static inline void fcmc_fe_neg(fcmc_fe *out, const fcmc_fe *a)
+{
+    fcmc_fe zero;
+    fcmc_fe_zero(&zero);
+    fcmc_fe_sub(out, &zero, a);
+}
+
+static inline void fcmc_fe_mul(fcmc_fe *out, const fcmc_fe *a, const fcmc_fe *b)
+{
+    uint64_t wide[2 * FCMC_LIMBS] = {0};
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        uint64_t carry = 0;
+        for (size_t j = 0; j < FCMC_LIMBS; j++) {
+            fcmc_wide t = (fcmc_wide)a->limbs[i] * b->limbs[j] + wide[i + j] + carry;
+            wide[i + j] = (uint64_t)t;
+            carry = (uint64_t)(t >> 64);
+        }
+        wide[i + FCMC_LIMBS] = carry;
+    }
+    fcmc_reduce(out, wide, 2 * FCMC_LIMBS);
+}
+
+/* a^(modulus - 2), so 0 for 0 */
+static inline void fcmc_fe_inv(fcmc_fe *out, const fcmc_fe *a)
+{
+    uint64_t exponent[FCMC_LIMBS];
+    uint64_t borrow = 2;
+    fcmc_fe base = *a, result;
+    for (size_t i = 0; i < FCMC_LIMBS; i++) {
+        fcmc_wide d = (fcmc_wide)fcmc_modulus[i] - borrow;
+        exponent[i] = (uint64_t)d;
+        borrow = (uint64_t)(d >> 64) & 1;
+    }
+    fcmc_fe_one(&result);
+    for (size_t bit = 64 * FCMC_LIMBS; bit-- > 0;) {
+        fcmc_fe_mul(&result, &result, &result);
+        if ((exponent[bit / 64] >> (bit % 64)) & 1) {
+            fcmc_fe_mul(&result, &result, &base);
+        }
+    }
+    *out = result;
+}
+
+/* The element a reduced decimal string denotes */
+static inline void fcmc_fe_from_dec(fcmc_fe *out, const char *decimal)
+{
+    fcmc_fe r, ten, digit;
+    fcmc_fe_zero(&r);
+    fcmc_fe_from_u64(&ten, 10);
+    for (; *decimal != '\0'; decimal++) {
+        fcmc_fe_mul(&r, &r, &ten);
+        fcmc_fe_from_u64(&digit, (uint64_t)(*decimal - '0'));
+        fcmc_fe_add(&r, &r, &digit);
+    }
+    *out = r;
+}
+
+/* Bit `index` of the canonical value */
+static inline unsigned fcmc_fe_bit(const fcmc_fe *a, uint32_t index)
+{
+    if (index / 64 >= FCMC_LIMBS) {
+        return 0;
+    }
+    return (unsigned)((a->limbs[index / 64] >> (index % 64)) & 1);
+}
+
+/* Store the canonical value in `out` and return 1 if it fits in 64 bits, else return 0 */
+static inline int fcmc_fe_to_u64(const fcmc_fe *a, uint64_t *out)
+{
+    for (size_t i = 1; i < FCMC_LIMBS; i++) {
+        if (a->limbs[i] != 0) {
+            return 0;
+        }
+    }
+    *out = a->limbs[0];
+    return 1;
+}
+
+#endif
+"#;
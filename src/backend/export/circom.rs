@@ -0,0 +1,362 @@
+//! The binary `.r1cs` and text `.sym` formats of circom, and the `.wtns` witness
+//! format, read by snarkjs.
+//!
+//! All describe wires in the order the R1CS backend already allocates them: the
+//! constant one, public outputs, public inputs, private inputs, then internal wires.
+//! `R1csWriter` writes `.r1cs` files from a constraint stream without holding it.
+//! `read_r1cs` and `read_wtns` read circuits and witnesses that circom made back in.
+
+use crate::backend::export::Reader;
+use crate::backend::lowering::LinearCombination;
+use crate::backend::r1cs::{R1CSCircuit, R1CSConstraint};
+use crate::backend::{CircuitBackend, CircuitSink};
+use crate::optimization::field::FieldConfig;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"r1cs";
+const VERSION: u32 = 1;
+
+const HEADER_SECTION: u32 = 1;
+const CONSTRAINTS_SECTION: u32 = 2;
+const WIRE_TO_LABEL_SECTION: u32 = 3;
+
+const WTNS_MAGIC: &[u8; 4] = b"wtns";
+const WTNS_VERSION: u32 = 2;
+
+const WTNS_HEADER_SECTION: u32 = 1;
+const WTNS_VALUES_SECTION: u32 = 2;
+
+/// Write `circuit` to `path` as `.r1cs`, and its wire names next to it as `.sym`
+pub fn write_r1cs(circuit: &dyn CircuitBackend, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let r1cs = circuit.as_any().downcast_ref::<R1CSCircuit>().ok_or_else(|| {
+        FCMCError::BackendError(format!("circom export needs an R1CS circuit, not {:?}", circuit.target()))
+    })?;
+    write_file(path, &r1cs_bytes(r1cs))?;
+    write_file(&path.with_extension("sym"), sym_file(r1cs).as_bytes())
+}
+
+/// Write `witness`, one value per wire of `circuit`, to `path` as `.wtns`
+pub fn write_wtns(circuit: &dyn CircuitBackend, witness: &[BigUint], path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let r1cs = circuit.as_any().downcast_ref::<R1CSCircuit>().ok_or_else(|| {
+        FCMCError::BackendError(format!("circom export needs an R1CS circuit, not {:?}", circuit.target()))
+    })?;
+    write_file(path.as_ref(), &wtns_bytes(witness, &r1cs.field.modulus()))
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), FCMCError> {
+    std::fs::write(path, contents)
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Bytes per field element: the modulus rounded up to 64-bit words
+fn field_size(modulus: &BigUint) -> usize {
+    (modulus.bits() as usize).div_ceil(64) * 8
+}
+
+fn push_element(out: &mut Vec<u8>, value: &BigUint, size: usize) {
+    let mut bytes = value.to_bytes_le();
+    bytes.resize(size, 0);
+    out.extend_from_slice(&bytes);
+}
+
+fn push_section(out: &mut Vec<u8>, kind: u32, content: &[u8]) {
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(&(content.len() as u64).to_le_bytes());
+    out.extend_from_slice(content);
+}
+
+fn push_lc(out: &mut Vec<u8>, lc: &LinearCombination, size: usize) {
+    out.extend_from_slice(&(lc.len() as u32).to_le_bytes());
+    for (&wire, coefficient) in lc {
+        out.extend_from_slice(&(wire as u32).to_le_bytes());
+        push_element(out, coefficient, size);
+    }
+}
+
+fn push_constraint(out: &mut Vec<u8>, constraint: &R1CSConstraint, size: usize) {
+    push_lc(out, &constraint.a, size);
+    push_lc(out, &constraint.b, size);
+    push_lc(out, &constraint.c, size);
+}
+
+fn header_section(circuit: &R1CSCircuit, constraints: usize) -> Vec<u8> {
+    let modulus = circuit.field.modulus();
+    let size = field_size(&modulus);
+    let mut header = Vec::new();
+    header.extend_from_slice(&(size as u32).to_le_bytes());
+    push_element(&mut header, &modulus, size);
+    header.extend_from_slice(&(circuit.wires.len() as u32).to_le_bytes());
+    header.extend_from_slice(&(circuit.public_outputs as u32).to_le_bytes());
+    header.extend_from_slice(&(circuit.public_inputs as u32).to_le_bytes());
+    header.extend_from_slice(&(circuit.private_inputs as u32).to_le_bytes());
+    // One label per wire
+    header.extend_from_slice(&(circuit.wires.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(constraints as u32).to_le_bytes());
+    header
+}
+
+fn labels_section(circuit: &R1CSCircuit) -> Vec<u8> {
+    let mut labels = Vec::with_capacity(circuit.wires.len() * 8);
+    for wire in 0..circuit.wires.len() as u64 {
+        labels.extend_from_slice(&wire.to_le_bytes());
+    }
+    labels
+}
+
+/// The circuit in circom's binary `.r1cs` format, version 1
+pub fn r1cs_bytes(circuit: &R1CSCircuit) -> Vec<u8> {
+    let size = field_size(&circuit.field.modulus());
+    let mut constraints = Vec::new();
+    for constraint in &circuit.constraints {
+        push_constraint(&mut constraints, constraint, size);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&3u32.to_le_bytes());
+    push_section(&mut out, HEADER_SECTION, &header_section(circuit, circuit.constraints.len()));
+    push_section(&mut out, CONSTRAINTS_SECTION, &constraints);
+    push_section(&mut out, WIRE_TO_LABEL_SECTION, &labels_section(circuit));
+    out
+}
+
+/// Writes a `.r1cs` file constraint by constraint as `compile_to_sink` produces them.
+/// The constraints section comes first and its size is patched in at the end, followed
+/// by the header; circom's format lets sections appear in any order.
+pub struct R1csWriter<W: Write + Seek> {
+    out: W,
+    field: FieldConfig,
+    size: usize,
+    constraints: usize,
+    /// Offset of the constraints section's length
+    length_offset: u64,
+    length: u64,
+    buffer: Vec<u8>,
+}
+
+fn stream_error(e: std::io::Error) -> FCMCError {
+    FCMCError::BackendError(format!("Failed to write R1CS stream: {}", e))
+}
+
+impl R1csWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, field: &FieldConfig) -> Result<Self, FCMCError> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))?;
+        Self::new(BufWriter::new(file), field)
+    }
+}
+
+impl<W: Write + Seek> R1csWriter<W> {
+    pub fn new(mut out: W, field: &FieldConfig) -> Result<Self, FCMCError> {
+        let mut start = Vec::new();
+        start.extend_from_slice(MAGIC);
+        start.extend_from_slice(&VERSION.to_le_bytes());
+        start.extend_from_slice(&3u32.to_le_bytes());
+        start.extend_from_slice(&CONSTRAINTS_SECTION.to_le_bytes());
+        let length_offset = start.len() as u64;
+        start.extend_from_slice(&0u64.to_le_bytes());
+        out.write_all(&start).map_err(stream_error)?;
+        Ok(Self {
+            out,
+            field: field.clone(),
+            size: field_size(&field.modulus()),
+            constraints: 0,
+            length_offset,
+            length: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Seek> CircuitSink for R1csWriter<W> {
+    fn push_constraint(&mut self, constraint: R1CSConstraint) -> Result<(), FCMCError> {
+        self.buffer.clear();
+        push_constraint(&mut self.buffer, &constraint, self.size);
+        self.out.write_all(&self.buffer).map_err(stream_error)?;
+        self.length += self.buffer.len() as u64;
+        self.constraints += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, layout: &R1CSCircuit) -> Result<(), FCMCError> {
+        if layout.field != self.field {
+            return Err(FCMCError::BackendError(format!(
+                "R1CS stream was opened for the {} field but the circuit is over {}",
+                self.field, layout.field
+            )));
+        }
+        let end = self.out.stream_position().map_err(stream_error)?;
+        self.out.seek(SeekFrom::Start(self.length_offset)).map_err(stream_error)?;
+        self.out.write_all(&self.length.to_le_bytes()).map_err(stream_error)?;
+        self.out.seek(SeekFrom::Start(end)).map_err(stream_error)?;
+
+        let mut tail = Vec::new();
+        push_section(&mut tail, HEADER_SECTION, &header_section(layout, self.constraints));
+        push_section(&mut tail, WIRE_TO_LABEL_SECTION, &labels_section(layout));
+        self.out.write_all(&tail).map_err(stream_error)?;
+        self.out.flush().map_err(stream_error)
+    }
+}
+
+/// `label,wire,component,name` per wire, names prefixed with `main.` as circom does
+pub fn sym_file(circuit: &R1CSCircuit) -> String {
+    circuit
+        .wire_names
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(wire, name)| format!("{},{},0,main.{}\n", wire, wire, name))
+        .collect()
+}
+
+/// The witness in snarkjs' binary `.wtns` format, version 2
+pub fn wtns_bytes(witness: &[BigUint], modulus: &BigUint) -> Vec<u8> {
+    let size = field_size(modulus);
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(size as u32).to_le_bytes());
+    push_element(&mut header, modulus, size);
+    header.extend_from_slice(&(witness.len() as u32).to_le_bytes());
+
+    let mut values = Vec::with_capacity(witness.len() * size);
+    for value in witness {
+        push_element(&mut values, value, size);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(WTNS_MAGIC);
+    out.extend_from_slice(&WTNS_VERSION.to_le_bytes());
+    out.extend_from_slice(&2u32.to_le_bytes());
+    push_section(&mut out, WTNS_HEADER_SECTION, &header);
+    push_section(&mut out, WTNS_VALUES_SECTION, &values);
+    out
+}
+
+/// A circuit read from a `.r1cs` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircomR1cs {
+    pub modulus: BigUint,
+    /// Every wire, the constant one included
+    pub wires: usize,
+    pub public_outputs: usize,
+    pub public_inputs: usize,
+    pub private_inputs: usize,
+    pub constraints: Vec<R1CSConstraint>,
+}
+
+impl CircomR1cs {
+    /// Whether `witness` has a value per wire and satisfies every row; runs in the
+    /// active field
+    pub fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        witness.len() == self.wires
+            && self
+                .constraints
+                .iter()
+                .all(|constraint| constraint.is_satisfied(witness))
+    }
+
+    /// The public outputs of `witness`
+    pub fn outputs<'a>(&self, witness: &'a [BigUint]) -> &'a [BigUint] {
+        &witness[1..(1 + self.public_outputs).min(witness.len())]
+    }
+}
+
+/// Read a circuit in circom's binary `.r1cs` format
+pub fn read_r1cs(bytes: &[u8]) -> Result<CircomR1cs, FCMCError> {
+    let mut reader = Reader::new(bytes, "r1cs");
+    if reader.take(4)? != MAGIC {
+        return Err(reader.invalid("not an r1cs file"));
+    }
+    let _version = reader.u32()?;
+    let sections = reader.u32()?;
+
+    // The constraints section may come before the header that gives its field size
+    let mut header = None;
+    let mut constraints = None;
+    for _ in 0..sections {
+        match reader.section()? {
+            (HEADER_SECTION, mut section) => {
+                let size = section.u32()? as usize;
+                let modulus = section.fixed_element(size)?;
+                let counts = [section.u32()?, section.u32()?, section.u32()?, section.u32()?];
+                let _labels = section.u64()?;
+                header = Some((size, modulus, counts, section.u32()?));
+            }
+            (CONSTRAINTS_SECTION, section) => constraints = Some(section),
+            _ => {}
+        }
+    }
+
+    let (size, modulus, [wires, public_outputs, public_inputs, private_inputs], count) =
+        header.ok_or_else(|| reader.invalid("no header section"))?;
+    let mut section = constraints.ok_or_else(|| reader.invalid("no constraints section"))?;
+    // Each row is at least the term counts of A, B and C
+    let mut rows = Vec::with_capacity(section.count(count, 12)?);
+    for _ in 0..count {
+        rows.push(R1CSConstraint {
+            a: read_lc(&mut section, size)?,
+            b: read_lc(&mut section, size)?,
+            c: read_lc(&mut section, size)?,
+        });
+    }
+    Ok(CircomR1cs {
+        modulus,
+        wires: wires as usize,
+        public_outputs: public_outputs as usize,
+        public_inputs: public_inputs as usize,
+        private_inputs: private_inputs as usize,
+        constraints: rows,
+    })
+}
+
+fn read_lc(section: &mut Reader, size: usize) -> Result<LinearCombination, FCMCError> {
+    let terms = section.u32()?;
+    let mut lc = LinearCombination::new();
+    for _ in 0..terms {
+        let wire = section.u32()? as usize;
+        lc.insert(wire, section.fixed_element(size)?);
+    }
+    Ok(lc)
+}
+
+/// Read a witness in snarkjs' binary `.wtns` format, with the modulus it was computed in
+pub fn read_wtns(bytes: &[u8]) -> Result<(BigUint, Vec<BigUint>), FCMCError> {
+    let mut reader = Reader::new(bytes, "wtns");
+    if reader.take(4)? != WTNS_MAGIC {
+        return Err(reader.invalid("not a wtns file"));
+    }
+    let _version = reader.u32()?;
+    let sections = reader.u32()?;
+
+    let mut header = None;
+    let mut values = None;
+    for _ in 0..sections {
+        match reader.section()? {
+            (WTNS_HEADER_SECTION, mut section) => {
+                let size = section.u32()? as usize;
+                header = Some((size, section.fixed_element(size)?, section.u32()?));
+            }
+            (WTNS_VALUES_SECTION, section) => values = Some(section),
+            _ => {}
+        }
+    }
+
+    let (size, modulus, count) = header.ok_or_else(|| reader.invalid("no header section"))?;
+    let mut section = values.ok_or_else(|| reader.invalid("no values section"))?;
+    let mut witness = Vec::with_capacity(section.count(count, size)?);
+    for _ in 0..count {
+        witness.push(section.fixed_element(size)?);
+    }
+    Ok((modulus, witness))
+}
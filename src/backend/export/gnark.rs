@@ -0,0 +1,183 @@
+//! Generation of a Go package defining a gnark `frontend.Circuit` for an R1CS circuit.
+//!
+//! Every wire but the constant one is a field of the circuit struct: the public wires,
+//! outputs then inputs, in `Public` and the rest in `Private`, both in FCMC's wire order.
+//! `Define` asserts each rank-1 row, so gnark compiles the package with either of its
+//! proof systems. Provers compute the witness with FCMC and pass it to `Assignment`.
+
+use crate::backend::lowering::{LinearCombination, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::CircuitBackend;
+use crate::optimization::field::FieldConfig;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::One;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Write the Go package for `circuit` to `path`, as package `package`
+pub fn write_gnark_circuit(circuit: &dyn CircuitBackend, package: &str, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let r1cs = circuit.as_any().downcast_ref::<R1CSCircuit>().ok_or_else(|| {
+        FCMCError::BackendError(format!("gnark export needs an R1CS circuit, not {:?}", circuit.target()))
+    })?;
+    std::fs::write(path, gnark_source(r1cs, package)?)
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// The gnark-crypto curve whose scalar field is `field`
+fn curve(field: &FieldConfig) -> Result<&'static str, FCMCError> {
+    match field {
+        FieldConfig::Bn254 => Ok("BN254"),
+        FieldConfig::Bls12_381 => Ok("BLS12_381"),
+        _ => Err(FCMCError::BackendError(format!("gnark has no curve with the {} scalar field", field))),
+    }
+}
+
+/// Go package name for `name`: lowercase letters, digits and underscores
+fn package_name(name: &str) -> String {
+    let mut package: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if !package.starts_with(|c: char| c.is_ascii_lowercase()) {
+        package.insert_str(0, "circuit");
+    }
+    package
+}
+
+/// Decimal constants `Define` parses once, by value
+#[derive(Default)]
+struct Constants {
+    values: Vec<BigUint>,
+    index: HashMap<BigUint, usize>,
+}
+
+impl Constants {
+    fn get(&mut self, value: &BigUint) -> String {
+        let values = &mut self.values;
+        let index = *self.index.entry(value.clone()).or_insert_with(|| {
+            values.push(value.clone());
+            values.len() - 1
+        });
+        format!("k[{}]", index)
+    }
+}
+
+fn lc_expression(lc: &LinearCombination, constants: &mut Constants) -> String {
+    let terms: Vec<String> = lc
+        .iter()
+        .map(|(&wire, coefficient)| match (wire, coefficient.is_one()) {
+            (ONE_WIRE, _) => constants.get(coefficient),
+            (_, true) => format!("w[{}]", wire),
+            (_, false) => format!("api.Mul(w[{}], {})", wire, constants.get(coefficient)),
+        })
+        .collect();
+    match terms.len() {
+        0 => "0".to_string(),
+        1 => terms[0].clone(),
+        _ => format!("api.Add({})", terms.join(", ")),
+    }
+}
+
+/// Source of the generated package
+pub fn gnark_source(circuit: &R1CSCircuit, package: &str) -> Result<String, FCMCError> {
+    if !circuit.ranges.is_empty()
+        || !circuit.black_boxes.is_empty()
+        || !circuit.custom_gates.is_empty()
+        || !circuit.table_lookups.is_empty()
+    {
+        return Err(FCMCError::BackendError("gnark export needs every constraint lowered to rank-1 rows".to_string()));
+    }
+    let curve = curve(&circuit.field)?;
+    let public = circuit.public_count();
+    let private = circuit.wires.len() - 1 - public;
+
+    let mut constants = Constants::default();
+    let mut body = String::new();
+    // Writing to a String cannot fail
+    for constraint in &circuit.constraints {
+        let a = lc_expression(&constraint.a, &mut constants);
+        let b = lc_expression(&constraint.b, &mut constants);
+        let c = lc_expression(&constraint.c, &mut constants);
+        let _ = writeln!(body, "\tapi.AssertIsEqual(api.Mul({}, {}), {})", a, b, c);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// Code generated by FCMC. DO NOT EDIT.");
+    let _ = writeln!(out, "\n// Package {} is an FCMC circuit for gnark.", package_name(package));
+    let _ = writeln!(out, "package {}", package_name(package));
+    out.push_str(IMPORTS);
+
+    let _ = writeln!(out, "\n// Curve has the scalar field the circuit was compiled over.");
+    let _ = writeln!(out, "const Curve = ecc.{}", curve);
+    let _ = writeln!(out, "\n// Wires counts every FCMC wire, the constant one included.");
+    let _ = writeln!(out, "const Wires = {}", circuit.wires.len());
+
+    let _ = writeln!(out, "\nvar constants = [{}]string{{", constants.values.len());
+    for value in &constants.values {
+        let _ = writeln!(out, "\t\"{}\",", value);
+    }
+    out.push_str("}\n");
+
+    let _ = writeln!(out, "\n// Circuit holds every wire but the constant one, in FCMC order.");
+    out.push_str("type Circuit struct {\n");
+    for (wire, name) in circuit.wire_names.iter().enumerate().skip(1) {
+        if wire <= public {
+            let _ = writeln!(out, "\t// Public[{}]: {}", wire - 1, name);
+        } else {
+            let _ = writeln!(out, "\t// Private[{}]: {}", wire - 1 - public, name);
+        }
+    }
+    let _ = writeln!(out, "\tPublic  [{}]frontend.Variable `gnark:\",public\"`", public);
+    let _ = writeln!(out, "\tPrivate [{}]frontend.Variable `gnark:\",secret\"`", private);
+    out.push_str("}\n");
+
+    out.push_str(HELPERS);
+    out.push_str("\n// Define asserts every rank-1 row of the circuit.\n");
+    out.push_str("func (circuit *Circuit) Define(api frontend.API) error {\n");
+    out.push_str("\tk := parse(constants[:])\n");
+    out.push_str("\tw := circuit.wires()\n");
+    out.push_str(&body);
+    out.push_str("\treturn nil\n}\n");
+    Ok(out)
+}
+
+const IMPORTS: &str = r#"
+import (
+	"math/big"
+
+	"github.com/consensys/gnark-crypto/ecc"
+	"github.com/consensys/gnark/frontend"
+)
+"#;
+
+const HELPERS: &str = r#"
+// Assignment of the full FCMC witness, one value per wire starting with the constant one.
+func Assignment(witness []*big.Int) *Circuit {
+	var circuit Circuit
+	for i := range circuit.Public {
+		circuit.Public[i] = witness[1+i]
+	}
+	for i := range circuit.Private {
+		circuit.Private[i] = witness[1+len(circuit.Public)+i]
+	}
+	return &circuit
+}
+
+func (circuit *Circuit) wires() []frontend.Variable {
+	w := make([]frontend.Variable, 0, Wires)
+	w = append(w, 1)
+	w = append(w, circuit.Public[:]...)
+	return append(w, circuit.Private[:]...)
+}
+
+func parse(decimals []string) []*big.Int {
+	values := make([]*big.Int, len(decimals))
+	for i, decimal := range decimals {
+		values[i], _ = new(big.Int).SetString(decimal, 10)
+	}
+	return values
+}
+"#;
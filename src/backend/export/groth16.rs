@@ -0,0 +1,158 @@
+//! Groth16 trusted-setup sizes of an R1CS circuit, and the shape check of snarkjs'
+//! `.zkey` proving keys against it.
+//!
+//! A key only fits the circuit it was set up for: a key left over from before the
+//! circuit changed still loads, but every proof made with it fails to verify. Comparing
+//! the key's header with the compiled circuit catches that before proving.
+
+use crate::backend::export::Reader;
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::CircuitBackend;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::collections::HashSet;
+use std::path::Path;
+
+const ZKEY_MAGIC: &[u8; 4] = b"zkey";
+
+const ZKEY_HEADER_SECTION: u32 = 1;
+const ZKEY_GROTH16_HEADER_SECTION: u32 = 2;
+
+/// Protocol id snarkjs writes for Groth16 keys
+const GROTH16_PROTOCOL: u32 = 1;
+
+/// What a Groth16 setup for the circuit has to provide
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupSizes {
+    pub constraints: usize,
+    /// Every wire, the constant one included
+    pub variables: usize,
+    /// Outputs and public inputs
+    pub public: usize,
+    /// Size of the evaluation domain, as snarkjs picks it
+    pub domain_size: usize,
+    /// Nonzero entries of the A, B and C matrices
+    pub nonzeros: [usize; 3],
+    /// Wires with a nonzero entry in B; each needs a G2 point in the key
+    pub b_wires: usize,
+    pub modulus: BigUint,
+}
+
+impl SetupSizes {
+    pub fn of(circuit: &R1CSCircuit) -> Self {
+        let mut nonzeros = [0; 3];
+        let mut b_wires = HashSet::new();
+        for constraint in &circuit.constraints {
+            nonzeros[0] += constraint.a.len();
+            nonzeros[1] += constraint.b.len();
+            nonzeros[2] += constraint.c.len();
+            b_wires.extend(constraint.b.keys().copied());
+        }
+        let public = circuit.public_count();
+        Self {
+            constraints: circuit.constraints.len(),
+            variables: circuit.wires.len(),
+            public,
+            domain_size: domain_size(circuit.constraints.len() + public),
+            nonzeros,
+            b_wires: b_wires.len(),
+            modulus: circuit.field.modulus(),
+        }
+    }
+
+    /// Fraction of each matrix's entries that are nonzero
+    pub fn densities(&self) -> [f64; 3] {
+        let entries = (self.constraints * self.variables).max(1) as f64;
+        self.nonzeros.map(|nonzeros| nonzeros as f64 / entries)
+    }
+
+    /// How `key` differs from what the circuit needs; empty if it fits
+    pub fn mismatches(&self, key: &ZkeyShape) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if key.modulus != self.modulus {
+            mismatches.push("the key is over another scalar field".to_string());
+        }
+        let counts = [
+            ("variables", key.variables, self.variables),
+            ("public values", key.public, self.public),
+            ("domain size", key.domain_size, self.domain_size),
+        ];
+        for (what, found, expected) in counts {
+            if found != expected {
+                mismatches.push(format!("{}: the key has {}, the circuit {}", what, found, expected));
+            }
+        }
+        mismatches
+    }
+}
+
+/// The Groth16 domain for `rows` constraints and public values: the smallest power of
+/// two above `rows`, leaving a row for the constant one
+fn domain_size(rows: usize) -> usize {
+    1 << (usize::BITS - rows.leading_zeros()).max(1)
+}
+
+/// Header fields of a Groth16 `.zkey`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZkeyShape {
+    /// Scalar field the key was set up over
+    pub modulus: BigUint,
+    pub variables: usize,
+    pub public: usize,
+    pub domain_size: usize,
+}
+
+/// Read the header of a snarkjs Groth16 `.zkey`
+pub fn zkey_shape(bytes: &[u8]) -> Result<ZkeyShape, FCMCError> {
+    let mut reader = Reader::new(bytes, "zkey");
+    if reader.take(4)? != ZKEY_MAGIC {
+        return Err(reader.invalid("not a zkey file"));
+    }
+    let _version = reader.u32()?;
+    let sections = reader.u32()?;
+
+    let mut protocol = None;
+    let mut shape = None;
+    for _ in 0..sections {
+        let (kind, mut section) = reader.section()?;
+        match kind {
+            ZKEY_HEADER_SECTION => protocol = Some(section.u32()?),
+            ZKEY_GROTH16_HEADER_SECTION => {
+                // Base field of the curve, then the scalar field
+                section.element()?;
+                shape = Some(ZkeyShape {
+                    modulus: section.element()?,
+                    variables: section.u32()? as usize,
+                    public: section.u32()? as usize,
+                    domain_size: section.u32()? as usize,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    if protocol != Some(GROTH16_PROTOCOL) {
+        return Err(reader.invalid("not a Groth16 key"));
+    }
+    shape.ok_or_else(|| reader.invalid("no Groth16 header"))
+}
+
+/// Check that the `.zkey` at `path` was set up for `circuit`
+pub fn check_zkey(circuit: &dyn CircuitBackend, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let r1cs = circuit.as_any().downcast_ref::<R1CSCircuit>().ok_or_else(|| {
+        FCMCError::BackendError(format!("Groth16 keys need an R1CS circuit, not {:?}", circuit.target()))
+    })?;
+    let bytes = std::fs::read(path)
+        .map_err(|e| FCMCError::BackendError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mismatches = SetupSizes::of(r1cs).mismatches(&zkey_shape(&bytes)?);
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(FCMCError::VerificationError(format!(
+            "{} was set up for another circuit ({}); run the setup again",
+            path.display(),
+            mismatches.join("; ")
+        )))
+    }
+}
@@ -0,0 +1,389 @@
+//! Generation of a Rust module implementing a halo2 `Circuit` for a compiled circuit.
+//!
+//! The module holds the plonkish gate list as constant tables and a chip that configures
+//! the standard gate, one range-table lookup per width the circuit checks, one lookup per
+//! function table and one gate per custom gate the circuit uses, and assigns the rows
+//! region by region. Rows lowered
+//! from a subcircuit the optimizer marked as a repeated gadget get a region named after
+//! it, so the layouter can place them apart.
+
+use crate::backend::custom_gate::{self, CustomGate};
+use crate::backend::plonk::PlonkCircuit;
+use crate::backend::{CircuitBackend, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::subcircuit::GADGET_ATTRIBUTE;
+use crate::{CompiledCircuit, FCMCError};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Region of rows lowered from nodes outside any marked gadget
+const DEFAULT_REGION: &str = "gates";
+
+/// Write the halo2 module for `compiled` to `path`; types are prefixed with `name`
+pub fn write_halo2_module(compiled: &CompiledCircuit, name: &str, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let circuit = match compiled.circuit.as_plonk() {
+        Some(circuit) if matches!(circuit.target, TargetSystem::Halo2) => circuit,
+        _ => {
+            return Err(FCMCError::BackendError(format!(
+                "halo2 code generation needs a Halo2 circuit, not {:?}",
+                compiled.circuit.target()
+            )));
+        }
+    };
+    std::fs::write(path, halo2_module(circuit, &compiled.ir, name))
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Consecutive rows lowered from the same gadget, as `(name, start, end)`
+fn regions(circuit: &PlonkCircuit, graph: &IRGraph) -> Vec<(String, usize, usize)> {
+    let mut regions: Vec<(String, usize, usize)> = Vec::new();
+    for (row, gate) in circuit.gates.iter().enumerate() {
+        let name = gate
+            .origin
+            .and_then(|origin| graph.get_node(origin))
+            .and_then(|node| node.attributes.get(GADGET_ATTRIBUTE))
+            .map_or(DEFAULT_REGION, String::as_str);
+        match regions.last_mut() {
+            Some((last, _, end)) if last == name => *end = row + 1,
+            _ => regions.push((name.to_string(), row, row + 1)),
+        }
+    }
+    regions
+}
+
+/// `configure` code listing each custom gate in `gates` with the selector enabling it
+fn custom_gate_code(gates: &[CustomGate]) -> String {
+    let cells: [&str; custom_gate::CELLS] = [
+        "cur[0].clone()",
+        "cur[1].clone()",
+        "cur[2].clone()",
+        "next[0].clone()",
+        "next[1].clone()",
+        "next[2].clone()",
+    ];
+
+    let mut out = String::from("        let custom = vec![\n");
+    for gate in gates {
+        let _ = writeln!(out, "            ({:?}, {{", gate.name());
+        out.push_str("                let selector = meta.selector();\n");
+        let _ = writeln!(out, "                meta.create_gate({:?}, |meta| {{", gate.name());
+        out.push_str("                    let q = meta.query_selector(selector);\n");
+        out.push_str("                    let cur = advice.map(|column| meta.query_advice(column, Rotation::cur()));\n");
+        out.push_str("                    let next = advice.map(|column| meta.query_advice(column, Rotation::next()));\n");
+        let _ = writeln!(out, "                    vec![q * ({})]", gate.expression(&cells));
+        out.push_str("                });\n");
+        out.push_str("                selector\n");
+        out.push_str("            }),\n");
+    }
+    out.push_str("        ];\n");
+    out
+}
+
+/// Source of the generated module
+pub fn halo2_module(circuit: &PlonkCircuit, graph: &IRGraph, name: &str) -> String {
+    let tables = circuit.range_tables();
+    let regions = regions(circuit, graph);
+    let public_rows = circuit.public_rows();
+    let mut out = String::new();
+
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "//! halo2 circuit `{}`, generated by FCMC. Do not edit.", name);
+    out.push_str(PRELUDE);
+
+    let _ = writeln!(out, "\npub const VARIABLES: usize = {};", circuit.variables.len());
+    let _ = writeln!(out, "\n/// Variables in the a, b and c cells of each row");
+    let _ = writeln!(out, "pub const ROWS: [[usize; 3]; {}] = [", circuit.gates.len());
+    for gate in &circuit.gates {
+        let _ = writeln!(out, "    {:?},", gate.wires);
+    }
+    out.push_str("];\n");
+
+    let _ = writeln!(out, "\n/// q_l, q_r, q_o, q_m and q_c of each row, in decimal");
+    let _ = writeln!(out, "pub const SELECTORS: [[&str; 5]; {}] = [", circuit.gates.len());
+    for gate in &circuit.gates {
+        // Public rows are bound to the instance column instead of the gate
+        let selectors: Vec<String> = gate
+            .selectors()
+            .iter()
+            .map(|(_, value)| if gate.public { "\"0\"".to_string() } else { format!("\"{}\"", value) })
+            .collect();
+        let _ = writeln!(out, "    [{}],", selectors.join(", "));
+    }
+    out.push_str("];\n");
+
+    let _ = writeln!(out, "\n/// Width of the range table each row's a cell is looked up in, 0 for none");
+    let _ = writeln!(out, "pub const LOOKUPS: [u32; {}] = [", circuit.gates.len());
+    for gate in &circuit.gates {
+        let _ = writeln!(out, "    {},", gate.lookup.unwrap_or(0));
+    }
+    out.push_str("];\n");
+
+    let _ = writeln!(out, "\n/// Function table each row's a and b cells are looked up in, plus one; 0 for none");
+    let _ = writeln!(out, "pub const TABLE_LOOKUPS: [usize; {}] = [", circuit.gates.len());
+    for gate in &circuit.gates {
+        let _ = writeln!(out, "    {},", gate.table.map_or(0, |table| table + 1));
+    }
+    out.push_str("];\n");
+
+    let _ = writeln!(out, "\n/// Custom gate enabled on each row, empty for none");
+    let _ = writeln!(out, "pub const CUSTOM: [&str; {}] = [", circuit.gates.len());
+    for gate in &circuit.gates {
+        let _ = writeln!(out, "    {:?},", gate.custom.map_or("", |custom| custom.name()));
+    }
+    out.push_str("];\n");
+
+    let _ = writeln!(out, "\npub const RANGE_TABLES: [u32; {}] = {:?};", tables.len(), tables);
+    let _ = writeln!(out, "\n/// Value of every row of each function table, in decimal");
+    let _ = writeln!(out, "pub const FUNCTION_TABLES: [&[&str]; {}] = [", circuit.tables.len());
+    for table in &circuit.tables {
+        let values: Vec<String> = table.values.iter().map(|value| format!("\"{}\"", value)).collect();
+        let _ = writeln!(out, "    // {}", table.name);
+        let _ = writeln!(out, "    &[{}],", values.join(", "));
+    }
+    out.push_str("];\n");
+    let _ = writeln!(out, "\n/// Rows whose a cell is a public input, in instance order");
+    let _ = writeln!(out, "pub const PUBLIC_ROWS: [usize; {}] = {:?};", public_rows.len(), public_rows);
+    let _ = writeln!(out, "\n/// Region name and row range");
+    let _ = writeln!(out, "pub const REGIONS: [(&str, usize, usize); {}] = [", regions.len());
+    for (region, start, end) in &regions {
+        let _ = writeln!(out, "    ({:?}, {}, {}),", region, start, end);
+    }
+    out.push_str("];\n");
+
+    let chip = CHIP
+        .replace("{name}", name)
+        .replace("{custom_gates}\n", &custom_gate_code(&circuit.custom_gates()));
+    out.push_str(&chip);
+    out
+}
+
+const PRELUDE: &str = r#"
+use ff::PrimeField;
+use halo2_proofs::circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value};
+use halo2_proofs::plonk::{
+    Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, Selector, TableColumn,
+};
+use halo2_proofs::poly::Rotation;
+use std::marker::PhantomData;
+"#;
+
+const CHIP: &str = r#"
+fn constant<F: PrimeField>(decimal: &str) -> F {
+    F::from_str_vartime(decimal).expect("reduced field constant")
+}
+
+#[derive(Clone, Debug)]
+pub struct {name}Config {
+    pub advice: [Column<Advice>; 3],
+    pub fixed: [Column<Fixed>; 5],
+    pub instance: Column<Instance>,
+    /// Table width, the selector enabling the lookup, and the table
+    pub ranges: Vec<(u32, Selector, TableColumn)>,
+    /// The selector enabling each function table's lookup, and its index and value columns
+    pub tables: Vec<(Selector, TableColumn, TableColumn)>,
+    /// Custom gate name and the selector enabling it
+    pub custom: Vec<(&'static str, Selector)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct {name}Chip<F: PrimeField> {
+    config: {name}Config,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> {name}Chip<F> {
+    pub fn construct(config: {name}Config) -> Self {
+        Self { config, _marker: PhantomData }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> {name}Config {
+        let advice = [meta.advice_column(), meta.advice_column(), meta.advice_column()];
+        let fixed = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        for column in advice {
+            meta.enable_equality(column);
+        }
+
+        meta.create_gate("standard", |meta| {
+            let [a, b, c] = advice.map(|column| meta.query_advice(column, Rotation::cur()));
+            let [q_l, q_r, q_o, q_m, q_c] = fixed.map(|column| meta.query_fixed(column, Rotation::cur()));
+            vec![q_l * a.clone() + q_r * b.clone() + q_o * c + q_m * a * b + q_c]
+        });
+
+{custom_gates}
+
+        let ranges = RANGE_TABLES
+            .iter()
+            .map(|&bits| {
+                let selector = meta.complex_selector();
+                let table = meta.lookup_table_column();
+                meta.lookup("range", |meta| {
+                    let enabled = meta.query_selector(selector);
+                    let value = meta.query_advice(advice[0], Rotation::cur());
+                    vec![(enabled * value, table)]
+                });
+                (bits, selector, table)
+            })
+            .collect();
+
+        // Table rows are shifted down by one behind a (0, 0) row, which the rows with the
+        // lookup disabled match whatever the table's first value
+        let tables = FUNCTION_TABLES
+            .iter()
+            .map(|_| {
+                let selector = meta.complex_selector();
+                let index = meta.lookup_table_column();
+                let value = meta.lookup_table_column();
+                meta.lookup("function", |meta| {
+                    let enabled = meta.query_selector(selector);
+                    let a = meta.query_advice(advice[0], Rotation::cur()) + Expression::Constant(F::ONE);
+                    let b = meta.query_advice(advice[1], Rotation::cur());
+                    vec![(enabled.clone() * a, index), (enabled * b, value)]
+                });
+                (selector, index, value)
+            })
+            .collect();
+
+        {name}Config { advice, fixed, instance, ranges, tables, custom }
+    }
+
+    pub fn load_tables(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        for &(bits, _, table) in &self.config.ranges {
+            layouter.assign_table(
+                || format!("range{}", bits),
+                |mut cells| {
+                    for value in 0..(1u64 << bits) {
+                        cells.assign_cell(|| "value", table, value as usize, || Value::known(F::from(value)))?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+        for (values, &(_, index, value)) in FUNCTION_TABLES.iter().zip(&self.config.tables) {
+            layouter.assign_table(
+                || "function",
+                |mut cells| {
+                    cells.assign_cell(|| "index", index, 0, || Value::known(F::ZERO))?;
+                    cells.assign_cell(|| "value", value, 0, || Value::known(F::ZERO))?;
+                    for (row, decimal) in values.iter().enumerate() {
+                        let shifted = F::from(row as u64 + 1);
+                        cells.assign_cell(|| "index", index, row + 1, || Value::known(shifted))?;
+                        cells.assign_cell(|| "value", value, row + 1, || Value::known(constant::<F>(decimal)))?;
+                    }
+                    Ok(())
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Assign every region and return the cells of the public rows
+    pub fn assign(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        witness: &[Value<F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let mut cells: Vec<Option<AssignedCell<F, F>>> = vec![None; VARIABLES];
+        let mut public = Vec::new();
+        for &(name, start, end) in REGIONS.iter() {
+            let (assigned, exposed) = layouter.assign_region(
+                || name,
+                |mut region| {
+                    let mut assigned: Vec<(usize, AssignedCell<F, F>)> = Vec::new();
+                    let mut exposed = Vec::new();
+                    for row in start..end {
+                        let offset = row - start;
+                        for (column, value) in self.config.fixed.iter().zip(SELECTORS[row]) {
+                            region.assign_fixed(|| "selector", *column, offset, || Value::known(constant::<F>(value)))?;
+                        }
+                        for &(bits, selector, _) in &self.config.ranges {
+                            if LOOKUPS[row] == bits {
+                                selector.enable(&mut region, offset)?;
+                            }
+                        }
+                        for (table, &(selector, _, _)) in self.config.tables.iter().enumerate() {
+                            if TABLE_LOOKUPS[row] == table + 1 {
+                                selector.enable(&mut region, offset)?;
+                            }
+                        }
+                        for &(gate, selector) in &self.config.custom {
+                            if CUSTOM[row] == gate {
+                                selector.enable(&mut region, offset)?;
+                            }
+                        }
+                        for (index, (column, &variable)) in self.config.advice.iter().zip(&ROWS[row]).enumerate() {
+                            let cell = region.assign_advice(|| "cell", *column, offset, || witness[variable])?;
+                            let first = cells[variable]
+                                .as_ref()
+                                .or_else(|| assigned.iter().find(|(v, _)| *v == variable).map(|(_, cell)| cell));
+                            match first {
+                                Some(first) => region.constrain_equal(first.cell(), cell.cell())?,
+                                None => assigned.push((variable, cell.clone())),
+                            }
+                            if index == 0 && PUBLIC_ROWS.contains(&row) {
+                                exposed.push(cell);
+                            }
+                        }
+                    }
+                    Ok((assigned, exposed))
+                },
+            )?;
+            for (variable, cell) in assigned {
+                cells[variable] = Some(cell);
+            }
+            public.extend(exposed);
+        }
+        Ok(public)
+    }
+}
+
+/// The circuit, with one value per variable of the FCMC witness
+#[derive(Clone, Debug)]
+pub struct {name}Circuit<F: PrimeField> {
+    pub witness: Vec<Value<F>>,
+}
+
+impl<F: PrimeField> {name}Circuit<F> {
+    pub fn new(witness: &[F]) -> Self {
+        Self { witness: witness.iter().map(|value| Value::known(*value)).collect() }
+    }
+}
+
+impl<F: PrimeField> Default for {name}Circuit<F> {
+    fn default() -> Self {
+        Self { witness: vec![Value::unknown(); VARIABLES] }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for {name}Circuit<F> {
+    type Config = {name}Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        {name}Chip::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let instance = config.instance;
+        let chip = {name}Chip::construct(config);
+        chip.load_tables(&mut layouter)?;
+        let public = chip.assign(&mut layouter, &self.witness)?;
+        for (index, cell) in public.iter().enumerate() {
+            layouter.constrain_instance(cell.cell(), instance, index)?;
+        }
+        Ok(())
+    }
+}
+"#;
@@ -0,0 +1,80 @@
+//! Writers, and some readers, for the file formats of external proving toolchains
+
+pub mod c;
+pub mod circom;
+pub mod gnark;
+pub mod groth16;
+pub mod halo2;
+pub mod plan;
+pub mod rust;
+pub mod smt;
+pub mod wasm;
+
+use crate::FCMCError;
+use num_bigint::BigUint;
+
+/// Reads the little-endian fields of the binary formats circom and snarkjs share
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    /// File type named in errors
+    format: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], format: &'static str) -> Self {
+        Self { bytes, format }
+    }
+
+    pub(crate) fn invalid(&self, reason: &str) -> FCMCError {
+        FCMCError::BackendError(format!("Invalid {} file: {}", self.format, reason))
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], FCMCError> {
+        if len > self.bytes.len() {
+            return Err(self.invalid("unexpected end of file"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, FCMCError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, FCMCError> {
+        let bytes = self.take(8)?;
+        let mut word = [0; 8];
+        word.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(word))
+    }
+
+    /// A count of items each at least `item_size` bytes long, checked against the bytes
+    /// left so a corrupt header cannot allocate more than the file holds
+    pub(crate) fn count(&self, count: u32, item_size: usize) -> Result<usize, FCMCError> {
+        let count = count as usize;
+        if count.saturating_mul(item_size.max(1)) > self.bytes.len() {
+            return Err(self.invalid("unexpected end of file"));
+        }
+        Ok(count)
+    }
+
+    /// A field element of `size` bytes
+    pub(crate) fn fixed_element(&mut self, size: usize) -> Result<BigUint, FCMCError> {
+        Ok(BigUint::from_bytes_le(self.take(size)?))
+    }
+
+    /// A field element preceded by its size in bytes
+    pub(crate) fn element(&mut self) -> Result<BigUint, FCMCError> {
+        let size = self.u32()? as usize;
+        self.fixed_element(size)
+    }
+
+    /// The next section's kind and a reader over its contents
+    pub(crate) fn section(&mut self) -> Result<(u32, Reader<'a>), FCMCError> {
+        let kind = self.u32()?;
+        let len = usize::try_from(self.u64()?).map_err(|_| self.invalid("section too large"))?;
+        Ok((kind, Reader::new(self.take(len)?, self.format)))
+    }
+}
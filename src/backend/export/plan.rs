@@ -0,0 +1,128 @@
+//! The witness plan shared by the witness-generator code emitters: which IR nodes the
+//! prover evaluates, in what order, and how each wire is computed from them.
+
+use crate::backend::lowering::{FunctionTable, WireSource};
+use crate::backend::CircuitBackend;
+use crate::ir::IRNodeType;
+use crate::optimization::field;
+use crate::optimization::interpreter::extracted_bit;
+use crate::optimization::lookup::function_table;
+use crate::{CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use std::collections::{HashMap, HashSet};
+
+/// How one IR node's value is computed
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeStep {
+    /// The input at this position of `WitnessPlan::inputs`
+    Input(usize),
+    Constant(BigUint),
+    /// A pure operation on the values of earlier nodes, in operand order
+    Operation(IRNodeType, Vec<usize>),
+    /// The row of `WitnessPlan::tables[table]` at the index the operands pack
+    Lookup(usize, Vec<usize>),
+    /// Bit `index` of an earlier node's value
+    Bit(usize, u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct WitnessPlan {
+    /// Name of every input, public and private, in node order
+    pub inputs: Vec<String>,
+    /// Nodes the wires depend on, in evaluation order
+    pub nodes: Vec<(usize, NodeStep)>,
+    pub wires: Vec<WireSource>,
+    pub wire_names: Vec<String>,
+    /// Function tables the lookup steps read
+    pub tables: Vec<FunctionTable>,
+    pub modulus: BigUint,
+}
+
+impl WitnessPlan {
+    pub fn new(compiled: &CompiledCircuit) -> Result<Self, FCMCError> {
+        compiled.in_field(|| Self::build(compiled))
+    }
+
+    fn build(compiled: &CompiledCircuit) -> Result<Self, FCMCError> {
+        let graph = &compiled.ir;
+        let wires = compiled.circuit.wire_sources().to_vec();
+
+        let mut needed = HashSet::new();
+        let mut stack: Vec<usize> = wires
+            .iter()
+            .filter_map(|source| match source {
+                WireSource::Node(node_id) => Some(*node_id),
+                _ => None,
+            })
+            .collect();
+        while let Some(node_id) = stack.pop() {
+            if needed.insert(node_id) {
+                stack.extend(graph.get_predecessors(node_id));
+            }
+        }
+
+        let mut inputs = Vec::new();
+        let mut input_index = HashMap::new();
+        for node in graph.live_nodes() {
+            if let IRNodeType::Input(name) | IRNodeType::PrivateInput(name) = &node.node_type {
+                input_index.insert(node.id, inputs.len());
+                inputs.push(name.clone());
+            }
+        }
+
+        let mut tables: Vec<FunctionTable> = Vec::new();
+        let mut nodes = Vec::new();
+        for node_id in graph.topological_sort() {
+            if !needed.contains(&node_id) {
+                continue;
+            }
+            let node = match graph.get_node(node_id) {
+                Some(node) => node,
+                None => continue,
+            };
+            if let Some(index) = extracted_bit(node) {
+                let operand = graph.get_predecessors(node_id).first().copied().ok_or_else(|| {
+                    FCMCError::BackendError(format!("Bit extraction at node {} has no operand", node_id))
+                })?;
+                nodes.push((node_id, NodeStep::Bit(operand, index)));
+                continue;
+            }
+            let step = match (&node.node_type, function_table(graph, node)) {
+                (IRNodeType::Input(_), _) | (IRNodeType::PrivateInput(_), _) => {
+                    NodeStep::Input(input_index[&node_id])
+                }
+                (IRNodeType::Constant(value), _) => NodeStep::Constant(field::parse_element(value).ok_or_else(|| {
+                    FCMCError::BackendError(format!("Invalid field constant: {}", value))
+                })?),
+                (IRNodeType::Lookup, Some(table)) => {
+                    let table = FunctionTable::from_table(table)?;
+                    let index = match tables.iter().position(|existing| existing.same_rows(&table)) {
+                        Some(index) => index,
+                        None => {
+                            tables.push(table);
+                            tables.len() - 1
+                        }
+                    };
+                    NodeStep::Lookup(index, graph.get_predecessors(node_id))
+                }
+                (IRNodeType::Constraint(_), _) | (IRNodeType::RangeCheck, _) | (IRNodeType::Lookup, None) => {
+                    return Err(FCMCError::BackendError(format!(
+                        "Node {} has no value a wire can read",
+                        node_id
+                    )));
+                }
+                (node_type, _) => NodeStep::Operation(node_type.clone(), graph.get_predecessors(node_id)),
+            };
+            nodes.push((node_id, step));
+        }
+
+        Ok(Self {
+            inputs,
+            nodes,
+            wires,
+            wire_names: compiled.circuit.wire_names().to_vec(),
+            tables,
+            modulus: compiled.field.modulus(),
+        })
+    }
+}
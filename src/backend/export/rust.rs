@@ -0,0 +1,265 @@
+//! Generation of a standalone Rust witness calculator.
+//!
+//! The module depends only on the `ff` crate: `compute_witness` evaluates the IR nodes
+//! the witness reads and then every wire in order, generic over the prover's
+//! `PrimeField`, which must be the field the circuit was compiled over.
+
+use crate::backend::export::plan::{NodeStep, WitnessPlan};
+use crate::backend::lowering::{LinearCombination, WireSource, ONE_WIRE};
+use crate::ir::IRNodeType;
+use crate::{CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Write the witness calculator module for `compiled` to `path`
+pub fn write_witness_module(compiled: &CompiledCircuit, name: &str, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let plan = WitnessPlan::new(compiled)?;
+    std::fs::write(path, witness_module(&plan, name))
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Rust identifier for an input name, unique among `taken`
+fn identifier(name: &str, taken: &mut Vec<String>) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    let base = ident.clone();
+    let mut suffix = 1;
+    while taken.contains(&ident) {
+        ident = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    taken.push(ident.clone());
+    ident
+}
+
+/// Decimal constants the module parses once, by value
+#[derive(Default)]
+struct Constants {
+    values: Vec<BigUint>,
+    index: HashMap<BigUint, usize>,
+}
+
+impl Constants {
+    fn get(&mut self, value: &BigUint) -> String {
+        let values = &mut self.values;
+        let index = *self.index.entry(value.clone()).or_insert_with(|| {
+            values.push(value.clone());
+            values.len() - 1
+        });
+        format!("k[{}]", index)
+    }
+}
+
+fn lc_expression(lc: &LinearCombination, constants: &mut Constants) -> String {
+    let terms: Vec<String> = lc
+        .iter()
+        .map(|(&wire, coefficient)| match (wire, coefficient.is_one()) {
+            (ONE_WIRE, _) => constants.get(coefficient),
+            (_, true) => format!("w[{}]", wire),
+            (_, false) => format!("w[{}] * {}", wire, constants.get(coefficient)),
+        })
+        .collect();
+    if terms.is_empty() {
+        "F::ZERO".to_string()
+    } else {
+        terms.join(" + ")
+    }
+}
+
+fn operation(node_type: &IRNodeType, operands: &[String]) -> String {
+    let arg = |index: usize| operands.get(index).cloned().unwrap_or_else(|| "F::ZERO".to_string());
+    let (a, b) = (arg(0), arg(1));
+    match node_type {
+        IRNodeType::Add => format!("{} + {}", a, b),
+        IRNodeType::Sub => format!("{} - {}", a, b),
+        IRNodeType::Mul | IRNodeType::And => format!("{} * {}", a, b),
+        IRNodeType::Neg => format!("-{}", a),
+        // A zero divisor leaves the circuit's nonzero check unsatisfied
+        IRNodeType::Div => format!("{} * inverse({})", a, b),
+        IRNodeType::Or => format!("{a} + {b} - {a} * {b}", a = a, b = b),
+        IRNodeType::Xor => format!("{a} + {b} - ({a} * {b}).double()", a = a, b = b),
+        IRNodeType::Not => format!("F::ONE - {}", a),
+        IRNodeType::Eq => format!("boolean::<F>({} == {})", a, b),
+        IRNodeType::Ne => format!("boolean::<F>({} != {})", a, b),
+        IRNodeType::Lt => format!("boolean::<F>(less({}, {}))", a, b),
+        IRNodeType::Le => format!("boolean::<F>(!less({}, {}))", b, a),
+        IRNodeType::Gt => format!("boolean::<F>(less({}, {}))", b, a),
+        IRNodeType::Ge => format!("boolean::<F>(!less({}, {}))", a, b),
+        IRNodeType::Select => format!("if bool::from({}.is_zero()) {{ {} }} else {{ {} }}", a, arg(2), b),
+        _ => a,
+    }
+}
+
+fn wire_expression(source: &WireSource, constants: &mut Constants) -> String {
+    match source {
+        WireSource::One => "F::ONE".to_string(),
+        WireSource::Node(node_id) => format!("v{}", node_id),
+        WireSource::Linear(lc) => lc_expression(lc, constants),
+        WireSource::Product(a, b) => format!(
+            "({}) * ({})",
+            lc_expression(a, constants),
+            lc_expression(b, constants)
+        ),
+        WireSource::Inverse(lc) => format!("inverse({})", lc_expression(lc, constants)),
+        WireSource::Bit(lc, index) => format!("bit({}, {})", lc_expression(lc, constants), index),
+        WireSource::Byte(lc, index) => format!("byte({}, {})", lc_expression(lc, constants), index),
+        WireSource::IsZero(lc) => format!("boolean::<F>(bool::from(({}).is_zero()))", lc_expression(lc, constants)),
+    }
+}
+
+/// Source of the generated module
+pub fn witness_module(plan: &WitnessPlan, name: &str) -> String {
+    let mut constants = Constants::default();
+    let mut taken = Vec::new();
+    let fields: Vec<String> = plan.inputs.iter().map(|input| identifier(input, &mut taken)).collect();
+
+    let mut body = String::new();
+    // Writing to a String cannot fail
+    for (node_id, step) in &plan.nodes {
+        let value = match step {
+            NodeStep::Input(index) => format!("inputs.{}", fields[*index]),
+            NodeStep::Constant(value) => constants.get(value),
+            NodeStep::Operation(node_type, operands) => {
+                let operands: Vec<String> = operands.iter().map(|operand| format!("v{}", operand)).collect();
+                operation(node_type, &operands)
+            }
+            NodeStep::Lookup(table, operands) => {
+                let index: Vec<String> = operands
+                    .iter()
+                    .zip(plan.tables[*table].weights())
+                    .map(|(operand, weight)| {
+                        if weight.is_one() {
+                            format!("v{}", operand)
+                        } else {
+                            format!("v{} * {}", operand, constants.get(&weight))
+                        }
+                    })
+                    .collect();
+                let index = if index.is_empty() { "F::ZERO".to_string() } else { index.join(" + ") };
+                format!("lookup(&t{}, {})", table, index)
+            }
+            NodeStep::Bit(operand, index) => format!("bit(v{}, {})", operand, index),
+        };
+        let _ = writeln!(body, "    let v{} = {};", node_id, value);
+    }
+    let _ = writeln!(body, "    let mut w: Vec<F> = Vec::with_capacity(WIRES);");
+    for (wire, source) in plan.wires.iter().enumerate() {
+        let wire_name = plan.wire_names.get(wire).map_or("", String::as_str);
+        let _ = writeln!(body, "    // {}", wire_name);
+        let _ = writeln!(body, "    w.push({});", wire_expression(source, &mut constants));
+    }
+    body.push_str("    w\n");
+
+    let mut out = String::new();
+    let _ = writeln!(out, "//! Witness calculator for `{}`, generated by FCMC. Do not edit.", name);
+    let _ = writeln!(out, "//!");
+    let _ = writeln!(out, "//! `F` must have modulus {}.", plan.modulus);
+    out.push_str("#![allow(dead_code, clippy::all)]\n");
+    out.push_str(PRELUDE);
+
+    let _ = writeln!(out, "\npub const WIRES: usize = {};", plan.wires.len());
+    let _ = writeln!(out, "\nconst CONSTANTS: [&str; {}] = [", constants.values.len());
+    for value in &constants.values {
+        let _ = writeln!(out, "    \"{}\",", value);
+    }
+    out.push_str("];\n");
+    for (index, table) in plan.tables.iter().enumerate() {
+        let _ = writeln!(out, "\n/// Rows of table `{}`", table.name);
+        let _ = writeln!(out, "const TABLE_{}: [&str; {}] = [", index, table.values.len());
+        for value in &table.values {
+            let _ = writeln!(out, "    \"{}\",", value);
+        }
+        out.push_str("];\n");
+    }
+
+    let _ = writeln!(out, "\n/// Every input, public and private");
+    out.push_str("#[derive(Clone, Debug, Default)]\npub struct Inputs<F> {\n");
+    for (field, input) in fields.iter().zip(&plan.inputs) {
+        let _ = writeln!(out, "    /// `{}`", input);
+        let _ = writeln!(out, "    pub {}: F,", field);
+    }
+    if plan.inputs.is_empty() {
+        out.push_str("    pub _field: std::marker::PhantomData<F>,\n");
+    }
+    out.push_str("}\n");
+
+    out.push_str(HELPERS);
+    let _ = writeln!(out, "\n/// The full witness, one value per wire, starting with the constant one");
+    let _ = writeln!(out, "pub fn compute_witness<F: PrimeField>(inputs: &Inputs<F>) -> Vec<F> {{");
+    out.push_str("    let k: Vec<F> = CONSTANTS.iter().map(|c| F::from_str_vartime(c).expect(\"reduced field constant\")).collect();\n");
+    for index in 0..plan.tables.len() {
+        let _ = writeln!(
+            out,
+            "    let t{index}: Vec<F> = TABLE_{index}.iter().map(|c| F::from_str_vartime(c).expect(\"reduced field constant\")).collect();"
+        );
+    }
+    out.push_str(&body);
+    out.push_str("}\n");
+    out
+}
+
+const PRELUDE: &str = r#"
+use ff::PrimeField;
+"#;
+
+const HELPERS: &str = r#"
+/// Canonical value as little-endian bytes, whatever the field's `Repr` order
+fn le_bytes<F: PrimeField>(value: F) -> Vec<u8> {
+    let mut bytes = value.to_repr().as_ref().to_vec();
+    if F::ONE.to_repr().as_ref()[0] != 1 {
+        bytes.reverse();
+    }
+    bytes
+}
+
+fn boolean<F: PrimeField>(value: bool) -> F {
+    if value { F::ONE } else { F::ZERO }
+}
+
+fn inverse<F: PrimeField>(value: F) -> F {
+    Option::from(value.invert()).unwrap_or(F::ZERO)
+}
+
+/// Comparison of canonical integer values
+fn less<F: PrimeField>(a: F, b: F) -> bool {
+    le_bytes(a).iter().rev().lt(le_bytes(b).iter().rev())
+}
+
+fn bit<F: PrimeField>(value: F, index: u32) -> F {
+    let bytes = le_bytes(value);
+    let byte = bytes.get((index / 8) as usize).copied().unwrap_or(0);
+    boolean(byte >> (index % 8) & 1 == 1)
+}
+
+/// Row of `table` at the canonical value of `index`, zero past its end
+fn lookup<F: PrimeField>(table: &[F], index: F) -> F {
+    let bytes = le_bytes(index);
+    if bytes.iter().skip(8).any(|&byte| byte != 0) {
+        return F::ZERO;
+    }
+    let mut word = [0u8; 8];
+    for (slot, byte) in word.iter_mut().zip(&bytes) {
+        *slot = *byte;
+    }
+    usize::try_from(u64::from_le_bytes(word))
+        .ok()
+        .and_then(|row| table.get(row))
+        .copied()
+        .unwrap_or(F::ZERO)
+}
+
+/// Byte `index` of the value as 32 big-endian bytes
+fn byte<F: PrimeField>(value: F, index: u32) -> F {
+    let bytes = le_bytes(value);
+    F::from(bytes.get(31 - index as usize).copied().unwrap_or(0) as u64)
+}
+"#;
@@ -0,0 +1,179 @@
+//! SMT-LIB 2 encoding of a lowered R1CS circuit, for auditors to state and prove
+//! properties of the constraints a prover is actually held to with Z3 or cvc5
+//!
+//! Every wire but the constant one is an integer in `[0, p)`. `|w_I|` holds wire `I`,
+//! `|in_NAME|` and `|out_NAME|` the input and output wires, and `|constraints_hold|`
+//! whether every rank-1 row and native range check holds. An auditor appends the negation
+//! of a property, such as `(assert (and |constraints_hold| (= |out_ok| 1) (not ...)))`,
+//! and `(check-sat)`; `unsat` proves the property for every witness the circuit accepts.
+
+use crate::backend::lowering::{LinearCombination, WireSource, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::ir::IRGraph;
+use crate::language::ast::Type;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::fmt::Write as _;
+
+/// How `smt_source` declares the input wires
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputSorts {
+    /// Every input is an integer in `[0, p)`
+    #[default]
+    Field,
+    /// Boolean and `u32` inputs are bitvectors of their width, read as integers through
+    /// `bv2nat`, so the solver only considers values of the declared type
+    BitVectors,
+}
+
+/// SMT-LIB encoding of `circuit`, without a query; `graph` is the IR it was lowered
+/// from, which gives the declared type of each input
+pub fn smt_source(circuit: &R1CSCircuit, graph: &IRGraph, sorts: InputSorts) -> Result<String, FCMCError> {
+    if !circuit.black_boxes.is_empty() || !circuit.custom_gates.is_empty() || !circuit.table_lookups.is_empty() {
+        return Err(FCMCError::BackendError(
+            "SMT export needs every constraint lowered to rank-1 rows or range checks".to_string(),
+        ));
+    }
+    let p = circuit.field.modulus().to_string();
+    let outputs = 1..1 + circuit.public_outputs;
+    let inputs = outputs.end..outputs.end + circuit.public_inputs + circuit.private_inputs;
+
+    let widths: Vec<Option<u32>> = (0..circuit.wires.len())
+        .map(|wire| {
+            let node_id = match (sorts, &circuit.wires[wire]) {
+                (InputSorts::BitVectors, WireSource::Node(node_id)) if inputs.contains(&wire) => *node_id,
+                _ => return None,
+            };
+            match graph.get_node(node_id).map(|node| &node.data_type) {
+                Some(Type::Bool) => Some(1),
+                Some(Type::U32) => Some(32),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let mut smt = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(
+        smt,
+        "; Constraints of {} wires over the integers modulo {}",
+        circuit.wires.len(),
+        p
+    );
+    let logic = if widths.iter().any(Option::is_some) {
+        "ALL"
+    } else {
+        "QF_NIA"
+    };
+    let _ = writeln!(smt, "(set-logic {})", logic);
+    let _ = writeln!(smt, "(set-option :produce-models true)");
+    for (wire, name) in circuit.wire_names.iter().enumerate() {
+        let symbol = wire_symbol(wire);
+        if wire == ONE_WIRE {
+            let _ = writeln!(smt, "(define-fun {} () Int 1)", symbol);
+            continue;
+        }
+        let _ = writeln!(smt, "; {}", name);
+        match widths[wire] {
+            Some(width) => {
+                let bits = format!("|bv_{}|", wire);
+                let _ = writeln!(smt, "(declare-const {} (_ BitVec {}))", bits, width);
+                let _ = writeln!(smt, "(define-fun {} () Int (bv2nat {}))", symbol, bits);
+            }
+            None => {
+                let _ = writeln!(smt, "(declare-const {} Int)", symbol);
+                let _ = writeln!(smt, "(assert (and (<= 0 {}) (< {} {})))", symbol, symbol, p);
+            }
+        }
+    }
+    for wire in inputs {
+        let _ = writeln!(
+            smt,
+            "(define-fun {} () Int {})",
+            named_symbol("in", &circuit.wire_names[wire]),
+            wire_symbol(wire)
+        );
+    }
+    for wire in outputs {
+        let _ = writeln!(
+            smt,
+            "(define-fun {} () Int {})",
+            named_symbol("out", &circuit.wire_names[wire]),
+            wire_symbol(wire)
+        );
+    }
+
+    let mut holds: Vec<String> = circuit
+        .constraints
+        .iter()
+        .map(|row| {
+            let (a, b, c) = (lc_term(&row.a), lc_term(&row.b), lc_term(&row.c));
+            format!("(= (mod (* {} {}) {}) (mod {} {}))", a, b, p, c, p)
+        })
+        .collect();
+    holds.extend(
+        circuit
+            .ranges
+            .iter()
+            .map(|(value, bits)| format!("(< (mod {} {}) {})", lc_term(value), p, BigUint::from(1u8) << *bits)),
+    );
+    let conjunction = if holds.is_empty() {
+        "true".to_string()
+    } else {
+        format!("(and true {})", holds.join(" "))
+    };
+    let _ = writeln!(smt, "(define-fun |constraints_hold| () Bool {})", conjunction);
+    Ok(smt)
+}
+
+fn wire_symbol(wire: usize) -> String {
+    format!("|w_{}|", wire)
+}
+
+fn named_symbol(prefix: &str, name: &str) -> String {
+    format!("|{}_{}|", prefix, name.replace('|', "_"))
+}
+
+/// Sum of the combination's terms, unreduced
+fn lc_term(lc: &LinearCombination) -> String {
+    let terms: Vec<String> = lc
+        .iter()
+        .map(|(wire, coefficient)| format!("(* {} {})", coefficient, wire_symbol(*wire)))
+        .collect();
+    match terms.len() {
+        0 => "0".to_string(),
+        1 => terms.into_iter().next().unwrap_or_default(),
+        _ => format!("(+ {})", terms.join(" ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::field::FieldConfig;
+    use crate::FCMC;
+
+    #[test]
+    fn encodes_rows_and_named_wires() {
+        let circuit = FCMC::new()
+            .with_field(FieldConfig::Bn254)
+            .compile("fn main(x: field, y: field) -> field { return x * y; }")
+            .unwrap();
+        let smt = circuit.export_smt(InputSorts::Field).unwrap();
+        assert!(smt.contains("(set-logic QF_NIA)"));
+        assert!(smt.contains("(define-fun |w_0| () Int 1)"));
+        assert!(smt.contains("(define-fun |in_x| () Int"));
+        assert!(smt.contains("(define-fun |out_return| () Int"));
+        assert!(smt.contains("(define-fun |constraints_hold| () Bool (and true (= (mod (*"));
+    }
+
+    #[test]
+    fn declares_bounded_inputs_as_bitvectors() {
+        let circuit = FCMC::new()
+            .compile("fn main(a: u32, b: u32) -> u32 { return a + b; }")
+            .unwrap();
+        let smt = circuit.export_smt(InputSorts::BitVectors).unwrap();
+        assert!(smt.contains("(set-logic ALL)"));
+        assert!(smt.contains("(_ BitVec 32)"));
+    }
+}
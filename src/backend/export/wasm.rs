@@ -0,0 +1,949 @@
+//! Generation of a WebAssembly witness calculator with the interface of circom 2's,
+//! so snarkjs and the web provers built on it load FCMC circuits unchanged.
+//!
+//! The module exports circom's functions (`init`, `getInputSignalSize`,
+//! `setInputSignal`, `getWitnessSize`, `getWitness`, the shared read/write memory and the
+//! raw prime). Inputs are found by the 64-bit FNV-1a hash of their name. Field elements
+//! travel through the shared memory as `n32` little-endian 32-bit words; inside the
+//! module they are kept in Montgomery form.
+//!
+//! The witness plan is compiled to a flat program over value slots, which a small
+//! interpreter in the module runs once every input is set.
+
+use crate::backend::export::plan::{NodeStep, WitnessPlan};
+use crate::backend::lowering::{LinearCombination, WireSource, ONE_WIRE};
+use crate::ir::IRNodeType;
+use crate::{CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Write the witness calculator for `compiled` to `path`
+pub fn write_witness_wasm(compiled: &CompiledCircuit, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+    let path = path.as_ref();
+    let plan = WitnessPlan::new(compiled)?;
+    std::fs::write(path, witness_wasm(&plan))
+        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// circom's hash of a signal name, as the `(msb, lsb)` halves snarkjs passes
+pub fn fnv_hash(name: &str) -> (u32, u32) {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for unit in name.encode_utf16() {
+        hash ^= u64::from(unit);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    ((hash >> 32) as u32, hash as u32)
+}
+
+// Program operations: `[op, dst, a, b, c]`, operands are slots unless noted
+const OP_COPY: u32 = 0;
+const OP_ADD: u32 = 1;
+const OP_SUB: u32 = 2;
+const OP_MUL: u32 = 3;
+/// `a^(p-2)`, so 0 for 0
+const OP_INV: u32 = 4;
+/// 1 if `a < b` as canonical integers
+const OP_LT: u32 = 5;
+const OP_EQ: u32 = 6;
+/// `b` unless `a` is 0, then `c`
+const OP_SELECT: u32 = 7;
+/// `(a >> b) & (2^c - 1)` of the canonical integer; `b` and `c` are immediates
+const OP_EXTRACT: u32 = 8;
+/// Slot `b + a` for a canonical `a` below `c`, else 0; `b` and `c` are immediates
+const OP_TABLE: u32 = 9;
+
+/// The witness plan as a program over slots. Slots `0..wires` hold the witness, then
+/// one slot per input.
+struct Program {
+    code: Vec<[u32; 5]>,
+    slots: u32,
+    constants: HashMap<BigUint, u32>,
+    /// First of the consecutive slots holding each table of the plan's rows
+    tables: BTreeMap<usize, u32>,
+    wires: u32,
+}
+
+impl Program {
+    fn compile(plan: &WitnessPlan) -> Self {
+        let mut program = Program {
+            code: Vec::new(),
+            slots: (plan.wires.len() + plan.inputs.len()) as u32,
+            constants: HashMap::new(),
+            tables: BTreeMap::new(),
+            wires: plan.wires.len() as u32,
+        };
+        let zero = program.constant(&BigUint::zero());
+        let one = program.constant(&BigUint::one());
+
+        let mut nodes: HashMap<usize, u32> = HashMap::new();
+        for (node_id, step) in &plan.nodes {
+            let slot = match step {
+                NodeStep::Input(index) => program.wires + *index as u32,
+                NodeStep::Constant(value) => program.constant(value),
+                NodeStep::Operation(node_type, operands) => {
+                    let arg = |index: usize| operands.get(index).and_then(|operand| nodes.get(operand)).copied();
+                    let (a, b) = (arg(0).unwrap_or(zero), arg(1).unwrap_or(zero));
+                    match node_type {
+                        IRNodeType::Add => program.apply(OP_ADD, a, b),
+                        IRNodeType::Sub => program.apply(OP_SUB, a, b),
+                        IRNodeType::Mul | IRNodeType::And => program.apply(OP_MUL, a, b),
+                        IRNodeType::Neg => program.apply(OP_SUB, zero, a),
+                        IRNodeType::Div => {
+                            let inverse = program.apply(OP_INV, b, 0);
+                            program.apply(OP_MUL, a, inverse)
+                        }
+                        // a + b - ab
+                        IRNodeType::Or => {
+                            let sum = program.apply(OP_ADD, a, b);
+                            let product = program.apply(OP_MUL, a, b);
+                            program.apply(OP_SUB, sum, product)
+                        }
+                        // a + b - 2ab
+                        IRNodeType::Xor => {
+                            let sum = program.apply(OP_ADD, a, b);
+                            let product = program.apply(OP_MUL, a, b);
+                            let twice = program.apply(OP_ADD, product, product);
+                            program.apply(OP_SUB, sum, twice)
+                        }
+                        IRNodeType::Not => program.apply(OP_SUB, one, a),
+                        IRNodeType::Eq => program.apply(OP_EQ, a, b),
+                        IRNodeType::Ne => {
+                            let equal = program.apply(OP_EQ, a, b);
+                            program.apply(OP_SUB, one, equal)
+                        }
+                        IRNodeType::Lt => program.apply(OP_LT, a, b),
+                        IRNodeType::Gt => program.apply(OP_LT, b, a),
+                        IRNodeType::Le => {
+                            let greater = program.apply(OP_LT, b, a);
+                            program.apply(OP_SUB, one, greater)
+                        }
+                        IRNodeType::Ge => {
+                            let less = program.apply(OP_LT, a, b);
+                            program.apply(OP_SUB, one, less)
+                        }
+                        IRNodeType::Select => {
+                            let slot = program.slot();
+                            program.code.push([OP_SELECT, slot, a, b, arg(2).unwrap_or(zero)]);
+                            slot
+                        }
+                        // Phi, bit decompositions and outputs pass their operand through
+                        _ => a,
+                    }
+                }
+                NodeStep::Lookup(table, operands) => {
+                    let rows = plan.tables[*table].values.len() as u32;
+                    let start = program.table(*table, rows);
+                    let mut index = zero;
+                    for (operand, weight) in operands.iter().zip(plan.tables[*table].weights()) {
+                        let value = nodes.get(operand).copied().unwrap_or(zero);
+                        let weight = program.constant(&weight);
+                        let term = program.apply(OP_MUL, value, weight);
+                        index = program.apply(OP_ADD, index, term);
+                    }
+                    let slot = program.slot();
+                    program.code.push([OP_TABLE, slot, index, start, rows]);
+                    slot
+                }
+                NodeStep::Bit(operand, index) => {
+                    let slot = program.slot();
+                    let value = nodes.get(operand).copied().unwrap_or(zero);
+                    program.code.push([OP_EXTRACT, slot, value, *index, 1]);
+                    slot
+                }
+            };
+            nodes.insert(*node_id, slot);
+        }
+
+        for (wire, source) in plan.wires.iter().enumerate() {
+            let wire = wire as u32;
+            let (op, a, b, c) = match source {
+                WireSource::One => (OP_COPY, one, 0, 0),
+                WireSource::Node(node_id) => (OP_COPY, nodes.get(node_id).copied().unwrap_or(zero), 0, 0),
+                WireSource::Linear(lc) => (OP_COPY, program.linear(lc, zero), 0, 0),
+                WireSource::Product(a, b) => {
+                    let a = program.linear(a, zero);
+                    (OP_MUL, a, program.linear(b, zero), 0)
+                }
+                WireSource::Inverse(lc) => (OP_INV, program.linear(lc, zero), 0, 0),
+                WireSource::Bit(lc, index) => (OP_EXTRACT, program.linear(lc, zero), *index, 1),
+                WireSource::Byte(lc, index) => (OP_EXTRACT, program.linear(lc, zero), 8 * (31 - *index), 8),
+                WireSource::IsZero(lc) => (OP_EQ, program.linear(lc, zero), zero, 0),
+            };
+            program.code.push([op, wire, a, b, c]);
+        }
+        program
+    }
+
+    fn slot(&mut self) -> u32 {
+        self.slots += 1;
+        self.slots - 1
+    }
+
+    fn constant(&mut self, value: &BigUint) -> u32 {
+        if let Some(&slot) = self.constants.get(value) {
+            return slot;
+        }
+        let slot = self.slot();
+        self.constants.insert(value.clone(), slot);
+        slot
+    }
+
+    /// First of `rows` consecutive slots for table `index` of the plan
+    fn table(&mut self, index: usize, rows: u32) -> u32 {
+        if let Some(&start) = self.tables.get(&index) {
+            return start;
+        }
+        let start = self.slots;
+        self.slots += rows;
+        self.tables.insert(index, start);
+        start
+    }
+
+    fn apply(&mut self, op: u32, a: u32, b: u32) -> u32 {
+        let slot = self.slot();
+        self.code.push([op, slot, a, b, 0]);
+        slot
+    }
+
+    /// Slot holding `lc`; witness wires are read from their own slots
+    fn linear(&mut self, lc: &LinearCombination, zero: u32) -> u32 {
+        let mut acc: Option<u32> = None;
+        for (&wire, coefficient) in lc {
+            let term = if wire == ONE_WIRE {
+                self.constant(coefficient)
+            } else if coefficient.is_one() {
+                wire as u32
+            } else {
+                let coefficient = self.constant(coefficient);
+                self.apply(OP_MUL, wire as u32, coefficient)
+            };
+            acc = Some(match acc {
+                Some(acc) => self.apply(OP_ADD, acc, term),
+                None => term,
+            });
+        }
+        acc.unwrap_or(zero)
+    }
+}
+
+/// Byte offsets of the module's memory
+struct Layout {
+    limbs: u32,
+    shared: u32,
+    prime: u32,
+    r2: u32,
+    exponent: u32,
+    raw_one: u32,
+    n_prime: u32,
+    /// `limbs + 2` words of Montgomery product
+    product: u32,
+    /// Scratch values: power base, power result, two canonical operands
+    scratch: [u32; 4],
+    signals: u32,
+    program: u32,
+    program_end: u32,
+    slots: u32,
+    end: u32,
+}
+
+impl Layout {
+    fn new(limbs: u32, inputs: u32, instructions: u32, slots: u32) -> Self {
+        let value = limbs * 4;
+        let shared = 0;
+        let prime = shared + value;
+        let r2 = prime + value;
+        let exponent = r2 + value;
+        let raw_one = exponent + value;
+        let n_prime = raw_one + value;
+        let product = n_prime + 4;
+        let scratch_start = product + value + 8;
+        let scratch = [
+            scratch_start,
+            scratch_start + value,
+            scratch_start + 2 * value,
+            scratch_start + 3 * value,
+        ];
+        let signals = scratch_start + 4 * value;
+        let program = signals + inputs * 12;
+        let program_end = program + instructions * 20;
+        Self {
+            limbs,
+            shared,
+            prime,
+            r2,
+            exponent,
+            raw_one,
+            n_prime,
+            product,
+            scratch,
+            signals,
+            program,
+            program_end,
+            slots: program_end,
+            end: program_end + slots * value,
+        }
+    }
+
+    fn slot(&self, slot: u32) -> u32 {
+        self.slots + slot * self.limbs * 4
+    }
+}
+
+fn words(value: &BigUint, limbs: u32) -> Vec<u8> {
+    let mut bytes = value.to_bytes_le();
+    bytes.resize(limbs as usize * 4, 0);
+    bytes
+}
+
+/// The witness calculator module
+pub fn witness_wasm(plan: &WitnessPlan) -> Vec<u8> {
+    let program = Program::compile(plan);
+    let p = &plan.modulus;
+    let limbs = (p.bits() as u32).div_ceil(64) * 2;
+    let layout = Layout::new(limbs, plan.inputs.len() as u32, program.code.len() as u32, program.slots);
+
+    let r = (BigUint::one() << (32 * limbs)) % p;
+    let r2 = (&r * &r) % p;
+    // -p^-1 mod 2^32 by Newton iteration
+    let p0 = (p % (1u64 << 32)).to_u64().unwrap_or(1) as u32;
+    let mut inverse: u32 = 1;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(p0.wrapping_mul(inverse)));
+    }
+    let n_prime = inverse.wrapping_neg();
+
+    let mut header = Vec::new();
+    header.resize(layout.prime as usize, 0);
+    header.extend(words(p, limbs));
+    header.extend(words(&r2, limbs));
+    header.extend(words(&(p - BigUint::from(2u32)), limbs));
+    header.extend(words(&BigUint::one(), limbs));
+    header.extend(n_prime.to_le_bytes());
+    header.resize(layout.signals as usize, 0);
+    for (index, input) in plan.inputs.iter().enumerate() {
+        let (msb, lsb) = fnv_hash(input);
+        header.extend(msb.to_le_bytes());
+        header.extend(lsb.to_le_bytes());
+        header.extend((program.wires + index as u32).to_le_bytes());
+    }
+    for instruction in &program.code {
+        for word in instruction {
+            header.extend(word.to_le_bytes());
+        }
+    }
+
+    let mut segments = vec![(0u32, header)];
+    let mut constants: Vec<(&BigUint, &u32)> = program.constants.iter().collect();
+    constants.sort_by_key(|(_, slot)| **slot);
+    for (value, slot) in constants {
+        let montgomery = (value * &r) % p;
+        if !montgomery.is_zero() {
+            segments.push((layout.slot(*slot), words(&montgomery, limbs)));
+        }
+    }
+    for (&table, &start) in &program.tables {
+        let rows: Vec<u8> = plan.tables[table]
+            .values
+            .iter()
+            .flat_map(|value| words(&((value * &r) % p), limbs))
+            .collect();
+        segments.push((layout.slot(start), rows));
+    }
+
+    let functions = functions(&layout, &program, plan.inputs.len() as u32);
+    module(&functions, layout.end.div_ceil(65536).max(1), &segments)
+}
+
+// Value types and instruction opcodes
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+
+/// Instruction sequence of one function body
+#[derive(Default)]
+struct Asm {
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn op(&mut self, byte: u8) -> &mut Self {
+        self.code.push(byte);
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        leb_u32(&mut self.code, value);
+        self
+    }
+
+    fn i32_const(&mut self, value: i32) -> &mut Self {
+        self.op(0x41);
+        leb_i64(&mut self.code, value as i64);
+        self
+    }
+
+    fn u32_const(&mut self, value: u32) -> &mut Self {
+        self.i32_const(value as i32)
+    }
+
+    fn i64_const(&mut self, value: i64) -> &mut Self {
+        self.op(0x42);
+        leb_i64(&mut self.code, value);
+        self
+    }
+
+    fn get(&mut self, local: u32) -> &mut Self {
+        self.op(0x20).u32(local)
+    }
+
+    fn set(&mut self, local: u32) -> &mut Self {
+        self.op(0x21).u32(local)
+    }
+
+    fn global_get(&mut self, global: u32) -> &mut Self {
+        self.op(0x23).u32(global)
+    }
+
+    fn global_set(&mut self, global: u32) -> &mut Self {
+        self.op(0x24).u32(global)
+    }
+
+    fn call(&mut self, function: u32) -> &mut Self {
+        self.op(0x10).u32(function)
+    }
+
+    fn block(&mut self) -> &mut Self {
+        self.op(0x02).op(0x40)
+    }
+
+    fn loop_(&mut self) -> &mut Self {
+        self.op(0x03).op(0x40)
+    }
+
+    fn if_(&mut self) -> &mut Self {
+        self.op(0x04).op(0x40)
+    }
+
+    fn else_(&mut self) -> &mut Self {
+        self.op(0x05)
+    }
+
+    fn end(&mut self) -> &mut Self {
+        self.op(0x0b)
+    }
+
+    fn br(&mut self, depth: u32) -> &mut Self {
+        self.op(0x0c).u32(depth)
+    }
+
+    fn br_if(&mut self, depth: u32) -> &mut Self {
+        self.op(0x0d).u32(depth)
+    }
+
+    fn ret(&mut self) -> &mut Self {
+        self.op(0x0f)
+    }
+
+    fn load(&mut self) -> &mut Self {
+        self.op(0x28).u32(2).u32(0)
+    }
+
+    fn load64(&mut self) -> &mut Self {
+        self.op(0x35).u32(2).u32(0)
+    }
+
+    fn store(&mut self) -> &mut Self {
+        self.op(0x36).u32(2).u32(0)
+    }
+
+    fn store64(&mut self) -> &mut Self {
+        self.op(0x3e).u32(2).u32(0)
+    }
+
+    /// Address of limb `index` of the value at the address in local `base`
+    fn limb(&mut self, base: u32, index: u32) -> &mut Self {
+        self.get(base).get(index).i32_const(2).op(0x74).op(0x6a)
+    }
+
+    /// Address of limb `index` of the value at the fixed address `base`
+    fn fixed_limb(&mut self, base: u32, index: u32) -> &mut Self {
+        self.get(index).i32_const(2).op(0x74).u32_const(base).op(0x6a)
+    }
+
+    /// `for (index = start; index < end; index++) body`
+    fn for_range(&mut self, index: u32, start: u32, end: u32, body: impl FnOnce(&mut Asm)) -> &mut Self {
+        self.u32_const(start).set(index);
+        self.block().loop_();
+        self.get(index).u32_const(end).op(0x4f).br_if(1);
+        body(self);
+        self.get(index).i32_const(1).op(0x6a).set(index);
+        self.br(0).end().end()
+    }
+}
+
+fn leb_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn leb_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+struct Function {
+    params: Vec<u8>,
+    results: Vec<u8>,
+    locals: Vec<u8>,
+    body: Asm,
+    export: Option<&'static str>,
+}
+
+fn function(params: &[u8], results: &[u8], locals: &[u8], export: Option<&'static str>) -> Function {
+    Function {
+        params: params.to_vec(),
+        results: results.to_vec(),
+        locals: locals.to_vec(),
+        body: Asm::default(),
+        export,
+    }
+}
+
+// Internal functions, by index
+const COPY: u32 = 0;
+const ADD_RAW: u32 = 1;
+const SUB_RAW: u32 = 2;
+const LESS: u32 = 3;
+const EQUAL: u32 = 4;
+const IS_ZERO: u32 = 5;
+const ADD: u32 = 6;
+const SUB: u32 = 7;
+const MUL: u32 = 8;
+const POW: u32 = 9;
+const EXTRACT: u32 = 10;
+const RUN: u32 = 11;
+
+/// Global counting the inputs set since `init`
+const INPUTS_SET: u32 = 0;
+
+fn functions(layout: &Layout, program: &Program, inputs: u32) -> Vec<Function> {
+    let n = layout.limbs;
+    let value = n * 4;
+    let [base, power, left, right] = layout.scratch;
+    let one_slot = layout.slot(program.constants[&BigUint::one()]);
+    let zero_slot = layout.slot(program.constants[&BigUint::zero()]);
+    let mut functions = Vec::new();
+
+    // copy(dst, src)
+    let mut f = function(&[I32, I32], &[], &[I32], None);
+    f.body.for_range(2, 0, n, |a| {
+        a.limb(0, 2).limb(1, 2).load().store();
+    });
+    f.body.end();
+    functions.push(f);
+
+    // add_raw(dst, a, b) -> carry
+    let mut f = function(&[I32, I32, I32], &[I32], &[I32, I64, I64], None);
+    f.body.i64_const(0).set(4);
+    f.body.for_range(3, 0, n, |a| {
+        a.limb(1, 3).load64().limb(2, 3).load64().op(0x7c).get(4).op(0x7c).set(5);
+        a.limb(0, 3).get(5).store64();
+        a.get(5).i64_const(32).op(0x88).set(4);
+    });
+    f.body.get(4).op(0xa7).end();
+    functions.push(f);
+
+    // sub_raw(dst, a, b) -> borrow
+    let mut f = function(&[I32, I32, I32], &[I32], &[I32, I64, I64], None);
+    f.body.i64_const(0).set(4);
+    f.body.for_range(3, 0, n, |a| {
+        a.limb(1, 3).load64().limb(2, 3).load64().op(0x7d).get(4).op(0x7d).set(5);
+        a.limb(0, 3).get(5).store64();
+        a.get(5).i64_const(63).op(0x88).set(4);
+    });
+    f.body.get(4).op(0xa7).end();
+    functions.push(f);
+
+    // less(a, b): compare limbs from the most significant
+    let mut f = function(&[I32, I32], &[I32], &[I32, I32, I32], None);
+    f.body.u32_const(n).set(2);
+    f.body.block().loop_();
+    f.body.get(2).op(0x45).br_if(1);
+    f.body.get(2).i32_const(1).op(0x6b).set(2);
+    f.body.limb(0, 2).load().set(3);
+    f.body.limb(1, 2).load().set(4);
+    f.body.get(3).get(4).op(0x49).if_().i32_const(1).ret().end();
+    f.body.get(3).get(4).op(0x4b).if_().i32_const(0).ret().end();
+    f.body.br(0).end().end();
+    f.body.i32_const(0).end();
+    functions.push(f);
+
+    // equal(a, b)
+    let mut f = function(&[I32, I32], &[I32], &[I32], None);
+    f.body.for_range(2, 0, n, |a| {
+        a.limb(0, 2).load().limb(1, 2).load().op(0x47).if_().i32_const(0).ret().end();
+    });
+    f.body.i32_const(1).end();
+    functions.push(f);
+
+    // is_zero(a)
+    let mut f = function(&[I32], &[I32], &[I32], None);
+    f.body.for_range(1, 0, n, |a| {
+        a.limb(0, 1).load().if_().i32_const(0).ret().end();
+    });
+    f.body.i32_const(1).end();
+    functions.push(f);
+
+    // add(dst, a, b) mod p
+    let mut f = function(&[I32, I32, I32], &[], &[], None);
+    f.body.get(0).get(1).get(2).call(ADD_RAW);
+    f.body.get(0).u32_const(layout.prime).call(LESS).op(0x45).op(0x72);
+    f.body.if_().get(0).get(0).u32_const(layout.prime).call(SUB_RAW).op(0x1a).end();
+    f.body.end();
+    functions.push(f);
+
+    // sub(dst, a, b) mod p
+    let mut f = function(&[I32, I32, I32], &[], &[], None);
+    f.body.get(0).get(1).get(2).call(SUB_RAW);
+    f.body.if_().get(0).get(0).u32_const(layout.prime).call(ADD_RAW).op(0x1a).end();
+    f.body.end();
+    functions.push(f);
+
+    // mul(dst, a, b): Montgomery product, coarsely integrated operand scanning
+    // locals: 3 i, 4 j, 5 carry, 6 m, 7 b_i, 8 sum
+    let t = layout.product;
+    let mut f = function(&[I32, I32, I32], &[], &[I32, I32, I64, I64, I64, I64], None);
+    f.body.for_range(4, 0, n + 2, |a| {
+        a.fixed_limb(t, 4).i32_const(0).store();
+    });
+    f.body.for_range(3, 0, n, |a| {
+        a.limb(2, 3).load64().set(7);
+        a.i64_const(0).set(5);
+        a.for_range(4, 0, n, |a| {
+            a.fixed_limb(t, 4).load64().limb(1, 4).load64().get(7).op(0x7e).op(0x7c).get(5).op(0x7c).set(8);
+            a.fixed_limb(t, 4).get(8).store64();
+            a.get(8).i64_const(32).op(0x88).set(5);
+        });
+        a.u32_const(t + value).load64().get(5).op(0x7c).set(8);
+        a.u32_const(t + value).get(8).store64();
+        a.u32_const(t + value + 4).get(8).i64_const(32).op(0x88).store64();
+        a.u32_const(t).load64().u32_const(layout.n_prime).load64().op(0x7e).i64_const(0xffff_ffff).op(0x83).set(6);
+        a.u32_const(t).load64().get(6).u32_const(layout.prime).load64().op(0x7e).op(0x7c);
+        a.i64_const(32).op(0x88).set(5);
+        a.for_range(4, 1, n, |a| {
+            a.fixed_limb(t, 4).load64().get(6).fixed_limb(layout.prime, 4).load64().op(0x7e).op(0x7c);
+            a.get(5).op(0x7c).set(8);
+            a.fixed_limb(t - 4, 4).get(8).store64();
+            a.get(8).i64_const(32).op(0x88).set(5);
+        });
+        a.u32_const(t + value).load64().get(5).op(0x7c).set(8);
+        a.u32_const(t + value - 4).get(8).store64();
+        a.u32_const(t + value).u32_const(t + value + 4).load64().get(8).i64_const(32).op(0x88).op(0x7c).store64();
+    });
+    f.body.u32_const(t + value).load().u32_const(t).u32_const(layout.prime).call(LESS).op(0x45).op(0x72);
+    f.body.if_().u32_const(t).u32_const(t).u32_const(layout.prime).call(SUB_RAW).op(0x1a).end();
+    f.body.get(0).u32_const(t).call(COPY);
+    f.body.end();
+    functions.push(f);
+
+    // pow(dst, a) = a^(p-2); locals: 2 bit
+    let mut f = function(&[I32, I32], &[], &[I32], None);
+    f.body.u32_const(base).get(1).call(COPY);
+    f.body.u32_const(power).u32_const(one_slot).call(COPY);
+    f.body.u32_const(n * 32).set(2);
+    f.body.block().loop_();
+    f.body.get(2).op(0x45).br_if(1);
+    f.body.get(2).i32_const(1).op(0x6b).set(2);
+    f.body.u32_const(power).u32_const(power).u32_const(power).call(MUL);
+    f.body.get(2).i32_const(5).op(0x76).i32_const(2).op(0x74).u32_const(layout.exponent).op(0x6a).load();
+    f.body.get(2).i32_const(31).op(0x71).op(0x76).i32_const(1).op(0x71);
+    f.body.if_().u32_const(power).u32_const(power).u32_const(base).call(MUL).end();
+    f.body.br(0).end().end();
+    f.body.get(0).u32_const(power).call(COPY);
+    f.body.end();
+    functions.push(f);
+
+    // extract(dst, a, shift, width); locals: 4 word, 5 bits
+    let mut f = function(&[I32, I32, I32, I32], &[], &[I32, I64], None);
+    f.body.u32_const(left).get(1).u32_const(layout.raw_one).call(MUL);
+    f.body.get(2).i32_const(5).op(0x76).set(4);
+    f.body.i64_const(0).set(5);
+    f.body.get(4).u32_const(n).op(0x49).if_();
+    f.body.fixed_limb(left, 4).load64().set(5);
+    f.body.end();
+    f.body.get(4).i32_const(1).op(0x6a).u32_const(n).op(0x49).if_();
+    f.body.get(5).fixed_limb(left + 4, 4).load64().i64_const(32).op(0x86).op(0x84).set(5);
+    f.body.end();
+    f.body.get(5).get(2).i32_const(31).op(0x71).op(0xad).op(0x88);
+    f.body.i64_const(1).get(3).op(0xad).op(0x86).i64_const(1).op(0x7d).op(0x83).set(5);
+    f.body.for_range(4, 0, n, |a| {
+        a.fixed_limb(right, 4).i32_const(0).store();
+    });
+    f.body.u32_const(right).get(5).store64();
+    f.body.get(0).u32_const(right).u32_const(layout.r2).call(MUL);
+    f.body.end();
+    functions.push(f);
+
+    // run(): interpret the program; locals: 0 pc, 1 op, 2 dst, 3 a, 4 b, 5 c, 6 row
+    let slot = |a: &mut Asm, local: u32| {
+        a.get(local).u32_const(value).op(0x6c).u32_const(layout.slots).op(0x6a);
+    };
+    let mut f = function(&[], &[], &[I32, I32, I32, I32, I32, I32, I32], None);
+    let a = &mut f.body;
+    a.u32_const(layout.program).set(0);
+    a.block().loop_();
+    a.get(0).u32_const(layout.program_end).op(0x4f).br_if(1);
+    a.get(0).load().set(1);
+    for (local, offset) in [(2u32, 4i32), (3, 8), (4, 12), (5, 16)] {
+        a.get(0).i32_const(offset).op(0x6a).load().set(local);
+    }
+    let dispatch = |a: &mut Asm, op: u32, body: &dyn Fn(&mut Asm)| {
+        a.get(1).u32_const(op).op(0x46).if_();
+        body(a);
+        a.end();
+    };
+    dispatch(a, OP_COPY, &|a| {
+        slot(a, 2);
+        slot(a, 3);
+        a.call(COPY);
+    });
+    for (op, function) in [(OP_ADD, ADD), (OP_SUB, SUB), (OP_MUL, MUL)] {
+        dispatch(a, op, &|a| {
+            slot(a, 2);
+            slot(a, 3);
+            slot(a, 4);
+            a.call(function);
+        });
+    }
+    dispatch(a, OP_INV, &|a| {
+        slot(a, 2);
+        slot(a, 3);
+        a.call(POW);
+    });
+    dispatch(a, OP_LT, &|a| {
+        a.u32_const(left);
+        slot(a, 3);
+        a.u32_const(layout.raw_one).call(MUL);
+        a.u32_const(right);
+        slot(a, 4);
+        a.u32_const(layout.raw_one).call(MUL);
+        slot(a, 2);
+        a.u32_const(one_slot).u32_const(zero_slot);
+        a.u32_const(left).u32_const(right).call(LESS).op(0x1b).call(COPY);
+    });
+    dispatch(a, OP_EQ, &|a| {
+        slot(a, 2);
+        a.u32_const(one_slot).u32_const(zero_slot);
+        slot(a, 3);
+        slot(a, 4);
+        a.call(EQUAL).op(0x1b).call(COPY);
+    });
+    dispatch(a, OP_SELECT, &|a| {
+        slot(a, 2);
+        slot(a, 5);
+        slot(a, 4);
+        slot(a, 3);
+        a.call(IS_ZERO).op(0x1b).call(COPY);
+    });
+    dispatch(a, OP_EXTRACT, &|a| {
+        slot(a, 2);
+        slot(a, 3);
+        a.get(4).get(5).call(EXTRACT);
+    });
+    // A row past the table's end, including any canonical value above 32 bits, reads 0
+    dispatch(a, OP_TABLE, &|a| {
+        a.u32_const(left);
+        slot(a, 3);
+        a.u32_const(layout.raw_one).call(MUL);
+        a.u32_const(left).load().set(6);
+        for limb in 1..n {
+            a.u32_const(left + 4 * limb).load().if_().get(5).set(6).end();
+        }
+        slot(a, 2);
+        a.get(4).get(6).op(0x6a).u32_const(value).op(0x6c).u32_const(layout.slots).op(0x6a);
+        a.u32_const(zero_slot);
+        a.get(6).get(5).op(0x49).op(0x1b).call(COPY);
+    });
+    a.get(0).i32_const(20).op(0x6a).set(0);
+    a.br(0).end().end();
+    a.end();
+    functions.push(f);
+
+    // Exports, in circom's interface
+    let constant = |name: &'static str, value: u32| {
+        let mut f = function(&[], &[I32], &[], Some(name));
+        f.body.u32_const(value).end();
+        f
+    };
+    functions.push(constant("getVersion", 2));
+    functions.push(constant("getMinorVersion", 0));
+    functions.push(constant("getPatchVersion", 0));
+    functions.push(constant("getSharedRWMemoryStart", layout.shared));
+    functions.push(constant("getFieldNumLen32", n));
+    functions.push(constant("getWitnessSize", program.wires));
+    functions.push(constant("getInputSize", inputs));
+    functions.push(constant("getMessageChar", 0));
+
+    let mut f = function(&[I32], &[I32], &[], Some("readSharedRWMemory"));
+    f.body.get(0).i32_const(2).op(0x74).u32_const(layout.shared).op(0x6a).load().end();
+    functions.push(f);
+
+    let mut f = function(&[I32, I32], &[], &[], Some("writeSharedRWMemory"));
+    f.body.get(0).i32_const(2).op(0x74).u32_const(layout.shared).op(0x6a).get(1).store().end();
+    functions.push(f);
+
+    let mut f = function(&[I32], &[], &[], Some("init"));
+    f.body.i32_const(0).global_set(INPUTS_SET);
+    if inputs == 0 {
+        f.body.call(RUN);
+    }
+    f.body.end();
+    functions.push(f);
+
+    // Signal table entry of a hash, left in local 2, or -1; locals: 2 entry
+    let find = |a: &mut Asm| {
+        a.i32_const(-1).set(2);
+        a.block();
+        a.for_range(3, 0, inputs, |a| {
+            a.get(3).i32_const(12).op(0x6c).u32_const(layout.signals).op(0x6a).set(4);
+            a.get(4).load().get(0).op(0x46);
+            a.get(4).i32_const(4).op(0x6a).load().get(1).op(0x46).op(0x71);
+            a.if_().get(4).set(2).br(3).end();
+        });
+        a.end();
+    };
+    let mut f = function(&[I32, I32], &[I32], &[I32, I32, I32], Some("getInputSignalSize"));
+    find(&mut f.body);
+    f.body.get(2).i32_const(-1).op(0x46).if_().i32_const(-1).ret().end();
+    f.body.i32_const(1).end();
+    functions.push(f);
+
+    let mut f = function(&[I32, I32, I32], &[], &[I32, I32, I32], Some("setInputSignal"));
+    // The position takes the place of the entry local's slot in `find`
+    f.body.get(2).set(5);
+    find(&mut f.body);
+    f.body.get(2).i32_const(-1).op(0x46).if_().ret().end();
+    f.body.get(2).i32_const(8).op(0x6a).load().get(5).op(0x6a).u32_const(value).op(0x6c);
+    f.body.u32_const(layout.slots).op(0x6a);
+    f.body.u32_const(layout.shared).u32_const(layout.r2).call(MUL);
+    f.body.global_get(INPUTS_SET).i32_const(1).op(0x6a).global_set(INPUTS_SET);
+    f.body.global_get(INPUTS_SET).u32_const(inputs).op(0x46).if_().call(RUN).end();
+    f.body.end();
+    functions.push(f);
+
+    let mut f = function(&[], &[], &[], Some("getRawPrime"));
+    f.body.u32_const(layout.shared).u32_const(layout.prime).call(COPY).end();
+    functions.push(f);
+
+    let mut f = function(&[I32], &[], &[], Some("getWitness"));
+    f.body.u32_const(layout.shared);
+    f.body.get(0).u32_const(value).op(0x6c).u32_const(layout.slots).op(0x6a);
+    f.body.u32_const(layout.raw_one).call(MUL).end();
+    functions.push(f);
+
+    functions
+}
+
+fn section(out: &mut Vec<u8>, id: u8, content: Vec<u8>) {
+    out.push(id);
+    leb_u32(out, content.len() as u32);
+    out.extend(content);
+}
+
+fn vector(out: &mut Vec<u8>, items: &[u8]) {
+    leb_u32(out, items.len() as u32);
+    out.extend_from_slice(items);
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    vector(out, name.as_bytes());
+}
+
+fn module(functions: &[Function], pages: u32, segments: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut out = b"\0asm".to_vec();
+    out.extend(1u32.to_le_bytes());
+
+    // One type per function keeps the encoding simple
+    let mut types = Vec::new();
+    leb_u32(&mut types, functions.len() as u32);
+    for function in functions {
+        types.push(0x60);
+        vector(&mut types, &function.params);
+        vector(&mut types, &function.results);
+    }
+    section(&mut out, 1, types);
+
+    let mut declarations = Vec::new();
+    leb_u32(&mut declarations, functions.len() as u32);
+    for index in 0..functions.len() {
+        leb_u32(&mut declarations, index as u32);
+    }
+    section(&mut out, 3, declarations);
+
+    let mut memory = vec![1, 0x00];
+    leb_u32(&mut memory, pages);
+    section(&mut out, 5, memory);
+
+    let mut globals = vec![1, I32, 0x01];
+    globals.extend([0x41, 0x00, 0x0b]);
+    section(&mut out, 6, globals);
+
+    let exported: Vec<(usize, &str)> = functions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, function)| function.export.map(|export| (index, export)))
+        .collect();
+    let mut exports = Vec::new();
+    leb_u32(&mut exports, exported.len() as u32 + 1);
+    name(&mut exports, "memory");
+    exports.extend([0x02, 0x00]);
+    for (index, export) in exported {
+        name(&mut exports, export);
+        exports.push(0x00);
+        leb_u32(&mut exports, index as u32);
+    }
+    section(&mut out, 7, exports);
+
+    let mut code = Vec::new();
+    leb_u32(&mut code, functions.len() as u32);
+    for function in functions {
+        let mut body = Vec::new();
+        leb_u32(&mut body, function.locals.len() as u32);
+        for &local in &function.locals {
+            body.extend([1, local]);
+        }
+        body.extend(&function.body.code);
+        leb_u32(&mut code, body.len() as u32);
+        code.extend(body);
+    }
+    section(&mut out, 10, code);
+
+    let mut data = Vec::new();
+    leb_u32(&mut data, segments.len() as u32);
+    for (offset, bytes) in segments {
+        data.push(0x00);
+        data.push(0x41);
+        leb_i64(&mut data, *offset as i64);
+        data.push(0x0b);
+        vector(&mut data, bytes);
+    }
+    section(&mut out, 11, data);
+
+    out
+}
@@ -0,0 +1,145 @@
+//! Soundness report for the values the prover computes outside the constraints.
+//!
+//! Quotients, inverses, bits, bytes and zero tests are hints: the witness program fills
+//! them in, and only the constraints that read them stop a malicious prover from putting
+//! anything else there. The report lists every hint with the rows and native checks that
+//! read it; a hint nothing reads is completely unconstrained.
+
+use crate::backend::lowering::WireSource;
+use crate::backend::r1cs::R1CSCircuit;
+use crate::ir::{IRGraph, IRNodeType};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintKind {
+    /// Quotient of a division by a variable
+    Quotient,
+    Inverse,
+    Bit,
+    Byte,
+    IsZero,
+}
+
+impl HintKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HintKind::Quotient => "quotient",
+            HintKind::Inverse => "inverse",
+            HintKind::Bit => "bit",
+            HintKind::Byte => "byte",
+            HintKind::IsZero => "zero test",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub wire: usize,
+    pub name: String,
+    pub kind: HintKind,
+    /// Constraint rows reading the wire
+    pub rows: Vec<usize>,
+    /// Range checks, black-box calls, custom gates and lookups reading the wire
+    pub native_uses: usize,
+}
+
+impl Hint {
+    pub fn is_constrained(&self) -> bool {
+        !self.rows.is_empty() || self.native_uses > 0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HintReport {
+    pub hints: Vec<Hint>,
+}
+
+impl HintReport {
+    pub fn unconstrained(&self) -> impl Iterator<Item = &Hint> {
+        self.hints.iter().filter(|hint| !hint.is_constrained())
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.hints
+            .iter()
+            .map(|hint| {
+                if hint.is_constrained() {
+                    format!(
+                        "{} wire {} ({}) is read by {} row(s) and {} native check(s)",
+                        hint.kind.as_str(),
+                        hint.wire,
+                        hint.name,
+                        hint.rows.len(),
+                        hint.native_uses
+                    )
+                } else {
+                    format!(
+                        "{} wire {} ({}) is not constrained",
+                        hint.kind.as_str(),
+                        hint.wire,
+                        hint.name
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// The hints of `circuit`, lowered from `graph`, and what reads each
+pub fn hint_report(circuit: &R1CSCircuit, graph: &IRGraph) -> HintReport {
+    let mut rows: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (row, constraint) in circuit.constraints.iter().enumerate() {
+        let mut wires: Vec<usize> = constraint
+            .a
+            .keys()
+            .chain(constraint.b.keys())
+            .chain(constraint.c.keys())
+            .copied()
+            .collect();
+        wires.sort_unstable();
+        wires.dedup();
+        for wire in wires {
+            rows.entry(wire).or_default().push(row);
+        }
+    }
+    let mut native_uses: HashMap<usize, usize> = HashMap::new();
+    let native_inputs = circuit
+        .ranges
+        .iter()
+        .map(|(value, _)| value)
+        .chain(circuit.black_boxes.iter().flat_map(|call| &call.inputs))
+        .chain(circuit.custom_gates.iter().flat_map(|call| &call.inputs))
+        .chain(circuit.table_lookups.iter().flat_map(|call| &call.inputs));
+    for lc in native_inputs {
+        for wire in lc.keys() {
+            *native_uses.entry(*wire).or_default() += 1;
+        }
+    }
+
+    let hints = circuit
+        .wires
+        .iter()
+        .enumerate()
+        .filter_map(|(wire, source)| {
+            let kind = match source {
+                WireSource::Node(node_id) => match graph.get_node(*node_id).map(|node| &node.node_type) {
+                    Some(IRNodeType::Div) => HintKind::Quotient,
+                    _ => return None,
+                },
+                WireSource::Inverse(_) => HintKind::Inverse,
+                WireSource::Bit(..) => HintKind::Bit,
+                WireSource::Byte(..) => HintKind::Byte,
+                WireSource::IsZero(_) => HintKind::IsZero,
+                _ => return None,
+            };
+            Some(Hint {
+                wire,
+                name: circuit.wire_names.get(wire).cloned().unwrap_or_default(),
+                kind,
+                rows: rows.remove(&wire).unwrap_or_default(),
+                native_uses: native_uses.get(&wire).copied().unwrap_or(0),
+            })
+        })
+        .collect();
+    HintReport { hints }
+}
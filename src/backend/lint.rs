@@ -0,0 +1,296 @@
+//! Structural lints for constraint patterns that are a common source of soundness bugs.
+//!
+//! Each lint looks at the shape of the circuit only, without solving anything, so a
+//! finding is a pattern worth a second look rather than a proven bug: a comparison whose
+//! operands are not range checked, a `Select` condition not proven boolean, a public input
+//! bound equal to another one, and a public output no constraint reads.
+
+use crate::backend::lowering::{lc_constant_value, lc_scale, lc_sub, LinearCombination, ONE_WIRE};
+use crate::backend::r1cs::{max_comparison_bits, R1CSCircuit, DEFAULT_COMPARISON_BITS};
+use crate::ir::{IRGraph, IRNodeType, SourceSpan};
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::field;
+use crate::optimization::interval::IntervalAnalysis;
+use num_traits::Zero;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Wasteful or surprising, but sound
+    Warning,
+    /// Lets a prover satisfy the constraints with a wrong result
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A comparison operand may be wider than the bits the comparison decomposes
+    UncheckedComparison,
+    /// A `Select` condition may be something other than 0 or 1
+    NonBooleanSelector,
+    /// Two public inputs are constrained to be equal
+    AliasedPublicInput,
+    /// A public output is read by no constraint
+    UnconstrainedOutput,
+}
+
+impl Lint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Lint::UncheckedComparison => "unchecked comparison",
+            Lint::NonBooleanSelector => "non-boolean selector",
+            Lint::AliasedPublicInput => "aliased public input",
+            Lint::UnconstrainedOutput => "unconstrained output",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Lint::AliasedPublicInput => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Why the pattern is a problem and how it is usually fixed
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Lint::UncheckedComparison => {
+                "A comparison decomposes the difference of its operands into a fixed number of bits; \
+                 operands wider than that wrap around the field, and the prover can make the comparison \
+                 come out either way. Range check both operands first."
+            }
+            Lint::NonBooleanSelector => {
+                "A select computes b + c * (a - b), which picks a or b only when c is 0 or 1; any other \
+                 condition value gives the prover a free output. Constrain the condition to be boolean."
+            }
+            Lint::AliasedPublicInput => {
+                "The constraints force two public inputs to be equal, so the verifier supplies the same \
+                 value twice and a proof for any other pair fails. Use one input instead."
+            }
+            Lint::UnconstrainedOutput => {
+                "No constraint reads the output wire, so the prover can claim any output value. Make \
+                 sure the output is bound to the value it is computed from."
+            }
+        }
+    }
+
+    /// The usual fix, the last sentence of the explanation
+    pub fn fix(&self) -> &'static str {
+        match self {
+            Lint::UncheckedComparison => "Range check both operands first.",
+            Lint::NonBooleanSelector => "Constrain the condition to be boolean.",
+            Lint::AliasedPublicInput => "Use one input instead.",
+            Lint::UnconstrainedOutput => "Make sure the output is bound to the value it is computed from.",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    pub lint: Lint,
+    /// IR node the finding is about, for the lints on the IR
+    pub node: Option<usize>,
+    /// R1CS wires the finding is about, for the lints on the constraint system
+    pub wires: Vec<usize>,
+    pub span: Option<SourceSpan>,
+    pub message: String,
+}
+
+impl LintFinding {
+    pub fn severity(&self) -> Severity {
+        self.lint.severity()
+    }
+
+    pub fn describe(&self) -> String {
+        let location = self.span.map(|span| format!(" at {}", span)).unwrap_or_default();
+        format!(
+            "{} [{}]{}: {}",
+            self.severity().as_str(),
+            self.lint.as_str(),
+            location,
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintReport {
+    pub findings: Vec<LintFinding>,
+}
+
+impl LintReport {
+    pub fn errors(&self) -> impl Iterator<Item = &LintFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == Severity::Error)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.findings.iter().map(LintFinding::describe).collect()
+    }
+}
+
+/// Lint `circuit`, lowered from `graph`; must run in the circuit's field
+pub fn lint(circuit: &R1CSCircuit, graph: &IRGraph) -> LintReport {
+    let mut findings = lint_graph(graph);
+    findings.extend(aliased_public_inputs(circuit));
+    findings.extend(unconstrained_outputs(circuit));
+    findings.sort_by_key(|finding| (std::cmp::Reverse(finding.severity()), finding.span, finding.node));
+    LintReport { findings }
+}
+
+fn lint_graph(graph: &IRGraph) -> Vec<LintFinding> {
+    let booleans = BooleanityAnalysis::run(graph);
+    let intervals = IntervalAnalysis::run(graph, &booleans);
+    let mut findings = Vec::new();
+    for node in graph.live_nodes() {
+        let operands = graph.get_predecessors(node.id);
+        let unchecked: Vec<usize> = match (&node.node_type, operands.as_slice()) {
+            (IRNodeType::Lt | IRNodeType::Le | IRNodeType::Gt | IRNodeType::Ge, [a, b]) => {
+                let bits = node
+                    .attributes
+                    .get("bits")
+                    .and_then(|bits| bits.parse().ok())
+                    .unwrap_or_else(|| DEFAULT_COMPARISON_BITS.min(max_comparison_bits()));
+                [*a, *b]
+                    .into_iter()
+                    .filter(|operand| !intervals.range(*operand).fits_in_bits(bits))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        if !unchecked.is_empty() {
+            let operands: Vec<String> = unchecked.iter().map(|operand| operand.to_string()).collect();
+            findings.push(LintFinding {
+                lint: Lint::UncheckedComparison,
+                node: Some(node.id),
+                wires: Vec::new(),
+                span: graph.span(node.id),
+                message: format!(
+                    "comparison node {} reads node(s) {} with no range check that fits its width",
+                    node.id,
+                    operands.join(", ")
+                ),
+            });
+        }
+        if let (IRNodeType::Select, [condition, ..]) = (&node.node_type, operands.as_slice()) {
+            if !booleans.is_boolean(*condition) && !intervals.range(*condition).fits_in_bits(1) {
+                findings.push(LintFinding {
+                    lint: Lint::NonBooleanSelector,
+                    node: Some(node.id),
+                    wires: Vec::new(),
+                    span: graph.span(node.id),
+                    message: format!(
+                        "condition node {} of select node {} is not proven 0 or 1",
+                        condition, node.id
+                    ),
+                });
+            }
+        }
+    }
+    findings
+}
+
+fn aliased_public_inputs(circuit: &R1CSCircuit) -> Vec<LintFinding> {
+    let first = 1 + circuit.public_outputs;
+    let inputs = first..first + circuit.public_inputs;
+    let mut seen = HashSet::new();
+    let mut findings = Vec::new();
+    for (row, constraint) in circuit.constraints.iter().enumerate() {
+        // A linear row k * x - c = 0 with one side of the product constant
+        let linear = match (lc_constant_value(&constraint.a), lc_constant_value(&constraint.b)) {
+            (Some(k), _) => lc_sub(&lc_scale(&constraint.b, &k), &constraint.c),
+            (_, Some(k)) => lc_sub(&lc_scale(&constraint.a, &k), &constraint.c),
+            _ => continue,
+        };
+        let (x, y) = match equated_pair(&linear) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if !inputs.contains(&x) || !inputs.contains(&y) || !seen.insert((x, y)) {
+            continue;
+        }
+        let name = |wire: usize| circuit.wire_names.get(wire).cloned().unwrap_or_default();
+        findings.push(LintFinding {
+            lint: Lint::AliasedPublicInput,
+            node: None,
+            wires: vec![x, y],
+            span: None,
+            message: format!(
+                "row {} forces public inputs {} and {} (wires {} and {}) to be equal",
+                row,
+                name(x),
+                name(y),
+                x,
+                y
+            ),
+        });
+    }
+    findings
+}
+
+/// The two wires of `k * x - k * y`, if that is what `linear` is
+fn equated_pair(linear: &LinearCombination) -> Option<(usize, usize)> {
+    if linear.len() != 2 || linear.contains_key(&ONE_WIRE) {
+        return None;
+    }
+    let mut terms = linear.iter();
+    let (&x, kx) = terms.next()?;
+    let (&y, ky) = terms.next()?;
+    field::add(kx, ky).is_zero().then_some((x, y))
+}
+
+fn unconstrained_outputs(circuit: &R1CSCircuit) -> Vec<LintFinding> {
+    let mut read: HashSet<usize> = HashSet::new();
+    for constraint in &circuit.constraints {
+        read.extend(
+            constraint
+                .a
+                .keys()
+                .chain(constraint.b.keys())
+                .chain(constraint.c.keys()),
+        );
+    }
+    let native_inputs = circuit
+        .ranges
+        .iter()
+        .map(|(value, _)| value)
+        .chain(circuit.black_boxes.iter().flat_map(|call| &call.inputs))
+        .chain(circuit.custom_gates.iter().flat_map(|call| &call.inputs))
+        .chain(circuit.table_lookups.iter().flat_map(|call| &call.inputs));
+    for lc in native_inputs {
+        read.extend(lc.keys());
+    }
+    // A native call's output is bound by the call itself
+    read.extend(circuit.black_boxes.iter().map(|call| call.output));
+    read.extend(circuit.custom_gates.iter().map(|call| call.output));
+    read.extend(circuit.table_lookups.iter().map(|call| call.output));
+
+    (1..=circuit.public_outputs)
+        .filter(|wire| !read.contains(wire))
+        .map(|wire| LintFinding {
+            lint: Lint::UnconstrainedOutput,
+            node: None,
+            wires: vec![wire],
+            span: None,
+            message: format!(
+                "public output {} (wire {}) is not read by any constraint",
+                circuit.wire_names.get(wire).cloned().unwrap_or_default(),
+                wire
+            ),
+        })
+        .collect()
+}
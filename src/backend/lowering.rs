@@ -0,0 +1,309 @@
+//! Pieces shared by every backend's lowering: linear combinations over wires, and the
+//! recipe the prover follows to compute each wire of the witness.
+
+use crate::backend::custom_gate::CustomGate;
+use crate::ir::{IRGraph, LookupTable};
+use crate::optimization::field;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+
+/// Wire 0 always carries the constant 1
+pub const ONE_WIRE: usize = 0;
+
+/// Names the black-box function (e.g. `sha256`) a gadget's root node computes
+pub const BLACKBOX_ATTRIBUTE: &str = "blackbox";
+
+/// Comma-separated node ids of a black-box gadget's inputs, in argument order
+pub const BLACKBOX_INPUTS_ATTRIBUTE: &str = "blackbox.inputs";
+
+/// Black-box function of the stdlib's Poseidon hash gadget
+pub const POSEIDON_BLACK_BOX: &str = "poseidon2";
+
+/// Black-box function of the stdlib's Rescue-Prime hash gadget
+pub const RESCUE_BLACK_BOX: &str = "rescue";
+
+/// Names the custom gate (e.g. `mul_add`) a pattern's root node is computed with
+pub const CUSTOM_GATE_ATTRIBUTE: &str = "custom_gate";
+
+/// Comma-separated node ids of a custom gate's inputs, in argument order
+pub const CUSTOM_GATE_INPUTS_ATTRIBUTE: &str = "custom_gate.inputs";
+
+/// Wire -> nonzero coefficient
+pub type LinearCombination = BTreeMap<usize, BigUint>;
+
+/// Wires a piece of a circuit lowered on its own allocates are numbered from here, until
+/// the piece is appended to the circuit and they are moved after its wires
+pub const FRESH_WIRE: usize = usize::MAX / 2;
+
+/// `wire`, moved to `first` onwards if it is a fresh wire
+pub fn rebase_wire(wire: usize, first: usize) -> usize {
+    if wire >= FRESH_WIRE {
+        wire - FRESH_WIRE + first
+    } else {
+        wire
+    }
+}
+
+/// `lc` with its fresh wires moved to `first` onwards. The order of the terms is kept,
+/// since every fresh wire lands after every other one.
+pub fn lc_rebase(lc: &LinearCombination, first: usize) -> LinearCombination {
+    lc.iter()
+        .map(|(&wire, coefficient)| (rebase_wire(wire, first), coefficient.clone()))
+        .collect()
+}
+
+pub fn lc_constant(value: BigUint) -> LinearCombination {
+    lc_scaled(ONE_WIRE, value)
+}
+
+pub fn lc_wire(wire: usize) -> LinearCombination {
+    lc_scaled(wire, BigUint::one())
+}
+
+fn lc_scaled(wire: usize, coefficient: BigUint) -> LinearCombination {
+    let mut lc = LinearCombination::new();
+    if !coefficient.is_zero() {
+        lc.insert(wire, coefficient);
+    }
+    lc
+}
+
+/// `a + factor * b`
+pub fn lc_add_scaled(a: &LinearCombination, b: &LinearCombination, factor: &BigUint) -> LinearCombination {
+    let mut sum = a.clone();
+    for (&wire, coefficient) in b {
+        let entry = sum.entry(wire).or_insert_with(BigUint::zero);
+        *entry = field::add(entry, &field::mul(coefficient, factor));
+        if entry.is_zero() {
+            sum.remove(&wire);
+        }
+    }
+    sum
+}
+
+pub fn lc_add(a: &LinearCombination, b: &LinearCombination) -> LinearCombination {
+    lc_add_scaled(a, b, &BigUint::one())
+}
+
+pub fn lc_sub(a: &LinearCombination, b: &LinearCombination) -> LinearCombination {
+    lc_add_scaled(a, b, &field::neg(&BigUint::one()))
+}
+
+pub fn lc_scale(a: &LinearCombination, factor: &BigUint) -> LinearCombination {
+    lc_add_scaled(&LinearCombination::new(), a, factor)
+}
+
+/// The value of a combination that reads no wire but the constant one
+pub fn lc_constant_value(lc: &LinearCombination) -> Option<BigUint> {
+    match lc.keys().next() {
+        None => Some(BigUint::zero()),
+        Some(&ONE_WIRE) if lc.len() == 1 => Some(lc[&ONE_WIRE].clone()),
+        _ => None,
+    }
+}
+
+pub fn lc_evaluate(lc: &LinearCombination, witness: &[BigUint]) -> BigUint {
+    lc.iter().fold(BigUint::zero(), |sum, (&wire, coefficient)| {
+        let value = witness.get(wire).cloned().unwrap_or_else(BigUint::zero);
+        field::add(&sum, &field::mul(coefficient, &value))
+    })
+}
+
+/// `{"<wire>": "<coefficient>"}`, coefficients in decimal
+pub fn lc_json(lc: &LinearCombination) -> Value {
+    let terms: serde_json::Map<String, Value> = lc
+        .iter()
+        .map(|(wire, coefficient)| (wire.to_string(), json!(coefficient.to_string())))
+        .collect();
+    Value::Object(terms)
+}
+
+/// How the prover computes one wire from the IR values and the wires before it
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireSource {
+    One,
+    /// Value of an IR node
+    Node(usize),
+    Linear(LinearCombination),
+    Product(LinearCombination, LinearCombination),
+    /// Inverse of the combination, or 0 if it is 0
+    Inverse(LinearCombination),
+    /// Bit `index` of the combination's canonical integer value
+    Bit(LinearCombination, u32),
+    /// Byte `index` of the combination's value as 32 big-endian bytes
+    Byte(LinearCombination, u32),
+    /// 1 if the combination is 0, else 0
+    IsZero(LinearCombination),
+}
+
+impl WireSource {
+    pub fn value(&self, values: &HashMap<usize, BigUint>, witness: &[BigUint]) -> Result<BigUint, FCMCError> {
+        Ok(match self {
+            WireSource::One => BigUint::one(),
+            WireSource::Node(node_id) => values.get(node_id).cloned().ok_or_else(|| {
+                FCMCError::BackendError(format!("No value for IR node {} in the witness", node_id))
+            })?,
+            WireSource::Linear(lc) => lc_evaluate(lc, witness),
+            WireSource::Product(a, b) => field::mul(&lc_evaluate(a, witness), &lc_evaluate(b, witness)),
+            WireSource::Inverse(lc) => field::inverse(&lc_evaluate(lc, witness)).unwrap_or_else(BigUint::zero),
+            WireSource::Bit(lc, index) => (lc_evaluate(lc, witness) >> *index) & BigUint::one(),
+            WireSource::Byte(lc, index) => (lc_evaluate(lc, witness) >> (8 * (31 - *index))) & BigUint::from(0xffu32),
+            WireSource::IsZero(lc) => {
+                if lc_evaluate(lc, witness).is_zero() {
+                    BigUint::one()
+                } else {
+                    BigUint::zero()
+                }
+            }
+        })
+    }
+
+    /// The source with the fresh wires it reads moved to `first` onwards
+    pub fn rebase(&self, first: usize) -> WireSource {
+        self.map_lc(|lc| lc_rebase(lc, first))
+    }
+
+    /// The source with each combination it reads replaced by `f` of it
+    pub fn map_lc(&self, f: impl Fn(&LinearCombination) -> LinearCombination) -> WireSource {
+        match self {
+            WireSource::One => WireSource::One,
+            WireSource::Node(node_id) => WireSource::Node(*node_id),
+            WireSource::Linear(lc) => WireSource::Linear(f(lc)),
+            WireSource::Product(a, b) => WireSource::Product(f(a), f(b)),
+            WireSource::Inverse(lc) => WireSource::Inverse(f(lc)),
+            WireSource::Bit(lc, index) => WireSource::Bit(f(lc), *index),
+            WireSource::Byte(lc, index) => WireSource::Byte(f(lc), *index),
+            WireSource::IsZero(lc) => WireSource::IsZero(f(lc)),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            WireSource::One => json!({ "one": null }),
+            WireSource::Node(node_id) => json!({ "node": node_id }),
+            WireSource::Linear(lc) => json!({ "linear": lc_json(lc) }),
+            WireSource::Product(a, b) => json!({ "product": [lc_json(a), lc_json(b)] }),
+            WireSource::Inverse(lc) => json!({ "inverse": lc_json(lc) }),
+            WireSource::Bit(lc, index) => json!({ "bit": [lc_json(lc), index] }),
+            WireSource::Byte(lc, index) => json!({ "byte": [lc_json(lc), index] }),
+            WireSource::IsZero(lc) => json!({ "is_zero": lc_json(lc) }),
+        }
+    }
+}
+
+/// A gadget the backend hands to the prover as one native call instead of lowering it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlackBoxCall {
+    pub function: String,
+    pub inputs: Vec<LinearCombination>,
+    /// Wire holding the root node's value
+    pub output: usize,
+}
+
+/// A pattern the backend computes with one custom gate instead of lowering it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomGateCall {
+    pub gate: CustomGate,
+    pub inputs: Vec<LinearCombination>,
+    /// Wire holding the root node's value
+    pub output: usize,
+}
+
+/// A function table with its rows parsed, as backends with lookup arguments load it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionTable {
+    pub name: String,
+    /// Bit width of each input, in operand order
+    pub input_bits: Vec<u32>,
+    /// Value of every row, by packed index
+    pub values: Vec<BigUint>,
+}
+
+impl FunctionTable {
+    pub fn from_table(table: &LookupTable) -> Result<Self, FCMCError> {
+        let values = table
+            .outputs
+            .iter()
+            .map(|output| {
+                field::parse_element(output).ok_or_else(|| {
+                    FCMCError::BackendError(format!("Invalid value {} in table '{}'", output, table.name))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name: table.name.clone(),
+            input_bits: table.input_bits.clone(),
+            values,
+        })
+    }
+
+    /// Weight of each input in the packed row index, the first input lowest
+    pub fn weights(&self) -> Vec<BigUint> {
+        let mut offset = 0;
+        self.input_bits
+            .iter()
+            .map(|&bits| {
+                let weight = BigUint::one() << offset;
+                offset += bits;
+                weight
+            })
+            .collect()
+    }
+
+    /// Tables with the same inputs and rows are interchangeable whatever their names
+    pub fn same_rows(&self, other: &FunctionTable) -> bool {
+        self.input_bits == other.input_bits && self.values == other.values
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "input_bits": self.input_bits,
+            "values": self.values.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A lookup the backend proves against a function table instead of interpolating it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableLookupCall {
+    /// Index of the table among the circuit's function tables
+    pub table: usize,
+    pub inputs: Vec<LinearCombination>,
+    /// Wire holding the looked-up value
+    pub output: usize,
+}
+
+/// Function name and input nodes of a black-box gadget root
+pub fn black_box(graph: &IRGraph, node_id: usize) -> Option<(String, Vec<usize>)> {
+    let node = graph.get_node(node_id)?;
+    let function = node.attributes.get(BLACKBOX_ATTRIBUTE)?.clone();
+    Some((function, node_list(node.attributes.get(BLACKBOX_INPUTS_ATTRIBUTE))))
+}
+
+/// Gate and input nodes of a custom gate root
+pub fn custom_gate(graph: &IRGraph, node_id: usize) -> Option<(CustomGate, Vec<usize>)> {
+    let node = graph.get_node(node_id)?;
+    let gate = CustomGate::from_name(node.attributes.get(CUSTOM_GATE_ATTRIBUTE)?)?;
+    let inputs = node_list(node.attributes.get(CUSTOM_GATE_INPUTS_ATTRIBUTE));
+    (inputs.len() == gate.arity()).then_some((gate, inputs))
+}
+
+fn node_list(attribute: Option<&String>) -> Vec<usize> {
+    attribute
+        .map(|inputs| inputs.split(',').filter_map(|id| id.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Compute every wire in order; each source only reads wires allocated before it
+pub fn assign_wires(sources: &[WireSource], values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+    let mut witness = Vec::with_capacity(sources.len());
+    for source in sources {
+        let value = source.value(values, &witness)?;
+        witness.push(value);
+    }
+    Ok(witness)
+}
@@ -0,0 +1,263 @@
+//! Backends: lowering of the optimized IR to the constraint systems of proof systems
+
+pub mod acir;
+pub mod air;
+#[cfg(feature = "arkworks")]
+pub mod arkworks;
+#[cfg(feature = "bellman-circuit")]
+pub mod bellman_circuit;
+pub mod capabilities;
+pub mod ccs;
+pub mod custom_gate;
+pub mod export;
+pub mod hints;
+pub mod lint;
+pub mod lowering;
+pub mod partition;
+pub mod plonk;
+pub mod plonky2;
+pub mod public_inputs;
+pub mod r1cs;
+pub mod registry;
+pub mod sanity;
+pub mod sink;
+pub mod size_estimate;
+pub mod tags;
+pub mod target_circuit;
+pub mod underconstrained;
+pub mod wire_minimization;
+pub mod witness_stats;
+
+pub use acir::AcirCircuit;
+pub use air::{AirCircuit, AirExpression};
+pub use capabilities::BackendCapabilities;
+pub use ccs::{CcsCircuit, IvcSteps};
+pub use custom_gate::CustomGate;
+pub use hints::{hint_report, Hint, HintKind, HintReport};
+pub use lint::{lint, Lint, LintFinding, LintReport, Severity};
+pub use partition::{PartitionedCircuit, SharedValue};
+pub use plonk::{PlonkCircuit, PlonkGate, WitnessLayout};
+pub use plonky2::Plonky2Circuit;
+pub use public_inputs::{PackedInputs, PublicLayout};
+pub use r1cs::{R1CSCircuit, R1CSConstraint};
+pub use registry::{register, BackendFactory};
+pub use sanity::{SanityCheck, SanityReport};
+pub use sink::CircuitSink;
+pub use size_estimate::{estimate, estimate_in, SizeEstimate};
+pub use tags::{ConstraintTag, TagQuery};
+pub use target_circuit::TargetCircuit;
+pub use underconstrained::{find_underconstrained, Underconstraint, UnderconstrainedWire, UnderconstraintReport};
+pub use wire_minimization::{minimize_wires, WireMinimization};
+pub use witness_stats::{witness_stats, VariableWires, WitnessStats, DEFAULT_WITNESS_SAMPLES};
+
+use crate::backend::lowering::{WireSource, BLACKBOX_ATTRIBUTE, BLACKBOX_INPUTS_ATTRIBUTE};
+use crate::ir::IRGraph;
+use crate::optimization::field::FieldConfig;
+use crate::optimization::LookupLowering;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TargetSystem {
+    R1CS,
+    Plonk,
+    Halo2,
+    AIR,
+    /// Noir's intermediate representation, proved by Barretenberg
+    ACIR,
+    /// Plonky2 over the 64-bit Goldilocks field
+    Plonky2,
+    /// Customizable constraint system, for folding schemes
+    CCS,
+    /// A backend added at run time with `register`, by name
+    Custom(String),
+}
+
+impl TargetSystem {
+    /// Field circuits for the target are defined over unless another is configured
+    pub fn default_field(&self) -> FieldConfig {
+        self.capabilities().native_field.unwrap_or(FieldConfig::Bls12_381)
+    }
+
+    /// Whether the target's prover can work over `field`
+    pub fn supports_field(&self, field: &FieldConfig) -> bool {
+        self.capabilities().supports_field(field)
+    }
+
+    pub fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities::for_target(self.clone())
+    }
+}
+
+/// A built-in target's name in lower case, or the name of a registered backend
+impl FromStr for TargetSystem {
+    type Err = FCMCError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "r1cs" => Ok(TargetSystem::R1CS),
+            "plonk" => Ok(TargetSystem::Plonk),
+            "halo2" => Ok(TargetSystem::Halo2),
+            "air" => Ok(TargetSystem::AIR),
+            "acir" => Ok(TargetSystem::ACIR),
+            "plonky2" => Ok(TargetSystem::Plonky2),
+            "ccs" => Ok(TargetSystem::CCS),
+            _ if registry::factory(name).is_some() => Ok(TargetSystem::Custom(name.to_string())),
+            _ => Err(FCMCError::BackendError(format!("unknown target '{}'", name))),
+        }
+    }
+}
+
+/// A circuit compiled for one proof system
+pub trait CircuitBackend: std::fmt::Debug + Send + Sync {
+    fn target(&self) -> TargetSystem;
+
+    /// What the proof system checks natively; the target's unless the backend says otherwise
+    fn capabilities(&self) -> BackendCapabilities {
+        self.target().capabilities()
+    }
+
+    /// Rows of the constraint system: R1CS constraints, or gates
+    fn constraint_count(&self) -> usize;
+
+    /// Witness values the prover supplies, including the constant one
+    fn wire_count(&self) -> usize;
+
+    /// How the prover computes each witness value, in witness order
+    fn wire_sources(&self) -> &[WireSource];
+
+    fn wire_names(&self) -> &[String];
+
+    /// Full witness from the values of every IR node
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError>;
+
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool;
+
+    /// First constraint row `witness` violates, with the values it reads by name; `None`
+    /// if every row holds or the backend cannot tell which one fails
+    fn unsatisfied_row(&self, _witness: &[BigUint]) -> Option<(usize, Vec<(String, BigUint)>)> {
+        None
+    }
+
+    /// Tag of each constraint row, in row order, `None` for rows without one; empty if the
+    /// backend keeps no tags
+    fn constraint_tags(&self) -> Vec<Option<&ConstraintTag>> {
+        Vec::new()
+    }
+
+    /// Rows whose tag matches `query`
+    fn constraints_tagged(&self, query: &TagQuery) -> Vec<usize> {
+        self.constraint_tags()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, tag)| tag.map_or(false, |tag| query.matches(tag)))
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    fn to_json(&self) -> Value;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Rewrite IR operations `capabilities` cannot express natively into ones it can, ahead
+/// of lowering. Returns the number of nodes rewritten.
+pub fn legalize(ir: &mut IRGraph, capabilities: &BackendCapabilities) -> Result<usize, FCMCError> {
+    let fallbacks = unmark_black_boxes(ir, capabilities);
+    if capabilities.function_tables {
+        return Ok(fallbacks);
+    }
+    Ok(fallbacks + LookupLowering::default().run(ir)?)
+}
+
+/// Drop the black-box marks of gadgets the target has no native function for, so their
+/// arithmetic expansion is lowered instead. Returns the number of gadgets unmarked.
+fn unmark_black_boxes(ir: &mut IRGraph, capabilities: &BackendCapabilities) -> usize {
+    let unsupported: Vec<usize> = ir
+        .live_nodes()
+        .filter(|node| match node.attributes.get(BLACKBOX_ATTRIBUTE) {
+            Some(function) => !capabilities.supports_black_box(function),
+            None => false,
+        })
+        .map(|node| node.id)
+        .collect();
+    for &node_id in &unsupported {
+        if let Some(node) = ir.get_node_mut(node_id) {
+            if let Some(function) = node.attributes.remove(BLACKBOX_ATTRIBUTE) {
+                tracing::debug!("Lowering black-box '{}' gadget at node {} to arithmetic", function, node_id);
+            }
+            node.attributes.remove(BLACKBOX_INPUTS_ATTRIBUTE);
+        }
+    }
+    unsupported.len()
+}
+
+/// `ir` must already be legalized for the target: witness wires name IR nodes, so the
+/// circuit has to be compiled from the same graph the prover evaluates
+///
+/// The lowering runs in the target's default field.
+pub fn compile_to_target(ir: &IRGraph, target: TargetSystem) -> Result<TargetCircuit, FCMCError> {
+    let field = target.default_field();
+    compile_to_target_in(ir, target, &field)
+}
+
+/// Lower `ir` for `target` over `field`
+pub fn compile_to_target_in(
+    ir: &IRGraph,
+    target: TargetSystem,
+    field: &FieldConfig,
+) -> Result<TargetCircuit, FCMCError> {
+    if !target.supports_field(field) {
+        return Err(FCMCError::BackendError(format!(
+            "{:?} circuits cannot be defined over the {} field",
+            target, field
+        )));
+    }
+    field.enter(|| compile_in_field(ir, target))
+}
+
+/// Lower `ir` for `target`, handing each constraint to `sink` as it is produced instead of
+/// collecting them; returns the number of constraints. Only R1CS streams: the other
+/// targets rework the whole constraint set after lowering it.
+pub fn compile_to_sink(ir: &IRGraph, target: TargetSystem, sink: &mut dyn CircuitSink) -> Result<usize, FCMCError> {
+    let field = target.default_field();
+    compile_to_sink_in(ir, target, &field, sink)
+}
+
+/// Stream `ir` lowered for `target` over `field` into `sink`
+pub fn compile_to_sink_in(
+    ir: &IRGraph,
+    target: TargetSystem,
+    field: &FieldConfig,
+    sink: &mut dyn CircuitSink,
+) -> Result<usize, FCMCError> {
+    if target != TargetSystem::R1CS {
+        return Err(FCMCError::BackendError(format!(
+            "{:?} circuits cannot be streamed; compile them with compile_to_target",
+            target
+        )));
+    }
+    if !target.supports_field(field) {
+        return Err(FCMCError::BackendError(format!(
+            "{:?} circuits cannot be defined over the {} field",
+            target, field
+        )));
+    }
+    field.enter(|| R1CSCircuit::stream(ir, sink))
+}
+
+fn compile_in_field(ir: &IRGraph, target: TargetSystem) -> Result<TargetCircuit, FCMCError> {
+    Ok(match target {
+        TargetSystem::R1CS => TargetCircuit::R1CS(R1CSCircuit::compile(ir)?),
+        TargetSystem::Plonk | TargetSystem::Halo2 => TargetCircuit::Plonk(PlonkCircuit::compile(ir, target)?),
+        TargetSystem::AIR => TargetCircuit::AIR(AirCircuit::compile(ir)?),
+        TargetSystem::ACIR => TargetCircuit::ACIR(AcirCircuit::compile(ir)?),
+        TargetSystem::Plonky2 => TargetCircuit::Plonky2(Plonky2Circuit::compile(ir)?),
+        TargetSystem::CCS => TargetCircuit::CCS(CcsCircuit::compile(ir)?),
+        TargetSystem::Custom(name) => TargetCircuit::Custom(registry::compile(&name, ir)?),
+    })
+}
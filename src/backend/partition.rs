@@ -0,0 +1,399 @@
+//! Splitting a circuit too large for one prover into several circuits proved separately.
+//!
+//! Nodes are first cut into `K` contiguous runs of the topological order with balanced
+//! estimated cost, then moved one at a time to a neighbouring partition while that lowers
+//! the number of values crossing a boundary and keeps the partition within its share.
+//! Constants and public inputs are copied into every partition that reads them. Any other
+//! value read outside its partition is a shared value: its partition exposes it as the
+//! public output `shared.k` and every reader takes it as the public input `shared.k`. A
+//! commit-and-prove wrapper replaces these with commitments and checks that the
+//! commitments of each shared value agree, which is what ties the proofs together.
+
+use crate::backend::lowering::{
+    BLACKBOX_ATTRIBUTE, BLACKBOX_INPUTS_ATTRIBUTE, CUSTOM_GATE_ATTRIBUTE, CUSTOM_GATE_INPUTS_ATTRIBUTE,
+};
+use crate::backend::{compile_to_target_in, CircuitBackend, TargetCircuit, TargetSystem};
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::optimization::cost::CostModel;
+use crate::optimization::field::FieldConfig;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+
+/// How far above an even share of the cost a partition may grow during refinement
+const BALANCE_TOLERANCE: f64 = 0.1;
+
+/// Refinement sweeps over the graph; each stops early once no move helps
+const MAX_REFINEMENT_PASSES: usize = 8;
+
+/// A value computed in one partition and read in others
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedValue {
+    /// Public output of the producer and public input of every consumer
+    pub name: String,
+    /// Node of the unpartitioned graph holding the value
+    pub node: usize,
+    pub producer: usize,
+    pub consumers: Vec<usize>,
+}
+
+/// One of the circuits a partitioned circuit is proved as
+#[derive(Debug)]
+pub struct CircuitPartition {
+    pub graph: IRGraph,
+    /// Id in the unpartitioned graph of each node of `graph`
+    pub origin: Vec<usize>,
+    pub circuit: TargetCircuit,
+}
+
+#[derive(Debug)]
+pub struct PartitionedCircuit {
+    pub partitions: Vec<CircuitPartition>,
+    pub shared: Vec<SharedValue>,
+}
+
+impl PartitionedCircuit {
+    /// Split `graph`, already legalized for `target`, into `parts` circuits over `field`
+    pub fn split(graph: &IRGraph, parts: usize, target: TargetSystem, field: &FieldConfig) -> Result<Self, FCMCError> {
+        let cost = CostModel::for_target(target.clone());
+        let order: Vec<usize> = graph
+            .topological_sort()
+            .into_iter()
+            .filter(|&id| movable(graph, id))
+            .collect();
+        if parts == 0 || parts > order.len() {
+            return Err(FCMCError::BackendError(format!(
+                "cannot split a circuit of {} nodes into {} partitions",
+                order.len(),
+                parts
+            )));
+        }
+
+        let mut assignment = Assignment::new(graph, &order, parts, &cost);
+        assignment.refine(&order);
+        let shared = assignment.shared_values(&order);
+
+        let partitions = (0..parts)
+            .map(|part| {
+                let (partition_graph, origin) = extract_partition(graph, &assignment.part, part, &order, &shared);
+                let circuit = compile_to_target_in(&partition_graph, target.clone(), field)?;
+                Ok(CircuitPartition {
+                    graph: partition_graph,
+                    origin,
+                    circuit,
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        Ok(Self { partitions, shared })
+    }
+
+    /// Witness of partition `index` from the values of the unpartitioned graph's nodes
+    pub fn witness(&self, index: usize, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        let partition = self
+            .partitions
+            .get(index)
+            .ok_or_else(|| FCMCError::BackendError(format!("no partition {}", index)))?;
+        let local = partition
+            .origin
+            .iter()
+            .enumerate()
+            .filter_map(|(id, origin)| values.get(origin).map(|value| (id, value.clone())))
+            .collect();
+        partition.circuit.assign(&local)
+    }
+
+    pub fn constraint_counts(&self) -> Vec<usize> {
+        self.partitions
+            .iter()
+            .map(|partition| partition.circuit.constraint_count())
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "partitions": self.partitions.iter().map(|partition| json!({
+                "constraints": partition.circuit.constraint_count(),
+                "wires": partition.circuit.wire_count(),
+            })).collect::<Vec<_>>(),
+            "shared": self.shared.iter().map(|value| json!({
+                "name": value.name,
+                "node": value.node,
+                "producer": value.producer,
+                "consumers": value.consumers,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Whether `node_id` is placed in one partition; constants and public inputs are copied
+/// into each that reads them
+fn movable(graph: &IRGraph, node_id: usize) -> bool {
+    !matches!(
+        graph.get_node(node_id).map(|node| &node.node_type),
+        Some(IRNodeType::Constant(_)) | Some(IRNodeType::Input(_)) | None
+    )
+}
+
+/// Estimated cost of the rows `node_id` lowers to; at least one, so every node counts
+/// towards the balance
+fn node_cost(graph: &IRGraph, node_id: usize, cost: &CostModel) -> u64 {
+    let estimate = match graph.get_node(node_id).map(|node| &node.node_type) {
+        Some(IRNodeType::Mul) => cost.mul,
+        Some(IRNodeType::Div) => cost.div,
+        Some(IRNodeType::Add) | Some(IRNodeType::Sub) | Some(IRNodeType::Neg) => cost.add,
+        _ => 1,
+    };
+    u64::from(estimate.max(1))
+}
+
+struct Assignment {
+    operands: HashMap<usize, Vec<usize>>,
+    readers: HashMap<usize, Vec<usize>>,
+    /// Partition of each movable node, by node id
+    part: HashMap<usize, usize>,
+    cost: HashMap<usize, u64>,
+    load: Vec<u64>,
+    limit: u64,
+}
+
+impl Assignment {
+    /// Contiguous runs of `order` of about equal cost
+    fn new(graph: &IRGraph, order: &[usize], parts: usize, model: &CostModel) -> Self {
+        let mut operands: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut readers: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (from, to, _) in graph.edges() {
+            operands.entry(*to).or_default().push(*from);
+            readers.entry(*from).or_default().push(*to);
+        }
+        let cost: HashMap<usize, u64> = order.iter().map(|&id| (id, node_cost(graph, id, model))).collect();
+        let total: u64 = cost.values().sum();
+        let share = total as f64 / parts as f64;
+
+        let mut part = HashMap::new();
+        let mut load = vec![0; parts];
+        let mut current = 0;
+        for (position, &node_id) in order.iter().enumerate() {
+            // Move on once this run has its share, leaving a node for each later run
+            let remaining = order.len() - position;
+            if current + 1 < parts && (load[current] as f64 >= share || remaining <= parts - current - 1) {
+                current += 1;
+            }
+            part.insert(node_id, current);
+            load[current] += cost[&node_id];
+        }
+
+        let limit = (share * (1.0 + BALANCE_TOLERANCE)).ceil() as u64;
+        Self {
+            operands,
+            readers,
+            part,
+            cost,
+            load,
+            limit,
+        }
+    }
+
+    fn neighbours(map: &HashMap<usize, Vec<usize>>, node_id: usize) -> &[usize] {
+        map.get(&node_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Partitions other than `value`'s own that read it
+    fn foreign_readers(&self, value: usize) -> BTreeSet<usize> {
+        let own = self.part.get(&value);
+        Self::neighbours(&self.readers, value)
+            .iter()
+            .filter_map(|reader| self.part.get(reader))
+            .filter(|&part| Some(part) != own)
+            .copied()
+            .collect()
+    }
+
+    /// Boundary crossings of `node_id`'s value and of the values it reads
+    fn local_cut(&self, node_id: usize) -> usize {
+        let mut values: BTreeSet<usize> = Self::neighbours(&self.operands, node_id)
+            .iter()
+            .copied()
+            .filter(|operand| self.part.contains_key(operand))
+            .collect();
+        values.insert(node_id);
+        values.iter().map(|&value| self.foreign_readers(value).len()).sum()
+    }
+
+    /// Move nodes to neighbouring partitions while that lowers the cut
+    fn refine(&mut self, order: &[usize]) {
+        for _ in 0..MAX_REFINEMENT_PASSES {
+            let mut moved = false;
+            for &node_id in order {
+                let from = self.part[&node_id];
+                // Never empty a partition
+                if self.load[from] == self.cost[&node_id] {
+                    continue;
+                }
+                let neighbours: BTreeSet<usize> = Self::neighbours(&self.operands, node_id)
+                    .iter()
+                    .chain(Self::neighbours(&self.readers, node_id))
+                    .filter_map(|neighbour| self.part.get(neighbour).copied())
+                    .filter(|&part| part != from)
+                    .collect();
+
+                let before = self.local_cut(node_id);
+                let mut best: Option<(usize, usize)> = None;
+                for to in neighbours {
+                    if self.load[to] + self.cost[&node_id] > self.limit {
+                        continue;
+                    }
+                    self.part.insert(node_id, to);
+                    let after = self.local_cut(node_id);
+                    if after < before && best.map_or(true, |(cut, _)| after < cut) {
+                        best = Some((after, to));
+                    }
+                }
+                match best {
+                    Some((_, to)) => {
+                        self.part.insert(node_id, to);
+                        self.load[from] -= self.cost[&node_id];
+                        self.load[to] += self.cost[&node_id];
+                        moved = true;
+                    }
+                    None => {
+                        self.part.insert(node_id, from);
+                    }
+                }
+            }
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    fn shared_values(&self, order: &[usize]) -> Vec<SharedValue> {
+        order
+            .iter()
+            .filter_map(|&node_id| {
+                let consumers = self.foreign_readers(node_id);
+                if consumers.is_empty() {
+                    return None;
+                }
+                Some((node_id, consumers))
+            })
+            .enumerate()
+            .map(|(index, (node_id, consumers))| SharedValue {
+                name: format!("shared.{}", index),
+                node: node_id,
+                producer: self.part[&node_id],
+                consumers: consumers.into_iter().collect(),
+            })
+            .collect()
+    }
+}
+
+/// The graph of partition `index`, with the id in `graph` of each of its nodes
+fn extract_partition(
+    graph: &IRGraph,
+    part: &HashMap<usize, usize>,
+    index: usize,
+    order: &[usize],
+    shared: &[SharedValue],
+) -> (IRGraph, Vec<usize>) {
+    let mut partition = IRGraph::new();
+    for table in graph.tables() {
+        partition.add_table(table.clone());
+    }
+    let mut origin = Vec::new();
+    let mut local: HashMap<usize, usize> = HashMap::new();
+    let shared_name: HashMap<usize, &str> = shared.iter().map(|value| (value.node, value.name.as_str())).collect();
+
+    let nodes: Vec<usize> = order
+        .iter()
+        .copied()
+        .filter(|id| part.get(id) == Some(&index))
+        .collect();
+    for &node_id in &nodes {
+        let node = graph.get_node(node_id).expect("live node");
+        let id = partition.add_node(node.node_type.clone(), node.data_type.clone(), node.label.clone());
+        if let Some(copy) = partition.get_node_mut(id) {
+            copy.attributes = node.attributes.clone();
+        }
+        local.insert(node_id, id);
+        origin.push(node_id);
+    }
+
+    for (operand, node_id, edge_type) in graph.edges() {
+        if part.get(node_id) != Some(&index) {
+            continue;
+        }
+        let from = match local.get(operand) {
+            Some(&from) => from,
+            None => {
+                let node = graph.get_node(*operand).expect("operand exists");
+                let from = match (&node.node_type, shared_name.get(operand)) {
+                    (IRNodeType::Constant(_), _) | (IRNodeType::Input(_), _) => {
+                        let from = partition.add_node(node.node_type.clone(), node.data_type.clone(), None);
+                        if let Some(copy) = partition.get_node_mut(from) {
+                            copy.attributes = node.attributes.clone();
+                        }
+                        from
+                    }
+                    (_, Some(name)) => {
+                        partition.add_node(IRNodeType::Input(name.to_string()), node.data_type.clone(), None)
+                    }
+                    _ => unreachable!("values read across partitions are shared"),
+                };
+                local.insert(*operand, from);
+                origin.push(*operand);
+                from
+            }
+        };
+        partition.add_edge(from, local[node_id], edge_type.clone());
+    }
+
+    for value in shared.iter().filter(|value| value.producer == index) {
+        let data_type = graph
+            .get_node(value.node)
+            .expect("shared node exists")
+            .data_type
+            .clone();
+        let id = partition.add_node(IRNodeType::Output(value.name.clone()), data_type, None);
+        partition.add_edge(local[&value.node], id, EdgeType::DataFlow);
+        origin.push(value.node);
+    }
+
+    remap_gadget_inputs(&mut partition, &local);
+    (partition, origin)
+}
+
+/// Rewrite the input lists of black-box and custom-gate roots to the partition's node
+/// ids; a gadget whose inputs are not all in the partition is lowered to arithmetic
+fn remap_gadget_inputs(partition: &mut IRGraph, local: &HashMap<usize, usize>) {
+    let gadgets = [
+        (BLACKBOX_ATTRIBUTE, BLACKBOX_INPUTS_ATTRIBUTE),
+        (CUSTOM_GATE_ATTRIBUTE, CUSTOM_GATE_INPUTS_ATTRIBUTE),
+    ];
+    let ids: Vec<usize> = partition.live_nodes().map(|node| node.id).collect();
+    for node_id in ids {
+        let node = match partition.get_node_mut(node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+        for (mark, inputs) in gadgets {
+            let remapped = match node.attributes.get(inputs) {
+                Some(list) => list
+                    .split(',')
+                    .map(|id| id.trim().parse::<usize>().ok().and_then(|id| local.get(&id)))
+                    .map(|id| id.map(ToString::to_string))
+                    .collect::<Option<Vec<_>>>(),
+                None => continue,
+            };
+            match remapped {
+                Some(ids) => {
+                    node.attributes.insert(inputs.to_string(), ids.join(","));
+                }
+                None => {
+                    node.attributes.remove(mark);
+                    node.attributes.remove(inputs);
+                }
+            }
+        }
+    }
+}
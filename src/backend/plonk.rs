@@ -0,0 +1,621 @@
+//! Plonkish arithmetization with the standard gate
+//! `q_l·a + q_r·b + q_o·c + q_m·a·b + q_c = 0`.
+//!
+//! The circuit is built from the R1CS lowering: each combination is reduced to
+//! `scale·var + const` with a chain of addition gates, and each R1CS row then fits one
+//! gate. Variables shared between cells become copy constraints over the advice columns
+//! `a`, `b` and `c`; the selectors are the fixed columns. On targets with lookup arguments
+//! range checks are rows whose `a` cell is looked up in a table of small values, and
+//! lookups into the function tables the optimizer generated are rows whose `(a, b)` pair
+//! is looked up in the table's `(index, value)` columns, one table per distinct set of
+//! rows. On targets with custom gates the patterns the optimizer marked take two rows
+//! under the gate's own selector.
+//!
+//! Large circuits generate their rows in parallel, in chunks of R1CS rows whose new
+//! variables are renumbered as the chunks are appended in order.
+
+use crate::backend::custom_gate::{self, CustomGate};
+use crate::backend::lowering::{
+    assign_wires, lc_add, lc_add_scaled, lc_constant_value, lc_wire, rebase_wire, CustomGateCall, FunctionTable,
+    LinearCombination, TableLookupCall, WireSource, FRESH_WIRE, ONE_WIRE,
+};
+use crate::backend::r1cs::{R1CSCircuit, R1CSConstraint};
+use crate::backend::{CircuitBackend, ConstraintTag, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::FCMCError;
+use crate::platform::*;
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// R1CS rows, range checks and gadget calls per chunk of parallel row generation; fewer
+/// are turned into rows on the calling thread
+pub const PARALLEL_ROWS: usize = 4096;
+
+/// Advice column of a gate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    A,
+    B,
+    C,
+}
+
+impl Column {
+    pub const ALL: [Column; 3] = [Column::A, Column::B, Column::C];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::A => "a",
+            Column::B => "b",
+            Column::C => "c",
+        }
+    }
+}
+
+/// One cell of the advice columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Cell {
+    pub column: Column,
+    pub row: usize,
+}
+
+/// Two cells that must hold the same value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CopyConstraint {
+    pub left: Cell,
+    pub right: Cell,
+}
+
+/// One row of the circuit: selector values and the variables in its advice cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlonkGate {
+    pub q_l: BigUint,
+    pub q_r: BigUint,
+    pub q_o: BigUint,
+    pub q_m: BigUint,
+    pub q_c: BigUint,
+    /// Variables in columns a, b and c
+    pub wires: [usize; 3],
+    /// The `a` cell is exposed as a public input
+    pub public: bool,
+    /// The `a` cell is looked up in the table of values below `2^bits`
+    pub lookup: Option<u32>,
+    /// The `a` and `b` cells are looked up as an index and its value in this function table
+    pub table: Option<usize>,
+    /// Custom gate enabled on this row, over its cells and the next row's
+    pub custom: Option<CustomGate>,
+    /// IR node the gate was lowered from
+    pub origin: Option<usize>,
+}
+
+impl PlonkGate {
+    fn empty(wires: [usize; 3]) -> Self {
+        Self {
+            q_l: BigUint::zero(),
+            q_r: BigUint::zero(),
+            q_o: BigUint::zero(),
+            q_m: BigUint::zero(),
+            q_c: BigUint::zero(),
+            wires,
+            public: false,
+            lookup: None,
+            table: None,
+            custom: None,
+            origin: None,
+        }
+    }
+
+    /// `q_l·a + q_r·b + q_o·c + q_m·a·b + q_c`, minus the public input on public rows
+    pub fn evaluate(&self, witness: &[BigUint]) -> BigUint {
+        let value = |index: usize| witness.get(self.wires[index]).cloned().unwrap_or_else(BigUint::zero);
+        let (a, b, c) = (value(0), value(1), value(2));
+        let mut sum = field::add(&field::mul(&self.q_l, &a), &field::mul(&self.q_r, &b));
+        sum = field::add(&sum, &field::mul(&self.q_o, &c));
+        sum = field::add(&sum, &field::mul(&self.q_m, &field::mul(&a, &b)));
+        sum = field::add(&sum, &self.q_c);
+        if self.public {
+            sum = field::sub(&sum, &a);
+        }
+        sum
+    }
+
+    pub fn selectors(&self) -> [(&'static str, &BigUint); 5] {
+        [
+            ("q_l", &self.q_l),
+            ("q_r", &self.q_r),
+            ("q_o", &self.q_o),
+            ("q_m", &self.q_m),
+            ("q_c", &self.q_c),
+        ]
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut gate = serde_json::Map::new();
+        for (name, value) in self.selectors() {
+            gate.insert(name.to_string(), json!(value.to_string()));
+        }
+        gate.insert("wires".to_string(), json!(self.wires));
+        if self.public {
+            gate.insert("public".to_string(), json!(true));
+        }
+        if let Some(bits) = self.lookup {
+            gate.insert("lookup".to_string(), json!({ "range": bits }));
+        }
+        if let Some(table) = self.table {
+            gate.insert("lookup".to_string(), json!({ "table": table }));
+        }
+        if let Some(custom) = self.custom {
+            gate.insert("custom".to_string(), json!(custom.name()));
+        }
+        if let Some(origin) = self.origin {
+            gate.insert("origin".to_string(), json!(origin));
+        }
+        Value::Object(gate)
+    }
+}
+
+/// How the witness is laid out in the trace
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WitnessLayout {
+    pub advice_columns: Vec<&'static str>,
+    pub fixed_columns: Vec<String>,
+    /// Variables in the advice cells of each row
+    pub rows: Vec<[usize; 3]>,
+    /// Rows whose `a` cell is a public input, in public input order
+    pub public_rows: Vec<usize>,
+    pub variable_names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlonkCircuit {
+    /// `Plonk` or `Halo2`; both share this arithmetization
+    pub target: TargetSystem,
+    pub gates: Vec<PlonkGate>,
+    /// Function tables the lookup rows read, each distinct
+    pub tables: Vec<FunctionTable>,
+    /// How the prover computes each variable; the first are the R1CS wires
+    pub variables: Vec<WireSource>,
+    pub variable_names: Vec<String>,
+    /// Tag of each IR node gates were lowered from, for those with one
+    pub tags: HashMap<usize, ConstraintTag>,
+}
+
+impl PlonkCircuit {
+    /// Range checks become table lookups on targets with lookup arguments
+    pub fn compile(graph: &IRGraph, target: TargetSystem) -> Result<Self, FCMCError> {
+        let r1cs = R1CSCircuit::compile_with(graph, target.capabilities().lowering_options())?;
+        Ok(Self::from_r1cs(&r1cs, target))
+    }
+
+    pub fn from_r1cs(r1cs: &R1CSCircuit, target: TargetSystem) -> Self {
+        let mut circuit = Self {
+            target,
+            gates: Vec::new(),
+            tables: r1cs.tables.clone(),
+            variables: r1cs.wires.clone(),
+            variable_names: r1cs.wire_names.clone(),
+            tags: r1cs.tags.clone(),
+        };
+
+        // Public inputs occupy the first rows so verifiers find them at fixed positions
+        for variable in 1..=r1cs.public_count() {
+            let mut gate = PlonkGate::empty([variable, ONE_WIRE, ONE_WIRE]);
+            gate.q_l = BigUint::one();
+            gate.public = true;
+            circuit.gates.push(gate);
+        }
+        // Variable 0 is the constant one: a - 1 = 0
+        let mut gate = PlonkGate::empty([ONE_WIRE, ONE_WIRE, ONE_WIRE]);
+        gate.q_l = BigUint::one();
+        gate.q_c = field::neg(&BigUint::one());
+        circuit.gates.push(gate);
+
+        let mut items: Vec<(RowSource, usize)> = Vec::new();
+        for (constraint, &origin) in r1cs.constraints.iter().zip(&r1cs.origins) {
+            items.push((RowSource::Constraint(constraint), origin));
+        }
+        for ((value, bits), &origin) in r1cs.ranges.iter().zip(&r1cs.range_origins) {
+            items.push((RowSource::Range(value, *bits), origin));
+        }
+        for (call, &origin) in r1cs.custom_gates.iter().zip(&r1cs.custom_gate_origins) {
+            items.push((RowSource::CustomGate(call), origin));
+        }
+        for (call, &origin) in r1cs.table_lookups.iter().zip(&r1cs.table_lookup_origins) {
+            items.push((RowSource::TableLookup(call), origin));
+        }
+
+        let rows = |items: &[(RowSource, usize)]| {
+            let mut rows = Rows::new(&r1cs.wire_names, &r1cs.tables);
+            for (source, origin) in items {
+                rows.add(source, Some(*origin));
+            }
+            rows
+        };
+        let pieces = if items.len() < PARALLEL_ROWS {
+            vec![rows(&items)]
+        } else {
+            // Worker threads do not inherit this thread's field
+            let active = field::active();
+            items
+                .par_chunks(PARALLEL_ROWS)
+                .map(|chunk| active.enter(|| rows(chunk)))
+                .collect()
+        };
+        for piece in pieces {
+            circuit.append(piece);
+        }
+        circuit
+    }
+
+    /// Append rows generated on their own, moving their new variables after this
+    /// circuit's variables
+    fn append(&mut self, rows: Rows) {
+        let first = self.variables.len();
+        self.gates.extend(rows.gates.into_iter().map(|mut gate| {
+            gate.wires = gate.wires.map(|variable| rebase_wire(variable, first));
+            gate
+        }));
+        self.variables.extend(rows.variables.iter().map(|source| source.rebase(first)));
+        self.variable_names.extend(rows.variable_names);
+    }
+
+    /// Custom gates enabled on some row
+    pub fn custom_gates(&self) -> Vec<CustomGate> {
+        let gates: BTreeSet<CustomGate> = self.gates.iter().filter_map(|gate| gate.custom).collect();
+        gates.into_iter().collect()
+    }
+
+    /// Rows each custom gate is enabled on
+    pub fn custom_gate_rows(&self, custom: CustomGate) -> Vec<usize> {
+        self.gates
+            .iter()
+            .enumerate()
+            .filter(|(_, gate)| gate.custom == Some(custom))
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    /// Whether gate `row`, with the lookup or custom gate it enables, holds on `witness`
+    fn row_holds(&self, row: usize, witness: &[BigUint]) -> bool {
+        let value = |variable: &usize| witness.get(*variable).cloned().unwrap_or_else(BigUint::zero);
+        let gate = &self.gates[row];
+        let in_table = match gate.lookup {
+            Some(bits) => witness.get(gate.wires[0]).map_or(false, |value| value.bits() <= bits as u64),
+            None => true,
+        };
+        let in_function_table = match gate.table {
+            Some(table) => value(&gate.wires[0])
+                .to_usize()
+                .and_then(|index| self.tables.get(table)?.values.get(index))
+                .map_or(false, |row| *row == value(&gate.wires[1])),
+            None => true,
+        };
+        let custom_holds = match gate.custom {
+            Some(custom) => {
+                let next = self.gates.get(row + 1).map_or([ONE_WIRE; 3], |next| next.wires);
+                let cells: Vec<BigUint> = gate.wires.iter().chain(&next).map(value).collect();
+                custom.constraint(&cells).is_zero()
+            }
+            None => true,
+        };
+        in_table && in_function_table && custom_holds && gate.evaluate(witness).is_zero()
+    }
+
+    /// Bit widths of the range tables the lookup rows read
+    pub fn range_tables(&self) -> Vec<u32> {
+        let widths: BTreeSet<u32> = self.gates.iter().filter_map(|gate| gate.lookup).collect();
+        widths.into_iter().collect()
+    }
+
+    /// Every cell each variable appears in
+    fn cells(&self) -> BTreeMap<usize, Vec<Cell>> {
+        let mut cells: BTreeMap<usize, Vec<Cell>> = BTreeMap::new();
+        for (row, gate) in self.gates.iter().enumerate() {
+            for (column, &variable) in Column::ALL.iter().zip(&gate.wires) {
+                cells.entry(variable).or_default().push(Cell { column: *column, row });
+            }
+        }
+        cells
+    }
+
+    /// Copy constraints chaining every cell of a variable to the next one
+    pub fn copy_constraints(&self) -> Vec<CopyConstraint> {
+        self.cells()
+            .values()
+            .flat_map(|cells| {
+                cells
+                    .windows(2)
+                    .map(|pair| CopyConstraint {
+                        left: pair[0],
+                        right: pair[1],
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn public_rows(&self) -> Vec<usize> {
+        self.gates
+            .iter()
+            .enumerate()
+            .filter(|(_, gate)| gate.public)
+            .map(|(row, _)| row)
+            .collect()
+    }
+
+    pub fn witness_layout(&self) -> WitnessLayout {
+        let mut fixed_columns: Vec<String> = ["q_l", "q_r", "q_o", "q_m", "q_c"].map(String::from).to_vec();
+        fixed_columns.extend(self.custom_gates().iter().map(CustomGate::selector));
+        WitnessLayout {
+            advice_columns: Column::ALL.iter().map(Column::name).collect(),
+            fixed_columns,
+            rows: self.gates.iter().map(|gate| gate.wires).collect(),
+            public_rows: self.public_rows(),
+            variable_names: self.variable_names.clone(),
+        }
+    }
+
+    /// Advice cell values of every row
+    pub fn trace(&self, witness: &[BigUint]) -> Vec<[BigUint; 3]> {
+        self.gates
+            .iter()
+            .map(|gate| gate.wires.map(|variable| witness.get(variable).cloned().unwrap_or_else(BigUint::zero)))
+            .collect()
+    }
+}
+
+/// Where a group of rows comes from in the R1CS circuit
+enum RowSource<'a> {
+    Constraint(&'a R1CSConstraint),
+    Range(&'a LinearCombination, u32),
+    CustomGate(&'a CustomGateCall),
+    TableLookup(&'a TableLookupCall),
+}
+
+/// Rows generated from part of an R1CS circuit, with the variables they add numbered
+/// from `FRESH_WIRE` until they are appended to the circuit
+struct Rows<'a> {
+    wire_names: &'a [String],
+    tables: &'a [FunctionTable],
+    gates: Vec<PlonkGate>,
+    variables: Vec<WireSource>,
+    variable_names: Vec<String>,
+}
+
+impl<'a> Rows<'a> {
+    fn new(wire_names: &'a [String], tables: &'a [FunctionTable]) -> Self {
+        Self {
+            wire_names,
+            tables,
+            gates: Vec::new(),
+            variables: Vec::new(),
+            variable_names: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, source: &RowSource, origin: Option<usize>) {
+        match source {
+            RowSource::Constraint(constraint) => self.add_row(&constraint.a, &constraint.b, &constraint.c, origin),
+            RowSource::Range(value, bits) => self.add_range(value, *bits, origin),
+            RowSource::CustomGate(call) => self.add_custom_gate(call, origin),
+            RowSource::TableLookup(call) => self.add_table_lookup(call, origin),
+        }
+    }
+
+    fn allocate(&mut self, source: WireSource, name: String) -> usize {
+        self.variables.push(source);
+        self.variable_names.push(name);
+        FRESH_WIRE + self.variables.len() - 1
+    }
+
+    fn name(&self, variable: usize) -> &str {
+        if variable >= FRESH_WIRE {
+            &self.variable_names[variable - FRESH_WIRE]
+        } else {
+            &self.wire_names[variable]
+        }
+    }
+
+    /// Reduce `lc` to `(scale, variable, constant)` with addition gates
+    fn reduce(&mut self, lc: &LinearCombination, origin: Option<usize>) -> (BigUint, usize, BigUint) {
+        let constant = lc.get(&ONE_WIRE).cloned().unwrap_or_else(BigUint::zero);
+        let mut terms = lc.iter().filter(|(&wire, _)| wire != ONE_WIRE);
+        let (mut variable, mut scale) = match terms.next() {
+            Some((&wire, coefficient)) => (wire, coefficient.clone()),
+            None => return (BigUint::zero(), ONE_WIRE, constant),
+        };
+
+        // partial = scale·variable + coefficient·wire
+        let mut partial = LinearCombination::from([(variable, scale.clone())]);
+        for (&wire, coefficient) in terms {
+            partial = lc_add(&partial, &LinearCombination::from([(wire, coefficient.clone())]));
+            let name = format!("{}.sum", self.name(wire));
+            let sum = self.allocate(WireSource::Linear(partial.clone()), name);
+            let mut gate = PlonkGate::empty([variable, wire, sum]);
+            gate.q_l = scale;
+            gate.q_r = coefficient.clone();
+            gate.q_o = field::neg(&BigUint::one());
+            gate.origin = origin;
+            self.gates.push(gate);
+            partial = lc_wire(sum);
+            variable = sum;
+            scale = BigUint::one();
+        }
+        (scale, variable, constant)
+    }
+
+    /// One gate for `a * b = c`
+    fn add_row(&mut self, a: &LinearCombination, b: &LinearCombination, c: &LinearCombination, origin: Option<usize>) {
+        let (scale_a, var_a, const_a) = self.reduce(a, origin);
+        let (scale_b, var_b, const_b) = self.reduce(b, origin);
+        let (scale_c, var_c, const_c) = self.reduce(c, origin);
+
+        // (sa·x + ca)(sb·y + cb) - (sc·z + cc) = 0
+        let mut gate = PlonkGate::empty([var_a, var_b, var_c]);
+        gate.q_m = field::mul(&scale_a, &scale_b);
+        gate.q_l = field::mul(&scale_a, &const_b);
+        gate.q_r = field::mul(&const_a, &scale_b);
+        gate.q_o = field::neg(&scale_c);
+        gate.q_c = field::sub(&field::mul(&const_a, &const_b), &const_c);
+        gate.origin = origin;
+        if lc_constant_value(a).is_some() && lc_constant_value(b).is_some() && lc_constant_value(c).is_some() {
+            // Nothing to check unless the row is unsatisfiable
+            if gate.q_c.is_zero() {
+                return;
+            }
+        }
+        self.gates.push(gate);
+    }
+
+    /// A variable holding `value`, with a gate defining it unless it is one already
+    fn materialize(&mut self, value: &LinearCombination, suffix: &str, origin: Option<usize>) -> usize {
+        let (scale, variable, constant) = self.reduce(value, origin);
+        if scale.is_one() && constant.is_zero() {
+            return variable;
+        }
+        // scale·variable + constant - materialized = 0
+        let name = format!("{}.{}", self.name(variable), suffix);
+        let materialized = self.allocate(WireSource::Linear(value.clone()), name);
+        let mut gate = PlonkGate::empty([variable, ONE_WIRE, materialized]);
+        gate.q_l = scale;
+        gate.q_o = field::neg(&BigUint::one());
+        gate.q_c = constant;
+        gate.origin = origin;
+        self.gates.push(gate);
+        materialized
+    }
+
+    /// A lookup row checking `value < 2^bits`
+    fn add_range(&mut self, value: &LinearCombination, bits: u32, origin: Option<usize>) {
+        let variable = self.materialize(value, "checked", origin);
+        let mut gate = PlonkGate::empty([variable, ONE_WIRE, ONE_WIRE]);
+        gate.lookup = Some(bits);
+        gate.origin = origin;
+        self.gates.push(gate);
+    }
+
+    /// A lookup row reading the packed index of the inputs and the output from the table.
+    /// Several inputs are range checked as well, so the index determines each of them.
+    fn add_table_lookup(&mut self, call: &TableLookupCall, origin: Option<usize>) {
+        let table = &self.tables[call.table];
+        let input_bits = table.input_bits.clone();
+        let weights = table.weights();
+        let mut index = LinearCombination::new();
+        for ((input, &bits), weight) in call.inputs.iter().zip(&input_bits).zip(&weights) {
+            if input_bits.len() > 1 {
+                self.add_range(input, bits, origin);
+            }
+            index = lc_add_scaled(&index, input, weight);
+        }
+        let index = self.materialize(&index, "index", origin);
+        let mut gate = PlonkGate::empty([index, call.output, ONE_WIRE]);
+        gate.table = Some(call.table);
+        gate.origin = origin;
+        self.gates.push(gate);
+    }
+
+    /// Two rows holding the gate's inputs and output, the first enabling the gate
+    fn add_custom_gate(&mut self, call: &CustomGateCall, origin: Option<usize>) {
+        let mut cells: Vec<usize> = call
+            .inputs
+            .iter()
+            .map(|input| self.materialize(input, "input", origin))
+            .collect();
+        cells.push(call.output);
+        cells.resize(custom_gate::CELLS, ONE_WIRE);
+
+        let mut gate = PlonkGate::empty([cells[0], cells[1], cells[2]]);
+        gate.custom = Some(call.gate);
+        gate.origin = origin;
+        self.gates.push(gate);
+        let mut next = PlonkGate::empty([cells[3], cells[4], cells[5]]);
+        next.origin = origin;
+        self.gates.push(next);
+    }
+}
+
+impl CircuitBackend for PlonkCircuit {
+    fn target(&self) -> TargetSystem {
+        self.target.clone()
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.gates.len()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.variables.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.variables
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.variable_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        assign_wires(&self.variables, values)
+    }
+
+    /// Copy constraints hold by construction: every cell reads its variable's value
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        (0..self.gates.len()).all(|row| self.row_holds(row, witness))
+    }
+
+    fn unsatisfied_row(&self, witness: &[BigUint]) -> Option<(usize, Vec<(String, BigUint)>)> {
+        let row = (0..self.gates.len()).find(|&row| !self.row_holds(row, witness))?;
+        let values = self.gates[row]
+            .wires
+            .iter()
+            .zip(["a", "b", "c"])
+            .map(|(&variable, cell)| {
+                let name = self.variable_names.get(variable).map_or("?", String::as_str);
+                let value = witness.get(variable).cloned().unwrap_or_else(BigUint::zero);
+                (format!("{} ({})", cell, name), value)
+            })
+            .collect();
+        Some((row, values))
+    }
+
+    fn constraint_tags(&self) -> Vec<Option<&ConstraintTag>> {
+        self.gates
+            .iter()
+            .map(|gate| gate.origin.and_then(|origin| self.tags.get(&origin)))
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "system": match self.target {
+                TargetSystem::Halo2 => "halo2",
+                _ => "plonk",
+            },
+            "gates": self.gates.iter().map(PlonkGate::to_json).collect::<Vec<_>>(),
+            "copy_constraints": self.copy_constraints(),
+            "range_tables": self.range_tables(),
+            "function_tables": self.tables.iter().map(FunctionTable::to_json).collect::<Vec<_>>(),
+            "custom_gates": self
+                .custom_gates()
+                .into_iter()
+                .map(|custom| {
+                    let mut definition = custom.to_json();
+                    definition["selector_rows"] = json!(self.custom_gate_rows(custom));
+                    definition
+                })
+                .collect::<Vec<_>>(),
+            "layout": self.witness_layout(),
+            "variable_sources": self.variables.iter().map(WireSource::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,226 @@
+//! Plonky2 target over the Goldilocks field.
+//!
+//! The circuit is a list of `CircuitBuilder` operations: every R1CS wire becomes a
+//! virtual target the prover sets, and each row `a * b = c` is computed with
+//! `arithmetic` operations (`const_0·m_0·m_1 + const_1·addend`) and asserted zero. Range
+//! checks stay native `range_check` calls. The whole lowering runs in the Goldilocks
+//! field, so constants and range checks respect its 64-bit modulus.
+
+use crate::backend::lowering::{assign_wires, lc_constant_value, LinearCombination, WireSource, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::{CircuitBackend, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::field::{self, FieldConfig};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::HashMap;
+
+/// One call on Plonky2's `CircuitBuilder`; targets are numbered in creation order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BuilderOp {
+    /// `add_virtual_target`, set by the prover from the witness
+    VirtualTarget { target: usize },
+    /// `constant(value)`
+    Constant { target: usize, value: String },
+    /// `arithmetic(const_0, const_1, multiplicand_0, multiplicand_1, addend)`
+    Arithmetic {
+        target: usize,
+        const_0: String,
+        const_1: String,
+        multiplicand_0: usize,
+        multiplicand_1: usize,
+        addend: usize,
+    },
+    AssertZero { target: usize },
+    RangeCheck { target: usize, bits: u32 },
+    RegisterPublicInput { target: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Plonky2Circuit {
+    pub ops: Vec<BuilderOp>,
+    /// Targets `0..wires.len()` are the R1CS wires; target 0 is the constant one
+    pub wires: Vec<WireSource>,
+    pub wire_names: Vec<String>,
+    targets: usize,
+    constants: HashMap<BigUint, usize>,
+}
+
+impl Plonky2Circuit {
+    /// Lower `graph`, which must already be defined over the Goldilocks field
+    pub fn compile(graph: &IRGraph) -> Result<Self, FCMCError> {
+        let r1cs = R1CSCircuit::compile_with(graph, TargetSystem::Plonky2.capabilities().lowering_options())?;
+        Ok(Self::from_r1cs(&r1cs))
+    }
+
+    pub fn from_r1cs(r1cs: &R1CSCircuit) -> Self {
+        let mut circuit = Self {
+            ops: Vec::new(),
+            wires: r1cs.wires.clone(),
+            wire_names: r1cs.wire_names.clone(),
+            targets: r1cs.wires.len(),
+            constants: HashMap::new(),
+        };
+        circuit.ops.push(BuilderOp::Constant {
+            target: ONE_WIRE,
+            value: "1".to_string(),
+        });
+        circuit.constants.insert(BigUint::one(), ONE_WIRE);
+        for target in 1..r1cs.wires.len() {
+            circuit.ops.push(BuilderOp::VirtualTarget { target });
+        }
+        for target in 1..=r1cs.public_count() {
+            circuit.ops.push(BuilderOp::RegisterPublicInput { target });
+        }
+
+        for row in &r1cs.constraints {
+            let c = circuit.lc_target(&row.c);
+            // a·b - c, folding a constant side into the coefficient
+            let (coefficient, a, b) = match (lc_constant_value(&row.a), lc_constant_value(&row.b)) {
+                (Some(k), _) => (k, circuit.lc_target(&row.b), ONE_WIRE),
+                (_, Some(k)) => (k, circuit.lc_target(&row.a), ONE_WIRE),
+                _ => (BigUint::one(), circuit.lc_target(&row.a), circuit.lc_target(&row.b)),
+            };
+            let difference = circuit.arithmetic(coefficient, field::neg(&BigUint::one()), a, b, c);
+            circuit.ops.push(BuilderOp::AssertZero { target: difference });
+        }
+        for (value, bits) in &r1cs.ranges {
+            let target = circuit.lc_target(value);
+            circuit.ops.push(BuilderOp::RangeCheck { target, bits: *bits });
+        }
+        circuit
+    }
+
+    fn constant(&mut self, value: BigUint) -> usize {
+        if let Some(&target) = self.constants.get(&value) {
+            return target;
+        }
+        let target = self.targets;
+        self.targets += 1;
+        self.ops.push(BuilderOp::Constant {
+            target,
+            value: value.to_string(),
+        });
+        self.constants.insert(value, target);
+        target
+    }
+
+    fn arithmetic(&mut self, const_0: BigUint, const_1: BigUint, m0: usize, m1: usize, addend: usize) -> usize {
+        let target = self.targets;
+        self.targets += 1;
+        self.ops.push(BuilderOp::Arithmetic {
+            target,
+            const_0: const_0.to_string(),
+            const_1: const_1.to_string(),
+            multiplicand_0: m0,
+            multiplicand_1: m1,
+            addend,
+        });
+        target
+    }
+
+    /// A target holding `lc`, accumulated one term at a time
+    fn lc_target(&mut self, lc: &LinearCombination) -> usize {
+        if let [(&wire, coefficient)] = lc.iter().collect::<Vec<_>>().as_slice() {
+            if coefficient.is_one() {
+                return wire;
+            }
+        }
+        let constant = lc.get(&ONE_WIRE).cloned().unwrap_or_else(BigUint::zero);
+        let mut acc = self.constant(constant);
+        for (&wire, coefficient) in lc.iter().filter(|(&wire, _)| wire != ONE_WIRE) {
+            acc = self.arithmetic(coefficient.clone(), BigUint::one(), wire, ONE_WIRE, acc);
+        }
+        acc
+    }
+
+    /// Value of every target, given the virtual targets' values
+    pub fn evaluate(&self, witness: &[BigUint]) -> Vec<BigUint> {
+        let mut values = witness.to_vec();
+        values.resize(self.targets, BigUint::zero());
+        let value = |values: &[BigUint], target: usize| values[target].clone();
+        for op in &self.ops {
+            match op {
+                BuilderOp::Constant { target, value } => {
+                    values[*target] = field::parse_element(value).unwrap_or_default();
+                }
+                BuilderOp::Arithmetic {
+                    target,
+                    const_0,
+                    const_1,
+                    multiplicand_0,
+                    multiplicand_1,
+                    addend,
+                } => {
+                    let product = field::mul(&value(&values, *multiplicand_0), &value(&values, *multiplicand_1));
+                    let scaled = field::mul(&field::parse_element(const_0).unwrap_or_default(), &product);
+                    let added = field::mul(&field::parse_element(const_1).unwrap_or_default(), &value(&values, *addend));
+                    values[*target] = field::add(&scaled, &added);
+                }
+                _ => {}
+            }
+        }
+        values
+    }
+}
+
+impl CircuitBackend for Plonky2Circuit {
+    fn target(&self) -> TargetSystem {
+        TargetSystem::Plonky2
+    }
+
+    /// Arithmetic operations; Plonky2 packs 20 of them into each gate row
+    fn constraint_count(&self) -> usize {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op, BuilderOp::Arithmetic { .. } | BuilderOp::RangeCheck { .. }))
+            .count()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.wires.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.wires
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.wire_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        FieldConfig::Goldilocks.enter(|| assign_wires(&self.wires, values))
+    }
+
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        FieldConfig::Goldilocks.enter(|| {
+            let values = self.evaluate(witness);
+            self.ops.iter().all(|op| match op {
+                BuilderOp::AssertZero { target } => values[*target].is_zero(),
+                BuilderOp::RangeCheck { target, bits } => values[*target].bits() <= *bits as u64,
+                _ => true,
+            })
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "system": "plonky2",
+            "field": "goldilocks",
+            "targets": self.targets,
+            "ops": self.ops,
+            "wire_names": self.wire_names,
+            "wire_sources": self.wires.iter().map(WireSource::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
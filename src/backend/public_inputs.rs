@@ -0,0 +1,217 @@
+//! Order and packing of a circuit's public values.
+//!
+//! Verifiers read the public values as one list, the outputs and then the public inputs.
+//! An existing verifier may expect either group in another order, and many small public
+//! inputs are cheaper to verify packed into one field element. Both are recorded on the
+//! IR, so every backend lowers the same layout and the circuit signature reports it.
+
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::field::FieldConfig;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::One;
+use std::collections::HashMap;
+
+/// Node attribute giving a public value's position among the outputs, or among the
+/// public inputs
+pub const PUBLIC_INDEX_ATTRIBUTE: &str = "public_index";
+
+/// Attribute of a packed output listing what it packs, as `name:bits` from the lowest
+/// bits up
+pub const PACKED_INPUTS_ATTRIBUTE: &str = "packed_inputs";
+
+/// Public inputs made private and packed into one public output, lowest bits first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedInputs {
+    /// Name of the output holding the packed value
+    pub name: String,
+    /// Each input with the bits it is range-checked to
+    pub inputs: Vec<(String, u32)>,
+}
+
+impl PackedInputs {
+    pub fn new(name: &str, inputs: &[(&str, u32)]) -> Self {
+        Self {
+            name: name.to_string(),
+            inputs: inputs.iter().map(|&(input, bits)| (input.to_string(), bits)).collect(),
+        }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.inputs.iter().map(|(_, bits)| bits).sum()
+    }
+
+    /// The packed value for `values`, as the verifier computes its public input
+    pub fn pack(&self, values: &HashMap<String, BigUint>) -> Result<BigUint, FCMCError> {
+        let mut packed = BigUint::default();
+        let mut offset = 0;
+        for (name, bits) in &self.inputs {
+            let value = values
+                .get(name)
+                .ok_or_else(|| FCMCError::BackendError(format!("No value for packed input '{}'", name)))?;
+            if value.bits() > u64::from(*bits) {
+                return Err(FCMCError::BackendError(format!(
+                    "Packed input '{}' does not fit in {} bits",
+                    name, bits
+                )));
+            }
+            packed |= value << offset;
+            offset += bits;
+        }
+        Ok(packed)
+    }
+
+    fn attribute(&self) -> String {
+        let inputs: Vec<String> = self.inputs.iter().map(|(name, bits)| format!("{}:{}", name, bits)).collect();
+        inputs.join(",")
+    }
+
+    fn from_attribute(name: &str, value: &str) -> Option<Self> {
+        let inputs = value
+            .split(',')
+            .map(|input| {
+                let (name, bits) = input.rsplit_once(':')?;
+                Some((name.to_string(), bits.parse().ok()?))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            name: name.to_string(),
+            inputs,
+        })
+    }
+}
+
+/// Requested order and packing of the public values
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicLayout {
+    /// Outputs and public inputs by name, in the order the verifier expects them within
+    /// their group; unnamed ones follow in declaration order
+    pub order: Vec<String>,
+    pub packs: Vec<PackedInputs>,
+}
+
+impl PublicLayout {
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty() && self.packs.is_empty()
+    }
+
+    /// Pack, then order, the public values of `graph`
+    pub fn apply(&self, graph: &mut IRGraph, field: &FieldConfig) -> Result<(), FCMCError> {
+        for pack in &self.packs {
+            pack_inputs(graph, pack, field)?;
+        }
+        for (index, name) in self.order.iter().enumerate() {
+            let node_id = public_node(graph, name).ok_or_else(|| {
+                FCMCError::BackendError(format!("'{}' is not a public input or output of the circuit", name))
+            })?;
+            if let Some(node) = graph.get_node_mut(node_id) {
+                node.attributes.insert(PUBLIC_INDEX_ATTRIBUTE.to_string(), index.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pack_inputs(graph: &mut IRGraph, pack: &PackedInputs, field: &FieldConfig) -> Result<(), FCMCError> {
+    if pack.bits() > field.capacity() {
+        return Err(FCMCError::BackendError(format!(
+            "Packing {} bits into '{}' overflows the {}-bit capacity of the {} field",
+            pack.bits(),
+            pack.name,
+            field.capacity(),
+            field
+        )));
+    }
+    if public_node(graph, &pack.name).is_some() {
+        return Err(FCMCError::BackendError(format!(
+            "The packed output '{}' clashes with a public value of the same name",
+            pack.name
+        )));
+    }
+
+    // packed = sum of input_i * 2^offset_i, with every input kept inside its width so
+    // the packing is one-to-one
+    let mut packed = None;
+    let mut offset = 0;
+    for (name, bits) in &pack.inputs {
+        let input = graph
+            .live_nodes()
+            .find(|node| matches!(&node.node_type, IRNodeType::Input(input) if input == name))
+            .map(|node| node.id)
+            .ok_or_else(|| FCMCError::BackendError(format!("'{}' is not a public input of the circuit", name)))?;
+        if let Some(node) = graph.get_node_mut(input) {
+            node.node_type = IRNodeType::PrivateInput(name.clone());
+        }
+        let range = graph.add_node(IRNodeType::Constraint(ConstraintType::Range { bits: *bits }), Type::Bool, None);
+        graph.add_edge(input, range, EdgeType::Constraint);
+
+        let term = if offset == 0 {
+            input
+        } else {
+            let scale = (BigUint::one() << offset).to_string();
+            let scale = graph.add_node(IRNodeType::Constant(scale), Type::Field, None);
+            binary(graph, IRNodeType::Mul, input, scale)
+        };
+        packed = Some(match packed {
+            Some(sum) => binary(graph, IRNodeType::Add, sum, term),
+            None => term,
+        });
+        offset += bits;
+    }
+    let packed = packed
+        .ok_or_else(|| FCMCError::BackendError(format!("The packed output '{}' packs no inputs", pack.name)))?;
+
+    let output = graph.add_node(IRNodeType::Output(pack.name.clone()), Type::Field, Some(pack.name.clone()));
+    graph.add_edge(packed, output, EdgeType::DataFlow);
+    if let Some(node) = graph.get_node_mut(output) {
+        node.attributes.insert(PACKED_INPUTS_ATTRIBUTE.to_string(), pack.attribute());
+    }
+    Ok(())
+}
+
+fn binary(graph: &mut IRGraph, op: IRNodeType, left: usize, right: usize) -> usize {
+    let id = graph.add_node(op, Type::Field, None);
+    graph.add_edge(left, id, EdgeType::DataFlow);
+    graph.add_edge(right, id, EdgeType::DataFlow);
+    id
+}
+
+fn public_node(graph: &IRGraph, name: &str) -> Option<usize> {
+    graph
+        .live_nodes()
+        .find(|node| match &node.node_type {
+            IRNodeType::Output(output) | IRNodeType::Input(output) => output == name,
+            _ => false,
+        })
+        .map(|node| node.id)
+}
+
+/// Live nodes `filter` accepts, in wire order: by `public_index`, then by node id
+pub fn public_order(graph: &IRGraph, filter: impl Fn(&IRNodeType) -> bool) -> Vec<usize> {
+    let mut nodes: Vec<(Option<usize>, usize)> = graph
+        .live_nodes()
+        .filter(|node| filter(&node.node_type))
+        .map(|node| {
+            let index = node.attributes.get(PUBLIC_INDEX_ATTRIBUTE).and_then(|index| index.parse().ok());
+            (index, node.id)
+        })
+        .collect();
+    // Ordered values first
+    nodes.sort_by_key(|&(index, id)| (index.is_none(), index, id));
+    nodes.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Packs recorded on the outputs of `graph`, in output order
+pub fn packed_inputs(graph: &IRGraph) -> Vec<PackedInputs> {
+    public_order(graph, |node_type| matches!(node_type, IRNodeType::Output(_)))
+        .into_iter()
+        .filter_map(|node_id| {
+            let node = graph.get_node(node_id)?;
+            match (&node.node_type, node.attributes.get(PACKED_INPUTS_ATTRIBUTE)) {
+                (IRNodeType::Output(name), Some(value)) => PackedInputs::from_attribute(name, value),
+                _ => None,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,908 @@
+//! Lowering of the IR to a rank-1 constraint system.
+//!
+//! Every row reads `A·w * B·w = C·w` over the witness `w`. Wires are ordered as the
+//! constant one, the outputs, the public inputs, the private inputs, then every internal
+//! wire. Linear nodes allocate no wire: they are folded into the combinations of the rows
+//! that read them, unless the sparsity pass marked them with `r1cs.wire`.
+//!
+//! Nodes are lowered level by level, each level reading only earlier ones. The nodes of a
+//! wide level are lowered in parallel, each into a fragment with its own fresh wires, and
+//! the fragments are appended in node order, so the circuit is the same on any number of
+//! threads. The lowered circuit then goes through wire minimization; streamed lowering
+//! skips it, as its rows are already written.
+
+use crate::backend::lowering::{
+    assign_wires, black_box, custom_gate, lc_add, lc_add_scaled, lc_constant, lc_constant_value, lc_evaluate,
+    lc_json, lc_rebase, lc_scale, lc_sub, lc_wire, rebase_wire, BlackBoxCall, CustomGateCall, FunctionTable,
+    LinearCombination, TableLookupCall, WireSource, FRESH_WIRE, ONE_WIRE,
+};
+use crate::backend::public_inputs::public_order;
+use crate::backend::tags::node_tags;
+use crate::backend::wire_minimization::minimize_wires;
+use crate::backend::{CircuitBackend, CircuitSink, ConstraintTag, TargetSystem};
+use crate::ir::{ConstraintType, IRGraph, IRNodeType, LookupTable};
+use crate::optimization::field::{self, FieldConfig};
+use crate::optimization::interpreter::extracted_bit;
+use crate::optimization::lookup::function_table;
+use crate::optimization::sparsity::WIRE_ATTRIBUTE;
+use crate::FCMCError;
+use crate::platform::*;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde_json::{json, Value};
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Width assumed for comparison operands without a `bits` attribute, unless the field
+/// is too small to compare values that wide
+pub const DEFAULT_COMPARISON_BITS: u32 = 64;
+
+/// Levels with fewer nodes are lowered on the calling thread; forking costs more than it saves
+pub const PARALLEL_LEVEL_WIDTH: usize = 256;
+
+/// Widest operands a comparison can decompose without `a - b + 2^bits` wrapping
+pub(crate) fn max_comparison_bits() -> u32 {
+    (field::modulus().bits() as u32).saturating_sub(2)
+}
+
+/// What the target can check natively instead of in rank-1 rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoweringOptions {
+    /// Range checks are recorded in `R1CSCircuit::ranges` instead of decomposed into bits
+    pub native_ranges: bool,
+    /// Gadgets marked with a `blackbox` attribute are recorded as calls instead of lowered
+    pub black_boxes: bool,
+    /// Patterns marked with a `custom_gate` attribute are recorded as gate calls instead of lowered
+    pub custom_gates: bool,
+    /// Lookups into function tables are recorded as table lookups instead of rejected
+    pub function_tables: bool,
+}
+
+/// `a * b = c`
+#[derive(Debug, Clone, PartialEq)]
+pub struct R1CSConstraint {
+    pub a: LinearCombination,
+    pub b: LinearCombination,
+    pub c: LinearCombination,
+}
+
+impl R1CSConstraint {
+    pub fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        let left = field::mul(&lc_evaluate(&self.a, witness), &lc_evaluate(&self.b, witness));
+        left == lc_evaluate(&self.c, witness)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({ "a": lc_json(&self.a), "b": lc_json(&self.b), "c": lc_json(&self.c) })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct R1CSCircuit {
+    pub constraints: Vec<R1CSConstraint>,
+    /// How the prover computes each wire, by wire index
+    pub wires: Vec<WireSource>,
+    pub wire_names: Vec<String>,
+    pub public_outputs: usize,
+    pub public_inputs: usize,
+    pub private_inputs: usize,
+    /// `(value, bits)` checks left to the target; empty unless `native_ranges` is set
+    pub ranges: Vec<(LinearCombination, u32)>,
+    /// Empty unless `black_boxes` is set
+    pub black_boxes: Vec<BlackBoxCall>,
+    /// Empty unless `custom_gates` is set
+    pub custom_gates: Vec<CustomGateCall>,
+    /// Distinct tables the lookups read; empty unless `function_tables` is set
+    pub tables: Vec<FunctionTable>,
+    pub table_lookups: Vec<TableLookupCall>,
+    /// IR node each constraint was lowered from
+    pub origins: Vec<usize>,
+    /// IR node each native range check was lowered from
+    pub range_origins: Vec<usize>,
+    /// IR node each custom gate call computes
+    pub custom_gate_origins: Vec<usize>,
+    /// IR node each table lookup computes
+    pub table_lookup_origins: Vec<usize>,
+    /// Tag of each IR node rows were lowered from, for those with one
+    pub tags: HashMap<usize, ConstraintTag>,
+    /// Field the circuit was lowered over
+    pub field: FieldConfig,
+}
+
+impl R1CSCircuit {
+    pub fn compile(graph: &IRGraph) -> Result<Self, FCMCError> {
+        Self::compile_with(graph, LoweringOptions::default())
+    }
+
+    pub fn compile_with(graph: &IRGraph, options: LoweringOptions) -> Result<Self, FCMCError> {
+        let mut builder = R1CSBuilder::new(graph, options);
+        for level in lowering_levels(graph) {
+            builder.lower_level(&level)?;
+        }
+        let mut circuit = builder.finish();
+        minimize_wires(&mut circuit);
+        Ok(circuit)
+    }
+
+    /// Lower `graph`, handing each constraint to `sink` once its node is lowered; returns
+    /// the number of constraints
+    pub fn stream(graph: &IRGraph, sink: &mut dyn CircuitSink) -> Result<usize, FCMCError> {
+        let mut builder = R1CSBuilder::new(graph, LoweringOptions::default());
+        let mut count = 0;
+        for node_id in lowering_levels(graph).into_iter().flatten() {
+            builder.lower(node_id)?;
+            count += builder.constraints.len();
+            builder.origins.clear();
+            for constraint in builder.constraints.drain(..) {
+                sink.push_constraint(constraint)?;
+            }
+        }
+        sink.finish(&builder.finish())?;
+        Ok(count)
+    }
+
+    /// Wires after the constant one that the verifier supplies
+    pub fn public_count(&self) -> usize {
+        self.public_outputs + self.public_inputs
+    }
+
+    /// IR nodes the constraints, native range checks, custom gates and lookups were
+    /// lowered from
+    pub fn origin_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.origins
+            .iter()
+            .chain(&self.range_origins)
+            .chain(&self.custom_gate_origins)
+            .chain(&self.table_lookup_origins)
+            .copied()
+    }
+}
+
+impl CircuitBackend for R1CSCircuit {
+    fn target(&self) -> TargetSystem {
+        TargetSystem::R1CS
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.constraints.len()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.wires.len()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        &self.wires
+    }
+
+    fn wire_names(&self) -> &[String] {
+        &self.wire_names
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        self.field.enter(|| assign_wires(&self.wires, values))
+    }
+
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        self.field
+            .enter(|| self.constraints.iter().all(|constraint| constraint.is_satisfied(witness)))
+    }
+
+    fn unsatisfied_row(&self, witness: &[BigUint]) -> Option<(usize, Vec<(String, BigUint)>)> {
+        self.field.enter(|| {
+            let row = self
+                .constraints
+                .iter()
+                .position(|constraint| !constraint.is_satisfied(witness))?;
+            let constraint = &self.constraints[row];
+            let mut values = vec![
+                ("A·w".to_string(), lc_evaluate(&constraint.a, witness)),
+                ("B·w".to_string(), lc_evaluate(&constraint.b, witness)),
+                ("C·w".to_string(), lc_evaluate(&constraint.c, witness)),
+            ];
+            let wires: BTreeSet<usize> = constraint
+                .a
+                .keys()
+                .chain(constraint.b.keys())
+                .chain(constraint.c.keys())
+                .copied()
+                .filter(|&wire| wire != ONE_WIRE)
+                .collect();
+            for wire in wires {
+                let name = self.wire_names.get(wire).cloned().unwrap_or_else(|| format!("w{}", wire));
+                values.push((name, witness.get(wire).cloned().unwrap_or_else(BigUint::zero)));
+            }
+            Some((row, values))
+        })
+    }
+
+    fn constraint_tags(&self) -> Vec<Option<&ConstraintTag>> {
+        self.origins.iter().map(|origin| self.tags.get(origin)).collect()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "system": "r1cs",
+            "field": self.field.to_string(),
+            "wires": self.wires.len(),
+            "public_outputs": self.public_outputs,
+            "public_inputs": self.public_inputs,
+            "private_inputs": self.private_inputs,
+            "wire_names": self.wire_names,
+            "wire_sources": self.wires.iter().map(WireSource::to_json).collect::<Vec<_>>(),
+            "constraints": self.constraints.iter().map(R1CSConstraint::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+struct R1CSBuilder<'a> {
+    graph: &'a IRGraph,
+    constraints: Vec<R1CSConstraint>,
+    wires: Vec<WireSource>,
+    wire_names: Vec<String>,
+    /// Combination each lowered node evaluates to
+    lcs: HashMap<usize, LinearCombination>,
+    public_outputs: usize,
+    public_inputs: usize,
+    private_inputs: usize,
+    options: LoweringOptions,
+    ranges: Vec<(LinearCombination, u32)>,
+    black_boxes: Vec<BlackBoxCall>,
+    custom_gates: Vec<CustomGateCall>,
+    tables: Vec<FunctionTable>,
+    table_lookups: Vec<TableLookupCall>,
+    /// Nodes only black-box and custom gate calls read, which are not lowered
+    internal: HashSet<usize>,
+    origins: Vec<usize>,
+    range_origins: Vec<usize>,
+    custom_gate_origins: Vec<usize>,
+    table_lookup_origins: Vec<usize>,
+    /// Node being lowered
+    current: usize,
+    /// Id of the first wire this builder allocates: 0, or `FRESH_WIRE` for a fragment
+    first_wire: usize,
+}
+
+impl<'a> R1CSBuilder<'a> {
+    fn new(graph: &'a IRGraph, options: LoweringOptions) -> Self {
+        let mut builder = Self {
+            graph,
+            constraints: Vec::new(),
+            wires: vec![WireSource::One],
+            wire_names: vec!["one".to_string()],
+            lcs: HashMap::new(),
+            public_outputs: 0,
+            public_inputs: 0,
+            private_inputs: 0,
+            options,
+            ranges: Vec::new(),
+            black_boxes: Vec::new(),
+            custom_gates: Vec::new(),
+            tables: Vec::new(),
+            table_lookups: Vec::new(),
+            internal: gadget_internals(graph, &options),
+            origins: Vec::new(),
+            range_origins: Vec::new(),
+            custom_gate_origins: Vec::new(),
+            table_lookup_origins: Vec::new(),
+            current: 0,
+            first_wire: 0,
+        };
+
+        // Public and private wires come first, the public ones in the requested order and
+        // the rest in node order
+        for node_id in public_order(graph, |node_type| matches!(node_type, IRNodeType::Output(_))) {
+            builder.allocate_node(node_id);
+            builder.public_outputs += 1;
+        }
+        for node_id in public_order(graph, |node_type| matches!(node_type, IRNodeType::Input(_))) {
+            builder.allocate_node(node_id);
+            builder.public_inputs += 1;
+        }
+        for node in graph.live_nodes().filter(|node| matches!(node.node_type, IRNodeType::PrivateInput(_))) {
+            builder.allocate_node(node.id);
+            builder.private_inputs += 1;
+        }
+        builder
+    }
+
+    fn finish(self) -> R1CSCircuit {
+        let origins = self
+            .origins
+            .iter()
+            .chain(&self.range_origins)
+            .chain(&self.custom_gate_origins)
+            .chain(&self.table_lookup_origins)
+            .copied();
+        let tags = node_tags(self.graph, origins);
+        R1CSCircuit {
+            constraints: self.constraints,
+            wires: self.wires,
+            wire_names: self.wire_names,
+            public_outputs: self.public_outputs,
+            public_inputs: self.public_inputs,
+            private_inputs: self.private_inputs,
+            ranges: self.ranges,
+            black_boxes: self.black_boxes,
+            custom_gates: self.custom_gates,
+            tables: self.tables,
+            table_lookups: self.table_lookups,
+            origins: self.origins,
+            range_origins: self.range_origins,
+            custom_gate_origins: self.custom_gate_origins,
+            table_lookup_origins: self.table_lookup_origins,
+            tags,
+            field: field::active(),
+        }
+    }
+
+    /// A builder lowering `node_id` on its own, with copies of the combinations it reads
+    fn fragment(&self, node_id: usize) -> Self {
+        let lcs = self
+            .reads(node_id)
+            .into_iter()
+            .filter_map(|read| Some((read, self.lcs.get(&read)?.clone())))
+            .collect();
+        Self {
+            graph: self.graph,
+            constraints: Vec::new(),
+            wires: Vec::new(),
+            wire_names: Vec::new(),
+            lcs,
+            public_outputs: 0,
+            public_inputs: 0,
+            private_inputs: 0,
+            options: self.options,
+            ranges: Vec::new(),
+            black_boxes: Vec::new(),
+            custom_gates: Vec::new(),
+            tables: Vec::new(),
+            table_lookups: Vec::new(),
+            internal: HashSet::new(),
+            origins: Vec::new(),
+            range_origins: Vec::new(),
+            custom_gate_origins: Vec::new(),
+            table_lookup_origins: Vec::new(),
+            current: node_id,
+            first_wire: FRESH_WIRE,
+        }
+    }
+
+    /// Nodes whose combinations lowering `node_id` reads
+    fn reads(&self, node_id: usize) -> Vec<usize> {
+        let mut reads = self.graph.get_predecessors(node_id);
+        reads.push(node_id);
+        if self.options.black_boxes {
+            if let Some((_, inputs)) = black_box(self.graph, node_id) {
+                reads.extend(inputs);
+            }
+        }
+        if self.options.custom_gates {
+            if let Some((_, inputs)) = custom_gate(self.graph, node_id) {
+                reads.extend(inputs);
+            }
+        }
+        reads
+    }
+
+    /// Append what a fragment lowered, moving its fresh wires after this builder's wires
+    fn append(&mut self, fragment: Self) {
+        let first = self.first_wire + self.wires.len();
+        let rebase = |lc: &LinearCombination| lc_rebase(lc, first);
+        let rebase_all = |lcs: &[LinearCombination]| lcs.iter().map(rebase).collect::<Vec<_>>();
+
+        self.constraints.extend(fragment.constraints.iter().map(|constraint| R1CSConstraint {
+            a: rebase(&constraint.a),
+            b: rebase(&constraint.b),
+            c: rebase(&constraint.c),
+        }));
+        self.wires.extend(fragment.wires.iter().map(|source| source.rebase(first)));
+        self.wire_names.extend(fragment.wire_names);
+        if let Some(lc) = fragment.lcs.get(&fragment.current) {
+            self.lcs.insert(fragment.current, rebase(lc));
+        }
+        self.ranges.extend(fragment.ranges.iter().map(|(value, bits)| (rebase(value), *bits)));
+        self.black_boxes.extend(fragment.black_boxes.iter().map(|call| BlackBoxCall {
+            function: call.function.clone(),
+            inputs: rebase_all(&call.inputs),
+            output: rebase_wire(call.output, first),
+        }));
+        self.custom_gates.extend(fragment.custom_gates.iter().map(|call| CustomGateCall {
+            gate: call.gate,
+            inputs: rebase_all(&call.inputs),
+            output: rebase_wire(call.output, first),
+        }));
+        for call in &fragment.table_lookups {
+            let table = self.add_function_table(fragment.tables[call.table].clone());
+            self.table_lookups.push(TableLookupCall {
+                table,
+                inputs: rebase_all(&call.inputs),
+                output: rebase_wire(call.output, first),
+            });
+        }
+        self.origins.extend(fragment.origins);
+        self.range_origins.extend(fragment.range_origins);
+        self.custom_gate_origins.extend(fragment.custom_gate_origins);
+        self.table_lookup_origins.extend(fragment.table_lookup_origins);
+    }
+
+    /// Lower one level of independent nodes, on the rayon pool if it is wide enough
+    fn lower_level(&mut self, level: &[usize]) -> Result<(), FCMCError> {
+        if level.len() < PARALLEL_LEVEL_WIDTH {
+            for &node_id in level {
+                self.lower(node_id)?;
+            }
+            return Ok(());
+        }
+        let builder: &Self = self;
+        // Worker threads do not inherit this thread's field
+        let active = field::active();
+        let fragments = level
+            .par_iter()
+            .filter(|&&node_id| !builder.internal.contains(&node_id))
+            .map(|&node_id| {
+                active.enter(|| {
+                    let mut fragment = builder.fragment(node_id);
+                    fragment.lower(node_id)?;
+                    Ok(fragment)
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+        for fragment in fragments {
+            self.append(fragment);
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, source: WireSource, name: String) -> usize {
+        self.wires.push(source);
+        self.wire_names.push(name);
+        self.last_wire()
+    }
+
+    fn last_wire(&self) -> usize {
+        self.first_wire + self.wires.len() - 1
+    }
+
+    /// Allocate a wire holding the value of an IR node
+    fn allocate_node(&mut self, node_id: usize) -> LinearCombination {
+        let name = self.node_name(node_id);
+        let wire = self.allocate(WireSource::Node(node_id), name);
+        let lc = lc_wire(wire);
+        self.lcs.insert(node_id, lc.clone());
+        lc
+    }
+
+    fn node_name(&self, node_id: usize) -> String {
+        match self.graph.get_node(node_id).map(|node| (&node.node_type, &node.label)) {
+            Some((IRNodeType::Input(name), _))
+            | Some((IRNodeType::PrivateInput(name), _))
+            | Some((IRNodeType::Output(name), _)) => name.clone(),
+            Some((_, Some(label))) => label.clone(),
+            _ => format!("n{}", node_id),
+        }
+    }
+
+    fn enforce(&mut self, a: LinearCombination, b: LinearCombination, c: LinearCombination) {
+        self.constraints.push(R1CSConstraint { a, b, c });
+        self.origins.push(self.current);
+    }
+
+    fn one() -> LinearCombination {
+        lc_wire(ONE_WIRE)
+    }
+
+    /// Combinations of the nodes a gadget call reads
+    fn gadget_inputs(&self, node_id: usize, inputs: &[usize]) -> Result<Vec<LinearCombination>, FCMCError> {
+        inputs
+            .iter()
+            .map(|input| {
+                self.lcs.get(input).cloned().ok_or_else(|| {
+                    FCMCError::BackendError(format!("Gadget input {} of node {} is missing", input, node_id))
+                })
+            })
+            .collect()
+    }
+
+    fn operands(&self, node_id: usize) -> Result<Vec<LinearCombination>, FCMCError> {
+        self.graph
+            .get_predecessors(node_id)
+            .into_iter()
+            .map(|operand| {
+                self.lcs.get(&operand).cloned().ok_or_else(|| {
+                    FCMCError::BackendError(format!("Node {} reads node {} before it is lowered", node_id, operand))
+                })
+            })
+            .collect()
+    }
+
+    fn arity_error(&self, node_id: usize) -> FCMCError {
+        FCMCError::BackendError(format!(
+            "Node {} ({:?}) has an unexpected number of operands",
+            node_id,
+            self.graph.get_node(node_id).map(|node| &node.node_type)
+        ))
+    }
+
+    fn attribute_bits(&self, node_id: usize) -> Option<u32> {
+        self.graph
+            .get_node(node_id)?
+            .attributes
+            .get("bits")
+            .and_then(|bits| bits.parse().ok())
+    }
+
+    /// Wire holding `a * b`, or a scaled combination if either side is constant
+    fn product(&mut self, a: &LinearCombination, b: &LinearCombination, name: String) -> LinearCombination {
+        if let Some(scalar) = lc_constant_value(a) {
+            return lc_scale(b, &scalar);
+        }
+        if let Some(scalar) = lc_constant_value(b) {
+            return lc_scale(a, &scalar);
+        }
+        let wire = self.allocate(WireSource::Product(a.clone(), b.clone()), name);
+        self.enforce(a.clone(), b.clone(), lc_wire(wire));
+        lc_wire(wire)
+    }
+
+    /// Little-endian bit wires of `value`, constrained boolean and to recompose it
+    fn decompose(&mut self, value: &LinearCombination, bits: u32, name: &str) -> Vec<LinearCombination> {
+        let mut recomposed = LinearCombination::new();
+        let mut weight = BigUint::one();
+        let mut result = Vec::with_capacity(bits as usize);
+        for index in 0..bits {
+            let wire = self.allocate(WireSource::Bit(value.clone(), index), format!("{}.bit{}", name, index));
+            let bit = lc_wire(wire);
+            self.enforce(bit.clone(), bit.clone(), bit.clone());
+            recomposed = lc_add_scaled(&recomposed, &bit, &weight);
+            weight = field::add(&weight, &weight);
+            result.push(bit);
+        }
+        self.enforce(recomposed, Self::one(), value.clone());
+        result
+    }
+
+    /// Wire holding bit `index` of `value`, constrained boolean. Only the graph's own
+    /// constraints tie it to `value`; gadgets extracting bits recompose them.
+    fn bit(&mut self, value: &LinearCombination, index: u32, name: &str) -> LinearCombination {
+        let wire = self.allocate(WireSource::Bit(value.clone(), index), format!("{}.bit{}", name, index));
+        let bit = lc_wire(wire);
+        self.enforce(bit.clone(), bit.clone(), bit.clone());
+        bit
+    }
+
+    /// Enforce `value < 2^bits`, natively if the target supports it
+    fn range(&mut self, value: &LinearCombination, bits: u32, name: &str) -> Result<(), FCMCError> {
+        // Values that wide wrap around the modulus, so the check would mean nothing
+        if BigUint::one() << bits >= field::modulus() {
+            return Err(FCMCError::BackendError(format!(
+                "{}: {}-bit values do not fit the {} field ({} bits)",
+                name,
+                bits,
+                field::active(),
+                field::modulus().bits()
+            )));
+        }
+        if self.options.native_ranges {
+            self.ranges.push((value.clone(), bits));
+            self.range_origins.push(self.current);
+        } else {
+            self.decompose(value, bits, name);
+        }
+        Ok(())
+    }
+
+    /// Index of `table` among the circuit's tables, sharing one with the same rows
+    fn table(&mut self, table: &LookupTable) -> Result<usize, FCMCError> {
+        Ok(self.add_function_table(FunctionTable::from_table(table)?))
+    }
+
+    fn add_function_table(&mut self, table: FunctionTable) -> usize {
+        if let Some(index) = self.tables.iter().position(|existing| existing.same_rows(&table)) {
+            return index;
+        }
+        self.tables.push(table);
+        self.tables.len() - 1
+    }
+
+    /// Enforce `value != 0` with the inverse trick
+    fn nonzero(&mut self, value: &LinearCombination, name: &str) {
+        let wire = self.allocate(WireSource::Inverse(value.clone()), format!("{}.inv", name));
+        self.enforce(value.clone(), lc_wire(wire), Self::one());
+    }
+
+    /// 1 if `value` is 0, else 0
+    fn is_zero(&mut self, value: &LinearCombination, name: &str) -> LinearCombination {
+        if let Some(constant) = lc_constant_value(value) {
+            return if constant.is_zero() { Self::one() } else { LinearCombination::new() };
+        }
+        let inverse = self.allocate(WireSource::Inverse(value.clone()), format!("{}.inv", name));
+        let result = lc_wire(self.allocate(WireSource::IsZero(value.clone()), format!("{}.is_zero", name)));
+        self.enforce(value.clone(), lc_wire(inverse), lc_sub(&Self::one(), &result));
+        self.enforce(value.clone(), result.clone(), LinearCombination::new());
+        result
+    }
+
+    /// 1 if `a >= b`, for operands below 2^bits
+    fn greater_or_equal(
+        &mut self,
+        a: &LinearCombination,
+        b: &LinearCombination,
+        bits: Option<u32>,
+        name: &str,
+    ) -> Result<LinearCombination, FCMCError> {
+        let bits = match bits {
+            Some(bits) if bits > max_comparison_bits() => {
+                return Err(FCMCError::BackendError(format!(
+                    "{}: cannot compare {}-bit operands in the {} field ({} bits)",
+                    name,
+                    bits,
+                    field::active(),
+                    field::modulus().bits()
+                )));
+            }
+            Some(bits) => bits,
+            None => DEFAULT_COMPARISON_BITS.min(max_comparison_bits()),
+        };
+        let offset = lc_constant(BigUint::one() << bits);
+        let shifted = lc_add(&lc_sub(a, b), &offset);
+        let mut decomposed = self.decompose(&shifted, bits + 1, name);
+        Ok(decomposed.pop().unwrap_or_default())
+    }
+
+    fn lower(&mut self, node_id: usize) -> Result<(), FCMCError> {
+        self.current = node_id;
+        let node = match self.graph.get_node(node_id) {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+        if self.internal.contains(&node_id) {
+            return Ok(());
+        }
+        if self.options.black_boxes {
+            if let Some((function, inputs)) = black_box(self.graph, node_id) {
+                let inputs = self.gadget_inputs(node_id, &inputs)?;
+                self.allocate_node(node_id);
+                self.black_boxes.push(BlackBoxCall {
+                    function,
+                    inputs,
+                    output: self.last_wire(),
+                });
+                return Ok(());
+            }
+        }
+        if self.options.custom_gates {
+            if let Some((gate, inputs)) = custom_gate(self.graph, node_id) {
+                let inputs = self.gadget_inputs(node_id, &inputs)?;
+                self.allocate_node(node_id);
+                self.custom_gates.push(CustomGateCall {
+                    gate,
+                    inputs,
+                    output: self.last_wire(),
+                });
+                self.custom_gate_origins.push(node_id);
+                return Ok(());
+            }
+        }
+        let name = self.node_name(node_id);
+        let operands = self.operands(node_id)?;
+        let minus_one = field::neg(&BigUint::one());
+
+        let lc = match (&node.node_type, operands.as_slice()) {
+            (IRNodeType::Input(_), _) | (IRNodeType::PrivateInput(_), _) => return Ok(()),
+            (IRNodeType::Output(_), [value]) => {
+                let output = self.lcs[&node_id].clone();
+                self.enforce(value.clone(), Self::one(), output);
+                return Ok(());
+            }
+            (IRNodeType::Constant(value), _) => lc_constant(
+                field::parse_element(value)
+                    .ok_or_else(|| FCMCError::BackendError(format!("Invalid field constant: {}", value)))?,
+            ),
+            (IRNodeType::Add, [a, b]) => lc_add(a, b),
+            (IRNodeType::Sub, [a, b]) => lc_sub(a, b),
+            (IRNodeType::Neg, [a]) => lc_scale(a, &minus_one),
+            (IRNodeType::Mul, [a, b]) | (IRNodeType::And, [a, b]) => {
+                if lc_constant_value(a).is_some() || lc_constant_value(b).is_some() {
+                    self.product(a, b, name)
+                } else {
+                    let wire = self.allocate_node(node_id);
+                    self.enforce(a.clone(), b.clone(), wire.clone());
+                    wire
+                }
+            }
+            (IRNodeType::Div, [a, b]) => match lc_constant_value(b).map(|divisor| field::inverse(&divisor)) {
+                Some(Some(inverse)) => lc_scale(a, &inverse),
+                Some(None) => {
+                    return Err(FCMCError::BackendError(format!("Division by zero at node {}", node_id)));
+                }
+                None => {
+                    let quotient = self.allocate_node(node_id);
+                    self.enforce(b.clone(), quotient.clone(), a.clone());
+                    self.nonzero(b, &name);
+                    quotient
+                }
+            },
+            // a + b - ab
+            (IRNodeType::Or, [a, b]) => {
+                let ab = self.product(a, b, format!("{}.and", name));
+                lc_sub(&lc_add(a, b), &ab)
+            }
+            // a + b - 2ab
+            (IRNodeType::Xor, [a, b]) => {
+                let ab = self.product(a, b, format!("{}.and", name));
+                lc_add_scaled(&lc_add(a, b), &ab, &field::neg(&BigUint::from(2u32)))
+            }
+            (IRNodeType::Not, [a]) => lc_sub(&Self::one(), a),
+            (IRNodeType::Eq, [a, b]) => self.is_zero(&lc_sub(a, b), &name),
+            (IRNodeType::Ne, [a, b]) => {
+                let equal = self.is_zero(&lc_sub(a, b), &name);
+                lc_sub(&Self::one(), &equal)
+            }
+            (IRNodeType::Ge, [a, b]) | (IRNodeType::Le, [b, a]) => {
+                let bits = self.attribute_bits(node_id);
+                self.greater_or_equal(a, b, bits, &name)?
+            }
+            (IRNodeType::Lt, [a, b]) | (IRNodeType::Gt, [b, a]) => {
+                let bits = self.attribute_bits(node_id);
+                let ge = self.greater_or_equal(a, b, bits, &name)?;
+                lc_sub(&Self::one(), &ge)
+            }
+            // b + c * (a - b)
+            (IRNodeType::Select, [condition, a, b]) => {
+                let picked = self.product(condition, &lc_sub(a, b), format!("{}.select", name));
+                lc_add(b, &picked)
+            }
+            (IRNodeType::BitDecomposition, [a, ..]) => match extracted_bit(node) {
+                Some(index) => self.bit(a, index, &name),
+                None => a.clone(),
+            },
+            (IRNodeType::Phi, [a, ..]) => a.clone(),
+            (IRNodeType::RangeCheck, _) => {
+                let bits = self.attribute_bits(node_id).ok_or_else(|| {
+                    FCMCError::BackendError(format!("Range check at node {} has no bit width", node_id))
+                })?;
+                for (index, value) in operands.iter().enumerate() {
+                    self.range(value, bits, &format!("{}.{}", name, index))?;
+                }
+                LinearCombination::new()
+            }
+            (IRNodeType::Lookup, _) => {
+                if let Some(table) = function_table(self.graph, node) {
+                    if !self.options.function_tables {
+                        return Err(FCMCError::BackendError(format!(
+                            "Lookup into table '{}' at node {} must be lowered before R1CS compilation",
+                            table.name, node_id
+                        )));
+                    }
+                    let table = self.table(table)?;
+                    self.allocate_node(node_id);
+                    self.table_lookups.push(TableLookupCall {
+                        table,
+                        inputs: operands.clone(),
+                        output: self.last_wire(),
+                    });
+                    self.table_lookup_origins.push(node_id);
+                    return Ok(());
+                }
+                // Range-table lookups become bit decompositions
+                let bits = self.attribute_bits(node_id).ok_or_else(|| {
+                    FCMCError::BackendError(format!("Lookup at node {} reads no known table", node_id))
+                })?;
+                for (index, value) in operands.iter().enumerate() {
+                    self.range(value, bits, &format!("{}.{}", name, index))?;
+                }
+                LinearCombination::new()
+            }
+            (IRNodeType::Constraint(constraint), _) => {
+                self.lower_constraint(node_id, constraint, &operands, &name)?;
+                LinearCombination::new()
+            }
+            _ => return Err(self.arity_error(node_id)),
+        };
+
+        let lc = if node.attributes.contains_key(WIRE_ATTRIBUTE) && lc_constant_value(&lc).is_none() {
+            let wire = self.allocate_node(node_id);
+            self.enforce(lc, Self::one(), wire.clone());
+            wire
+        } else {
+            lc
+        };
+        self.lcs.insert(node_id, lc);
+        Ok(())
+    }
+
+    fn lower_constraint(
+        &mut self,
+        node_id: usize,
+        constraint: &ConstraintType,
+        operands: &[LinearCombination],
+        name: &str,
+    ) -> Result<(), FCMCError> {
+        match (constraint, operands) {
+            (ConstraintType::Equality, [value]) => self.enforce(value.clone(), Self::one(), Self::one()),
+            (ConstraintType::Equality, [a, b]) => self.enforce(lc_sub(a, b), Self::one(), LinearCombination::new()),
+            (ConstraintType::Inequality, [value]) => self.nonzero(value, name),
+            (ConstraintType::Inequality, [a, b]) => self.nonzero(&lc_sub(a, b), name),
+            (ConstraintType::Range { bits }, [value]) => self.range(value, *bits, name)?,
+            // Horner: ((c_n x + c_{n-1}) x + ...) x + c_0 = 0
+            (ConstraintType::Polynomial { coefficients }, [x]) => {
+                let mut acc = LinearCombination::new();
+                for (degree, coefficient) in coefficients.iter().enumerate().rev() {
+                    let coefficient = field::parse_element(coefficient)
+                        .ok_or_else(|| FCMCError::BackendError(format!("Invalid field constant: {}", coefficient)))?;
+                    acc = self.product(&acc, x, format!("{}.horner{}", name, degree));
+                    acc = lc_add(&acc, &lc_constant(coefficient));
+                }
+                self.enforce(acc, Self::one(), LinearCombination::new());
+            }
+            _ => return Err(self.arity_error(node_id)),
+        }
+        Ok(())
+    }
+}
+
+/// Live nodes grouped by depth, each level reading only nodes of earlier levels, in
+/// topological order within a level
+fn lowering_levels(graph: &IRGraph) -> Vec<Vec<usize>> {
+    let mut depths: HashMap<usize, usize> = HashMap::new();
+    let mut levels: Vec<Vec<usize>> = Vec::new();
+    for node_id in graph.topological_sort() {
+        let depth = graph
+            .get_predecessors(node_id)
+            .iter()
+            .filter_map(|operand| depths.get(operand))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(node_id, depth);
+        if levels.len() <= depth {
+            levels.resize_with(depth + 1, Vec::new);
+        }
+        levels[depth].push(node_id);
+    }
+    levels
+}
+
+/// Nodes whose values only flow into black-box gadgets or custom gates, so lowering them
+/// is wasted
+pub(crate) fn gadget_internals(graph: &IRGraph, options: &LoweringOptions) -> HashSet<usize> {
+    if !options.black_boxes && !options.custom_gates {
+        return HashSet::new();
+    }
+    let gadget_inputs = |node_id: usize| {
+        let black_box = options.black_boxes.then(|| black_box(graph, node_id)).flatten();
+        match black_box {
+            Some((_, inputs)) => Some(inputs),
+            None => options
+                .custom_gates
+                .then(|| custom_gate(graph, node_id))
+                .flatten()
+                .map(|(_, inputs)| inputs),
+        }
+    };
+    let mut needed = HashSet::new();
+    let mut stack: Vec<usize> = graph
+        .live_nodes()
+        .filter(|node| graph.get_successors(node.id).is_empty())
+        .map(|node| node.id)
+        .collect();
+    while let Some(node_id) = stack.pop() {
+        if !needed.insert(node_id) {
+            continue;
+        }
+        match gadget_inputs(node_id) {
+            Some(inputs) => stack.extend(inputs),
+            None => stack.extend(graph.get_predecessors(node_id)),
+        }
+    }
+    graph
+        .live_nodes()
+        .map(|node| node.id)
+        .filter(|node_id| !needed.contains(node_id))
+        .collect()
+}
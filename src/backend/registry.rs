@@ -0,0 +1,60 @@
+//! Backends registered at run time, so downstream crates can add proof systems this
+//! crate does not know about. A registered backend is selected with
+//! `TargetSystem::Custom(name)`.
+
+use crate::backend::{BackendCapabilities, CircuitBackend};
+use crate::ir::IRGraph;
+use crate::optimization::cost::CostModel;
+use crate::FCMCError;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Compiles legalized IR for one custom target
+pub trait BackendFactory: Send + Sync {
+    /// Consulted by the optimizer and the legalization step, as for built-in targets
+    fn capabilities(&self) -> BackendCapabilities;
+
+    fn cost_model(&self) -> CostModel {
+        CostModel::default()
+    }
+
+    /// Lower `ir`; runs with the compilation's field active
+    fn compile(&self, ir: &IRGraph) -> Result<Box<dyn CircuitBackend>, FCMCError>;
+}
+
+type Factories = BTreeMap<String, Arc<dyn BackendFactory>>;
+
+fn factories() -> &'static RwLock<Factories> {
+    static FACTORIES: OnceLock<RwLock<Factories>> = OnceLock::new();
+    FACTORIES.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+/// Register a backend under `name`, replacing any existing backend with that name
+pub fn register(name: &str, factory: Box<dyn BackendFactory>) {
+    let mut factories = factories().write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    factories.insert(name.to_string(), Arc::from(factory));
+}
+
+/// Remove the backend registered under `name`; returns whether there was one
+pub fn unregister(name: &str) -> bool {
+    let mut factories = factories().write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    factories.remove(name).is_some()
+}
+
+pub fn factory(name: &str) -> Option<Arc<dyn BackendFactory>> {
+    let factories = factories().read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    factories.get(name).cloned()
+}
+
+/// Names of every registered backend, sorted
+pub fn registered() -> Vec<String> {
+    let factories = factories().read().unwrap_or_else(|poisoned| poisoned.into_inner());
+    factories.keys().cloned().collect()
+}
+
+pub(crate) fn compile(name: &str, ir: &IRGraph) -> Result<Box<dyn CircuitBackend>, FCMCError> {
+    match factory(name) {
+        Some(factory) => factory.compile(ir),
+        None => Err(FCMCError::BackendError(format!("No backend registered for target '{}'", name))),
+    }
+}
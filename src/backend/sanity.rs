@@ -0,0 +1,167 @@
+//! Injection of the safety constraints a circuit needs but its source may have left out.
+//!
+//! Lowering assumes some facts it does not prove: a `Select` condition is 0 or 1, a lookup
+//! index fits the table's input widths, and a divisor is not zero. Where neither the IR
+//! nor the target's own lowering establishes such a fact, the pass adds the constraint
+//! for it and reports what it added.
+
+use crate::backend::TargetSystem;
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNodeType, MESSAGE_ATTRIBUTE};
+use crate::language::ast::Type;
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::interpreter::apply;
+use crate::optimization::lookup::{function_table, small_domain_wires};
+use num_traits::Zero;
+use std::collections::HashSet;
+
+/// The fact an injected constraint enforces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SanityCheck {
+    /// The wire is a `Select` condition, so it must be 0 or 1
+    Booleanity,
+    /// The wire is a lookup index, so it must fit the table's input width
+    SelectorRange { bits: u32 },
+    /// The wire is a divisor, so it must not be zero
+    NonZero,
+}
+
+impl SanityCheck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SanityCheck::Booleanity => "booleanity",
+            SanityCheck::SelectorRange { .. } => "selector range",
+            SanityCheck::NonZero => "non-zero",
+        }
+    }
+}
+
+/// One constraint the pass added
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedCheck {
+    pub check: SanityCheck,
+    /// Node the check constrains
+    pub wire: usize,
+    /// Node that reads the wire and needs the fact
+    pub user: usize,
+    /// The added constraint node
+    pub constraint: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SanityReport {
+    pub injected: Vec<InjectedCheck>,
+}
+
+impl SanityReport {
+    pub fn count(&self, check: SanityCheck) -> usize {
+        self.injected.iter().filter(|injected| injected.check == check).count()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.injected
+            .iter()
+            .map(|injected| match injected.check {
+                SanityCheck::SelectorRange { bits } => format!(
+                    "injected {}-bit {} check on node {} for node {}",
+                    bits,
+                    injected.check.as_str(),
+                    injected.wire,
+                    injected.user
+                ),
+                check => format!(
+                    "injected {} check on node {} for node {}",
+                    check.as_str(),
+                    injected.wire,
+                    injected.user
+                ),
+            })
+            .collect()
+    }
+}
+
+/// Whether lowering for `target` already proves lookup indices in range and divisors
+/// non-zero. The built-in targets share the R1CS lowering, which does; a registered
+/// backend is not trusted to.
+fn lowering_checks_gadgets(target: &TargetSystem) -> bool {
+    !matches!(target, TargetSystem::Custom(_))
+}
+
+/// Add the booleanity, selector range and non-zero constraints `graph` is missing for
+/// `target`
+pub fn inject_sanity_checks(graph: &mut IRGraph, target: &TargetSystem) -> SanityReport {
+    let booleans = BooleanityAnalysis::run(graph);
+    let widths = small_domain_wires(graph, &booleans);
+    let gadgets = !lowering_checks_gadgets(target);
+    let nonzero: HashSet<usize> = graph
+        .live_nodes()
+        .filter(|node| node.node_type == IRNodeType::Constraint(ConstraintType::Inequality))
+        .filter_map(|node| match graph.get_predecessors(node.id).as_slice() {
+            [wire] => Some(*wire),
+            _ => None,
+        })
+        .collect();
+
+    // Collected first, then added once per wire and fact
+    let mut needed: Vec<(SanityCheck, usize, usize)> = Vec::new();
+    for node in graph.live_nodes() {
+        let operands = graph.get_predecessors(node.id);
+        match (&node.node_type, operands.as_slice()) {
+            (IRNodeType::Select, [condition, ..]) if !booleans.is_boolean(*condition) => {
+                needed.push((SanityCheck::Booleanity, *condition, node.id));
+            }
+            (IRNodeType::Lookup, _) if gadgets => {
+                if let Some(table) = function_table(graph, node) {
+                    for (&input, &bits) in operands.iter().zip(&table.input_bits) {
+                        if widths.get(&input).map_or(true, |&width| width > bits) {
+                            needed.push((SanityCheck::SelectorRange { bits }, input, node.id));
+                        }
+                    }
+                }
+            }
+            (IRNodeType::Div, [_, divisor]) if gadgets && !nonzero.contains(divisor) => {
+                if !is_nonzero_constant(graph, *divisor) {
+                    needed.push((SanityCheck::NonZero, *divisor, node.id));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut report = SanityReport::default();
+    let mut seen = HashSet::new();
+    for (check, wire, user) in needed {
+        if !seen.insert((check, wire)) {
+            continue;
+        }
+        let (constraint, message) = match check {
+            SanityCheck::Booleanity => (
+                ConstraintType::Range { bits: 1 },
+                format!("condition of node {} is not 0 or 1", user),
+            ),
+            SanityCheck::SelectorRange { bits } => (
+                ConstraintType::Range { bits },
+                format!("lookup index of node {} does not fit {} bits", user, bits),
+            ),
+            SanityCheck::NonZero => (ConstraintType::Inequality, format!("divisor of node {} is zero", user)),
+        };
+        let constraint = graph.add_node(IRNodeType::Constraint(constraint), Type::Bool, None);
+        graph.add_edge(wire, constraint, EdgeType::Constraint);
+        if let Some(node) = graph.get_node_mut(constraint) {
+            node.attributes.insert(MESSAGE_ATTRIBUTE.to_string(), message);
+        }
+        report.injected.push(InjectedCheck {
+            check,
+            wire,
+            user,
+            constraint,
+        });
+    }
+    report
+}
+
+fn is_nonzero_constant(graph: &IRGraph, node_id: usize) -> bool {
+    match graph.get_node(node_id).map(|node| &node.node_type) {
+        Some(node_type @ IRNodeType::Constant(_)) => apply(node_type, &[]).map_or(false, |value| !value.is_zero()),
+        _ => false,
+    }
+}
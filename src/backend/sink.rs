@@ -0,0 +1,23 @@
+//! Consumers of constraints as the lowering produces them. Streaming a circuit into a
+//! sink holds only the constraints of the node being lowered in memory, so circuits far
+//! larger than RAM can still be written out.
+
+use crate::backend::r1cs::{R1CSCircuit, R1CSConstraint};
+use crate::FCMCError;
+
+pub trait CircuitSink {
+    fn push_constraint(&mut self, constraint: R1CSConstraint) -> Result<(), FCMCError>;
+
+    /// Called once after the last constraint with the circuit's wires; the layout's
+    /// `constraints` are empty
+    fn finish(&mut self, _layout: &R1CSCircuit) -> Result<(), FCMCError> {
+        Ok(())
+    }
+}
+
+impl CircuitSink for Vec<R1CSConstraint> {
+    fn push_constraint(&mut self, constraint: R1CSConstraint) -> Result<(), FCMCError> {
+        self.push(constraint);
+        Ok(())
+    }
+}
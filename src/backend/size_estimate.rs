@@ -0,0 +1,250 @@
+//! Size estimates of a circuit for a target without lowering it.
+//!
+//! Each node is counted as the rows and wires the shared R1CS lowering gives it, read off
+//! its type, bit width and operands instead of building its linear combinations; targets
+//! other than R1CS then scale those counts by their cost model. Linear combinations that
+//! fold to constants through other nodes, and wire minimization, make the real circuit
+//! somewhat smaller, so the estimate is an upper bound in practice.
+
+use crate::backend::lowering::{black_box, custom_gate};
+use crate::backend::r1cs::{gadget_internals, max_comparison_bits, DEFAULT_COMPARISON_BITS};
+use crate::backend::TargetSystem;
+use crate::ir::{ConstraintType, IRGraph, IRNodeType};
+use crate::optimization::cost::CostModel;
+use crate::optimization::field::{self, FieldConfig};
+use crate::optimization::interpreter::extracted_bit;
+use crate::optimization::lookup::function_table;
+use crate::optimization::sparsity::{r1cs_nonzeros, WIRE_ATTRIBUTE};
+use serde_json::{json, Value};
+
+/// Selector and wiring columns of a Plonkish gate, each a field element per row
+const GATE_COLUMNS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeEstimate {
+    pub target: TargetSystem,
+    /// Rows of the constraint system: R1CS constraints, ACIR opcodes, or the gates of
+    /// Plonkish and AIR traces
+    pub rows: usize,
+    /// Witness values, including the constant one
+    pub variables: usize,
+    /// Range checks, lookups and gadget calls the target proves natively
+    pub native_calls: usize,
+    /// Nonzero entries of the A, B and C matrices of the R1CS lowering
+    pub nonzeros: usize,
+    /// Bytes the prover holds for the witness and the constraint system
+    pub memory_bytes: usize,
+}
+
+impl SizeEstimate {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "target": format!("{:?}", self.target),
+            "rows": self.rows,
+            "variables": self.variables,
+            "native_calls": self.native_calls,
+            "nonzeros": self.nonzeros,
+            "memory_bytes": self.memory_bytes,
+        })
+    }
+}
+
+/// Rows and wires of the R1CS lowering, before the target's own rework
+#[derive(Debug, Default)]
+struct R1csCounts {
+    rows: usize,
+    wires: usize,
+    native_calls: usize,
+    /// Linear nodes, free in R1CS but a gate each on Plonkish targets
+    linear: usize,
+    public: usize,
+}
+
+impl R1csCounts {
+    fn add(&mut self, rows: usize, wires: usize) {
+        self.rows += rows;
+        self.wires += wires;
+    }
+
+    /// A range check of `bits`, native or as a bit decomposition
+    fn range(&mut self, bits: u32, native: bool) {
+        if native {
+            self.native_calls += 1;
+        } else {
+            self.add(bits as usize + 1, bits as usize);
+        }
+    }
+}
+
+/// Estimate the size of `ir`, legalized for `target`, in the target's default field
+pub fn estimate(ir: &IRGraph, target: TargetSystem) -> SizeEstimate {
+    let field = target.default_field();
+    estimate_in(ir, target, &field)
+}
+
+/// Estimate the size of `ir`, legalized for `target`, over `field`
+pub fn estimate_in(ir: &IRGraph, target: TargetSystem, field: &FieldConfig) -> SizeEstimate {
+    field.enter(|| estimate_in_field(ir, target))
+}
+
+fn estimate_in_field(ir: &IRGraph, target: TargetSystem) -> SizeEstimate {
+    let counts = r1cs_counts(ir, &target);
+    let cost = CostModel::for_target(target.clone());
+    let element_bytes = (field::modulus().bits() as usize).div_ceil(8);
+    let nonzeros = r1cs_nonzeros(ir);
+
+    let (rows, variables) = match target {
+        TargetSystem::R1CS | TargetSystem::CCS | TargetSystem::Custom(_) => (counts.rows, counts.wires),
+        TargetSystem::ACIR => (counts.rows + counts.native_calls, counts.wires),
+        // Public values take a row each, and linear nodes a gate and a variable
+        TargetSystem::Plonk | TargetSystem::Halo2 | TargetSystem::Plonky2 | TargetSystem::AIR => (
+            counts.public + counts.rows + counts.native_calls + counts.linear * cost.add as usize,
+            counts.wires + counts.linear,
+        ),
+    };
+    let system_bytes = match target {
+        TargetSystem::Plonk | TargetSystem::Halo2 | TargetSystem::Plonky2 | TargetSystem::AIR => {
+            rows * GATE_COLUMNS * element_bytes
+        }
+        _ => nonzeros * (element_bytes + std::mem::size_of::<usize>()),
+    };
+    SizeEstimate {
+        target,
+        rows,
+        variables,
+        native_calls: counts.native_calls,
+        nonzeros,
+        memory_bytes: variables * element_bytes + system_bytes,
+    }
+}
+
+fn r1cs_counts(ir: &IRGraph, target: &TargetSystem) -> R1csCounts {
+    let options = target.capabilities().lowering_options();
+    let internal = gadget_internals(ir, &options);
+    let is_constant =
+        |node_id: &usize| matches!(ir.get_node(*node_id).map(|node| &node.node_type), Some(IRNodeType::Constant(_)));
+    let bits_of = |node_id: usize| {
+        ir.get_node(node_id)
+            .and_then(|node| node.attributes.get("bits"))
+            .and_then(|bits| bits.parse::<u32>().ok())
+    };
+
+    // The constant one
+    let mut counts = R1csCounts {
+        wires: 1,
+        ..R1csCounts::default()
+    };
+    for node in ir.live_nodes() {
+        if internal.contains(&node.id) {
+            continue;
+        }
+        if (options.black_boxes && black_box(ir, node.id).is_some())
+            || (options.custom_gates && custom_gate(ir, node.id).is_some())
+        {
+            counts.native_calls += 1;
+            counts.wires += 1;
+            continue;
+        }
+        let operands = ir.get_predecessors(node.id);
+        let any_constant = operands.iter().any(is_constant);
+        let linear = match (&node.node_type, operands.as_slice()) {
+            (IRNodeType::Input(_), _) => {
+                counts.public += 1;
+                counts.wires += 1;
+                false
+            }
+            (IRNodeType::PrivateInput(_), _) => {
+                counts.wires += 1;
+                false
+            }
+            (IRNodeType::Output(_), _) => {
+                counts.public += 1;
+                counts.add(1, 1);
+                false
+            }
+            (IRNodeType::Constant(_), _) => false,
+            // An extracted bit is a hint wire with a booleanity row
+            (IRNodeType::BitDecomposition, _) if extracted_bit(node).is_some() => {
+                counts.add(1, 1);
+                false
+            }
+            (IRNodeType::Add, _)
+            | (IRNodeType::Sub, _)
+            | (IRNodeType::Neg, _)
+            | (IRNodeType::Not, _)
+            | (IRNodeType::Phi, _)
+            | (IRNodeType::BitDecomposition, _) => true,
+            (IRNodeType::Mul, _) | (IRNodeType::And, _) | (IRNodeType::Or, _) | (IRNodeType::Xor, _) => {
+                if any_constant {
+                    true
+                } else {
+                    counts.add(1, 1);
+                    false
+                }
+            }
+            (IRNodeType::Div, [_, divisor]) => {
+                if is_constant(divisor) {
+                    true
+                } else {
+                    // Quotient and the divisor's inverse
+                    counts.add(2, 2);
+                    false
+                }
+            }
+            (IRNodeType::Eq, _) | (IRNodeType::Ne, _) => {
+                counts.add(2, 2);
+                false
+            }
+            (IRNodeType::Lt, _) | (IRNodeType::Le, _) | (IRNodeType::Gt, _) | (IRNodeType::Ge, _) => {
+                let bits = bits_of(node.id).unwrap_or(DEFAULT_COMPARISON_BITS.min(max_comparison_bits()));
+                counts.range(bits + 1, false);
+                false
+            }
+            (IRNodeType::Select, _) => {
+                counts.add(1, 1);
+                false
+            }
+            (IRNodeType::RangeCheck, _) => {
+                let bits = bits_of(node.id).unwrap_or(0);
+                for _ in &operands {
+                    counts.range(bits, options.native_ranges);
+                }
+                false
+            }
+            (IRNodeType::Lookup, _) => {
+                if function_table(ir, node).is_some() {
+                    counts.native_calls += 1;
+                    counts.wires += 1;
+                } else {
+                    let bits = bits_of(node.id).unwrap_or(0);
+                    for _ in &operands {
+                        counts.range(bits, options.native_ranges);
+                    }
+                }
+                false
+            }
+            (IRNodeType::Constraint(constraint), _) => {
+                match constraint {
+                    ConstraintType::Equality => counts.add(1, 0),
+                    ConstraintType::Inequality => counts.add(1, 1),
+                    ConstraintType::Range { bits } => counts.range(*bits, options.native_ranges),
+                    // One product per degree after the leading one, and the final check
+                    ConstraintType::Polynomial { coefficients } => {
+                        let products = coefficients.len().saturating_sub(1);
+                        counts.add(products + 1, products);
+                    }
+                }
+                false
+            }
+            _ => false,
+        };
+        if linear {
+            if node.attributes.contains_key(WIRE_ATTRIBUTE) {
+                counts.add(1, 1);
+            } else {
+                counts.linear += 1;
+            }
+        }
+    }
+    counts
+}
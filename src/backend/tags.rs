@@ -0,0 +1,70 @@
+//! Tags on constraint rows: the source position, gadget and failure message of the IR
+//! node each row was lowered from, so profilers and debuggers can map rows back to the
+//! program and pick out the rows of one line, gadget or check.
+
+use crate::backend::lowering::BLACKBOX_ATTRIBUTE;
+use crate::ir::{IRGraph, SourceSpan, MESSAGE_ATTRIBUTE};
+use crate::optimization::subcircuit::GADGET_ATTRIBUTE;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConstraintTag {
+    pub span: Option<SourceSpan>,
+    /// Repeated subcircuit or black-box function the row belongs to
+    pub gadget: Option<String>,
+    /// What a failure of the row means, such as the assertion it checks
+    pub message: Option<String>,
+}
+
+impl ConstraintTag {
+    /// Tag of rows lowered from `node_id`; `None` if the node carries nothing to tag
+    pub fn of(graph: &IRGraph, node_id: usize) -> Option<Self> {
+        let node = graph.get_node(node_id)?;
+        let gadget = node
+            .attributes
+            .get(GADGET_ATTRIBUTE)
+            .or_else(|| node.attributes.get(BLACKBOX_ATTRIBUTE))
+            .cloned();
+        let tag = Self {
+            span: graph.span(node_id),
+            gadget,
+            message: node.attributes.get(MESSAGE_ATTRIBUTE).cloned(),
+        };
+        if tag == Self::default() {
+            None
+        } else {
+            Some(tag)
+        }
+    }
+}
+
+/// Tags of the nodes in `origins`, by node id
+pub fn node_tags(graph: &IRGraph, origins: impl IntoIterator<Item = usize>) -> HashMap<usize, ConstraintTag> {
+    origins
+        .into_iter()
+        .filter_map(|node_id| Some((node_id, ConstraintTag::of(graph, node_id)?)))
+        .collect()
+}
+
+/// Which tagged rows to select
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagQuery {
+    /// Rows lowered from anywhere on the source line
+    Line(usize),
+    Gadget(String),
+    /// Rows whose message contains the text
+    Message(String),
+}
+
+impl TagQuery {
+    pub fn matches(&self, tag: &ConstraintTag) -> bool {
+        match self {
+            TagQuery::Line(line) => tag.span.map_or(false, |span| span.line == *line),
+            TagQuery::Gadget(name) => tag.gadget.as_deref() == Some(name.as_str()),
+            TagQuery::Message(text) => tag
+                .message
+                .as_deref()
+                .map_or(false, |message| message.contains(text.as_str())),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! The compiled circuit in the concrete type of its target, so callers reach gate lists,
+//! matrices and other target-specific data without downcasting.
+
+use crate::backend::lowering::WireSource;
+use crate::backend::{
+    AcirCircuit, AirCircuit, BackendCapabilities, CcsCircuit, CircuitBackend, ConstraintTag, PlonkCircuit,
+    Plonky2Circuit, R1CSCircuit, TargetSystem,
+};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::any::Any;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub enum TargetCircuit {
+    R1CS(R1CSCircuit),
+    /// `Plonk` and `Halo2` circuits
+    Plonk(PlonkCircuit),
+    AIR(AirCircuit),
+    ACIR(AcirCircuit),
+    Plonky2(Plonky2Circuit),
+    CCS(CcsCircuit),
+    /// A circuit from a backend added with `register`
+    Custom(Box<dyn CircuitBackend>),
+}
+
+impl TargetCircuit {
+    /// The circuit behind the common interface
+    pub fn backend(&self) -> &dyn CircuitBackend {
+        match self {
+            TargetCircuit::R1CS(circuit) => circuit,
+            TargetCircuit::Plonk(circuit) => circuit,
+            TargetCircuit::AIR(circuit) => circuit,
+            TargetCircuit::ACIR(circuit) => circuit,
+            TargetCircuit::Plonky2(circuit) => circuit,
+            TargetCircuit::CCS(circuit) => circuit,
+            TargetCircuit::Custom(circuit) => circuit.as_ref(),
+        }
+    }
+
+    pub fn as_r1cs(&self) -> Option<&R1CSCircuit> {
+        match self {
+            TargetCircuit::R1CS(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+
+    pub fn as_plonk(&self) -> Option<&PlonkCircuit> {
+        match self {
+            TargetCircuit::Plonk(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+
+    pub fn as_air(&self) -> Option<&AirCircuit> {
+        match self {
+            TargetCircuit::AIR(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+
+    pub fn as_acir(&self) -> Option<&AcirCircuit> {
+        match self {
+            TargetCircuit::ACIR(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+
+    pub fn as_plonky2(&self) -> Option<&Plonky2Circuit> {
+        match self {
+            TargetCircuit::Plonky2(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+
+    pub fn as_ccs(&self) -> Option<&CcsCircuit> {
+        match self {
+            TargetCircuit::CCS(circuit) => Some(circuit),
+            _ => None,
+        }
+    }
+}
+
+impl CircuitBackend for TargetCircuit {
+    fn target(&self) -> TargetSystem {
+        self.backend().target()
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.backend().capabilities()
+    }
+
+    fn constraint_count(&self) -> usize {
+        self.backend().constraint_count()
+    }
+
+    fn wire_count(&self) -> usize {
+        self.backend().wire_count()
+    }
+
+    fn wire_sources(&self) -> &[WireSource] {
+        self.backend().wire_sources()
+    }
+
+    fn wire_names(&self) -> &[String] {
+        self.backend().wire_names()
+    }
+
+    fn assign(&self, values: &HashMap<usize, BigUint>) -> Result<Vec<BigUint>, FCMCError> {
+        self.backend().assign(values)
+    }
+
+    fn is_satisfied(&self, witness: &[BigUint]) -> bool {
+        self.backend().is_satisfied(witness)
+    }
+
+    fn unsatisfied_row(&self, witness: &[BigUint]) -> Option<(usize, Vec<(String, BigUint)>)> {
+        self.backend().unsatisfied_row(witness)
+    }
+
+    fn constraint_tags(&self) -> Vec<Option<&ConstraintTag>> {
+        self.backend().constraint_tags()
+    }
+
+    fn to_json(&self) -> Value {
+        self.backend().to_json()
+    }
+
+    /// The concrete circuit, so downcasts see through the enum
+    fn as_any(&self) -> &dyn Any {
+        self.backend().as_any()
+    }
+}
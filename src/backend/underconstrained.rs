@@ -0,0 +1,305 @@
+//! Detection of witness wires the constraints do not pin down once the inputs are fixed.
+//!
+//! A wire no constraint reads can take any value. Other wires are checked at satisfying
+//! witnesses for random inputs: the constraints' Jacobian with respect to the non-input
+//! wires is reduced over the field, and a wire is determined only if its unit vector lies
+//! in the Jacobian's row space. A wire that is free to first order in every sample lets a
+//! malicious prover pick its value. Wires determined only up to a finite choice, such as
+//! a square root, pass the test.
+
+use crate::backend::lowering::{lc_evaluate, LinearCombination};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::CircuitBackend;
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::optimization::interpreter::{evaluate, input_names};
+use crate::optimization::soundness::random_value;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Satisfying witnesses to test at unless configured otherwise
+pub const DEFAULT_UNDERCONSTRAINT_SAMPLES: usize = 4;
+
+/// Random input draws per sample before giving up on satisfying the constraints
+const ATTEMPTS_PER_SAMPLE: usize = 16;
+
+const SEED: u64 = 0x0dd_5eed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Underconstraint {
+    /// No constraint reads the wire
+    Unconstrained,
+    /// Constraints read the wire but admit other values for it
+    Underdetermined,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnderconstrainedWire {
+    pub wire: usize,
+    pub name: String,
+    pub kind: Underconstraint,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnderconstraintReport {
+    pub wires: Vec<UnderconstrainedWire>,
+    /// Satisfying witnesses the rank test ran at; 0 means only the structural check ran
+    pub samples: usize,
+}
+
+impl UnderconstraintReport {
+    pub fn is_sound(&self) -> bool {
+        self.wires.is_empty()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.wires
+            .iter()
+            .map(|wire| match wire.kind {
+                Underconstraint::Unconstrained => format!("wire {} ({}) is not constrained", wire.wire, wire.name),
+                Underconstraint::Underdetermined => format!(
+                    "wire {} ({}) is not determined by the inputs in any of {} samples",
+                    wire.wire, wire.name, self.samples
+                ),
+            })
+            .collect()
+    }
+}
+
+/// Find the wires of `circuit`, lowered from `graph`, that the inputs do not determine,
+/// testing at up to `samples` satisfying witnesses
+pub fn find_underconstrained(
+    circuit: &R1CSCircuit,
+    graph: &IRGraph,
+    samples: usize,
+) -> Result<UnderconstraintReport, FCMCError> {
+    circuit.field.enter(|| {
+        let inputs = 1 + circuit.public_outputs..1 + circuit.public_count() + circuit.private_inputs;
+        // The target computes and checks the outputs of native calls itself
+        let native: HashSet<usize> = circuit
+            .black_boxes
+            .iter()
+            .map(|call| call.output)
+            .chain(circuit.custom_gates.iter().map(|call| call.output))
+            .chain(circuit.table_lookups.iter().map(|call| call.output))
+            .collect();
+        let checked: Vec<usize> = (1..circuit.wires.len())
+            .filter(|wire| !inputs.contains(wire) && !native.contains(wire))
+            .collect();
+
+        let native_inputs = circuit
+            .black_boxes
+            .iter()
+            .flat_map(|call| &call.inputs)
+            .chain(circuit.custom_gates.iter().flat_map(|call| &call.inputs))
+            .chain(circuit.table_lookups.iter().flat_map(|call| &call.inputs))
+            .chain(circuit.ranges.iter().map(|(value, _)| value));
+        let read: HashSet<usize> = circuit
+            .constraints
+            .iter()
+            .flat_map(|constraint| [&constraint.a, &constraint.b, &constraint.c])
+            .chain(native_inputs)
+            .flat_map(|lc| lc.keys())
+            .copied()
+            .collect();
+        let mut report = UnderconstraintReport::default();
+        for &wire in checked.iter().filter(|wire| !read.contains(wire)) {
+            report
+                .wires
+                .push(flagged(circuit, wire, Underconstraint::Unconstrained));
+        }
+
+        // Wires free at every sample so far
+        let mut free: HashSet<usize> = checked.iter().copied().filter(|wire| read.contains(wire)).collect();
+        let names = input_names(graph);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for _ in 0..samples * ATTEMPTS_PER_SAMPLE {
+            if report.samples == samples || free.is_empty() {
+                break;
+            }
+            let assignment: HashMap<String, BigUint> = names
+                .iter()
+                .map(|name| (name.clone(), random_value(&mut rng)))
+                .collect();
+            let witness = match satisfying_witness(circuit, graph, &assignment) {
+                Some(witness) => witness,
+                None => continue,
+            };
+            report.samples += 1;
+            let determined = determined_wires(circuit, &witness, &free);
+            free.retain(|wire| !determined.contains(wire));
+        }
+
+        if report.samples > 0 {
+            let mut free: Vec<usize> = free.into_iter().collect();
+            free.sort_unstable();
+            for wire in free {
+                report
+                    .wires
+                    .push(flagged(circuit, wire, Underconstraint::Underdetermined));
+            }
+        }
+        report.wires.sort_by_key(|wire| wire.wire);
+        Ok(report)
+    })
+}
+
+fn flagged(circuit: &R1CSCircuit, wire: usize, kind: Underconstraint) -> UnderconstrainedWire {
+    UnderconstrainedWire {
+        wire,
+        name: circuit.wire_names.get(wire).cloned().unwrap_or_default(),
+        kind,
+    }
+}
+
+pub(crate) fn satisfying_witness(
+    circuit: &R1CSCircuit,
+    graph: &IRGraph,
+    inputs: &HashMap<String, BigUint>,
+) -> Option<Vec<BigUint>> {
+    let evaluation = evaluate(graph, inputs).ok()?;
+    if !evaluation.is_satisfied() {
+        return None;
+    }
+    let witness = circuit.assign(&evaluation.values).ok()?;
+    circuit
+        .constraints
+        .iter()
+        .all(|constraint| constraint.is_satisfied(&witness))
+        .then_some(witness)
+}
+
+/// Wires of `candidates` whose unit vector is in the row space of the constraints'
+/// Jacobian at `witness`, taken with respect to the candidates
+fn determined_wires(circuit: &R1CSCircuit, witness: &[BigUint], candidates: &HashSet<usize>) -> HashSet<usize> {
+    let mut echelon = Echelon::default();
+    for constraint in &circuit.constraints {
+        let a = lc_evaluate(&constraint.a, witness);
+        let b = lc_evaluate(&constraint.b, witness);
+        // d/dx (A·w)(B·w) - C·w = A_x (B·w) + B_x (A·w) - C_x
+        let mut row = LinearCombination::new();
+        let terms = [
+            (&constraint.a, &b),
+            (&constraint.b, &a),
+            (&constraint.c, &field::neg(&BigUint::one())),
+        ];
+        for (lc, scale) in terms {
+            for (wire, coefficient) in lc.iter().filter(|(wire, _)| candidates.contains(wire)) {
+                let entry = row.entry(*wire).or_insert_with(BigUint::zero);
+                *entry = field::add(entry, &field::mul(coefficient, scale));
+            }
+        }
+        row.retain(|_, coefficient| !coefficient.is_zero());
+        echelon.insert(row);
+    }
+    echelon
+        .pivots
+        .iter()
+        .filter(|(_, row)| row.len() == 1)
+        .map(|(&wire, _)| wire)
+        .collect()
+}
+
+/// Rows in reduced row echelon form, by pivot column, each scaled so its pivot is one
+#[derive(Default)]
+struct Echelon {
+    pivots: BTreeMap<usize, LinearCombination>,
+}
+
+impl Echelon {
+    fn insert(&mut self, mut row: LinearCombination) {
+        // Clear the existing pivot columns from the new row
+        let pivots: Vec<usize> = row
+            .keys()
+            .copied()
+            .filter(|wire| self.pivots.contains_key(wire))
+            .collect();
+        for pivot in pivots {
+            let factor = match row.get(&pivot) {
+                Some(factor) => factor.clone(),
+                None => continue,
+            };
+            subtract_scaled(&mut row, &self.pivots[&pivot], &factor);
+        }
+        let (&pivot, coefficient) = match row.iter().next() {
+            Some(first) => first,
+            None => return,
+        };
+        let inverse = field::inverse(coefficient).expect("nonzero entries are invertible");
+        for coefficient in row.values_mut() {
+            *coefficient = field::mul(coefficient, &inverse);
+        }
+
+        // And the new pivot column from the existing rows
+        for existing in self.pivots.values_mut() {
+            if let Some(factor) = existing.get(&pivot).cloned() {
+                subtract_scaled(existing, &row, &factor);
+            }
+        }
+        self.pivots.insert(pivot, row);
+    }
+}
+
+/// `row -= factor * other`, dropping entries that cancel
+fn subtract_scaled(row: &mut LinearCombination, other: &LinearCombination, factor: &BigUint) {
+    for (wire, coefficient) in other {
+        let entry = row.entry(*wire).or_insert_with(BigUint::zero);
+        *entry = field::sub(entry, &field::mul(coefficient, factor));
+    }
+    row.retain(|_, coefficient| !coefficient.is_zero());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::lowering::lc_wire;
+    use crate::backend::r1cs::R1CSConstraint;
+    use crate::{CompiledCircuit, FCMC};
+
+    fn product() -> CompiledCircuit {
+        FCMC::new()
+            .compile("fn main(x: field, y: field) -> field { return x * y; }")
+            .unwrap()
+    }
+
+    fn kinds(report: &UnderconstraintReport) -> Vec<(String, Underconstraint)> {
+        report.wires.iter().map(|wire| (wire.name.clone(), wire.kind)).collect()
+    }
+
+    #[test]
+    fn product_output_is_determined() {
+        let compiled = product();
+        let report = find_underconstrained(compiled.circuit.as_r1cs().unwrap(), &compiled.ir, 4).unwrap();
+        assert!(report.is_sound(), "{:?}", report.summary());
+        assert_eq!(report.samples, 4);
+    }
+
+    #[test]
+    fn output_without_a_row_is_unconstrained() {
+        let compiled = product();
+        let mut circuit = compiled.circuit.as_r1cs().unwrap().clone();
+        circuit.constraints.clear();
+        circuit.origins.clear();
+        let report = find_underconstrained(&circuit, &compiled.ir, 4).unwrap();
+        assert_eq!(kinds(&report), [("return".to_string(), Underconstraint::Unconstrained)]);
+    }
+
+    #[test]
+    fn output_read_by_a_vacuous_row_is_underdetermined() {
+        let compiled = product();
+        let mut circuit = compiled.circuit.as_r1cs().unwrap().clone();
+        // out * 0 = 0 reads the output but holds for any value of it
+        circuit.constraints = vec![R1CSConstraint {
+            a: lc_wire(1),
+            b: LinearCombination::new(),
+            c: LinearCombination::new(),
+        }];
+        let report = find_underconstrained(&circuit, &compiled.ir, 4).unwrap();
+        assert_eq!(kinds(&report), [("return".to_string(), Underconstraint::Underdetermined)]);
+        assert_eq!(report.samples, 4);
+    }
+}
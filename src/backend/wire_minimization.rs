@@ -0,0 +1,194 @@
+//! Wire minimization on a lowered R1CS circuit.
+//!
+//! A row with a constant side, `k * B = C`, is linear: it defines its latest wire in terms
+//! of earlier ones. When that wire is internal and read by at most one other row, its
+//! definition is substituted into that row, and the defining row and the wire are dropped.
+//! Wire sources and gadget calls that read the wire get the substitution as well, so the
+//! witness is still computed front to back. The remaining wires are then renumbered
+//! without gaps.
+
+use crate::backend::lowering::{lc_add_scaled, lc_constant_value, lc_scale, lc_sub, LinearCombination, ONE_WIRE};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::optimization::field;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WireMinimization {
+    /// Wires substituted away; each took its defining row with it
+    pub eliminated: usize,
+}
+
+/// Substitute away internal wires defined by a linear row and read by at most one other
+/// row, then renumber the wires
+pub fn minimize_wires(circuit: &mut R1CSCircuit) -> WireMinimization {
+    circuit.field.clone().enter(|| {
+        let substitutions = eliminate(circuit);
+        if !substitutions.is_empty() {
+            substitute_sources(circuit, &substitutions);
+            compact(circuit, &substitutions);
+        }
+        WireMinimization {
+            eliminated: substitutions.len(),
+        }
+    })
+}
+
+/// `a * b - c` as a linear combination, if one side is constant
+fn linear_form(a: &LinearCombination, b: &LinearCombination, c: &LinearCombination) -> Option<LinearCombination> {
+    match (lc_constant_value(a), lc_constant_value(b)) {
+        (Some(scalar), _) => Some(lc_sub(&lc_scale(b, &scalar), c)),
+        (None, Some(scalar)) => Some(lc_sub(&lc_scale(a, &scalar), c)),
+        (None, None) => None,
+    }
+}
+
+/// `lc` with `wire` replaced by `value`
+fn substitute(lc: &LinearCombination, wire: usize, value: &LinearCombination) -> LinearCombination {
+    match lc.get(&wire) {
+        Some(coefficient) => {
+            let mut rest = lc.clone();
+            rest.remove(&wire);
+            lc_add_scaled(&rest, value, coefficient)
+        }
+        None => lc.clone(),
+    }
+}
+
+/// Eliminate wires row by row, rewriting the rows in place; returns each eliminated wire
+/// with its value, in elimination order
+fn eliminate(circuit: &mut R1CSCircuit) -> Vec<(usize, LinearCombination)> {
+    // Inputs, outputs and the results of native calls keep their wires
+    let mut protected: HashSet<usize> = (0..=circuit.public_count() + circuit.private_inputs).collect();
+    protected.extend(circuit.black_boxes.iter().map(|call| call.output));
+    protected.extend(circuit.custom_gates.iter().map(|call| call.output));
+    protected.extend(circuit.table_lookups.iter().map(|call| call.output));
+
+    let mut uses: HashMap<usize, HashSet<usize>> = HashMap::new();
+    for (row, constraint) in circuit.constraints.iter().enumerate() {
+        for &wire in constraint.a.keys().chain(constraint.b.keys()).chain(constraint.c.keys()) {
+            uses.entry(wire).or_default().insert(row);
+        }
+    }
+
+    let mut removed = vec![false; circuit.constraints.len()];
+    let mut substitutions = Vec::new();
+    for row in 0..circuit.constraints.len() {
+        let constraint = &circuit.constraints[row];
+        let linear = match linear_form(&constraint.a, &constraint.b, &constraint.c) {
+            Some(linear) => linear,
+            None => continue,
+        };
+        // Every other wire of the row comes before the latest one, so its value is known
+        // wherever the latest one is read
+        let (wire, coefficient) = match linear.iter().next_back() {
+            Some((&wire, coefficient)) if wire != ONE_WIRE && !protected.contains(&wire) => (wire, coefficient.clone()),
+            _ => continue,
+        };
+        let readers: Vec<usize> = uses
+            .get(&wire)
+            .map(|rows| rows.iter().copied().filter(|&other| other != row && !removed[other]).collect())
+            .unwrap_or_default();
+        if readers.len() > 1 {
+            continue;
+        }
+        let inverse = match field::inverse(&coefficient) {
+            Some(inverse) => inverse,
+            None => continue,
+        };
+
+        // wire = -(linear - coefficient·wire) / coefficient
+        let mut rest = linear;
+        rest.remove(&wire);
+        let value = lc_scale(&rest, &field::neg(&inverse));
+        for &reader in &readers {
+            let constraint = &mut circuit.constraints[reader];
+            constraint.a = substitute(&constraint.a, wire, &value);
+            constraint.b = substitute(&constraint.b, wire, &value);
+            constraint.c = substitute(&constraint.c, wire, &value);
+            for &read in value.keys() {
+                uses.entry(read).or_default().insert(reader);
+            }
+        }
+        removed[row] = true;
+        substitutions.push((wire, value));
+    }
+
+    let mut rows = removed.iter();
+    circuit.constraints.retain(|_| !rows.next().copied().unwrap_or(false));
+    let mut rows = removed.iter();
+    circuit.origins.retain(|_| !rows.next().copied().unwrap_or(false));
+    substitutions
+}
+
+/// Resolve eliminated wires in the combinations wire sources and native calls read. A
+/// value only reads wires live when it was eliminated, so resolving until none is left
+/// terminates.
+fn substitute_sources(circuit: &mut R1CSCircuit, substitutions: &[(usize, LinearCombination)]) {
+    let values: HashMap<usize, &LinearCombination> = substitutions.iter().map(|(wire, value)| (*wire, value)).collect();
+    let resolve = |lc: &LinearCombination| {
+        let mut lc = lc.clone();
+        while let Some(wire) = lc.keys().copied().find(|wire| values.contains_key(wire)) {
+            lc = substitute(&lc, wire, values[&wire]);
+        }
+        lc
+    };
+    map_wires(circuit, resolve, |wire| wire);
+}
+
+/// Drop the eliminated wires and number the rest consecutively, in their old order
+fn compact(circuit: &mut R1CSCircuit, substitutions: &[(usize, LinearCombination)]) {
+    let eliminated: HashSet<usize> = substitutions.iter().map(|(wire, _)| *wire).collect();
+    let kept = |wire: &usize| !eliminated.contains(wire);
+    let mut renumbered = HashMap::new();
+    for wire in (0..circuit.wires.len()).filter(kept) {
+        renumbered.insert(wire, renumbered.len());
+    }
+
+    let mut wire = 0;
+    circuit.wires.retain(|_| {
+        wire += 1;
+        kept(&(wire - 1))
+    });
+    let mut wire = 0;
+    circuit.wire_names.retain(|_| {
+        wire += 1;
+        kept(&(wire - 1))
+    });
+    for constraint in &mut circuit.constraints {
+        for lc in [&mut constraint.a, &mut constraint.b, &mut constraint.c] {
+            *lc = lc.iter().map(|(wire, coefficient)| (renumbered[wire], coefficient.clone())).collect();
+        }
+    }
+    map_wires(
+        circuit,
+        |lc| lc.iter().map(|(wire, coefficient)| (renumbered[wire], coefficient.clone())).collect(),
+        |wire| renumbered[&wire],
+    );
+}
+
+/// Rewrite the combinations wire sources and native calls read with `lc`, and the wires
+/// native calls write with `output`
+fn map_wires(
+    circuit: &mut R1CSCircuit,
+    lc: impl Fn(&LinearCombination) -> LinearCombination,
+    output: impl Fn(usize) -> usize,
+) {
+    for source in &mut circuit.wires {
+        *source = source.map_lc(&lc);
+    }
+    for (value, _) in &mut circuit.ranges {
+        *value = lc(value);
+    }
+    for call in &mut circuit.black_boxes {
+        call.inputs = call.inputs.iter().map(&lc).collect();
+        call.output = output(call.output);
+    }
+    for call in &mut circuit.custom_gates {
+        call.inputs = call.inputs.iter().map(&lc).collect();
+        call.output = output(call.output);
+    }
+    for call in &mut circuit.table_lookups {
+        call.inputs = call.inputs.iter().map(&lc).collect();
+        call.output = output(call.output);
+    }
+}
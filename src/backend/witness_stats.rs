@@ -0,0 +1,159 @@
+//! Size and redundancy statistics for the witness vector, for cutting prover memory.
+//!
+//! The witness is sampled at satisfying assignments of random inputs. A wire with the
+//! same value in every sample is reported as constant, and a wire whose values match an
+//! earlier wire's in every sample as a duplicate; both are candidates for folding away.
+//! Wires are also attributed to the source variable they were lowered from, so the
+//! variables that dominate the witness stand out.
+
+use crate::backend::lowering::{lc_constant_value, WireSource};
+use crate::backend::r1cs::R1CSCircuit;
+use crate::backend::underconstrained::satisfying_witness;
+use crate::ir::{IRGraph, SourceSpan};
+use crate::optimization::field;
+use crate::optimization::interpreter::input_names;
+use crate::optimization::soundness::random_value;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+/// Satisfying witnesses to sample unless configured otherwise
+pub const DEFAULT_WITNESS_SAMPLES: usize = 4;
+
+/// Random input draws per sample before giving up on satisfying the constraints
+const ATTEMPTS_PER_SAMPLE: usize = 16;
+
+const SEED: u64 = 0x5_1ed_5eed;
+
+/// Witness wires lowered from one source variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableWires {
+    pub name: String,
+    pub span: Option<SourceSpan>,
+    pub wires: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WitnessStats {
+    /// Witness length, the constant one included
+    pub wires: usize,
+    pub public: usize,
+    pub private_inputs: usize,
+    pub internal: usize,
+    /// Bytes the witness takes with one canonical field element per wire
+    pub bytes: usize,
+    /// Wires other than the constant one with the same value in every sample
+    pub constants: usize,
+    /// Non-constant wires equal to an earlier wire in every sample
+    pub duplicates: usize,
+    /// Satisfying witnesses the constants and duplicates were found in
+    pub samples: usize,
+    /// Wires per source variable, the largest first
+    pub variables: Vec<VariableWires>,
+}
+
+impl WitnessStats {
+    /// Wires left once constants and duplicates are folded away
+    pub fn distinct(&self) -> usize {
+        self.wires - self.constants - self.duplicates
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} wires ({} bytes): {} public, {} private inputs, {} internal",
+                self.wires, self.bytes, self.public, self.private_inputs, self.internal
+            ),
+            format!(
+                "{} constant and {} duplicate wire(s) over {} sample(s)",
+                self.constants, self.duplicates, self.samples
+            ),
+        ];
+        lines.extend(self.variables.iter().map(|variable| match variable.span {
+            Some(span) => format!("{} ({}): {} wires", variable.name, span, variable.wires),
+            None => format!("{}: {} wires", variable.name, variable.wires),
+        }));
+        lines
+    }
+}
+
+/// Statistics for the witness of `circuit`, lowered from `graph`, sampled at up to
+/// `samples` satisfying witnesses of random inputs
+pub fn witness_stats(circuit: &R1CSCircuit, graph: &IRGraph, samples: usize) -> Result<WitnessStats, FCMCError> {
+    circuit.field.enter(|| {
+        let wires = circuit.wires.len();
+        let public = 1 + circuit.public_count();
+        let mut stats = WitnessStats {
+            wires,
+            public,
+            private_inputs: circuit.private_inputs,
+            internal: wires.saturating_sub(public + circuit.private_inputs),
+            bytes: wires * ((field::modulus().bits() as usize + 7) / 8),
+            variables: variable_wires(circuit, graph),
+            ..WitnessStats::default()
+        };
+
+        // Value of each wire in each sample, by wire
+        let mut columns: Vec<Vec<BigUint>> = vec![Vec::new(); wires];
+        let names = input_names(graph);
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for _ in 0..samples * ATTEMPTS_PER_SAMPLE {
+            if stats.samples == samples {
+                break;
+            }
+            let assignment: HashMap<String, BigUint> = names
+                .iter()
+                .map(|name| (name.clone(), random_value(&mut rng)))
+                .collect();
+            if let Some(witness) = satisfying_witness(circuit, graph, &assignment) {
+                for (column, value) in columns.iter_mut().zip(witness) {
+                    column.push(value);
+                }
+                stats.samples += 1;
+            }
+        }
+
+        let mut seen: HashMap<&[BigUint], usize> = HashMap::new();
+        for (wire, column) in columns.iter().enumerate().skip(1) {
+            let constant = match &circuit.wires[wire] {
+                WireSource::Linear(lc) => lc_constant_value(lc).is_some(),
+                _ => false,
+            };
+            if constant || (stats.samples > 0 && column.iter().all(|value| *value == column[0])) {
+                stats.constants += 1;
+            } else if stats.samples > 0 && seen.insert(column.as_slice(), wire).is_some() {
+                stats.duplicates += 1;
+            }
+        }
+        Ok(stats)
+    })
+}
+
+/// Wires per source variable: a wire belongs to the IR node it was lowered from, named
+/// by the part of its wire name before the first `.`
+fn variable_wires(circuit: &R1CSCircuit, graph: &IRGraph) -> Vec<VariableWires> {
+    let mut variables: Vec<VariableWires> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for (wire, name) in circuit.wire_names.iter().enumerate().skip(1) {
+        let variable = name.split('.').next().unwrap_or(name);
+        let span = match circuit.wires.get(wire) {
+            Some(WireSource::Node(node_id)) => graph.span(*node_id),
+            _ => None,
+        };
+        let position = *index.entry(variable).or_insert_with(|| {
+            variables.push(VariableWires {
+                name: variable.to_string(),
+                span: None,
+                wires: 0,
+            });
+            variables.len() - 1
+        });
+        let entry = &mut variables[position];
+        entry.wires += 1;
+        entry.span = entry.span.or(span);
+    }
+    variables.sort_by(|a, b| b.wires.cmp(&a.wires).then_with(|| a.name.cmp(&b.name)));
+    variables
+}
@@ -0,0 +1,236 @@
+//! Compile-time and circuit-size benchmarks over a corpus of representative circuits,
+//! with a JSON baseline to catch size regressions.
+//!
+//! `run` compiles each case and records the compile time, the IR before and after
+//! optimization, the constraint count and, when `PeakAllocator` is the global allocator,
+//! the peak heap use. `compare` checks the results against a baseline: more constraints
+//! or optimized IR nodes than the baseline allows is a regression, while time and memory
+//! depend on the machine and are reported without failing. `benches/circuit_bench.rs`
+//! runs the corpus this way before timing it with criterion.
+
+use crate::backend::CircuitBackend;
+use crate::platform::Instant;
+use crate::{FCMCError, FCMC};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Growth in constraints or optimized nodes over the baseline that `compare` accepts, as a
+/// fraction of the baseline
+pub const DEFAULT_REGRESSION_THRESHOLD: f64 = 0.02;
+
+/// Environment variable that makes the bench suite write its baseline instead of comparing
+pub const UPDATE_BASELINE_ENV: &str = "FCMC_UPDATE_BASELINE";
+
+/// The built-in corpus, by name and source
+pub const CORPUS: &[(&str, &str)] = &[
+    ("polynomial", include_str!("../benches/corpus/polynomial.zk")),
+    ("poseidon", include_str!("../benches/corpus/poseidon.zk")),
+    ("merkle", include_str!("../benches/corpus/merkle.zk")),
+    ("range", include_str!("../benches/corpus/range.zk")),
+    ("eddsa", include_str!("../benches/corpus/eddsa.zk")),
+];
+
+/// The system allocator, recording the most heap in use at once; install it with
+/// `#[global_allocator]` in a bench binary to have `run` report peak memory
+pub struct PeakAllocator {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+    installed: AtomicBool,
+}
+
+impl PeakAllocator {
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            installed: AtomicBool::new(false),
+        }
+    }
+
+    /// Start a new peak from the heap in use now
+    pub fn reset_peak(&self) {
+        self.peak.store(self.current.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    /// The peak since the last reset, `None` if the allocator has never allocated, so is
+    /// not the global one
+    pub fn peak(&self) -> Option<usize> {
+        self.installed
+            .load(Ordering::Relaxed)
+            .then(|| self.peak.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for PeakAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            self.installed.store(true, Ordering::Relaxed);
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+/// What compiling one case measured
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResult {
+    pub compile_ms: f64,
+    /// Most heap in use while compiling, from `PeakAllocator`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_bytes: Option<usize>,
+    /// IR nodes before the first optimization pass
+    pub initial_nodes: usize,
+    pub optimized_nodes: usize,
+    pub constraints: usize,
+}
+
+/// Results by case name, as written to and read from the baseline file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub cases: BTreeMap<String, BenchResult>,
+}
+
+impl Baseline {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FCMCError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&text)
+            .map_err(|e| FCMCError::ParseError(format!("Invalid baseline {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+        let path = path.as_ref();
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| FCMCError::BackendError(format!("Failed to serialize baseline: {}", e)))?;
+        std::fs::write(path, text + "\n")
+            .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+    }
+}
+
+/// A case that grew past the threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regression {
+    pub case: String,
+    /// `constraints` or `optimized nodes`
+    pub metric: &'static str,
+    pub baseline: usize,
+    pub current: usize,
+}
+
+impl Regression {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}: {} grew from {} to {} ({:+.1}%)",
+            self.case,
+            self.metric,
+            self.baseline,
+            self.current,
+            100.0 * (self.current as f64 - self.baseline as f64) / self.baseline.max(1) as f64
+        )
+    }
+}
+
+/// Compile every case of `corpus` with `compiler`, measuring peak memory with `allocator`
+/// if given
+pub fn run(compiler: &FCMC, corpus: &[(&str, &str)], allocator: Option<&PeakAllocator>) -> Result<Baseline, FCMCError> {
+    let mut cases = BTreeMap::new();
+    for (name, source) in corpus {
+        if let Some(allocator) = allocator {
+            allocator.reset_peak();
+        }
+        let started = Instant::now();
+        let compiled = compiler
+            .compile(source)
+            .map_err(|e| FCMCError::BackendError(format!("Benchmark '{}' does not compile: {}", name, e)))?;
+        let compile_ms = started.elapsed().as_secs_f64() * 1000.0;
+        let optimized_nodes = compiled.stats.optimized_nodes;
+        let result = BenchResult {
+            compile_ms,
+            peak_bytes: allocator.and_then(PeakAllocator::peak),
+            initial_nodes: compiled.stats.original_nodes,
+            optimized_nodes,
+            constraints: compiled.circuit.constraint_count(),
+        };
+        cases.insert(name.to_string(), result);
+    }
+    Ok(Baseline { cases })
+}
+
+/// Cases of `current` whose constraints or optimized nodes exceed `baseline` by more than
+/// `threshold`, a fraction of the baseline; cases missing from either side are skipped
+pub fn compare(baseline: &Baseline, current: &Baseline, threshold: f64) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for (case, result) in &current.cases {
+        let Some(expected) = baseline.cases.get(case) else {
+            continue;
+        };
+        let metrics = [
+            ("constraints", expected.constraints, result.constraints),
+            ("optimized nodes", expected.optimized_nodes, result.optimized_nodes),
+        ];
+        for (metric, baseline, current) in metrics {
+            if current as f64 > baseline as f64 * (1.0 + threshold) {
+                regressions.push(Regression {
+                    case: case.clone(),
+                    metric,
+                    baseline,
+                    current,
+                });
+            }
+        }
+    }
+    regressions
+}
+
+/// One line per case: each metric and its change from `baseline`
+pub fn summary(baseline: &Baseline, current: &Baseline) -> Vec<String> {
+    let change = |old: f64, new: f64| {
+        if old > 0.0 {
+            format!(" ({:+.1}%)", 100.0 * (new - old) / old)
+        } else {
+            String::new()
+        }
+    };
+    current
+        .cases
+        .iter()
+        .map(|(case, result)| {
+            let expected = baseline.cases.get(case);
+            let memory = result
+                .peak_bytes
+                .map(|bytes| format!(", peak {:.1} MiB", bytes as f64 / (1024.0 * 1024.0)))
+                .unwrap_or_default();
+            format!(
+                "{}: {} constraints{}, {} -> {} nodes, {:.1} ms{}{}",
+                case,
+                result.constraints,
+                expected.map_or(String::new(), |expected| change(
+                    expected.constraints as f64,
+                    result.constraints as f64
+                )),
+                result.initial_nodes,
+                result.optimized_nodes,
+                result.compile_ms,
+                expected.map_or(String::new(), |expected| change(expected.compile_ms, result.compile_ms)),
+                memory
+            )
+        })
+        .collect()
+}
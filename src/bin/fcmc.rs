@@ -0,0 +1,824 @@
+//! `fcmc`: the compiler from the command line.
+//!
+//! - `fcmc compile input.zk --target r1cs -O2 -o out/` writes `out/circuit.bin`, which
+//!   `CompiledCircuit::load` reads, and for R1CS also circom's `out/circuit.r1cs`.
+//! - `fcmc inspect out/circuit.bin` describes a compiled circuit without compiling again.
+//!   `--report markdown`, on it or on `compile`, prints a summary to paste into a pull
+//!   request instead; `text` and `json` are the other formats.
+//! - `fcmc diff old.bin new.bin` compares two compiled circuits: constraints overall and
+//!   per function, and the public and private values added, removed or reordered.
+//! - `fcmc check input.zk` compiles with every check and lints the result, failing on
+//!   lint errors and on constraints that hold or fail for every input.
+//! - `fcmc profile input.zk` shows which functions, loops, lines and gadgets the
+//!   constraints come from; `--folded` prints collapsed stacks for `flamegraph.pl`.
+//! - `fcmc watch input.zk`, or `fcmc watch` in a project, compiles and then recompiles
+//!   whenever a source file is saved, printing each circuit's constraint count. It runs
+//!   until interrupted, reporting errors and carrying on.
+//! - `fcmc repl` reads expressions and functions interactively, printing each
+//!   expression's value on sample inputs and what it and each function cost.
+//! - `fcmc package lib.zk --name hashes --export pair` compiles the exported functions
+//!   into `hashes-0.1.0.fcmp`, a package of gadgets projects link from `[packages]`,
+//!   and prints its digest for their manifests to pin.
+//! - `fcmc build` compiles every circuit an `fcmc.toml` project declares, for each of
+//!   its targets, into the project's output directory.
+//! - `fcmc witness out/circuit.bin input.json -o out/witness.wtns` computes the witness
+//!   for the inputs in a JSON or TOML file, optionally writing it in snarkjs' format.
+//! - `fcmc prove out/circuit.bin input.json`, with the `groth16` feature, sets up Groth16
+//!   keys, proves and verifies. The keys come from a fixed seed, so this checks a
+//!   circuit locally; it is not a trusted setup.
+//!
+//! Every subcommand prints a human summary, or one JSON object with `--json`. Errors go
+//! to stderr, or into the object's `error` field, and exit with status 1.
+//! `--diagnostics json` writes errors, compiler warnings and `check`'s lints to stderr
+//! as one JSON object per line, with a code, a position and suggested fixes, for editors
+//! and CI problem matchers.
+
+use clap::{Args, Parser, Subcommand};
+use fcmc_compiler::backend::export::circom;
+use fcmc_compiler::optimization::field::FieldConfig;
+use fcmc_compiler::package::PACKAGE_EXTENSION;
+use fcmc_compiler::repl::{Reply, Session};
+use fcmc_compiler::{
+    project, witness, CompiledCircuit, CompilerConfig, Diagnostic, DiagnosticsFormat, FCMCError, IncrementalCompiler,
+    InputMap, Package, PassProgress, ProgressObserver, Project, Recompilation, ReportFormat, Stage, TargetSystem, FCMC,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(name = "fcmc", version, about = "Formal Circuit Minimization Compiler")]
+struct Cli {
+    /// Print one JSON object instead of a human summary
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// human, or json for one diagnostic object per line on stderr
+    #[arg(long, global = true, default_value = "human")]
+    diagnostics: DiagnosticsFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compile a program and write the circuit to a directory
+    Compile {
+        input: PathBuf,
+        #[command(flatten)]
+        options: CompileOptions,
+        /// Directory to write the circuit to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+        /// Print a text, json or markdown summary instead of the usual lines
+        #[arg(long, value_name = "FORMAT")]
+        report: Option<ReportFormat>,
+    },
+    /// Describe a circuit written by `compile`
+    Inspect {
+        circuit: PathBuf,
+        /// Print a text, json or markdown summary instead of the usual lines
+        #[arg(long, value_name = "FORMAT")]
+        report: Option<ReportFormat>,
+    },
+    /// Compare two circuits written by `compile`
+    Diff { old: PathBuf, new: PathBuf },
+    /// Compile a program with every check and lint the circuit, writing nothing
+    #[command(alias = "verify")]
+    Check {
+        input: PathBuf,
+        #[command(flatten)]
+        options: CompileOptions,
+    },
+    /// Compile functions of a program into a package of gadgets other projects can link
+    Package {
+        input: PathBuf,
+        #[command(flatten)]
+        options: CompileOptions,
+        /// The package's name, which calls to its gadgets start with
+        #[arg(long)]
+        name: String,
+        #[arg(long = "package-version", default_value = "0.1.0")]
+        version: String,
+        /// A function to export; repeat for each
+        #[arg(long = "export", required = true)]
+        exports: Vec<String>,
+        /// File to write; `<name>-<version>.fcmp` if omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compile every circuit of a project
+    Build {
+        /// The project's `fcmc.toml`, or the directory holding it
+        #[arg(default_value = ".")]
+        project: PathBuf,
+    },
+    /// Attribute a circuit's constraints to functions, loops, source lines and gadgets
+    Profile {
+        /// A source file, or a circuit written by `compile` if it ends in `.bin`
+        input: PathBuf,
+        #[command(flatten)]
+        options: CompileOptions,
+        /// Print collapsed stacks for flamegraph tools instead of the tree
+        #[arg(long)]
+        folded: bool,
+    },
+    /// Recompile a program, or every circuit of a project, each time a source file changes
+    Watch {
+        /// A source file, or a project's `fcmc.toml` or the directory holding it
+        #[arg(default_value = ".")]
+        input: PathBuf,
+        /// Ignored for a project, whose manifest sets them
+        #[command(flatten)]
+        options: CompileOptions,
+        /// Milliseconds between checks of the files' modification times
+        #[arg(long, default_value_t = 200)]
+        interval: u64,
+    },
+    /// Type expressions and functions to see their values and constraint costs
+    Repl {
+        #[command(flatten)]
+        options: CompileOptions,
+    },
+    /// Compute a circuit's witness from a JSON or TOML file of inputs
+    Witness {
+        circuit: PathBuf,
+        inputs: PathBuf,
+        /// File to write the witness to in snarkjs' `.wtns` format
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Prove a circuit on a file of inputs with Groth16 and verify the proof
+    #[cfg(feature = "groth16")]
+    Prove { circuit: PathBuf, inputs: PathBuf },
+}
+
+#[derive(Args)]
+struct CompileOptions {
+    /// r1cs, plonk, halo2, air, acir, plonky2, ccs or a registered backend
+    #[arg(short, long, default_value = "r1cs")]
+    target: String,
+    /// Optimization level, 0 to 3
+    #[arg(short = 'O', default_value_t = 2)]
+    optimization_level: u8,
+    /// bn254, bls12-381, goldilocks, pallas, vesta or a decimal prime; the target's
+    /// default field if omitted
+    #[arg(long)]
+    field: Option<String>,
+    /// Comma-separated passes to run instead of the level's pipeline, like `cse,constfold*2,dce`
+    #[arg(long)]
+    passes: Option<String>,
+    /// Skip checking the optimized IR and the circuit
+    #[arg(long)]
+    no_verify: bool,
+    /// Fail once the IR and circuit take more than this many MiB, approximately
+    #[arg(long, value_name = "MIB")]
+    memory_limit: Option<usize>,
+    /// Print each stage and optimization pass to stderr as it finishes
+    #[arg(long)]
+    progress: bool,
+}
+
+impl CompileOptions {
+    /// A compiler for the program in `input`, if it comes from a file, reporting warnings
+    /// in `diagnostics`
+    fn compiler(&self, input: Option<&Path>, diagnostics: DiagnosticsFormat) -> Result<FCMC, FCMCError> {
+        let mut config = CompilerConfig::new()
+            .with_target(self.target.parse::<TargetSystem>()?)
+            .with_optimization_level(self.optimization_level)
+            .with_verification(!self.no_verify);
+        if let Some(field) = &self.field {
+            config = config.with_field(field.parse::<FieldConfig>()?);
+        }
+        if let Some(passes) = &self.passes {
+            config = config.with_passes(&passes.split(',').collect::<Vec<_>>());
+        }
+        let mut compiler = config.build()?.with_diagnostics_format(diagnostics);
+        if let Some(input) = input {
+            compiler = compiler.with_source_name(input.display().to_string());
+        }
+        if let Some(mebibytes) = self.memory_limit {
+            compiler = compiler.with_memory_limit(mebibytes.saturating_mul(1024 * 1024));
+        }
+        if self.progress {
+            compiler = compiler.with_progress(Arc::new(StderrProgress));
+        }
+        Ok(compiler)
+    }
+}
+
+/// Progress as lines on stderr, which stay readable in CI logs
+struct StderrProgress;
+
+impl ProgressObserver for StderrProgress {
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        eprintln!("{}: {} ms", stage, elapsed.as_millis());
+    }
+
+    fn pass_finished(&self, progress: &PassProgress) {
+        eprintln!(
+            "  [{:>3.0}%] {} (iteration {}): {} nodes",
+            progress.percent, progress.pass, progress.iteration, progress.nodes
+        );
+    }
+}
+
+/// What a subcommand reports: a JSON object, the lines of its human form, and whether
+/// it found a problem that should fail the run
+struct Report {
+    json: Value,
+    lines: Vec<String>,
+    failed: bool,
+}
+
+fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")))
+        .with_writer(std::io::stderr)
+        .init();
+    let cli = Cli::parse();
+    let diagnostics = cli.diagnostics;
+    let result = match &cli.command {
+        Command::Compile {
+            input,
+            options,
+            output,
+            report,
+        } => compile(input, options, output, *report, diagnostics),
+        Command::Inspect { circuit, report } => inspect(circuit, *report),
+        Command::Diff { old, new } => diff(old, new),
+        Command::Check { input, options } => check(input, options, diagnostics),
+        Command::Package {
+            input,
+            options,
+            name,
+            version,
+            exports,
+            output,
+        } => package(input, options, name, version, exports, output.as_deref(), diagnostics),
+        Command::Build { project } => build(project),
+        Command::Profile { input, options, folded } => profile(input, options, *folded, diagnostics),
+        Command::Watch {
+            input,
+            options,
+            interval,
+        } => watch(input, options, *interval, cli.json, diagnostics),
+        Command::Repl { options } => repl(options, diagnostics),
+        Command::Witness {
+            circuit,
+            inputs,
+            output,
+        } => witness(circuit, inputs, output.as_deref()),
+        #[cfg(feature = "groth16")]
+        Command::Prove { circuit, inputs } => prove(circuit, inputs),
+    };
+
+    match result {
+        Ok(report) => {
+            if cli.json {
+                println!("{}", report.json);
+            } else {
+                for line in &report.lines {
+                    println!("{}", line);
+                }
+            }
+            if report.failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(error) => {
+            if cli.json {
+                println!("{}", json!({ "error": error.to_string() }));
+            }
+            if diagnostics == DiagnosticsFormat::Json || !cli.json {
+                let mut diagnostic = Diagnostic::from_error(&error);
+                if let Some(source) = cli.command.source() {
+                    diagnostic = diagnostic.in_file(source.display().to_string());
+                }
+                eprintln!("{}", diagnostic.render(diagnostics));
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+impl Command {
+    /// The source file the subcommand compiles, which errors are placed in
+    fn source(&self) -> Option<&Path> {
+        match self {
+            Command::Compile { input, .. } | Command::Check { input, .. } | Command::Package { input, .. } => {
+                Some(input)
+            }
+            Command::Profile { input, .. } if !input.extension().is_some_and(|extension| extension == "bin") => {
+                Some(input)
+            }
+            Command::Watch { input, .. }
+                if input.is_file() && !input.extension().is_some_and(|extension| extension == "toml") =>
+            {
+                Some(input)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn compile(
+    input: &Path,
+    options: &CompileOptions,
+    output: &Path,
+    summary: Option<ReportFormat>,
+    diagnostics: DiagnosticsFormat,
+) -> Result<Report, FCMCError> {
+    let circuit = options
+        .compiler(Some(input), diagnostics)?
+        .compile(&read_source(input)?)?;
+    std::fs::create_dir_all(output)
+        .map_err(|e| FCMCError::BackendError(format!("Failed to create {}: {}", output.display(), e)))?;
+
+    let written = project::write_artifacts(&circuit, output, "circuit")?;
+    let mut report = describe(&circuit);
+    let files: Vec<String> = written.iter().map(|path| path.display().to_string()).collect();
+    report.lines.extend(files.iter().map(|file| format!("wrote {}", file)));
+    report.json["files"] = json!(files);
+    report.json["stats"] = circuit.stats.to_json();
+    if let Some(format) = summary {
+        report.lines = summary_lines(&circuit, format);
+    }
+    Ok(report)
+}
+
+fn inspect(path: &Path, summary: Option<ReportFormat>) -> Result<Report, FCMCError> {
+    let circuit = CompiledCircuit::load(path)?;
+    let mut report = describe(&circuit);
+    if let Some(format) = summary {
+        report.lines = summary_lines(&circuit, format);
+    }
+    Ok(report)
+}
+
+fn summary_lines(circuit: &CompiledCircuit, format: ReportFormat) -> Vec<String> {
+    circuit.report(format).lines().map(str::to_string).collect()
+}
+
+fn diff(old: &Path, new: &Path) -> Result<Report, FCMCError> {
+    let diff = CompiledCircuit::load(old)?.diff(&CompiledCircuit::load(new)?);
+    Ok(Report {
+        json: diff.to_json(),
+        lines: diff.summary(),
+        failed: false,
+    })
+}
+
+fn check(input: &Path, options: &CompileOptions, diagnostics: DiagnosticsFormat) -> Result<Report, FCMCError> {
+    let circuit = options
+        .compiler(Some(input), diagnostics)?
+        .compile(&read_source(input)?)?;
+    let lint = circuit.lint()?;
+    let lints: Vec<Diagnostic> = lint
+        .findings
+        .iter()
+        .map(|finding| Diagnostic::from_lint(finding).in_file(input.display().to_string()))
+        .collect();
+    if diagnostics == DiagnosticsFormat::Json {
+        for diagnostic in &lints {
+            eprintln!("{}", diagnostic.to_json());
+        }
+    }
+    let suspicious: Vec<String> = circuit
+        .stats
+        .suspicious_constraints
+        .iter()
+        .map(|constraint| constraint.describe())
+        .collect();
+
+    let mut report = describe(&circuit);
+    report.lines.extend(lints.iter().map(Diagnostic::render_human));
+    report.lines.extend(suspicious.iter().cloned());
+    report.failed = lint.errors().next().is_some() || !suspicious.is_empty();
+    report
+        .lines
+        .push(if report.failed { "check failed" } else { "check passed" }.to_string());
+    report.json["lints"] = Value::Array(
+        lint.findings
+            .iter()
+            .map(|finding| {
+                json!({
+                    "lint": finding.lint.as_str(),
+                    "severity": finding.severity().as_str(),
+                    "line": finding.span.map(|span| span.line),
+                    "message": finding.message,
+                })
+            })
+            .collect(),
+    );
+    report.json["suspicious_constraints"] = json!(suspicious);
+    report.json["passed"] = json!(!report.failed);
+    Ok(report)
+}
+
+fn package(
+    input: &Path,
+    options: &CompileOptions,
+    name: &str,
+    version: &str,
+    exports: &[String],
+    output: Option<&Path>,
+    diagnostics: DiagnosticsFormat,
+) -> Result<Report, FCMCError> {
+    let compiler = options.compiler(Some(input), diagnostics)?;
+    let exports: Vec<&str> = exports.iter().map(String::as_str).collect();
+    let package = Package::build(&compiler, name, version, &read_source(input)?, &exports)?;
+    let path = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(format!("{}-{}.{}", name, version, PACKAGE_EXTENSION)));
+    package.save(&path)?;
+    let digest = package.digest();
+    let mut lines: Vec<String> = package
+        .gadgets
+        .iter()
+        .map(|gadget| {
+            format!(
+                "{}::{}: {} inputs, {} constraints",
+                name,
+                gadget.name,
+                gadget.arity(),
+                gadget.circuit.circuit.constraint_count()
+            )
+        })
+        .collect();
+    lines.push(format!("wrote {}", path.display()));
+    lines.push(format!("sha256 {}", digest));
+    let json = json!({
+        "package": name,
+        "version": version,
+        "field": package.field.to_string(),
+        "file": path.display().to_string(),
+        "sha256": digest,
+        "gadgets": package
+            .gadgets
+            .iter()
+            .map(|gadget| json!({
+                "name": gadget.name,
+                "inputs": gadget.arity(),
+                "constraints": gadget.circuit.circuit.constraint_count(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+    Ok(Report {
+        json,
+        lines,
+        failed: false,
+    })
+}
+
+fn build(path: &Path) -> Result<Report, FCMCError> {
+    let project = Project::load(path)?;
+    let artifacts = project.build()?;
+    let mut lines = Vec::new();
+    for artifact in &artifacts {
+        lines.push(format!(
+            "{} ({}): {} constraints",
+            artifact.circuit, artifact.target, artifact.constraints
+        ));
+        lines.extend(artifact.files.iter().map(|file| format!("  wrote {}", file.display())));
+    }
+    let json = json!({
+        "project": project.manifest.project.name,
+        "artifacts": artifacts
+            .iter()
+            .map(|artifact| {
+                json!({
+                    "circuit": artifact.circuit,
+                    "target": artifact.target,
+                    "constraints": artifact.constraints,
+                    "files": artifact.files.iter().map(|file| file.display().to_string()).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+    Ok(Report {
+        json,
+        lines,
+        failed: false,
+    })
+}
+
+fn profile(
+    input: &Path,
+    options: &CompileOptions,
+    folded: bool,
+    diagnostics: DiagnosticsFormat,
+) -> Result<Report, FCMCError> {
+    let circuit = if input.extension().is_some_and(|extension| extension == "bin") {
+        CompiledCircuit::load(input)?
+    } else {
+        options
+            .compiler(Some(input), diagnostics)?
+            .compile(&read_source(input)?)?
+    };
+    let profile = circuit.profile();
+    let lines = if folded {
+        profile.folded().lines().map(str::to_string).collect()
+    } else {
+        profile.summary()
+    };
+    Ok(Report {
+        json: json!({
+            "target": format!("{:?}", circuit.circuit.target()),
+            "constraints": profile.constraints,
+            "functions": profile
+                .functions()
+                .into_iter()
+                .map(|(name, constraints)| json!({ "function": name, "constraints": constraints }))
+                .collect::<Vec<_>>(),
+            "profile": profile.to_json(),
+        }),
+        lines,
+        failed: false,
+    })
+}
+
+/// Compile, then poll the files every `interval` milliseconds and recompile the circuits
+/// that read a changed one; returns only on an error setting up
+fn watch(
+    input: &Path,
+    options: &CompileOptions,
+    interval: u64,
+    json: bool,
+    diagnostics: DiagnosticsFormat,
+) -> Result<Report, FCMCError> {
+    let mut compilers: Vec<(String, IncrementalCompiler)> = Vec::new();
+    if input.is_dir() || input.extension().is_some_and(|extension| extension == "toml") {
+        let project = Project::load(input)?;
+        for circuit in &project.manifest.circuits {
+            let files = project.files(circuit)?;
+            for target in project.targets(circuit) {
+                let compiler = project
+                    .compiler(circuit, target)?
+                    .with_diagnostics_format(diagnostics)
+                    .incremental(files.clone());
+                compilers.push((format!("{} ({})", circuit.name, target), compiler));
+            }
+        }
+    } else {
+        let compiler = options
+            .compiler(Some(input), diagnostics)?
+            .incremental(vec![input.to_path_buf()]);
+        compilers.push((input.display().to_string(), compiler));
+    }
+
+    for (name, compiler) in &mut compilers {
+        let result = compiler.compile();
+        print_recompilation(name, compiler, result, json, diagnostics);
+    }
+    let mut modified = modification_times(&compilers);
+    loop {
+        std::thread::sleep(Duration::from_millis(interval));
+        let current = modification_times(&compilers);
+        let changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, time)| modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        modified = current;
+        for (name, compiler) in &mut compilers {
+            if compiler.files().iter().any(|file| changed.contains(file)) {
+                let result = compiler.recompile(&changed);
+                print_recompilation(name, compiler, result, json, diagnostics);
+            }
+        }
+    }
+}
+
+/// Modification time of every file the compilers read that can be inspected now; a file
+/// missing mid-save reappears as changed
+fn modification_times(compilers: &[(String, IncrementalCompiler)]) -> HashMap<PathBuf, SystemTime> {
+    compilers
+        .iter()
+        .flat_map(|(_, compiler)| compiler.files())
+        .filter_map(|path| Some((path.clone(), std::fs::metadata(path).ok()?.modified().ok()?)))
+        .collect()
+}
+
+/// One line, or one JSON object, per compilation; errors also as a diagnostic on stderr
+/// with `--diagnostics json`
+fn print_recompilation(
+    name: &str,
+    compiler: &IncrementalCompiler,
+    result: Result<Recompilation, FCMCError>,
+    json: bool,
+    diagnostics: DiagnosticsFormat,
+) {
+    let recompilation = match result {
+        Ok(recompilation) => recompilation,
+        Err(error) => {
+            if json {
+                println!("{}", json!({ "circuit": name, "error": error.to_string() }));
+            }
+            if diagnostics == DiagnosticsFormat::Json {
+                eprintln!("{}", compiler.compiler().diagnose(&error).to_json());
+            } else if !json {
+                eprintln!("{}: error: {}", name, error);
+            }
+            return;
+        }
+    };
+    if json {
+        println!(
+            "{}",
+            json!({
+                "circuit": name,
+                "recompiled": recompilation.recompiled,
+                "changed": recompilation.changed,
+                "constraints": recompilation.constraints,
+                "previous_constraints": recompilation.previous_constraints,
+                "elapsed_ms": recompilation.elapsed.as_millis() as u64,
+            })
+        );
+    } else if !recompilation.recompiled {
+        println!("{}: no change to the circuit", name);
+    } else {
+        let delta = match recompilation.previous_constraints {
+            Some(previous) => format!(" ({:+})", recompilation.constraints as i64 - previous as i64),
+            None => String::new(),
+        };
+        let changed = if recompilation.changed.is_empty() || recompilation.previous_constraints.is_none() {
+            String::new()
+        } else {
+            format!(", changed: {}", recompilation.changed.join(", "))
+        };
+        println!(
+            "{}: {} constraints{} in {} ms{}",
+            name,
+            recompilation.constraints,
+            delta,
+            recompilation.elapsed.as_millis(),
+            changed
+        );
+    }
+}
+
+/// Read lines from stdin until `:quit` or the end of input, printing each reply
+fn repl(options: &CompileOptions, diagnostics: DiagnosticsFormat) -> Result<Report, FCMCError> {
+    let mut session = Session::new(options.compiler(None, diagnostics)?);
+    let stdin = std::io::stdin();
+    eprintln!("fcmc {} repl; :help lists the commands", env!("CARGO_PKG_VERSION"));
+    loop {
+        eprint!("{}", if session.is_continuing() { "...> " } else { "fcmc> " });
+        std::io::stderr().flush().ok();
+        let mut line = String::new();
+        let read = stdin
+            .read_line(&mut line)
+            .map_err(|e| FCMCError::ParseError(format!("Failed to read stdin: {}", e)))?;
+        if read == 0 {
+            break;
+        }
+        match session.eval(&line) {
+            Reply::Lines(lines) => {
+                for line in lines {
+                    println!("{}", line);
+                }
+            }
+            Reply::Incomplete => {}
+            Reply::Quit => break,
+        }
+    }
+    Ok(Report {
+        json: json!({}),
+        lines: Vec::new(),
+        failed: false,
+    })
+}
+
+fn witness(circuit: &Path, inputs: &Path, output: Option<&Path>) -> Result<Report, FCMCError> {
+    let circuit = CompiledCircuit::load(circuit)?;
+    let inputs = read_inputs(&circuit, inputs)?;
+    let witness = circuit.compute_witness(&inputs)?;
+    if let Some(path) = output {
+        circuit.in_field(|| circom::write_wtns(circuit.circuit.backend(), &witness.values, path))?;
+    }
+
+    let mut lines = vec![format!("wires: {}", witness.values.len())];
+    lines.extend(
+        witness
+            .outputs
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value)),
+    );
+    lines.extend(output.map(|path| format!("wrote {}", path.display())));
+    let outputs: serde_json::Map<String, Value> = witness
+        .outputs
+        .iter()
+        .map(|(name, value)| (name.clone(), json!(value.to_string())))
+        .collect();
+    Ok(Report {
+        json: json!({
+            "wires": witness.values.len(),
+            "outputs": outputs,
+            "file": output.map(|path| path.display().to_string()),
+        }),
+        lines,
+        failed: false,
+    })
+}
+
+#[cfg(feature = "groth16")]
+fn prove(circuit: &Path, inputs: &Path) -> Result<Report, FCMCError> {
+    let circuit = CompiledCircuit::load(circuit)?;
+    let inputs = read_inputs(&circuit, inputs)?;
+    let verified = fcmc_compiler::e2e::prove_and_verify(&circuit, &inputs)?;
+    Ok(Report {
+        json: json!({ "verified": verified }),
+        lines: vec![if verified {
+            "proof verified"
+        } else {
+            "proof did not verify"
+        }
+        .to_string()],
+        failed: !verified,
+    })
+}
+
+/// Inputs for `circuit` from a JSON file, or a TOML file by its extension
+fn read_inputs(circuit: &CompiledCircuit, path: &Path) -> Result<InputMap, FCMCError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let json: Value = if path.extension().is_some_and(|extension| extension == "toml") {
+        toml::from_str(&text)
+            .map_err(|e| FCMCError::ParseError(format!("Invalid TOML in {}: {}", path.display(), e)))?
+    } else {
+        serde_json::from_str(&text)
+            .map_err(|e| FCMCError::ParseError(format!("Invalid JSON in {}: {}", path.display(), e)))?
+    };
+    circuit.in_field(|| witness::inputs_from_json(&json))
+}
+
+fn read_source(path: &Path) -> Result<String, FCMCError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+/// Target, field, size, signature and audited gadgets of `circuit`
+fn describe(circuit: &CompiledCircuit) -> Report {
+    let target = format!("{:?}", circuit.circuit.target());
+    let signature = circuit.signature();
+    let constraints = circuit.circuit.constraint_count();
+    let mut lines = vec![
+        format!("target: {}", target),
+        format!("field: {}", circuit.field),
+        format!("constraints: {}", constraints),
+        format!(
+            "IR nodes: {} -> {} ({:.1}% fewer)",
+            circuit.stats.original_nodes,
+            circuit.stats.optimized_nodes,
+            circuit.optimization_ratio()
+        ),
+        format!("witness size: {}", circuit.stats.witness_size),
+        format!("{}: {}", target, circuit.stats.target.summary()),
+        format!("memory: {}", circuit.stats.memory.summary()),
+        format!("public outputs: {}", signature.public_outputs.join(", ")),
+        format!("public inputs: {}", signature.public_inputs.join(", ")),
+        format!("private inputs: {}", signature.private_inputs.join(", ")),
+    ];
+    lines.extend(circuit.stats.audit.iter().map(|boundary| {
+        let rows: Vec<String> = boundary
+            .rows
+            .iter()
+            .map(|range| format!("{}..{}", range.start, range.end))
+            .collect();
+        format!(
+            "audited {} (fn {}, sha256 {}): rows {}",
+            boundary.name,
+            boundary.function,
+            boundary.content_hash,
+            rows.join(", ")
+        )
+    }));
+    let json = json!({
+        "target": target,
+        "field": circuit.field.to_string(),
+        "constraints": constraints,
+        "original_ir_nodes": circuit.stats.original_nodes,
+        "ir_nodes": circuit.stats.optimized_nodes,
+        "witness_size": circuit.stats.witness_size,
+        "metrics": circuit.stats.target,
+        "memory": circuit.stats.memory.to_json(),
+        "public_outputs": signature.public_outputs,
+        "public_inputs": signature.public_inputs,
+        "private_inputs": signature.private_inputs,
+        "audit": circuit.stats.audit,
+    });
+    Report {
+        json,
+        lines,
+        failed: false,
+    }
+}
@@ -0,0 +1,94 @@
+//! The settings that decide what circuit comes out, checked together before a compiler is
+//! built from them.
+//!
+//! `FCMC` accepts any combination of options and rejects a bad one when compiling. A
+//! `CompilerConfig` is for settings that come from outside, a request or a config file
+//! say: `build` reports an unsupported combination, such as lookups on R1CS or a pass
+//! pipeline naming an unknown pass, as soon as the settings are known.
+
+use crate::optimization::field::FieldConfig;
+use crate::{FCMCError, TargetSystem, FCMC};
+
+#[derive(Debug, Clone)]
+pub struct CompilerConfig {
+    optimization_level: u8,
+    target: TargetSystem,
+    /// The target's default field if unset
+    field: Option<FieldConfig>,
+    verification: bool,
+    /// The level's pipeline if unset
+    passes: Option<Vec<String>>,
+    /// Whatever the target supports if unset
+    lookups: Option<bool>,
+}
+
+impl Default for CompilerConfig {
+    fn default() -> Self {
+        Self {
+            optimization_level: 2,
+            target: TargetSystem::R1CS,
+            field: None,
+            verification: true,
+            passes: None,
+            lookups: None,
+        }
+    }
+}
+
+impl CompilerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_optimization_level(mut self, level: u8) -> Self {
+        self.optimization_level = level;
+        self
+    }
+
+    pub fn with_target(mut self, target: TargetSystem) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_field(mut self, field: FieldConfig) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    /// See `FCMC::with_verification`
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verification = verify;
+        self
+    }
+
+    /// See `FCMC::with_passes`
+    pub fn with_passes(mut self, passes: &[&str]) -> Self {
+        self.passes = Some(passes.iter().map(|pass| pass.to_string()).collect());
+        self
+    }
+
+    pub fn with_lookups(mut self, lookups: bool) -> Self {
+        self.lookups = Some(lookups);
+        self
+    }
+
+    /// The compiler these settings describe, or the first reason they cannot work together
+    pub fn build(&self) -> Result<FCMC, FCMCError> {
+        let mut compiler = FCMC::new()
+            .with_target(self.target.clone())
+            .with_optimization_level(self.optimization_level)
+            .with_verification(self.verification);
+        if let Some(field) = &self.field {
+            compiler = compiler.with_field(field.clone());
+        }
+        if let Some(passes) = &self.passes {
+            let passes: Vec<&str> = passes.iter().map(String::as_str).collect();
+            compiler = compiler.with_passes(&passes);
+        }
+        if let Some(lookups) = self.lookups {
+            compiler = compiler.with_lookups(lookups);
+        }
+        compiler.validate()?;
+        Ok(compiler)
+    }
+}
@@ -0,0 +1,179 @@
+//! Errors and warnings as diagnostics for editors and CI: a code, a severity, a source
+//! position, a message and suggested fixes.
+//!
+//! `DiagnosticsFormat::Human` renders `file:line:column: severity[code]: message`, then a
+//! `help:` line per suggestion, which GitHub Actions problem matchers, VS Code's `$gcc`
+//! matcher and Emacs' compilation mode read as they are. `DiagnosticsFormat::Json`
+//! renders one JSON object per diagnostic, on one line, with the same fields and the
+//! human form under `rendered`, as rustc's `--error-format=json` does.
+//!
+//! Compile errors carry no position of their own; one whose message names a line is
+//! placed on it, and the others on the whole file.
+
+use crate::backend::{LintFinding, Severity};
+use crate::ir::SourceSpan;
+use crate::optimization::{ConstraintProblem, SuspiciousConstraint};
+use crate::FCMCError;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// How the compiler and `fcmc` print diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosticsFormat {
+    /// Through the log, one line per diagnostic and one per suggestion
+    #[default]
+    Human,
+    /// One JSON object per line on stderr
+    Json,
+}
+
+impl FromStr for DiagnosticsFormat {
+    type Err = FCMCError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "human" => Ok(DiagnosticsFormat::Human),
+            "json" => Ok(DiagnosticsFormat::Json),
+            _ => Err(FCMCError::ParseError(format!("unknown diagnostics format '{}'", name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// What kind of problem this is, such as `parse-error` or `unchecked-comparison`;
+    /// stable across releases, for filtering
+    pub code: String,
+    pub severity: Severity,
+    /// The source file, when the compiler was given its name
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// Only known together with the line
+    pub column: Option<usize>,
+    pub message: String,
+    /// Fixes worth trying, in plain words
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    fn new(code: &str, severity: Severity, span: Option<SourceSpan>, message: String) -> Self {
+        Self {
+            code: code.to_string(),
+            severity,
+            file: None,
+            line: span.map(|span| span.line),
+            column: span.map(|span| span.column),
+            message,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn from_error(error: &FCMCError) -> Self {
+        let (code, message) = match error {
+            FCMCError::ParseError(message) => ("parse-error", message.clone()),
+            FCMCError::TypeError(message) => ("type-error", message.clone()),
+            FCMCError::SemanticError(message) => ("semantic-error", message.clone()),
+            FCMCError::OptimizationError(message) => ("optimization-error", message.clone()),
+            FCMCError::BackendError(message) => ("backend-error", message.clone()),
+            FCMCError::VerificationError(message) => ("verification-error", message.clone()),
+            FCMCError::Cancelled => ("cancelled", error.to_string()),
+            FCMCError::MemoryLimit { .. } => ("memory-limit", error.to_string()),
+        };
+        let mut diagnostic = Self::new(code, Severity::Error, None, message);
+        diagnostic.line = line_in(&diagnostic.message);
+        diagnostic
+    }
+
+    pub fn from_lint(finding: &LintFinding) -> Self {
+        let mut diagnostic = Self::new(
+            &finding.lint.as_str().replace(' ', "-"),
+            finding.severity(),
+            finding.span,
+            finding.message.clone(),
+        );
+        diagnostic.suggestions.push(finding.lint.fix().to_string());
+        diagnostic
+    }
+
+    /// A constraint that never or always holds; a warning, since the circuit still compiles
+    pub fn from_suspicious(constraint: &SuspiciousConstraint) -> Self {
+        let (code, fix) = match constraint.problem {
+            ConstraintProblem::Unsatisfiable => (
+                "unsatisfiable-constraint",
+                "No proof of the circuit exists; check the constraint reads the variables it was meant to.",
+            ),
+            ConstraintProblem::TriviallyTrue => (
+                "trivial-constraint",
+                "The constraint checks nothing; make it read the input-dependent value it was meant to check.",
+            ),
+        };
+        let mut diagnostic = Self::new(code, Severity::Warning, constraint.span, constraint.describe());
+        diagnostic.suggestions.push(fix.to_string());
+        diagnostic
+    }
+
+    /// Hints no constraint reads, when the compiler lets them through
+    pub(crate) fn unconstrained_hints(message: String) -> Self {
+        let mut diagnostic = Self::new("unconstrained-hint", Severity::Warning, None, message);
+        diagnostic
+            .suggestions
+            .push("Add a constraint reading each hint, such as checking a quotient against its product.".to_string());
+        diagnostic
+    }
+
+    /// Place the diagnostic in `file`, unless it already names one
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file.get_or_insert_with(|| file.into());
+        self
+    }
+
+    pub fn render(&self, format: DiagnosticsFormat) -> String {
+        match format {
+            DiagnosticsFormat::Human => self.render_human(),
+            DiagnosticsFormat::Json => self.to_json().to_string(),
+        }
+    }
+
+    /// The diagnostic line, then one `help:` line per suggestion
+    pub fn render_human(&self) -> String {
+        let location: Vec<String> = self
+            .file
+            .iter()
+            .cloned()
+            .chain(self.line.map(|line| line.to_string()))
+            .chain(self.column.map(|column| column.to_string()))
+            .collect();
+        let mut rendered = if location.is_empty() {
+            String::new()
+        } else {
+            format!("{}: ", location.join(":"))
+        };
+        rendered.push_str(&format!("{}[{}]: {}", self.severity.as_str(), self.code, self.message));
+        for suggestion in &self.suggestions {
+            rendered.push_str(&format!("\n  help: {}", suggestion));
+        }
+        rendered
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "code": self.code,
+            "severity": self.severity.as_str(),
+            "file": self.file,
+            "line": self.line,
+            "column": self.column,
+            "message": self.message,
+            "suggestions": self.suggestions,
+            "rendered": self.render_human(),
+        })
+    }
+}
+
+/// The first `line N` in `message`, as the property and annotation errors write it
+fn line_in(message: &str) -> Option<usize> {
+    message.match_indices("line ").find_map(|(position, word)| {
+        let rest = &message[position + word.len()..];
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        rest[..digits].parse().ok()
+    })
+}
@@ -0,0 +1,192 @@
+//! What changed between two compiled circuits, to review the effect of a source change
+//! on the circuit rather than on the source.
+//!
+//! Constraint counts are compared per function through each circuit's profile, so the
+//! artifacts need their source outline; rows outside any function, and all rows of
+//! circuits saved without one, only count towards the totals. The signature is compared
+//! name by name: an added, removed or moved public value changes what a verifier reads,
+//! while changes to the private inputs only concern provers.
+
+use crate::backend::CircuitBackend;
+use crate::CompiledCircuit;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// A function whose constraint count differs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionChange {
+    pub function: String,
+    /// 0 where the function has no rows
+    pub old: usize,
+    pub new: usize,
+}
+
+impl FunctionChange {
+    pub fn delta(&self) -> i64 {
+        self.new as i64 - self.old as i64
+    }
+}
+
+/// How one list of the signature changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NamesChange {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Whether the names both circuits have come in a different order
+    pub reordered: bool,
+}
+
+impl NamesChange {
+    fn between(old: &[String], new: &[String]) -> Self {
+        let added: Vec<String> = new.iter().filter(|name| !old.contains(name)).cloned().collect();
+        let removed: Vec<String> = old.iter().filter(|name| !new.contains(name)).cloned().collect();
+        let kept_old = old.iter().filter(|name| new.contains(name));
+        let kept_new = new.iter().filter(|name| old.contains(name));
+        Self {
+            added,
+            removed,
+            reordered: !kept_old.eq(kept_new),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && !self.reordered
+    }
+
+    fn summary(&self, list: &str) -> Vec<String> {
+        let mut lines: Vec<String> = self.added.iter().map(|name| format!("+ {} {}", list, name)).collect();
+        lines.extend(self.removed.iter().map(|name| format!("- {} {}", list, name)));
+        if self.reordered {
+            lines.push(format!("~ {} reordered", list));
+        }
+        lines
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "added": self.added,
+            "removed": self.removed,
+            "reordered": self.reordered,
+        })
+    }
+}
+
+/// Differences between an old and a new circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitDiff {
+    /// The targets, when they differ
+    pub target: Option<(String, String)>,
+    /// The fields, when they differ
+    pub field: Option<(String, String)>,
+    pub old_constraints: usize,
+    pub new_constraints: usize,
+    /// Largest change first
+    pub functions: Vec<FunctionChange>,
+    pub public_outputs: NamesChange,
+    pub public_inputs: NamesChange,
+    pub private_inputs: NamesChange,
+}
+
+impl CircuitDiff {
+    pub fn between(old: &CompiledCircuit, new: &CompiledCircuit) -> Self {
+        let differs = |before: String, after: String| if before == after { None } else { Some((before, after)) };
+        let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for (function, constraints) in old.profile().functions() {
+            counts.entry(function).or_default().0 = constraints;
+        }
+        for (function, constraints) in new.profile().functions() {
+            counts.entry(function).or_default().1 = constraints;
+        }
+        let mut functions: Vec<FunctionChange> = counts
+            .into_iter()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(function, (old, new))| FunctionChange { function, old, new })
+            .collect();
+        functions.sort_by_key(|change| Reverse(change.delta().abs()));
+
+        let (old_signature, new_signature) = (old.signature(), new.signature());
+        Self {
+            target: differs(
+                format!("{:?}", old.circuit.target()),
+                format!("{:?}", new.circuit.target()),
+            ),
+            field: differs(old.field.to_string(), new.field.to_string()),
+            old_constraints: old.circuit.constraint_count(),
+            new_constraints: new.circuit.constraint_count(),
+            functions,
+            public_outputs: NamesChange::between(&old_signature.public_outputs, &new_signature.public_outputs),
+            public_inputs: NamesChange::between(&old_signature.public_inputs, &new_signature.public_inputs),
+            private_inputs: NamesChange::between(&old_signature.private_inputs, &new_signature.private_inputs),
+        }
+    }
+
+    /// Whether a verifier of the old circuit reads different public values from the new
+    /// one, or works in another proof system or field
+    pub fn verifier_changed(&self) -> bool {
+        self.target.is_some()
+            || self.field.is_some()
+            || !self.public_outputs.is_empty()
+            || !self.public_inputs.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.verifier_changed()
+            && self.old_constraints == self.new_constraints
+            && self.functions.is_empty()
+            && self.private_inputs.is_empty()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        if self.is_empty() {
+            return vec!["no changes".to_string()];
+        }
+        let mut lines = Vec::new();
+        if let Some((old, new)) = &self.target {
+            lines.push(format!("target: {} -> {}", old, new));
+        }
+        if let Some((old, new)) = &self.field {
+            lines.push(format!("field: {} -> {}", old, new));
+        }
+        lines.push(format!(
+            "constraints: {} -> {} ({:+})",
+            self.old_constraints,
+            self.new_constraints,
+            self.new_constraints as i64 - self.old_constraints as i64
+        ));
+        for change in &self.functions {
+            lines.push(format!(
+                "  fn {}: {} -> {} ({:+})",
+                change.function,
+                change.old,
+                change.new,
+                change.delta()
+            ));
+        }
+        lines.extend(self.public_outputs.summary("public output"));
+        lines.extend(self.public_inputs.summary("public input"));
+        lines.extend(self.private_inputs.summary("private input"));
+        if self.verifier_changed() {
+            lines.push("the verifier's view of the circuit changed".to_string());
+        }
+        lines
+    }
+
+    pub fn to_json(&self) -> Value {
+        let pair = |pair: &Option<(String, String)>| pair.as_ref().map(|(old, new)| json!({ "old": old, "new": new }));
+        json!({
+            "target": pair(&self.target),
+            "field": pair(&self.field),
+            "constraints": { "old": self.old_constraints, "new": self.new_constraints },
+            "functions": self
+                .functions
+                .iter()
+                .map(|change| json!({ "function": change.function, "old": change.old, "new": change.new }))
+                .collect::<Vec<_>>(),
+            "public_outputs": self.public_outputs.to_json(),
+            "public_inputs": self.public_inputs.to_json(),
+            "private_inputs": self.private_inputs.to_json(),
+            "verifier_changed": self.verifier_changed(),
+        })
+    }
+}
@@ -0,0 +1,49 @@
+//! End-to-end Groth16 run for integration tests: compile, set up, prove and verify.
+//!
+//! The keys come from a fixed seed, so this is only a test of the whole chain, not a
+//! trusted setup. The pairing is picked by the field the circuit was compiled over:
+//! BN254 or BLS12-381.
+
+use crate::backend::arkworks::ArkworksCircuit;
+use crate::optimization::field::FieldConfig;
+use crate::{CompiledCircuit, FCMCError, InputMap};
+use ark_ec::pairing::Pairing;
+use ark_groth16::Groth16;
+use ark_snark::SNARK;
+use ark_std::rand::rngs::StdRng;
+use ark_std::rand::SeedableRng;
+
+const SEED: u64 = 0x6_1607_5eed;
+
+/// Set up Groth16 keys for `circuit`, prove it on `inputs` and verify the proof against
+/// the public values; `Ok(false)` if the proof does not verify, which is what an
+/// unsatisfiable witness gives
+pub fn prove_and_verify(circuit: &CompiledCircuit, inputs: &InputMap) -> Result<bool, FCMCError> {
+    match circuit.field {
+        FieldConfig::Bn254 => prove_and_verify_with::<ark_bn254::Bn254>(circuit, inputs),
+        FieldConfig::Bls12_381 => prove_and_verify_with::<ark_bls12_381::Bls12_381>(circuit, inputs),
+        ref field => Err(FCMCError::BackendError(format!(
+            "Groth16 needs a circuit over bn254 or bls12-381, not {}",
+            field
+        ))),
+    }
+}
+
+fn prove_and_verify_with<E: Pairing>(circuit: &CompiledCircuit, inputs: &InputMap) -> Result<bool, FCMCError> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let (proving_key, verifying_key) =
+        Groth16::<E>::circuit_specific_setup(ArkworksCircuit::setup(circuit)?, &mut rng).map_err(groth16_error)?;
+
+    let prover = ArkworksCircuit::prove(circuit, inputs)?;
+    let public = prover
+        .public_inputs::<E::ScalarField>()
+        .ok_or_else(|| FCMCError::BackendError("The prover has no witness".to_string()))?;
+    let proof = Groth16::<E>::prove(&proving_key, prover, &mut rng).map_err(groth16_error)?;
+
+    let prepared = Groth16::<E>::process_vk(&verifying_key).map_err(groth16_error)?;
+    Groth16::<E>::verify_with_processed_vk(&prepared, &public, &proof).map_err(groth16_error)
+}
+
+fn groth16_error(e: impl std::fmt::Display) -> FCMCError {
+    FCMCError::BackendError(format!("Groth16 failed: {}", e))
+}
@@ -0,0 +1,114 @@
+//! Exhaustive verification of circuits with small input domains.
+//!
+//! When every input is a boolean or a small unsigned integer, the compiled circuit is run
+//! on every assignment of the inputs and checked against the IR interpreter on the
+//! unoptimized program: the same assignments are accepted and give the same outputs. For
+//! small gadgets this is a proof of functional equivalence rather than a test.
+//!
+//! An input's domain is its declared type, narrowed by a `bits` attribute or by a range
+//! check that reads it directly; values outside a range check are rejected by the check
+//! itself rather than enumerated.
+
+use crate::fuzz::compare_with_interpreter;
+use crate::ir::IRNodeType;
+use crate::language::ast::Type;
+use crate::optimization::range_check::range_checks;
+use crate::{CompiledCircuit, FCMCError, InputMap};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+/// Most input assignments the exhaustive check enumerates
+pub const MAX_EXHAUSTIVE_ASSIGNMENTS: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExhaustiveReport {
+    /// Bit width of each input's domain, by name
+    pub domains: Vec<(String, u32)>,
+    /// Assignments both circuits accept with the same outputs
+    pub accepted: u64,
+    /// Assignments both circuits reject
+    pub rejected: u64,
+}
+
+impl ExhaustiveReport {
+    pub fn assignments(&self) -> u64 {
+        self.accepted + self.rejected
+    }
+}
+
+/// Check `circuit` against the interpreter on `reference`, the same program compiled
+/// without optimization, on every assignment of the inputs
+pub(crate) fn verify_exhaustive(
+    reference: &CompiledCircuit,
+    circuit: &CompiledCircuit,
+) -> Result<ExhaustiveReport, FCMCError> {
+    let domains = input_domains(reference)?;
+    let total_bits: u32 = domains.iter().map(|(_, bits)| bits).sum();
+    if total_bits as u64 >= u64::BITS as u64 || 1u64 << total_bits > MAX_EXHAUSTIVE_ASSIGNMENTS {
+        return Err(FCMCError::VerificationError(format!(
+            "The inputs span 2^{} assignments, more than the {} the exhaustive check enumerates",
+            total_bits, MAX_EXHAUSTIVE_ASSIGNMENTS
+        )));
+    }
+
+    let mut report = ExhaustiveReport {
+        domains: domains.clone(),
+        ..ExhaustiveReport::default()
+    };
+    for assignment in 0u64..1 << total_bits {
+        let mut offset = 0;
+        let inputs: InputMap = domains
+            .iter()
+            .map(|(name, bits)| {
+                let value = (assignment >> offset) & ((1u64 << bits) - 1);
+                offset += bits;
+                (name.clone(), BigUint::from(value))
+            })
+            .collect();
+        if compare_with_interpreter(reference, circuit, "Compiled circuit", &inputs)? {
+            report.accepted += 1;
+        } else {
+            report.rejected += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// Bit width of the domain of each input, by name; fails for an input no narrower than a
+/// field element
+fn input_domains(compiled: &CompiledCircuit) -> Result<Vec<(String, u32)>, FCMCError> {
+    let graph = &compiled.ir;
+    let mut checked: HashMap<usize, u32> = HashMap::new();
+    for (_, wire, bits) in range_checks(graph) {
+        let entry = checked.entry(wire).or_insert(bits);
+        *entry = (*entry).min(bits);
+    }
+
+    let mut domains: Vec<(String, u32)> = Vec::new();
+    for node in graph.live_nodes() {
+        let name = match &node.node_type {
+            IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => name,
+            _ => continue,
+        };
+        let declared = match node.data_type {
+            Type::Bool => Some(1),
+            Type::U32 => Some(32),
+            _ => None,
+        };
+        let attribute = node.attributes.get("bits").and_then(|bits| bits.parse::<u32>().ok());
+        let bits = [declared, attribute, checked.get(&node.id).copied()]
+            .into_iter()
+            .flatten()
+            .min()
+            .ok_or_else(|| {
+                FCMCError::VerificationError(format!(
+                    "Input '{}' ranges over the whole field and cannot be enumerated",
+                    name
+                ))
+            })?;
+        if !domains.iter().any(|(seen, _)| seen == name) {
+            domains.push((name.clone(), bits));
+        }
+    }
+    Ok(domains)
+}
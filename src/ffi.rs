@@ -0,0 +1,206 @@
+//! C API, so the compiler can be embedded in other toolchains through the shared
+//! library built with the `ffi` feature. `cbindgen.toml` at the crate root generates the
+//! header `fcmc.h`.
+//!
+//! Circuits are opaque `FcmcCircuit` pointers owned by the caller, released with
+//! `fcmc_free_circuit`, and strings the library returns are released with
+//! `fcmc_free_string`. A function that fails returns `NULL` or a negative status and
+//! leaves a message that `fcmc_last_error` returns, per thread, until the next call
+//! fails. Panics are caught at the boundary and reported the same way.
+
+use crate::backend::CircuitBackend;
+use crate::optimization::field::FieldConfig;
+use crate::{CompiledCircuit, FCMCError, TargetSystem, FCMC};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// A compiled circuit
+pub struct FcmcCircuit {
+    compiled: CompiledCircuit,
+}
+
+/// Returned by functions with nothing else to return
+pub const FCMC_OK: i32 = 0;
+
+/// Returned on failure, with the reason left for `fcmc_last_error`
+pub const FCMC_ERROR: i32 = -1;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message on the C side
+    let message = CString::new(message.replace('\0', " ")).expect("NUL bytes were replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning an error or a panic into `fallback` and a message for
+/// `fcmc_last_error`
+fn guard<T>(fallback: T, f: impl FnOnce() -> Result<T, FCMCError>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(error)) => {
+            set_last_error(error.to_string());
+            fallback
+        }
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .map(|reason| reason.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("Internal compiler error: {}", reason));
+            fallback
+        }
+    }
+}
+
+/// The UTF-8 string at `pointer`, `None` for `NULL`
+///
+/// # Safety
+/// `pointer` is `NULL` or a NUL-terminated string valid for the call.
+unsafe fn optional_str<'a>(pointer: *const c_char, name: &str) -> Result<Option<&'a str>, FCMCError> {
+    if pointer.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map(Some)
+        .map_err(|_| FCMCError::ParseError(format!("{} is not valid UTF-8", name)))
+}
+
+/// # Safety
+/// As for `optional_str`.
+unsafe fn required_str<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, FCMCError> {
+    optional_str(pointer, name)?.ok_or_else(|| FCMCError::ParseError(format!("{} is NULL", name)))
+}
+
+/// # Safety
+/// `circuit` is `NULL` or a pointer from this library not yet freed.
+unsafe fn circuit_ref<'a>(circuit: *const FcmcCircuit) -> Result<&'a CompiledCircuit, FCMCError> {
+    circuit
+        .as_ref()
+        .map(|circuit| &circuit.compiled)
+        .ok_or_else(|| FCMCError::BackendError("circuit is NULL".to_string()))
+}
+
+fn into_raw_string(value: String) -> Result<*mut c_char, FCMCError> {
+    CString::new(value)
+        .map(CString::into_raw)
+        .map_err(|_| FCMCError::BackendError("the result contains a NUL byte".to_string()))
+}
+
+/// Compile `source` for `target` (`"r1cs"`, `"plonk"`, …; R1CS if `NULL`) at
+/// `optimization_level` over `field` (`"bn254"`, `"bls12-381"`, … or a decimal prime; the
+/// target's default if `NULL`). Returns `NULL` on failure.
+///
+/// # Safety
+/// `source` is a NUL-terminated string; `target` and `field` are `NULL` or NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_compile(
+    source: *const c_char,
+    target: *const c_char,
+    optimization_level: u8,
+    field: *const c_char,
+) -> *mut FcmcCircuit {
+    guard(ptr::null_mut(), || {
+        let source = required_str(source, "source")?;
+        let mut compiler = FCMC::new().with_optimization_level(optimization_level);
+        if let Some(target) = optional_str(target, "target")? {
+            compiler = compiler.with_target(target.parse::<TargetSystem>()?);
+        }
+        if let Some(field) = optional_str(field, "field")? {
+            compiler = compiler.with_field(field.parse::<FieldConfig>()?);
+        }
+        let compiled = compiler.compile(source)?;
+        Ok(Box::into_raw(Box::new(FcmcCircuit { compiled })))
+    })
+}
+
+/// Read a circuit written by `fcmc_save_circuit` or `fcmc compile`. Returns `NULL` on
+/// failure.
+///
+/// # Safety
+/// `path` is a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_load_circuit(path: *const c_char) -> *mut FcmcCircuit {
+    guard(ptr::null_mut(), || {
+        let compiled = CompiledCircuit::load(required_str(path, "path")?)?;
+        Ok(Box::into_raw(Box::new(FcmcCircuit { compiled })))
+    })
+}
+
+/// Write `circuit` to `path` in FCMC's binary format
+///
+/// # Safety
+/// `circuit` comes from this library and is not freed; `path` is a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_save_circuit(circuit: *const FcmcCircuit, path: *const c_char) -> i32 {
+    guard(FCMC_ERROR, || {
+        circuit_ref(circuit)?.save(required_str(path, "path")?)?;
+        Ok(FCMC_OK)
+    })
+}
+
+/// Release a circuit; `NULL` is ignored
+///
+/// # Safety
+/// `circuit` is `NULL` or comes from this library and is not used again.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_free_circuit(circuit: *mut FcmcCircuit) {
+    if !circuit.is_null() {
+        drop(Box::from_raw(circuit));
+    }
+}
+
+/// Constraints of `circuit`, or -1 for `NULL`
+///
+/// # Safety
+/// `circuit` is `NULL` or comes from this library and is not freed.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_get_constraint_count(circuit: *const FcmcCircuit) -> i64 {
+    guard(FCMC_ERROR as i64, || {
+        Ok(circuit_ref(circuit)?.circuit.constraint_count() as i64)
+    })
+}
+
+/// The circuit in its target's JSON form, to release with `fcmc_free_string`; `NULL`
+/// on failure
+///
+/// # Safety
+/// `circuit` comes from this library and is not freed.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_circuit_to_json(circuit: *const FcmcCircuit) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let compiled = circuit_ref(circuit)?;
+        into_raw_string(compiled.in_field(|| compiled.circuit.to_json()).to_string())
+    })
+}
+
+/// Release a string returned by this library; `NULL` is ignored
+///
+/// # Safety
+/// `string` is `NULL` or was returned by this library and is not used again.
+#[no_mangle]
+pub unsafe extern "C" fn fcmc_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Message of the last failure on the calling thread, `NULL` if nothing failed. The
+/// library owns the string, which stays valid until the next failure on the thread.
+#[no_mangle]
+pub extern "C" fn fcmc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// The library's version, a static NUL-terminated string
+#[no_mangle]
+pub extern "C" fn fcmc_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
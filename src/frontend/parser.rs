@@ -294,7 +294,14 @@ impl Parser {
                 Ok(Expression::Literal(Literal::Number(value)))
             }
             TokenKind::Identifier => {
-                let name = self.advance().lexeme.clone();
+                let mut name = self.advance().lexeme.clone();
+                // Paths into the standard library, e.g. `std::hash::poseidon`
+                while self.check_path_separator() {
+                    self.advance();
+                    self.advance();
+                    name.push_str("::");
+                    name.push_str(&self.advance().lexeme);
+                }
                 if self.check(TokenKind::LParen) {
                     self.parse_function_call(name)
                 } else {
@@ -371,7 +378,24 @@ impl Parser {
             }
             TokenKind::Identifier => {
                 let name = self.advance().lexeme.clone();
-                if self.check(TokenKind::LBracket) {
+                if name == "BigUint" && self.check(TokenKind::Less) {
+                    // BigUint<N> is N 64-bit limbs, least significant first, for std::bigint
+                    self.advance(); // Consume '<'
+                    let limbs = match self.consume(TokenKind::Number, "Expected limb count")?.lexeme.parse() {
+                        Ok(limbs) if limbs > 0 => limbs,
+                        _ => return Err(FCMCError::ParseError("Expected a positive limb count".to_string())),
+                    };
+                    self.consume(TokenKind::Greater, "Expected '>'")?;
+                    Ok(Type::Array(Box::new(Type::Field), limbs))
+                } else if name == "Fixed" && self.check(TokenKind::Less) {
+                    // Fixed<I, F> is a field element; std::fixed takes I and F as arguments
+                    self.advance(); // Consume '<'
+                    self.consume(TokenKind::Number, "Expected integer bits")?;
+                    self.consume(TokenKind::Comma, "Expected ','")?;
+                    self.consume(TokenKind::Number, "Expected fraction bits")?;
+                    self.consume(TokenKind::Greater, "Expected '>'")?;
+                    Ok(Type::Field)
+                } else if self.check(TokenKind::LBracket) {
                     self.advance(); // Consume '['
                     let size = match self.parse_expression()? {
                         Expression::Literal(Literal::Number(n)) => n.parse().unwrap_or(0),
@@ -509,6 +533,14 @@ impl Parser {
         !self.is_at_end() && self.peek().kind == kind
     }
     
+    /// `::` followed by an identifier, which the lexer gives as two colons
+    fn check_path_separator(&self) -> bool {
+        let kind = |offset: usize| self.tokens.get(self.position + offset).map(|token| &token.kind);
+        kind(0) == Some(&TokenKind::Colon)
+            && kind(1) == Some(&TokenKind::Colon)
+            && kind(2) == Some(&TokenKind::Identifier)
+    }
+    
     fn consume(&mut self, kind: TokenKind, message: &str) -> Result<&Token, FCMCError> {
         if self.check(kind) {
             Ok(self.advance())
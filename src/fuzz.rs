@@ -0,0 +1,178 @@
+//! Differential fuzzing of the whole compiler.
+//!
+//! Random well-typed programs are compiled at every optimization level, and on random
+//! inputs the witness each compiled circuit computes must give the outputs the IR
+//! interpreter gives for the unoptimized program, and must be refused exactly when the
+//! interpreter rejects the inputs. The `fuzz/` crate drives this from `cargo fuzz`;
+//! `check_program` is also usable from ordinary tests with a fixed seed.
+
+use crate::optimization::interpreter::{evaluate, input_names};
+use crate::optimization::soundness::random_value;
+use crate::{CompiledCircuit, FCMCError, InputMap, FCMC};
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Optimization levels every program is compiled at
+pub const FUZZ_LEVELS: [u8; 4] = [0, 1, 2, 3];
+
+/// Random input assignments each compiled program is run on unless configured otherwise
+pub const DEFAULT_FUZZ_SAMPLES: usize = 8;
+
+const MAX_INPUTS: usize = 4;
+const MAX_STATEMENTS: usize = 8;
+const MAX_DEPTH: u32 = 3;
+
+/// What a program that passed was checked on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzReport {
+    /// Input assignments the interpreter accepted, compared at every level
+    pub accepted: usize,
+    /// Input assignments the interpreter rejected, refused at every level
+    pub rejected: usize,
+}
+
+/// A random program of `let` bindings over field inputs and arithmetic, returning one value
+pub fn generate_program(rng: &mut impl Rng) -> String {
+    let inputs = rng.gen_range(1..=MAX_INPUTS);
+    let mut variables: Vec<String> = (0..inputs).map(|index| format!("x{}", index)).collect();
+    let params: Vec<String> = variables.iter().map(|name| format!("{}: field", name)).collect();
+
+    let mut body = String::new();
+    for index in 0..rng.gen_range(0..=MAX_STATEMENTS) {
+        let value = expression(rng, &variables, MAX_DEPTH);
+        let name = format!("v{}", index);
+        body.push_str(&format!("    let {}: field = {};\n", name, value));
+        variables.push(name);
+    }
+    body.push_str(&format!("    return {};\n", expression(rng, &variables, MAX_DEPTH)));
+    format!("fn main({}) -> field {{\n{}}}\n", params.join(", "), body)
+}
+
+/// The program `generate_program` makes from a seed taken from `data`, so a fuzzer's byte
+/// mutations explore programs
+pub fn program_from_bytes(data: &[u8]) -> String {
+    let mut seed = [0u8; 32];
+    for (index, byte) in data.iter().enumerate() {
+        seed[index % seed.len()] ^= byte.rotate_left((index / seed.len()) as u32);
+    }
+    generate_program(&mut StdRng::from_seed(seed))
+}
+
+/// Compile `source` at every level in `FUZZ_LEVELS` and compare each circuit's witness
+/// with the interpreter on `samples` random inputs; any error is a finding
+pub fn check_program(source: &str, samples: usize) -> Result<FuzzReport, FCMCError> {
+    let reference = FCMC::new().with_optimization_level(0).compile(source)?;
+    let mut compiled = Vec::new();
+    for level in FUZZ_LEVELS {
+        let circuit = FCMC::new()
+            .with_optimization_level(level)
+            .compile(source)
+            .map_err(|e| {
+                let what = format!("Circuit at optimization level {}", level);
+                finding(&what, "does not compile", &e.to_string())
+            })?;
+        compiled.push((level, circuit));
+    }
+
+    let names = input_names(&reference.ir);
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let mut rng = StdRng::seed_from_u64(hasher.finish());
+    let mut report = FuzzReport::default();
+    for _ in 0..samples {
+        let inputs: HashMap<String, BigUint> = reference.in_field(|| {
+            names
+                .iter()
+                .map(|name| (name.clone(), random_value(&mut rng)))
+                .collect()
+        });
+        let mut accepted = false;
+        for (level, circuit) in &compiled {
+            let what = format!("Circuit at optimization level {}", level);
+            accepted = compare_with_interpreter(&reference, circuit, &what, &inputs)?;
+        }
+        if accepted {
+            report.accepted += 1;
+        } else {
+            report.rejected += 1;
+        }
+    }
+    Ok(report)
+}
+
+/// Check that `circuit` accepts `inputs` exactly when the interpreter accepts them on the
+/// IR of `reference`, and then computes the same outputs; `Ok(true)` if both accept and
+/// `Ok(false)` if both reject. Errors name the circuit as `what`
+pub(crate) fn compare_with_interpreter(
+    reference: &CompiledCircuit,
+    circuit: &CompiledCircuit,
+    what: &str,
+    inputs: &InputMap,
+) -> Result<bool, FCMCError> {
+    let expected = reference
+        .in_field(|| evaluate(&reference.ir, inputs))
+        .ok()
+        .filter(|evaluation| evaluation.is_satisfied())
+        .map(|evaluation| {
+            let mut outputs = evaluation.outputs;
+            outputs.sort();
+            outputs
+        });
+    match (expected, circuit.compute_witness(inputs)) {
+        (Some(expected), Ok(witness)) => {
+            let mut outputs = witness.outputs;
+            outputs.sort();
+            if outputs != expected {
+                let detail = format!("outputs {:?} instead of {:?}", outputs, expected);
+                return Err(finding(what, "computes wrong outputs", &with_inputs(inputs, &detail)));
+            }
+            Ok(true)
+        }
+        (Some(_), Err(e)) => Err(finding(
+            what,
+            "rejects valid inputs",
+            &with_inputs(inputs, &e.to_string()),
+        )),
+        (None, Ok(_)) => Err(finding(
+            what,
+            "accepts invalid inputs",
+            &with_inputs(inputs, "the interpreter rejects"),
+        )),
+        (None, Err(_)) => Ok(false),
+    }
+}
+
+fn expression(rng: &mut impl Rng, variables: &[String], depth: u32) -> String {
+    if depth == 0 || rng.gen_bool(0.3) {
+        return if rng.gen_bool(0.75) {
+            variables[rng.gen_range(0..variables.len())].clone()
+        } else {
+            rng.gen_range(0u32..16).to_string()
+        };
+    }
+    let a = expression(rng, variables, depth - 1);
+    match rng.gen_range(0..4) {
+        0 => format!("({} + {})", a, expression(rng, variables, depth - 1)),
+        1 => format!("({} - {})", a, expression(rng, variables, depth - 1)),
+        2 => format!("({} * {})", a, expression(rng, variables, depth - 1)),
+        // Divisors are nonzero constants, so every input is valid
+        _ => format!("({} / {})", a, rng.gen_range(1u32..8)),
+    }
+}
+
+fn with_inputs(inputs: &HashMap<String, BigUint>, detail: &str) -> String {
+    let mut assignment: Vec<String> = inputs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    assignment.sort();
+    format!("on inputs [{}] {}", assignment.join(", "), detail)
+}
+
+fn finding(what: &str, problem: &str, detail: &str) -> FCMCError {
+    FCMCError::VerificationError(format!("{} {}: {}", what, problem, detail))
+}
@@ -0,0 +1,4 @@
+//! Reference semantics for the source language, reachable beside the AST it evaluates;
+//! the implementation lives in `crate::semantics`.
+
+pub use crate::semantics::*;
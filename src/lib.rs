@@ -7,12 +7,65 @@ pub mod optimization;
 pub mod backend;
 pub mod language;
 pub mod utils;
+pub mod artifact;
+pub mod audit;
+pub mod config;
+pub mod witness;
+pub mod trace;
+pub mod fuzz;
+pub mod bench;
+pub mod testing;
+pub mod exhaustive;
+pub mod semantics;
+pub mod stdlib;
+pub mod project;
+pub mod package;
+pub mod profile;
+pub mod diagnostics;
+pub mod diff;
+pub mod repl;
+pub mod progress;
+pub mod report;
+pub mod memory;
+pub mod session;
+pub mod platform;
+pub mod watch;
+#[cfg(feature = "groth16")]
+pub mod e2e;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub use frontend::{compile_source, parse_source};
 pub use ir::{Circuit, IRGraph};
 pub use optimization::OptimizationFramework;
-pub use backend::{TargetSystem, compile_to_target};
+pub use backend::{TargetCircuit, TargetSystem, compile_to_target};
+pub use artifact::CircuitSignature;
+pub use audit::AuditBoundary;
+pub use config::CompilerConfig;
+pub use witness::{InputMap, Witness};
+pub use trace::{ConstraintTrace, TraceStep};
+pub use exhaustive::ExhaustiveReport;
+pub use project::Project;
+pub use package::Package;
+pub use profile::{ConstraintProfile, Frame, ProfileNode, SourceOutline};
+pub use diagnostics::{Diagnostic, DiagnosticsFormat};
+pub use diff::CircuitDiff;
+pub use progress::{CancellationToken, PassProgress, ProgressObserver, Stage};
+pub use report::{CircuitSummary, CompilationReport, ReportFormat, StageReport, TargetMetrics};
+pub use memory::MemoryReport;
+pub use session::{CompilerSession, SessionStats};
+pub use watch::{IncrementalCompiler, Recompilation};
 
+use backend::CircuitBackend;
+use platform::Instant;
+use num_bigint::BigUint;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -34,13 +87,45 @@ pub enum FCMCError {
     
     #[error("Verification error: {0}")]
     VerificationError(String),
+    
+    /// The compilation's `CancellationToken` was cancelled
+    #[error("Compilation cancelled")]
+    Cancelled,
+    
+    /// The compilation held more than `FCMC::with_memory_limit` allows
+    #[error("Memory limit exceeded during {during}: about {used} bytes in use, over the limit of {limit}")]
+    MemoryLimit { during: String, used: usize, limit: usize },
 }
 
 /// Main compiler interface
+#[derive(Clone)]
 pub struct FCMC {
     optimization_level: u8,
     target_system: TargetSystem,
+    field: Option<optimization::field::FieldConfig>,
     verify_output: bool,
+    soundness_samples: usize,
+    exhaustive_equivalence: bool,
+    deny_unconstrained_hints: bool,
+    dump_ir_after: Vec<String>,
+    pass_stats_path: Option<PathBuf>,
+    rewrite_rules: Vec<PathBuf>,
+    rule_sets: Vec<optimization::RuleSet>,
+    packages: Vec<Arc<Package>>,
+    passes: Option<Vec<String>>,
+    lookups: Option<bool>,
+    memory_limit: Option<usize>,
+    explain: bool,
+    time_budget: Option<Duration>,
+    max_node_growth: Option<f64>,
+    inline_policy: optimization::InlinePolicy,
+    public_layout: backend::PublicLayout,
+    sanity_checks: bool,
+    reproducible: bool,
+    progress: Option<Arc<dyn ProgressObserver>>,
+    cancellation: Option<CancellationToken>,
+    diagnostics_format: DiagnosticsFormat,
+    source_name: Option<String>,
 }
 
 impl FCMC {
@@ -48,7 +133,30 @@ impl FCMC {
         Self {
             optimization_level: 2,
             target_system: TargetSystem::R1CS,
+            field: None,
             verify_output: true,
+            soundness_samples: optimization::DEFAULT_SOUNDNESS_SAMPLES,
+            exhaustive_equivalence: false,
+            deny_unconstrained_hints: true,
+            dump_ir_after: Vec::new(),
+            pass_stats_path: None,
+            rewrite_rules: Vec::new(),
+            rule_sets: Vec::new(),
+            packages: Vec::new(),
+            passes: None,
+            lookups: None,
+            memory_limit: None,
+            explain: false,
+            time_budget: None,
+            max_node_growth: None,
+            inline_policy: optimization::InlinePolicy::default(),
+            public_layout: backend::PublicLayout::default(),
+            sanity_checks: false,
+            reproducible: false,
+            progress: None,
+            cancellation: None,
+            diagnostics_format: DiagnosticsFormat::Human,
+            source_name: None,
         }
     }
     
@@ -62,66 +170,905 @@ impl FCMC {
         self
     }
     
+    /// Define the circuit over `field` instead of the target's default field
+    pub fn with_field(mut self, field: optimization::field::FieldConfig) -> Self {
+        self.field = Some(field);
+        self
+    }
+    
+    /// The field circuits are compiled over
+    pub fn field(&self) -> optimization::field::FieldConfig {
+        self.field.clone().unwrap_or_else(|| self.target_system.default_field())
+    }
+    
+    /// Check the optimized IR against the original, the lowered circuit and its hints;
+    /// on by default
+    pub fn with_verification(mut self, verify: bool) -> Self {
+        self.verify_output = verify;
+        self
+    }
+    
+    /// Optimize with these passes, in order, instead of the level's pipeline; entries are
+    /// registered pass names, optionally repeated as `cse*2`
+    pub fn with_passes(mut self, passes: &[&str]) -> Self {
+        self.passes = Some(passes.iter().map(|pass| pass.to_string()).collect());
+        self
+    }
+    
+    /// Whether the optimizer may emit lookups; defaults to whether the target has them
+    pub fn with_lookups(mut self, lookups: bool) -> Self {
+        self.lookups = Some(lookups);
+        self
+    }
+    
+    /// Fail with `FCMCError::MemoryLimit` once the IR and circuit take more than about
+    /// `bytes`, measured after every stage and optimization pass; see `memory`
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+    
+    /// Number of random inputs each optimization pass, and the optimized circuit against
+    /// the original, is differentially tested on when output verification is enabled
+    pub fn with_soundness_samples(mut self, samples: usize) -> Self {
+        self.soundness_samples = samples;
+        self
+    }
+    
+    /// Check the optimized circuit against the original on every input assignment when
+    /// all inputs are constrained to bits and there are at most
+    /// `optimization::MAX_EXHAUSTIVE_INPUTS` of them
+    pub fn with_exhaustive_equivalence(mut self, exhaustive: bool) -> Self {
+        self.exhaustive_equivalence = exhaustive;
+        self
+    }
+    
+    /// Fail output verification when a quotient, inverse, bit or other prover-computed
+    /// hint is read by no constraint (the default), rather than only warning
+    pub fn with_deny_unconstrained_hints(mut self, deny: bool) -> Self {
+        self.deny_unconstrained_hints = deny;
+        self
+    }
+    
+    /// Log a snapshot of the IR after each run of `pass` (`"all"` for every pass)
+    pub fn with_dump_ir_after(mut self, pass: &str) -> Self {
+        self.dump_ir_after.push(pass.to_string());
+        self
+    }
+    
+    /// Write per-pass optimization statistics as JSON to `path`
+    pub fn with_pass_stats_json(mut self, path: impl Into<PathBuf>) -> Self {
+        self.pass_stats_path = Some(path.into());
+        self
+    }
+    
+    /// Load extra algebraic rewrite rules from `path` at compile time
+    pub fn with_rewrite_rules(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rewrite_rules.push(path.into());
+        self
+    }
+    
+    /// Optimize with already parsed rewrite rules too, after any loaded from files
+    pub fn with_rule_set(mut self, rules: optimization::RuleSet) -> Self {
+        self.rule_sets.push(rules);
+        self
+    }
+    
+    /// Let programs call the gadgets of `package` as `<name>::<function>`
+    pub fn with_package(mut self, package: Arc<Package>) -> Self {
+        self.packages.push(package);
+        self
+    }
+    
+    /// Report where each optimization pass saved constraints, by source line
+    pub fn with_explain(mut self, explain: bool) -> Self {
+        self.explain = explain;
+        self
+    }
+    
+    /// Stop optimizing after `budget` and compile the best IR found by then
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+    
+    /// Reject optimization passes that grow the IR beyond `ratio` times its initial size
+    pub fn with_max_node_growth(mut self, ratio: f64) -> Self {
+        self.max_node_growth = Some(ratio);
+        self
+    }
+    
+    /// Force (`Always`) or forbid (`Never`) inlining of calls to `function`
+    pub fn with_inline_hint(mut self, function: &str, hint: optimization::InlineHint) -> Self {
+        self.inline_policy.set_hint(function, hint);
+        self
+    }
+    
+    /// Inline callees of at most `size` expression nodes at every call site
+    pub fn with_max_inline_size(mut self, size: usize) -> Self {
+        self.inline_policy = self.inline_policy.with_max_size(size);
+        self
+    }
+    
+    /// Give the named outputs and public inputs these positions within their group, as an
+    /// existing verifier expects them; the others follow in declaration order
+    pub fn with_public_order(mut self, names: &[&str]) -> Self {
+        self.public_layout.order = names.iter().map(ToString::to_string).collect();
+        self
+    }
+    
+    /// Make `inputs` private and expose them packed into the public output `name`, each
+    /// range-checked to its bit width, the first in the lowest bits
+    pub fn with_packed_inputs(mut self, name: &str, inputs: &[(&str, u32)]) -> Self {
+        self.public_layout.packs.push(backend::PackedInputs::new(name, inputs));
+        self
+    }
+    
+    /// Add the booleanity, lookup index range and non-zero divisor constraints the
+    /// source left out and the target's lowering does not prove itself
+    pub fn with_sanity_checks(mut self, enabled: bool) -> Self {
+        self.sanity_checks = enabled;
+        self
+    }
+    
+    /// Let no wall-clock limit decide the output, so the same source and options always
+    /// give a byte-identical circuit: equality saturation stops only at its iteration and
+    /// node limits, and a time budget is refused
+    pub fn with_reproducible(mut self, reproducible: bool) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+    
+    /// Report each stage, and each optimization pass run, to `observer` as it finishes
+    pub fn with_progress(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.progress = Some(observer);
+        self
+    }
+    
+    /// Fail with `FCMCError::Cancelled` before the next stage or pass once `token` is
+    /// cancelled
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+    
+    /// Report the warnings found while compiling in `format`: through the log, or as one
+    /// JSON object per line on stderr
+    pub fn with_diagnostics_format(mut self, format: DiagnosticsFormat) -> Self {
+        self.diagnostics_format = format;
+        self
+    }
+    
+    /// Name the file the source comes from in diagnostics
+    pub fn with_source_name(mut self, name: impl Into<String>) -> Self {
+        self.source_name = Some(name.into());
+        self
+    }
+    
+    /// `error` from compiling, as a diagnostic in the source's file
+    pub fn diagnose(&self, error: &FCMCError) -> Diagnostic {
+        self.locate(Diagnostic::from_error(error))
+    }
+    
+    fn locate(&self, diagnostic: Diagnostic) -> Diagnostic {
+        match &self.source_name {
+            Some(name) => diagnostic.in_file(name.as_str()),
+            None => diagnostic,
+        }
+    }
+    
+    /// Report a warning that does not stop the compilation
+    fn warn(&self, diagnostic: Diagnostic) {
+        let diagnostic = self.locate(diagnostic);
+        match self.diagnostics_format {
+            DiagnosticsFormat::Human => tracing::warn!("{}", diagnostic.render_human()),
+            DiagnosticsFormat::Json => eprintln!("{}", diagnostic.to_json()),
+        }
+    }
+    
+    /// A compiler for the program in `files`, read in order and concatenated, that keeps
+    /// its circuit across edits that cannot change it
+    pub fn incremental(&self, files: Vec<PathBuf>) -> IncrementalCompiler {
+        IncrementalCompiler::new(self.clone(), files)
+    }
+    
+    /// A session compiling with these options, sharing parsed rewrite rules, Poseidon
+    /// parameters and compiled circuits between compilations
+    pub fn session(&self) -> Result<CompilerSession, FCMCError> {
+        CompilerSession::new(self.clone())
+    }
+    
+    /// Compile `source` and check the circuit against the interpreter on the unoptimized
+    /// program for every assignment of its inputs; only for programs whose inputs are
+    /// booleans or small unsigned integers
+    pub fn verify_exhaustive(&self, source: &str) -> Result<ExhaustiveReport, FCMCError> {
+        let reference = self.clone().with_optimization_level(0).compile(source)?;
+        let compiled = self.compile(source)?;
+        compiled.in_field(|| exhaustive::verify_exhaustive(&reference, &compiled))
+    }
+    
+    /// Compile `source`; constant folding, range checks and the backend all work in `field()`
     pub fn compile(&self, source: &str) -> Result<CompiledCircuit, FCMCError> {
-        log::info!("Starting compilation with optimization level {}", self.optimization_level);
+        let field = self.field();
+        self.validate()?;
+        let _span = tracing::info_span!(
+            "compile",
+            target = ?self.target_system,
+            level = self.optimization_level,
+            field = %field,
+        )
+        .entered();
+        package::link(&self.packages, || field.enter(|| self.compile_in_field(source, field.clone())))
+    }
+    
+    /// Fail on options that cannot work together, before compiling anything
+    pub fn validate(&self) -> Result<(), FCMCError> {
+        let field = self.field();
+        let capabilities = self.target_system.capabilities();
+        if self.optimization_level > 3 {
+            return Err(FCMCError::OptimizationError(format!(
+                "Optimization level {} does not exist; levels are 0 to 3",
+                self.optimization_level
+            )));
+        }
+        if self.lookups == Some(true) && !capabilities.lookups {
+            return Err(FCMCError::BackendError(format!(
+                "{:?} circuits have no lookup arguments; compile for Plonk or Halo2 to use lookups",
+                self.target_system
+            )));
+        }
+        if let Some(passes) = &self.passes {
+            if self.optimization_level == 0 {
+                return Err(FCMCError::OptimizationError(
+                    "A pass pipeline runs only above optimization level 0".to_string(),
+                ));
+            }
+            optimization::PassManager::builder().spec(&passes.join(",")).build()?;
+            let uses = |pass: &str| {
+                passes
+                    .iter()
+                    .any(|entry| entry.split('*').next().map(str::trim) == Some(pass))
+            };
+            if uses("lookups") && !self.lookups.unwrap_or(capabilities.lookups) {
+                return Err(FCMCError::OptimizationError(format!(
+                    "The lookups pass emits lookups, which {:?} circuits {}",
+                    self.target_system,
+                    if capabilities.lookups { "were told not to use" } else { "do not have" }
+                )));
+            }
+            if uses("customgates") && !capabilities.custom_gates {
+                return Err(FCMCError::OptimizationError(format!(
+                    "The customgates pass needs custom gates, which {:?} circuits do not have",
+                    self.target_system
+                )));
+            }
+        }
+        if self.exhaustive_equivalence && !self.verify_output {
+            return Err(FCMCError::VerificationError(
+                "Exhaustive equivalence checking is part of verification, which is off".to_string(),
+            ));
+        }
+        if self.reproducible && self.time_budget.is_some() {
+            return Err(FCMCError::OptimizationError(
+                "A time budget makes the output depend on how fast the compiler runs; it cannot be reproducible".to_string(),
+            ));
+        }
+        if !self.target_system.supports_field(&field) {
+            return Err(FCMCError::BackendError(format!(
+                "{:?} circuits cannot be defined over the {} field",
+                self.target_system, field
+            )));
+        }
+        Ok(())
+    }
+    
+    /// Size of the circuit `source` compiles to, from the optimized IR without lowering
+    /// it, to compare targets and optimization settings quickly
+    pub fn estimate(&self, source: &str) -> Result<backend::SizeEstimate, FCMCError> {
+        let field = self.field();
+        self.validate()?;
+        package::link(&self.packages, || {
+            field.enter(|| {
+                let prepared = self.prepare(source, &field)?;
+                Ok(backend::estimate_in(&prepared.ir, self.target_system.clone(), &field))
+            })
+        })
+    }
+    
+    fn compile_in_field(
+        &self,
+        source: &str,
+        field: optimization::field::FieldConfig,
+    ) -> Result<CompiledCircuit, FCMCError> {
+        let Prepared {
+            ir,
+            inlining,
+            sanity,
+            optimization: optimization_stats,
+            equivalence,
+            suspicious_constraints,
+            properties,
+            outline,
+            mut report,
+            peak_ir_bytes,
+            original_nodes,
+            mut audit,
+        } = self.prepare(source, &field)?;
+        
+        // 5. Backend compilation
+        let run = self.start_stage(Stage::Lower)?;
+        if self.memory_limit.is_some() {
+            // Fail before building a circuit that is bound to go over the limit
+            let estimate = backend::estimate_in(&ir, self.target_system.clone(), &field);
+            let expected = memory::ir_bytes(&ir) + memory::estimated_circuit_bytes(&estimate);
+            memory::check(expected, self.memory_limit, Stage::Lower.as_str())?;
+        }
+        let mut circuit = backend::compile_to_target_in(&ir, self.target_system.clone(), &field)?;
+        let duplicate_constraints = match &mut circuit {
+            TargetCircuit::R1CS(r1cs) => optimization::deduplicate_rows(r1cs),
+            _ => 0,
+        };
+        tracing::info!("Circuit compiled successfully with {} constraints", circuit.constraint_count());
+        self.finish_stage(run, Some(circuit.constraint_count()), &mut report);
+        report.constraints = circuit.constraint_count();
+        audit::assign_rows(&mut audit, &circuit);
+        let mut memory = MemoryReport::of(&ir, &circuit);
+        memory.peak_ir_bytes = memory.peak_ir_bytes.max(peak_ir_bytes);
+        memory::check(memory.ir_bytes + memory.constraint_bytes, self.memory_limit, Stage::Lower.as_str())?;
+        
+        // 6. Verification if enabled
+        let mut hints = None;
+        if self.verify_output {
+            let run = self.start_stage(Stage::Verify)?;
+            utils::verification::verify_circuit(circuit.backend())?;
+            let hint_report = backend::hint_report(&*r1cs_lowering(&circuit, &ir)?, &ir);
+            if let Some(message) = unconstrained_hints_message(&hint_report) {
+                if self.deny_unconstrained_hints {
+                    return Err(FCMCError::VerificationError(message));
+                }
+                self.warn(Diagnostic::unconstrained_hints(message));
+            }
+            hints = Some(hint_report);
+            tracing::debug!("Circuit verification passed");
+            self.finish_stage(run, None, &mut report);
+        }
+        
+        report.passes = optimization_stats.passes.clone();
+        let signature = CircuitSignature::of(&ir);
+        let stats = CompilationStats {
+            original_nodes,
+            optimized_nodes: ir.node_count(),
+            constraint_count: circuit.constraint_count(),
+            witness_size: circuit.wire_count(),
+            public_inputs: signature.public_inputs.len(),
+            public_outputs: signature.public_outputs.len(),
+            target: TargetMetrics::of(&circuit),
+            duplicate_constraints,
+            inlining,
+            sanity,
+            optimization: optimization_stats,
+            equivalence,
+            suspicious_constraints,
+            properties,
+            hints,
+            outline,
+            report,
+            memory,
+            audit,
+        };
+        Ok(CompiledCircuit {
+            ir,
+            circuit,
+            field,
+            stats,
+        })
+    }
+    
+    /// Fail if the compilation was cancelled, or enter `stage`'s span and report that it
+    /// started
+    fn start_stage(&self, stage: Stage) -> Result<StageRun, FCMCError> {
+        if let Some(token) = &self.cancellation {
+            token.check()?;
+        }
+        if let Some(observer) = &self.progress {
+            observer.stage_started(stage);
+        }
+        let span = tracing::info_span!(
+            "stage",
+            stage = stage.as_str(),
+            nodes = tracing::field::Empty,
+            micros = tracing::field::Empty,
+        );
+        Ok(StageRun {
+            stage,
+            started: Instant::now(),
+            span: span.entered(),
+        })
+    }
+    
+    /// Record how long the stage took and the IR nodes it left, if it works on the IR
+    fn finish_stage(&self, run: StageRun, nodes: Option<usize>, report: &mut CompilationReport) {
+        let elapsed = run.started.elapsed();
+        run.span.record("nodes", nodes);
+        run.span.record("micros", elapsed.as_micros() as u64);
+        if let Some(observer) = &self.progress {
+            observer.stage_finished(run.stage, elapsed);
+        }
+        report.stages.push(StageReport {
+            stage: run.stage,
+            elapsed,
+            nodes,
+        });
+    }
+    
+    /// Measure `ir` after `stage` into `peak`, failing over the memory limit
+    fn account_memory(&self, stage: Stage, ir: &ir::IRGraph, peak: &mut usize) -> Result<(), FCMCError> {
+        let bytes = memory::ir_bytes(ir);
+        *peak = (*peak).max(bytes);
+        memory::check(bytes, self.memory_limit, stage.as_str())
+    }
+    
+    /// Parse, optimize and legalize `source`, everything up to lowering
+    fn prepare(&self, source: &str, field: &optimization::field::FieldConfig) -> Result<Prepared, FCMCError> {
+        tracing::info!("Starting compilation with optimization level {}", self.optimization_level);
+        let mut report = CompilationReport::default();
+        let mut peak_ir_bytes = 0;
+        
+        // 1. Frontend: Parse and semantic analysis, with the property annotations set
+        //    aside for verification
+        let run = self.start_stage(Stage::Parse)?;
+        let (source, properties) = optimization::extract_properties(source)?;
+        let (source, audit) = audit::extract_boundaries(&source)?;
+        let ast = frontend::parse_source(&source)?;
+        let outline = SourceOutline::of(&source);
+        tracing::debug!("AST generated successfully");
+        self.finish_stage(run, None, &mut report);
         
-        // 1. Frontend: Parse and semantic analysis
-        let ast = frontend::parse_source(source)?;
-        log::debug!("AST generated successfully");
+        let run = self.start_stage(Stage::Inline)?;
+        let (ast, inlining) = optimization::inline_calls(&ast, &self.inline_policy)?;
+        tracing::debug!("Inlined {} call(s), kept {}", inlining.inlined(), inlining.kept());
+        self.finish_stage(run, None, &mut report);
         
         // 2. Generate initial IR
+        let run = self.start_stage(Stage::BuildIr)?;
         let mut ir = ir::IRGraph::from_ast(&ast)?;
-        log::debug!("Initial IR generated with {} nodes", ir.node_count());
+        tracing::debug!("Initial IR generated with {} nodes", ir.node_count());
+        
+        // Constraints the inputs cannot affect, checked before optimization folds them away
+        let mut suspicious_constraints = Vec::new();
+        if self.verify_output {
+            suspicious_constraints =
+                optimization::find_suspicious_constraints(&ir, optimization::DEFAULT_COUNTEREXAMPLE_SAMPLES);
+            for suspicious in &suspicious_constraints {
+                self.warn(Diagnostic::from_suspicious(suspicious));
+            }
+        }
+        let original_nodes = ir.node_count();
+        self.finish_stage(run, Some(original_nodes), &mut report);
+        self.account_memory(Stage::BuildIr, &ir, &mut peak_ir_bytes)?;
         
         // 3. Apply optimizations
+        let mut optimization_stats = optimization::OptimizationStats::default();
+        let mut equivalence = None;
         if self.optimization_level > 0 {
+            let run = self.start_stage(Stage::Optimize)?;
+            let original = if self.verify_output { Some(ir.clone()) } else { None };
             let mut optimizer = optimization::OptimizationFramework::new();
+            if let Some(budget) = self.time_budget {
+                optimizer = optimizer.with_time_budget(budget);
+            }
+            if let Some(ratio) = self.max_node_growth {
+                optimizer = optimizer.with_max_node_growth(ratio);
+            }
             optimizer.set_level(self.optimization_level);
-            ir = optimizer.optimize(ir)?;
-            log::debug!("Optimized IR with {} nodes", ir.node_count());
+            if let Some(limit) = self.memory_limit {
+                optimizer.set_memory_limit(limit);
+            }
+            if let Some(passes) = &self.passes {
+                optimizer.set_pipeline_spec(&passes.join(","))?;
+            }
+            if let Some(lookups) = self.lookups {
+                optimizer.set_lookup_support(lookups);
+            }
+            if self.reproducible {
+                optimizer.set_saturation_timeout(Duration::MAX);
+            }
+            if self.verify_output {
+                optimizer.set_soundness_samples(self.soundness_samples);
+            }
+            for pass in &self.dump_ir_after {
+                optimizer.dump_ir_after(pass);
+            }
+            for path in &self.rewrite_rules {
+                optimizer.load_rewrite_rules(path)?;
+            }
+            for rules in &self.rule_sets {
+                optimizer.add_rewrite_rules(rules.clone());
+            }
+            optimizer.set_explain(self.explain);
+            if let Some(observer) = &self.progress {
+                optimizer.set_progress(observer.clone());
+            }
+            if let Some(token) = &self.cancellation {
+                optimizer.set_cancellation(token.clone());
+            }
+            ir = optimizer.optimize(ir, self.target_system.clone())?;
+            tracing::debug!("Optimized IR with {} nodes", ir.node_count());
+            
+            if let Some(original) = original {
+                let report = optimization::verify_equivalence(
+                    &original,
+                    &ir,
+                    self.soundness_samples,
+                    self.exhaustive_equivalence,
+                )?;
+                tracing::debug!(
+                    "Optimized IR agrees with the original on {} assignment(s){}",
+                    report.assignments,
+                    if report.exhaustive { ", all of them" } else { "" }
+                );
+                equivalence = Some(report);
+            }
+            
+            for snapshot in optimizer.snapshots() {
+                tracing::debug!("IR after '{}' (iteration {}):\n{:#?}", snapshot.pass, snapshot.iteration, snapshot.graph);
+            }
+            optimization_stats = optimizer.stats().clone();
+            for line in optimization_stats.explain_report() {
+                tracing::info!("{}", line);
+            }
+            
+            if let Some(path) = &self.pass_stats_path {
+                std::fs::write(path, optimization_stats.to_json()?).map_err(|e| {
+                    FCMCError::OptimizationError(format!("Failed to write pass stats to {}: {}", path.display(), e))
+                })?;
+            }
+            self.finish_stage(run, Some(ir.node_count()), &mut report);
+            self.account_memory(Stage::Optimize, &ir, &mut peak_ir_bytes)?;
         }
         
-        // 4. Backend compilation
-        let circuit = backend::compile_to_target(&ir, self.target_system)?;
-        log::info!("Circuit compiled successfully with {} constraints", circuit.constraint_count());
+        // 4. Lay out the public values as requested, add any missing sanity constraints,
+        //    rewrite what the target cannot express natively, such as function tables the
+        //    optimizer left in place or hash gadgets without a native call, then remove
+        //    constraints the rewrite left duplicated
+        let run = self.start_stage(Stage::Legalize)?;
+        self.public_layout.apply(&mut ir, field)?;
+        let mut sanity = backend::SanityReport::default();
+        if self.sanity_checks {
+            sanity = backend::sanity::inject_sanity_checks(&mut ir, &self.target_system);
+            for line in sanity.summary() {
+                tracing::info!("{}", line);
+            }
+        }
+        backend::legalize(&mut ir, &self.target_system.capabilities())?;
+        optimization::ConstraintDeduplication::default().run(&mut ir)?;
         
-        // 5. Verification if enabled
-        if self.verify_output {
-            utils::verification::verify_circuit(&circuit)?;
-            log::debug!("Circuit verification passed");
+        let mut property_report = None;
+        if self.verify_output && !properties.is_empty() {
+            let report = optimization::check_properties(&ir, &properties, optimization::DEFAULT_PROPERTY_SAMPLES)?;
+            tracing::debug!("{} property annotation(s) held on {} accepted input(s)", report.properties, report.checked);
+            property_report = Some(report);
         }
+        self.finish_stage(run, Some(ir.node_count()), &mut report);
+        self.account_memory(Stage::Legalize, &ir, &mut peak_ir_bytes)?;
         
-        Ok(CompiledCircuit {
+        Ok(Prepared {
             ir,
-            circuit,
-            stats: CompilationStats {
-                original_nodes: 0, // Would be tracked
-                optimized_nodes: ir.node_count(),
-                constraint_count: circuit.constraint_count(),
-            },
+            inlining,
+            sanity,
+            optimization: optimization_stats,
+            equivalence,
+            suspicious_constraints,
+            properties: property_report,
+            outline,
+            report,
+            peak_ir_bytes,
+            original_nodes,
+            audit,
         })
     }
 }
 
+/// The R1CS `circuit` is, or was built from with its target's lowering options
+fn r1cs_lowering<'a>(
+    circuit: &'a backend::TargetCircuit,
+    ir: &ir::IRGraph,
+) -> Result<Cow<'a, backend::R1CSCircuit>, FCMCError> {
+    match circuit.as_r1cs() {
+        Some(r1cs) => Ok(Cow::Borrowed(r1cs)),
+        None => {
+            let options = circuit.target().capabilities().lowering_options();
+            Ok(Cow::Owned(backend::R1CSCircuit::compile_with(ir, options)?))
+        }
+    }
+}
+
+/// What to warn about hints no constraint reads, if there are any
+fn unconstrained_hints_message(report: &backend::HintReport) -> Option<String> {
+    let unconstrained: Vec<String> = report
+        .unconstrained()
+        .map(|hint| format!("{} wire {} ({})", hint.kind.as_str(), hint.wire, hint.name))
+        .collect();
+    if unconstrained.is_empty() {
+        None
+    } else {
+        Some(format!("Hints no constraint reads: {}", unconstrained.join(", ")))
+    }
+}
+
+/// The IR ready for lowering, with what the steps before lowering reported
+struct Prepared {
+    ir: ir::IRGraph,
+    inlining: optimization::InlineReport,
+    sanity: backend::SanityReport,
+    optimization: optimization::OptimizationStats,
+    equivalence: Option<optimization::EquivalenceReport>,
+    suspicious_constraints: Vec<optimization::SuspiciousConstraint>,
+    properties: Option<optimization::PropertyReport>,
+    outline: SourceOutline,
+    /// The stages so far
+    report: CompilationReport,
+    /// Largest IR measured so far
+    peak_ir_bytes: usize,
+    /// IR nodes before optimization
+    original_nodes: usize,
+    /// The functions marked `#[audit_boundary]`, without rows until lowered
+    audit: Vec<AuditBoundary>,
+}
+
+/// A stage in progress, inside its span
+struct StageRun {
+    stage: Stage,
+    started: Instant,
+    span: tracing::span::EnteredSpan,
+}
+
 pub struct CompiledCircuit {
     pub ir: ir::IRGraph,
-    pub circuit: Box<dyn backend::CircuitBackend>,
+    pub circuit: backend::TargetCircuit,
+    pub field: optimization::field::FieldConfig,
     pub stats: CompilationStats,
 }
 
 pub struct CompilationStats {
+    /// IR nodes built from the source, before optimization
     pub original_nodes: usize,
+    /// IR nodes lowered to the circuit, after optimization and legalization
     pub optimized_nodes: usize,
     pub constraint_count: usize,
+    /// Witness values the prover supplies, including the constant one
+    pub witness_size: usize,
+    pub public_inputs: usize,
+    pub public_outputs: usize,
+    /// Size of the circuit in its proof system's terms
+    pub target: TargetMetrics,
+    /// Lowered R1CS rows removed as duplicates or scalar multiples of another row; 0 for
+    /// other targets
+    pub duplicate_constraints: usize,
+    /// Which calls were inlined and why
+    pub inlining: optimization::InlineReport,
+    /// Constraints added by `with_sanity_checks`
+    pub sanity: backend::SanityReport,
+    pub optimization: optimization::OptimizationStats,
+    /// How the optimized IR was checked against the original; `None` when output
+    /// verification is off or nothing was optimized
+    pub equivalence: Option<optimization::EquivalenceReport>,
+    /// Constraints of the unoptimized IR that fail or hold for every input, each with an
+    /// assignment showing it; empty when output verification is off
+    pub suspicious_constraints: Vec<optimization::SuspiciousConstraint>,
+    /// How the `#[require]` and `#[ensure]` annotations on `main` were checked; `None`
+    /// without annotations or with output verification off
+    pub properties: Option<optimization::PropertyReport>,
+    /// Values the prover computes outside the constraints and what ties each back;
+    /// `None` with output verification off
+    pub hints: Option<backend::HintReport>,
+    /// Lines of each function and loop of the source, which `profile` attributes rows by
+    pub outline: SourceOutline,
+    /// Time and IR size of each stage and pass; empty for circuits read by `load`
+    pub report: CompilationReport,
+    /// Approximate memory the IR and circuit took; for circuits read by `load`, what they
+    /// take as loaded
+    pub memory: MemoryReport,
+    /// The functions marked `#[audit_boundary]`, with their source hashes and rows
+    pub audit: Vec<AuditBoundary>,
 }
 
-impl CompiledCircuit {
+impl CompilationStats {
+    /// Share of the original IR nodes optimization removed, in percent; negative when
+    /// legalization added more than optimization removed
     pub fn optimization_ratio(&self) -> f64 {
-        if self.stats.original_nodes > 0 {
-            let reduction = self.stats.original_nodes as f64 - self.stats.optimized_nodes as f64;
-            reduction / self.stats.original_nodes as f64 * 100.0
+        if self.original_nodes > 0 {
+            let reduction = self.original_nodes as f64 - self.optimized_nodes as f64;
+            reduction / self.original_nodes as f64 * 100.0
         } else {
             0.0
         }
     }
+    
+    /// How long each stage took, in the order they ran; empty for circuits read by `load`
+    pub fn stage_durations(&self) -> Vec<(Stage, Duration)> {
+        self.report.stages.iter().map(|stage| (stage.stage, stage.elapsed)).collect()
+    }
+    
+    /// The counts, timings and memory, with the verification findings summarized by
+    /// number; the same as serializing the stats
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "original_nodes": self.original_nodes,
+            "optimized_nodes": self.optimized_nodes,
+            "optimization_ratio": self.optimization_ratio(),
+            "constraints": self.constraint_count,
+            "witness_size": self.witness_size,
+            "public_inputs": self.public_inputs,
+            "public_outputs": self.public_outputs,
+            "target": self.target,
+            "duplicate_constraints": self.duplicate_constraints,
+            "inlining": self.inlining,
+            "sanity_checks": self.sanity.injected.len(),
+            "optimization": {
+                "iterations": self.optimization.iterations,
+                "rewrites": self.optimization.total_rewrites(),
+                "total_micros": self.optimization.total_micros,
+                "stopped_early": self.optimization.stopped_early,
+            },
+            "equivalence": self.equivalence.as_ref().map(|report| serde_json::json!({
+                "assignments": report.assignments,
+                "skipped": report.skipped,
+                "exhaustive": report.exhaustive,
+            })),
+            "suspicious_constraints": self.suspicious_constraints.len(),
+            "report": self.report,
+            "memory": self.memory,
+            "audit": self.audit,
+        })
+    }
+}
+
+impl serde::Serialize for CompilationStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.to_json(), serializer)
+    }
+}
+
+impl CompiledCircuit {
+    /// Run `f` with field arithmetic in the circuit's field, as evaluating its IR needs
+    pub fn in_field<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.field.enter(f)
+    }
+    
+    /// Compute every wire from the named `inputs` and check every constraint, reporting
+    /// the first that fails with its source position and the values it read
+    pub fn compute_witness(&self, inputs: &InputMap) -> Result<Witness, FCMCError> {
+        witness::compute_witness(self, inputs)
+    }
+    
+    /// Evaluate every constraint, range check and lookup on `inputs` in source order,
+    /// recording whether each holds and the values it read
+    pub fn trace(&self, inputs: &InputMap) -> Result<ConstraintTrace, FCMCError> {
+        trace::trace(self, inputs)
+    }
+    
+    /// Compute the witness for `inputs` and write it to `path` in snarkjs' `.wtns`
+    /// format, refusing a witness that does not satisfy every constraint
+    pub fn write_wtns(&self, inputs: &HashMap<String, BigUint>, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+        let witness = self.compute_witness(inputs)?;
+        backend::export::circom::write_wtns(&self.circuit, &witness.values, path)
+    }
+    
+    /// Check that the snarkjs Groth16 proving key at `path` was set up for this circuit
+    pub fn check_zkey(&self, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+        backend::export::groth16::check_zkey(&self.circuit, path)
+    }
+    
+    /// SMT-LIB encoding of the rank-1 constraints the circuit lowers to, for proving
+    /// properties of it with Z3 or cvc5
+    pub fn export_smt(&self, sorts: backend::export::smt::InputSorts) -> Result<String, FCMCError> {
+        self.in_field(|| {
+            let r1cs = r1cs_lowering(&self.circuit, &self.ir)?;
+            backend::export::smt::smt_source(&r1cs, &self.ir, sorts)
+        })
+    }
+    
+    /// Names of the circuit's outputs, public inputs and private inputs
+    pub fn signature(&self) -> CircuitSignature {
+        CircuitSignature::of(&self.ir)
+    }
+    
+    /// Split the circuit into `parts` circuits, each proved on its own, tied together by
+    /// the shared values they expose
+    pub fn partition(&self, parts: usize) -> Result<backend::PartitionedCircuit, FCMCError> {
+        backend::PartitionedCircuit::split(&self.ir, parts, self.circuit.target(), &self.field)
+    }
+    
+    /// Find witness wires the constraints leave free once the inputs are fixed, testing
+    /// at up to `samples` satisfying witnesses of random inputs
+    pub fn underconstrained(&self, samples: usize) -> Result<backend::UnderconstraintReport, FCMCError> {
+        let r1cs = self.in_field(|| r1cs_lowering(&self.circuit, &self.ir))?;
+        backend::find_underconstrained(&r1cs, &self.ir, samples)
+    }
+    
+    /// Every quotient, inverse, bit, byte and zero test the prover computes outside the
+    /// constraints, with the rows that tie each back
+    pub fn hints(&self) -> Result<backend::HintReport, FCMCError> {
+        let r1cs = self.in_field(|| r1cs_lowering(&self.circuit, &self.ir))?;
+        Ok(backend::hint_report(&r1cs, &self.ir))
+    }
+    
+    /// Witness size, constant and duplicate wires found at up to `samples` satisfying
+    /// witnesses, and the source variables the wires come from
+    pub fn witness_stats(&self, samples: usize) -> Result<backend::WitnessStats, FCMCError> {
+        let r1cs = self.in_field(|| r1cs_lowering(&self.circuit, &self.ir))?;
+        backend::witness_stats(&r1cs, &self.ir, samples)
+    }
+    
+    /// Rows of the circuit attributed to the functions, loops, source lines and gadgets
+    /// they were lowered from
+    pub fn profile(&self) -> ConstraintProfile {
+        profile::profile(self)
+    }
+    
+    /// What changed from `self` to `newer`: constraints per function and the signature
+    pub fn diff(&self, newer: &CompiledCircuit) -> CircuitDiff {
+        CircuitDiff::between(self, newer)
+    }
+    
+    /// Structural lints for comparisons, selectors, public inputs and outputs that are
+    /// often underconstrained, each with a severity and an explanation
+    pub fn lint(&self) -> Result<backend::LintReport, FCMCError> {
+        self.in_field(|| {
+            let r1cs = r1cs_lowering(&self.circuit, &self.ir)?;
+            Ok(backend::lint(&r1cs, &self.ir))
+        })
+    }
+    
+    /// Write the circuit to `path` in FCMC's versioned binary format, for `load`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+        let path = path.as_ref();
+        std::fs::write(path, artifact::encode(self))
+            .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+    }
+    
+    /// Read a circuit written by `save`, without compiling it again
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FCMCError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| FCMCError::BackendError(format!("Failed to read {}: {}", path.display(), e)))?;
+        artifact::decode(&bytes)
+    }
+    
+    pub fn optimization_ratio(&self) -> f64 {
+        self.stats.optimization_ratio()
+    }
+    
+    /// The warnings compiling the circuit reported, without the source file; empty for
+    /// circuits read by `load`, which keep no verification findings
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings: Vec<Diagnostic> =
+            self.stats.suspicious_constraints.iter().map(Diagnostic::from_suspicious).collect();
+        if let Some(message) = self.stats.hints.as_ref().and_then(unconstrained_hints_message) {
+            warnings.push(Diagnostic::unconstrained_hints(message));
+        }
+        warnings
+    }
+    
+    /// The boundary of the gadget audited as `name`, if its source hashes to
+    /// `content_hash`, the hash of the code the auditor reviewed
+    pub fn check_audited(&self, name: &str, content_hash: &str) -> Result<&AuditBoundary, FCMCError> {
+        let boundary = self.stats.audit.iter().find(|boundary| boundary.name == name).ok_or_else(|| {
+            FCMCError::VerificationError(format!("The circuit has no audit boundary named '{}'", name))
+        })?;
+        if !boundary.content_hash.eq_ignore_ascii_case(content_hash) {
+            return Err(FCMCError::VerificationError(format!(
+                "Gadget '{}' hashes to {}, not the audited {}",
+                name, boundary.content_hash, content_hash
+            )));
+        }
+        Ok(boundary)
+    }
+    
+    /// Sizes, optimization savings, the costliest functions, injected safety constraints
+    /// and warnings, laid out for a terminal, a dashboard or a pull request
+    pub fn report(&self, format: ReportFormat) -> String {
+        CircuitSummary::of(self).render(format)
+    }
 }
@@ -0,0 +1,176 @@
+//! Approximate heap accounting for a compilation, and the limit that stops one before it
+//! takes the machine down.
+
+use crate::backend::lowering::{LinearCombination, WireSource};
+use crate::backend::{CircuitBackend, SizeEstimate, TargetCircuit, TargetSystem};
+use crate::ir::{EdgeType, IRGraph, IRNode, IRNodeType};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+use std::mem::size_of;
+
+/// Bytes assumed per row of targets whose constraint storage is not measured in detail:
+/// a gate's selectors, its wiring and a coefficient or two
+pub const APPROXIMATE_ROW_BYTES: usize = 192;
+
+/// Bookkeeping of a hash map or B-tree entry beyond its key and value
+const ENTRY_OVERHEAD: usize = 16;
+
+/// What a compilation held, in approximate bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// Largest IR measured, at the end of a stage or, under a limit, after a pass
+    pub peak_ir_bytes: usize,
+    /// The optimized IR the circuit keeps
+    pub ir_bytes: usize,
+    pub constraint_bytes: usize,
+    /// Nonzero coefficients of the A, B and C matrices; `None` for targets other than R1CS
+    pub nonzeros: Option<usize>,
+}
+
+impl MemoryReport {
+    /// The IR and circuit as they are, without a history to take a peak from
+    pub fn of(ir: &IRGraph, circuit: &TargetCircuit) -> Self {
+        let ir_bytes = ir_bytes(ir);
+        Self {
+            peak_ir_bytes: ir_bytes,
+            ir_bytes,
+            constraint_bytes: circuit_bytes(circuit),
+            nonzeros: circuit.as_r1cs().map(|r1cs| {
+                r1cs.constraints
+                    .iter()
+                    .map(|constraint| constraint.a.len() + constraint.b.len() + constraint.c.len())
+                    .sum()
+            }),
+        }
+    }
+
+    /// Most held at once: the largest IR, or the final IR with the circuit
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_ir_bytes.max(self.ir_bytes + self.constraint_bytes)
+    }
+
+    pub fn summary(&self) -> String {
+        let nonzeros = self
+            .nonzeros
+            .map(|nonzeros| format!(", {} nonzeros", nonzeros))
+            .unwrap_or_default();
+        format!(
+            "peak {}, IR {}, constraints {}{}",
+            mebibytes(self.peak_bytes()),
+            mebibytes(self.ir_bytes),
+            mebibytes(self.constraint_bytes),
+            nonzeros
+        )
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "peak_bytes": self.peak_bytes(),
+            "peak_ir_bytes": self.peak_ir_bytes,
+            "ir_bytes": self.ir_bytes,
+            "constraint_bytes": self.constraint_bytes,
+            "nonzeros": self.nonzeros,
+        })
+    }
+}
+
+impl Serialize for MemoryReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+/// Approximate heap bytes `graph` holds
+pub fn ir_bytes(graph: &IRGraph) -> usize {
+    let nodes: usize = graph.nodes().iter().map(node_bytes).sum();
+    let edges = graph.edges().len() * size_of::<(usize, usize, EdgeType)>();
+    let endpoints = (graph.inputs().len() + graph.outputs().len()) * size_of::<usize>();
+    let removed =
+        (0..graph.nodes().len()).filter(|&id| graph.is_removed(id)).count() * (size_of::<usize>() + ENTRY_OVERHEAD);
+    let tables: usize = graph
+        .tables()
+        .iter()
+        .map(|table| {
+            table.name.len() + table.input_bits.len() * 4 + table.outputs.iter().map(String::len).sum::<usize>()
+        })
+        .sum();
+    let loops: usize = graph
+        .loops()
+        .iter()
+        .map(|region| region.induction_var.len() + region.hoisted.len() * size_of::<usize>())
+        .sum();
+    nodes + edges + endpoints + removed + tables + loops
+}
+
+fn node_bytes(node: &IRNode) -> usize {
+    let payload = match &node.node_type {
+        IRNodeType::Constant(text)
+        | IRNodeType::Input(text)
+        | IRNodeType::PrivateInput(text)
+        | IRNodeType::Output(text) => text.len(),
+        _ => 0,
+    };
+    let attributes: usize = node
+        .attributes
+        .iter()
+        .map(|(key, value)| key.len() + value.len() + 2 * size_of::<String>() + ENTRY_OVERHEAD)
+        .sum();
+    size_of::<IRNode>() + payload + node.label.as_ref().map_or(0, String::len) + attributes
+}
+
+/// Approximate heap bytes `circuit`'s constraints and witness layout hold
+pub fn circuit_bytes(circuit: &TargetCircuit) -> usize {
+    let Some(r1cs) = circuit.as_r1cs() else {
+        return circuit.constraint_count() * APPROXIMATE_ROW_BYTES + circuit.wire_count() * size_of::<WireSource>();
+    };
+    let constraints: usize = r1cs
+        .constraints
+        .iter()
+        .map(|constraint| lc_bytes(&constraint.a) + lc_bytes(&constraint.b) + lc_bytes(&constraint.c))
+        .sum();
+    let wires = r1cs.wires.len() * size_of::<WireSource>();
+    let names: usize = r1cs
+        .wire_names
+        .iter()
+        .map(|name| name.len() + size_of::<String>())
+        .sum();
+    constraints + wires + names
+}
+
+/// Heap the circuit `estimate` describes will hold once lowered, counting each R1CS
+/// coefficient without its digits so the figure stays below `circuit_bytes`
+pub fn estimated_circuit_bytes(estimate: &SizeEstimate) -> usize {
+    let constraints = match estimate.target {
+        TargetSystem::R1CS => estimate.nonzeros * (size_of::<usize>() + size_of::<BigUint>() + ENTRY_OVERHEAD),
+        _ => estimate.rows * APPROXIMATE_ROW_BYTES,
+    };
+    constraints + estimate.variables * size_of::<WireSource>()
+}
+
+fn lc_bytes(lc: &LinearCombination) -> usize {
+    lc.values()
+        .map(|coefficient| size_of::<usize>() + biguint_bytes(coefficient) + ENTRY_OVERHEAD)
+        .sum()
+}
+
+fn biguint_bytes(value: &BigUint) -> usize {
+    size_of::<BigUint>() + value.bits().div_ceil(64) as usize * 8
+}
+
+/// Fail with `FCMCError::MemoryLimit` if `used` is over `limit`
+pub fn check(used: usize, limit: Option<usize>, during: &str) -> Result<(), FCMCError> {
+    match limit {
+        Some(limit) if used > limit => Err(FCMCError::MemoryLimit {
+            during: during.to_string(),
+            used,
+            limit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn mebibytes(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
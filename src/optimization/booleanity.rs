@@ -0,0 +1,278 @@
+use crate::ir::{ConstraintType, IRGraph, IRNodeType};
+use crate::optimization::interpreter::extracted_bit;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use std::collections::HashMap;
+
+/// Node attribute written by `BooleanityAnalysis::annotate`
+pub const BOOLEAN_ATTRIBUTE: &str = "boolean";
+
+/// Why a wire is known to carry only 0 or 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanSource {
+    /// Constant 0 or 1
+    Constant,
+    /// Output of a comparison, boolean by construction in every backend
+    Comparison,
+    /// Logical combination of wires that are already boolean
+    Derived,
+    /// Single bit extracted from a wider value, constrained boolean in every backend
+    Bit,
+    /// Enforced by an explicit b*(b-1)=0 constraint node
+    Constrained(usize),
+}
+
+impl BooleanSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BooleanSource::Constant => "constant",
+            BooleanSource::Comparison => "comparison",
+            BooleanSource::Derived => "derived",
+            BooleanSource::Bit => "bit",
+            BooleanSource::Constrained(_) => "constrained",
+        }
+    }
+}
+
+/// Dataflow analysis tracking which wires are provably boolean
+#[derive(Debug, Clone, Default)]
+pub struct BooleanityAnalysis {
+    facts: HashMap<usize, BooleanSource>,
+}
+
+impl BooleanityAnalysis {
+    pub fn run(graph: &IRGraph) -> Self {
+        // First booleanity constraint seen for each wire
+        let mut constrained = HashMap::new();
+        for constraint_id in graph.get_constraint_nodes() {
+            if graph.is_removed(constraint_id) {
+                continue;
+            }
+            if let Some(wire) = booleanity_target(graph, constraint_id) {
+                constrained.entry(wire).or_insert(constraint_id);
+            }
+        }
+
+        let mut facts = HashMap::new();
+        for node_id in graph.topological_sort() {
+            let node = match graph.get_node(node_id) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            let operands = graph.get_predecessors(node_id);
+            let all_boolean = !operands.is_empty()
+                && operands.iter().all(|operand| facts.contains_key(operand));
+
+            // Structural facts win over constraints, so constraints on such wires become redundant
+            let structural = match &node.node_type {
+                IRNodeType::Constant(value) if is_boolean_constant(value) => Some(BooleanSource::Constant),
+                IRNodeType::Eq
+                | IRNodeType::Ne
+                | IRNodeType::Lt
+                | IRNodeType::Le
+                | IRNodeType::Gt
+                | IRNodeType::Ge => Some(BooleanSource::Comparison),
+                IRNodeType::And
+                | IRNodeType::Or
+                | IRNodeType::Xor
+                | IRNodeType::Not
+                | IRNodeType::Mul
+                | IRNodeType::Select
+                | IRNodeType::Phi if all_boolean => Some(BooleanSource::Derived),
+                IRNodeType::BitDecomposition if extracted_bit(node).is_some() => Some(BooleanSource::Bit),
+                _ => None,
+            };
+
+            let source = structural.or_else(|| {
+                constrained
+                    .get(&node_id)
+                    .map(|&constraint_id| BooleanSource::Constrained(constraint_id))
+            });
+
+            if let Some(source) = source {
+                facts.insert(node_id, source);
+            }
+        }
+
+        Self { facts }
+    }
+
+    pub fn is_boolean(&self, node_id: usize) -> bool {
+        self.facts.contains_key(&node_id)
+    }
+
+    pub fn source(&self, node_id: usize) -> Option<BooleanSource> {
+        self.facts.get(&node_id).copied()
+    }
+
+    pub fn boolean_wires(&self) -> impl Iterator<Item = usize> + '_ {
+        self.facts.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.facts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+    }
+
+    /// Record the analysis results as node attributes so backends can skip re-deriving them
+    pub fn annotate(&self, graph: &mut IRGraph) {
+        for (&node_id, source) in &self.facts {
+            if let Some(node) = graph.get_node_mut(node_id) {
+                node.attributes
+                    .insert(BOOLEAN_ATTRIBUTE.to_string(), source.as_str().to_string());
+            }
+        }
+    }
+}
+
+/// Remove booleanity constraints that are implied by the analysis.
+///
+/// A wire that is boolean by construction needs no constraint at all; a wire that
+/// is only boolean because of a constraint keeps exactly one of them.
+pub fn eliminate_redundant_booleanity(graph: &mut IRGraph) -> Result<usize, FCMCError> {
+    let analysis = BooleanityAnalysis::run(graph);
+    Ok(remove_redundant(graph, &analysis))
+}
+
+fn remove_redundant(graph: &mut IRGraph, analysis: &BooleanityAnalysis) -> usize {
+    let redundant: Vec<usize> = graph
+        .get_constraint_nodes()
+        .into_iter()
+        .filter(|&constraint_id| !graph.is_removed(constraint_id))
+        .filter(|&constraint_id| match booleanity_target(graph, constraint_id) {
+            Some(wire) => analysis.source(wire) != Some(BooleanSource::Constrained(constraint_id)),
+            None => false,
+        })
+        .collect();
+
+    for &constraint_id in &redundant {
+        graph.remove_node(constraint_id);
+    }
+
+    redundant.len()
+}
+
+/// Pass wrapper around `eliminate_redundant_booleanity`
+#[derive(Debug, Default)]
+pub struct RedundantBooleanityElimination {
+    last_rewrites: usize,
+}
+
+impl OptimizationPass for RedundantBooleanityElimination {
+    fn name(&self) -> &str {
+        "booleanity"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let analysis = analyses.booleanity(graph);
+        self.last_rewrites = remove_redundant(graph, analysis);
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+/// The wire constrained by a booleanity constraint node, if `node_id` is one
+pub fn booleanity_target(graph: &IRGraph, node_id: usize) -> Option<usize> {
+    let node = graph.get_node(node_id)?;
+    let is_booleanity = match &node.node_type {
+        IRNodeType::Constraint(constraint) => is_booleanity_constraint(constraint),
+        _ => false,
+    };
+    if !is_booleanity {
+        return None;
+    }
+
+    match graph.get_predecessors(node_id).as_slice() {
+        [wire] => Some(*wire),
+        _ => None,
+    }
+}
+
+fn is_booleanity_constraint(constraint: &ConstraintType) -> bool {
+    match constraint {
+        ConstraintType::Range { bits } => *bits == 1,
+        // c0 + c1*b + c2*b^2 = 0 with b^2 - b or b - b^2
+        ConstraintType::Polynomial { coefficients } => {
+            let coefficients: Vec<&str> = coefficients.iter().map(|c| c.trim()).collect();
+            matches!(coefficients.as_slice(), ["0", "-1", "1"] | ["0", "1", "-1"])
+        }
+        _ => false,
+    }
+}
+
+fn is_boolean_constant(value: &str) -> bool {
+    matches!(value.trim(), "0" | "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::EdgeType;
+    use crate::language::ast::Type;
+    use crate::optimization::interpreter::assert_equivalent;
+    use num_bigint::BigUint;
+
+    /// `out = (x == 3) * w`, with a booleanity constraint on the comparison and two on `w`
+    fn constrained_product() -> IRGraph {
+        let mut graph = IRGraph::new();
+        let x = graph.add_node(IRNodeType::Input("x".to_string()), Type::Field, None);
+        let w = graph.add_node(IRNodeType::PrivateInput("w".to_string()), Type::Field, None);
+        let three = graph.add_node(IRNodeType::Constant("3".to_string()), Type::Field, None);
+        let eq = graph.add_node(IRNodeType::Eq, Type::Bool, None);
+        let product = graph.add_node(IRNodeType::Mul, Type::Field, None);
+        let out = graph.add_node(IRNodeType::Output("out".to_string()), Type::Field, None);
+        for (from, to) in [(x, eq), (three, eq), (eq, product), (w, product), (product, out)] {
+            graph.add_edge(from, to, EdgeType::DataFlow);
+        }
+        let boolean = ConstraintType::Polynomial {
+            coefficients: vec!["0".to_string(), "-1".to_string(), "1".to_string()],
+        };
+        for (wire, constraint) in [
+            (eq, boolean.clone()),
+            (w, boolean),
+            (w, ConstraintType::Range { bits: 1 }),
+        ] {
+            let node = graph.add_node(IRNodeType::Constraint(constraint), Type::Bool, None);
+            graph.add_edge(wire, node, EdgeType::Constraint);
+        }
+        graph
+    }
+
+    fn assignment(x: u64, w: u64) -> HashMap<String, BigUint> {
+        HashMap::from([("x".to_string(), BigUint::from(x)), ("w".to_string(), BigUint::from(w))])
+    }
+
+    #[test]
+    fn tracks_structural_and_constrained_wires() {
+        let graph = constrained_product();
+        let analysis = BooleanityAnalysis::run(&graph);
+        assert_eq!(analysis.source(3), Some(BooleanSource::Comparison));
+        assert_eq!(analysis.source(1), Some(BooleanSource::Constrained(7)));
+        assert_eq!(analysis.source(4), Some(BooleanSource::Derived));
+        assert!(!analysis.is_boolean(0));
+    }
+
+    #[test]
+    fn keeps_exactly_one_constraint_per_unproven_wire() {
+        let before = constrained_product();
+        let mut after = before.clone();
+        assert_eq!(eliminate_redundant_booleanity(&mut after).unwrap(), 2);
+        let remaining: Vec<usize> = after
+            .get_constraint_nodes()
+            .into_iter()
+            .filter(|&id| !after.is_removed(id))
+            .collect();
+        assert_eq!(remaining, [7]);
+
+        // A non-boolean `w` must still be rejected
+        let inputs = [assignment(3, 1), assignment(4, 1), assignment(3, 0), assignment(3, 2)];
+        assert_equivalent(&before, &after, &inputs);
+    }
+}
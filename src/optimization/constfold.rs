@@ -0,0 +1,79 @@
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::{constant_value, field};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Evaluates arithmetic over constant operands at compile time
+#[derive(Debug, Default)]
+pub struct ConstantFolding {
+    last_rewrites: usize,
+}
+
+impl ConstantFolding {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let mut folded = 0;
+
+        for node_id in graph.topological_sort() {
+            let (node_type, data_type) = match graph.get_node(node_id) {
+                Some(node) => (node.node_type.clone(), node.data_type.clone()),
+                None => continue,
+            };
+
+            let values: Option<Vec<BigUint>> = graph
+                .get_predecessors(node_id)
+                .into_iter()
+                .map(|operand| constant_value(graph, operand))
+                .collect();
+            let values = match values {
+                Some(values) if !values.is_empty() => values,
+                _ => continue,
+            };
+
+            let result = match (&node_type, values.as_slice()) {
+                (IRNodeType::Add, [a, b]) => Some(field::add(a, b)),
+                (IRNodeType::Sub, [a, b]) => Some(field::sub(a, b)),
+                (IRNodeType::Mul, [a, b]) => Some(field::mul(a, b)),
+                (IRNodeType::Neg, [a]) => Some(field::neg(a)),
+                // Division by zero is left for the backend to report
+                (IRNodeType::Div, [a, b]) => field::inverse(b).map(|inv| field::mul(a, &inv)),
+                (IRNodeType::Eq, [a, b]) => Some(bool_value(a == b)),
+                (IRNodeType::Ne, [a, b]) => Some(bool_value(a != b)),
+                _ => None,
+            };
+
+            if let Some(value) = result {
+                let constant = graph.add_node(IRNodeType::Constant(value.to_string()), data_type, None);
+                graph.replace_uses(node_id, constant);
+                graph.remove_node(node_id);
+                folded += 1;
+            }
+        }
+
+        Ok(folded)
+    }
+}
+
+impl OptimizationPass for ConstantFolding {
+    fn name(&self) -> &str {
+        "constfold"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = ConstantFolding::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+fn bool_value(value: bool) -> BigUint {
+    if value {
+        BigUint::one()
+    } else {
+        BigUint::zero()
+    }
+}
@@ -0,0 +1,66 @@
+use crate::backend::{registry, TargetSystem};
+use crate::ir::IRGraph;
+use crate::optimization::sparsity;
+
+/// Relative cost of IR operations once lowered to a given target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    /// Product of two wires
+    pub mul: u32,
+    /// Product of a wire and a constant
+    pub const_mul: u32,
+    pub add: u32,
+    pub div: u32,
+}
+
+impl CostModel {
+    pub fn for_target(target: TargetSystem) -> Self {
+        match target {
+            // Linear combinations are free in R1CS, only products cost a constraint;
+            // ACIR expressions and CCS rows likewise take any number of linear terms
+            TargetSystem::R1CS | TargetSystem::ACIR | TargetSystem::CCS => Self {
+                mul: 1,
+                const_mul: 0,
+                add: 0,
+                div: 1,
+            },
+            // Every arithmetic gate occupies a row
+            TargetSystem::Plonk | TargetSystem::Halo2 | TargetSystem::Plonky2 => Self {
+                mul: 1,
+                const_mul: 1,
+                add: 1,
+                div: 2,
+            },
+            // Products raise the transition constraint degree
+            TargetSystem::AIR => Self {
+                mul: 2,
+                const_mul: 2,
+                add: 1,
+                div: 3,
+            },
+            TargetSystem::Custom(name) => registry::factory(&name).map_or_else(Self::default, |factory| factory.cost_model()),
+        }
+    }
+
+    /// Non-zero entries of the A, B and C matrices of the graph's R1CS lowering.
+    /// Groth16 prover time scales with this rather than with the row count.
+    pub fn nonzeros(&self, graph: &IRGraph) -> usize {
+        sparsity::r1cs_nonzeros(graph)
+    }
+
+    /// Cost of computing `k * x` with a double-and-add chain
+    pub fn addition_chain_cost(&self, k: u64) -> u32 {
+        if k < 2 {
+            return 0;
+        }
+        let doublings = 63 - k.leading_zeros();
+        let additions = k.count_ones() - 1;
+        (doublings + additions) * self.add
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::for_target(TargetSystem::R1CS)
+    }
+}
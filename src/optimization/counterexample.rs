@@ -0,0 +1,172 @@
+//! Constraints that can never hold, or always hold, with an input assignment showing it.
+//!
+//! Each constraint is reduced to a residual that is zero exactly when it holds, such as
+//! `a - b` for an equality, and evaluated on random inputs. A residual that takes the same
+//! value on every sample is almost surely a constant polynomial of the inputs, so the
+//! constraint either fails for every input or constrains nothing. Either is usually a bug
+//! in the source: a misspelt assertion, or one that was meant to read another variable.
+
+use crate::ir::{ConstraintType, IRGraph, IRNodeType, SourceSpan};
+use crate::optimization::field;
+use crate::optimization::interpreter::{evaluate, input_names, Evaluation};
+use crate::optimization::soundness::random_value;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+/// Random input assignments each constraint's residual is evaluated on
+pub const DEFAULT_COUNTEREXAMPLE_SAMPLES: usize = 16;
+
+const SEED: u64 = 0xc0_ffee;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintProblem {
+    /// Fails for every input, so no proof of the circuit exists
+    Unsatisfiable,
+    /// Holds for every input, so it checks nothing
+    TriviallyTrue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuspiciousConstraint {
+    pub node: usize,
+    pub problem: ConstraintProblem,
+    pub span: Option<SourceSpan>,
+    /// An assignment on which the constraint fails, or holds, as every other one does
+    pub inputs: Vec<(String, BigUint)>,
+    /// Values of the constraint's operands on `inputs`
+    pub operands: Vec<BigUint>,
+}
+
+impl SuspiciousConstraint {
+    pub fn describe(&self) -> String {
+        let location = self.span.map(|span| format!(" at {}", span)).unwrap_or_default();
+        let verdict = match self.problem {
+            ConstraintProblem::Unsatisfiable => "can never hold",
+            ConstraintProblem::TriviallyTrue => "holds for every input",
+        };
+        let inputs: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        let operands: Vec<String> = self.operands.iter().map(ToString::to_string).collect();
+        format!(
+            "Constraint node {}{} {}: on inputs [{}] its operands are [{}]",
+            self.node,
+            location,
+            verdict,
+            inputs.join(", "),
+            operands.join(", ")
+        )
+    }
+}
+
+/// Find the constraints of `graph` whose outcome does not depend on the inputs, testing
+/// each on `samples` random assignments
+pub fn find_suspicious_constraints(graph: &IRGraph, samples: usize) -> Vec<SuspiciousConstraint> {
+    let names = input_names(graph);
+    let constraints: Vec<(usize, ConstraintType, Vec<usize>)> = graph
+        .live_nodes()
+        .filter_map(|node| match &node.node_type {
+            IRNodeType::Constraint(constraint) => Some((node.id, constraint.clone(), graph.get_predecessors(node.id))),
+            _ => None,
+        })
+        .collect();
+
+    // The first assignment the graph could be evaluated on, and each constraint's residual
+    // there; `None` once it has been seen to vary
+    let mut first: Option<(HashMap<String, BigUint>, Evaluation)> = None;
+    let mut residuals: Vec<Option<BigUint>> = Vec::new();
+    let mut rng = StdRng::seed_from_u64(SEED);
+    // Without inputs a single evaluation decides every constraint
+    let samples = if names.is_empty() { samples.min(1) } else { samples };
+    for _ in 0..samples {
+        let inputs: HashMap<String, BigUint> = names
+            .iter()
+            .map(|name| (name.clone(), random_value(&mut rng)))
+            .collect();
+        let evaluation = match evaluate(graph, &inputs) {
+            Ok(evaluation) => evaluation,
+            Err(_) => continue,
+        };
+        let values: Vec<Option<BigUint>> = constraints
+            .iter()
+            .map(|(_, constraint, operands)| residual(constraint, &operand_values(&evaluation, operands)))
+            .collect();
+        if first.is_none() {
+            residuals = values;
+            first = Some((inputs, evaluation));
+            continue;
+        }
+        for (seen, value) in residuals.iter_mut().zip(values) {
+            if *seen != value {
+                *seen = None;
+            }
+        }
+    }
+
+    let (inputs, evaluation) = match first {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let mut inputs: Vec<(String, BigUint)> = inputs.into_iter().collect();
+    inputs.sort();
+    constraints
+        .iter()
+        .zip(residuals)
+        .filter_map(|((node_id, constraint, operands), residual)| {
+            Some(SuspiciousConstraint {
+                node: *node_id,
+                problem: problem(constraint, &residual?),
+                span: graph.span(*node_id),
+                inputs: inputs.clone(),
+                operands: operand_values(&evaluation, operands),
+            })
+        })
+        .collect()
+}
+
+fn operand_values(evaluation: &Evaluation, operands: &[usize]) -> Vec<BigUint> {
+    operands
+        .iter()
+        .map(|operand| evaluation.values.get(operand).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// A value that is zero exactly when an equality or polynomial constraint holds, and
+/// nonzero exactly when an inequality does; a range check's value itself. `None` for
+/// shapes the interpreter does not model
+fn residual(constraint: &ConstraintType, operands: &[BigUint]) -> Option<BigUint> {
+    match (constraint, operands) {
+        (ConstraintType::Equality, [value]) => Some(field::sub(value, &BigUint::one())),
+        (ConstraintType::Equality, [a, b]) | (ConstraintType::Inequality, [a, b]) => Some(field::sub(a, b)),
+        (ConstraintType::Inequality, [value]) | (ConstraintType::Range { .. }, [value]) => Some(value.clone()),
+        (ConstraintType::Polynomial { coefficients }, [x]) => {
+            let mut sum = BigUint::zero();
+            let mut power = BigUint::one();
+            for coefficient in coefficients {
+                sum = field::add(&sum, &field::mul(&field::parse_element(coefficient)?, &power));
+                power = field::mul(&power, x);
+            }
+            Some(sum)
+        }
+        _ => None,
+    }
+}
+
+/// What a residual that is the same for every input means for `constraint`
+fn problem(constraint: &ConstraintType, residual: &BigUint) -> ConstraintProblem {
+    let holds = match constraint {
+        ConstraintType::Equality | ConstraintType::Polynomial { .. } => residual.is_zero(),
+        ConstraintType::Inequality => !residual.is_zero(),
+        ConstraintType::Range { bits } => residual.bits() <= *bits as u64,
+    };
+    if holds {
+        ConstraintProblem::TriviallyTrue
+    } else {
+        ConstraintProblem::Unsatisfiable
+    }
+}
@@ -0,0 +1,95 @@
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use std::collections::HashMap;
+
+/// Merges nodes that compute the same operation over the same operands
+#[derive(Debug, Default)]
+pub struct CommonSubexpressionElimination {
+    last_rewrites: usize,
+}
+
+impl CommonSubexpressionElimination {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let mut seen: HashMap<(IRNodeType, Vec<usize>), usize> = HashMap::new();
+        let mut merged = 0;
+
+        for node_id in graph.topological_sort() {
+            let node_type = match graph.get_node(node_id) {
+                Some(node) if is_pure(&node.node_type) => node.node_type.clone(),
+                _ => continue,
+            };
+
+            // Earlier merges already rewired our operands to their representatives
+            let mut operands = graph.get_predecessors(node_id);
+            if is_commutative(&node_type) {
+                operands.sort_unstable();
+            }
+
+            match seen.get(&(node_type.clone(), operands.clone())) {
+                Some(&representative) => {
+                    graph.replace_uses(node_id, representative);
+                    graph.remove_node(node_id);
+                    merged += 1;
+                }
+                None => {
+                    seen.insert((node_type, operands), node_id);
+                }
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+impl OptimizationPass for CommonSubexpressionElimination {
+    fn name(&self) -> &str {
+        "cse"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = CommonSubexpressionElimination::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+/// Nodes whose value depends only on their operands
+fn is_pure(node_type: &IRNodeType) -> bool {
+    matches!(
+        node_type,
+        IRNodeType::Add
+            | IRNodeType::Sub
+            | IRNodeType::Mul
+            | IRNodeType::Div
+            | IRNodeType::Neg
+            | IRNodeType::And
+            | IRNodeType::Or
+            | IRNodeType::Xor
+            | IRNodeType::Not
+            | IRNodeType::Eq
+            | IRNodeType::Ne
+            | IRNodeType::Lt
+            | IRNodeType::Le
+            | IRNodeType::Gt
+            | IRNodeType::Ge
+            | IRNodeType::Select
+            | IRNodeType::Constant(_)
+    )
+}
+
+fn is_commutative(node_type: &IRNodeType) -> bool {
+    matches!(
+        node_type,
+        IRNodeType::Add
+            | IRNodeType::Mul
+            | IRNodeType::And
+            | IRNodeType::Or
+            | IRNodeType::Xor
+            | IRNodeType::Eq
+            | IRNodeType::Ne
+    )
+}
@@ -0,0 +1,84 @@
+use crate::ir::{IRGraph, IRNode, IRNodeType};
+use crate::optimization::lookup::function_table;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DceReport {
+    pub removed: usize,
+    /// Private inputs that fed nothing observable, usually a missing assertion
+    pub eliminated_witnesses: Vec<String>,
+}
+
+/// Removes nodes that contribute to neither an output nor a constraint
+#[derive(Debug, Default)]
+pub struct DeadCodeElimination {
+    last_rewrites: usize,
+}
+
+impl DeadCodeElimination {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<DceReport, FCMCError> {
+        let mut live = HashSet::new();
+        let mut worklist: Vec<usize> = graph
+            .live_nodes()
+            .filter(|node| is_root(graph, node))
+            .map(|node| node.id)
+            .collect();
+
+        while let Some(node_id) = worklist.pop() {
+            if live.insert(node_id) {
+                worklist.extend(graph.get_predecessors(node_id));
+            }
+        }
+
+        let mut report = DceReport::default();
+        let dead: Vec<usize> = graph
+            .live_nodes()
+            .filter(|node| !live.contains(&node.id))
+            .map(|node| node.id)
+            .collect();
+
+        for node_id in dead {
+            if let Some(IRNodeType::PrivateInput(name)) = graph.get_node(node_id).map(|node| &node.node_type) {
+                report.eliminated_witnesses.push(name.clone());
+            }
+            graph.remove_node(node_id);
+            report.removed += 1;
+        }
+
+        if !report.eliminated_witnesses.is_empty() {
+            tracing::warn!(
+                "Dead code elimination removed unused witness variables: {} (missing assertion?)",
+                report.eliminated_witnesses.join(", ")
+            );
+        }
+
+        Ok(report)
+    }
+}
+
+impl OptimizationPass for DeadCodeElimination {
+    fn name(&self) -> &str {
+        "dce"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = DeadCodeElimination::run(self, graph)?.removed;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+/// Nodes that are observable by the verifier and must never be removed.
+/// Function lookups only compute a value and are removable like any other operation.
+fn is_root(graph: &IRGraph, node: &IRNode) -> bool {
+    match node.node_type {
+        IRNodeType::Output(_) | IRNodeType::Input(_) | IRNodeType::Constraint(_) | IRNodeType::RangeCheck => true,
+        IRNodeType::Lookup => function_table(graph, node).is_none(),
+        _ => false,
+    }
+}
@@ -0,0 +1,263 @@
+//! Deduplication of constraints, in the IR and in lowered R1CS rows.
+//!
+//! The `dedup` pass canonicalizes each IR constraint into a polynomial over the nodes it
+//! reads, expanded through additions and multiplications up to quadratic degree, and
+//! scaled so its leading coefficient is one. `deduplicate_rows` does the same for the
+//! rows lowering produced, which repeat whenever a multiplication, zero test or range
+//! decomposition is lowered twice.
+
+use crate::backend::lowering::LinearCombination;
+use crate::backend::R1CSCircuit;
+use crate::ir::{ConstraintType, IRGraph, IRNodeType};
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Highest monomial degree expanded; R1CS rows are at most quadratic
+const MAX_DEGREE: usize = 2;
+
+/// Expressions with more terms than this are kept as a single wire
+const MAX_TERMS: usize = 64;
+
+/// Sorted wire ids of a monomial -> nonzero coefficient
+type Polynomial = BTreeMap<Vec<usize>, BigUint>;
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ConstraintKey {
+    Zero(Polynomial),
+    NonZero(Polynomial),
+    Range(u32, Polynomial),
+    Opaque(ConstraintType, Vec<usize>),
+}
+
+/// Removes constraints that repeat, or are scalar multiples of, an earlier constraint
+#[derive(Debug, Default)]
+pub struct ConstraintDeduplication {
+    last_rewrites: usize,
+}
+
+impl ConstraintDeduplication {
+    /// Returns the number of constraints removed
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let mut expansions: HashMap<usize, Polynomial> = HashMap::new();
+        let mut seen: HashMap<ConstraintKey, usize> = HashMap::new();
+        let mut removed = 0;
+
+        for node_id in graph.topological_sort() {
+            let constraint = match graph.get_node(node_id).map(|node| &node.node_type) {
+                Some(IRNodeType::Constraint(constraint)) => constraint.clone(),
+                _ => continue,
+            };
+            let operands = graph.get_predecessors(node_id);
+            let key = canonical_key(graph, &constraint, &operands, &mut expansions);
+
+            match seen.get(&key) {
+                Some(&representative) => {
+                    graph.replace_uses(node_id, representative);
+                    graph.remove_node(node_id);
+                    removed += 1;
+                }
+                None => {
+                    seen.insert(key, node_id);
+                }
+            }
+        }
+
+        if removed > 0 {
+            tracing::debug!("Removed {} duplicate constraints", removed);
+        }
+        Ok(removed)
+    }
+}
+
+impl OptimizationPass for ConstraintDeduplication {
+    fn name(&self) -> &str {
+        "dedup"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = ConstraintDeduplication::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+fn canonical_key(
+    graph: &IRGraph,
+    constraint: &ConstraintType,
+    operands: &[usize],
+    expansions: &mut HashMap<usize, Polynomial>,
+) -> ConstraintKey {
+    let mut wire = |node_id: usize| expand(graph, node_id, expansions);
+
+    match (constraint, operands) {
+        (ConstraintType::Equality, &[value]) => {
+            ConstraintKey::Zero(normalize(sub(&wire(value), &constant(BigUint::one()))))
+        }
+        (ConstraintType::Equality, &[a, b]) => ConstraintKey::Zero(normalize(sub(&wire(a), &wire(b)))),
+        (ConstraintType::Inequality, &[value]) => ConstraintKey::NonZero(normalize(wire(value))),
+        (ConstraintType::Inequality, &[a, b]) => ConstraintKey::NonZero(normalize(sub(&wire(a), &wire(b)))),
+        // Scaling changes which values fit, so range operands are compared exactly
+        (ConstraintType::Range { bits }, &[value]) => ConstraintKey::Range(*bits, wire(value)),
+        (ConstraintType::Polynomial { coefficients }, &[x]) => {
+            let x = wire(x);
+            match evaluate_polynomial(coefficients, &x) {
+                Some(poly) => ConstraintKey::Zero(normalize(poly)),
+                None => ConstraintKey::Opaque(constraint.clone(), operands.to_vec()),
+            }
+        }
+        _ => ConstraintKey::Opaque(constraint.clone(), operands.to_vec()),
+    }
+}
+
+/// Polynomial computed by `node_id`; nodes the expansion cannot see through are wires
+fn expand(graph: &IRGraph, node_id: usize, expansions: &mut HashMap<usize, Polynomial>) -> Polynomial {
+    if let Some(poly) = expansions.get(&node_id) {
+        return poly.clone();
+    }
+
+    let operands = graph.get_predecessors(node_id);
+    let expanded = match (graph.get_node(node_id).map(|node| &node.node_type), operands.as_slice()) {
+        (Some(IRNodeType::Constant(value)), _) => field::parse_element(value).map(constant),
+        (Some(IRNodeType::Add), &[a, b]) => Some(add(&expand(graph, a, expansions), &expand(graph, b, expansions))),
+        (Some(IRNodeType::Sub), &[a, b]) => Some(sub(&expand(graph, a, expansions), &expand(graph, b, expansions))),
+        (Some(IRNodeType::Neg), &[a]) => Some(scale(&expand(graph, a, expansions), &field::neg(&BigUint::one()))),
+        (Some(IRNodeType::Mul), &[a, b]) => mul(&expand(graph, a, expansions), &expand(graph, b, expansions)),
+        _ => None,
+    };
+
+    let poly = expanded
+        .filter(|poly| poly.len() <= MAX_TERMS)
+        .unwrap_or_else(|| Polynomial::from([(vec![node_id], BigUint::one())]));
+    expansions.insert(node_id, poly.clone());
+    poly
+}
+
+fn evaluate_polynomial(coefficients: &[String], x: &Polynomial) -> Option<Polynomial> {
+    // Horner's rule from the highest coefficient down
+    let mut result = Polynomial::new();
+    for coefficient in coefficients.iter().rev() {
+        result = mul(&result, x)?;
+        result = add(&result, &constant(field::parse_element(coefficient)?));
+    }
+    Some(result)
+}
+
+fn constant(value: BigUint) -> Polynomial {
+    let mut poly = Polynomial::new();
+    if !value.is_zero() {
+        poly.insert(Vec::new(), value);
+    }
+    poly
+}
+
+fn add(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    let mut sum = a.clone();
+    for (monomial, coefficient) in b {
+        let entry = sum.entry(monomial.clone()).or_insert_with(BigUint::zero);
+        *entry = field::add(entry, coefficient);
+        if entry.is_zero() {
+            sum.remove(monomial);
+        }
+    }
+    sum
+}
+
+fn sub(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    add(a, &scale(b, &field::neg(&BigUint::one())))
+}
+
+fn scale(a: &Polynomial, factor: &BigUint) -> Polynomial {
+    a.iter()
+        .map(|(monomial, coefficient)| (monomial.clone(), field::mul(coefficient, factor)))
+        .filter(|(_, coefficient)| !coefficient.is_zero())
+        .collect()
+}
+
+/// Product of `a` and `b`, or `None` if it exceeds the expanded degree
+fn mul(a: &Polynomial, b: &Polynomial) -> Option<Polynomial> {
+    let mut product = Polynomial::new();
+    for (ma, ca) in a {
+        for (mb, cb) in b {
+            if ma.len() + mb.len() > MAX_DEGREE {
+                return None;
+            }
+            let mut monomial: Vec<usize> = ma.iter().chain(mb).copied().collect();
+            monomial.sort_unstable();
+            let entry = product.entry(monomial).or_insert_with(BigUint::zero);
+            *entry = field::add(entry, &field::mul(ca, cb));
+        }
+    }
+    product.retain(|_, coefficient| !coefficient.is_zero());
+    Some(product)
+}
+
+/// Scale so the first term has coefficient one
+fn normalize(poly: Polynomial) -> Polynomial {
+    let factor = match poly.values().next().and_then(field::inverse) {
+        Some(factor) => factor,
+        None => return poly,
+    };
+    scale(&poly, &factor)
+}
+
+/// Remove rows of `circuit` that repeat, or are scalar multiples of, an earlier row,
+/// returning how many were removed; runs in the active field
+pub fn deduplicate_rows(circuit: &mut R1CSCircuit) -> usize {
+    let mut seen = HashSet::new();
+    let keep: Vec<bool> = circuit
+        .constraints
+        .iter()
+        .map(|row| seen.insert(row_key(&row.a, &row.b, &row.c)))
+        .collect();
+    let removed = keep.iter().filter(|&&kept| !kept).count();
+    if removed == 0 {
+        return 0;
+    }
+    let mut rows = keep.iter();
+    circuit.constraints.retain(|_| *rows.next().unwrap_or(&true));
+    let mut origins = keep.iter();
+    circuit.origins.retain(|_| *origins.next().unwrap_or(&true));
+    tracing::debug!("Removed {} duplicate R1CS rows", removed);
+    removed
+}
+
+/// `a * b = c` scaled so `a` and `b` lead with one, with `a` and `b` in order; rows with
+/// an empty side say `c = 0`, and only `c` is scaled
+fn row_key(
+    a: &LinearCombination,
+    b: &LinearCombination,
+    c: &LinearCombination,
+) -> (LinearCombination, LinearCombination, LinearCombination) {
+    let lead = |lc: &LinearCombination| lc.values().next().and_then(field::inverse);
+    let scale_lc = |lc: &LinearCombination, factor: &BigUint| -> LinearCombination {
+        lc.iter()
+            .map(|(&wire, coefficient)| (wire, field::mul(coefficient, factor)))
+            .filter(|(_, coefficient)| !coefficient.is_zero())
+            .collect()
+    };
+    match (lead(a), lead(b)) {
+        (Some(alpha), Some(beta)) => {
+            let (a, b) = (scale_lc(a, &alpha), scale_lc(b, &beta));
+            let c = scale_lc(c, &field::mul(&alpha, &beta));
+            if a <= b {
+                (a, b, c)
+            } else {
+                (b, a, c)
+            }
+        }
+        _ => {
+            let c = match lead(c) {
+                Some(gamma) => scale_lc(c, &gamma),
+                None => c.clone(),
+            };
+            (LinearCombination::new(), LinearCombination::new(), c)
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Multiplicative depth of every live node; products with a constant do not add depth
+pub fn multiplicative_depths(graph: &IRGraph) -> HashMap<usize, usize> {
+    let mut depths = HashMap::new();
+    for node_id in graph.topological_sort() {
+        let depth = node_depth(graph, &depths, node_id);
+        depths.insert(node_id, depth);
+    }
+    depths
+}
+
+/// Largest multiplicative depth in the circuit
+pub fn multiplicative_depth(graph: &IRGraph) -> usize {
+    multiplicative_depths(graph).values().copied().max().unwrap_or(0)
+}
+
+fn node_depth(graph: &IRGraph, depths: &HashMap<usize, usize>, node_id: usize) -> usize {
+    let operands = graph.get_predecessors(node_id);
+    let deepest = operands
+        .iter()
+        .map(|operand| depths.get(operand).copied().unwrap_or(0))
+        .max()
+        .unwrap_or(0);
+
+    let is_mul = matches!(graph.get_node(node_id).map(|node| &node.node_type), Some(IRNodeType::Mul));
+    if is_mul && !operands.iter().any(|&operand| is_constant(graph, operand)) {
+        deepest + 1
+    } else {
+        deepest
+    }
+}
+
+fn is_constant(graph: &IRGraph, node_id: usize) -> bool {
+    matches!(graph.get_node(node_id).map(|node| &node.node_type), Some(IRNodeType::Constant(_)))
+}
+
+fn is_mul(graph: &IRGraph, node_id: usize) -> bool {
+    matches!(graph.get_node(node_id).map(|node| &node.node_type), Some(IRNodeType::Mul))
+}
+
+/// Rebalances product trees so a chain of n factors has depth log2(n) instead of n
+#[derive(Debug, Default)]
+pub struct DepthReduction {
+    last_rewrites: usize,
+}
+
+impl DepthReduction {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let mut depths = HashMap::new();
+        let mut rebalanced = 0;
+
+        for node_id in graph.topological_sort() {
+            if graph.is_removed(node_id) {
+                continue;
+            }
+
+            let mut current = node_id;
+            if is_mul(graph, node_id) && !is_interior(graph, node_id) {
+                if let Some(new_root) = rebalance(graph, &mut depths, node_id) {
+                    current = new_root;
+                    rebalanced += 1;
+                }
+            }
+
+            let depth = node_depth(graph, &depths, current);
+            depths.insert(current, depth);
+        }
+
+        Ok(rebalanced)
+    }
+}
+
+impl OptimizationPass for DepthReduction {
+    fn name(&self) -> &str {
+        "depth"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = DepthReduction::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+/// A product whose only consumer is another product belongs to that product's tree
+fn is_interior(graph: &IRGraph, node_id: usize) -> bool {
+    match graph.get_successors(node_id).as_slice() {
+        [consumer] => is_mul(graph, *consumer),
+        _ => false,
+    }
+}
+
+/// Operand of a planned product: an existing node or an earlier planned product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Factor {
+    Node(usize),
+    Product(usize),
+}
+
+impl Factor {
+    fn resolve(self, built: &[usize]) -> usize {
+        match self {
+            Factor::Node(id) => id,
+            Factor::Product(index) => built[index],
+        }
+    }
+}
+
+/// Rebuild the product tree rooted at `root` with the shallowest factors combined first.
+/// Returns the new root if the depth improved.
+fn rebalance(graph: &mut IRGraph, depths: &mut HashMap<usize, usize>, root: usize) -> Option<usize> {
+    let mut leaves = Vec::new();
+    let mut interior = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node_id) = stack.pop() {
+        for operand in graph.get_predecessors(node_id) {
+            if is_mul(graph, operand) && is_interior(graph, operand) {
+                interior.push(operand);
+                stack.push(operand);
+            } else {
+                leaves.push(operand);
+            }
+        }
+    }
+
+    if leaves.len() < 3 {
+        return None;
+    }
+
+    let old_depth = tree_depth(graph, depths, root);
+
+    // Huffman-style combination yields the minimum depth for the given leaf depths
+    let mut heap: BinaryHeap<Reverse<(usize, Factor)>> = leaves
+        .iter()
+        .map(|&leaf| Reverse((leaf_depth(graph, depths, leaf), Factor::Node(leaf))))
+        .collect();
+    let mut new_depth = 0;
+    let mut plan = Vec::new();
+    while heap.len() > 1 {
+        let Reverse((depth_a, a)) = heap.pop()?;
+        let Reverse((depth_b, b)) = heap.pop()?;
+        new_depth = depth_a.max(depth_b) + 1;
+        heap.push(Reverse((new_depth, Factor::Product(plan.len()))));
+        plan.push((a, b));
+    }
+
+    if new_depth >= old_depth {
+        return None;
+    }
+
+    let data_type = graph.get_node(root)?.data_type.clone();
+    let mut built: Vec<usize> = Vec::new();
+    for (a, b) in plan {
+        let product = graph.add_node(IRNodeType::Mul, data_type.clone(), None);
+        graph.add_edge(a.resolve(&built), product, EdgeType::DataFlow);
+        graph.add_edge(b.resolve(&built), product, EdgeType::DataFlow);
+        let depth = node_depth(graph, depths, product);
+        depths.insert(product, depth);
+        built.push(product);
+    }
+
+    let new_root = *built.last()?;
+    graph.replace_uses(root, new_root);
+    graph.remove_node(root);
+    for node_id in interior {
+        graph.remove_node(node_id);
+    }
+
+    Some(new_root)
+}
+
+fn leaf_depth(graph: &IRGraph, depths: &HashMap<usize, usize>, node_id: usize) -> usize {
+    depths
+        .get(&node_id)
+        .copied()
+        .unwrap_or_else(|| node_depth(graph, depths, node_id))
+}
+
+fn tree_depth(graph: &IRGraph, depths: &HashMap<usize, usize>, node_id: usize) -> usize {
+    let deepest = graph
+        .get_predecessors(node_id)
+        .into_iter()
+        .map(|operand| {
+            if is_mul(graph, operand) && is_interior(graph, operand) {
+                tree_depth(graph, depths, operand)
+            } else {
+                leaf_depth(graph, depths, operand)
+            }
+        })
+        .max()
+        .unwrap_or(0);
+    deepest + 1
+}
@@ -0,0 +1,179 @@
+//! Explain mode: attributes what every pass run saved to the source locations it touched.
+//!
+//! Before a pass runs, a census records each live node with its location and cost. Nodes
+//! the pass removed and nodes it added are then grouped by location, so each entry reads
+//! like "folded constant expression at line 42 (saved 3 constraints)".
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::constant_value;
+use crate::optimization::cost::CostModel;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// What one pass run did at one source location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation {
+    pub pass: String,
+    pub iteration: usize,
+    /// `line N`, a variable name, or `unknown location`
+    pub location: String,
+    pub line: Option<usize>,
+    pub nodes_removed: usize,
+    /// Constraint cost removed minus cost added; negative when the pass spent constraints here
+    pub constraints_saved: i64,
+}
+
+impl Explanation {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} at {} (saved {} constraint{})",
+            describe_pass(&self.pass),
+            self.location,
+            self.constraints_saved,
+            if self.constraints_saved == 1 { "" } else { "s" }
+        )
+    }
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
+fn describe_pass(pass: &str) -> String {
+    match pass {
+        "constfold" => "folded constant expression".to_string(),
+        "cse" => "merged common subexpression".to_string(),
+        "dce" => "removed dead code".to_string(),
+        "dedup" => "removed duplicate constraint".to_string(),
+        "depth" => "rebalanced multiplication chain".to_string(),
+        "parallel" => "folded and merged expressions".to_string(),
+        "booleanity" => "removed redundant booleanity check".to_string(),
+        "rangecheck" => "merged range checks".to_string(),
+        "lookups" => "replaced small-domain function with lookup table".to_string(),
+        "lowerlookups" => "lowered lookup to arithmetic".to_string(),
+        "strength" => "reduced operation strength".to_string(),
+        "saturation" => "rewrote expression by equality saturation".to_string(),
+        "superopt" => "replaced subcircuit with cheaper equivalent".to_string(),
+        "subcircuits" => "extracted repeated subcircuit".to_string(),
+        other => format!("pass '{}' rewrote", other),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Location {
+    line: Option<usize>,
+    name: Option<String>,
+}
+
+impl Location {
+    fn of(graph: &IRGraph, node_id: usize) -> Option<Self> {
+        if let Some(span) = graph.span(node_id) {
+            return Some(Self {
+                line: Some(span.line),
+                name: None,
+            });
+        }
+        let label = graph.get_node(node_id)?.label.clone()?;
+        Some(Self {
+            line: None,
+            name: Some(label),
+        })
+    }
+
+    fn describe(&self) -> String {
+        match (self.line, &self.name) {
+            (Some(line), _) => format!("line {}", line),
+            (None, Some(name)) => format!("`{}`", name),
+            (None, None) => "unknown location".to_string(),
+        }
+    }
+}
+
+const UNKNOWN: Location = Location { line: None, name: None };
+
+/// Live nodes of a graph with their location and cost, taken before a pass runs
+#[derive(Debug)]
+pub(crate) struct NodeCensus {
+    nodes: HashMap<usize, (Option<Location>, i64)>,
+}
+
+impl NodeCensus {
+    pub(crate) fn take(graph: &IRGraph, cost: &CostModel) -> Self {
+        let nodes = graph
+            .live_nodes()
+            .map(|node| (node.id, (Location::of(graph, node.id), node_cost(graph, node.id, cost))))
+            .collect();
+        Self { nodes }
+    }
+
+    /// Group the nodes `pass` removed from and added to `graph` by location
+    pub(crate) fn explain(&self, graph: &IRGraph, cost: &CostModel, pass: &str, iteration: usize) -> Vec<Explanation> {
+        // location -> (nodes removed, cost removed, cost added)
+        let mut groups: BTreeMap<Location, (usize, i64, i64)> = BTreeMap::new();
+        for (&node_id, (location, node_cost)) in &self.nodes {
+            if graph.get_node(node_id).is_some() && !graph.is_removed(node_id) {
+                continue;
+            }
+            let group = groups.entry(location.clone().unwrap_or(UNKNOWN)).or_default();
+            group.0 += 1;
+            group.1 += node_cost;
+        }
+
+        let added: Vec<usize> = graph
+            .live_nodes()
+            .map(|node| node.id)
+            .filter(|node_id| !self.nodes.contains_key(node_id))
+            .collect();
+        // New nodes without a location most likely replace what the pass removed
+        let fallback = match groups.keys().collect::<Vec<_>>().as_slice() {
+            [only] => (*only).clone(),
+            _ => UNKNOWN,
+        };
+        for node_id in added {
+            let location = Location::of(graph, node_id).unwrap_or_else(|| fallback.clone());
+            groups.entry(location).or_default().2 += node_cost(graph, node_id, cost);
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, (removed, removed_cost, added_cost))| *removed > 0 || removed_cost != added_cost)
+            .map(|(location, (removed, removed_cost, added_cost))| Explanation {
+                pass: pass.to_string(),
+                iteration,
+                location: location.describe(),
+                line: location.line,
+                nodes_removed: removed,
+                constraints_saved: removed_cost - added_cost,
+            })
+            .collect()
+    }
+}
+
+/// Constraints a node contributes once lowered
+fn node_cost(graph: &IRGraph, node_id: usize, cost: &CostModel) -> i64 {
+    let node_type = match graph.get_node(node_id) {
+        Some(node) => &node.node_type,
+        None => return 0,
+    };
+    let unit = match node_type {
+        IRNodeType::Mul => {
+            let scaled = graph
+                .get_predecessors(node_id)
+                .into_iter()
+                .any(|operand| constant_value(graph, operand).is_some());
+            if scaled {
+                cost.const_mul
+            } else {
+                cost.mul
+            }
+        }
+        IRNodeType::Add | IRNodeType::Sub => cost.add,
+        IRNodeType::Neg => cost.const_mul,
+        IRNodeType::Div => cost.div,
+        IRNodeType::Constraint(_) | IRNodeType::Lookup => 1,
+        _ => 0,
+    };
+    unit as i64
+}
@@ -0,0 +1,215 @@
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+
+/// BLS12-381 scalar field modulus, the field circuits are defined over by default
+pub const SCALAR_FIELD_MODULUS: &str =
+    "52435875175126190479447740508185965837690552500527637822603658699938581184513";
+
+/// BN254 scalar field modulus, used by circom, snarkjs and Ethereum verifiers
+pub const BN254_MODULUS: &str =
+    "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+/// Goldilocks modulus `2^64 - 2^32 + 1`, used by Plonky2 and Plonky3
+pub const GOLDILOCKS_MODULUS: &str = "18446744069414584321";
+
+/// Pallas base field modulus, the scalar field of Vesta
+pub const PALLAS_MODULUS: &str =
+    "28948022309329048855892746252171976963363056481941560715954676764349967630337";
+
+/// Vesta base field modulus, the scalar field of Pallas
+pub const VESTA_MODULUS: &str =
+    "28948022309329048855892746252171976963363056481941647379679742748393362948097";
+
+/// The prime field a circuit is defined over
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum FieldConfig {
+    Bn254,
+    #[default]
+    Bls12_381,
+    Goldilocks,
+    Pallas,
+    Vesta,
+    Custom(BigUint),
+}
+
+impl FieldConfig {
+    /// A field of prime order `modulus`, named if it is one of the built-in ones
+    pub fn from_modulus(modulus: BigUint) -> Result<Self, FCMCError> {
+        let named = [
+            FieldConfig::Bn254,
+            FieldConfig::Bls12_381,
+            FieldConfig::Goldilocks,
+            FieldConfig::Pallas,
+            FieldConfig::Vesta,
+        ];
+        if let Some(field) = named.into_iter().find(|field| field.modulus() == modulus) {
+            return Ok(field);
+        }
+        if !is_probable_prime(&modulus) {
+            return Err(FCMCError::SemanticError(format!("field modulus {} is not prime", modulus)));
+        }
+        Ok(FieldConfig::Custom(modulus))
+    }
+
+    pub fn modulus(&self) -> BigUint {
+        let modulus = match self {
+            FieldConfig::Bn254 => BN254_MODULUS,
+            FieldConfig::Bls12_381 => SCALAR_FIELD_MODULUS,
+            FieldConfig::Goldilocks => GOLDILOCKS_MODULUS,
+            FieldConfig::Pallas => PALLAS_MODULUS,
+            FieldConfig::Vesta => VESTA_MODULUS,
+            FieldConfig::Custom(modulus) => return modulus.clone(),
+        };
+        modulus.parse().expect("valid field modulus")
+    }
+
+    /// Bit length of the modulus
+    pub fn bits(&self) -> u32 {
+        self.modulus().bits() as u32
+    }
+
+    /// Largest `n` such that every `n`-bit value is a distinct field element
+    pub fn capacity(&self) -> u32 {
+        self.bits() - 1
+    }
+
+    /// Run `f` with every field operation on this thread reduced modulo this field
+    pub fn enter<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = ACTIVE_FIELD.with(|active| active.replace(Some((self.clone(), self.modulus()))));
+        let result = f();
+        ACTIVE_FIELD.with(|active| *active.borrow_mut() = previous);
+        result
+    }
+}
+
+impl fmt::Display for FieldConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldConfig::Bn254 => write!(f, "bn254"),
+            FieldConfig::Bls12_381 => write!(f, "bls12-381"),
+            FieldConfig::Goldilocks => write!(f, "goldilocks"),
+            FieldConfig::Pallas => write!(f, "pallas"),
+            FieldConfig::Vesta => write!(f, "vesta"),
+            FieldConfig::Custom(modulus) => write!(f, "{}", modulus),
+        }
+    }
+}
+
+/// A field name as printed by `Display`, or a decimal prime
+impl FromStr for FieldConfig {
+    type Err = FCMCError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "bn254" | "bn128" => Ok(FieldConfig::Bn254),
+            "bls12-381" | "bls12_381" => Ok(FieldConfig::Bls12_381),
+            "goldilocks" => Ok(FieldConfig::Goldilocks),
+            "pallas" => Ok(FieldConfig::Pallas),
+            "vesta" => Ok(FieldConfig::Vesta),
+            other => match other.parse::<BigUint>() {
+                Ok(modulus) => FieldConfig::from_modulus(modulus),
+                Err(_) => Err(FCMCError::SemanticError(format!("unknown field '{}'", name))),
+            },
+        }
+    }
+}
+
+/// Miller-Rabin with fixed bases, enough to reject a mistyped modulus
+fn is_probable_prime(n: &BigUint) -> bool {
+    let two = BigUint::from(2u32);
+    if *n < two {
+        return false;
+    }
+    let bases = [2u32, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    for &base in &bases {
+        if *n == BigUint::from(base) {
+            return true;
+        }
+        if (n % base).is_zero() {
+            return false;
+        }
+    }
+    let minus_one = n - BigUint::one();
+    let shift = minus_one.trailing_zeros().unwrap_or(0);
+    let odd = &minus_one >> shift;
+    bases.iter().all(|&base| {
+        let mut x = BigUint::from(base).modpow(&odd, n);
+        if x.is_one() || x == minus_one {
+            return true;
+        }
+        for _ in 1..shift {
+            x = x.modpow(&two, n);
+            if x == minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+thread_local! {
+    /// Field entered on this thread, with its modulus, if any
+    static ACTIVE_FIELD: RefCell<Option<(FieldConfig, BigUint)>> = const { RefCell::new(None) };
+}
+
+/// Field entered on this thread, the default field otherwise
+pub fn active() -> FieldConfig {
+    ACTIVE_FIELD
+        .with(|active| active.borrow().as_ref().map(|(field, _)| field.clone()))
+        .unwrap_or_default()
+}
+
+pub fn modulus() -> BigUint {
+    ACTIVE_FIELD
+        .with(|active| active.borrow().as_ref().map(|(_, modulus)| modulus.clone()))
+        .unwrap_or_else(|| SCALAR_FIELD_MODULUS.parse().expect("valid field modulus"))
+}
+
+/// Parse a constant as written in the IR, reducing negative values into the field
+pub fn parse_element(value: &str) -> Option<BigUint> {
+    let value = value.trim();
+    let p = modulus();
+    match value.strip_prefix('-') {
+        Some(magnitude) => {
+            let magnitude = magnitude.parse::<BigUint>().ok()? % &p;
+            if magnitude.is_zero() {
+                Some(magnitude)
+            } else {
+                Some(p - magnitude)
+            }
+        }
+        None => Some(value.parse::<BigUint>().ok()? % p),
+    }
+}
+
+/// Multiplicative inverse via Fermat's little theorem
+pub fn inverse(value: &BigUint) -> Option<BigUint> {
+    let p = modulus();
+    let value = value % &p;
+    if value.is_zero() {
+        return None;
+    }
+    let exponent = &p - BigUint::one() - BigUint::one();
+    Some(value.modpow(&exponent, &p))
+}
+
+pub fn add(a: &BigUint, b: &BigUint) -> BigUint {
+    (a + b) % modulus()
+}
+
+pub fn sub(a: &BigUint, b: &BigUint) -> BigUint {
+    let p = modulus();
+    ((a % &p) + &p - (b % &p)) % p
+}
+
+pub fn mul(a: &BigUint, b: &BigUint) -> BigUint {
+    (a * b) % modulus()
+}
+
+pub fn neg(a: &BigUint) -> BigUint {
+    sub(&BigUint::zero(), a)
+}
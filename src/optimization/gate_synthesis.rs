@@ -0,0 +1,148 @@
+//! Custom-gate synthesis.
+//!
+//! Small patterns such as `a*b + c*d` or a select on a boolean take several rows of the
+//! standard plonkish gate but fit one custom gate. Patterns that occur often enough to
+//! pay for the gate's selector column get their root tagged with the gate and its input
+//! nodes; backends with custom gates then emit one gate per root and skip the pattern's
+//! inner nodes.
+
+use crate::backend::custom_gate::CustomGate;
+use crate::backend::lowering::{CUSTOM_GATE_ATTRIBUTE, CUSTOM_GATE_INPUTS_ATTRIBUTE};
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::constant_value;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::target::TargetProfile;
+use crate::FCMCError;
+use std::collections::BTreeMap;
+
+/// Fewest occurrences of a pattern worth a gate of its own
+pub const DEFAULT_MIN_OCCURRENCES: usize = 4;
+
+/// Tags the roots of frequent small patterns with the custom gate computing them
+pub struct CustomGateSynthesis {
+    min_occurrences: usize,
+    last_rewrites: usize,
+}
+
+impl CustomGateSynthesis {
+    pub fn new(min_occurrences: usize) -> Self {
+        Self {
+            min_occurrences,
+            last_rewrites: 0,
+        }
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<BTreeMap<CustomGate, usize>, FCMCError> {
+        let booleans = BooleanityAnalysis::run(graph);
+        self.run_with(graph, &booleans)
+    }
+
+    /// Run with a precomputed booleanity analysis of `graph`; returns the number of
+    /// roots tagged per gate
+    pub fn run_with(
+        &self,
+        graph: &mut IRGraph,
+        booleans: &BooleanityAnalysis,
+    ) -> Result<BTreeMap<CustomGate, usize>, FCMCError> {
+        let mut matches: BTreeMap<CustomGate, Vec<(usize, Vec<usize>)>> = BTreeMap::new();
+        for node_id in graph.topological_sort() {
+            if let Some((gate, inputs)) = match_pattern(graph, booleans, node_id) {
+                matches.entry(gate).or_default().push((node_id, inputs));
+            }
+        }
+
+        let mut tagged = BTreeMap::new();
+        for (gate, roots) in matches {
+            if roots.len() < self.min_occurrences {
+                continue;
+            }
+            tagged.insert(gate, roots.len());
+            for (root, inputs) in roots {
+                if let Some(node) = graph.get_node_mut(root) {
+                    let inputs: Vec<String> = inputs.iter().map(ToString::to_string).collect();
+                    node.attributes
+                        .insert(CUSTOM_GATE_ATTRIBUTE.to_string(), gate.name().to_string());
+                    node.attributes
+                        .insert(CUSTOM_GATE_INPUTS_ATTRIBUTE.to_string(), inputs.join(","));
+                }
+            }
+        }
+        Ok(tagged)
+    }
+}
+
+impl Default for CustomGateSynthesis {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_OCCURRENCES)
+    }
+}
+
+/// Operands of a product only `consumer` reads, unless a side is constant and the
+/// standard gate already scales it for free
+fn private_product(graph: &IRGraph, node_id: usize, consumer: usize) -> Option<Vec<usize>> {
+    let node = graph.get_node(node_id)?;
+    if node.node_type != IRNodeType::Mul || graph.get_successors(node_id) != [consumer] {
+        return None;
+    }
+    let operands = graph.get_predecessors(node_id);
+    if operands.len() != 2 || operands.iter().any(|&operand| constant_value(graph, operand).is_some()) {
+        return None;
+    }
+    Some(operands)
+}
+
+fn match_pattern(graph: &IRGraph, booleans: &BooleanityAnalysis, node_id: usize) -> Option<(CustomGate, Vec<usize>)> {
+    let node = graph.get_node(node_id)?;
+    let operands = graph.get_predecessors(node_id);
+    match (&node.node_type, operands.as_slice()) {
+        (IRNodeType::Add, &[left, right]) => {
+            let products = (private_product(graph, left, node_id), private_product(graph, right, node_id));
+            match products {
+                (Some(mut first), Some(second)) if left != right => {
+                    first.extend(second);
+                    Some((CustomGate::DoubleMulAdd, first))
+                }
+                // A constant addend fits the standard gate's q_c
+                (Some(mut product), None) if constant_value(graph, right).is_none() => {
+                    product.push(right);
+                    Some((CustomGate::MulAdd, product))
+                }
+                (None, Some(mut product)) if constant_value(graph, left).is_none() => {
+                    product.push(left);
+                    Some((CustomGate::MulAdd, product))
+                }
+                _ => None,
+            }
+        }
+        (IRNodeType::Select, &[condition, _, _]) if booleans.is_boolean(condition) => {
+            Some((CustomGate::Select, operands))
+        }
+        _ => None,
+    }
+}
+
+impl OptimizationPass for CustomGateSynthesis {
+    fn name(&self) -> &str {
+        "customgates"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let booleans = analyses.booleanity(graph);
+        let tagged = self.run_with(graph, booleans)?;
+        for (gate, roots) in &tagged {
+            tracing::info!("{} pattern(s) become the custom gate '{}'", roots, gate.name());
+        }
+        self.last_rewrites = tagged.values().sum();
+        // Tagging roots does not change the circuit
+        Ok(false)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+
+    fn supports(&self, target: &TargetProfile) -> bool {
+        target.custom_gates
+    }
+}
@@ -0,0 +1,501 @@
+//! Function inlining over the call graph of a parsed program.
+//!
+//! Inlining happens on the AST, before the IR is built: a call that is inlined has the
+//! callee's body spliced in ahead of the calling statement, with the callee's locals
+//! renamed apart, and the call replaced by the variable holding the returned value.
+//! Calls that are not inlined are left for the backend to emit as a shared subcircuit.
+
+use crate::ir::assigned_variables;
+use crate::language::ast::{Expression, Function, Literal, Program, Statement};
+use crate::FCMCError;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Callees up to this many expression nodes are inlined at every call site
+pub const DEFAULT_MAX_INLINE_SIZE: usize = 32;
+
+/// Calls nested deeper than this through inlined bodies are left in place
+const MAX_INLINE_DEPTH: usize = 16;
+
+/// Per-function override, the equivalent of `#[inline(always)]` and `#[inline(never)]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineHint {
+    Always,
+    Never,
+}
+
+impl InlineHint {
+    /// Hint for an attribute such as `inline(always)`
+    pub fn from_attribute(attribute: &str) -> Option<Self> {
+        let compact: String = attribute.chars().filter(|c| !c.is_whitespace()).collect();
+        match compact.as_str() {
+            "inline(always)" => Some(InlineHint::Always),
+            "inline(never)" => Some(InlineHint::Never),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InlinePolicy {
+    max_size: usize,
+    hints: HashMap<String, InlineHint>,
+}
+
+impl Default for InlinePolicy {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_INLINE_SIZE,
+            hints: HashMap::new(),
+        }
+    }
+}
+
+impl InlinePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn set_hint(&mut self, function: &str, hint: InlineHint) {
+        self.hints.insert(function.to_string(), hint);
+    }
+
+    pub fn hint(&self, function: &str) -> Option<InlineHint> {
+        self.hints.get(function).copied()
+    }
+}
+
+/// Which functions call which, one entry per call site
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    calls: HashMap<String, Vec<String>>,
+}
+
+impl CallGraph {
+    pub fn from_program(program: &Program) -> Self {
+        let calls = program
+            .functions
+            .iter()
+            .map(|function| {
+                let mut callees = Vec::new();
+                for statement in &function.body {
+                    statement_calls(statement, &mut callees);
+                }
+                (function.name.clone(), callees)
+            })
+            .collect();
+        Self { calls }
+    }
+
+    pub fn callees(&self, function: &str) -> &[String] {
+        self.calls.get(function).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of call sites of `function` across the program
+    pub fn call_sites(&self, function: &str) -> usize {
+        self.calls.values().flatten().filter(|callee| *callee == function).count()
+    }
+
+    /// Whether `function` can reach itself through calls
+    pub fn is_recursive(&self, function: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<&str> = self.callees(function).iter().map(String::as_str).collect();
+        while let Some(current) = stack.pop() {
+            if current == function {
+                return true;
+            }
+            if visited.insert(current) {
+                stack.extend(self.callees(current).iter().map(String::as_str));
+            }
+        }
+        false
+    }
+}
+
+/// The outcome for one call site
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InlineDecision {
+    pub caller: String,
+    pub callee: String,
+    pub inlined: bool,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InlineReport {
+    pub decisions: Vec<InlineDecision>,
+    /// Functions dropped because every call to them was inlined
+    pub removed_functions: Vec<String>,
+}
+
+impl InlineReport {
+    pub fn inlined(&self) -> usize {
+        self.decisions.iter().filter(|decision| decision.inlined).count()
+    }
+
+    pub fn kept(&self) -> usize {
+        self.decisions.len() - self.inlined()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        self.decisions
+            .iter()
+            .map(|decision| {
+                format!(
+                    "{} call {} -> {} ({})",
+                    if decision.inlined { "inlined" } else { "kept" },
+                    decision.caller,
+                    decision.callee,
+                    decision.reason
+                )
+            })
+            .collect()
+    }
+}
+
+/// Inline the calls in `program` that `policy` selects
+pub fn inline_calls(program: &Program, policy: &InlinePolicy) -> Result<(Program, InlineReport), FCMCError> {
+    let graph = CallGraph::from_program(program);
+    for function in &program.functions {
+        if policy.hint(&function.name) == Some(InlineHint::Always) && graph.is_recursive(&function.name) {
+            return Err(FCMCError::SemanticError(format!(
+                "Recursive function '{}' cannot be marked #[inline(always)]",
+                function.name
+            )));
+        }
+    }
+
+    let mut inliner = Inliner {
+        functions: program.functions.iter().map(|f| (f.name.clone(), f)).collect(),
+        graph: &graph,
+        policy,
+        report: InlineReport::default(),
+        next_site: 0,
+    };
+
+    let mut result = program.clone();
+    for function in &mut result.functions {
+        let body = std::mem::take(&mut function.body);
+        function.body = inliner.inline_block(&function.name, body, 0);
+    }
+
+    // Callees whose every call was inlined no longer need their own circuit
+    let mut report = inliner.report;
+    let kept: HashSet<&str> = report
+        .decisions
+        .iter()
+        .filter(|decision| !decision.inlined)
+        .map(|decision| decision.callee.as_str())
+        .collect();
+    let removed: Vec<String> = program
+        .functions
+        .iter()
+        .map(|function| function.name.clone())
+        .filter(|name| {
+            *name != program.entry_point && graph.call_sites(name) > 0 && !kept.contains(name.as_str())
+        })
+        .collect();
+    result.functions.retain(|function| !removed.contains(&function.name));
+    report.removed_functions = removed;
+
+    Ok((result, report))
+}
+
+struct Inliner<'a> {
+    functions: HashMap<String, &'a Function>,
+    graph: &'a CallGraph,
+    policy: &'a InlinePolicy,
+    report: InlineReport,
+    next_site: usize,
+}
+
+impl Inliner<'_> {
+    fn inline_block(&mut self, caller: &str, block: Vec<Statement>, depth: usize) -> Vec<Statement> {
+        let mut result = Vec::new();
+        for mut statement in block {
+            let mut prelude = Vec::new();
+            match &mut statement {
+                Statement::Let { value: expr, .. }
+                | Statement::Return(expr)
+                | Statement::Assert(expr)
+                | Statement::Expression(expr) => self.inline_expr(caller, expr, &mut prelude, depth),
+                Statement::If { condition, then_branch, else_branch } => {
+                    self.inline_expr(caller, condition, &mut prelude, depth);
+                    *then_branch = self.inline_block(caller, std::mem::take(then_branch), depth);
+                    if let Some(else_branch) = else_branch {
+                        *else_branch = self.inline_block(caller, std::mem::take(else_branch), depth);
+                    }
+                }
+                Statement::For { start, end, body, .. } => {
+                    self.inline_expr(caller, start, &mut prelude, depth);
+                    self.inline_expr(caller, end, &mut prelude, depth);
+                    *body = self.inline_block(caller, std::mem::take(body), depth);
+                }
+            }
+            result.extend(prelude);
+            result.push(statement);
+        }
+        result
+    }
+
+    /// Replace inlinable calls in `expr`, innermost first, appending the callee bodies to `prelude`
+    fn inline_expr(&mut self, caller: &str, expr: &mut Expression, prelude: &mut Vec<Statement>, depth: usize) {
+        match expr {
+            Expression::Binary { left, right, .. } => {
+                self.inline_expr(caller, left, prelude, depth);
+                self.inline_expr(caller, right, prelude, depth);
+            }
+            Expression::Unary { expr, .. } => self.inline_expr(caller, expr, prelude, depth),
+            Expression::Assignment(_, value) => self.inline_expr(caller, value, prelude, depth),
+            Expression::Array(elements) => {
+                for element in elements {
+                    self.inline_expr(caller, element, prelude, depth);
+                }
+            }
+            Expression::FunctionCall { name, args } => {
+                for arg in args.iter_mut() {
+                    self.inline_expr(caller, arg, prelude, depth);
+                }
+                let callee = match self.functions.get(name.as_str()) {
+                    Some(&callee) => callee,
+                    // Builtins and gadgets are lowered by the IR builder
+                    None => return,
+                };
+
+                let (inlined, reason) = self.decide(callee, depth);
+                self.report.decisions.push(InlineDecision {
+                    caller: caller.to_string(),
+                    callee: callee.name.clone(),
+                    inlined,
+                    reason,
+                });
+                if inlined {
+                    let args = std::mem::take(args);
+                    let (body, result) = self.instantiate(callee, args);
+                    prelude.extend(self.inline_block(caller, body, depth + 1));
+                    *expr = result;
+                }
+            }
+            Expression::Literal(_) | Expression::Variable(_) => {}
+        }
+    }
+
+    fn decide(&self, callee: &Function, depth: usize) -> (bool, &'static str) {
+        if depth >= MAX_INLINE_DEPTH {
+            return (false, "inline depth limit");
+        }
+        if self.graph.is_recursive(&callee.name) {
+            return (false, "recursive");
+        }
+        if !returns_last(&callee.body) {
+            return (false, "returns early");
+        }
+        match self.policy.hint(&callee.name) {
+            Some(InlineHint::Always) => (true, "#[inline(always)]"),
+            Some(InlineHint::Never) => (false, "#[inline(never)]"),
+            None if self.graph.call_sites(&callee.name) == 1 => (true, "single call site"),
+            None if block_size(&callee.body) <= self.policy.max_size => (true, "small body"),
+            None => (false, "body too large"),
+        }
+    }
+
+    /// Callee body with locals renamed apart and parameters bound to `args`, and the
+    /// expression standing for the call's value
+    fn instantiate(&mut self, callee: &Function, args: Vec<Expression>) -> (Vec<Statement>, Expression) {
+        let site = self.next_site;
+        self.next_site += 1;
+
+        let mut locals = assigned_variables(&callee.body);
+        locals.extend(callee.params.iter().map(|(name, _)| name.clone()));
+        let renames: HashMap<String, String> = locals
+            .into_iter()
+            .map(|name| {
+                let renamed = format!("__inline{}_{}_{}", site, callee.name, name);
+                (name, renamed)
+            })
+            .collect();
+
+        let mut body: Vec<Statement> = callee
+            .params
+            .iter()
+            .zip(args)
+            .map(|((name, param_type), value)| Statement::Let {
+                name: renames[name].clone(),
+                var_type: Some(param_type.clone()),
+                value,
+            })
+            .collect();
+        body.extend(callee.body.iter().cloned());
+        for statement in &mut body[callee.params.len()..] {
+            rename_statement(statement, &renames);
+        }
+
+        let result = match body.pop() {
+            Some(Statement::Return(value)) => value,
+            Some(other) => {
+                // Unit functions are called for their assertions only
+                body.push(other);
+                Expression::Literal(Literal::Number("0".to_string()))
+            }
+            None => Expression::Literal(Literal::Number("0".to_string())),
+        };
+        (body, result)
+    }
+}
+
+/// The body returns only from its last statement, if at all
+fn returns_last(body: &[Statement]) -> bool {
+    let (last, rest) = match body.split_last() {
+        Some(split) => split,
+        None => return true,
+    };
+    !rest.iter().any(contains_return) && (matches!(last, Statement::Return(_)) || !contains_return(last))
+}
+
+fn contains_return(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_) => true,
+        Statement::If { then_branch, else_branch, .. } => {
+            then_branch.iter().chain(else_branch.iter().flatten()).any(contains_return)
+        }
+        Statement::For { body, .. } => body.iter().any(contains_return),
+        Statement::Let { .. } | Statement::Assert(_) | Statement::Expression(_) => false,
+    }
+}
+
+fn rename_statement(statement: &mut Statement, renames: &HashMap<String, String>) {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            rename(name, renames);
+            rename_expr(value, renames);
+        }
+        Statement::If { condition, then_branch, else_branch } => {
+            rename_expr(condition, renames);
+            for statement in then_branch.iter_mut().chain(else_branch.iter_mut().flatten()) {
+                rename_statement(statement, renames);
+            }
+        }
+        Statement::For { var_name, start, end, body } => {
+            rename(var_name, renames);
+            rename_expr(start, renames);
+            rename_expr(end, renames);
+            for statement in body {
+                rename_statement(statement, renames);
+            }
+        }
+        Statement::Return(expr) | Statement::Assert(expr) | Statement::Expression(expr) => {
+            rename_expr(expr, renames);
+        }
+    }
+}
+
+fn rename_expr(expr: &mut Expression, renames: &HashMap<String, String>) {
+    match expr {
+        Expression::Variable(name) => rename(name, renames),
+        Expression::Assignment(target, value) => {
+            rename_expr(target, renames);
+            rename_expr(value, renames);
+        }
+        Expression::Binary { left, right, .. } => {
+            rename_expr(left, renames);
+            rename_expr(right, renames);
+        }
+        Expression::Unary { expr, .. } => rename_expr(expr, renames),
+        Expression::FunctionCall { args, .. } => {
+            for arg in args {
+                rename_expr(arg, renames);
+            }
+        }
+        Expression::Array(elements) => {
+            for element in elements {
+                rename_expr(element, renames);
+            }
+        }
+        Expression::Literal(_) => {}
+    }
+}
+
+fn rename(name: &mut String, renames: &HashMap<String, String>) {
+    if let Some(renamed) = renames.get(name.as_str()) {
+        *name = renamed.clone();
+    }
+}
+
+fn statement_calls(statement: &Statement, calls: &mut Vec<String>) {
+    match statement {
+        Statement::Let { value: expr, .. }
+        | Statement::Return(expr)
+        | Statement::Assert(expr)
+        | Statement::Expression(expr) => expr_calls(expr, calls),
+        Statement::If { condition, then_branch, else_branch } => {
+            expr_calls(condition, calls);
+            for statement in then_branch.iter().chain(else_branch.iter().flatten()) {
+                statement_calls(statement, calls);
+            }
+        }
+        Statement::For { start, end, body, .. } => {
+            expr_calls(start, calls);
+            expr_calls(end, calls);
+            for statement in body {
+                statement_calls(statement, calls);
+            }
+        }
+    }
+}
+
+fn expr_calls(expr: &Expression, calls: &mut Vec<String>) {
+    match expr {
+        Expression::FunctionCall { name, args } => {
+            calls.push(name.clone());
+            for arg in args {
+                expr_calls(arg, calls);
+            }
+        }
+        Expression::Binary { left, right, .. } | Expression::Assignment(left, right) => {
+            expr_calls(left, calls);
+            expr_calls(right, calls);
+        }
+        Expression::Unary { expr, .. } => expr_calls(expr, calls),
+        Expression::Array(elements) => {
+            for element in elements {
+                expr_calls(element, calls);
+            }
+        }
+        Expression::Literal(_) | Expression::Variable(_) => {}
+    }
+}
+
+/// Expression nodes in a block, the size measure of the inlining heuristic
+fn block_size(block: &[Statement]) -> usize {
+    block.iter().map(statement_size).sum()
+}
+
+fn statement_size(statement: &Statement) -> usize {
+    match statement {
+        Statement::Let { value: expr, .. }
+        | Statement::Return(expr)
+        | Statement::Assert(expr)
+        | Statement::Expression(expr) => expr_size(expr),
+        Statement::If { condition, then_branch, else_branch } => {
+            expr_size(condition) + block_size(then_branch) + else_branch.as_deref().map_or(0, block_size)
+        }
+        Statement::For { start, end, body, .. } => expr_size(start) + expr_size(end) + block_size(body),
+    }
+}
+
+fn expr_size(expr: &Expression) -> usize {
+    1 + match expr {
+        Expression::Binary { left, right, .. } | Expression::Assignment(left, right) => {
+            expr_size(left) + expr_size(right)
+        }
+        Expression::Unary { expr, .. } => expr_size(expr),
+        Expression::FunctionCall { args, .. } => args.iter().map(expr_size).sum(),
+        Expression::Array(elements) => elements.iter().map(expr_size).sum(),
+        Expression::Literal(_) | Expression::Variable(_) => 0,
+    }
+}
@@ -0,0 +1,254 @@
+//! Reference evaluator for the IR over the scalar field
+
+use crate::ir::{ConstraintType, IRGraph, IRNode, IRNodeType};
+use crate::optimization::field;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use std::collections::HashMap;
+
+/// Attribute turning a `BitDecomposition` node into bit `index` of its operand's canonical
+/// integer value, rather than the operand itself
+pub const BIT_ATTRIBUTE: &str = "bit";
+
+/// Index of the bit a `BitDecomposition` node extracts, if it extracts one
+pub fn extracted_bit(node: &IRNode) -> Option<u32> {
+    match node.node_type {
+        IRNodeType::BitDecomposition => node.attributes.get(BIT_ATTRIBUTE)?.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Result of evaluating every live node of a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub values: HashMap<usize, BigUint>,
+    /// Output name and value, in node order
+    pub outputs: Vec<(String, BigUint)>,
+    /// Constraint-like nodes that do not hold
+    pub violated: Vec<usize>,
+}
+
+impl Evaluation {
+    pub fn is_satisfied(&self) -> bool {
+        self.violated.is_empty()
+    }
+
+    pub fn output(&self, name: &str) -> Option<&BigUint> {
+        self.outputs
+            .iter()
+            .find(|(output, _)| output == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Names of all inputs, public and private, in node order
+pub fn input_names(graph: &IRGraph) -> Vec<String> {
+    graph
+        .live_nodes()
+        .filter_map(|node| match &node.node_type {
+            IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn evaluate(graph: &IRGraph, inputs: &HashMap<String, BigUint>) -> Result<Evaluation, FCMCError> {
+    let mut values: HashMap<usize, BigUint> = HashMap::new();
+    let mut outputs = Vec::new();
+    let mut violated = Vec::new();
+
+    for node_id in graph.topological_sort() {
+        let node = match graph.get_node(node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+
+        let operands: Vec<BigUint> = graph
+            .get_predecessors(node_id)
+            .iter()
+            .map(|operand| values.get(operand).cloned().unwrap_or_else(BigUint::zero))
+            .collect();
+        let arg = |index: usize| operands.get(index).cloned().unwrap_or_else(BigUint::zero);
+
+        let value = match &node.node_type {
+            IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => match inputs.get(name) {
+                Some(value) => value % field::modulus(),
+                None => {
+                    return Err(FCMCError::VerificationError(format!("Missing value for input '{}'", name)));
+                }
+            },
+            IRNodeType::Output(name) => {
+                let value = arg(0);
+                outputs.push((name.clone(), value.clone()));
+                value
+            }
+            IRNodeType::Constraint(constraint) => {
+                if !constraint_holds(constraint, &operands) {
+                    violated.push(node_id);
+                }
+                BigUint::zero()
+            }
+            IRNodeType::Lookup => match table_lookup(graph, node, &operands) {
+                Some(Some(value)) => value,
+                // Index outside the table: the lookup argument fails
+                Some(None) => {
+                    violated.push(node_id);
+                    BigUint::zero()
+                }
+                None => {
+                    if !bits_hold(node, &operands) {
+                        violated.push(node_id);
+                    }
+                    BigUint::zero()
+                }
+            },
+            IRNodeType::RangeCheck => {
+                if !bits_hold(node, &operands) {
+                    violated.push(node_id);
+                }
+                BigUint::zero()
+            }
+            node_type @ IRNodeType::BitDecomposition => match extracted_bit(node) {
+                Some(index) => (arg(0) >> index) & BigUint::one(),
+                None => apply(node_type, &operands)?,
+            },
+            node_type => apply(node_type, &operands).map_err(|e| match e {
+                FCMCError::VerificationError(message) => {
+                    FCMCError::VerificationError(format!("{} at node {}", message, node_id))
+                }
+                other => other,
+            })?,
+        };
+
+        values.insert(node_id, value);
+    }
+
+    Ok(Evaluation {
+        values,
+        outputs,
+        violated,
+    })
+}
+
+/// Value of a side-effect-free node applied to its operand values
+pub(crate) fn apply(node_type: &IRNodeType, operands: &[BigUint]) -> Result<BigUint, FCMCError> {
+    let arg = |index: usize| operands.get(index).cloned().unwrap_or_else(BigUint::zero);
+
+    Ok(match node_type {
+        IRNodeType::Constant(value) => field::parse_element(value)
+            .ok_or_else(|| FCMCError::VerificationError(format!("Invalid field constant: {}", value)))?,
+        IRNodeType::Add => field::add(&arg(0), &arg(1)),
+        IRNodeType::Sub => field::sub(&arg(0), &arg(1)),
+        IRNodeType::Mul | IRNodeType::And => field::mul(&arg(0), &arg(1)),
+        IRNodeType::Neg => field::neg(&arg(0)),
+        IRNodeType::Div => match field::inverse(&arg(1)) {
+            Some(inverse) => field::mul(&arg(0), &inverse),
+            None => return Err(FCMCError::VerificationError("Division by zero".to_string())),
+        },
+        // a + b - ab
+        IRNodeType::Or => {
+            let (a, b) = (arg(0), arg(1));
+            field::sub(&field::add(&a, &b), &field::mul(&a, &b))
+        }
+        // a + b - 2ab
+        IRNodeType::Xor => {
+            let (a, b) = (arg(0), arg(1));
+            let ab = field::mul(&a, &b);
+            field::sub(&field::add(&a, &b), &field::add(&ab, &ab))
+        }
+        IRNodeType::Not => field::sub(&BigUint::one(), &arg(0)),
+        IRNodeType::Eq => bool_value(arg(0) == arg(1)),
+        IRNodeType::Ne => bool_value(arg(0) != arg(1)),
+        IRNodeType::Lt => bool_value(arg(0) < arg(1)),
+        IRNodeType::Le => bool_value(arg(0) <= arg(1)),
+        IRNodeType::Gt => bool_value(arg(0) > arg(1)),
+        IRNodeType::Ge => bool_value(arg(0) >= arg(1)),
+        IRNodeType::Select => {
+            if arg(0).is_zero() {
+                arg(2)
+            } else {
+                arg(1)
+            }
+        }
+        IRNodeType::Phi | IRNodeType::BitDecomposition | IRNodeType::Output(_) => arg(0),
+        IRNodeType::Input(_)
+        | IRNodeType::PrivateInput(_)
+        | IRNodeType::Constraint(_)
+        | IRNodeType::RangeCheck
+        | IRNodeType::Lookup => {
+            return Err(FCMCError::VerificationError(format!("{:?} has no pure value", node_type)));
+        }
+    })
+}
+
+/// Row of a function table selected by `operands`; `None` if the node uses no registered
+/// table, `Some(None)` if the operands fall outside the table
+fn table_lookup(graph: &IRGraph, node: &IRNode, operands: &[BigUint]) -> Option<Option<BigUint>> {
+    let table = graph.table(node.attributes.get("table")?)?;
+
+    let mut index = BigUint::zero();
+    let mut offset = 0;
+    for (value, &bits) in operands.iter().zip(&table.input_bits) {
+        if value.bits() > bits as u64 {
+            return Some(None);
+        }
+        index += value << offset;
+        offset += bits;
+    }
+
+    let row = index.to_usize()?;
+    Some(table.outputs.get(row).and_then(|value| field::parse_element(value)))
+}
+
+fn bits_hold(node: &IRNode, operands: &[BigUint]) -> bool {
+    match node.attributes.get("bits").and_then(|bits| bits.parse::<u64>().ok()) {
+        Some(bits) => operands.iter().all(|value| value.bits() <= bits),
+        // Tables without a known semantics are trusted
+        None => true,
+    }
+}
+
+fn constraint_holds(constraint: &ConstraintType, operands: &[BigUint]) -> bool {
+    match (constraint, operands) {
+        (ConstraintType::Equality, [value]) => value.is_one(),
+        (ConstraintType::Equality, [a, b]) => a == b,
+        (ConstraintType::Inequality, [value]) => !value.is_zero(),
+        (ConstraintType::Inequality, [a, b]) => a != b,
+        (ConstraintType::Range { bits }, [value]) => value.bits() <= *bits as u64,
+        (ConstraintType::Polynomial { coefficients }, [x]) => {
+            let mut sum = BigUint::zero();
+            let mut power = BigUint::one();
+            for coefficient in coefficients {
+                let coefficient = match field::parse_element(coefficient) {
+                    Some(coefficient) => coefficient,
+                    None => return false,
+                };
+                sum = field::add(&sum, &field::mul(&coefficient, &power));
+                power = field::mul(&power, x);
+            }
+            sum.is_zero()
+        }
+        // Shapes the interpreter does not model are trusted
+        _ => true,
+    }
+}
+
+fn bool_value(value: bool) -> BigUint {
+    if value {
+        BigUint::one()
+    } else {
+        BigUint::zero()
+    }
+}
+
+/// Panic unless `before` and `after` give every output the same value, and agree on
+/// whether their constraints hold, for each assignment in `inputs`
+#[cfg(test)]
+pub(crate) fn assert_equivalent(before: &IRGraph, after: &IRGraph, inputs: &[HashMap<String, BigUint>]) {
+    for assignment in inputs {
+        let (expected, actual) = (evaluate(before, assignment).unwrap(), evaluate(after, assignment).unwrap());
+        assert_eq!(actual.outputs, expected.outputs, "outputs for {:?}", assignment);
+        assert_eq!(actual.is_satisfied(), expected.is_satisfied(), "satisfaction for {:?}", assignment);
+    }
+}
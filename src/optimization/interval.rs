@@ -0,0 +1,311 @@
+//! Interval analysis over IR values and elision of range checks it proves redundant.
+//!
+//! Every wire gets an integer interval within `[0, p)` from constants, booleans, range
+//! checks on its operands, and arithmetic on known ranges that cannot wrap modulo `p`.
+//! A wire constrained equal to a value whose range follows from the graph's structure
+//! alone, such as bits recomposed by a decomposition or the limbs and carry of a limb
+//! sum, takes that range too. A range check is redundant when the interval derived for
+//! its wire without that check already fits; on `u32` arithmetic this proves the
+//! operation cannot overflow, and a limb that is decomposed anyway needs no separate check.
+
+use crate::ir::{ConstraintType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::constant_value;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::range_check::range_checks;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::HashMap;
+
+/// Closed interval of field elements read as integers in `[0, p)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    pub lo: BigUint,
+    pub hi: BigUint,
+}
+
+impl Interval {
+    pub fn exact(value: BigUint) -> Self {
+        Self {
+            lo: value.clone(),
+            hi: value,
+        }
+    }
+
+    pub fn boolean() -> Self {
+        Self {
+            lo: BigUint::zero(),
+            hi: BigUint::one(),
+        }
+    }
+
+    /// Every value representable in `bits` bits
+    pub fn bits(bits: u32) -> Self {
+        Self {
+            lo: BigUint::zero(),
+            hi: (BigUint::one() << bits) - BigUint::one(),
+        }
+    }
+
+    pub fn full() -> Self {
+        Self {
+            lo: BigUint::zero(),
+            hi: field::modulus() - BigUint::one(),
+        }
+    }
+
+    pub fn fits_in_bits(&self, bits: u32) -> bool {
+        self.hi.bits() <= bits as u64
+    }
+
+    /// `None` if some value would reach the modulus and wrap
+    fn checked(lo: BigUint, hi: BigUint) -> Option<Self> {
+        (hi < field::modulus()).then(|| Self { lo, hi })
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        Self::checked(&self.lo + &other.lo, &self.hi + &other.hi)
+    }
+
+    fn sub(&self, other: &Self) -> Option<Self> {
+        (self.lo >= other.hi).then(|| Self {
+            lo: &self.lo - &other.hi,
+            hi: &self.hi - &other.lo,
+        })
+    }
+
+    fn mul(&self, other: &Self) -> Option<Self> {
+        Self::checked(&self.lo * &other.lo, &self.hi * &other.hi)
+    }
+
+    fn hull(&self, other: &Self) -> Self {
+        Self {
+            lo: (&self.lo).min(&other.lo).clone(),
+            hi: (&self.hi).max(&other.hi).clone(),
+        }
+    }
+
+    fn meet(&self, other: &Self) -> Self {
+        Self {
+            lo: (&self.lo).max(&other.lo).clone(),
+            hi: (&self.hi).min(&other.hi).clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// Known ranges of every wire
+#[derive(Debug, Clone, Default)]
+pub struct IntervalAnalysis {
+    /// Range derived from a wire's operands and the equalities constraining it
+    derived: HashMap<usize, Interval>,
+    /// Derived range narrowed by the range checks on the wire
+    known: HashMap<usize, Interval>,
+}
+
+impl IntervalAnalysis {
+    pub fn run(graph: &IRGraph, booleans: &BooleanityAnalysis) -> Self {
+        let mut checked: HashMap<usize, u32> = HashMap::new();
+        for (_, wire, bits) in range_checks(graph) {
+            let tightest = checked.entry(wire).or_insert(bits);
+            *tightest = (*tightest).min(bits);
+        }
+
+        // Ranges for equalities come from a pass that ignores every check, so a range
+        // check is never proven redundant by a range that rests on itself
+        let structural = Self::propagate(graph, booleans, &HashMap::new(), &HashMap::new());
+        let mut equal: HashMap<usize, Interval> = HashMap::new();
+        for (left, right) in equalities(graph) {
+            for (wire, other) in [(left, right), (right, left)] {
+                let range = structural.range(other);
+                let bound = equal.entry(wire).or_insert_with(Interval::full);
+                *bound = bound.meet(&range);
+            }
+        }
+        Self::propagate(graph, booleans, &checked, &equal)
+    }
+
+    fn propagate(
+        graph: &IRGraph,
+        booleans: &BooleanityAnalysis,
+        checked: &HashMap<usize, u32>,
+        equal: &HashMap<usize, Interval>,
+    ) -> Self {
+        let mut analysis = Self::default();
+        for node_id in graph.topological_sort() {
+            let mut derived = analysis.derive(graph, booleans, node_id);
+            if let Some(range) = equal.get(&node_id) {
+                derived = derived.meet(range);
+            }
+            let known = match checked.get(&node_id) {
+                Some(&bits) => derived.meet(&Interval::bits(bits)),
+                None => derived.clone(),
+            };
+            analysis.derived.insert(node_id, derived);
+            analysis.known.insert(node_id, known);
+        }
+        analysis
+    }
+
+    /// Range of `node_id` taking every constraint into account
+    pub fn range(&self, node_id: usize) -> Interval {
+        self.known.get(&node_id).cloned().unwrap_or_else(Interval::full)
+    }
+
+    /// Range of `node_id` without the range checks placed on it
+    pub fn derived_range(&self, node_id: usize) -> Interval {
+        self.derived.get(&node_id).cloned().unwrap_or_else(Interval::full)
+    }
+
+    fn derive(&self, graph: &IRGraph, booleans: &BooleanityAnalysis, node_id: usize) -> Interval {
+        if let Some(value) = constant_value(graph, node_id) {
+            return Interval::exact(value);
+        }
+        if booleans.is_boolean(node_id) {
+            return Interval::boolean();
+        }
+
+        let operands: Vec<Interval> = graph
+            .get_predecessors(node_id)
+            .into_iter()
+            .map(|operand| self.range(operand))
+            .collect();
+        let derived = match (graph.get_node(node_id).map(|node| &node.node_type), operands.as_slice()) {
+            (Some(IRNodeType::Add), [a, b]) => a.add(b),
+            (Some(IRNodeType::Sub), [a, b]) => a.sub(b),
+            (Some(IRNodeType::Mul), [a, b]) => a.mul(b),
+            (Some(IRNodeType::Select), [_, a, b]) => Some(a.hull(b)),
+            (Some(IRNodeType::Eq), _)
+            | (Some(IRNodeType::Ne), _)
+            | (Some(IRNodeType::Lt), _)
+            | (Some(IRNodeType::Le), _)
+            | (Some(IRNodeType::Gt), _)
+            | (Some(IRNodeType::Ge), _) => Some(Interval::boolean()),
+            (Some(IRNodeType::Phi), [a, ..]) | (Some(IRNodeType::Output(_)), [a]) => Some(a.clone()),
+            _ => None,
+        };
+        derived.unwrap_or_else(Interval::full)
+    }
+}
+
+/// Wire pairs of the two-operand equality constraints
+fn equalities(graph: &IRGraph) -> Vec<(usize, usize)> {
+    graph
+        .live_nodes()
+        .filter(|node| matches!(node.node_type, IRNodeType::Constraint(ConstraintType::Equality)))
+        .filter_map(|node| match graph.get_predecessors(node.id).as_slice() {
+            &[left, right] => Some((left, right)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// What `RangeCheckElision` removed and what it could not prove
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntervalReport {
+    /// Range checks removed, as (checked wire, bits, proven range)
+    pub range_checks: Vec<(usize, u32, Interval)>,
+    /// Overflow checks on `u32` arithmetic removed because the operation cannot wrap
+    pub overflow_checks: Vec<(usize, Interval)>,
+    /// `u32` arithmetic whose result is not proven below 2^32
+    pub may_wrap: Vec<usize>,
+}
+
+impl IntervalReport {
+    pub fn elided(&self) -> usize {
+        self.range_checks.len() + self.overflow_checks.len()
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (wire, bits, range) in &self.range_checks {
+            lines.push(format!("elided {}-bit range check on node {}: value in {}", bits, wire, range));
+        }
+        for (wire, range) in &self.overflow_checks {
+            lines.push(format!("u32 arithmetic at node {} cannot wrap: value in {}", wire, range));
+        }
+        for wire in &self.may_wrap {
+            lines.push(format!("u32 arithmetic at node {} may wrap", wire));
+        }
+        lines
+    }
+}
+
+/// Removes range and overflow checks implied by interval analysis
+#[derive(Debug, Default)]
+pub struct RangeCheckElision {
+    last_rewrites: usize,
+}
+
+impl RangeCheckElision {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<IntervalReport, FCMCError> {
+        let booleans = BooleanityAnalysis::run(graph);
+        self.run_with(graph, &booleans)
+    }
+
+    /// Run with a precomputed booleanity analysis of `graph`
+    pub fn run_with(&self, graph: &mut IRGraph, booleans: &BooleanityAnalysis) -> Result<IntervalReport, FCMCError> {
+        let analysis = IntervalAnalysis::run(graph, booleans);
+        let mut report = IntervalReport::default();
+
+        let mut to_remove = Vec::new();
+        for (constraint_id, wire, bits) in range_checks(graph) {
+            let range = analysis.derived_range(wire);
+            if !range.fits_in_bits(bits) {
+                continue;
+            }
+            if is_u32_arithmetic(graph, wire) && bits == 32 {
+                report.overflow_checks.push((wire, range));
+            } else {
+                report.range_checks.push((wire, bits, range));
+            }
+            to_remove.push(constraint_id);
+        }
+
+        for node in graph.live_nodes() {
+            if is_u32_arithmetic(graph, node.id) && !analysis.range(node.id).fits_in_bits(32) {
+                report.may_wrap.push(node.id);
+            }
+        }
+
+        for constraint_id in to_remove {
+            graph.remove_node(constraint_id);
+        }
+        Ok(report)
+    }
+}
+
+fn is_u32_arithmetic(graph: &IRGraph, node_id: usize) -> bool {
+    graph.get_node(node_id).map_or(false, |node| {
+        matches!(node.data_type, Type::U32)
+            && matches!(node.node_type, IRNodeType::Add | IRNodeType::Sub | IRNodeType::Mul)
+    })
+}
+
+impl OptimizationPass for RangeCheckElision {
+    fn name(&self) -> &str {
+        "intervals"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let booleans = analyses.booleanity(graph);
+        let report = self.run_with(graph, booleans)?;
+        for line in report.summary() {
+            tracing::debug!("{}", line);
+        }
+        self.last_rewrites = report.elided();
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
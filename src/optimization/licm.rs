@@ -0,0 +1,7 @@
+//! Loop-invariant code motion.
+//!
+//! Loops are unrolled while the IR is built, so hoisting has to happen there: the
+//! builder's `LoopContext` tells which expressions of a loop body are invariant and
+//! emits them once, ahead of the first iteration, instead of once per iteration.
+
+pub use crate::ir::{assigned_variables, is_loop_invariant, LoopContext};
@@ -0,0 +1,421 @@
+//! Lookup-table conversion.
+//!
+//! Arithmetic that only depends on a handful of small-domain wires (S-boxes, byte-wise
+//! XOR, ...) computes a fixed function of those wires, so on targets with lookup arguments
+//! it can be replaced by one lookup into a generated table. Targets without lookups get
+//! such tables lowered back to arithmetic by interpolating the table.
+
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNode, IRNodeType, LookupTable};
+use crate::language::ast::Type;
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::cost::CostModel;
+use crate::optimization::field;
+use crate::optimization::interpreter;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::range_check::range_checks;
+use crate::optimization::target::TargetProfile;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::{BTreeSet, HashMap};
+
+/// Node attribute naming the table a `Lookup` node reads
+pub const TABLE_ATTRIBUTE: &str = "table";
+
+/// Largest table, in index bits, the conversion pass generates
+pub const MAX_TABLE_BITS: u32 = 16;
+
+/// Largest table, in index bits, lowered to a polynomial on targets without lookups
+pub const MAX_LOWERED_TABLE_BITS: u32 = 8;
+
+/// Wires known to hold values of at most the given number of bits
+pub fn small_domain_wires(graph: &IRGraph, booleans: &BooleanityAnalysis) -> HashMap<usize, u32> {
+    let mut bits: HashMap<usize, u32> = HashMap::new();
+    let mut narrow = |wire: usize, width: u32| {
+        let entry = bits.entry(wire).or_insert(width);
+        *entry = (*entry).min(width);
+    };
+
+    for wire in booleans.boolean_wires() {
+        narrow(wire, 1);
+    }
+    for (_, wire, width) in range_checks(graph) {
+        narrow(wire, width);
+    }
+    // Range checks already merged into range-table lookups
+    for node in graph.live_nodes() {
+        if node.node_type != IRNodeType::Lookup || function_table(graph, node).is_some() {
+            continue;
+        }
+        if let Some(width) = node.attributes.get("bits").and_then(|bits| bits.parse::<u32>().ok()) {
+            for wire in graph.get_predecessors(node.id) {
+                narrow(wire, width);
+            }
+        }
+    }
+
+    bits
+}
+
+/// The generated table a `Lookup` node reads, if it is a function lookup rather than a range check
+pub fn function_table<'a>(graph: &'a IRGraph, node: &IRNode) -> Option<&'a LookupTable> {
+    graph.table(node.attributes.get(TABLE_ATTRIBUTE)?)
+}
+
+/// Small-domain wires a node depends on, and the approximate cost of computing it from them
+#[derive(Debug, Clone)]
+struct Cone {
+    support: BTreeSet<usize>,
+    cost: u64,
+}
+
+/// Replaces fixed functions of small-domain wires with table lookups
+pub struct LookupConversion {
+    cost: CostModel,
+    max_table_bits: u32,
+    last_rewrites: usize,
+}
+
+impl LookupConversion {
+    pub fn new(cost: CostModel) -> Self {
+        Self {
+            cost,
+            max_table_bits: MAX_TABLE_BITS,
+            last_rewrites: 0,
+        }
+    }
+
+    pub fn with_max_table_bits(mut self, bits: u32) -> Self {
+        self.max_table_bits = bits;
+        self
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let booleans = BooleanityAnalysis::run(graph);
+        self.run_with(graph, &booleans)
+    }
+
+    /// Run with a precomputed booleanity analysis of `graph`; returns the number of lookups created
+    pub fn run_with(&self, graph: &mut IRGraph, booleans: &BooleanityAnalysis) -> Result<usize, FCMCError> {
+        let small = small_domain_wires(graph, booleans);
+        let order = graph.topological_sort();
+
+        let mut cones: HashMap<usize, Cone> = HashMap::new();
+        for &node_id in &order {
+            if let Some(cone) = self.cone(graph, &small, &cones, node_id) {
+                cones.insert(node_id, cone);
+            }
+        }
+
+        let is_candidate = |node_id: usize| {
+            !small.contains_key(&node_id)
+                && cones.get(&node_id).map_or(false, |cone| {
+                    !cone.support.is_empty() && cone.cost > self.cost.mul as u64
+                })
+        };
+
+        // Convert the largest cones: candidates used by something that is not itself a candidate
+        let roots: Vec<usize> = order
+            .iter()
+            .copied()
+            .filter(|&node_id| is_candidate(node_id))
+            .filter(|&node_id| graph.get_successors(node_id).into_iter().any(|s| !is_candidate(s)))
+            .collect();
+
+        let mut converted = 0;
+        for root in roots {
+            let support: Vec<usize> = cones[&root].support.iter().copied().collect();
+            let input_bits: Vec<u32> = support.iter().map(|wire| small[wire]).collect();
+            let outputs = match tabulate(graph, root, &support, &input_bits) {
+                Ok(outputs) => outputs,
+                // The function is undefined somewhere in the domain (e.g. division by zero)
+                Err(_) => continue,
+            };
+
+            let name = graph.add_table(LookupTable {
+                name: format!("fn-{}", graph.tables().len()),
+                input_bits,
+                outputs,
+            });
+
+            let data_type = match graph.get_node(root) {
+                Some(node) => node.data_type.clone(),
+                None => continue,
+            };
+            let lookup = graph.add_node(IRNodeType::Lookup, data_type, None);
+            if let Some(node) = graph.get_node_mut(lookup) {
+                node.attributes.insert(TABLE_ATTRIBUTE.to_string(), name);
+            }
+            for &wire in &support {
+                graph.add_edge(wire, lookup, EdgeType::DataFlow);
+            }
+            graph.replace_uses(root, lookup);
+            converted += 1;
+        }
+
+        Ok(converted)
+    }
+
+    fn cone(
+        &self,
+        graph: &IRGraph,
+        small: &HashMap<usize, u32>,
+        cones: &HashMap<usize, Cone>,
+        node_id: usize,
+    ) -> Option<Cone> {
+        if small.contains_key(&node_id) {
+            return Some(Cone {
+                support: BTreeSet::from([node_id]),
+                cost: 0,
+            });
+        }
+
+        let node = graph.get_node(node_id)?;
+        let unit = match node.node_type {
+            IRNodeType::Constant(_) => {
+                return Some(Cone {
+                    support: BTreeSet::new(),
+                    cost: 0,
+                })
+            }
+            IRNodeType::Add | IRNodeType::Sub => self.cost.add,
+            IRNodeType::Neg => self.cost.const_mul,
+            IRNodeType::Div => self.cost.div,
+            IRNodeType::Mul
+            | IRNodeType::And
+            | IRNodeType::Or
+            | IRNodeType::Xor
+            | IRNodeType::Not
+            | IRNodeType::Eq
+            | IRNodeType::Ne
+            | IRNodeType::Lt
+            | IRNodeType::Le
+            | IRNodeType::Gt
+            | IRNodeType::Ge
+            | IRNodeType::Select => self.cost.mul,
+            _ => return None,
+        };
+
+        let mut cone = Cone {
+            support: BTreeSet::new(),
+            cost: unit as u64,
+        };
+        for operand in graph.get_predecessors(node_id) {
+            let operand_cone = cones.get(&operand)?;
+            cone.support.extend(operand_cone.support.iter().copied());
+            cone.cost += operand_cone.cost;
+        }
+
+        let bits: u32 = cone.support.iter().map(|wire| small[wire]).sum();
+        if bits > self.max_table_bits {
+            return None;
+        }
+        Some(cone)
+    }
+}
+
+impl OptimizationPass for LookupConversion {
+    fn name(&self) -> &str {
+        "lookups"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let booleans = analyses.booleanity(graph);
+        self.last_rewrites = self.run_with(graph, booleans)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+
+    fn supports(&self, target: &TargetProfile) -> bool {
+        target.lookups
+    }
+}
+
+/// Evaluate the cone of `root` on every assignment of its support wires
+fn tabulate(graph: &IRGraph, root: usize, support: &[usize], input_bits: &[u32]) -> Result<Vec<String>, FCMCError> {
+    let index_bits: u32 = input_bits.iter().sum();
+    let mut outputs = Vec::with_capacity(1 << index_bits);
+
+    for index in 0u64..(1u64 << index_bits) {
+        let mut leaves = HashMap::new();
+        let mut offset = 0;
+        for (&wire, &bits) in support.iter().zip(input_bits) {
+            let value = (index >> offset) & ((1u64 << bits) - 1);
+            leaves.insert(wire, BigUint::from(value));
+            offset += bits;
+        }
+
+        let mut memo = HashMap::new();
+        outputs.push(evaluate_cone(graph, root, &leaves, &mut memo)?.to_string());
+    }
+
+    Ok(outputs)
+}
+
+fn evaluate_cone(
+    graph: &IRGraph,
+    node_id: usize,
+    leaves: &HashMap<usize, BigUint>,
+    memo: &mut HashMap<usize, BigUint>,
+) -> Result<BigUint, FCMCError> {
+    if let Some(value) = leaves.get(&node_id).or_else(|| memo.get(&node_id)) {
+        return Ok(value.clone());
+    }
+
+    let node = graph
+        .get_node(node_id)
+        .ok_or_else(|| FCMCError::OptimizationError(format!("Missing node {}", node_id)))?;
+    let mut operands = Vec::new();
+    for operand in graph.get_predecessors(node_id) {
+        operands.push(evaluate_cone(graph, operand, leaves, memo)?);
+    }
+    let value = interpreter::apply(&node.node_type, &operands)?;
+    memo.insert(node_id, value.clone());
+    Ok(value)
+}
+
+/// Rewrites function lookups into arithmetic for targets without lookup arguments.
+///
+/// The table is interpolated as a polynomial in the packed index and evaluated in Newton
+/// form with Horner's rule; range constraints on the inputs keep the index in the domain.
+#[derive(Debug, Default)]
+pub struct LookupLowering {
+    last_rewrites: usize,
+}
+
+impl LookupLowering {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let lookups: Vec<(usize, LookupTable)> = graph
+            .live_nodes()
+            .filter(|node| node.node_type == IRNodeType::Lookup)
+            .filter_map(|node| function_table(graph, node).map(|table| (node.id, table.clone())))
+            .collect();
+
+        for (lookup, table) in &lookups {
+            if table.index_bits() > MAX_LOWERED_TABLE_BITS {
+                return Err(FCMCError::OptimizationError(format!(
+                    "Lookup table '{}' has {} index bits; the target needs lookup arguments for tables over {} bits",
+                    table.name,
+                    table.index_bits(),
+                    MAX_LOWERED_TABLE_BITS
+                )));
+            }
+            lower(graph, *lookup, table)?;
+        }
+
+        Ok(lookups.len())
+    }
+}
+
+impl OptimizationPass for LookupLowering {
+    fn name(&self) -> &str {
+        "lowerlookups"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = LookupLowering::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+
+    fn supports(&self, target: &TargetProfile) -> bool {
+        !target.lookups
+    }
+}
+
+fn lower(graph: &mut IRGraph, lookup: usize, table: &LookupTable) -> Result<(), FCMCError> {
+    let inputs = graph.get_predecessors(lookup);
+    let data_type = graph
+        .get_node(lookup)
+        .map(|node| node.data_type.clone())
+        .unwrap_or(Type::Field);
+
+    // index = sum of input_i * 2^offset_i, with every input kept inside its declared width
+    let mut index = None;
+    let mut offset = 0;
+    for (&input, &bits) in inputs.iter().zip(&table.input_bits) {
+        let range = graph.add_node(IRNodeType::Constraint(ConstraintType::Range { bits }), Type::Bool, None);
+        graph.add_edge(input, range, EdgeType::Constraint);
+
+        let term = if offset == 0 {
+            input
+        } else {
+            let scale = constant(graph, &(BigUint::one() << offset));
+            binary(graph, IRNodeType::Mul, input, scale)
+        };
+        index = Some(match index {
+            Some(sum) => binary(graph, IRNodeType::Add, sum, term),
+            None => term,
+        });
+        offset += bits;
+    }
+    let index = match index {
+        Some(index) => index,
+        None => constant(graph, &BigUint::zero()),
+    };
+
+    let coefficients = newton_coefficients(&table.outputs)?;
+    let degree = coefficients.iter().rposition(|c| !c.is_zero()).unwrap_or(0);
+
+    // f(x) = a_0 + (x - 0)(a_1 + (x - 1)(a_2 + ...))
+    let mut acc = constant(graph, &coefficients[degree]);
+    for k in (0..degree).rev() {
+        let shifted = if k == 0 {
+            index
+        } else {
+            let point = constant(graph, &BigUint::from(k));
+            binary(graph, IRNodeType::Sub, index, point)
+        };
+        let product = binary(graph, IRNodeType::Mul, acc, shifted);
+        let coefficient = constant(graph, &coefficients[k]);
+        acc = binary(graph, IRNodeType::Add, product, coefficient);
+    }
+
+    if let Some(node) = graph.get_node_mut(acc) {
+        node.data_type = data_type;
+    }
+    graph.replace_uses(lookup, acc);
+    graph.remove_node(lookup);
+    Ok(())
+}
+
+/// Newton divided differences of the table over the points 0, 1, 2, ...
+fn newton_coefficients(outputs: &[String]) -> Result<Vec<BigUint>, FCMCError> {
+    let mut coefficients = outputs
+        .iter()
+        .map(|value| {
+            field::parse_element(value)
+                .ok_or_else(|| FCMCError::OptimizationError(format!("Invalid table value: {}", value)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if coefficients.is_empty() {
+        coefficients.push(BigUint::zero());
+    }
+
+    for j in 1..coefficients.len() {
+        let inverse = field::inverse(&BigUint::from(j)).expect("table size is below the field characteristic");
+        for i in (j..coefficients.len()).rev() {
+            let difference = field::sub(&coefficients[i], &coefficients[i - 1]);
+            coefficients[i] = field::mul(&difference, &inverse);
+        }
+    }
+
+    Ok(coefficients)
+}
+
+fn constant(graph: &mut IRGraph, value: &BigUint) -> usize {
+    graph.add_node(IRNodeType::Constant(value.to_string()), Type::Field, None)
+}
+
+fn binary(graph: &mut IRGraph, op: IRNodeType, left: usize, right: usize) -> usize {
+    let id = graph.add_node(op, Type::Field, None);
+    graph.add_edge(left, id, EdgeType::DataFlow);
+    graph.add_edge(right, id, EdgeType::DataFlow);
+    id
+}
@@ -0,0 +1,377 @@
+//! Algebraic optimization framework
+//! Transforms the IR into an equivalent graph with fewer constraints
+
+pub mod booleanity;
+pub mod constfold;
+pub mod cost;
+pub mod counterexample;
+pub mod cse;
+pub mod dce;
+pub mod dedup;
+pub mod explain;
+pub mod depth;
+pub mod field;
+pub mod gate_synthesis;
+pub mod inline;
+pub mod interpreter;
+pub mod interval;
+pub mod licm;
+pub mod lookup;
+pub mod parallel;
+pub mod pass;
+pub mod pass_manager;
+pub mod properties;
+pub mod range_check;
+pub mod rules;
+pub mod saturation;
+#[cfg(feature = "smt-validation")]
+pub mod smt;
+pub mod soundness;
+pub mod sparsity;
+pub mod stats;
+pub mod strength;
+pub mod subcircuit;
+pub mod superopt;
+pub mod target;
+
+use crate::backend::TargetSystem;
+use crate::ir::{IRGraph, IRNodeType};
+use crate::platform::Instant;
+use crate::progress::{CancellationToken, ProgressObserver, SharedObserver};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use booleanity::{BooleanityAnalysis, BooleanSource};
+pub use constfold::ConstantFolding;
+pub use cost::CostModel;
+pub use counterexample::{
+    find_suspicious_constraints, ConstraintProblem, SuspiciousConstraint, DEFAULT_COUNTEREXAMPLE_SAMPLES,
+};
+pub use cse::CommonSubexpressionElimination;
+pub use dce::{DceReport, DeadCodeElimination};
+pub use dedup::{deduplicate_rows, ConstraintDeduplication};
+pub use explain::Explanation;
+pub use gate_synthesis::CustomGateSynthesis;
+pub use inline::{inline_calls, CallGraph, InlineHint, InlinePolicy, InlineReport};
+pub use interpreter::{evaluate, Evaluation};
+pub use interval::{Interval, IntervalAnalysis, IntervalReport, RangeCheckElision};
+pub use depth::{multiplicative_depth, DepthReduction};
+pub use lookup::{LookupConversion, LookupLowering};
+pub use parallel::ParallelLocalOptimization;
+pub use pass::{AnalysisCache, Changed, FnPass, OptimizationPass, PassRegistry};
+pub use pass_manager::{PassContext, PassManager, PassManagerBuilder};
+pub use properties::{
+    check_properties, extract_properties, Property, PropertyKind, PropertyReport, DEFAULT_PROPERTY_SAMPLES,
+};
+pub use range_check::{RangeCheckPass, RangeCheckReport};
+pub use rules::{RewriteRule, RuleSet};
+pub use saturation::{EqualitySaturation, SaturationConfig, SaturationReport};
+#[cfg(feature = "smt-validation")]
+pub use smt::{SmtSolver, TranslationValidator};
+pub use soundness::{verify_equivalence, EquivalenceReport, DEFAULT_SOUNDNESS_SAMPLES, MAX_EXHAUSTIVE_INPUTS};
+pub use sparsity::{r1cs_nonzeros, SparsityOptimization};
+pub use stats::{IrSnapshot, OptimizationStats, PassRecorder, PassStats};
+pub use strength::StrengthReduction;
+pub use subcircuit::{find_repeated_subcircuits, SubcircuitExtraction, SubcircuitReport};
+pub use superopt::Superoptimizer;
+pub use target::TargetProfile;
+
+/// Drives the optimization pipeline, either the default one for the level or a user-supplied one
+pub struct OptimizationFramework {
+    level: u8,
+    pipeline: Option<PassManager>,
+    registry: PassRegistry,
+    extra_passes: Vec<Box<dyn OptimizationPass>>,
+    dump_ir_after: Vec<String>,
+    stats: OptimizationStats,
+    snapshots: Vec<IrSnapshot>,
+    lookup_support: Option<bool>,
+    cost_model: Option<CostModel>,
+    saturation: SaturationConfig,
+    rewrite_rules: RuleSet,
+    soundness_samples: usize,
+    #[cfg(feature = "smt-validation")]
+    translation_validation: Option<TranslationValidator>,
+    explain: bool,
+    time_budget: Option<Duration>,
+    max_node_growth: Option<f64>,
+    memory_limit: Option<usize>,
+    booleanity: Option<BooleanityAnalysis>,
+    progress: Option<SharedObserver>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl OptimizationFramework {
+    pub fn new() -> Self {
+        Self {
+            level: 2,
+            pipeline: None,
+            registry: PassRegistry::with_builtins(),
+            extra_passes: Vec::new(),
+            dump_ir_after: Vec::new(),
+            stats: OptimizationStats::default(),
+            snapshots: Vec::new(),
+            lookup_support: None,
+            cost_model: None,
+            saturation: SaturationConfig::default(),
+            rewrite_rules: RuleSet::new(),
+            soundness_samples: 0,
+            #[cfg(feature = "smt-validation")]
+            translation_validation: None,
+            explain: false,
+            time_budget: None,
+            max_node_growth: None,
+            memory_limit: None,
+            booleanity: None,
+            progress: None,
+            cancellation: None,
+        }
+    }
+
+    /// Stop starting new passes once `budget` has elapsed, keeping the IR optimized so far
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Undo any pass that grows the graph beyond `ratio` times its initial node count,
+    /// and end the pipeline there
+    pub fn with_max_node_growth(mut self, ratio: f64) -> Self {
+        self.max_node_growth = Some(ratio);
+        self
+    }
+
+    /// Fail with `FCMCError::MemoryLimit` after any pass that leaves the IR over about
+    /// `bytes`
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = Some(bytes);
+    }
+
+    pub fn set_level(&mut self, level: u8) {
+        self.level = level;
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Replace the level-based default pipeline
+    pub fn set_pipeline(&mut self, pipeline: PassManager) {
+        self.pipeline = Some(pipeline);
+    }
+
+    /// Replace the default pipeline with one parsed from a spec like `"cse,constfold,dce"`.
+    /// Names may refer to passes added with `register_pass`.
+    pub fn set_pipeline_spec(&mut self, spec: &str) -> Result<(), FCMCError> {
+        self.pipeline = Some(PassManager::builder().spec(spec).build_with(&self.registry)?);
+        Ok(())
+    }
+
+    /// Make a pass available by name to pipeline specs
+    pub fn register_pass<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&PassContext) -> Box<dyn OptimizationPass> + 'static,
+    {
+        self.registry.register(name, factory);
+    }
+
+    /// Run `pass` after the selected pipeline on every `optimize` call
+    pub fn add_pass<P: OptimizationPass + 'static>(&mut self, pass: P) {
+        self.extra_passes.push(Box::new(pass));
+    }
+
+    pub fn registered_passes(&self) -> impl Iterator<Item = &str> {
+        self.registry.names()
+    }
+
+    /// Override whether passes may emit lookup nodes; defaults to what the target supports
+    pub fn set_lookup_support(&mut self, enabled: bool) {
+        self.lookup_support = Some(enabled);
+    }
+
+    /// Override the cost model used by cost-driven rewrites; defaults to the target's
+    pub fn set_cost_model(&mut self, cost_model: CostModel) {
+        self.cost_model = Some(cost_model);
+    }
+
+    /// Time budget for the equality-saturation stage at level 3; `Duration::MAX` leaves
+    /// only its iteration and node limits
+    pub fn set_saturation_timeout(&mut self, timeout: Duration) {
+        self.saturation.timeout = timeout;
+    }
+
+    pub fn set_saturation_config(&mut self, config: SaturationConfig) {
+        self.saturation = config;
+    }
+
+    /// Load extra rewrite rules for equality saturation from a rule file
+    pub fn load_rewrite_rules(&mut self, path: &Path) -> Result<(), FCMCError> {
+        let rules = RuleSet::load(path)?;
+        tracing::debug!("Loaded {} rewrite rule(s) from {}", rules.len(), path.display());
+        self.rewrite_rules.extend(rules);
+        Ok(())
+    }
+
+    pub fn add_rewrite_rules(&mut self, rules: RuleSet) {
+        self.rewrite_rules.extend(rules);
+    }
+
+    /// Check every pass that changes the graph against the unmodified graph on `samples`
+    /// random inputs, failing with `OptimizationError` on any difference; 0 disables the check
+    pub fn set_soundness_samples(&mut self, samples: usize) {
+        self.soundness_samples = samples;
+    }
+
+    /// Prove the passes selected in `validator` equivalence-preserving with an SMT solver
+    #[cfg(feature = "smt-validation")]
+    pub fn set_translation_validation(&mut self, validator: TranslationValidator) {
+        self.translation_validation = Some(validator);
+    }
+
+    /// Keep a copy of the IR after each run of `pass`; `"all"` snapshots after every pass
+    pub fn dump_ir_after(&mut self, pass: &str) {
+        self.dump_ir_after.push(pass.to_string());
+    }
+
+    /// Record where each pass saved constraints, see `OptimizationStats::explain_report`
+    pub fn set_explain(&mut self, explain: bool) {
+        self.explain = explain;
+    }
+
+    /// Report every pass run to `observer`
+    pub fn set_progress(&mut self, observer: Arc<dyn ProgressObserver>) {
+        self.progress = Some(SharedObserver(observer));
+    }
+
+    /// Fail with `FCMCError::Cancelled` before the next pass run once `token` is cancelled
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    /// Per-pass statistics from the last `optimize` call
+    pub fn stats(&self) -> &OptimizationStats {
+        &self.stats
+    }
+
+    /// IR snapshots requested with `dump_ir_after`, from the last `optimize` call
+    pub fn snapshots(&self) -> &[IrSnapshot] {
+        &self.snapshots
+    }
+
+    /// Booleanity facts computed during the last `optimize` call
+    pub fn booleanity(&self) -> Option<&BooleanityAnalysis> {
+        self.booleanity.as_ref()
+    }
+
+    fn pass_context(&self, target: &TargetSystem, ir: &IRGraph) -> PassContext {
+        let mut profile = TargetProfile::for_target(target.clone());
+        if let Some(cost_model) = self.cost_model {
+            profile.cost_model = cost_model;
+        }
+        if let Some(lookups) = self.lookup_support {
+            profile.lookups = lookups;
+        }
+
+        PassContext {
+            target: profile,
+            saturation: self.saturation,
+            rewrite_rules: self.rewrite_rules.clone(),
+            soundness_samples: self.soundness_samples,
+            #[cfg(feature = "smt-validation")]
+            translation_validation: self.translation_validation.clone(),
+            deadline: self.time_budget.map(|budget| Instant::now() + budget),
+            cancellation: self.cancellation.clone(),
+            max_nodes: self
+                .max_node_growth
+                .map(|ratio| (ir.node_count() as f64 * ratio).ceil() as usize),
+            memory_limit: self.memory_limit,
+        }
+    }
+
+    /// Optimize `ir` for `target`; passes that do not benefit the target are skipped
+    pub fn optimize(&mut self, mut ir: IRGraph, target: TargetSystem) -> Result<IRGraph, FCMCError> {
+        let ctx = self.pass_context(&target, &ir);
+        let mut recorder = PassRecorder::new().with_dump_after(self.dump_ir_after.iter().cloned());
+        if self.explain {
+            recorder = recorder.with_explain(ctx.target.cost_model);
+        }
+        let mut default_pipeline = None;
+        let pipeline = match self.pipeline.as_mut() {
+            Some(pipeline) => pipeline,
+            None => default_pipeline.insert(PassManager::for_level(self.level)),
+        };
+        if let Some(SharedObserver(observer)) = &self.progress {
+            recorder = recorder.with_progress(observer.clone(), pipeline.planned_runs() + self.extra_passes.len());
+        }
+
+        let iterations = pipeline.run(&mut ir, &ctx, &self.registry, &mut recorder)?;
+        tracing::debug!("Optimization pipeline finished after {} iteration(s)", iterations);
+
+        let mut analyses = AnalysisCache::new();
+        for pass in &mut self.extra_passes {
+            if recorder.stopped() {
+                break;
+            }
+            ctx.check_cancelled()?;
+            if ctx.out_of_time() {
+                recorder.stop("time budget exhausted".to_string());
+                break;
+            }
+            if !pass.supports(&ctx.target) {
+                tracing::debug!("Skipping pass '{}' for {:?}", pass.name(), target);
+                continue;
+            }
+            let keep_before = ctx.checks_pass(pass.name()) || ctx.max_nodes.is_some();
+            let before = keep_before.then(|| ir.clone());
+            let census = recorder.census(&ir);
+            let mut stats = PassStats::start(pass.name(), 1, &ir);
+            let _span = stats.span().entered();
+            let started = Instant::now();
+            let changed = pass.run(&mut ir, &analyses)?;
+            stats.changed = changed;
+            stats.rewrites = pass.rewrites();
+            recorder.record(stats, &ir, started.elapsed());
+            ctx.check_memory(&ir, pass.name())?;
+            if ctx.exceeds_node_budget(&ir) {
+                if let Some(before) = before {
+                    ir = before;
+                }
+                recorder.stop(format!("pass '{}' exceeded the node growth budget", pass.name()));
+                break;
+            }
+            if let (true, Some(census)) = (changed, census) {
+                recorder.explain(census, pass.name(), 1, &ir);
+            }
+
+            tracing::debug!("Pass '{}' changed graph: {}", pass.name(), changed);
+            if let (true, Some(before)) = (changed, &before) {
+                ctx.check_pass(before, &ir, pass.name())?;
+            }
+            if changed {
+                analyses.invalidate();
+            }
+        }
+
+        let (stats, snapshots) = recorder.finish();
+        self.stats = stats;
+        self.snapshots = snapshots;
+
+        // Re-run the analysis on the final graph so backends see up-to-date facts
+        let analysis = BooleanityAnalysis::run(&ir);
+        analysis.annotate(&mut ir);
+        self.booleanity = Some(analysis);
+
+        Ok(ir)
+    }
+}
+
+/// Value of a constant node reduced into the field
+pub(crate) fn constant_value(graph: &IRGraph, node_id: usize) -> Option<BigUint> {
+    match &graph.get_node(node_id)?.node_type {
+        IRNodeType::Constant(value) => field::parse_element(value),
+        _ => None,
+    }
+}
@@ -0,0 +1,255 @@
+//! Region-parallel local optimization.
+//!
+//! Inputs and constants are shared leaves; removing them splits a circuit into regions
+//! that exchange no values. Each region is extracted into its own graph, folded and
+//! deduplicated on the rayon thread pool, or in turn without the `parallel` feature, and
+//! the results are reassembled before one global CSE pass merges expressions over shared
+//! leaves that several regions compute.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::constfold::ConstantFolding;
+use crate::optimization::cse::CommonSubexpressionElimination;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use crate::platform::*;
+use std::collections::{HashMap, HashSet};
+
+/// Graphs smaller than this are optimized sequentially; splitting them costs more than it saves
+pub const PARALLEL_THRESHOLD: usize = 50_000;
+
+/// Nodes shared between regions, and every region's nodes (including the shared
+/// nodes it reads) in topological order
+pub fn partition(graph: &IRGraph) -> (Vec<usize>, Vec<Vec<usize>>) {
+    let order = graph.topological_sort();
+    let is_shared = |node_id: usize| {
+        matches!(
+            graph.get_node(node_id).map(|node| &node.node_type),
+            Some(IRNodeType::Input(_)) | Some(IRNodeType::PrivateInput(_)) | Some(IRNodeType::Constant(_))
+        )
+    };
+
+    let mut parents: HashMap<usize, usize> = HashMap::new();
+    for &node_id in &order {
+        if is_shared(node_id) {
+            continue;
+        }
+        parents.insert(node_id, node_id);
+        for operand in graph.get_predecessors(node_id) {
+            if !is_shared(operand) && parents.contains_key(&operand) {
+                union(&mut parents, node_id, operand);
+            }
+        }
+    }
+
+    let shared: Vec<usize> = order.iter().copied().filter(|&id| is_shared(id)).collect();
+    // root -> (nodes, shared nodes already added)
+    let mut regions: HashMap<usize, (Vec<usize>, HashSet<usize>)> = HashMap::new();
+    let mut region_order = Vec::new();
+    for &node_id in &order {
+        if is_shared(node_id) {
+            continue;
+        }
+        let root = find(&mut parents, node_id);
+        let (nodes, leaves) = regions.entry(root).or_insert_with(|| {
+            region_order.push(root);
+            (Vec::new(), HashSet::new())
+        });
+        for operand in graph.get_predecessors(node_id) {
+            if is_shared(operand) && leaves.insert(operand) {
+                nodes.push(operand);
+            }
+        }
+        nodes.push(node_id);
+    }
+
+    let regions = region_order
+        .into_iter()
+        .filter_map(|root| regions.remove(&root).map(|(nodes, _)| nodes))
+        .collect();
+    (shared, regions)
+}
+
+fn find(parents: &mut HashMap<usize, usize>, mut id: usize) -> usize {
+    while parents[&id] != id {
+        let grandparent = parents[&parents[&id]];
+        parents.insert(id, grandparent);
+        id = grandparent;
+    }
+    id
+}
+
+fn union(parents: &mut HashMap<usize, usize>, a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents.insert(a.max(b), a.min(b));
+    }
+}
+
+/// Constant folding and CSE, run per region in parallel on large graphs
+#[derive(Debug, Default)]
+pub struct ParallelLocalOptimization {
+    last_rewrites: usize,
+}
+
+impl ParallelLocalOptimization {
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        if graph.node_count() < PARALLEL_THRESHOLD {
+            return local_passes(graph);
+        }
+
+        let (shared, regions) = partition(graph);
+        if regions.len() < 2 {
+            return local_passes(graph);
+        }
+        tracing::debug!("Optimizing {} regions in parallel", regions.len());
+
+        let source: &IRGraph = graph;
+        // Worker threads do not inherit this thread's field
+        let active = field::active();
+        let results = regions
+            .par_iter()
+            .map(|nodes| {
+                active.enter(|| {
+                    let (mut region, origin) = source.extract(nodes);
+                    let rewrites = local_passes(&mut region)?;
+                    Ok((region, origin, rewrites))
+                })
+            })
+            .collect::<Result<Vec<_>, FCMCError>>()?;
+
+        let rewrites: usize = results.iter().map(|(_, _, rewrites)| rewrites).sum();
+        if rewrites == 0 {
+            return Ok(0);
+        }
+
+        let regions = results.into_iter().map(|(region, origin, _)| (region, origin)).collect();
+        *graph = graph.reassemble(&shared, regions);
+
+        // Regions cannot see each other's expressions over shared inputs; merge them now
+        let merged = CommonSubexpressionElimination::default().run(graph)?;
+        Ok(rewrites + merged)
+    }
+}
+
+fn local_passes(graph: &mut IRGraph) -> Result<usize, FCMCError> {
+    let folded = ConstantFolding::default().run(graph)?;
+    let merged = CommonSubexpressionElimination::default().run(graph)?;
+    Ok(folded + merged)
+}
+
+impl OptimizationPass for ParallelLocalOptimization {
+    fn name(&self) -> &str {
+        "parallel"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = ParallelLocalOptimization::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::public_inputs::public_order;
+    use crate::ir::EdgeType;
+    use crate::language::ast::Type;
+    use crate::optimization::interpreter::assert_equivalent;
+    use num_bigint::BigUint;
+
+    /// `regions` independent `out_i = (x_i + w_i) * (1 + 2)` circuits, each with a
+    /// foldable constant sum
+    fn independent_regions(regions: usize) -> IRGraph {
+        let mut graph = IRGraph::new();
+        let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+        for i in 0..regions {
+            let x = graph.add_node(IRNodeType::Input(format!("x{}", i)), Type::Field, None);
+            let w = graph.add_node(IRNodeType::PrivateInput(format!("w{}", i)), Type::Field, None);
+            let one = graph.add_node(IRNodeType::Constant("1".to_string()), Type::Field, None);
+            let two = graph.add_node(IRNodeType::Constant("2".to_string()), Type::Field, None);
+            let sum = graph.add_node(IRNodeType::Add, Type::Field, None);
+            let three = graph.add_node(IRNodeType::Add, Type::Field, None);
+            let product = graph.add_node(IRNodeType::Mul, Type::Field, None);
+            let out = graph.add_node(IRNodeType::Output(format!("out{}", i)), Type::Field, None);
+            let edges = [
+                (x, sum),
+                (w, sum),
+                (one, three),
+                (two, three),
+                (sum, product),
+                (three, product),
+                (product, out),
+            ];
+            for (from, to) in edges {
+                graph.add_edge(from, to, EdgeType::DataFlow);
+            }
+            inputs.push(x);
+            outputs.push(out);
+        }
+        IRGraph::from_parts(
+            graph.nodes().to_vec(),
+            graph.edges().to_vec(),
+            inputs,
+            outputs,
+            HashSet::new(),
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
+    /// Names of the public inputs and outputs in wire order
+    fn public_layout(graph: &IRGraph) -> Vec<String> {
+        public_order(graph, |node_type| {
+            matches!(node_type, IRNodeType::Input(_) | IRNodeType::Output(_))
+        })
+        .into_iter()
+        .filter_map(|id| match &graph.get_node(id)?.node_type {
+            IRNodeType::Input(name) | IRNodeType::Output(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn reassembly_keeps_the_public_layout() {
+        let graph = independent_regions(PARALLEL_THRESHOLD / 8 + 1);
+        assert!(graph.node_count() >= PARALLEL_THRESHOLD);
+        assert!(partition(&graph).1.len() >= 2);
+
+        let mut sequential = graph.clone();
+        local_passes(&mut sequential).unwrap();
+        let mut parallel = graph.clone();
+        assert!(ParallelLocalOptimization::default().run(&mut parallel).unwrap() > 0);
+
+        assert_eq!(public_layout(&parallel), public_layout(&sequential));
+        assert_eq!(public_layout(&parallel), public_layout(&graph));
+    }
+
+    #[test]
+    fn regions_fold_to_the_same_circuit() {
+        let regions = PARALLEL_THRESHOLD / 8 + 1;
+        let graph = independent_regions(regions);
+        let mut parallel = graph.clone();
+        ParallelLocalOptimization::default().run(&mut parallel).unwrap();
+        let additions = |graph: &IRGraph| graph.live_nodes().filter(|node| node.node_type == IRNodeType::Add).count();
+        assert_eq!((additions(&graph), additions(&parallel)), (2 * regions, regions));
+
+        let inputs: Vec<HashMap<String, BigUint>> = [1u64, 7]
+            .into_iter()
+            .map(|seed| {
+                (0..regions)
+                    .flat_map(|i| {
+                        let i = i as u64;
+                        [(format!("x{}", i), BigUint::from(seed * i)), (format!("w{}", i), BigUint::from(seed + i))]
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_equivalent(&graph, &parallel, &inputs);
+    }
+}
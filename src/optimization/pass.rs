@@ -0,0 +1,180 @@
+use crate::ir::IRGraph;
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::pass_manager::PassContext;
+use crate::optimization::target::TargetProfile;
+use crate::FCMCError;
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+
+/// Whether a pass modified the graph
+pub type Changed = bool;
+
+/// A single optimization over the IR.
+///
+/// Implement this in downstream crates and hand it to `OptimizationFramework::add_pass`
+/// or `OptimizationFramework::register_pass` to extend the optimizer without forking it.
+pub trait OptimizationPass {
+    fn name(&self) -> &str;
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError>;
+
+    /// Number of rewrites applied by the last `run`, for passes that count them
+    fn rewrites(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the pass is worth running for `target`; unsupported passes are skipped
+    fn supports(&self, _target: &TargetProfile) -> bool {
+        true
+    }
+}
+
+/// Analyses shared between passes, computed on first use and dropped whenever a pass changes the graph
+#[derive(Debug, Default)]
+pub struct AnalysisCache {
+    booleanity: OnceCell<BooleanityAnalysis>,
+}
+
+impl AnalysisCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn booleanity(&self, graph: &IRGraph) -> &BooleanityAnalysis {
+        self.booleanity.get_or_init(|| BooleanityAnalysis::run(graph))
+    }
+
+    pub fn invalidate(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Adapts a closure into an `OptimizationPass`
+pub struct FnPass<F> {
+    name: String,
+    run: F,
+}
+
+impl<F> FnPass<F>
+where
+    F: FnMut(&mut IRGraph) -> Result<Changed, FCMCError>,
+{
+    pub fn new(name: &str, run: F) -> Self {
+        Self {
+            name: name.to_string(),
+            run,
+        }
+    }
+}
+
+impl<F> OptimizationPass for FnPass<F>
+where
+    F: FnMut(&mut IRGraph) -> Result<Changed, FCMCError>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        (self.run)(graph)
+    }
+}
+
+/// Creates a fresh pass instance configured for the current compilation
+pub type PassFactory = dyn Fn(&PassContext) -> Box<dyn OptimizationPass>;
+
+/// Maps pass names used in pipeline specs to their factories
+pub struct PassRegistry {
+    factories: BTreeMap<String, Box<PassFactory>>,
+}
+
+impl PassRegistry {
+    pub fn empty() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// A registry containing every built-in pass
+    pub fn with_builtins() -> Self {
+        use crate::optimization::booleanity::RedundantBooleanityElimination;
+        use crate::optimization::constfold::ConstantFolding;
+        use crate::optimization::cse::CommonSubexpressionElimination;
+        use crate::optimization::dce::DeadCodeElimination;
+        use crate::optimization::dedup::ConstraintDeduplication;
+        use crate::optimization::depth::DepthReduction;
+        use crate::optimization::gate_synthesis::CustomGateSynthesis;
+        use crate::optimization::interval::RangeCheckElision;
+        use crate::optimization::lookup::{LookupConversion, LookupLowering};
+        use crate::optimization::parallel::ParallelLocalOptimization;
+        use crate::optimization::range_check::{RangeCheckPass, DEFAULT_LOOKUP_BITS};
+        use crate::optimization::saturation::EqualitySaturation;
+        use crate::optimization::sparsity::SparsityOptimization;
+        use crate::optimization::strength::StrengthReduction;
+        use crate::optimization::subcircuit::SubcircuitExtraction;
+        use crate::optimization::superopt::Superoptimizer;
+
+        let mut registry = Self::empty();
+        registry.register("constfold", |_| Box::new(ConstantFolding::default()));
+        registry.register("cse", |_| Box::new(CommonSubexpressionElimination::default()));
+        registry.register("dce", |_| Box::new(DeadCodeElimination::default()));
+        registry.register("dedup", |_| Box::new(ConstraintDeduplication::default()));
+        registry.register("depth", |_| Box::new(DepthReduction::default()));
+        registry.register("parallel", |_| Box::new(ParallelLocalOptimization::default()));
+        registry.register("booleanity", |_| Box::new(RedundantBooleanityElimination::default()));
+        registry.register("rangecheck", |ctx| {
+            let pass = RangeCheckPass::new();
+            if ctx.target.lookups {
+                Box::new(pass.with_lookup_bits(DEFAULT_LOOKUP_BITS))
+            } else {
+                Box::new(pass)
+            }
+        });
+        registry.register("intervals", |_| Box::new(RangeCheckElision::default()));
+        registry.register("lookups", |ctx| Box::new(LookupConversion::new(ctx.target.cost_model)));
+        registry.register("lowerlookups", |_| Box::new(LookupLowering::default()));
+        registry.register("strength", |ctx| Box::new(StrengthReduction::new(ctx.target.cost_model)));
+        registry.register("saturation", |ctx| {
+            let mut config = ctx.saturation;
+            if let Some(remaining) = ctx.remaining_time() {
+                config.timeout = config.timeout.min(remaining);
+            }
+            let pass = EqualitySaturation::new(config, ctx.target.cost_model);
+            Box::new(pass.with_rules(ctx.rewrite_rules.rules()))
+        });
+        registry.register("superopt", |ctx| Box::new(Superoptimizer::new(ctx.target.cost_model)));
+        registry.register("sparsity", |_| Box::new(SparsityOptimization::default()));
+        registry.register("subcircuits", |_| Box::new(SubcircuitExtraction::default()));
+        registry.register("customgates", |_| Box::new(CustomGateSynthesis::default()));
+        registry
+    }
+
+    /// Register a pass under `name`, replacing any existing pass with that name
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&PassContext) -> Box<dyn OptimizationPass> + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    pub fn create(&self, name: &str, ctx: &PassContext) -> Result<Box<dyn OptimizationPass>, FCMCError> {
+        match self.factories.get(name) {
+            Some(factory) => Ok(factory(ctx)),
+            None => Err(FCMCError::OptimizationError(format!("Unknown pass: {}", name))),
+        }
+    }
+}
+
+impl Default for PassRegistry {
+    fn default() -> Self {
+        Self::with_builtins()
+    }
+}
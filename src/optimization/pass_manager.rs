@@ -0,0 +1,341 @@
+use crate::ir::IRGraph;
+use crate::memory;
+use crate::optimization::pass::{AnalysisCache, Changed, FnPass, OptimizationPass, PassRegistry};
+use crate::optimization::rules::RuleSet;
+use crate::optimization::saturation::SaturationConfig;
+use crate::optimization::soundness;
+use crate::optimization::stats::{PassRecorder, PassStats};
+use crate::optimization::target::TargetProfile;
+use crate::platform::Instant;
+use crate::progress::CancellationToken;
+use crate::FCMCError;
+use std::time::Duration;
+
+/// Names of the passes registered by `PassRegistry::with_builtins`
+pub const BUILTIN_PASSES: &[&str] = &[
+    "constfold",
+    "cse",
+    "dce",
+    "dedup",
+    "depth",
+    "parallel",
+    "booleanity",
+    "rangecheck",
+    "intervals",
+    "lookups",
+    "lowerlookups",
+    "strength",
+    "saturation",
+    "superopt",
+    "sparsity",
+    "subcircuits",
+    "customgates",
+];
+
+/// Settings the built-in passes are instantiated with
+#[derive(Debug, Clone)]
+pub struct PassContext {
+    /// Target the circuit is optimized for, with any user overrides applied
+    pub target: TargetProfile,
+    pub saturation: SaturationConfig,
+    /// User rewrite rules applied during equality saturation
+    pub rewrite_rules: RuleSet,
+    /// Random inputs each changing pass is checked against; 0 disables the check
+    pub soundness_samples: usize,
+    /// Passes opted in to SMT translation validation
+    #[cfg(feature = "smt-validation")]
+    pub translation_validation: Option<crate::optimization::smt::TranslationValidator>,
+    /// No pass starts after this instant
+    pub deadline: Option<Instant>,
+    /// No pass starts once this is cancelled; the pipeline fails instead
+    pub cancellation: Option<CancellationToken>,
+    /// A pass that leaves more live nodes than this is undone and ends the pipeline
+    pub max_nodes: Option<usize>,
+    /// A pass that leaves an IR of more approximate bytes than this fails the compilation
+    pub memory_limit: Option<usize>,
+}
+
+impl PassContext {
+    /// Whether `pass` is checked against a copy of the graph taken before it runs
+    #[cfg_attr(not(feature = "smt-validation"), allow(unused_variables))]
+    pub(crate) fn checks_pass(&self, pass: &str) -> bool {
+        #[cfg(feature = "smt-validation")]
+        if let Some(validator) = &self.translation_validation {
+            if validator.applies_to(pass) {
+                return true;
+            }
+        }
+        self.soundness_samples > 0
+    }
+
+    /// `Err(FCMCError::Cancelled)` once the compilation was cancelled
+    pub(crate) fn check_cancelled(&self) -> Result<(), FCMCError> {
+        self.cancellation.as_ref().map_or(Ok(()), CancellationToken::check)
+    }
+
+    pub(crate) fn out_of_time(&self) -> bool {
+        self.deadline.map_or(false, |deadline| Instant::now() >= deadline)
+    }
+
+    /// Time left before the deadline, if there is one
+    pub(crate) fn remaining_time(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub(crate) fn exceeds_node_budget(&self, graph: &IRGraph) -> bool {
+        self.max_nodes.map_or(false, |max_nodes| graph.node_count() > max_nodes)
+    }
+
+    /// `Err(FCMCError::MemoryLimit)` if `pass` left the IR over the memory limit
+    pub(crate) fn check_memory(&self, graph: &IRGraph, pass: &str) -> Result<(), FCMCError> {
+        if self.memory_limit.is_none() {
+            return Ok(());
+        }
+        memory::check(memory::ir_bytes(graph), self.memory_limit, &format!("pass '{}'", pass))
+    }
+
+    /// Fail if the rewrite of `before` into `after` by `pass` changed the circuit semantics
+    pub(crate) fn check_pass(&self, before: &IRGraph, after: &IRGraph, pass: &str) -> Result<(), FCMCError> {
+        if self.soundness_samples > 0 {
+            soundness::check_pass(before, after, self.soundness_samples, pass)?;
+        }
+        #[cfg(feature = "smt-validation")]
+        if let Some(validator) = &self.translation_validation {
+            if validator.applies_to(pass) {
+                validator.validate(before, after, pass)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Step {
+    /// Resolved through the registry each time the pipeline runs
+    Named(String),
+    Instance(Box<dyn OptimizationPass>),
+}
+
+impl Step {
+    fn name(&self) -> &str {
+        match self {
+            Step::Named(name) => name,
+            Step::Instance(pass) => pass.name(),
+        }
+    }
+}
+
+/// An ordered list of passes with repetition counts and optional fixed-point iteration
+pub struct PassManager {
+    steps: Vec<(Step, usize)>,
+    max_iterations: usize,
+}
+
+impl PassManager {
+    pub fn builder() -> PassManagerBuilder {
+        PassManagerBuilder::new()
+    }
+
+    /// Parse a pipeline such as `"cse,constfold*2,dce"`
+    pub fn from_spec(spec: &str) -> Result<Self, FCMCError> {
+        Self::builder().spec(spec).build()
+    }
+
+    /// The default pipeline for an optimization level
+    pub fn for_level(level: u8) -> Self {
+        let builder = match level {
+            0 => PassManagerBuilder::new(),
+            1 => PassManagerBuilder::new().spec("constfold,booleanity,lowerlookups,dce"),
+            2 => PassManagerBuilder::new()
+                .spec("parallel,booleanity,lookups,rangecheck,intervals,strength,lowerlookups,dce,sparsity,customgates")
+                .run_until_stable(4),
+            _ => PassManagerBuilder::new().spec(
+                "parallel,booleanity,lookups,rangecheck,intervals,strength,saturation,superopt,constfold,cse,depth,lowerlookups,dce,sparsity,subcircuits,customgates",
+            ),
+        };
+        builder.build().expect("default pipelines only use built-in passes")
+    }
+
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.steps.iter().map(|(step, _)| step.name()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Pass runs the pipeline makes if it iterates as often as it may
+    pub fn planned_runs(&self) -> usize {
+        self.steps.iter().map(|(_, repeat)| repeat).sum::<usize>() * self.max_iterations
+    }
+
+    /// Run the pipeline, returning the number of full iterations performed
+    pub fn run(
+        &mut self,
+        graph: &mut IRGraph,
+        ctx: &PassContext,
+        registry: &PassRegistry,
+        recorder: &mut PassRecorder,
+    ) -> Result<usize, FCMCError> {
+        let mut analyses = AnalysisCache::new();
+        let mut iterations = 0;
+
+        'pipeline: while iterations < self.max_iterations {
+            iterations += 1;
+            let mut changed = false;
+
+            for (step, repeat) in &mut self.steps {
+                let mut created;
+                let pass: &mut dyn OptimizationPass = match step {
+                    Step::Named(name) => {
+                        created = registry.create(name, ctx)?;
+                        created.as_mut()
+                    }
+                    Step::Instance(pass) => pass.as_mut(),
+                };
+                if !pass.supports(&ctx.target) {
+                    tracing::debug!("Skipping pass '{}' for {:?}", pass.name(), ctx.target.system);
+                    continue;
+                }
+
+                for _ in 0..*repeat {
+                    ctx.check_cancelled()?;
+                    if ctx.out_of_time() {
+                        recorder.stop("time budget exhausted".to_string());
+                        break 'pipeline;
+                    }
+
+                    let keep_before = ctx.checks_pass(pass.name()) || ctx.max_nodes.is_some();
+                    let before = keep_before.then(|| graph.clone());
+                    let census = recorder.census(graph);
+                    let mut stats = PassStats::start(pass.name(), iterations, graph);
+                    let _span = stats.span().entered();
+                    let started = Instant::now();
+                    let pass_changed = pass.run(graph, &analyses)?;
+                    stats.changed = pass_changed;
+                    stats.rewrites = pass.rewrites();
+                    recorder.record(stats, graph, started.elapsed());
+                    ctx.check_memory(graph, pass.name())?;
+
+                    if ctx.exceeds_node_budget(graph) {
+                        // Keep the last graph within budget rather than failing
+                        if let Some(before) = before {
+                            *graph = before;
+                        }
+                        recorder.stop(format!("pass '{}' exceeded the node growth budget", pass.name()));
+                        break 'pipeline;
+                    }
+                    if let (true, Some(census)) = (pass_changed, census) {
+                        recorder.explain(census, pass.name(), iterations, graph);
+                    }
+
+                    if let (true, Some(before)) = (pass_changed, &before) {
+                        ctx.check_pass(before, graph, pass.name())?;
+                    }
+
+                    tracing::debug!("Pass '{}' changed graph: {}", pass.name(), pass_changed);
+                    if pass_changed {
+                        analyses.invalidate();
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        recorder.add_iterations(iterations);
+        Ok(iterations)
+    }
+}
+
+pub struct PassManagerBuilder {
+    steps: Vec<(Step, usize)>,
+    max_iterations: usize,
+    errors: Vec<String>,
+}
+
+impl PassManagerBuilder {
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            max_iterations: 1,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Append a registered pass by name
+    pub fn pass(self, name: &str) -> Self {
+        self.repeat(name, 1)
+    }
+
+    /// Append a registered pass that runs `times` times in a row
+    pub fn repeat(mut self, name: &str, times: usize) -> Self {
+        self.steps.push((Step::Named(name.to_string()), times));
+        self
+    }
+
+    /// Append a pass instance
+    pub fn add<P: OptimizationPass + 'static>(mut self, pass: P) -> Self {
+        self.steps.push((Step::Instance(Box::new(pass)), 1));
+        self
+    }
+
+    /// Append a closure as a pass
+    pub fn custom<F>(self, name: &str, run: F) -> Self
+    where
+        F: FnMut(&mut IRGraph) -> Result<Changed, FCMCError> + 'static,
+    {
+        self.add(FnPass::new(name, run))
+    }
+
+    /// Append the passes of a comma-separated spec; `name*N` repeats a pass N times
+    pub fn spec(mut self, spec: &str) -> Self {
+        for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (name, times) = match entry.split_once('*') {
+                Some((name, count)) => match count.trim().parse::<usize>() {
+                    Ok(times) => (name.trim(), times),
+                    Err(_) => {
+                        self.errors.push(format!("Invalid repetition count in '{}'", entry));
+                        continue;
+                    }
+                },
+                None => (entry, 1),
+            };
+            self = self.repeat(name, times);
+        }
+        self
+    }
+
+    /// Re-run the whole pipeline until no pass changes the graph, at most `max_iters` times
+    pub fn run_until_stable(mut self, max_iters: usize) -> Self {
+        self.max_iterations = max_iters.max(1);
+        self
+    }
+
+    /// Build against the built-in passes
+    pub fn build(self) -> Result<PassManager, FCMCError> {
+        self.build_with(&PassRegistry::with_builtins())
+    }
+
+    /// Build, checking pass names against `registry`
+    pub fn build_with(mut self, registry: &PassRegistry) -> Result<PassManager, FCMCError> {
+        for (step, _) in &self.steps {
+            if let Step::Named(name) = step {
+                if !registry.contains(name) {
+                    self.errors.push(format!("Unknown pass: {}", name));
+                }
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(FCMCError::OptimizationError(self.errors.join("; ")));
+        }
+
+        Ok(PassManager {
+            steps: self.steps,
+            max_iterations: self.max_iterations,
+        })
+    }
+}
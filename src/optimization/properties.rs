@@ -0,0 +1,421 @@
+//! Property annotations: lightweight specifications of a circuit, checked but not compiled.
+//!
+//! `#[require(expr)]` on `main` states what the inputs are expected to satisfy and
+//! `#[ensure(expr)]` what the outputs then satisfy:
+//!
+//! ```text
+//! #[require(depth <= 32)]
+//! #[ensure(valid == 0 || root == expected_root)]
+//! fn main(...) { ... }
+//! ```
+//!
+//! Expressions read inputs and outputs by name and use integer literals, `true`, `false`,
+//! `+ - *`, comparisons, `!`, `&&` and `||`, with the interpreter's field semantics. The
+//! annotations are removed from the source before parsing, and the compiled IR is then
+//! evaluated on random inputs: every input the preconditions and the constraints accept
+//! must meet every postcondition.
+
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::optimization::interpreter::{evaluate, input_names};
+use crate::optimization::soundness::random_value;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+/// Random input assignments the properties are checked on
+pub const DEFAULT_PROPERTY_SAMPLES: usize = 64;
+
+const SEED: u64 = 0x5bec_0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyKind {
+    /// Precondition on the inputs
+    Require,
+    /// Postcondition on the inputs and outputs
+    Ensure,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    pub kind: PropertyKind,
+    /// The annotation as written, such as `ensure(out == 1)`
+    pub text: String,
+    /// Source line of the annotation
+    pub line: usize,
+    expression: Spec,
+}
+
+impl Property {
+    /// The property an attribute such as `ensure(out == 1)` states; `None` for other attributes
+    pub fn from_attribute(attribute: &str, line: usize) -> Option<Result<Self, FCMCError>> {
+        let attribute = attribute.trim();
+        let (kind, body) = if let Some(body) = attribute.strip_prefix("require") {
+            (PropertyKind::Require, body)
+        } else if let Some(body) = attribute.strip_prefix("ensure") {
+            (PropertyKind::Ensure, body)
+        } else {
+            return None;
+        };
+        let body = body.trim().strip_prefix('(')?.strip_suffix(')')?;
+        Some(
+            parse_spec(body)
+                .map(|expression| Self {
+                    kind,
+                    text: attribute.to_string(),
+                    line,
+                    expression,
+                })
+                .map_err(|message| {
+                    FCMCError::ParseError(format!(
+                        "Invalid property `{}` at line {}: {}",
+                        attribute, line, message
+                    ))
+                }),
+        )
+    }
+
+    fn holds(&self, values: &HashMap<String, BigUint>) -> Result<bool, FCMCError> {
+        self.expression
+            .evaluate(values)
+            .map(|value| !value.is_zero())
+            .map_err(|name| {
+                FCMCError::VerificationError(format!(
+                    "Property `{}` at line {} reads '{}', which is neither an input nor an output",
+                    self.text, self.line, name
+                ))
+            })
+    }
+}
+
+/// How the properties of a circuit were checked
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PropertyReport {
+    pub properties: usize,
+    /// Random assignments tried
+    pub tried: usize,
+    /// Assignments the preconditions and constraints accepted, on which every
+    /// postcondition held
+    pub checked: usize,
+}
+
+/// Remove the `require` and `ensure` annotations from `source`, returning the source the
+/// parser should see, with every other character in place, and the properties
+pub fn extract_properties(source: &str) -> Result<(String, Vec<Property>), FCMCError> {
+    let mut stripped = source.as_bytes().to_vec();
+    let mut properties = Vec::new();
+    // Whether the annotations seen since the last item belong to the next one
+    let mut pending = false;
+    let mut position = 0;
+    let bytes = source.as_bytes();
+    while position < bytes.len() {
+        let rest = &source[position..];
+        if rest.starts_with("//") {
+            position += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("#[") {
+            let end = attribute_end(rest).ok_or_else(|| {
+                FCMCError::ParseError(format!("Unterminated attribute at line {}", line_of(source, position)))
+            })?;
+            let line = line_of(source, position);
+            if let Some(property) = Property::from_attribute(&rest[2..end], line) {
+                properties.push(property?);
+                for byte in &mut stripped[position..=position + end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                pending = true;
+            }
+            position += end + 1;
+            continue;
+        }
+        if pending && !bytes[position].is_ascii_whitespace() {
+            let item: Vec<&str> = rest
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|word| !word.is_empty())
+                .take(3)
+                .collect();
+            let on_main = matches!(item.as_slice(), ["fn", "main", ..] | ["pub", "fn", "main"]);
+            if !on_main {
+                return Err(FCMCError::ParseError(format!(
+                    "Property annotations are only allowed on main, found one before line {}",
+                    line_of(source, position)
+                )));
+            }
+            pending = false;
+        }
+        position += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    if pending {
+        return Err(FCMCError::ParseError(
+            "Property annotations at the end of the source annotate nothing".to_string(),
+        ));
+    }
+
+    let stripped = String::from_utf8(stripped)
+        .map_err(|e| FCMCError::ParseError(format!("Invalid source after removing properties: {}", e)))?;
+    Ok((stripped, properties))
+}
+
+/// Check `properties` against `graph` on `samples` random inputs, failing with the first
+/// accepted input that breaks a postcondition
+pub fn check_properties(graph: &IRGraph, properties: &[Property], samples: usize) -> Result<PropertyReport, FCMCError> {
+    let mut report = PropertyReport {
+        properties: properties.len(),
+        ..PropertyReport::default()
+    };
+    if properties.is_empty() {
+        return Ok(report);
+    }
+    let names = input_names(graph);
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..samples {
+        report.tried += 1;
+        let inputs: HashMap<String, BigUint> = names
+            .iter()
+            .map(|name| (name.clone(), random_value(&mut rng)))
+            .collect();
+        // Inputs the circuit rejects have no proof, so no property is at stake
+        let evaluation = match evaluate(graph, &inputs) {
+            Ok(evaluation) if evaluation.is_satisfied() => evaluation,
+            _ => continue,
+        };
+        let mut values = inputs.clone();
+        values.extend(evaluation.outputs);
+
+        let mut accepted = true;
+        for property in properties
+            .iter()
+            .filter(|property| property.kind == PropertyKind::Require)
+        {
+            accepted &= property.holds(&values)?;
+        }
+        if !accepted {
+            continue;
+        }
+        for property in properties
+            .iter()
+            .filter(|property| property.kind == PropertyKind::Ensure)
+        {
+            if !property.holds(&values)? {
+                let mut assignment: Vec<String> = values
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                assignment.sort();
+                return Err(FCMCError::VerificationError(format!(
+                    "Property `{}` at line {} does not hold for [{}]",
+                    property.text,
+                    property.line,
+                    assignment.join(", ")
+                )));
+            }
+        }
+        report.checked += 1;
+    }
+
+    if report.checked == 0 {
+        tracing::warn!(
+            "None of {} random inputs satisfied the circuit and its preconditions; properties are unchecked",
+            report.tried
+        );
+    }
+    Ok(report)
+}
+
+/// Offset of the `]` closing the attribute that `text` starts with
+pub(crate) fn attribute_end(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (offset, c) in text.char_indices().skip(1) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub(crate) fn line_of(source: &str, position: usize) -> usize {
+    source[..position].matches('\n').count() + 1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpecOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl SpecOp {
+    fn parse(token: &str) -> Option<Self> {
+        match token {
+            "+" => Some(SpecOp::Add),
+            "-" => Some(SpecOp::Sub),
+            "*" => Some(SpecOp::Mul),
+            "==" => Some(SpecOp::Eq),
+            "!=" => Some(SpecOp::Ne),
+            "<" => Some(SpecOp::Lt),
+            "<=" => Some(SpecOp::Le),
+            ">" => Some(SpecOp::Gt),
+            ">=" => Some(SpecOp::Ge),
+            "&&" => Some(SpecOp::And),
+            "||" => Some(SpecOp::Or),
+            _ => None,
+        }
+    }
+
+    /// Binding strength; higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            SpecOp::Or => 1,
+            SpecOp::And => 2,
+            SpecOp::Eq | SpecOp::Ne | SpecOp::Lt | SpecOp::Le | SpecOp::Gt | SpecOp::Ge => 3,
+            SpecOp::Add | SpecOp::Sub => 4,
+            SpecOp::Mul => 5,
+        }
+    }
+
+    fn apply(self, a: &BigUint, b: &BigUint) -> BigUint {
+        let truth = |value: bool| if value { BigUint::one() } else { BigUint::zero() };
+        match self {
+            SpecOp::Add => field::add(a, b),
+            SpecOp::Sub => field::sub(a, b),
+            SpecOp::Mul => field::mul(a, b),
+            SpecOp::Eq => truth(a == b),
+            SpecOp::Ne => truth(a != b),
+            SpecOp::Lt => truth(a < b),
+            SpecOp::Le => truth(a <= b),
+            SpecOp::Gt => truth(a > b),
+            SpecOp::Ge => truth(a >= b),
+            SpecOp::And => truth(!a.is_zero() && !b.is_zero()),
+            SpecOp::Or => truth(!a.is_zero() || !b.is_zero()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Spec {
+    Const(BigUint),
+    Var(String),
+    Not(Box<Spec>),
+    Neg(Box<Spec>),
+    Binary(SpecOp, Box<Spec>, Box<Spec>),
+}
+
+impl Spec {
+    /// The value of the expression; `Err` names a variable without a value
+    fn evaluate(&self, values: &HashMap<String, BigUint>) -> Result<BigUint, String> {
+        Ok(match self {
+            Spec::Const(value) => value.clone(),
+            Spec::Var(name) => values.get(name).cloned().ok_or_else(|| name.clone())?,
+            Spec::Not(inner) => {
+                if inner.evaluate(values)?.is_zero() {
+                    BigUint::one()
+                } else {
+                    BigUint::zero()
+                }
+            }
+            Spec::Neg(inner) => field::neg(&inner.evaluate(values)?),
+            Spec::Binary(op, a, b) => op.apply(&a.evaluate(values)?, &b.evaluate(values)?),
+        })
+    }
+}
+
+fn parse_spec(text: &str) -> Result<Spec, String> {
+    let tokens = tokenize(text)?;
+    let mut position = 0;
+    let spec = parse_binary(&tokens, &mut position, 1)?;
+    if position != tokens.len() {
+        return Err(format!("unexpected '{}'", tokens[position]));
+    }
+    Ok(spec)
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        if c.is_whitespace() {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        if c.is_alphanumeric() || c == '_' {
+            while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '_' || chars[index] == '.')
+            {
+                index += 1;
+            }
+        } else {
+            let pair: String = chars[index..chars.len().min(index + 2)].iter().collect();
+            index += if ["==", "!=", "<=", ">=", "&&", "||"].contains(&pair.as_str()) {
+                2
+            } else if "+-*<>!()".contains(c) {
+                1
+            } else {
+                return Err(format!("unexpected character '{}'", c));
+            };
+        }
+        tokens.push(chars[start..index].iter().collect());
+    }
+    Ok(tokens)
+}
+
+/// Precedence climbing over the binary operators binding at least as tightly as `min`
+fn parse_binary(tokens: &[String], position: &mut usize, min: u8) -> Result<Spec, String> {
+    let mut left = parse_unary(tokens, position)?;
+    while let Some(op) = tokens.get(*position).and_then(|token| SpecOp::parse(token)) {
+        if op.precedence() < min {
+            break;
+        }
+        *position += 1;
+        let right = parse_binary(tokens, position, op.precedence() + 1)?;
+        left = Spec::Binary(op, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+}
+
+fn parse_unary(tokens: &[String], position: &mut usize) -> Result<Spec, String> {
+    let token = tokens.get(*position).ok_or("unexpected end of expression")?;
+    *position += 1;
+    match token.as_str() {
+        "!" => Ok(Spec::Not(Box::new(parse_unary(tokens, position)?))),
+        "-" => Ok(Spec::Neg(Box::new(parse_unary(tokens, position)?))),
+        "(" => {
+            let inner = parse_binary(tokens, position, 1)?;
+            if tokens.get(*position).map(String::as_str) != Some(")") {
+                return Err("expected ')'".to_string());
+            }
+            *position += 1;
+            Ok(inner)
+        }
+        "true" => Ok(Spec::Const(BigUint::one())),
+        "false" => Ok(Spec::Const(BigUint::zero())),
+        _ if token.starts_with(|c: char| c.is_ascii_digit()) => token
+            .parse::<BigUint>()
+            .map(|value| Spec::Const(value % field::modulus()))
+            .map_err(|_| format!("invalid number '{}'", token)),
+        _ if token.starts_with(|c: char| c.is_alphabetic() || c == '_') => Ok(Spec::Var(token.clone())),
+        _ => Err(format!("unexpected '{}'", token)),
+    }
+}
@@ -0,0 +1,257 @@
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::collections::BTreeMap;
+
+/// Default width of the range table used when merging into lookups
+pub const DEFAULT_LOOKUP_BITS: u32 = 8;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RangeCheckReport {
+    /// Identical checks on the same wire
+    pub duplicates: usize,
+    /// Looser checks dominated by a tighter one on the same wire
+    pub tightened: usize,
+    /// Checks that hold trivially (constants, booleans, full field width)
+    pub elided: usize,
+    /// Small checks folded into shared lookup nodes
+    pub merged: usize,
+}
+
+impl RangeCheckReport {
+    pub fn total(&self) -> usize {
+        self.duplicates + self.tightened + self.elided + self.merged
+    }
+}
+
+/// Canonicalizes, deduplicates, and tightens range-check constraints
+pub struct RangeCheckPass {
+    lookup_bits: Option<u32>,
+    last_rewrites: usize,
+}
+
+impl RangeCheckPass {
+    pub fn new() -> Self {
+        Self {
+            lookup_bits: None,
+            last_rewrites: 0,
+        }
+    }
+
+    /// Merge checks of at most `bits` bits into one lookup per width
+    pub fn with_lookup_bits(mut self, bits: u32) -> Self {
+        self.lookup_bits = Some(bits);
+        self
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<RangeCheckReport, FCMCError> {
+        let booleans = BooleanityAnalysis::run(graph);
+        self.run_with(graph, &booleans)
+    }
+
+    /// Run with a precomputed booleanity analysis of `graph`
+    pub fn run_with(&self, graph: &mut IRGraph, booleans: &BooleanityAnalysis) -> Result<RangeCheckReport, FCMCError> {
+        let mut report = RangeCheckReport::default();
+
+        // wire -> [(constraint node, bits)]
+        let mut checks: BTreeMap<usize, Vec<(usize, u32)>> = BTreeMap::new();
+        for (constraint_id, wire, bits) in range_checks(graph) {
+            // Single-bit checks are booleanity constraints and handled by that pass
+            if bits <= 1 {
+                continue;
+            }
+            checks.entry(wire).or_default().push((constraint_id, bits));
+        }
+
+        let mut to_remove = Vec::new();
+        let mut survivors = Vec::new();
+
+        for (wire, mut wire_checks) in checks {
+            if trivially_holds(graph, booleans, wire, &wire_checks) {
+                report.elided += wire_checks.len();
+                to_remove.extend(wire_checks.iter().map(|(id, _)| *id));
+                continue;
+            }
+
+            // Keep the tightest check, ties broken by node id for determinism
+            wire_checks.sort_by_key(|&(id, bits)| (bits, id));
+            let (kept_id, kept_bits) = wire_checks[0];
+            for &(id, bits) in &wire_checks[1..] {
+                if bits == kept_bits {
+                    report.duplicates += 1;
+                } else {
+                    report.tightened += 1;
+                }
+                to_remove.push(id);
+            }
+
+            survivors.push((kept_id, wire, kept_bits));
+        }
+
+        for id in to_remove {
+            graph.remove_node(id);
+        }
+
+        if let Some(lookup_bits) = self.lookup_bits {
+            report.merged = merge_into_lookups(graph, &survivors, lookup_bits);
+        }
+
+        Ok(report)
+    }
+}
+
+impl OptimizationPass for RangeCheckPass {
+    fn name(&self) -> &str {
+        "rangecheck"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let booleans = analyses.booleanity(graph);
+        let report = self.run_with(graph, booleans)?;
+        tracing::debug!("Range-check pass removed {} checks ({:?})", report.total(), report);
+        self.last_rewrites = report.total();
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+fn trivially_holds(
+    graph: &IRGraph,
+    booleans: &BooleanityAnalysis,
+    wire: usize,
+    checks: &[(usize, u32)],
+) -> bool {
+    let tightest = checks.iter().map(|&(_, bits)| bits).min().unwrap_or(0);
+    // Every element of the active field fits in its modulus' bit length
+    if tightest >= field::active().bits() || booleans.is_boolean(wire) {
+        return true;
+    }
+
+    match graph.get_node(wire).map(|node| &node.node_type) {
+        Some(IRNodeType::Constant(value)) => match value.trim().parse::<BigUint>() {
+            Ok(value) => value.bits() <= tightest as u64,
+            // Leave unparsable constants for the backend to reject
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Replace groups of small range checks with a single lookup node per bit width.
+/// Returns the number of individual checks folded away.
+fn merge_into_lookups(graph: &mut IRGraph, survivors: &[(usize, usize, u32)], lookup_bits: u32) -> usize {
+    let mut groups: BTreeMap<u32, Vec<(usize, usize)>> = BTreeMap::new();
+    for &(constraint_id, wire, bits) in survivors {
+        if bits <= lookup_bits {
+            groups.entry(bits).or_default().push((constraint_id, wire));
+        }
+    }
+
+    let mut merged = 0;
+    for (bits, group) in groups {
+        if group.len() < 2 {
+            continue;
+        }
+
+        let lookup = graph.add_node(IRNodeType::Lookup, Type::Bool, None);
+        if let Some(node) = graph.get_node_mut(lookup) {
+            node.attributes.insert("table".to_string(), "range".to_string());
+            node.attributes.insert("bits".to_string(), bits.to_string());
+        }
+
+        for (constraint_id, wire) in group {
+            graph.add_edge(wire, lookup, EdgeType::Constraint);
+            graph.remove_node(constraint_id);
+            merged += 1;
+        }
+        // The lookup node itself replaces one of the checks
+        merged -= 1;
+    }
+
+    merged
+}
+
+/// All live `Range` constraints as (constraint node, checked wire, bits)
+pub fn range_checks(graph: &IRGraph) -> Vec<(usize, usize, u32)> {
+    let mut result = Vec::new();
+
+    for node in graph.live_nodes() {
+        let bits = match &node.node_type {
+            IRNodeType::Constraint(ConstraintType::Range { bits }) => *bits,
+            _ => continue,
+        };
+        if let [wire] = graph.get_predecessors(node.id).as_slice() {
+            result.push((node.id, *wire, bits));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::interpreter::assert_equivalent;
+    use std::collections::HashMap;
+
+    /// `out = x`, with `x` checked to 16 bits twice and to 32 bits once, and a constant
+    /// checked to 8 bits
+    fn overlapping_checks() -> IRGraph {
+        let mut graph = IRGraph::new();
+        let x = graph.add_node(IRNodeType::Input("x".to_string()), Type::Field, None);
+        let five = graph.add_node(IRNodeType::Constant("5".to_string()), Type::Field, None);
+        let out = graph.add_node(IRNodeType::Output("out".to_string()), Type::Field, None);
+        graph.add_edge(x, out, EdgeType::DataFlow);
+        for (wire, bits) in [(x, 32), (x, 16), (x, 16), (five, 8)] {
+            let check = graph.add_node(IRNodeType::Constraint(ConstraintType::Range { bits }), Type::Bool, None);
+            graph.add_edge(wire, check, EdgeType::Constraint);
+        }
+        graph
+    }
+
+    #[test]
+    fn keeps_only_the_tightest_check() {
+        let before = overlapping_checks();
+        let mut after = before.clone();
+        let report = RangeCheckPass::new().run(&mut after).unwrap();
+        assert_eq!(
+            report,
+            RangeCheckReport {
+                duplicates: 1,
+                tightened: 1,
+                elided: 1,
+                merged: 0,
+            }
+        );
+        assert_eq!(range_checks(&after), [(4, 0, 16)]);
+
+        let inputs: Vec<HashMap<String, BigUint>> = [0u64, 65535, 65536, 1 << 40]
+            .into_iter()
+            .map(|x| HashMap::from([("x".to_string(), BigUint::from(x))]))
+            .collect();
+        assert_equivalent(&before, &after, &inputs);
+    }
+
+    #[test]
+    fn merges_small_checks_into_one_lookup() {
+        let mut graph = IRGraph::new();
+        for name in ["a", "b", "c"] {
+            let wire = graph.add_node(IRNodeType::Input(name.to_string()), Type::Field, None);
+            let check = graph.add_node(IRNodeType::Constraint(ConstraintType::Range { bits: 8 }), Type::Bool, None);
+            graph.add_edge(wire, check, EdgeType::Constraint);
+        }
+        let report = RangeCheckPass::new().with_lookup_bits(DEFAULT_LOOKUP_BITS).run(&mut graph).unwrap();
+        assert_eq!(report.merged, 2);
+        assert!(range_checks(&graph).is_empty());
+        let lookups: Vec<_> = graph.live_nodes().filter(|node| node.node_type == IRNodeType::Lookup).collect();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(graph.get_predecessors(lookups[0].id).len(), 3);
+    }
+}
@@ -0,0 +1,278 @@
+//! User-supplied algebraic rewrite rules.
+//!
+//! Rules are read at runtime from a small text format, one rule per line:
+//!
+//! ```text
+//! # comments start with '#'
+//! (mul ?x 2) => (add ?x ?x) if target.add_cheaper_than_mul
+//! (sub (mul ?y ?y) (mul ?x ?x)) => (mul (add ?y ?x) (sub ?y ?x))
+//! ```
+//!
+//! Patterns are s-expressions over `add`, `sub`, `mul` and `neg`, field constants and
+//! `?`-prefixed variables. Equality saturation applies every rule whose condition holds
+//! for the target, so rules only take effect in pipelines that include `saturation`.
+
+use crate::optimization::cost::CostModel;
+use crate::optimization::field;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleOp {
+    Add,
+    Sub,
+    Mul,
+    Neg,
+}
+
+impl RuleOp {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "add" => Some(RuleOp::Add),
+            "sub" => Some(RuleOp::Sub),
+            "mul" => Some(RuleOp::Mul),
+            "neg" => Some(RuleOp::Neg),
+            _ => None,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            RuleOp::Neg => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Var(String),
+    Const(BigUint),
+    Op(RuleOp, Vec<Pattern>),
+}
+
+impl Pattern {
+    fn variables(&self, vars: &mut HashSet<String>) {
+        match self {
+            Pattern::Var(name) => {
+                vars.insert(name.clone());
+            }
+            Pattern::Const(_) => {}
+            Pattern::Op(_, args) => {
+                for arg in args {
+                    arg.variables(vars);
+                }
+            }
+        }
+    }
+}
+
+/// Per-operation costs a condition can compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostTerm {
+    Add,
+    Mul,
+    ConstMul,
+    Div,
+}
+
+impl CostTerm {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "add" => Some(CostTerm::Add),
+            "mul" => Some(CostTerm::Mul),
+            "const_mul" => Some(CostTerm::ConstMul),
+            "div" => Some(CostTerm::Div),
+            _ => None,
+        }
+    }
+
+    fn cost(self, cost: &CostModel) -> u32 {
+        match self {
+            CostTerm::Add => cost.add,
+            CostTerm::Mul => cost.mul,
+            CostTerm::ConstMul => cost.const_mul,
+            CostTerm::Div => cost.div,
+        }
+    }
+}
+
+/// Target predicate guarding a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// `target.<a>_cheaper_than_<b>`
+    CheaperThan(CostTerm, CostTerm),
+    /// `target.<a>_free`
+    Free(CostTerm),
+}
+
+impl Condition {
+    fn parse(text: &str) -> Option<Self> {
+        let predicate = text.trim().strip_prefix("target.")?;
+        if let Some((a, b)) = predicate.split_once("_cheaper_than_") {
+            return Some(Condition::CheaperThan(CostTerm::parse(a)?, CostTerm::parse(b)?));
+        }
+        let term = predicate.strip_suffix("_free")?;
+        Some(Condition::Free(CostTerm::parse(term)?))
+    }
+
+    pub fn holds(&self, cost: &CostModel) -> bool {
+        match self {
+            Condition::CheaperThan(a, b) => a.cost(cost) < b.cost(cost),
+            Condition::Free(term) => term.cost(cost) == 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewriteRule {
+    /// Where the rule was defined, for diagnostics
+    pub origin: String,
+    pub lhs: Pattern,
+    pub rhs: Pattern,
+    pub condition: Option<Condition>,
+}
+
+impl RewriteRule {
+    pub fn applies_to(&self, cost: &CostModel) -> bool {
+        self.condition.map_or(true, |condition| condition.holds(cost))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleSet {
+    rules: Vec<RewriteRule>,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, FCMCError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            FCMCError::OptimizationError(format!("Failed to read rewrite rules from {}: {}", path.display(), e))
+        })?;
+        Self::parse_named(&text, &path.display().to_string())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, FCMCError> {
+        Self::parse_named(text, "<rules>")
+    }
+
+    fn parse_named(text: &str, source: &str) -> Result<Self, FCMCError> {
+        let mut rules = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let origin = format!("{}:{}", source, index + 1);
+            let rule = parse_rule(line, &origin)
+                .map_err(|message| FCMCError::OptimizationError(format!("{}: {}", origin, message)))?;
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn extend(&mut self, other: RuleSet) {
+        self.rules.extend(other.rules);
+    }
+
+    pub fn rules(&self) -> &[RewriteRule] {
+        &self.rules
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+fn parse_rule(line: &str, origin: &str) -> Result<RewriteRule, String> {
+    let (lhs, rest) = line.split_once("=>").ok_or("expected '=>'")?;
+    let (rhs, condition) = match rest.split_once(" if ") {
+        Some((rhs, condition)) => {
+            let parsed =
+                Condition::parse(condition).ok_or_else(|| format!("unknown condition '{}'", condition.trim()))?;
+            (rhs, Some(parsed))
+        }
+        None => (rest, None),
+    };
+
+    let lhs = parse_pattern(lhs)?;
+    let rhs = parse_pattern(rhs)?;
+    if matches!(lhs, Pattern::Var(_)) {
+        return Err("left-hand side must not be a bare variable".to_string());
+    }
+
+    let mut bound = HashSet::new();
+    lhs.variables(&mut bound);
+    let mut used = HashSet::new();
+    rhs.variables(&mut used);
+    if let Some(unbound) = used.difference(&bound).next() {
+        return Err(format!("variable '?{}' is not bound by the left-hand side", unbound));
+    }
+
+    Ok(RewriteRule {
+        origin: origin.to_string(),
+        lhs,
+        rhs,
+        condition,
+    })
+}
+
+fn parse_pattern(text: &str) -> Result<Pattern, String> {
+    let tokens = tokenize(text);
+    let mut position = 0;
+    let pattern = parse_tokens(&tokens, &mut position)?;
+    if position != tokens.len() {
+        return Err(format!("unexpected '{}' after pattern", tokens[position]));
+    }
+    Ok(pattern)
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_tokens(tokens: &[String], position: &mut usize) -> Result<Pattern, String> {
+    let token = tokens.get(*position).ok_or("unexpected end of pattern")?;
+    *position += 1;
+
+    match token.as_str() {
+        "(" => {
+            let name = tokens.get(*position).ok_or("expected operation")?;
+            let op = RuleOp::parse(name).ok_or_else(|| format!("unknown operation '{}'", name))?;
+            *position += 1;
+
+            let mut args = Vec::new();
+            while tokens.get(*position).map(String::as_str) != Some(")") {
+                args.push(parse_tokens(tokens, position)?);
+            }
+            *position += 1;
+
+            if args.len() != op.arity() {
+                return Err(format!("'{}' takes {} operand(s), got {}", name, op.arity(), args.len()));
+            }
+            Ok(Pattern::Op(op, args))
+        }
+        ")" => Err("unexpected ')'".to_string()),
+        atom => match atom.strip_prefix('?') {
+            Some(name) if !name.is_empty() => Ok(Pattern::Var(name.to_string())),
+            Some(_) => Err("empty variable name".to_string()),
+            None => field::parse_element(atom)
+                .map(Pattern::Const)
+                .ok_or_else(|| format!("expected a constant or variable, got '{}'", atom)),
+        },
+    }
+}
@@ -0,0 +1,771 @@
+//! Equality saturation over the arithmetic part of the IR.
+//!
+//! Arithmetic nodes are loaded into an e-graph, algebraic rewrites are applied until
+//! saturation or until the budget runs out, and the cheapest equivalent circuit
+//! according to the target cost model is extracted back into the IR.
+
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::cost::CostModel;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::rules::{Pattern, RewriteRule, RuleOp};
+use crate::platform::Instant;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+type ClassId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Neg,
+    Const(BigUint),
+    /// Any IR node the e-graph treats as opaque
+    Leaf(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct ENode {
+    op: Op,
+    children: Vec<ClassId>,
+}
+
+impl ENode {
+    fn new(op: Op, children: Vec<ClassId>) -> Self {
+        Self { op, children }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SaturationConfig {
+    pub timeout: Duration,
+    pub max_iterations: usize,
+    pub max_nodes: usize,
+}
+
+impl Default for SaturationConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(500),
+            max_iterations: 30,
+            max_nodes: 50_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Saturated,
+    Timeout,
+    IterationLimit,
+    NodeLimit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaturationReport {
+    pub iterations: usize,
+    pub egraph_nodes: usize,
+    pub cost_before: u64,
+    pub cost_after: u64,
+    /// Whether the extracted circuit replaced the original one
+    pub applied: bool,
+    pub stop_reason: StopReason,
+}
+
+struct EGraph {
+    parents: Vec<ClassId>,
+    memo: HashMap<ENode, ClassId>,
+    classes: BTreeMap<ClassId, Vec<ENode>>,
+}
+
+impl EGraph {
+    fn new() -> Self {
+        Self {
+            parents: Vec::new(),
+            memo: HashMap::new(),
+            classes: BTreeMap::new(),
+        }
+    }
+
+    fn find(&self, mut id: ClassId) -> ClassId {
+        while self.parents[id] != id {
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn canonicalize(&self, node: &ENode) -> ENode {
+        ENode::new(
+            node.op.clone(),
+            node.children.iter().map(|&child| self.find(child)).collect(),
+        )
+    }
+
+    fn add(&mut self, node: ENode) -> ClassId {
+        let node = self.canonicalize(&node);
+        if let Some(&id) = self.memo.get(&node) {
+            return self.find(id);
+        }
+
+        let id = self.parents.len();
+        self.parents.push(id);
+        self.memo.insert(node.clone(), id);
+        self.classes.insert(id, vec![node]);
+        id
+    }
+
+    fn union(&mut self, a: ClassId, b: ClassId) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+
+        // Keep the older class as representative for stable ids
+        let (root, child) = if a < b { (a, b) } else { (b, a) };
+        self.parents[child] = root;
+        let nodes = self.classes.remove(&child).unwrap_or_default();
+        self.classes.entry(root).or_default().extend(nodes);
+        true
+    }
+
+    /// Restore the congruence invariant after a batch of unions
+    fn rebuild(&mut self) {
+        loop {
+            let mut memo = HashMap::new();
+            let mut merges = Vec::new();
+
+            let ids: Vec<ClassId> = self.classes.keys().copied().collect();
+            for id in ids {
+                let mut nodes: Vec<ENode> = self.classes[&id]
+                    .iter()
+                    .map(|node| self.canonicalize(node))
+                    .collect();
+                nodes.sort();
+                nodes.dedup();
+
+                for node in &nodes {
+                    match memo.get(node) {
+                        Some(&other) if other != id => merges.push((other, id)),
+                        Some(_) => {}
+                        None => {
+                            memo.insert(node.clone(), id);
+                        }
+                    }
+                }
+                self.classes.insert(id, nodes);
+            }
+
+            self.memo = memo;
+            if merges.is_empty() {
+                break;
+            }
+            for (a, b) in merges {
+                self.union(a, b);
+            }
+        }
+    }
+
+    fn node_count(&self) -> usize {
+        self.memo.len()
+    }
+
+    fn nodes(&self, id: ClassId) -> Vec<ENode> {
+        self.classes.get(&self.find(id)).cloned().unwrap_or_default()
+    }
+
+    fn constant(&self, id: ClassId) -> Option<BigUint> {
+        self.classes.get(&self.find(id))?.iter().find_map(|node| match &node.op {
+            Op::Const(value) => Some(value.clone()),
+            _ => None,
+        })
+    }
+
+    fn add_const(&mut self, value: BigUint) -> ClassId {
+        self.add(ENode::new(Op::Const(value), Vec::new()))
+    }
+
+    /// Apply every rewrite to every e-node once; returns the number of new equalities
+    fn apply_rewrites(&mut self, rules: &[RewriteRule], deadline: Option<Instant>) -> usize {
+        let snapshot: Vec<(ClassId, Vec<ENode>)> =
+            self.classes.iter().map(|(&id, nodes)| (id, nodes.clone())).collect();
+
+        let mut unions = Vec::new();
+        for (id, nodes) in snapshot {
+            if past(deadline) {
+                break;
+            }
+            for node in nodes {
+                self.rewrite(id, &node, &mut unions);
+            }
+            for rule in rules {
+                for bindings in self.ematch(&rule.lhs, id) {
+                    let rewritten = self.instantiate(&rule.rhs, &bindings);
+                    unions.push((id, rewritten));
+                }
+            }
+        }
+
+        let mut changed = 0;
+        for (a, b) in unions {
+            if self.union(a, b) {
+                changed += 1;
+            }
+        }
+        self.rebuild();
+        changed
+    }
+
+    fn rewrite(&mut self, id: ClassId, node: &ENode, unions: &mut Vec<(ClassId, ClassId)>) {
+        let children = &node.children;
+
+        // Constant folding
+        if !children.is_empty() {
+            let values: Option<Vec<BigUint>> = children.iter().map(|&c| self.constant(c)).collect();
+            if let Some(values) = values {
+                let folded = match (&node.op, values.as_slice()) {
+                    (Op::Add, [a, b]) => Some(field::add(a, b)),
+                    (Op::Sub, [a, b]) => Some(field::sub(a, b)),
+                    (Op::Mul, [a, b]) => Some(field::mul(a, b)),
+                    (Op::Neg, [a]) => Some(field::neg(a)),
+                    _ => None,
+                };
+                if let Some(value) = folded {
+                    let constant = self.add_const(value);
+                    unions.push((id, constant));
+                }
+            }
+        }
+
+        match (&node.op, children.as_slice()) {
+            (Op::Add, &[a, b]) | (Op::Mul, &[a, b]) => {
+                let op = node.op.clone();
+
+                // Commutativity
+                let swapped = self.add(ENode::new(op.clone(), vec![b, a]));
+                unions.push((id, swapped));
+
+                // Associativity: (x op y) op b => x op (y op b)
+                for inner in self.nodes(a) {
+                    if inner.op == op {
+                        if let [x, y] = inner.children[..] {
+                            let right = self.add(ENode::new(op.clone(), vec![y, b]));
+                            let assoc = self.add(ENode::new(op.clone(), vec![x, right]));
+                            unions.push((id, assoc));
+                        }
+                    }
+                }
+
+                let identity = if op == Op::Add { BigUint::zero() } else { BigUint::one() };
+                if self.constant(b) == Some(identity) {
+                    unions.push((id, a));
+                }
+                if op == Op::Mul && self.constant(b).map_or(false, |v| v.is_zero()) {
+                    unions.push((id, b));
+                }
+
+                if op == Op::Add {
+                    self.factor(id, Op::Add, a, b, unions);
+                } else {
+                    self.distribute(id, a, b, unions);
+                }
+            }
+            (Op::Sub, &[a, b]) => {
+                if self.find(a) == self.find(b) {
+                    let zero = self.add_const(BigUint::zero());
+                    unions.push((id, zero));
+                }
+                if self.constant(b).map_or(false, |v| v.is_zero()) {
+                    unions.push((id, a));
+                }
+                self.factor(id, Op::Sub, a, b, unions);
+            }
+            (Op::Neg, &[a]) => {
+                for inner in self.nodes(a) {
+                    if let (Op::Neg, [x]) = (&inner.op, inner.children.as_slice()) {
+                        unions.push((id, *x));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// a*x op a*y => a * (x op y)
+    fn factor(&mut self, id: ClassId, op: Op, left: ClassId, right: ClassId, unions: &mut Vec<(ClassId, ClassId)>) {
+        for l in self.nodes(left) {
+            if l.op != Op::Mul {
+                continue;
+            }
+            for r in self.nodes(right) {
+                if r.op != Op::Mul {
+                    continue;
+                }
+                if let ([a, x], [c, y]) = (&l.children[..], &r.children[..]) {
+                    if self.find(*a) == self.find(*c) {
+                        let inner = self.add(ENode::new(op.clone(), vec![*x, *y]));
+                        let factored = self.add(ENode::new(Op::Mul, vec![*a, inner]));
+                        unions.push((id, factored));
+                    }
+                }
+            }
+        }
+    }
+
+    /// a * (x op y) => a*x op a*y
+    fn distribute(&mut self, id: ClassId, a: ClassId, sum: ClassId, unions: &mut Vec<(ClassId, ClassId)>) {
+        for inner in self.nodes(sum) {
+            if !matches!(inner.op, Op::Add | Op::Sub) {
+                continue;
+            }
+            if let [x, y] = inner.children[..] {
+                let ax = self.add(ENode::new(Op::Mul, vec![a, x]));
+                let ay = self.add(ENode::new(Op::Mul, vec![a, y]));
+                let distributed = self.add(ENode::new(inner.op.clone(), vec![ax, ay]));
+                unions.push((id, distributed));
+            }
+        }
+    }
+
+    /// Every way `pattern` matches class `id`, as variable bindings
+    fn ematch(&self, pattern: &Pattern, id: ClassId) -> Vec<HashMap<String, ClassId>> {
+        match pattern {
+            Pattern::Var(name) => vec![HashMap::from([(name.clone(), self.find(id))])],
+            Pattern::Const(value) => {
+                if self.constant(id).as_ref() == Some(value) {
+                    vec![HashMap::new()]
+                } else {
+                    Vec::new()
+                }
+            }
+            Pattern::Op(op, args) => {
+                let op = rule_op(*op);
+                let mut matches = Vec::new();
+                for node in self.nodes(id) {
+                    if node.op != op || node.children.len() != args.len() {
+                        continue;
+                    }
+                    let mut partial = vec![HashMap::new()];
+                    for (arg, &child) in args.iter().zip(&node.children) {
+                        let child_matches = self.ematch(arg, child);
+                        partial = partial
+                            .iter()
+                            .flat_map(|bindings| {
+                                child_matches
+                                    .iter()
+                                    .filter_map(move |child_bindings| merge_bindings(bindings, child_bindings))
+                            })
+                            .collect();
+                        if partial.is_empty() {
+                            break;
+                        }
+                    }
+                    matches.extend(partial);
+                }
+                matches
+            }
+        }
+    }
+
+    fn instantiate(&mut self, pattern: &Pattern, bindings: &HashMap<String, ClassId>) -> ClassId {
+        match pattern {
+            Pattern::Var(name) => bindings[name],
+            Pattern::Const(value) => self.add_const(value.clone()),
+            Pattern::Op(op, args) => {
+                let children = args.iter().map(|arg| self.instantiate(arg, bindings)).collect();
+                self.add(ENode::new(rule_op(*op), children))
+            }
+        }
+    }
+
+    fn node_cost(&self, node: &ENode, cost: &CostModel) -> u64 {
+        let unit = match node.op {
+            Op::Add | Op::Sub => cost.add,
+            Op::Neg => cost.const_mul,
+            Op::Mul => {
+                if node.children.iter().any(|&c| self.constant(c).is_some()) {
+                    cost.const_mul
+                } else {
+                    cost.mul
+                }
+            }
+            Op::Const(_) | Op::Leaf(_) => 0,
+        };
+        unit as u64
+    }
+
+    /// Cheapest e-node per class, computed to a fixed point
+    fn extract(&self, cost: &CostModel) -> HashMap<ClassId, (u64, ENode)> {
+        let mut best: HashMap<ClassId, (u64, ENode)> = HashMap::new();
+
+        loop {
+            let mut changed = false;
+            for (&id, nodes) in &self.classes {
+                for node in nodes {
+                    let child_costs: Option<u64> = node
+                        .children
+                        .iter()
+                        .map(|&c| best.get(&self.find(c)).map(|(cost, _)| *cost))
+                        .sum();
+                    let total = match child_costs {
+                        Some(children) => children.saturating_add(self.node_cost(node, cost)),
+                        None => continue,
+                    };
+                    let improves = best.get(&id).map_or(true, |(current, _)| total < *current);
+                    if improves {
+                        best.insert(id, (total, node.clone()));
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// Cost of the extracted circuit with shared classes counted once
+    fn dag_cost(&self, roots: &[ClassId], best: &HashMap<ClassId, (u64, ENode)>, cost: &CostModel) -> u64 {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<ClassId> = roots.iter().map(|&r| self.find(r)).collect();
+        let mut total = 0u64;
+
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some((_, node)) = best.get(&id) {
+                total += self.node_cost(node, cost);
+                stack.extend(node.children.iter().map(|&c| self.find(c)));
+            }
+        }
+
+        total
+    }
+}
+
+fn rule_op(op: RuleOp) -> Op {
+    match op {
+        RuleOp::Add => Op::Add,
+        RuleOp::Sub => Op::Sub,
+        RuleOp::Mul => Op::Mul,
+        RuleOp::Neg => Op::Neg,
+    }
+}
+
+/// Combine two sets of bindings, failing if a variable is bound to different classes
+fn merge_bindings(
+    a: &HashMap<String, ClassId>,
+    b: &HashMap<String, ClassId>,
+) -> Option<HashMap<String, ClassId>> {
+    let mut merged = a.clone();
+    for (name, &class) in b {
+        match merged.get(name) {
+            Some(&bound) if bound != class => return None,
+            _ => {
+                merged.insert(name.clone(), class);
+            }
+        }
+    }
+    Some(merged)
+}
+
+/// Time-budgeted equality saturation stage
+pub struct EqualitySaturation {
+    config: SaturationConfig,
+    cost: CostModel,
+    rules: Vec<RewriteRule>,
+}
+
+impl EqualitySaturation {
+    pub fn new(config: SaturationConfig, cost: CostModel) -> Self {
+        Self {
+            config,
+            cost,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Also apply user rules, keeping only those whose condition holds for the cost model
+    pub fn with_rules(mut self, rules: &[RewriteRule]) -> Self {
+        self.rules
+            .extend(rules.iter().filter(|rule| rule.applies_to(&self.cost)).cloned());
+        self
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<SaturationReport, FCMCError> {
+        // A timeout too long to represent, such as `Duration::MAX`, means none
+        let deadline = Instant::now().checked_add(self.config.timeout);
+        let mut egraph = EGraph::new();
+
+        // Load arithmetic nodes; everything else becomes an opaque leaf
+        let mut classes: HashMap<usize, ClassId> = HashMap::new();
+        let mut arithmetic = Vec::new();
+        for node_id in graph.topological_sort() {
+            let node = match graph.get_node(node_id) {
+                Some(node) => node,
+                None => continue,
+            };
+            let operands = graph.get_predecessors(node_id);
+            let op = match (&node.node_type, operands.len()) {
+                (IRNodeType::Add, 2) => Some(Op::Add),
+                (IRNodeType::Sub, 2) => Some(Op::Sub),
+                (IRNodeType::Mul, 2) => Some(Op::Mul),
+                (IRNodeType::Neg, 1) => Some(Op::Neg),
+                (IRNodeType::Constant(value), 0) => field::parse_element(value).map(Op::Const),
+                _ => None,
+            };
+
+            let class = match op {
+                Some(op) => {
+                    let children = operands
+                        .iter()
+                        .map(|operand| leaf_or_class(&mut egraph, &mut classes, *operand))
+                        .collect();
+                    arithmetic.push(node_id);
+                    egraph.add(ENode::new(op, children))
+                }
+                None => egraph.add(ENode::new(Op::Leaf(node_id), Vec::new())),
+            };
+            classes.insert(node_id, class);
+        }
+
+        // Arithmetic results consumed outside the arithmetic fragment
+        let arithmetic_set: HashSet<usize> = arithmetic.iter().copied().collect();
+        let roots: Vec<usize> = arithmetic
+            .iter()
+            .copied()
+            .filter(|&id| !matches!(graph.get_node(id).map(|n| &n.node_type), Some(IRNodeType::Constant(_))))
+            .filter(|&id| graph.get_successors(id).iter().any(|s| !arithmetic_set.contains(s)))
+            .collect();
+        let root_classes: Vec<ClassId> = roots.iter().map(|r| classes[r]).collect();
+
+        let cost_before = ir_cost(graph, &arithmetic, &self.cost);
+
+        let mut iterations = 0;
+        let stop_reason = loop {
+            if past(deadline) {
+                break StopReason::Timeout;
+            }
+            if iterations >= self.config.max_iterations {
+                break StopReason::IterationLimit;
+            }
+            if egraph.node_count() >= self.config.max_nodes {
+                break StopReason::NodeLimit;
+            }
+            iterations += 1;
+            if egraph.apply_rewrites(&self.rules, deadline) == 0 {
+                break StopReason::Saturated;
+            }
+        };
+
+        let best = egraph.extract(&self.cost);
+        let cost_after = egraph.dag_cost(&root_classes, &best, &self.cost);
+        let applied = cost_after < cost_before;
+
+        if applied {
+            let mut built = HashMap::new();
+            for (&root, &class) in roots.iter().zip(&root_classes) {
+                let new_id = materialize(&egraph, &best, graph, class, &mut built);
+                if new_id != root {
+                    graph.replace_uses(root, new_id);
+                }
+            }
+            remove_unused(graph, &arithmetic);
+        }
+
+        Ok(SaturationReport {
+            iterations,
+            egraph_nodes: egraph.node_count(),
+            cost_before,
+            cost_after,
+            applied,
+            stop_reason,
+        })
+    }
+}
+
+fn past(deadline: Option<Instant>) -> bool {
+    deadline.map_or(false, |deadline| Instant::now() >= deadline)
+}
+
+/// Cost of the arithmetic fragment as it currently stands in the IR
+fn ir_cost(graph: &IRGraph, arithmetic: &[usize], cost: &CostModel) -> u64 {
+    let is_constant = |id: usize| {
+        matches!(graph.get_node(id).map(|node| &node.node_type), Some(IRNodeType::Constant(_)))
+    };
+
+    arithmetic
+        .iter()
+        .map(|&id| {
+            let unit = match graph.get_node(id).map(|node| &node.node_type) {
+                Some(IRNodeType::Add) | Some(IRNodeType::Sub) => cost.add,
+                Some(IRNodeType::Neg) => cost.const_mul,
+                Some(IRNodeType::Mul) => {
+                    if graph.get_predecessors(id).into_iter().any(is_constant) {
+                        cost.const_mul
+                    } else {
+                        cost.mul
+                    }
+                }
+                _ => 0,
+            };
+            unit as u64
+        })
+        .sum()
+}
+
+impl OptimizationPass for EqualitySaturation {
+    fn name(&self) -> &str {
+        "saturation"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let report = EqualitySaturation::run(self, graph)?;
+        tracing::debug!(
+            "Equality saturation stopped after {} iterations ({:?}), cost {} -> {}",
+            report.iterations,
+            report.stop_reason,
+            report.cost_before,
+            if report.applied { report.cost_after } else { report.cost_before }
+        );
+        Ok(report.applied)
+    }
+}
+
+fn leaf_or_class(egraph: &mut EGraph, classes: &mut HashMap<usize, ClassId>, node_id: usize) -> ClassId {
+    match classes.get(&node_id) {
+        Some(&class) => class,
+        None => {
+            let class = egraph.add(ENode::new(Op::Leaf(node_id), Vec::new()));
+            classes.insert(node_id, class);
+            class
+        }
+    }
+}
+
+fn materialize(
+    egraph: &EGraph,
+    best: &HashMap<ClassId, (u64, ENode)>,
+    graph: &mut IRGraph,
+    class: ClassId,
+    built: &mut HashMap<ClassId, usize>,
+) -> usize {
+    let class = egraph.find(class);
+    if let Some(&id) = built.get(&class) {
+        return id;
+    }
+
+    let node = best[&class].1.clone();
+    let id = match node.op {
+        Op::Leaf(id) => id,
+        Op::Const(value) => graph.add_node(IRNodeType::Constant(value.to_string()), Type::Field, None),
+        op => {
+            let children: Vec<usize> = node
+                .children
+                .iter()
+                .map(|&child| materialize(egraph, best, graph, child, built))
+                .collect();
+            let node_type = match op {
+                Op::Add => IRNodeType::Add,
+                Op::Sub => IRNodeType::Sub,
+                Op::Mul => IRNodeType::Mul,
+                _ => IRNodeType::Neg,
+            };
+            let id = graph.add_node(node_type, Type::Field, None);
+            for child in children {
+                graph.add_edge(child, id, EdgeType::DataFlow);
+            }
+            id
+        }
+    };
+
+    built.insert(class, id);
+    id
+}
+
+/// Drop replaced arithmetic nodes that no longer have consumers
+fn remove_unused(graph: &mut IRGraph, candidates: &[usize]) {
+    loop {
+        let unused: Vec<usize> = candidates
+            .iter()
+            .copied()
+            .filter(|&id| !graph.is_removed(id) && graph.get_successors(id).is_empty())
+            .collect();
+        if unused.is_empty() {
+            break;
+        }
+        for id in unused {
+            graph.remove_node(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TargetSystem;
+    use crate::optimization::interpreter::assert_equivalent;
+
+    /// `out = a*x + a*y`
+    fn common_factor() -> IRGraph {
+        let mut graph = IRGraph::new();
+        let [a, x, y] =
+            ["a", "x", "y"].map(|name| graph.add_node(IRNodeType::Input(name.to_string()), Type::Field, None));
+        let ax = graph.add_node(IRNodeType::Mul, Type::Field, None);
+        let ay = graph.add_node(IRNodeType::Mul, Type::Field, None);
+        let sum = graph.add_node(IRNodeType::Add, Type::Field, None);
+        let out = graph.add_node(IRNodeType::Output("out".to_string()), Type::Field, None);
+        for (from, to) in [(a, ax), (x, ax), (a, ay), (y, ay), (ax, sum), (ay, sum), (sum, out)] {
+            graph.add_edge(from, to, EdgeType::DataFlow);
+        }
+        graph
+    }
+
+    fn products(graph: &IRGraph) -> usize {
+        graph.live_nodes().filter(|node| node.node_type == IRNodeType::Mul).count()
+    }
+
+    #[test]
+    fn factors_out_a_shared_multiplier() {
+        let before = common_factor();
+        let mut after = before.clone();
+        let cost = CostModel::for_target(TargetSystem::R1CS);
+        let report = EqualitySaturation::new(SaturationConfig::default(), cost).run(&mut after).unwrap();
+        assert!(report.applied);
+        assert_eq!((report.cost_before, report.cost_after), (2, 1));
+        assert_eq!(products(&after), 1);
+
+        let inputs: Vec<HashMap<String, BigUint>> = [(2u64, 3u64, 5u64), (0, 7, 9), (11, 0, 1)]
+            .into_iter()
+            .map(|(a, x, y)| {
+                HashMap::from([
+                    ("a".to_string(), BigUint::from(a)),
+                    ("x".to_string(), BigUint::from(x)),
+                    ("y".to_string(), BigUint::from(y)),
+                ])
+            })
+            .collect();
+        assert_equivalent(&before, &after, &inputs);
+    }
+
+    #[test]
+    fn leaves_the_graph_alone_without_a_budget() {
+        let mut graph = common_factor();
+        let config = SaturationConfig {
+            max_iterations: 0,
+            ..SaturationConfig::default()
+        };
+        let report = EqualitySaturation::new(config, CostModel::default()).run(&mut graph).unwrap();
+        assert_eq!(report.stop_reason, StopReason::IterationLimit);
+        assert!(!report.applied);
+        assert_eq!(products(&graph), 2);
+    }
+}
@@ -0,0 +1,407 @@
+//! SMT-backed translation validation.
+//!
+//! The IR before and after a pass is encoded as one SMT-LIB query over the integers
+//! modulo the scalar field; the query is unsatisfiable exactly when no input makes the
+//! two graphs disagree on an output or on whether their constraints hold. Solving is
+//! slow, so validation is opt-in per pass.
+
+use crate::ir::{ConstraintType, IRGraph, IRNodeType, LookupTable};
+use crate::optimization::field;
+use crate::optimization::interpreter::{extracted_bit, input_names};
+use crate::FCMCError;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Answer of the solver to an equivalence query
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolverAnswer {
+    /// The graphs are equivalent
+    Unsat,
+    /// A distinguishing input exists; holds the solver's model
+    Sat(String),
+    /// Timeout or an answer the solver could not decide
+    Unknown(String),
+}
+
+/// An external SMT-LIB solver reading the query on stdin
+#[derive(Debug, Clone)]
+pub struct SmtSolver {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl SmtSolver {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    pub fn z3() -> Self {
+        Self::new("z3", &["-in", "-smt2"])
+    }
+
+    pub fn cvc5() -> Self {
+        Self::new("cvc5", &["--lang", "smt2", "--produce-models"])
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn check(&self, query: &str) -> Result<SolverAnswer, FCMCError> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| FCMCError::VerificationError(format!("Failed to start SMT solver '{}': {}", self.program, e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(query.as_bytes())
+                .map_err(|e| FCMCError::VerificationError(format!("Failed to send query to SMT solver: {}", e)))?;
+        }
+
+        let started = Instant::now();
+        loop {
+            let exited = child
+                .try_wait()
+                .map_err(|e| FCMCError::VerificationError(format!("SMT solver failed: {}", e)))?;
+            if exited.is_some() {
+                break;
+            }
+            if started.elapsed() >= self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(SolverAnswer::Unknown(format!("timed out after {:?}", self.timeout)));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut output = String::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout
+                .read_to_string(&mut output)
+                .map_err(|e| FCMCError::VerificationError(format!("Failed to read SMT solver output: {}", e)))?;
+        }
+
+        let mut lines = output.lines();
+        Ok(match lines.next().map(str::trim) {
+            Some("unsat") => SolverAnswer::Unsat,
+            Some("sat") => SolverAnswer::Sat(lines.collect::<Vec<_>>().join("\n")),
+            Some(other) => SolverAnswer::Unknown(other.to_string()),
+            None => SolverAnswer::Unknown("no answer".to_string()),
+        })
+    }
+}
+
+impl Default for SmtSolver {
+    fn default() -> Self {
+        Self::z3()
+    }
+}
+
+/// Validates selected passes by proving the pre- and post-pass IR equivalent
+#[derive(Debug, Clone)]
+pub struct TranslationValidator {
+    solver: SmtSolver,
+    passes: Vec<String>,
+    strict: bool,
+}
+
+impl TranslationValidator {
+    pub fn new(solver: SmtSolver) -> Self {
+        Self {
+            solver,
+            passes: Vec::new(),
+            strict: false,
+        }
+    }
+
+    /// Opt `pass` in to validation; `"all"` validates every pass
+    pub fn validate_pass(mut self, pass: &str) -> Self {
+        self.passes.push(pass.to_string());
+        self
+    }
+
+    /// Treat queries the solver cannot decide as failures instead of warnings
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn applies_to(&self, pass: &str) -> bool {
+        self.passes.iter().any(|name| name == pass || name == "all")
+    }
+
+    pub fn validate(&self, before: &IRGraph, after: &IRGraph, pass: &str) -> Result<(), FCMCError> {
+        let query = equivalence_query(before, after)?;
+        match self.solver.check(&query)? {
+            SolverAnswer::Unsat => {
+                tracing::debug!("Pass '{}' validated by SMT solver", pass);
+                Ok(())
+            }
+            SolverAnswer::Sat(model) => Err(FCMCError::OptimizationError(format!(
+                "Pass '{}' failed translation validation; distinguishing input:\n{}",
+                pass, model
+            ))),
+            SolverAnswer::Unknown(reason) if self.strict => Err(FCMCError::OptimizationError(format!(
+                "Pass '{}' could not be validated: {}",
+                pass, reason
+            ))),
+            SolverAnswer::Unknown(reason) => {
+                tracing::warn!("Pass '{}' could not be validated: {}", pass, reason);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// SMT-LIB query that is satisfiable iff some input is accepted by only one of the graphs,
+/// or is accepted by both but yields different outputs
+pub fn equivalence_query(before: &IRGraph, after: &IRGraph) -> Result<String, FCMCError> {
+    let mut query = String::new();
+    let p = field::modulus().to_string();
+
+    let _ = writeln!(query, "(set-logic QF_NIA)");
+    let _ = writeln!(query, "(set-option :produce-models true)");
+    let mut names = input_names(before);
+    names.sort();
+    names.dedup();
+    for name in &names {
+        let symbol = input_symbol(name);
+        let _ = writeln!(query, "(declare-const {} Int)", symbol);
+        let _ = writeln!(query, "(assert (and (<= 0 {}) (< {} {})))", symbol, symbol, p);
+    }
+
+    let pre = encode_graph(before, "a", &p, &mut query)?;
+    let post = encode_graph(after, "b", &p, &mut query)?;
+
+    // Only inputs the original circuit accepts are compared
+    for guard in &pre.division_guards {
+        let _ = writeln!(query, "(assert {})", guard);
+    }
+
+    let mut differences = Vec::new();
+    let mut pre_outputs = pre.outputs.clone();
+    let mut post_outputs = post.outputs.clone();
+    pre_outputs.sort();
+    post_outputs.sort();
+    if pre_outputs.len() != post_outputs.len()
+        || pre_outputs.iter().zip(&post_outputs).any(|((a, _), (b, _))| a != b)
+    {
+        return Err(FCMCError::OptimizationError(
+            "Pass changed the set of circuit outputs".to_string(),
+        ));
+    }
+    let pre_holds = conjunction(&pre.constraints);
+    // Outputs only matter for inputs the constraints accept
+    for ((_, a), (_, b)) in pre_outputs.iter().zip(&post_outputs) {
+        differences.push(format!("(and {} (not (= {} {})))", pre_holds, a, b));
+    }
+    differences.push(format!("(not (= {} {}))", pre_holds, conjunction(&post.constraints)));
+    // A division the original did not perform must not fail in the optimized circuit
+    for guard in &post.division_guards {
+        differences.push(format!("(not {})", guard));
+    }
+
+    let _ = writeln!(query, "(assert (or {}))", differences.join(" "));
+    let _ = writeln!(query, "(check-sat)");
+    let _ = writeln!(query, "(get-model)");
+    Ok(query)
+}
+
+struct Encoding {
+    /// Output name and the symbol holding its value
+    outputs: Vec<(String, String)>,
+    constraints: Vec<String>,
+    /// Divisor-nonzero conditions
+    division_guards: Vec<String>,
+}
+
+fn encode_graph(graph: &IRGraph, prefix: &str, p: &str, query: &mut String) -> Result<Encoding, FCMCError> {
+    let mut symbols: HashMap<usize, String> = HashMap::new();
+    let mut encoding = Encoding {
+        outputs: Vec::new(),
+        constraints: Vec::new(),
+        division_guards: Vec::new(),
+    };
+
+    for node_id in graph.topological_sort() {
+        let node = match graph.get_node(node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+        let symbol = format!("{}_{}", prefix, node_id);
+        let operands: Vec<String> = graph
+            .get_predecessors(node_id)
+            .iter()
+            .map(|operand| symbols.get(operand).cloned().unwrap_or_else(|| "0".to_string()))
+            .collect();
+        let arg = |index: usize| operands.get(index).cloned().unwrap_or_else(|| "0".to_string());
+        let reduce = |term: String| format!("(mod {} {})", term, p);
+
+        let term = match &node.node_type {
+            IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => input_symbol(name),
+            IRNodeType::Constant(value) => field::parse_element(value)
+                .ok_or_else(|| FCMCError::VerificationError(format!("Invalid field constant: {}", value)))?
+                .to_string(),
+            IRNodeType::Add => reduce(format!("(+ {} {})", arg(0), arg(1))),
+            IRNodeType::Sub => reduce(format!("(- {} {})", arg(0), arg(1))),
+            IRNodeType::Mul | IRNodeType::And => reduce(format!("(* {} {})", arg(0), arg(1))),
+            IRNodeType::Neg => reduce(format!("(- {})", arg(0))),
+            IRNodeType::Div => {
+                // The quotient is a fresh value q with q * b = a whenever b is nonzero
+                let guard = format!("(not (= {} 0))", arg(1));
+                let _ = writeln!(query, "(declare-const {} Int)", symbol);
+                let _ = writeln!(query, "(assert (and (<= 0 {}) (< {} {})))", symbol, symbol, p);
+                let _ = writeln!(
+                    query,
+                    "(assert (=> {} (= (mod (* {} {}) {}) {})))",
+                    guard,
+                    symbol,
+                    arg(1),
+                    p,
+                    arg(0)
+                );
+                encoding.division_guards.push(guard);
+                symbols.insert(node_id, symbol);
+                continue;
+            }
+            IRNodeType::Or => reduce(format!("(- (+ {a} {b}) (* {a} {b}))", a = arg(0), b = arg(1))),
+            IRNodeType::Xor => reduce(format!("(- (+ {a} {b}) (* 2 {a} {b}))", a = arg(0), b = arg(1))),
+            IRNodeType::Not => reduce(format!("(- 1 {})", arg(0))),
+            IRNodeType::Eq => boolean(format!("(= {} {})", arg(0), arg(1))),
+            IRNodeType::Ne => boolean(format!("(not (= {} {}))", arg(0), arg(1))),
+            IRNodeType::Lt => boolean(format!("(< {} {})", arg(0), arg(1))),
+            IRNodeType::Le => boolean(format!("(<= {} {})", arg(0), arg(1))),
+            IRNodeType::Gt => boolean(format!("(> {} {})", arg(0), arg(1))),
+            IRNodeType::Ge => boolean(format!("(>= {} {})", arg(0), arg(1))),
+            IRNodeType::Select => format!("(ite (= {} 0) {} {})", arg(0), arg(2), arg(1)),
+            IRNodeType::BitDecomposition => match extracted_bit(node) {
+                Some(index) => format!("(mod (div {} {}) 2)", arg(0), power_of_two(index)),
+                None => arg(0),
+            },
+            IRNodeType::Phi => arg(0),
+            IRNodeType::Output(name) => {
+                encoding.outputs.push((name.clone(), symbol.clone()));
+                arg(0)
+            }
+            IRNodeType::Constraint(constraint) => {
+                if let Some(predicate) = constraint_predicate(constraint, &operands, p) {
+                    encoding.constraints.push(predicate);
+                }
+                "0".to_string()
+            }
+            IRNodeType::Lookup | IRNodeType::RangeCheck => {
+                match node.attributes.get("table").and_then(|name| graph.table(name)) {
+                    Some(table) => {
+                        let (term, in_range) = encode_table(table, &operands)?;
+                        encoding.constraints.push(in_range);
+                        term
+                    }
+                    None => {
+                        if let Some(bits) = node.attributes.get("bits").and_then(|bits| bits.parse::<u32>().ok()) {
+                            for operand in &operands {
+                                encoding.constraints.push(format!("(< {} {})", operand, power_of_two(bits)));
+                            }
+                        }
+                        "0".to_string()
+                    }
+                }
+            }
+        };
+
+        let _ = writeln!(query, "(define-fun {} () Int {})", symbol, term);
+        symbols.insert(node_id, symbol);
+    }
+
+    Ok(encoding)
+}
+
+/// Tables larger than this are not expanded into the query
+const MAX_ENCODED_TABLE_ROWS: usize = 1 << 10;
+
+/// The selected row as an if-then-else chain over the packed index, plus the domain condition
+fn encode_table(table: &LookupTable, operands: &[String]) -> Result<(String, String), FCMCError> {
+    if table.outputs.len() > MAX_ENCODED_TABLE_ROWS {
+        return Err(FCMCError::VerificationError(format!(
+            "Lookup table '{}' has {} rows, too many to encode",
+            table.name,
+            table.outputs.len()
+        )));
+    }
+
+    let mut index = Vec::new();
+    let mut in_range = Vec::new();
+    let mut offset = 0;
+    for (operand, &bits) in operands.iter().zip(&table.input_bits) {
+        index.push(format!("(* {} {})", operand, power_of_two(offset)));
+        in_range.push(format!("(< {} {})", operand, power_of_two(bits)));
+        offset += bits;
+    }
+    let index = format!("(+ 0 {})", index.join(" "));
+
+    let mut term = "0".to_string();
+    for (row, value) in table.outputs.iter().enumerate().rev() {
+        let value = field::parse_element(value)
+            .ok_or_else(|| FCMCError::VerificationError(format!("Invalid field constant: {}", value)))?;
+        term = format!("(ite (= {} {}) {} {})", index, row, value, term);
+    }
+    Ok((term, conjunction(&in_range)))
+}
+
+/// Mirrors the constraint semantics of the interpreter
+fn constraint_predicate(constraint: &ConstraintType, operands: &[String], p: &str) -> Option<String> {
+    match (constraint, operands) {
+        (ConstraintType::Equality, [value]) => Some(format!("(= {} 1)", value)),
+        (ConstraintType::Equality, [a, b]) => Some(format!("(= {} {})", a, b)),
+        (ConstraintType::Inequality, [value]) => Some(format!("(not (= {} 0))", value)),
+        (ConstraintType::Inequality, [a, b]) => Some(format!("(not (= {} {}))", a, b)),
+        (ConstraintType::Range { bits }, [value]) => Some(format!("(< {} {})", value, power_of_two(*bits))),
+        (ConstraintType::Polynomial { coefficients }, [x]) => {
+            let mut terms = Vec::new();
+            for (degree, coefficient) in coefficients.iter().enumerate() {
+                let coefficient = field::parse_element(coefficient)?;
+                let powers = vec![x.as_str(); degree];
+                terms.push(if powers.is_empty() {
+                    coefficient.to_string()
+                } else {
+                    format!("(* {} {})", coefficient, powers.join(" "))
+                });
+            }
+            Some(format!("(= (mod (+ 0 {}) {}) 0)", terms.join(" "), p))
+        }
+        _ => None,
+    }
+}
+
+fn conjunction(predicates: &[String]) -> String {
+    if predicates.is_empty() {
+        "true".to_string()
+    } else {
+        format!("(and true {})", predicates.join(" "))
+    }
+}
+
+fn boolean(condition: String) -> String {
+    format!("(ite {} 1 0)", condition)
+}
+
+fn power_of_two(bits: u32) -> String {
+    (num_bigint::BigUint::from(1u32) << bits).to_string()
+}
+
+fn input_symbol(name: &str) -> String {
+    format!("|in_{}|", name.replace('|', "_"))
+}
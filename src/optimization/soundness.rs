@@ -0,0 +1,258 @@
+//! Differential testing of passes: the IR before and after a pass must agree on random inputs
+//!
+//! The same comparison checks the optimized IR against the unoptimized one as a whole,
+//! exhaustively when every input is constrained to a bit and there are few enough of them.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::booleanity::BooleanityAnalysis;
+use crate::optimization::field;
+use crate::optimization::interpreter::{evaluate, input_names};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Random input assignments tried per pass when the self-check is enabled
+pub const DEFAULT_SOUNDNESS_SAMPLES: usize = 16;
+
+/// Most boolean inputs whose every assignment the exhaustive check enumerates
+pub const MAX_EXHAUSTIVE_INPUTS: usize = 16;
+
+const SEED: u64 = 0x5eed_fc3c;
+
+/// How an optimized circuit was checked against the original
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EquivalenceReport {
+    /// Input assignments both circuits were evaluated on
+    pub assignments: usize,
+    /// Assignments the original rejects outright, such as a division by zero, and were skipped
+    pub skipped: usize,
+    /// Whether every assignment of the boolean inputs was covered
+    pub exhaustive: bool,
+}
+
+/// Outcome of evaluating two graphs on the same inputs
+enum Comparison {
+    /// The original cannot be evaluated on the inputs
+    Skipped,
+    Agree,
+    Differ(String),
+}
+
+/// Evaluate `before` and `after` on `samples` random inputs and fail if the constraints
+/// accept different inputs or an accepted input yields different outputs
+pub fn check_pass(before: &IRGraph, after: &IRGraph, samples: usize, pass: &str) -> Result<(), FCMCError> {
+    let names = input_names(before);
+    let mut rng = StdRng::seed_from_u64(SEED);
+
+    for _ in 0..samples {
+        let inputs: HashMap<String, BigUint> = names
+            .iter()
+            .map(|name| (name.clone(), random_value(&mut rng)))
+            .collect();
+
+        if let Comparison::Differ(detail) = compare(before, after, &inputs) {
+            return Err(unsound(pass, &inputs, &detail));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that `optimized` accepts the same inputs as `original` and computes the same
+/// outputs from them, on `samples` random inputs or, with `exhaustive` set and at most
+/// `MAX_EXHAUSTIVE_INPUTS` inputs all constrained to a bit, on every boolean assignment
+pub fn verify_equivalence(
+    original: &IRGraph,
+    optimized: &IRGraph,
+    samples: usize,
+    exhaustive: bool,
+) -> Result<EquivalenceReport, FCMCError> {
+    let names = input_names(original);
+    let mut report = EquivalenceReport::default();
+    let mut check = |inputs: HashMap<String, BigUint>| match compare(original, optimized, &inputs) {
+        Comparison::Skipped => {
+            report.skipped += 1;
+            Ok(())
+        }
+        Comparison::Agree => {
+            report.assignments += 1;
+            Ok(())
+        }
+        Comparison::Differ(detail) => Err(inequivalent(&inputs, &detail)),
+    };
+
+    if exhaustive && names.len() <= MAX_EXHAUSTIVE_INPUTS && boolean_inputs(original) {
+        for assignment in 0u32..1 << names.len() {
+            let inputs = names
+                .iter()
+                .enumerate()
+                .map(|(bit, name)| (name.clone(), BigUint::from((assignment >> bit) & 1)))
+                .collect();
+            check(inputs)?;
+        }
+        report.exhaustive = true;
+    } else {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        for _ in 0..samples {
+            let inputs = names
+                .iter()
+                .map(|name| (name.clone(), random_value(&mut rng)))
+                .collect();
+            check(inputs)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Whether every input of `graph` is constrained to 0 or 1
+fn boolean_inputs(graph: &IRGraph) -> bool {
+    let booleans = BooleanityAnalysis::run(graph);
+    graph
+        .live_nodes()
+        .filter(|node| matches!(node.node_type, IRNodeType::Input(_) | IRNodeType::PrivateInput(_)))
+        .all(|node| booleans.is_boolean(node.id))
+}
+
+fn compare(before: &IRGraph, after: &IRGraph, inputs: &HashMap<String, BigUint>) -> Comparison {
+    // Inputs the original circuit rejects (e.g. division by zero) say nothing about the change
+    let expected = match evaluate(before, inputs) {
+        Ok(evaluation) => evaluation,
+        Err(_) => return Comparison::Skipped,
+    };
+    let actual = match evaluate(after, inputs) {
+        Ok(evaluation) => evaluation,
+        Err(e) => return Comparison::Differ(e.to_string()),
+    };
+
+    if expected.is_satisfied() != actual.is_satisfied() {
+        return Comparison::Differ(format!(
+            "constraints {} but now {}",
+            holds(expected.is_satisfied()),
+            holds(actual.is_satisfied())
+        ));
+    }
+
+    // Outputs of a rejected assignment are unconstrained
+    if !expected.is_satisfied() {
+        return Comparison::Agree;
+    }
+    let mut expected_outputs = expected.outputs;
+    let mut actual_outputs = actual.outputs;
+    expected_outputs.sort();
+    actual_outputs.sort();
+    if expected_outputs != actual_outputs {
+        return Comparison::Differ(format!("outputs {:?} became {:?}", expected_outputs, actual_outputs));
+    }
+    Comparison::Agree
+}
+
+/// Half of the values are small so boolean and range constraints get exercised
+pub(crate) fn random_value(rng: &mut StdRng) -> BigUint {
+    if rng.gen_bool(0.5) {
+        BigUint::from(rng.gen_range(0u64..4))
+    } else {
+        let mut bytes = [0u8; 32];
+        rng.fill(&mut bytes);
+        BigUint::from_bytes_le(&bytes) % field::modulus()
+    }
+}
+
+fn holds(satisfied: bool) -> &'static str {
+    if satisfied {
+        "held"
+    } else {
+        "failed"
+    }
+}
+
+fn unsound(pass: &str, inputs: &HashMap<String, BigUint>, detail: &str) -> FCMCError {
+    FCMCError::OptimizationError(format!(
+        "Pass '{}' changed circuit semantics on inputs [{}]: {}",
+        pass,
+        assignment(inputs),
+        detail
+    ))
+}
+
+fn inequivalent(inputs: &HashMap<String, BigUint>, detail: &str) -> FCMCError {
+    FCMCError::VerificationError(format!(
+        "Optimized circuit is not equivalent to the original on inputs [{}]: {}",
+        assignment(inputs),
+        detail
+    ))
+}
+
+fn assignment(inputs: &HashMap<String, BigUint>) -> String {
+    let mut assignment: Vec<String> = inputs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    assignment.sort();
+    assignment.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{ConstraintType, EdgeType};
+    use crate::language::ast::Type;
+
+    /// `out = a op b`, with `a` and `b` constrained to a bit when `bits` is set
+    fn binary(op: IRNodeType, bits: bool) -> IRGraph {
+        let mut graph = IRGraph::new();
+        let [a, b] = ["a", "b"].map(|name| graph.add_node(IRNodeType::Input(name.to_string()), Type::Field, None));
+        let result = graph.add_node(op, Type::Field, None);
+        let out = graph.add_node(IRNodeType::Output("out".to_string()), Type::Field, None);
+        for (from, to) in [(a, result), (b, result), (result, out)] {
+            graph.add_edge(from, to, EdgeType::DataFlow);
+        }
+        if bits {
+            for input in [a, b] {
+                let check = graph.add_node(IRNodeType::Constraint(ConstraintType::Range { bits: 1 }), Type::Bool, None);
+                graph.add_edge(input, check, EdgeType::Constraint);
+            }
+        }
+        graph
+    }
+
+    #[test]
+    fn identical_circuits_agree_on_random_inputs() {
+        let graph = binary(IRNodeType::Mul, false);
+        let report = verify_equivalence(&graph, &graph.clone(), 8, true).unwrap();
+        assert_eq!(
+            report,
+            EquivalenceReport {
+                assignments: 8,
+                skipped: 0,
+                exhaustive: false,
+            }
+        );
+    }
+
+    #[test]
+    fn changed_output_is_caught_on_random_inputs() {
+        let error = verify_equivalence(&binary(IRNodeType::Mul, false), &binary(IRNodeType::Add, false), 8, false);
+        assert!(matches!(error, Err(FCMCError::VerificationError(_))));
+    }
+
+    #[test]
+    fn boolean_inputs_are_checked_exhaustively() {
+        // a * b and a AND b agree on bits
+        let product = binary(IRNodeType::Mul, true);
+        let report = verify_equivalence(&product, &binary(IRNodeType::And, true), 0, true).unwrap();
+        assert_eq!(
+            report,
+            EquivalenceReport {
+                assignments: 4,
+                skipped: 0,
+                exhaustive: true,
+            }
+        );
+
+        // a * b and a XOR b first differ at a = 1, b = 0
+        let error = verify_equivalence(&product, &binary(IRNodeType::Xor, true), 0, true);
+        assert!(matches!(error, Err(FCMCError::VerificationError(message)) if message.contains("a=1, b=0")));
+    }
+}
@@ -0,0 +1,268 @@
+//! Sparsity of the R1CS matrices.
+//!
+//! Linear nodes cost no constraint in R1CS: they are folded into the linear combinations
+//! of the rows that read them, and each such row repeats every term. A combination that
+//! many rows read is cheaper as its own wire, defined once by `lc * 1 = w`. This pass
+//! decides which linear nodes the backend should allocate a wire for, minimizing the
+//! number of non-zero entries in A, B and C rather than the number of rows.
+
+use crate::backend::TargetSystem;
+use crate::ir::{ConstraintType, IRGraph, IRNodeType};
+use crate::optimization::constant_value;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::target::TargetProfile;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::{HashMap, HashSet};
+
+/// Marks a linear node the R1CS backend allocates its own wire for
+pub const WIRE_ATTRIBUTE: &str = "r1cs.wire";
+
+/// Key of the constant-one wire in a linear combination
+const ONE: usize = usize::MAX;
+
+/// Wire -> nonzero coefficient
+type LinearCombination = HashMap<usize, BigUint>;
+
+fn is_wire(graph: &IRGraph, node_id: usize) -> bool {
+    graph
+        .get_node(node_id)
+        .map_or(false, |node| node.attributes.contains_key(WIRE_ATTRIBUTE))
+}
+
+/// Whether a node is folded into the combinations of its consumers
+fn is_linear(graph: &IRGraph, node_id: usize) -> bool {
+    match graph.get_node(node_id).map(|node| &node.node_type) {
+        Some(IRNodeType::Add) | Some(IRNodeType::Sub) | Some(IRNodeType::Neg) | Some(IRNodeType::Constant(_)) => true,
+        Some(IRNodeType::Mul) => graph
+            .get_predecessors(node_id)
+            .iter()
+            .any(|&operand| constant_value(graph, operand).is_some()),
+        _ => false,
+    }
+}
+
+fn combine(target: &mut LinearCombination, source: &LinearCombination, factor: &BigUint) {
+    for (&wire, coefficient) in source {
+        let entry = target.entry(wire).or_insert_with(BigUint::zero);
+        *entry = field::add(entry, &field::mul(coefficient, factor));
+        if entry.is_zero() {
+            target.remove(&wire);
+        }
+    }
+}
+
+/// Linear combinations of the R1CS lowering of a graph
+struct LinearForms {
+    /// Combination each linear node expands to
+    expanded: HashMap<usize, LinearCombination>,
+}
+
+impl LinearForms {
+    fn compute(graph: &IRGraph) -> Self {
+        let mut forms = Self {
+            expanded: HashMap::new(),
+        };
+        for node_id in graph.topological_sort() {
+            forms.update(graph, node_id);
+        }
+        forms
+    }
+
+    /// Recompute the expansion of `node_id` from its operands' current forms
+    fn update(&mut self, graph: &IRGraph, node_id: usize) {
+        if !is_linear(graph, node_id) {
+            return;
+        }
+        let operands = graph.get_predecessors(node_id);
+        let minus_one = field::neg(&BigUint::one());
+        let mut lc = LinearCombination::new();
+        match graph.get_node(node_id).map(|node| &node.node_type) {
+            Some(IRNodeType::Constant(_)) => {
+                if let Some(value) = constant_value(graph, node_id).filter(|value| !value.is_zero()) {
+                    lc.insert(ONE, value);
+                }
+            }
+            Some(IRNodeType::Add) => {
+                for &operand in &operands {
+                    combine(&mut lc, &self.seen(graph, operand), &BigUint::one());
+                }
+            }
+            Some(IRNodeType::Sub) => {
+                if let [a, b] = operands.as_slice() {
+                    combine(&mut lc, &self.seen(graph, *a), &BigUint::one());
+                    combine(&mut lc, &self.seen(graph, *b), &minus_one);
+                }
+            }
+            Some(IRNodeType::Neg) => {
+                if let [a] = operands.as_slice() {
+                    combine(&mut lc, &self.seen(graph, *a), &minus_one);
+                }
+            }
+            Some(IRNodeType::Mul) => {
+                if let [a, b] = operands.as_slice() {
+                    let (scalar, operand) = match constant_value(graph, *a) {
+                        Some(value) => (value, *b),
+                        None => (constant_value(graph, *b).unwrap_or_default(), *a),
+                    };
+                    combine(&mut lc, &self.seen(graph, operand), &scalar);
+                }
+            }
+            _ => {}
+        }
+        self.expanded.insert(node_id, lc);
+    }
+
+    /// Combination a consumer of `node_id` reads
+    fn seen(&self, graph: &IRGraph, node_id: usize) -> LinearCombination {
+        match self.expanded.get(&node_id) {
+            Some(lc) if !is_wire(graph, node_id) => lc.clone(),
+            _ => LinearCombination::from([(node_id, BigUint::one())]),
+        }
+    }
+
+    fn seen_len(&self, graph: &IRGraph, node_id: usize) -> usize {
+        match self.expanded.get(&node_id) {
+            Some(lc) if !is_wire(graph, node_id) => lc.len(),
+            _ => 1,
+        }
+    }
+}
+
+/// Non-zero entries of the A, B and C matrices once `graph` is lowered to R1CS
+pub fn r1cs_nonzeros(graph: &IRGraph) -> usize {
+    let forms = LinearForms::compute(graph);
+    graph
+        .live_nodes()
+        .map(|node| row_nonzeros(graph, &forms, node.id))
+        .sum()
+}
+
+fn row_nonzeros(graph: &IRGraph, forms: &LinearForms, node_id: usize) -> usize {
+    let operands = graph.get_predecessors(node_id);
+    let reads: usize = operands.iter().map(|&operand| forms.seen_len(graph, operand)).sum();
+    match graph.get_node(node_id).map(|node| &node.node_type) {
+        // Defined by `lc * 1 = w`
+        _ if forms.expanded.contains_key(&node_id) => {
+            if is_wire(graph, node_id) {
+                forms.expanded[&node_id].len() + 2
+            } else {
+                0
+            }
+        }
+        Some(IRNodeType::Input(_)) | Some(IRNodeType::PrivateInput(_)) | Some(IRNodeType::Output(_)) => 0,
+        // `(a - b) * 1 = 0`, or `(a - 1) * 1 = 0`
+        Some(IRNodeType::Constraint(ConstraintType::Equality)) => {
+            let minus_one = field::neg(&BigUint::one());
+            let mut lc = LinearCombination::new();
+            match operands.as_slice() {
+                [a] => {
+                    combine(&mut lc, &forms.seen(graph, *a), &BigUint::one());
+                    combine(&mut lc, &LinearCombination::from([(ONE, BigUint::one())]), &minus_one);
+                }
+                [a, b] => {
+                    combine(&mut lc, &forms.seen(graph, *a), &BigUint::one());
+                    combine(&mut lc, &forms.seen(graph, *b), &minus_one);
+                }
+                _ => return reads + 1,
+            }
+            lc.len() + 1
+        }
+        // One row reading every operand and producing the result
+        _ => reads + 1,
+    }
+}
+
+/// Allocates wires for linear combinations read by enough rows to pay for themselves
+#[derive(Debug, Default)]
+pub struct SparsityOptimization {
+    last_rewrites: usize,
+}
+
+impl SparsityOptimization {
+    /// Returns the number of nodes whose wire allocation changed
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let before = r1cs_nonzeros(graph);
+        let order = graph.topological_sort();
+        let original: HashSet<usize> = order.iter().copied().filter(|&id| is_wire(graph, id)).collect();
+
+        // How many rows copy each linear node's combination, with the current wires
+        let mut row_reads: HashMap<usize, usize> = HashMap::new();
+        for &node_id in order.iter().rev() {
+            if !is_linear(graph, node_id) {
+                continue;
+            }
+            let reads = graph
+                .get_successors(node_id)
+                .into_iter()
+                .map(|consumer| match row_reads.get(&consumer) {
+                    Some(&reads) if !is_wire(graph, consumer) => reads,
+                    _ => 1,
+                })
+                .sum();
+            row_reads.insert(node_id, reads);
+        }
+
+        // Decide upstream first so downstream sizes reflect the wires allocated above them
+        let mut forms = LinearForms {
+            expanded: HashMap::new(),
+        };
+        let mut changed = 0;
+        for &node_id in &order {
+            forms.update(graph, node_id);
+            let size = match forms.expanded.get(&node_id) {
+                Some(lc) if constant_value(graph, node_id).is_none() => lc.len(),
+                _ => continue,
+            };
+            let reads = row_reads.get(&node_id).copied().unwrap_or(0);
+            let allocate = size > 1 && reads * (size - 1) > size + 2;
+            if allocate != is_wire(graph, node_id) {
+                set_wire(graph, node_id, allocate);
+                changed += 1;
+            }
+        }
+
+        let after = r1cs_nonzeros(graph);
+        if after > before {
+            for &node_id in &order {
+                set_wire(graph, node_id, original.contains(&node_id));
+            }
+            return Ok(0);
+        }
+        if changed > 0 {
+            tracing::debug!("R1CS non-zeros: {} -> {}", before, after);
+        }
+        Ok(changed)
+    }
+}
+
+fn set_wire(graph: &mut IRGraph, node_id: usize, wire: bool) {
+    if let Some(node) = graph.get_node_mut(node_id) {
+        if wire {
+            node.attributes.insert(WIRE_ATTRIBUTE.to_string(), "true".to_string());
+        } else {
+            node.attributes.remove(WIRE_ATTRIBUTE);
+        }
+    }
+}
+
+impl OptimizationPass for SparsityOptimization {
+    fn name(&self) -> &str {
+        "sparsity"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = SparsityOptimization::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+
+    fn supports(&self, target: &TargetProfile) -> bool {
+        matches!(target.system, TargetSystem::R1CS)
+    }
+}
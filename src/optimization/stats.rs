@@ -0,0 +1,206 @@
+use crate::ir::IRGraph;
+use crate::optimization::cost::CostModel;
+use crate::optimization::explain::{Explanation, NodeCensus};
+use crate::progress::{PassProgress, ProgressObserver, SharedObserver};
+use crate::FCMCError;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a single pass invocation did to the graph
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PassStats {
+    pub name: String,
+    /// Pipeline iteration the pass ran in, starting at 1
+    pub iteration: usize,
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    pub edges_before: usize,
+    pub edges_after: usize,
+    /// Rewrites applied, for passes that count them
+    pub rewrites: Option<usize>,
+    pub changed: bool,
+    pub duration_micros: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OptimizationStats {
+    pub passes: Vec<PassStats>,
+    pub iterations: usize,
+    pub total_micros: u64,
+    /// Savings per pass run and source location, when explain mode is on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub explanations: Vec<Explanation>,
+    /// Why the pipeline stopped before finishing, if an optimization budget ran out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_early: Option<String>,
+}
+
+impl OptimizationStats {
+    pub fn total_rewrites(&self) -> usize {
+        self.passes.iter().filter_map(|pass| pass.rewrites).sum()
+    }
+
+    /// Explain-mode entries as readable lines, largest savings first
+    pub fn explain_report(&self) -> Vec<String> {
+        let mut entries: Vec<&Explanation> = self.explanations.iter().collect();
+        entries.sort_by(|a, b| b.constraints_saved.cmp(&a.constraints_saved).then(a.line.cmp(&b.line)));
+        entries.into_iter().map(Explanation::describe).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, FCMCError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| FCMCError::OptimizationError(format!("Failed to serialize pass stats: {}", e)))
+    }
+}
+
+/// Copy of the IR taken right after a pass, for `--dump-ir-after=<pass>` style debugging
+#[derive(Debug, Clone)]
+pub struct IrSnapshot {
+    pub pass: String,
+    pub iteration: usize,
+    pub graph: IRGraph,
+}
+
+/// Collects per-pass statistics and requested snapshots while a pipeline runs
+#[derive(Debug, Default)]
+pub struct PassRecorder {
+    stats: OptimizationStats,
+    dump_after: HashSet<String>,
+    snapshots: Vec<IrSnapshot>,
+    explain: Option<CostModel>,
+    progress: Option<SharedObserver>,
+    /// Most pass runs the pipeline can make, which progress is measured against
+    planned_runs: usize,
+    runs: usize,
+}
+
+impl PassRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshot the IR after every run of the named passes; `"all"` matches every pass
+    pub fn with_dump_after<I, S>(mut self, passes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.dump_after.extend(passes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Attribute every pass run's savings to source locations, costed with `cost`
+    pub fn with_explain(mut self, cost: CostModel) -> Self {
+        self.explain = Some(cost);
+        self
+    }
+
+    /// Report every pass run to `observer`, out of at most `planned_runs`
+    pub fn with_progress(mut self, observer: Arc<dyn ProgressObserver>, planned_runs: usize) -> Self {
+        self.progress = Some(SharedObserver(observer));
+        self.planned_runs = planned_runs;
+        self
+    }
+
+    /// Snapshot the graph before a pass, if explain mode needs one
+    pub(crate) fn census(&self, graph: &IRGraph) -> Option<NodeCensus> {
+        self.explain.as_ref().map(|cost| NodeCensus::take(graph, cost))
+    }
+
+    pub(crate) fn explain(&mut self, census: NodeCensus, pass: &str, iteration: usize, graph: &IRGraph) {
+        if let Some(cost) = &self.explain {
+            let explanations = census.explain(graph, cost, pass, iteration);
+            self.stats.explanations.extend(explanations);
+        }
+    }
+
+    pub(crate) fn record(&mut self, mut stats: PassStats, graph: &IRGraph, duration: Duration) {
+        stats.duration_micros = duration.as_micros() as u64;
+        stats.nodes_after = graph.node_count();
+        stats.edges_after = graph.edge_count();
+        self.stats.total_micros += stats.duration_micros;
+        // Called inside the pass's span
+        let span = tracing::Span::current();
+        span.record("nodes_after", stats.nodes_after);
+        span.record("changed", stats.changed);
+        span.record("micros", stats.duration_micros);
+
+        if self.dump_after.contains(&stats.name) || self.dump_after.contains("all") {
+            self.snapshots.push(IrSnapshot {
+                pass: stats.name.clone(),
+                iteration: stats.iteration,
+                graph: graph.clone(),
+            });
+        }
+
+        self.runs += 1;
+        if let Some(SharedObserver(observer)) = &self.progress {
+            observer.pass_finished(&PassProgress {
+                pass: stats.name.clone(),
+                iteration: stats.iteration,
+                runs: self.runs,
+                percent: (100.0 * self.runs as f64 / self.planned_runs.max(1) as f64).min(100.0),
+                nodes: stats.nodes_after,
+                changed: stats.changed,
+            });
+        }
+        self.stats.passes.push(stats);
+    }
+
+    /// Note that the pipeline ended early; the first reason is kept
+    pub(crate) fn stop(&mut self, reason: String) {
+        tracing::info!("Optimization stopped early: {}", reason);
+        self.stats.stopped_early.get_or_insert(reason);
+    }
+
+    pub(crate) fn stopped(&self) -> bool {
+        self.stats.stopped_early.is_some()
+    }
+
+    pub(crate) fn add_iterations(&mut self, iterations: usize) {
+        self.stats.iterations += iterations;
+    }
+
+    pub fn stats(&self) -> &OptimizationStats {
+        &self.stats
+    }
+
+    pub fn snapshots(&self) -> &[IrSnapshot] {
+        &self.snapshots
+    }
+
+    pub fn finish(self) -> (OptimizationStats, Vec<IrSnapshot>) {
+        (self.stats, self.snapshots)
+    }
+}
+
+impl PassStats {
+    pub(crate) fn start(name: &str, iteration: usize, graph: &IRGraph) -> Self {
+        Self {
+            name: name.to_string(),
+            iteration,
+            nodes_before: graph.node_count(),
+            nodes_after: graph.node_count(),
+            edges_before: graph.edge_count(),
+            edges_after: graph.edge_count(),
+            rewrites: None,
+            changed: false,
+            duration_micros: 0,
+        }
+    }
+
+    /// The `pass` span to run the pass in; `PassRecorder::record` fills in its outcome
+    pub(crate) fn span(&self) -> tracing::Span {
+        tracing::debug_span!(
+            "pass",
+            pass = %self.name,
+            iteration = self.iteration,
+            nodes_before = self.nodes_before,
+            nodes_after = tracing::field::Empty,
+            changed = tracing::field::Empty,
+            micros = tracing::field::Empty,
+        )
+    }
+}
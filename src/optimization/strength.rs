@@ -0,0 +1,204 @@
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::cost::CostModel;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::{constant_value, field};
+use crate::FCMCError;
+use num_traits::ToPrimitive;
+
+/// Largest constant multiplier considered for an addition chain
+const MAX_CHAIN_MULTIPLIER: u64 = 1 << 16;
+
+/// Replaces constant multiplications and divisions with cheaper equivalents
+pub struct StrengthReduction {
+    cost: CostModel,
+    last_rewrites: usize,
+}
+
+impl StrengthReduction {
+    pub fn new(cost: CostModel) -> Self {
+        Self {
+            cost,
+            last_rewrites: 0,
+        }
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let candidates: Vec<usize> = graph
+            .live_nodes()
+            .filter(|node| matches!(node.node_type, IRNodeType::Mul | IRNodeType::Div))
+            .map(|node| node.id)
+            .collect();
+
+        let mut rewrites = 0;
+        for node_id in candidates {
+            let rewritten = match graph.get_node(node_id).map(|node| node.node_type.clone()) {
+                Some(IRNodeType::Mul) => self.reduce_mul(graph, node_id),
+                Some(IRNodeType::Div) => self.reduce_div(graph, node_id),
+                _ => false,
+            };
+            if rewritten {
+                rewrites += 1;
+            }
+        }
+
+        Ok(rewrites)
+    }
+
+    /// x * k  =>  double-and-add chain over x, when the target prices the chain's
+    /// additions below one constant product. The built-in targets fold constant products
+    /// into linear combinations, so only AIR's doubling and custom cost models qualify.
+    fn reduce_mul(&self, graph: &mut IRGraph, node_id: usize) -> bool {
+        let (wire, k) = match graph.get_predecessors(node_id).as_slice() {
+            [a, b] => match (constant_value(graph, *a), constant_value(graph, *b)) {
+                (None, Some(k)) => (*a, k),
+                (Some(k), None) => (*b, k),
+                _ => return false,
+            },
+            _ => return false,
+        };
+
+        let k = match k.to_u64() {
+            // 0 and 1 are left to algebraic simplification
+            Some(k) if (2..=MAX_CHAIN_MULTIPLIER).contains(&k) => k,
+            _ => return false,
+        };
+        if self.cost.addition_chain_cost(k) >= self.cost.const_mul {
+            return false;
+        }
+
+        let data_type = node_data_type(graph, node_id);
+        let mut acc = wire;
+        for bit in (0..(63 - k.leading_zeros())).rev() {
+            acc = add_binary(graph, IRNodeType::Add, data_type.clone(), acc, acc);
+            if (k >> bit) & 1 == 1 {
+                acc = add_binary(graph, IRNodeType::Add, data_type.clone(), acc, wire);
+            }
+        }
+
+        graph.replace_uses(node_id, acc);
+        graph.remove_node(node_id);
+        true
+    }
+
+    /// x / c  =>  x * c^-1 with the inverse computed at compile time
+    fn reduce_div(&self, graph: &mut IRGraph, node_id: usize) -> bool {
+        let (dividend, divisor) = match graph.get_predecessors(node_id).as_slice() {
+            [dividend, divisor] => (*dividend, *divisor),
+            _ => return false,
+        };
+
+        // Division by zero stays in place so the backend reports it
+        let inverse = match constant_value(graph, divisor).and_then(|c| field::inverse(&c)) {
+            Some(inverse) => inverse,
+            None => return false,
+        };
+        if self.cost.const_mul > self.cost.div {
+            return false;
+        }
+
+        let data_type = node_data_type(graph, node_id);
+        let inverse_node = graph.add_node(IRNodeType::Constant(inverse.to_string()), Type::Field, None);
+        let product = add_binary(graph, IRNodeType::Mul, data_type, dividend, inverse_node);
+
+        graph.replace_uses(node_id, product);
+        graph.remove_node(node_id);
+        true
+    }
+}
+
+impl OptimizationPass for StrengthReduction {
+    fn name(&self) -> &str {
+        "strength"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = StrengthReduction::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+fn add_binary(graph: &mut IRGraph, node_type: IRNodeType, data_type: Type, left: usize, right: usize) -> usize {
+    let node = graph.add_node(node_type, data_type, None);
+    graph.add_edge(left, node, EdgeType::DataFlow);
+    graph.add_edge(right, node, EdgeType::DataFlow);
+    node
+}
+
+fn node_data_type(graph: &IRGraph, node_id: usize) -> Type {
+    graph
+        .get_node(node_id)
+        .map(|node| node.data_type.clone())
+        .unwrap_or(Type::Field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TargetSystem;
+    use crate::optimization::interpreter::evaluate;
+    use num_bigint::BigUint;
+    use std::collections::HashMap;
+
+    /// `out = x * k`, or `out = x / k` with `div`
+    fn scaled(k: u64, div: bool) -> IRGraph {
+        let mut graph = IRGraph::new();
+        let x = graph.add_node(IRNodeType::Input("x".to_string()), Type::Field, None);
+        let constant = graph.add_node(IRNodeType::Constant(k.to_string()), Type::Field, None);
+        let op = if div { IRNodeType::Div } else { IRNodeType::Mul };
+        let product = add_binary(&mut graph, op, Type::Field, x, constant);
+        let out = graph.add_node(IRNodeType::Output("out".to_string()), Type::Field, None);
+        graph.add_edge(product, out, EdgeType::DataFlow);
+        graph
+    }
+
+    fn count(graph: &IRGraph, node_type: IRNodeType) -> usize {
+        graph.live_nodes().filter(|node| node.node_type == node_type).count()
+    }
+
+    fn out(graph: &IRGraph, x: u64) -> BigUint {
+        let inputs = HashMap::from([("x".to_string(), BigUint::from(x))]);
+        evaluate(graph, &inputs).unwrap().output("out").cloned().unwrap()
+    }
+
+    #[test]
+    fn constant_product_stays_where_it_is_free() {
+        let mut graph = scaled(5, false);
+        assert_eq!(StrengthReduction::new(CostModel::for_target(TargetSystem::R1CS)).run(&mut graph).unwrap(), 0);
+        assert_eq!(count(&graph, IRNodeType::Mul), 1);
+    }
+
+    #[test]
+    fn doubling_becomes_an_addition_on_air() {
+        let mut graph = scaled(2, false);
+        let before = out(&graph, 21);
+        assert_eq!(StrengthReduction::new(CostModel::for_target(TargetSystem::AIR)).run(&mut graph).unwrap(), 1);
+        assert_eq!((count(&graph, IRNodeType::Mul), count(&graph, IRNodeType::Add)), (0, 1));
+        assert_eq!(out(&graph, 21), before);
+    }
+
+    #[test]
+    fn expensive_constant_product_becomes_an_addition_chain() {
+        let cost = CostModel {
+            const_mul: 8,
+            ..CostModel::for_target(TargetSystem::Plonk)
+        };
+        let mut graph = scaled(5, false);
+        assert_eq!(StrengthReduction::new(cost).run(&mut graph).unwrap(), 1);
+        // Two doublings and one addition of x
+        assert_eq!((count(&graph, IRNodeType::Mul), count(&graph, IRNodeType::Add)), (0, 3));
+        assert_eq!(out(&graph, 7), BigUint::from(35u8));
+    }
+
+    #[test]
+    fn division_by_a_constant_becomes_a_product_with_its_inverse() {
+        let mut graph = scaled(3, true);
+        assert_eq!(StrengthReduction::new(CostModel::default()).run(&mut graph).unwrap(), 1);
+        assert_eq!((count(&graph, IRNodeType::Div), count(&graph, IRNodeType::Mul)), (0, 1));
+        assert_eq!(out(&graph, 12), BigUint::from(4u8));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::target::TargetProfile;
+use crate::FCMCError;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Node attribute naming the repeated pattern a subgraph root belongs to
+pub const GADGET_ATTRIBUTE: &str = "gadget";
+
+/// Smallest subgraph, in operation nodes, worth reporting
+pub const DEFAULT_MIN_SIZE: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatedPattern {
+    pub fingerprint: u64,
+    /// Operation nodes in one copy of the pattern
+    pub size: usize,
+    /// Root node of every copy
+    pub roots: Vec<usize>,
+}
+
+impl RepeatedPattern {
+    pub fn occurrences(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn total_nodes(&self) -> usize {
+        self.size * self.roots.len()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubcircuitReport {
+    /// Largest total footprint first
+    pub patterns: Vec<RepeatedPattern>,
+    pub graph_nodes: usize,
+}
+
+impl SubcircuitReport {
+    /// Human-readable summary of the dominant patterns
+    pub fn summary(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                let share = if self.graph_nodes > 0 {
+                    pattern.total_nodes() as f64 / self.graph_nodes as f64 * 100.0
+                } else {
+                    0.0
+                };
+                format!(
+                    "{:.1}% of the circuit is {} copies of one {}-node pattern ({:016x})",
+                    share,
+                    pattern.occurrences(),
+                    pattern.size,
+                    pattern.fingerprint
+                )
+            })
+            .collect()
+    }
+}
+
+/// Find structurally identical subgraphs that differ only in their inputs
+pub fn find_repeated_subcircuits(graph: &IRGraph, min_size: usize) -> SubcircuitReport {
+    let mut fingerprints: HashMap<usize, u64> = HashMap::new();
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+
+    for node_id in graph.topological_sort() {
+        let node = match graph.get_node(node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+
+        let mut hasher = DefaultHasher::new();
+        let size = match &node.node_type {
+            // Inputs are abstracted so copies applied to different values still match
+            IRNodeType::Input(_) | IRNodeType::PrivateInput(_) => {
+                "input".hash(&mut hasher);
+                0
+            }
+            IRNodeType::Constant(value) => {
+                value.hash(&mut hasher);
+                0
+            }
+            node_type => {
+                node_type.hash(&mut hasher);
+                let operands = graph.get_predecessors(node_id);
+                for operand in &operands {
+                    fingerprints.get(operand).copied().unwrap_or(0).hash(&mut hasher);
+                }
+                1 + operands
+                    .iter()
+                    .map(|operand| sizes.get(operand).copied().unwrap_or(0))
+                    .fold(0usize, usize::saturating_add)
+            }
+        };
+
+        let fingerprint = hasher.finish();
+        fingerprints.insert(node_id, fingerprint);
+        sizes.insert(node_id, size);
+        if size >= min_size {
+            groups.entry(fingerprint).or_default().push(node_id);
+        }
+    }
+
+    let mut patterns: Vec<RepeatedPattern> = groups
+        .into_iter()
+        .filter(|(_, roots)| roots.len() > 1)
+        .map(|(fingerprint, roots)| RepeatedPattern {
+            fingerprint,
+            size: sizes[&roots[0]],
+            roots,
+        })
+        .collect();
+    patterns.sort_by(|a, b| {
+        b.total_nodes()
+            .cmp(&a.total_nodes())
+            .then(a.fingerprint.cmp(&b.fingerprint))
+    });
+
+    // Drop patterns that only occur inside copies of a larger pattern
+    let mut covered = HashSet::new();
+    patterns.retain(|pattern| {
+        if pattern.roots.iter().all(|root| covered.contains(root)) {
+            return false;
+        }
+        for &root in &pattern.roots {
+            collect_cone(graph, root, &mut covered);
+        }
+        true
+    });
+
+    SubcircuitReport {
+        patterns,
+        graph_nodes: graph.node_count(),
+    }
+}
+
+fn collect_cone(graph: &IRGraph, root: usize, cone: &mut HashSet<usize>) {
+    let mut stack = graph.get_predecessors(root);
+    while let Some(node_id) = stack.pop() {
+        if cone.insert(node_id) {
+            stack.extend(graph.get_predecessors(node_id));
+        }
+    }
+}
+
+/// Reports repeated subcircuits and tags their roots so backends with reusable
+/// gadgets (regions, chips) can emit one shared implementation
+pub struct SubcircuitExtraction {
+    min_size: usize,
+}
+
+impl SubcircuitExtraction {
+    pub fn new(min_size: usize) -> Self {
+        Self { min_size }
+    }
+
+    pub fn run(&self, graph: &mut IRGraph) -> Result<SubcircuitReport, FCMCError> {
+        let report = find_repeated_subcircuits(graph, self.min_size);
+        for (index, pattern) in report.patterns.iter().enumerate() {
+            for &root in &pattern.roots {
+                if let Some(node) = graph.get_node_mut(root) {
+                    node.attributes
+                        .insert(GADGET_ATTRIBUTE.to_string(), format!("pattern-{}", index));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl Default for SubcircuitExtraction {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_SIZE)
+    }
+}
+
+impl OptimizationPass for SubcircuitExtraction {
+    fn name(&self) -> &str {
+        "subcircuits"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        let report = SubcircuitExtraction::run(self, graph)?;
+        for line in report.summary() {
+            tracing::info!("{}", line);
+        }
+        // Tagging roots does not change the circuit
+        Ok(false)
+    }
+
+    fn supports(&self, target: &TargetProfile) -> bool {
+        target.custom_gates
+    }
+}
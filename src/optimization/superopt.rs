@@ -0,0 +1,453 @@
+//! Superoptimization of small repeated subcircuits.
+//!
+//! Subcircuits the fingerprinting profiler finds repeated are worth an expensive search:
+//! every expression over the subcircuit's inputs up to a size bound is enumerated, and
+//! expressions are compared by their exact polynomial normal form over the field. The
+//! cheapest expression with the same polynomial is provably the minimal implementation
+//! within that bound, and is substituted into every copy.
+
+use crate::ir::{EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::constant_value;
+use crate::optimization::cost::CostModel;
+use crate::optimization::field;
+use crate::optimization::pass::{AnalysisCache, Changed, OptimizationPass};
+use crate::optimization::subcircuit::find_repeated_subcircuits;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+/// Subcircuits with more inputs than this are not searched
+pub const MAX_LEAVES: usize = 4;
+
+/// Largest expression, in operations, the search enumerates
+pub const MAX_OPERATIONS: usize = 5;
+
+/// Upper bound on candidate expressions examined during one search
+pub const SEARCH_BUDGET: usize = 200_000;
+
+/// Multivariate polynomial: exponent vector -> nonzero coefficient
+type Polynomial = BTreeMap<Vec<u32>, BigUint>;
+
+fn poly_constant(value: BigUint, vars: usize) -> Polynomial {
+    let mut poly = Polynomial::new();
+    if !value.is_zero() {
+        poly.insert(vec![0; vars], value);
+    }
+    poly
+}
+
+fn poly_variable(index: usize, vars: usize) -> Polynomial {
+    let mut exponents = vec![0; vars];
+    exponents[index] = 1;
+    Polynomial::from([(exponents, BigUint::one())])
+}
+
+fn poly_add(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    let mut sum = a.clone();
+    for (exponents, coefficient) in b {
+        let entry = sum.entry(exponents.clone()).or_insert_with(BigUint::zero);
+        *entry = field::add(entry, coefficient);
+        if entry.is_zero() {
+            sum.remove(exponents);
+        }
+    }
+    sum
+}
+
+fn poly_neg(a: &Polynomial) -> Polynomial {
+    a.iter()
+        .map(|(exponents, coefficient)| (exponents.clone(), field::neg(coefficient)))
+        .collect()
+}
+
+fn poly_mul(a: &Polynomial, b: &Polynomial) -> Polynomial {
+    let mut product = Polynomial::new();
+    for (ea, ca) in a {
+        for (eb, cb) in b {
+            let exponents: Vec<u32> = ea.iter().zip(eb).map(|(x, y)| x + y).collect();
+            let entry = product.entry(exponents).or_insert_with(BigUint::zero);
+            *entry = field::add(entry, &field::mul(ca, cb));
+        }
+    }
+    product.retain(|_, coefficient| !coefficient.is_zero());
+    product
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Leaf(usize),
+    Const(BigUint),
+    Neg(Rc<Expr>),
+    Binary(Op, Rc<Expr>, Rc<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    expr: Rc<Expr>,
+    poly: Polynomial,
+    cost: u64,
+}
+
+/// Arithmetic cone of a root: its inputs in first-visit order and its operation nodes
+struct Cone {
+    leaves: Vec<usize>,
+    operations: Vec<usize>,
+}
+
+/// Exhaustive search for the cheapest implementation of hot subcircuits
+pub struct Superoptimizer {
+    cost: CostModel,
+    max_operations: usize,
+    last_rewrites: usize,
+}
+
+impl Superoptimizer {
+    pub fn new(cost: CostModel) -> Self {
+        Self {
+            cost,
+            max_operations: MAX_OPERATIONS,
+            last_rewrites: 0,
+        }
+    }
+
+    pub fn with_max_operations(mut self, max_operations: usize) -> Self {
+        self.max_operations = max_operations;
+        self
+    }
+
+    /// Returns the number of subcircuit copies replaced
+    pub fn run(&self, graph: &mut IRGraph) -> Result<usize, FCMCError> {
+        let report = find_repeated_subcircuits(graph, 2);
+        let mut replaced = 0;
+
+        for pattern in &report.patterns {
+            // A pattern rooted at a constraint or output is optimized through its arithmetic operands
+            let copies: Vec<Vec<usize>> = pattern
+                .roots
+                .iter()
+                .map(|&root| arithmetic_roots(graph, root))
+                .collect();
+            for position in 0..copies[0].len() {
+                let roots: Vec<usize> = copies.iter().filter_map(|roots| roots.get(position).copied()).collect();
+                replaced += self.optimize_copies(graph, &roots);
+            }
+        }
+
+        Ok(replaced)
+    }
+
+    /// Search once for the first copy and substitute the result into every equivalent copy
+    fn optimize_copies(&self, graph: &mut IRGraph, roots: &[usize]) -> usize {
+        let template = match arithmetic_cone(graph, roots[0]) {
+            Some(cone) if cone.leaves.len() <= MAX_LEAVES && cone.operations.len() > 1 => cone,
+            _ => return 0,
+        };
+        let target = match cone_polynomial(graph, roots[0], &template) {
+            Some(poly) => poly,
+            None => return 0,
+        };
+        let current_cost = self.dag_cost(graph, &template.operations);
+
+        let best = match self.search(graph, &template, &target, current_cost) {
+            Some(best) => best,
+            None => return 0,
+        };
+        tracing::debug!(
+            "Superoptimizer: {}-op subcircuit repeated {} times costs {} instead of {}",
+            template.operations.len(),
+            roots.len(),
+            best.cost,
+            current_cost
+        );
+
+        let mut replaced = 0;
+        for &root in roots {
+            // Copies only qualify if they compute the same polynomial of their own inputs
+            let cone = match arithmetic_cone(graph, root) {
+                Some(cone) if cone.leaves.len() == template.leaves.len() => cone,
+                _ => continue,
+            };
+            if cone_polynomial(graph, root, &cone).as_ref() != Some(&target) {
+                continue;
+            }
+            let new_root = materialize(graph, &best.expr, &cone.leaves);
+            graph.replace_uses(root, new_root);
+            replaced += 1;
+        }
+        replaced
+    }
+
+    fn op_cost(&self, op: Op, left: &Expr, right: &Expr) -> u64 {
+        let unit = match op {
+            Op::Add | Op::Sub => self.cost.add,
+            Op::Mul if matches!(left, Expr::Const(_)) || matches!(right, Expr::Const(_)) => self.cost.const_mul,
+            Op::Mul => self.cost.mul,
+        };
+        unit as u64
+    }
+
+    fn dag_cost(&self, graph: &IRGraph, operations: &[usize]) -> u64 {
+        operations
+            .iter()
+            .map(|&node_id| {
+                let operands = graph.get_predecessors(node_id);
+                let unit = match graph.get_node(node_id).map(|node| &node.node_type) {
+                    Some(IRNodeType::Add) | Some(IRNodeType::Sub) => self.cost.add,
+                    Some(IRNodeType::Neg) => self.cost.const_mul,
+                    Some(IRNodeType::Mul) if operands.iter().any(|&o| constant_value(graph, o).is_some()) => {
+                        self.cost.const_mul
+                    }
+                    Some(IRNodeType::Mul) => self.cost.mul,
+                    _ => 0,
+                };
+                unit as u64
+            })
+            .sum()
+    }
+
+    /// Cheapest expression of at most `max_operations` operations computing `target`,
+    /// if one is cheaper than `current_cost`
+    fn search(&self, graph: &IRGraph, cone: &Cone, target: &Polynomial, current_cost: u64) -> Option<Candidate> {
+        let vars = cone.leaves.len();
+        let mut constants: Vec<BigUint> = vec![BigUint::one(), BigUint::from(2u32)];
+        for &node_id in &cone.operations {
+            for operand in graph.get_predecessors(node_id) {
+                if let Some(value) = constant_value(graph, operand) {
+                    if !constants.contains(&value) {
+                        constants.push(value);
+                    }
+                }
+            }
+        }
+
+        // banks[n] holds the expressions with exactly n operations that compute a new polynomial
+        let mut banks: Vec<Vec<Candidate>> = vec![Vec::new()];
+        let mut search = Search {
+            target,
+            bound: current_cost,
+            best: HashMap::new(),
+            found: None,
+        };
+
+        let atoms = (0..vars)
+            .map(|index| Candidate {
+                expr: Rc::new(Expr::Leaf(index)),
+                poly: poly_variable(index, vars),
+                cost: 0,
+            })
+            .chain(constants.into_iter().map(|value| Candidate {
+                poly: poly_constant(value.clone(), vars),
+                expr: Rc::new(Expr::Const(value)),
+                cost: 0,
+            }));
+        for atom in atoms {
+            if search.best.insert(atom.poly.clone(), 0).is_none() {
+                banks[0].push(atom);
+            }
+        }
+
+        let mut explored = 0;
+        for size in 1..=self.max_operations {
+            let mut bank = Vec::new();
+
+            for operand in &banks[size - 1] {
+                let candidate = Candidate {
+                    expr: Rc::new(Expr::Neg(operand.expr.clone())),
+                    poly: poly_neg(&operand.poly),
+                    cost: operand.cost + self.cost.const_mul as u64,
+                };
+                search.consider(candidate, &mut bank);
+            }
+
+            for left_size in 0..size {
+                let right_size = size - 1 - left_size;
+                for left in &banks[left_size] {
+                    for right in &banks[right_size] {
+                        for op in [Op::Add, Op::Sub, Op::Mul] {
+                            // Commutative operations only need one operand order
+                            if op != Op::Sub && left_size > right_size {
+                                continue;
+                            }
+                            let poly = match op {
+                                Op::Add => poly_add(&left.poly, &right.poly),
+                                Op::Sub => poly_add(&left.poly, &poly_neg(&right.poly)),
+                                Op::Mul => poly_mul(&left.poly, &right.poly),
+                            };
+                            let cost = left.cost + right.cost + self.op_cost(op, &left.expr, &right.expr);
+                            let expr = Rc::new(Expr::Binary(op, left.expr.clone(), right.expr.clone()));
+                            search.consider(Candidate { expr, poly, cost }, &mut bank);
+
+                            explored += 1;
+                            if explored >= SEARCH_BUDGET {
+                                return search.found;
+                            }
+                        }
+                    }
+                }
+            }
+
+            banks.push(bank);
+        }
+
+        search.found
+    }
+}
+
+struct Search<'a> {
+    target: &'a Polynomial,
+    /// Only expressions cheaper than this are of interest
+    bound: u64,
+    /// Cheapest cost seen per polynomial
+    best: HashMap<Polynomial, u64>,
+    found: Option<Candidate>,
+}
+
+impl Search<'_> {
+    /// Keep `candidate` if it is the cheapest way seen so far to compute its polynomial
+    fn consider(&mut self, candidate: Candidate, bank: &mut Vec<Candidate>) {
+        if candidate.cost >= self.bound {
+            return;
+        }
+        if self.best.get(&candidate.poly).map_or(false, |&cost| cost <= candidate.cost) {
+            return;
+        }
+        self.best.insert(candidate.poly.clone(), candidate.cost);
+        if &candidate.poly == self.target && self.found.as_ref().map_or(true, |f| candidate.cost < f.cost) {
+            self.found = Some(candidate.clone());
+        }
+        bank.push(candidate);
+    }
+}
+
+impl OptimizationPass for Superoptimizer {
+    fn name(&self) -> &str {
+        "superopt"
+    }
+
+    fn run(&mut self, graph: &mut IRGraph, _analyses: &AnalysisCache) -> Result<Changed, FCMCError> {
+        self.last_rewrites = Superoptimizer::run(self, graph)?;
+        Ok(self.last_rewrites > 0)
+    }
+
+    fn rewrites(&self) -> Option<usize> {
+        Some(self.last_rewrites)
+    }
+}
+
+fn is_arithmetic(node_type: &IRNodeType) -> bool {
+    matches!(node_type, IRNodeType::Add | IRNodeType::Sub | IRNodeType::Mul | IRNodeType::Neg)
+}
+
+fn arithmetic_roots(graph: &IRGraph, root: usize) -> Vec<usize> {
+    let is_arithmetic_node =
+        |node_id: usize| graph.get_node(node_id).map_or(false, |node| is_arithmetic(&node.node_type));
+    if is_arithmetic_node(root) {
+        vec![root]
+    } else {
+        graph.get_predecessors(root).into_iter().filter(|&id| is_arithmetic_node(id)).collect()
+    }
+}
+
+/// Operations feeding `root` that compute nothing else; anything else is an input
+fn arithmetic_cone(graph: &IRGraph, root: usize) -> Option<Cone> {
+    if !is_arithmetic(&graph.get_node(root)?.node_type) {
+        return None;
+    }
+
+    let mut cone = Cone {
+        leaves: Vec::new(),
+        operations: Vec::new(),
+    };
+    let mut visited = HashSet::new();
+    visit(graph, root, root, &mut visited, &mut cone);
+    Some(cone)
+}
+
+fn visit(graph: &IRGraph, root: usize, node_id: usize, visited: &mut HashSet<usize>, cone: &mut Cone) {
+    if !visited.insert(node_id) {
+        return;
+    }
+
+    let internal = node_id == root
+        || (graph.get_node(node_id).map_or(false, |node| is_arithmetic(&node.node_type))
+            && graph.get_successors(node_id).len() == 1);
+    if constant_value(graph, node_id).is_some() {
+        return;
+    }
+    if !internal {
+        cone.leaves.push(node_id);
+        return;
+    }
+
+    for operand in graph.get_predecessors(node_id) {
+        visit(graph, root, operand, visited, cone);
+    }
+    cone.operations.push(node_id);
+}
+
+fn cone_polynomial(graph: &IRGraph, root: usize, cone: &Cone) -> Option<Polynomial> {
+    let vars = cone.leaves.len();
+    let mut polys: HashMap<usize, Polynomial> = cone
+        .leaves
+        .iter()
+        .enumerate()
+        .map(|(index, &leaf)| (leaf, poly_variable(index, vars)))
+        .collect();
+
+    for &node_id in &cone.operations {
+        let operands: Vec<Polynomial> = graph
+            .get_predecessors(node_id)
+            .into_iter()
+            .map(|operand| match constant_value(graph, operand) {
+                Some(value) => Some(poly_constant(value, vars)),
+                None => polys.get(&operand).cloned(),
+            })
+            .collect::<Option<_>>()?;
+        let poly = match (&graph.get_node(node_id)?.node_type, operands.as_slice()) {
+            (IRNodeType::Add, [a, b]) => poly_add(a, b),
+            (IRNodeType::Sub, [a, b]) => poly_add(a, &poly_neg(b)),
+            (IRNodeType::Mul, [a, b]) => poly_mul(a, b),
+            (IRNodeType::Neg, [a]) => poly_neg(a),
+            _ => return None,
+        };
+        polys.insert(node_id, poly);
+    }
+
+    polys.remove(&root)
+}
+
+fn materialize(graph: &mut IRGraph, expr: &Expr, leaves: &[usize]) -> usize {
+    match expr {
+        Expr::Leaf(index) => leaves[*index],
+        Expr::Const(value) => graph.add_node(IRNodeType::Constant(value.to_string()), Type::Field, None),
+        Expr::Neg(operand) => {
+            let operand = materialize(graph, operand, leaves);
+            let id = graph.add_node(IRNodeType::Neg, Type::Field, None);
+            graph.add_edge(operand, id, EdgeType::DataFlow);
+            id
+        }
+        Expr::Binary(op, left, right) => {
+            let left = materialize(graph, left, leaves);
+            let right = materialize(graph, right, leaves);
+            let node_type = match op {
+                Op::Add => IRNodeType::Add,
+                Op::Sub => IRNodeType::Sub,
+                Op::Mul => IRNodeType::Mul,
+            };
+            let id = graph.add_node(node_type, Type::Field, None);
+            graph.add_edge(left, id, EdgeType::DataFlow);
+            graph.add_edge(right, id, EdgeType::DataFlow);
+            id
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::backend::TargetSystem;
+use crate::optimization::cost::CostModel;
+
+/// What the optimizer knows about the backend the circuit is compiled for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetProfile {
+    pub system: TargetSystem,
+    pub cost_model: CostModel,
+    /// Lookup arguments are available, so range checks and small functions can use tables
+    pub lookups: bool,
+    /// Custom gates or reusable regions are available for repeated gadgets
+    pub custom_gates: bool,
+}
+
+impl TargetProfile {
+    pub fn for_target(system: TargetSystem) -> Self {
+        let capabilities = system.capabilities();
+        Self {
+            cost_model: CostModel::for_target(system.clone()),
+            system,
+            lookups: capabilities.lookups,
+            custom_gates: capabilities.custom_gates,
+        }
+    }
+}
+
+impl Default for TargetProfile {
+    fn default() -> Self {
+        Self::for_target(TargetSystem::R1CS)
+    }
+}
@@ -0,0 +1,397 @@
+//! Packages: libraries of compiled gadgets, shared without their source.
+//!
+//! A package has a name, a version, the field it was compiled for and its gadgets, each
+//! the optimized circuit of a `main` that only calls one exported function. A program
+//! calls a gadget as `<package>::<function>` once the package is linked, with
+//! `FCMC::with_package` or from an `fcmc.toml`'s `[packages]` table; the gadget's IR is
+//! copied into the caller in place of the call, its inputs bound to the arguments in the
+//! order the function declares them, arrays flattened, and its outputs returned in order.
+//!
+//! A `.fcmp` file is the magic `FCMP`, the format version, the name, version and field,
+//! then each gadget as its function name and its circuit in the artifact format. Its
+//! SHA-256 `digest` is what a manifest pins a package fetched from a URL to.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field::{self, FieldConfig};
+use crate::optimization::interpreter::evaluate;
+use crate::optimization::subcircuit::GADGET_ATTRIBUTE;
+use crate::semantics::Value;
+use crate::stdlib::sha256;
+use crate::{artifact, CompiledCircuit, FCMCError, InputMap, FCMC};
+use num_bigint::BigUint;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"FCMP";
+
+/// Version written to new packages; packages of any other version are rejected
+pub const PACKAGE_VERSION: u32 = 1;
+
+/// Extension of package files
+pub const PACKAGE_EXTENSION: &str = "fcmp";
+
+/// One exported function, compiled
+pub struct Gadget {
+    pub name: String,
+    pub circuit: CompiledCircuit,
+}
+
+impl Gadget {
+    /// Input nodes of the gadget's IR, in the order calls pass arguments
+    fn inputs(&self) -> Vec<(usize, String)> {
+        self.circuit
+            .ir
+            .live_nodes()
+            .filter_map(|node| match &node.node_type {
+                IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => Some((node.id, name.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Field elements a call passes
+    pub fn arity(&self) -> usize {
+        self.inputs().len()
+    }
+}
+
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub field: FieldConfig,
+    pub gadgets: Vec<Gadget>,
+}
+
+impl Package {
+    /// Compile each function of `exports` from `source` into a gadget with `compiler`
+    pub fn build(
+        compiler: &FCMC,
+        name: &str,
+        version: &str,
+        source: &str,
+        exports: &[&str],
+    ) -> Result<Self, FCMCError> {
+        check_name(name)?;
+        let mut gadgets = Vec::new();
+        for export in exports {
+            let header = function_header(source, export)?;
+            let (parameters, return_type) = crate::repl::signature(header)?;
+            let arguments: Vec<&str> = parameters
+                .iter()
+                .map(|(name, _)| name.split_whitespace().last().unwrap_or(name))
+                .collect();
+            let parameters: Vec<String> = parameters
+                .iter()
+                .map(|(name, type_name)| format!("{}: {}", name, type_name))
+                .collect();
+            let return_type = return_type
+                .ok_or_else(|| FCMCError::SemanticError(format!("Exported function '{}' returns nothing", export)))?;
+            let caller = format!(
+                "{}\nfn main({}) -> {} {{\n    return {}({});\n}}\n",
+                source,
+                parameters.join(", "),
+                return_type,
+                export,
+                arguments.join(", ")
+            );
+            let circuit = compiler.compile(&caller)?;
+            if !circuit.ir.loops().is_empty() || !circuit.ir.tables().is_empty() {
+                return Err(FCMCError::SemanticError(format!(
+                    "Exported function '{}' compiles to loops or lookup tables, which packages cannot carry",
+                    export
+                )));
+            }
+            gadgets.push(Gadget {
+                name: export.to_string(),
+                circuit,
+            });
+        }
+        Ok(Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            field: compiler.field(),
+            gadgets,
+        })
+    }
+
+    pub fn gadget(&self, name: &str) -> Option<&Gadget> {
+        self.gadgets.iter().find(|gadget| gadget.name == name)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&PACKAGE_VERSION.to_le_bytes());
+        put_bytes(&mut out, self.name.as_bytes());
+        put_bytes(&mut out, self.version.as_bytes());
+        put_bytes(&mut out, self.field.to_string().as_bytes());
+        out.extend_from_slice(&(self.gadgets.len() as u64).to_le_bytes());
+        for gadget in &self.gadgets {
+            put_bytes(&mut out, gadget.name.as_bytes());
+            put_bytes(&mut out, &artifact::encode(&gadget.circuit));
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, FCMCError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(corrupt("not an fcmc package"));
+        }
+        let version = u32::from_le_bytes(reader.take(4)?.try_into().expect("four bytes"));
+        if version != PACKAGE_VERSION {
+            return Err(FCMCError::BackendError(format!(
+                "Package format version {} is not supported; this compiler reads version {}",
+                version, PACKAGE_VERSION
+            )));
+        }
+        let name = reader.string()?;
+        let package_version = reader.string()?;
+        let field = reader.string()?.parse::<FieldConfig>()?;
+        let count = reader.u64()?;
+        let mut gadgets = Vec::new();
+        for _ in 0..count {
+            let name = reader.string()?;
+            let circuit = artifact::decode(reader.bytes()?)?;
+            gadgets.push(Gadget { name, circuit });
+        }
+        if !reader.bytes.is_empty() {
+            return Err(corrupt("trailing bytes"));
+        }
+        Ok(Self {
+            name,
+            version: package_version,
+            field,
+            gadgets,
+        })
+    }
+
+    /// SHA-256 of the encoded package, in hex
+    pub fn digest(&self) -> String {
+        hex::encode(sha256(&self.encode()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FCMCError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.encode())
+            .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FCMCError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| FCMCError::BackendError(format!("Failed to read {}: {}", path.display(), e)))?;
+        Self::decode(&bytes).map_err(|e| FCMCError::BackendError(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Whether a package of version `actual` satisfies a manifest asking for `requested`:
+/// `1.2` accepts `1.2`, `1.2.0` and `1.2.7`, not `1.3.0`
+pub fn version_matches(requested: &str, actual: &str) -> bool {
+    let actual: Vec<&str> = actual.split('.').collect();
+    let requested: Vec<&str> = requested.split('.').collect();
+    requested.len() <= actual.len() && requested.iter().zip(&actual).all(|(a, b)| a == b)
+}
+
+/// Fail unless `name` can prefix a call path
+pub fn check_name(name: &str) -> Result<(), FCMCError> {
+    if name.is_empty() || name == "std" || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(FCMCError::SemanticError(format!(
+            "'{}' cannot name a package; use letters, digits and underscores, and not 'std'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Download `url` to `path` with curl
+pub fn fetch(url: &str, path: &Path) -> Result<(), FCMCError> {
+    let status = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", "--output"])
+        .arg(path)
+        .arg(url)
+        .status()
+        .map_err(|e| FCMCError::BackendError(format!("Failed to run curl to fetch {}: {}", url, e)))?;
+    if !status.success() {
+        return Err(FCMCError::BackendError(format!(
+            "Failed to fetch {}: curl {}",
+            url, status
+        )));
+    }
+    Ok(())
+}
+
+thread_local! {
+    /// Packages linked on this thread
+    static LINKED: RefCell<Vec<Arc<Package>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `f` with calls to the gadgets of `packages` resolved on this thread
+pub fn link<T>(packages: &[Arc<Package>], f: impl FnOnce() -> T) -> T {
+    let previous = LINKED.with(|linked| linked.replace(packages.to_vec()));
+    let result = f();
+    LINKED.with(|linked| *linked.borrow_mut() = previous);
+    result
+}
+
+/// Whether `path` calls a gadget of a linked package
+pub fn is_linked(path: &str) -> bool {
+    linked(path).is_some()
+}
+
+fn linked(path: &str) -> Option<(Arc<Package>, usize)> {
+    let (package, function) = path.split_once("::")?;
+    LINKED.with(|linked| {
+        let package = linked.borrow().iter().find(|linked| linked.name == package)?.clone();
+        let index = package.gadgets.iter().position(|gadget| gadget.name == function)?;
+        Some((package, index))
+    })
+}
+
+/// The gadget `path` names, checked against the active field and `args`
+fn callable(path: &str, arguments: usize) -> Option<Result<(Arc<Package>, usize), FCMCError>> {
+    let (package, index) = linked(path)?;
+    if package.field.modulus() != field::modulus() {
+        return Some(Err(FCMCError::SemanticError(format!(
+            "{} was compiled for the {} field, not {}",
+            path,
+            package.field,
+            field::active()
+        ))));
+    }
+    let arity = package.gadgets[index].arity();
+    if arguments != arity {
+        return Some(Err(FCMCError::SemanticError(format!(
+            "{} takes {} field element(s), not {}",
+            path, arity, arguments
+        ))));
+    }
+    Some(Ok((package, index)))
+}
+
+/// Copy the gadget `path` names into `graph` on the nodes `args`, returning the nodes
+/// holding its outputs, or `None` if no linked package has it
+pub fn lower_call(graph: &mut IRGraph, path: &str, args: &[usize]) -> Option<Result<Vec<usize>, FCMCError>> {
+    Some(callable(path, args.len())?.map(|(package, index)| {
+        let gadget = &package.gadgets[index];
+        let source = &gadget.circuit.ir;
+        let mut copies: HashMap<usize, usize> = gadget
+            .inputs()
+            .into_iter()
+            .map(|(node, _)| node)
+            .zip(args.iter().copied())
+            .collect();
+        let mut outputs = Vec::new();
+        for node in source.live_nodes() {
+            match node.node_type {
+                IRNodeType::Input(_) | IRNodeType::PrivateInput(_) => {}
+                IRNodeType::Output(_) => outputs.push(node.id),
+                _ => {
+                    let copy = graph.add_node(node.node_type.clone(), node.data_type.clone(), node.label.clone());
+                    if let Some(copied) = graph.get_node_mut(copy) {
+                        copied.attributes = node.attributes.clone();
+                        copied.attributes.insert(GADGET_ATTRIBUTE.to_string(), path.to_string());
+                    }
+                    copies.insert(node.id, copy);
+                }
+            }
+        }
+        for (from, to, edge_type) in source.edges() {
+            if let (Some(&from), Some(&to)) = (copies.get(from), copies.get(to)) {
+                graph.add_edge(from, to, edge_type.clone());
+            }
+        }
+        // Each output is the node feeding it
+        outputs
+            .into_iter()
+            .filter_map(|output| {
+                source
+                    .edges()
+                    .iter()
+                    .find(|(_, to, _)| *to == output)
+                    .and_then(|(from, _, _)| copies.get(from).copied())
+            })
+            .collect()
+    }))
+}
+
+/// Evaluate the gadget `path` names on `args`, or `None` if no linked package has it
+pub fn eval_call(path: &str, args: &[BigUint]) -> Option<Result<Value, FCMCError>> {
+    Some(callable(path, args.len())?.and_then(|(package, index)| {
+        let gadget = &package.gadgets[index];
+        let inputs: InputMap = gadget
+            .inputs()
+            .into_iter()
+            .map(|(_, name)| name)
+            .zip(args.iter().cloned())
+            .collect();
+        let evaluation = evaluate(&gadget.circuit.ir, &inputs)?;
+        if !evaluation.is_satisfied() {
+            return Err(FCMCError::VerificationError(format!(
+                "{} fails {} of its constraints on these arguments",
+                path,
+                evaluation.violated.len()
+            )));
+        }
+        let mut values: Vec<Value> = evaluation
+            .outputs
+            .into_iter()
+            .map(|(_, value)| Value::Field(value))
+            .collect();
+        Ok(match values.len() {
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        })
+    }))
+}
+
+/// The header of `function` in `source`, up to its opening brace
+fn function_header<'a>(source: &'a str, function: &str) -> Result<&'a str, FCMCError> {
+    let pattern = format!("fn {}(", function);
+    let start = source
+        .find(&pattern)
+        .or_else(|| source.find(&format!("fn {} (", function)))
+        .ok_or_else(|| FCMCError::SemanticError(format!("No function '{}' to export", function)))?;
+    let end = source[start..]
+        .find('{')
+        .ok_or_else(|| FCMCError::ParseError(format!("Function '{}' has no body", function)))?;
+    Ok(&source[start..start + end])
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], FCMCError> {
+        if length > self.bytes.len() {
+            return Err(corrupt("truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> Result<u64, FCMCError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("eight bytes")))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], FCMCError> {
+        let length = usize::try_from(self.u64()?).map_err(|_| corrupt("length too large"))?;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, FCMCError> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| corrupt("invalid UTF-8"))
+    }
+}
+
+fn corrupt(reason: &str) -> FCMCError {
+    FCMCError::BackendError(format!("Corrupt package: {}", reason))
+}
@@ -0,0 +1,37 @@
+//! What differs between native builds and `wasm32-unknown-unknown`, which has no threads
+//! and no clock in `std`.
+//!
+//! With the `parallel` feature, on by default, data-parallel loops run on the rayon pool;
+//! without it the same `par_iter` and `par_chunks` calls run on the current thread.
+//! `Instant` is `std`'s, or on wasm the browser clock's through `web-time`.
+
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) use sequential::ParallelSlice;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    /// The slice methods of rayon's prelude, as sequential iterators
+    pub(crate) trait ParallelSlice<T> {
+        fn par_iter(&self) -> std::slice::Iter<'_, T>;
+
+        fn par_chunks(&self, size: usize) -> std::slice::Chunks<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_iter(&self) -> std::slice::Iter<'_, T> {
+            self.iter()
+        }
+
+        fn par_chunks(&self, size: usize) -> std::slice::Chunks<'_, T> {
+            self.chunks(size)
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Constraint profiles: where a circuit's rows come from, by function, loop, source line
+//! and gadget.
+//!
+//! Each row is attributed through its tag, the source position of the IR node it was
+//! lowered from, and each position through an outline of the source taken when it was
+//! compiled: the lines every function and `for` loop spans. Inlined calls keep the
+//! callee's positions, so a function's count is what its own lines cost over every call
+//! site, and an unrolled loop's count covers all of its iterations. Rows without a
+//! position are grouped under their gadget, or left unattributed, as are all rows of
+//! targets that do not tag them.
+//!
+//! `folded` writes the tree as the collapsed stacks `flamegraph.pl` and inferno read,
+//! and `to_json` as the nested `{name, value, children}` objects of d3-flame-graph.
+
+use crate::backend::CircuitBackend;
+use crate::CompiledCircuit;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// A function or loop of the source
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RegionKind {
+    Function(String),
+    /// A `for` loop, by its induction variable
+    Loop(String),
+}
+
+/// A function or loop and the lines it spans, both included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Region {
+    fn frame(&self) -> Frame {
+        match &self.kind {
+            RegionKind::Function(name) => Frame::Function(name.clone()),
+            RegionKind::Loop(variable) => Frame::Loop {
+                variable: variable.clone(),
+                line: self.start,
+            },
+        }
+    }
+}
+
+/// The functions and loops of a program in the order they open, so every region comes
+/// after those enclosing it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceOutline {
+    pub regions: Vec<Region>,
+}
+
+impl SourceOutline {
+    /// Outline of `source` from its keywords and braces, skipping comments and strings
+    pub fn of(source: &str) -> Self {
+        let mut regions: Vec<Region> = Vec::new();
+        // Index into `regions` of each open brace that opened a region
+        let mut braces: Vec<Option<usize>> = Vec::new();
+        // The kind of region `fn` or `for` starts and the keyword's line, waiting for the
+        // name that follows it
+        let mut keyword: Option<(fn(String) -> RegionKind, usize)> = None;
+        // The region the next `{` opens
+        let mut pending: Option<(RegionKind, usize)> = None;
+        let mut line = 1;
+        let mut chars = source.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '\n' => line += 1,
+                '/' if chars.peek().map(|&(_, next)| next) == Some('/') => {
+                    while chars.peek().is_some_and(|&(_, next)| next != '\n') {
+                        chars.next();
+                    }
+                }
+                '/' if chars.peek().map(|&(_, next)| next) == Some('*') => {
+                    chars.next();
+                    let mut previous = ' ';
+                    for (_, next) in chars.by_ref() {
+                        if next == '\n' {
+                            line += 1;
+                        }
+                        if previous == '*' && next == '/' {
+                            break;
+                        }
+                        previous = next;
+                    }
+                }
+                '"' => {
+                    let mut escaped = false;
+                    for (_, next) in chars.by_ref() {
+                        match next {
+                            '\n' => line += 1,
+                            '"' if !escaped => break,
+                            _ => {}
+                        }
+                        escaped = next == '\\' && !escaped;
+                    }
+                }
+                '{' => {
+                    braces.push(pending.take().map(|(kind, start)| {
+                        regions.push(Region {
+                            kind,
+                            start,
+                            end: start,
+                        });
+                        regions.len() - 1
+                    }));
+                }
+                '}' => {
+                    if let Some(Some(index)) = braces.pop() {
+                        regions[index].end = line;
+                    }
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut end = position + c.len_utf8();
+                    while let Some(&(next_position, next)) = chars.peek() {
+                        if !(next.is_alphanumeric() || next == '_') {
+                            break;
+                        }
+                        end = next_position + next.len_utf8();
+                        chars.next();
+                    }
+                    let word = &source[position..end];
+                    if let Some((kind, start)) = keyword.take() {
+                        pending = Some((kind(word.to_string()), start));
+                    } else if word == "fn" {
+                        keyword = Some((RegionKind::Function, line));
+                    } else if word == "for" {
+                        keyword = Some((RegionKind::Loop, line));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self { regions }
+    }
+
+    /// The regions spanning `line`, outermost first
+    pub fn enclosing(&self, line: usize) -> impl Iterator<Item = &Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.start <= line && line <= region.end)
+    }
+}
+
+/// One level of a profile's tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Frame {
+    Function(String),
+    /// A `for` loop by its induction variable and the line it starts on
+    Loop {
+        variable: String,
+        line: usize,
+    },
+    Line(usize),
+    /// Rows of a library gadget or black-box call
+    Gadget(String),
+    /// Rows with neither a source position nor a gadget
+    Unattributed,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Frame::Function(name) => write!(f, "fn {}", name),
+            Frame::Loop { variable, line } => write!(f, "for {} (line {})", variable, line),
+            Frame::Line(line) => write!(f, "line {}", line),
+            Frame::Gadget(name) => write!(f, "gadget {}", name),
+            Frame::Unattributed => f.write_str("unattributed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileNode {
+    pub frame: Frame,
+    /// Rows attributed to the frame and everything below it
+    pub constraints: usize,
+    /// Largest first
+    pub children: Vec<ProfileNode>,
+}
+
+impl ProfileNode {
+    /// Rows attributed to the frame itself rather than a child
+    pub fn own_constraints(&self) -> usize {
+        self.constraints - self.children.iter().map(|child| child.constraints).sum::<usize>()
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.frame.to_string(),
+            "value": self.constraints,
+            "children": self.children.iter().map(ProfileNode::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A circuit's rows as a tree of functions, loops, lines and gadgets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConstraintProfile {
+    pub constraints: usize,
+    /// Top-level frames, largest first
+    pub roots: Vec<ProfileNode>,
+}
+
+impl ConstraintProfile {
+    /// Rows per function, largest first
+    pub fn functions(&self) -> Vec<(String, usize)> {
+        let mut totals = BTreeMap::new();
+        self.visit(&mut |node| {
+            if let Frame::Function(name) = &node.frame {
+                *totals.entry(name.clone()).or_insert(0) += node.constraints;
+            }
+        });
+        largest_first(totals)
+    }
+
+    /// Rows per source line, largest first
+    pub fn lines(&self) -> Vec<(usize, usize)> {
+        let mut totals = BTreeMap::new();
+        self.visit(&mut |node| {
+            if let Frame::Line(line) = node.frame {
+                *totals.entry(line).or_insert(0) += node.constraints;
+            }
+        });
+        largest_first(totals)
+    }
+
+    /// The tree indented by depth, each frame with its rows and share of the circuit
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} constraints", self.constraints)];
+        let mut stack: Vec<(usize, &ProfileNode)> = self.roots.iter().rev().map(|node| (1, node)).collect();
+        while let Some((depth, node)) = stack.pop() {
+            lines.push(format!(
+                "{}{}: {} ({:.1}%)",
+                "  ".repeat(depth),
+                node.frame,
+                node.constraints,
+                100.0 * node.constraints as f64 / self.constraints.max(1) as f64
+            ));
+            stack.extend(node.children.iter().rev().map(|child| (depth + 1, child)));
+        }
+        lines
+    }
+
+    /// One `frame;frame;frame rows` line per frame with rows of its own
+    pub fn folded(&self) -> String {
+        let mut out = String::new();
+        let mut stack: Vec<(String, &ProfileNode)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|node| (node.frame.to_string(), node))
+            .collect();
+        while let Some((path, node)) = stack.pop() {
+            if node.own_constraints() > 0 {
+                out.push_str(&format!("{} {}\n", path, node.own_constraints()));
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|child| (format!("{};{}", path, child.frame), child)),
+            );
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": "circuit",
+            "value": self.constraints,
+            "children": self.roots.iter().map(ProfileNode::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    fn visit<'a>(&'a self, f: &mut impl FnMut(&'a ProfileNode)) {
+        let mut stack: Vec<&ProfileNode> = self.roots.iter().collect();
+        while let Some(node) = stack.pop() {
+            f(node);
+            stack.extend(&node.children);
+        }
+    }
+}
+
+pub(crate) fn profile(compiled: &CompiledCircuit) -> ConstraintProfile {
+    let tags = compiled.circuit.constraint_tags();
+    let constraints = compiled.circuit.constraint_count();
+    // Rows by line and gadget, so the tree is walked once per distinct tag
+    let mut counts: BTreeMap<(Option<usize>, Option<String>), usize> = BTreeMap::new();
+    for tag in &tags {
+        let key = tag.map_or((None, None), |tag| (tag.span.map(|span| span.line), tag.gadget.clone()));
+        *counts.entry(key).or_insert(0) += 1;
+    }
+    if constraints > tags.len() {
+        *counts.entry((None, None)).or_insert(0) += constraints - tags.len();
+    }
+
+    let outline = &compiled.stats.outline;
+    let mut roots = Vec::new();
+    for ((line, gadget), count) in counts {
+        let mut path = Vec::new();
+        if let Some(line) = line {
+            path.extend(outline.enclosing(line).map(Region::frame));
+            path.push(Frame::Line(line));
+        }
+        path.extend(gadget.map(Frame::Gadget));
+        if path.is_empty() {
+            path.push(Frame::Unattributed);
+        }
+        insert(&mut roots, &path, count);
+    }
+    sort(&mut roots);
+    ConstraintProfile { constraints, roots }
+}
+
+fn insert(nodes: &mut Vec<ProfileNode>, path: &[Frame], count: usize) {
+    let Some((frame, rest)) = path.split_first() else {
+        return;
+    };
+    let index = match nodes.iter().position(|node| node.frame == *frame) {
+        Some(index) => index,
+        None => {
+            nodes.push(ProfileNode {
+                frame: frame.clone(),
+                constraints: 0,
+                children: Vec::new(),
+            });
+            nodes.len() - 1
+        }
+    };
+    nodes[index].constraints += count;
+    insert(&mut nodes[index].children, rest, count);
+}
+
+fn sort(nodes: &mut [ProfileNode]) {
+    nodes.sort_by(|a, b| (Reverse(a.constraints), &a.frame).cmp(&(Reverse(b.constraints), &b.frame)));
+    for node in nodes {
+        sort(&mut node.children);
+    }
+}
+
+fn largest_first<K: Ord>(totals: BTreeMap<K, usize>) -> Vec<(K, usize)> {
+    let mut totals: Vec<(K, usize)> = totals.into_iter().collect();
+    totals.sort_by_key(|(_, count)| Reverse(*count));
+    totals
+}
@@ -0,0 +1,117 @@
+//! Progress reports and cancellation for long compilations.
+//!
+//! A `ProgressObserver` hears when each stage of the pipeline starts and finishes and,
+//! while optimizing, after every pass run. A `CancellationToken` is checked before each
+//! stage and each pass run; once it is cancelled, compilation stops at the next check
+//! with `FCMCError::Cancelled`. A running pass is not interrupted, so cancelling takes
+//! effect within one pass.
+
+use crate::FCMCError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A stage of the compilation pipeline, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    /// Parsing the source and setting property annotations aside
+    Parse,
+    Inline,
+    /// Building the IR and looking for constraints no input affects
+    BuildIr,
+    /// The pass pipeline, and checking the optimized IR against the original
+    Optimize,
+    /// Laying out public values, sanity checks, rewriting what the target cannot express
+    /// and checking property annotations
+    Legalize,
+    /// Lowering the IR to the target's constraint system
+    Lower,
+    /// Checking the lowered circuit and its hints
+    Verify,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Parse => "parse",
+            Stage::Inline => "inline",
+            Stage::BuildIr => "build-ir",
+            Stage::Optimize => "optimize",
+            Stage::Legalize => "legalize",
+            Stage::Lower => "lower",
+            Stage::Verify => "verify",
+        }
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A pass run that just finished
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassProgress {
+    pub pass: String,
+    /// Iteration of the pipeline, from 1
+    pub iteration: usize,
+    /// Pass runs finished so far, this one included
+    pub runs: usize,
+    /// Share of the optimization stage done, from 0 to 100, against the most pass runs
+    /// the pipeline can make; it jumps ahead when the pipeline converges early
+    pub percent: f64,
+    /// IR nodes after the pass
+    pub nodes: usize,
+    pub changed: bool,
+}
+
+/// Receives progress from a compilation, possibly from another thread than the one
+/// that started it
+pub trait ProgressObserver: Send + Sync {
+    fn stage_started(&self, _stage: Stage) {}
+
+    fn stage_finished(&self, _stage: Stage, _elapsed: Duration) {}
+
+    fn pass_finished(&self, _progress: &PassProgress) {}
+}
+
+/// The observer behind a compiler option, so the structs holding it stay `Debug`
+#[derive(Clone)]
+pub(crate) struct SharedObserver(pub(crate) Arc<dyn ProgressObserver>);
+
+impl std::fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressObserver")
+    }
+}
+
+/// A flag shared between a compilation and whoever may abort it; clones share the flag
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every compilation holding the token at its next check
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(FCMCError::Cancelled)` once cancelled
+    pub fn check(&self) -> Result<(), FCMCError> {
+        if self.is_cancelled() {
+            Err(FCMCError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Projects: an `fcmc.toml` manifest declaring the circuits to build and how; see the
+//! README for its format.
+
+use crate::backend::export::circom;
+use crate::backend::CircuitBackend;
+use crate::optimization::field::FieldConfig;
+use crate::package::{self, Package, PACKAGE_EXTENSION};
+use crate::{CompiledCircuit, FCMCError, TargetSystem, FCMC};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// File name of a project manifest
+pub const MANIFEST_NAME: &str = "fcmc.toml";
+
+/// Directory, relative to the manifest, that packages fetched from URLs are kept in
+pub const PACKAGE_CACHE: &str = ".fcmc/packages";
+
+/// Features a manifest can switch on
+pub const FEATURES: &[&str] = &["sanity-checks", "reproducible", "exhaustive-equivalence", "explain"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub project: ProjectSettings,
+    #[serde(default, rename = "circuit")]
+    pub circuits: Vec<CircuitEntry>,
+    /// By package name
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageDependency>,
+}
+
+/// The `[project]` table: the project's name and the settings circuits inherit
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSettings {
+    pub name: String,
+    #[serde(default = "default_output")]
+    pub output: PathBuf,
+    #[serde(default = "default_targets")]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default = "default_opt_level")]
+    pub opt_level: u8,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// A `[[circuit]]` table; unset settings come from the project
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitEntry {
+    pub name: String,
+    pub entry: PathBuf,
+    #[serde(default)]
+    pub targets: Option<Vec<String>>,
+    #[serde(default)]
+    pub field: Option<String>,
+    #[serde(default)]
+    pub opt_level: Option<u8>,
+    #[serde(default)]
+    pub features: Option<Vec<String>>,
+}
+
+/// A `[packages]` entry: where the package comes from, by `path` or `url`, and what it
+/// must be
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackageDependency {
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Digest the package must have, in hex; required with `url`
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+fn default_output() -> PathBuf {
+    PathBuf::from("build")
+}
+
+fn default_targets() -> Vec<String> {
+    vec!["r1cs".to_string()]
+}
+
+fn default_opt_level() -> u8 {
+    2
+}
+
+/// A circuit built for one target, and where it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub circuit: String,
+    pub target: String,
+    pub constraints: usize,
+    pub files: Vec<PathBuf>,
+}
+
+/// A manifest and the directory its paths are relative to
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Read the manifest at `path`, or `fcmc.toml` in the directory `path`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FCMCError> {
+        let path = path.as_ref();
+        let path = if path.is_dir() {
+            path.join(MANIFEST_NAME)
+        } else {
+            path.to_path_buf()
+        };
+        let text = read(&path)?;
+        let manifest: Manifest = toml::from_str(&text)
+            .map_err(|e| FCMCError::ParseError(format!("Invalid manifest {}: {}", path.display(), e)))?;
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let project = Self { root, manifest };
+        project.check()?;
+        Ok(project)
+    }
+
+    /// Fail on duplicate circuit names and unknown features before compiling anything
+    fn check(&self) -> Result<(), FCMCError> {
+        if self.manifest.circuits.is_empty() {
+            return Err(FCMCError::SemanticError(format!(
+                "Project {} declares no [[circuit]]",
+                self.manifest.project.name
+            )));
+        }
+        for (index, circuit) in self.manifest.circuits.iter().enumerate() {
+            if self.manifest.circuits[..index]
+                .iter()
+                .any(|other| other.name == circuit.name)
+            {
+                return Err(FCMCError::SemanticError(format!(
+                    "Circuit '{}' is declared twice",
+                    circuit.name
+                )));
+            }
+            for feature in self.features(circuit) {
+                if !FEATURES.contains(&feature.as_str()) {
+                    return Err(FCMCError::SemanticError(format!(
+                        "Unknown feature '{}' for circuit '{}'; features are {}",
+                        feature,
+                        circuit.name,
+                        FEATURES.join(", ")
+                    )));
+                }
+            }
+        }
+        for (name, dependency) in &self.manifest.packages {
+            package::check_name(name)?;
+            match (&dependency.path, &dependency.url) {
+                (Some(_), None) => {}
+                (None, Some(_)) if dependency.sha256.is_none() => {
+                    return Err(FCMCError::SemanticError(format!(
+                        "Package '{}' comes from a URL and must pin its sha256",
+                        name
+                    )));
+                }
+                (None, Some(_)) => {}
+                _ => {
+                    return Err(FCMCError::SemanticError(format!(
+                        "Package '{}' needs exactly one of path and url",
+                        name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Directory `build` writes to
+    pub fn output_dir(&self) -> PathBuf {
+        self.root.join(&self.manifest.project.output)
+    }
+
+    pub fn targets<'a>(&'a self, circuit: &'a CircuitEntry) -> &'a [String] {
+        circuit.targets.as_deref().unwrap_or(&self.manifest.project.targets)
+    }
+
+    pub fn features<'a>(&'a self, circuit: &'a CircuitEntry) -> &'a [String] {
+        circuit.features.as_deref().unwrap_or(&self.manifest.project.features)
+    }
+
+    /// The compiler configured for `circuit` on `target`
+    pub fn compiler(&self, circuit: &CircuitEntry, target: &str) -> Result<FCMC, FCMCError> {
+        let settings = &self.manifest.project;
+        let mut compiler = FCMC::new()
+            .with_target(target.parse::<TargetSystem>()?)
+            .with_optimization_level(circuit.opt_level.unwrap_or(settings.opt_level));
+        if let Some(field) = circuit.field.as_ref().or(settings.field.as_ref()) {
+            compiler = compiler.with_field(field.parse::<FieldConfig>()?);
+        }
+        for feature in self.features(circuit) {
+            compiler = match feature.as_str() {
+                "sanity-checks" => compiler.with_sanity_checks(true),
+                "reproducible" => compiler.with_reproducible(true),
+                "exhaustive-equivalence" => compiler.with_exhaustive_equivalence(true),
+                _ => compiler.with_explain(true),
+            };
+        }
+        for package in self.packages()? {
+            compiler = compiler.with_package(package);
+        }
+        Ok(compiler)
+    }
+
+    /// Every package of `[packages]`, fetched if need be and checked against its entry
+    pub fn packages(&self) -> Result<Vec<Arc<Package>>, FCMCError> {
+        self.manifest
+            .packages
+            .iter()
+            .map(|(name, dependency)| self.resolve(name, dependency).map(Arc::new))
+            .collect()
+    }
+
+    fn resolve(&self, name: &str, dependency: &PackageDependency) -> Result<Package, FCMCError> {
+        let package = match (&dependency.path, &dependency.url) {
+            (Some(path), _) => Package::load(self.root.join(path))?,
+            (None, Some(url)) => {
+                let digest = dependency.sha256.as_deref().unwrap_or_default().to_ascii_lowercase();
+                let cache = self.root.join(PACKAGE_CACHE);
+                let path = cache.join(format!("{}-{}.{}", name, digest, PACKAGE_EXTENSION));
+                if !path.exists() {
+                    std::fs::create_dir_all(&cache)
+                        .map_err(|e| FCMCError::BackendError(format!("Failed to create {}: {}", cache.display(), e)))?;
+                    // Fetched beside the cached name and renamed once complete, so an
+                    // interrupted download is never taken for the package
+                    let partial = path.with_extension("part");
+                    package::fetch(url, &partial)?;
+                    std::fs::rename(&partial, &path)
+                        .map_err(|e| FCMCError::BackendError(format!("Failed to write {}: {}", path.display(), e)))?;
+                }
+                Package::load(&path)?
+            }
+            (None, None) => unreachable!("checked when the manifest was loaded"),
+        };
+        if package.name != name {
+            return Err(FCMCError::SemanticError(format!(
+                "Package '{}' is named '{}' inside",
+                name, package.name
+            )));
+        }
+        if let Some(expected) = &dependency.sha256 {
+            let digest = package.digest();
+            if !digest.eq_ignore_ascii_case(expected) {
+                return Err(FCMCError::VerificationError(format!(
+                    "Package '{}' has sha256 {}, not the {} the manifest pins",
+                    name, digest, expected
+                )));
+            }
+        }
+        if let Some(requested) = &dependency.version {
+            if !package::version_matches(requested, &package.version) {
+                return Err(FCMCError::SemanticError(format!(
+                    "Package '{}' is version {}, which does not match {}",
+                    name, package.version, requested
+                )));
+            }
+        }
+        Ok(package)
+    }
+
+    /// `circuit`'s entry point followed by every dependency's files
+    pub fn files(&self, circuit: &CircuitEntry) -> Result<Vec<PathBuf>, FCMCError> {
+        let mut files = vec![self.root.join(&circuit.entry)];
+        for dependency in &self.manifest.project.dependencies {
+            files.extend(source_files(&self.root.join(dependency))?);
+        }
+        Ok(files)
+    }
+
+    /// Source of `circuit`'s entry point followed by every dependency
+    pub fn source(&self, circuit: &CircuitEntry) -> Result<String, FCMCError> {
+        let mut source = String::new();
+        for (index, path) in self.files(circuit)?.iter().enumerate() {
+            if index > 0 {
+                source.push('\n');
+            }
+            source.push_str(&read(path)?);
+        }
+        Ok(source)
+    }
+
+    /// Compile every circuit for each of its targets and write the artifacts
+    pub fn build(&self) -> Result<Vec<Artifact>, FCMCError> {
+        let output = self.output_dir();
+        std::fs::create_dir_all(&output)
+            .map_err(|e| FCMCError::BackendError(format!("Failed to create {}: {}", output.display(), e)))?;
+        let mut artifacts = Vec::new();
+        for circuit in &self.manifest.circuits {
+            let source = self.source(circuit)?;
+            for target in self.targets(circuit) {
+                let compiled = self.compiler(circuit, target)?.compile(&source)?;
+                let stem = format!("{}.{}", circuit.name, target.to_ascii_lowercase());
+                artifacts.push(Artifact {
+                    circuit: circuit.name.clone(),
+                    target: target.clone(),
+                    constraints: compiled.circuit.constraint_count(),
+                    files: write_artifacts(&compiled, &output, &stem)?,
+                });
+            }
+        }
+        Ok(artifacts)
+    }
+}
+
+/// Write `circuit` to `<dir>/<stem>.bin`, and to circom's `<dir>/<stem>.r1cs` for R1CS,
+/// returning the paths written
+pub fn write_artifacts(circuit: &CompiledCircuit, dir: &Path, stem: &str) -> Result<Vec<PathBuf>, FCMCError> {
+    let mut written = vec![dir.join(format!("{}.bin", stem))];
+    circuit.save(&written[0])?;
+    if let Some(r1cs) = circuit.circuit.as_r1cs() {
+        let path = dir.join(format!("{}.r1cs", stem));
+        circuit.in_field(|| circom::write_r1cs(r1cs, &path))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// `path` itself, or the `.zk` files in the directory `path` in name order
+fn source_files(path: &Path) -> Result<Vec<PathBuf>, FCMCError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = std::fs::read_dir(path)
+        .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))?;
+        if entry.path().extension().is_some_and(|extension| extension == "zk") {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read(path: &Path) -> Result<String, FCMCError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))
+}
@@ -0,0 +1,298 @@
+//! An interactive session, for `fcmc repl`: type an expression to see its value on sample
+//! inputs and what it costs in constraints, or a function to define it for later lines.
+//!
+//! Each expression is compiled as the body of a `main` taking every sample input, after
+//! the functions defined so far, and evaluated by the IR interpreter on the circuit it
+//! compiled to, so the value is the circuit's. A function is priced by compiling a `main`
+//! that only calls it. Lines starting with `:` are commands; `:help` lists them.
+//!
+//! The session does no I/O, so it can be driven by any front end: `eval` takes a line and
+//! returns what to print.
+
+use crate::backend::CircuitBackend;
+use crate::optimization::interpreter::evaluate;
+use crate::{CompiledCircuit, FCMCError, InputMap, TargetSystem, FCMC};
+use num_bigint::BigUint;
+
+const HELP: &[&str] = &[
+    "<expression>              compile and evaluate on the sample inputs",
+    "fn name(...) -> type { }  define a function, over several lines if needed",
+    ":input name: type = value declare a sample input, a field element if untyped",
+    ":inputs                   list the sample inputs",
+    ":target name              compile for another target",
+    ":opt level                compile at another optimization level",
+    ":reset                    forget every function and input",
+    ":quit                     end the session",
+];
+
+/// What to do after a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// Print these lines and read the next
+    Lines(Vec<String>),
+    /// The line left a definition open; read more of it
+    Incomplete,
+    Quit,
+}
+
+/// A sample input of every expression's `main`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SampleInput {
+    name: String,
+    /// As written after the colon, for the generated parameter list
+    type_name: String,
+    value: BigUint,
+}
+
+/// The functions and sample inputs typed so far and the compiler settings
+pub struct Session {
+    compiler: FCMC,
+    /// Source of each function by name, in the order they were first defined
+    functions: Vec<(String, String)>,
+    inputs: Vec<SampleInput>,
+    /// Lines of a definition whose braces are not yet balanced
+    pending: String,
+}
+
+impl Session {
+    /// A session compiling with `compiler`'s options, until `:target` or `:opt` changes them
+    pub fn new(compiler: FCMC) -> Self {
+        Self {
+            compiler,
+            functions: Vec::new(),
+            inputs: Vec::new(),
+            pending: String::new(),
+        }
+    }
+
+    /// Whether the next line continues a definition, for the prompt
+    pub fn is_continuing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Handle one line; a failure is reported in the reply and leaves the session as it was
+    pub fn eval(&mut self, line: &str) -> Reply {
+        if self.is_continuing() || line.trim_start().starts_with("fn ") {
+            self.pending.push_str(line);
+            self.pending.push('\n');
+            let depth = self.pending.matches('{').count() as i64 - self.pending.matches('}').count() as i64;
+            if depth > 0 || !self.pending.contains('{') {
+                return Reply::Incomplete;
+            }
+            let source = std::mem::take(&mut self.pending);
+            return Reply::Lines(lines_or_error(self.define(&source)));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Reply::Lines(Vec::new());
+        }
+        if let Some(command) = line.strip_prefix(':') {
+            let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+            return match name {
+                "quit" | "q" => Reply::Quit,
+                _ => Reply::Lines(lines_or_error(self.command(name, argument.trim()))),
+            };
+        }
+        Reply::Lines(lines_or_error(self.compute(line.trim_end_matches(';'))))
+    }
+
+    fn command(&mut self, name: &str, argument: &str) -> Result<Vec<String>, FCMCError> {
+        match name {
+            "help" | "h" => Ok(HELP.iter().map(ToString::to_string).collect()),
+            "input" => self.declare_input(argument),
+            "inputs" => Ok(self
+                .inputs
+                .iter()
+                .map(|input| format!("{}: {} = {}", input.name, input.type_name, input.value))
+                .collect()),
+            "target" => {
+                self.compiler = self.compiler.clone().with_target(argument.parse::<TargetSystem>()?);
+                Ok(vec![format!("compiling for {}", argument)])
+            }
+            "opt" => {
+                let level: u8 = argument
+                    .parse()
+                    .map_err(|_| FCMCError::ParseError(format!("'{}' is not an optimization level", argument)))?;
+                self.compiler = self.compiler.clone().with_optimization_level(level);
+                Ok(vec![format!("compiling at -O{}", level)])
+            }
+            "reset" => {
+                self.functions.clear();
+                self.inputs.clear();
+                Ok(vec!["forgot every function and input".to_string()])
+            }
+            _ => Err(FCMCError::ParseError(format!(
+                "unknown command ':{}'; :help lists the commands",
+                name
+            ))),
+        }
+    }
+
+    /// `name: type = value`, or `name = value` for a field element
+    fn declare_input(&mut self, declaration: &str) -> Result<Vec<String>, FCMCError> {
+        let usage = || FCMCError::ParseError("expected ':input name: type = value'".to_string());
+        let (binding, value) = declaration.split_once('=').ok_or_else(usage)?;
+        let (name, type_name) = binding.split_once(':').unwrap_or((binding, "field"));
+        let (name, type_name, value) = (name.trim(), type_name.trim(), value.trim());
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') || type_name.is_empty() {
+            return Err(usage());
+        }
+        let value = match value {
+            "true" => BigUint::from(1u32),
+            "false" => BigUint::from(0u32),
+            _ => value
+                .parse::<BigUint>()
+                .map_err(|_| FCMCError::ParseError(format!("'{}' is not a non-negative integer", value)))?,
+        };
+        let input = SampleInput {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            value,
+        };
+        let reply = format!("{}: {} = {}", input.name, input.type_name, input.value);
+        match self.inputs.iter_mut().find(|existing| existing.name == input.name) {
+            Some(existing) => *existing = input,
+            None => self.inputs.push(input),
+        }
+        Ok(vec![reply])
+    }
+
+    /// Compile `expression` over the sample inputs and evaluate it
+    fn compute(&self, expression: &str) -> Result<Vec<String>, FCMCError> {
+        let parameters: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}: {}", input.name, input.type_name))
+            .collect();
+        let source = format!(
+            "{}fn main({}) -> field {{\n    return {};\n}}\n",
+            self.definitions(None),
+            parameters.join(", "),
+            expression
+        );
+        let compiled = self.compiler.compile(&source)?;
+        let inputs: InputMap = self
+            .inputs
+            .iter()
+            .map(|input| (input.name.clone(), input.value.clone()))
+            .collect();
+        let evaluation = compiled.in_field(|| evaluate(&compiled.ir, &inputs))?;
+        let mut lines: Vec<String> = evaluation
+            .outputs
+            .iter()
+            .map(|(_, value)| format!("= {}", value))
+            .collect();
+        if !evaluation.is_satisfied() {
+            lines.push(format!(
+                "{} constraint(s) fail on the sample inputs",
+                evaluation.violated.len()
+            ));
+        }
+        lines.push(cost(&compiled));
+        Ok(lines)
+    }
+
+    /// Check `source`, one function, compiles, price a call to it, and keep it
+    fn define(&mut self, source: &str) -> Result<Vec<String>, FCMCError> {
+        let header = &source[..source.find('{').unwrap_or(source.len())];
+        let name = header
+            .trim_start()
+            .strip_prefix("fn")
+            .and_then(|rest| rest.split('(').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| FCMCError::ParseError("expected 'fn name(...)'".to_string()))?
+            .to_string();
+        if name == "main" {
+            return Err(FCMCError::ParseError(
+                "main is generated for each expression; give the function another name".to_string(),
+            ));
+        }
+        let (parameters, return_type) = signature(header)?;
+        let arguments: Vec<&str> = parameters.iter().map(|(name, _)| name.as_str()).collect();
+        let parameters: Vec<String> = parameters
+            .iter()
+            .map(|(name, type_name)| format!("{}: {}", name, type_name))
+            .collect();
+        // A function returning nothing is checked through a call whose value is dropped
+        let body = match &return_type {
+            Some(_) => format!("    return {}({});", name, arguments.join(", ")),
+            None => format!("    {}({});", name, arguments.join(", ")),
+        };
+        let caller = format!(
+            "{}{}fn main({}){} {{\n{}\n}}\n",
+            self.definitions(Some(&name)),
+            source,
+            parameters.join(", "),
+            return_type
+                .map(|return_type| format!(" -> {}", return_type))
+                .unwrap_or_default(),
+            body
+        );
+        let compiled = self.compiler.compile(&caller)?;
+
+        let reply = format!("fn {}: {} per call", name, cost(&compiled));
+        match self.functions.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = source.to_string(),
+            None => self.functions.push((name, source.to_string())),
+        }
+        Ok(vec![reply])
+    }
+
+    /// The functions defined so far, but for `except`, which is being redefined
+    fn definitions(&self, except: Option<&str>) -> String {
+        self.functions
+            .iter()
+            .filter(|(name, _)| Some(name.as_str()) != except)
+            .map(|(_, source)| source.as_str())
+            .collect()
+    }
+}
+
+fn cost(compiled: &CompiledCircuit) -> String {
+    format!(
+        "{} constraints ({:?})",
+        compiled.circuit.constraint_count(),
+        compiled.circuit.target()
+    )
+}
+
+/// Parameters, by name and type as written, and the return type of a function header
+pub(crate) fn signature(header: &str) -> Result<(Vec<(String, String)>, Option<String>), FCMCError> {
+    let malformed = || FCMCError::ParseError("expected 'fn name(parameters) -> type {'".to_string());
+    let open = header.find('(').ok_or_else(malformed)?;
+    let close = header.rfind(')').filter(|&close| close > open).ok_or_else(malformed)?;
+    let mut parameters = Vec::new();
+    for parameter in split_top_level(&header[open + 1..close]) {
+        let (name, type_name) = parameter.split_once(':').ok_or_else(malformed)?;
+        parameters.push((name.trim().to_string(), type_name.trim().to_string()));
+    }
+    let return_type = header[close + 1..]
+        .trim()
+        .strip_prefix("->")
+        .map(|return_type| return_type.trim().to_string());
+    Ok((parameters, return_type))
+}
+
+/// `list` split at the commas outside `<>` and `[]`, as in `Fixed<8, 8>`, without empty parts
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0i32, 0);
+    for (position, c) in list.char_indices() {
+        match c {
+            '<' | '[' => depth += 1,
+            '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&list[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&list[start..]);
+    parts.into_iter().filter(|part| !part.trim().is_empty()).collect()
+}
+
+fn lines_or_error(result: Result<Vec<String>, FCMCError>) -> Vec<String> {
+    result.unwrap_or_else(|error| vec![format!("error: {}", error)])
+}
@@ -0,0 +1,475 @@
+//! Where a compilation spent its time, stage by stage and pass by pass.
+//!
+//! Each stage runs in a `stage` tracing span and each optimization pass run in a `pass`
+//! span inside it, carrying the node counts and timings as fields, so a tracing
+//! subscriber can export them as they happen. The same measurements are kept in a
+//! `CompilationReport` on the compiled circuit, for analyzing performance without a
+//! subscriber or parsing log text.
+//!
+//! `TargetMetrics` sizes the lowered circuit in the terms of its proof system, the
+//! figures that decide proving cost there.
+//!
+//! A `CircuitSummary` gathers what a reviewer of a circuit change wants to see, its size,
+//! what optimization saved, the costliest functions, the safety constraints the compiler
+//! added, its warnings and the audited gadgets it embeds, and renders it as text, JSON
+//! or Markdown. Other formats can be built from its public fields.
+
+use crate::audit::AuditBoundary;
+use crate::backend::{CircuitBackend, TargetCircuit};
+use crate::diagnostics::Diagnostic;
+use crate::optimization::PassStats;
+use crate::progress::Stage;
+use crate::{CompiledCircuit, FCMCError};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Functions a `CircuitSummary` lists, costliest first
+pub const TOP_FUNCTIONS: usize = 10;
+
+/// One stage of a compilation
+#[derive(Debug, Clone, PartialEq)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub elapsed: Duration,
+    /// IR nodes after the stage, for the stages that work on the IR
+    pub nodes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompilationReport {
+    /// In the order they ran
+    pub stages: Vec<StageReport>,
+    /// Every optimization pass run, in order
+    pub passes: Vec<PassStats>,
+    pub constraints: usize,
+}
+
+impl CompilationReport {
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|stage| stage.elapsed).sum()
+    }
+
+    pub fn stage(&self, stage: Stage) -> Option<&StageReport> {
+        self.stages.iter().find(|report| report.stage == stage)
+    }
+
+    /// Time spent in each pass over all of its runs, longest first
+    pub fn pass_totals(&self) -> Vec<(String, Duration)> {
+        let mut totals: Vec<(String, Duration)> = Vec::new();
+        for pass in &self.passes {
+            let elapsed = Duration::from_micros(pass.duration_micros);
+            match totals.iter_mut().find(|(name, _)| *name == pass.name) {
+                Some((_, total)) => *total += elapsed,
+                None => totals.push((pass.name.clone(), elapsed)),
+            }
+        }
+        totals.sort_by(|a, b| b.1.cmp(&a.1));
+        totals
+    }
+
+    /// One line per stage with its share of the total, then the passes by time
+    pub fn summary(&self) -> Vec<String> {
+        let total = self.total().as_secs_f64().max(f64::EPSILON);
+        let mut lines = vec![format!(
+            "{:.1} ms, {} constraints",
+            self.total().as_secs_f64() * 1000.0,
+            self.constraints
+        )];
+        for stage in &self.stages {
+            let nodes = stage.nodes.map(|nodes| format!(", {} nodes", nodes)).unwrap_or_default();
+            lines.push(format!(
+                "  {}: {:.1} ms ({:.0}%){}",
+                stage.stage,
+                stage.elapsed.as_secs_f64() * 1000.0,
+                100.0 * stage.elapsed.as_secs_f64() / total,
+                nodes
+            ));
+        }
+        for (pass, elapsed) in self.pass_totals() {
+            lines.push(format!("    {}: {:.1} ms", pass, elapsed.as_secs_f64() * 1000.0));
+        }
+        lines
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "total_micros": self.total().as_micros() as u64,
+            "constraints": self.constraints,
+            "stages": self
+                .stages
+                .iter()
+                .map(|stage| json!({
+                    "stage": stage.stage.as_str(),
+                    "micros": stage.elapsed.as_micros() as u64,
+                    "nodes": stage.nodes,
+                }))
+                .collect::<Vec<_>>(),
+            "passes": self.passes,
+        })
+    }
+}
+
+impl Serialize for CompilationReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+/// Size of a lowered circuit by what its proof system counts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "target", rename_all = "snake_case")]
+pub enum TargetMetrics {
+    R1CS {
+        constraints: usize,
+        /// Nonzero coefficients of the A, B and C matrices
+        nonzeros: usize,
+        range_checks: usize,
+        black_boxes: usize,
+        custom_gates: usize,
+        table_lookups: usize,
+    },
+    /// `Plonk` and `Halo2` circuits
+    Plonk {
+        gates: usize,
+        public_rows: usize,
+        /// Rows reading a range or function table
+        lookup_rows: usize,
+        custom_gate_rows: usize,
+        copy_constraints: usize,
+    },
+    AIR {
+        rows: usize,
+        transition_constraints: usize,
+        boundary_constraints: usize,
+        copy_constraints: usize,
+    },
+    ACIR {
+        assert_zero: usize,
+        black_box_calls: usize,
+    },
+    Plonky2 {
+        builder_ops: usize,
+    },
+    CCS {
+        rows: usize,
+        matrices: usize,
+        nonzeros: usize,
+        public_inputs: usize,
+    },
+    /// A backend added with `register`, known only by its rows
+    Custom {
+        rows: usize,
+    },
+}
+
+impl TargetMetrics {
+    pub fn of(circuit: &TargetCircuit) -> Self {
+        match circuit {
+            TargetCircuit::R1CS(r1cs) => TargetMetrics::R1CS {
+                constraints: r1cs.constraints.len(),
+                nonzeros: r1cs
+                    .constraints
+                    .iter()
+                    .map(|constraint| constraint.a.len() + constraint.b.len() + constraint.c.len())
+                    .sum(),
+                range_checks: r1cs.ranges.len(),
+                black_boxes: r1cs.black_boxes.len(),
+                custom_gates: r1cs.custom_gates.len(),
+                table_lookups: r1cs.table_lookups.len(),
+            },
+            TargetCircuit::Plonk(plonk) => TargetMetrics::Plonk {
+                gates: plonk.gates.len(),
+                public_rows: plonk.public_rows().len(),
+                lookup_rows: plonk
+                    .gates
+                    .iter()
+                    .filter(|gate| gate.lookup.is_some() || gate.table.is_some())
+                    .count(),
+                custom_gate_rows: plonk.gates.iter().filter(|gate| gate.custom.is_some()).count(),
+                copy_constraints: plonk.copy_constraints().len(),
+            },
+            TargetCircuit::AIR(air) => TargetMetrics::AIR {
+                rows: air.rows.len(),
+                transition_constraints: air.transition_constraints.len(),
+                boundary_constraints: air.boundary_constraints.len(),
+                copy_constraints: air.copy_constraints.len(),
+            },
+            TargetCircuit::ACIR(acir) => {
+                let assert_zero = acir
+                    .opcodes
+                    .iter()
+                    .filter(|opcode| matches!(opcode, crate::backend::acir::Opcode::AssertZero(_)))
+                    .count();
+                TargetMetrics::ACIR {
+                    assert_zero,
+                    black_box_calls: acir.opcodes.len() - assert_zero,
+                }
+            }
+            TargetCircuit::Plonky2(plonky2) => TargetMetrics::Plonky2 {
+                builder_ops: plonky2.ops.len(),
+            },
+            TargetCircuit::CCS(ccs) => TargetMetrics::CCS {
+                rows: ccs.matrices.first().map_or(0, |matrix| matrix.rows),
+                matrices: ccs.matrices.len(),
+                nonzeros: ccs.matrices.iter().map(|matrix| matrix.entries.len()).sum(),
+                public_inputs: ccs.public_count,
+            },
+            TargetCircuit::Custom(circuit) => TargetMetrics::Custom {
+                rows: circuit.constraint_count(),
+            },
+        }
+    }
+
+    /// The counts as `name: value` pairs, by name
+    pub fn summary(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields
+                .iter()
+                .filter(|(name, _)| name.as_str() != "target")
+                .map(|(name, value)| format!("{}: {}", name.replace('_', " "), value))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::new(),
+        }
+    }
+}
+
+/// How `CompiledCircuit::report` lays out its summary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// Aligned lines for a terminal or a log
+    #[default]
+    Text,
+    /// One object, for dashboards and scripts
+    Json,
+    /// Tables and lists to paste into a pull request
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = FCMCError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(FCMCError::ParseError(format!("unknown report format '{}'", name))),
+        }
+    }
+}
+
+/// What a compiled circuit costs and what compiling it found
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitSummary {
+    pub target: String,
+    pub field: String,
+    pub constraints: usize,
+    pub witness_size: usize,
+    pub public_inputs: usize,
+    pub public_outputs: usize,
+    pub metrics: TargetMetrics,
+    pub original_nodes: usize,
+    pub optimized_nodes: usize,
+    /// `CompilationStats::optimization_ratio`
+    pub savings: f64,
+    /// Constraints removed as duplicates after legalization
+    pub duplicate_constraints: usize,
+    /// The `TOP_FUNCTIONS` functions with the most rows, costliest first
+    pub functions: Vec<(String, usize)>,
+    /// One line per safety constraint `with_sanity_checks` added
+    pub safety_constraints: Vec<String>,
+    pub warnings: Vec<Diagnostic>,
+    /// The functions marked `#[audit_boundary]`
+    pub audited: Vec<AuditBoundary>,
+    /// `None` for circuits read by `load`, which keep no timings
+    pub compile_time: Option<Duration>,
+}
+
+impl CircuitSummary {
+    pub fn of(circuit: &CompiledCircuit) -> Self {
+        let stats = &circuit.stats;
+        let mut functions = circuit.profile().functions();
+        functions.truncate(TOP_FUNCTIONS);
+        Self {
+            target: format!("{:?}", circuit.circuit.target()),
+            field: circuit.field.to_string(),
+            constraints: stats.constraint_count,
+            witness_size: stats.witness_size,
+            public_inputs: stats.public_inputs,
+            public_outputs: stats.public_outputs,
+            metrics: stats.target.clone(),
+            original_nodes: stats.original_nodes,
+            optimized_nodes: stats.optimized_nodes,
+            savings: stats.optimization_ratio(),
+            duplicate_constraints: stats.duplicate_constraints,
+            functions,
+            safety_constraints: stats.sanity.summary(),
+            warnings: circuit.warnings(),
+            audited: stats.audit.clone(),
+            compile_time: (!stats.report.stages.is_empty()).then(|| stats.report.total()),
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Json => serde_json::to_string_pretty(&self.to_json()).unwrap_or_default(),
+            ReportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("{} circuit over {}", self.target, self.field),
+            format!("  constraints:    {}", self.constraints),
+            format!("  witness size:   {}", self.witness_size),
+            format!(
+                "  public values:  {} inputs, {} outputs",
+                self.public_inputs, self.public_outputs
+            ),
+            format!("  {}", self.metrics.summary()),
+            format!(
+                "  IR nodes:       {} -> {} ({:.1}% saved)",
+                self.original_nodes,
+                self.optimized_nodes,
+                self.savings
+            ),
+            format!("  duplicates:     {} removed", self.duplicate_constraints),
+        ];
+        if let Some(elapsed) = self.compile_time {
+            lines.push(format!("  compile time:   {:.1} ms", elapsed.as_secs_f64() * 1000.0));
+        }
+        if !self.functions.is_empty() {
+            lines.push("Costliest functions:".to_string());
+            let width = self.functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            for (name, rows) in &self.functions {
+                lines.push(format!(
+                    "  {:<width$}  {:>8}  {:>5.1}%",
+                    name,
+                    rows,
+                    self.share(*rows),
+                    width = width
+                ));
+            }
+        }
+        if !self.safety_constraints.is_empty() {
+            lines.push(format!("Safety constraints added: {}", self.safety_constraints.len()));
+            lines.extend(self.safety_constraints.iter().map(|line| format!("  {}", line)));
+        }
+        if !self.audited.is_empty() {
+            lines.push("Audited gadgets:".to_string());
+            lines.extend(self.audited.iter().map(|boundary| {
+                format!(
+                    "  {} (fn {}): {} rows, sha256 {}",
+                    boundary.name,
+                    boundary.function,
+                    boundary.row_count(),
+                    boundary.content_hash
+                )
+            }));
+        }
+        lines.push(format!("Warnings: {}", self.warnings.len()));
+        lines.extend(
+            self.warnings
+                .iter()
+                .map(|warning| format!("  {}", warning.render_human())),
+        );
+        lines.join("\n")
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "target": self.target,
+            "field": self.field,
+            "constraints": self.constraints,
+            "witness_size": self.witness_size,
+            "public_inputs": self.public_inputs,
+            "public_outputs": self.public_outputs,
+            "metrics": self.metrics,
+            "original_nodes": self.original_nodes,
+            "optimized_nodes": self.optimized_nodes,
+            "savings_percent": self.savings,
+            "duplicate_constraints": self.duplicate_constraints,
+            "functions": self
+                .functions
+                .iter()
+                .map(|(name, rows)| json!({ "name": name, "constraints": rows }))
+                .collect::<Vec<_>>(),
+            "safety_constraints": self.safety_constraints,
+            "warnings": self.warnings.iter().map(Diagnostic::to_json).collect::<Vec<_>>(),
+            "audited": self.audited,
+            "compile_micros": self.compile_time.map(|elapsed| elapsed.as_micros() as u64),
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("### {} circuit over {}\n\n", self.target, self.field);
+        out.push_str("| | |\n|---|---:|\n");
+        out.push_str(&format!("| Constraints | {} |\n", self.constraints));
+        out.push_str(&format!("| Witness size | {} |\n", self.witness_size));
+        out.push_str(&format!("| Public inputs | {} |\n", self.public_inputs));
+        out.push_str(&format!("| Public outputs | {} |\n", self.public_outputs));
+        out.push_str(&format!(
+            "| IR nodes | {} → {} ({:.1}% saved) |\n",
+            self.original_nodes,
+            self.optimized_nodes,
+            self.savings
+        ));
+        out.push_str(&format!(
+            "| Duplicate constraints removed | {} |\n",
+            self.duplicate_constraints
+        ));
+        if let Some(elapsed) = self.compile_time {
+            out.push_str(&format!(
+                "| Compile time | {:.1} ms |\n",
+                elapsed.as_secs_f64() * 1000.0
+            ));
+        }
+        out.push_str(&format!("\n{}\n", self.metrics.summary()));
+        if !self.functions.is_empty() {
+            out.push_str("\n#### Costliest functions\n\n| Function | Constraints | Share |\n|---|---:|---:|\n");
+            for (name, rows) in &self.functions {
+                out.push_str(&format!("| `{}` | {} | {:.1}% |\n", name, rows, self.share(*rows)));
+            }
+        }
+        if !self.safety_constraints.is_empty() {
+            out.push_str(&format!(
+                "\n#### Safety constraints added ({})\n\n",
+                self.safety_constraints.len()
+            ));
+            for line in &self.safety_constraints {
+                out.push_str(&format!("- {}\n", line));
+            }
+        }
+        if !self.audited.is_empty() {
+            out.push_str("\n#### Audited gadgets\n\n| Gadget | Function | Rows | SHA-256 |\n|---|---|---:|---|\n");
+            for boundary in &self.audited {
+                out.push_str(&format!(
+                    "| {} | `{}` | {} | `{}` |\n",
+                    boundary.name,
+                    boundary.function,
+                    boundary.row_count(),
+                    boundary.content_hash
+                ));
+            }
+        }
+        if self.warnings.is_empty() {
+            out.push_str("\nNo warnings.\n");
+        } else {
+            out.push_str(&format!("\n#### Warnings ({})\n\n", self.warnings.len()));
+            for warning in &self.warnings {
+                let line = warning.line.map(|line| format!(" (line {})", line)).unwrap_or_default();
+                out.push_str(&format!("- `{}`{}: {}\n", warning.code, line, warning.message));
+            }
+        }
+        out
+    }
+
+    /// `rows` as a share of the circuit, in percent
+    fn share(&self, rows: usize) -> f64 {
+        100.0 * rows as f64 / self.constraints.max(1) as f64
+    }
+}
@@ -0,0 +1,450 @@
+//! Reference semantics for the source language, executed directly on the AST as an oracle
+//! for the frontend's lowering and the optimizer; the README lists the rules.
+
+use crate::language::ast::{BinaryOp, Expression, Function, Literal, Program, Statement, Type, UnaryOp};
+use crate::optimization::field;
+use crate::stdlib;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+use std::collections::HashMap;
+
+/// Deepest chain of nested calls before evaluation gives up
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// Most loop iterations, over the whole run, before evaluation gives up
+pub const MAX_LOOP_ITERATIONS: u64 = 1 << 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Unit,
+    Field(BigUint),
+    Bool(bool),
+    U32(u32),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// The value as field elements, arrays flattened in order
+    pub fn field_elements(&self) -> Vec<BigUint> {
+        match self {
+            Value::Unit => Vec::new(),
+            Value::Field(value) => vec![value.clone()],
+            Value::Bool(value) => vec![BigUint::from(*value as u8)],
+            Value::U32(value) => vec![BigUint::from(*value)],
+            Value::Array(elements) => elements.iter().flat_map(Value::field_elements).collect(),
+        }
+    }
+
+    fn scalar(&self) -> Option<BigUint> {
+        match self {
+            Value::Field(value) => Some(value.clone()),
+            Value::Bool(value) => Some(BigUint::from(*value as u8)),
+            Value::U32(value) => Some(BigUint::from(*value)),
+            Value::Unit | Value::Array(_) => None,
+        }
+    }
+}
+
+/// Run the entry point of `program` on `inputs` in the active field
+pub fn eval(program: &Program, inputs: &HashMap<String, BigUint>) -> Result<Value, FCMCError> {
+    let entry = program
+        .functions
+        .iter()
+        .find(|function| function.name == program.entry_point)
+        .ok_or_else(|| failure(format!("The program has no function '{}'", program.entry_point)))?;
+    let mut arguments = Vec::with_capacity(entry.params.len());
+    for (name, param_type) in &entry.params {
+        arguments.push(input(name, param_type, inputs)?);
+    }
+    let mut machine = Machine {
+        program,
+        depth: 0,
+        iterations: 0,
+    };
+    machine.call(entry, arguments)
+}
+
+fn input(name: &str, param_type: &Type, inputs: &HashMap<String, BigUint>) -> Result<Value, FCMCError> {
+    match param_type {
+        Type::Array(element, size) => (0..*size)
+            .map(|index| input(&format!("{}[{}]", name, index), element, inputs))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        _ => {
+            let value = inputs
+                .get(name)
+                .ok_or_else(|| failure(format!("Missing value for input '{}'", name)))?;
+            conform(
+                Value::Field(value % field::modulus()),
+                param_type,
+                &format!("input '{}'", name),
+            )
+        }
+    }
+}
+
+/// How a statement list finished
+enum Flow {
+    Next,
+    Return(Value),
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    depth: usize,
+    iterations: u64,
+}
+
+/// Variable bindings, innermost scope last
+struct Scopes(Vec<HashMap<String, Value>>);
+
+impl Scopes {
+    fn get(&self, name: &str) -> Result<Value, FCMCError> {
+        self.0
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+            .ok_or_else(|| failure(format!("Variable '{}' is not defined", name)))
+    }
+
+    fn assign(&mut self, name: &str, value: Value) -> Result<(), FCMCError> {
+        let slot = self
+            .0
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .ok_or_else(|| failure(format!("Assignment to undefined variable '{}'", name)))?;
+        // The binding keeps the type it was declared with
+        let value = match slot {
+            Value::Field(_) => Value::Field(scalar(&value, "assigned value")?),
+            Value::Bool(_) => conform(value, &Type::Bool, &format!("assignment to '{}'", name))?,
+            Value::U32(_) => conform(value, &Type::U32, &format!("assignment to '{}'", name))?,
+            Value::Unit | Value::Array(_) => value,
+        };
+        *slot = value;
+        Ok(())
+    }
+
+    fn bind(&mut self, name: &str, value: Value) {
+        if let Some(scope) = self.0.last_mut() {
+            scope.insert(name.to_string(), value);
+        }
+    }
+
+    fn block<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.0.push(HashMap::new());
+        let result = f(self);
+        self.0.pop();
+        result
+    }
+}
+
+impl<'a> Machine<'a> {
+    fn call(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value, FCMCError> {
+        if arguments.len() != function.params.len() {
+            return Err(failure(format!(
+                "'{}' takes {} argument(s) but got {}",
+                function.name,
+                function.params.len(),
+                arguments.len()
+            )));
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(failure(format!(
+                "Calls nest deeper than {} at '{}'",
+                MAX_CALL_DEPTH, function.name
+            )));
+        }
+        let mut scope = HashMap::new();
+        for ((name, param_type), argument) in function.params.iter().zip(arguments) {
+            let what = format!("argument '{}' of '{}'", name, function.name);
+            scope.insert(name.clone(), conform(argument, param_type, &what)?);
+        }
+
+        self.depth += 1;
+        let flow = self.statements(&function.body, &mut Scopes(vec![scope]));
+        self.depth -= 1;
+        let value = match flow? {
+            Flow::Return(value) => value,
+            Flow::Next => Value::Unit,
+        };
+        conform(
+            value,
+            &function.return_type,
+            &format!("return value of '{}'", function.name),
+        )
+    }
+
+    fn statements(&mut self, statements: &[Statement], scopes: &mut Scopes) -> Result<Flow, FCMCError> {
+        for statement in statements {
+            if let Flow::Return(value) = self.statement(statement, scopes)? {
+                return Ok(Flow::Return(value));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn statement(&mut self, statement: &Statement, scopes: &mut Scopes) -> Result<Flow, FCMCError> {
+        match statement {
+            Statement::Let { name, var_type, value } => {
+                let value = self.expression(value, scopes)?;
+                let value = match var_type {
+                    Some(var_type) => conform(value, var_type, &format!("'{}'", name))?,
+                    None => value,
+                };
+                scopes.bind(name, value);
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.expression(condition, scopes)?;
+                let branch = match conform(condition, &Type::Bool, "'if' condition")? {
+                    Value::Bool(true) => Some(then_branch),
+                    _ => else_branch.as_ref(),
+                };
+                if let Some(branch) = branch {
+                    return scopes.block(|scopes| self.statements(branch, scopes));
+                }
+            }
+            Statement::For {
+                var_name,
+                start,
+                end,
+                body,
+            } => {
+                let start = self.bound(start, scopes, "start")?;
+                let end = self.bound(end, scopes, "end")?;
+                for index in start..end.max(start) {
+                    self.iterations += 1;
+                    if self.iterations > MAX_LOOP_ITERATIONS {
+                        return Err(failure(format!(
+                            "Loops run more than {} iterations",
+                            MAX_LOOP_ITERATIONS
+                        )));
+                    }
+                    let flow = scopes.block(|scopes| {
+                        scopes.bind(var_name, Value::U32(index));
+                        self.statements(body, scopes)
+                    })?;
+                    if let Flow::Return(value) = flow {
+                        return Ok(Flow::Return(value));
+                    }
+                }
+            }
+            Statement::Return(value) => return Ok(Flow::Return(self.expression(value, scopes)?)),
+            Statement::Assert(condition) => {
+                if self.expression(condition, scopes)?.scalar() != Some(BigUint::one()) {
+                    return Err(failure("Assertion failed".to_string()));
+                }
+            }
+            Statement::Expression(expression) => {
+                self.expression(expression, scopes)?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// A loop bound, which must be a `u32`
+    fn bound(&mut self, expression: &Expression, scopes: &mut Scopes, which: &str) -> Result<u32, FCMCError> {
+        match conform(
+            self.expression(expression, scopes)?,
+            &Type::U32,
+            &format!("loop {}", which),
+        )? {
+            Value::U32(value) => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, scopes: &mut Scopes) -> Result<Value, FCMCError> {
+        Ok(match expression {
+            Expression::Literal(literal) => match literal {
+                Literal::Number(text) => Value::Field(
+                    field::parse_element(text).ok_or_else(|| failure(format!("Invalid number literal {}", text)))?,
+                ),
+            },
+            Expression::Variable(name) => scopes.get(name)?,
+            Expression::Assignment(target, value) => {
+                let name = match target.as_ref() {
+                    Expression::Variable(name) => name,
+                    _ => return Err(failure("Only variables can be assigned to".to_string())),
+                };
+                let value = self.expression(value, scopes)?;
+                scopes.assign(name, value)?;
+                scopes.get(name)?
+            }
+            Expression::Binary { left, operator, right } => {
+                let left = self.expression(left, scopes)?;
+                let right = self.expression(right, scopes)?;
+                binary(operator, left, right)?
+            }
+            Expression::Unary { operator, expr } => {
+                let operand = self.expression(expr, scopes)?;
+                match (operator, operand) {
+                    (UnaryOp::Neg, Value::U32(value)) => Value::U32(u32_result(BigUint::zero(), value, "Neg")?),
+                    (UnaryOp::Neg, operand) => Value::Field(field::neg(&scalar(&operand, "operand of '-'")?)),
+                    (UnaryOp::Not, operand) => match conform(operand, &Type::Bool, "operand of '!'")? {
+                        Value::Bool(value) => Value::Bool(!value),
+                        _ => unreachable!(),
+                    },
+                }
+            }
+            Expression::FunctionCall { name, args } => {
+                let mut arguments = Vec::with_capacity(args.len());
+                for arg in args {
+                    arguments.push(self.expression(arg, scopes)?);
+                }
+                self.call_named(name, arguments)?
+            }
+            Expression::Array(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.expression(element, scopes)?);
+                }
+                Value::Array(values)
+            }
+        })
+    }
+
+    fn call_named(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, FCMCError> {
+        let elements: Vec<BigUint> = arguments.iter().flat_map(Value::field_elements).collect();
+        if let Some(result) = stdlib::eval_call(name, &elements) {
+            return result.map_err(|e| failure(e.to_string()));
+        }
+        let program = self.program;
+        if let Some(function) = program.functions.iter().find(|function| function.name == name) {
+            return self.call(function, arguments);
+        }
+        let constraint = program
+            .constraints
+            .iter()
+            .find(|constraint| constraint.name == name)
+            .ok_or_else(|| failure(format!("No function or constraint named '{}'", name)))?;
+        if arguments.len() != constraint.params.len() {
+            return Err(failure(format!(
+                "Constraint '{}' takes {} argument(s) but got {}",
+                name,
+                constraint.params.len(),
+                arguments.len()
+            )));
+        }
+        let mut scope = HashMap::new();
+        for ((param, param_type), argument) in constraint.params.iter().zip(arguments) {
+            let what = format!("argument '{}' of constraint '{}'", param, name);
+            scope.insert(param.clone(), conform(argument, param_type, &what)?);
+        }
+        let holds = self.expression(&constraint.body, &mut Scopes(vec![scope]))?;
+        if holds.scalar() != Some(BigUint::one()) {
+            return Err(failure(format!("Constraint '{}' does not hold", name)));
+        }
+        Ok(Value::Unit)
+    }
+}
+
+fn binary(operator: &BinaryOp, left: Value, right: Value) -> Result<Value, FCMCError> {
+    if let (BinaryOp::Eq | BinaryOp::Ne, Value::Array(_), _) | (BinaryOp::Eq | BinaryOp::Ne, _, Value::Array(_)) =
+        (operator, &left, &right)
+    {
+        let equal = left.field_elements() == right.field_elements();
+        return Ok(Value::Bool(equal == matches!(operator, BinaryOp::Eq)));
+    }
+    let symbol = format!("{:?}", operator);
+    let a = scalar(&left, &format!("left operand of '{}'", symbol))?;
+    let b = scalar(&right, &format!("right operand of '{}'", symbol))?;
+    match operator {
+        BinaryOp::Eq => return Ok(Value::Bool(a == b)),
+        BinaryOp::Ne => return Ok(Value::Bool(a != b)),
+        BinaryOp::Lt => return Ok(Value::Bool(a < b)),
+        BinaryOp::Le => return Ok(Value::Bool(a <= b)),
+        BinaryOp::Gt => return Ok(Value::Bool(a > b)),
+        BinaryOp::Ge => return Ok(Value::Bool(a >= b)),
+        _ => {}
+    }
+
+    // Arithmetic is on u32s if either side is one, and on field elements otherwise
+    if matches!(left, Value::U32(_)) || matches!(right, Value::U32(_)) {
+        let what = format!("operand of u32 '{}'", symbol);
+        let a = as_u32(a, &what)?;
+        let b = as_u32(b, &what)?;
+        let value = match operator {
+            BinaryOp::Add => u32_result(BigUint::from(a) + b, 0, &symbol)?,
+            BinaryOp::Sub => u32_result(BigUint::from(a), b, &symbol)?,
+            BinaryOp::Mul => u32_result(BigUint::from(a) * b, 0, &symbol)?,
+            BinaryOp::Div | BinaryOp::Mod if b == 0 => {
+                return Err(failure(format!("u32 '{}' by zero", symbol)));
+            }
+            BinaryOp::Div => a / b,
+            BinaryOp::Mod => a % b,
+            _ => return Err(failure(format!("'{}' is not defined on u32 values", symbol))),
+        };
+        return Ok(Value::U32(value));
+    }
+    Ok(Value::Field(match operator {
+        BinaryOp::Add => field::add(&a, &b),
+        BinaryOp::Sub => field::sub(&a, &b),
+        BinaryOp::Mul => field::mul(&a, &b),
+        BinaryOp::Div => match field::inverse(&b) {
+            Some(inverse) => field::mul(&a, &inverse),
+            None => return Err(failure("Division by zero".to_string())),
+        },
+        BinaryOp::Mod if b.is_zero() => return Err(failure("Remainder by zero".to_string())),
+        BinaryOp::Mod => a % b,
+        _ => return Err(failure(format!("'{}' is not defined on field elements", symbol))),
+    }))
+}
+
+/// `minuend - subtrahend` as a u32, failing outside `[0, 2^32)`
+fn u32_result(minuend: BigUint, subtrahend: u32, symbol: &str) -> Result<u32, FCMCError> {
+    if minuend < BigUint::from(subtrahend) {
+        return Err(failure(format!("u32 '{}' underflows", symbol)));
+    }
+    (minuend - subtrahend)
+        .to_u32()
+        .ok_or_else(|| failure(format!("u32 '{}' overflows", symbol)))
+}
+
+fn as_u32(value: BigUint, what: &str) -> Result<u32, FCMCError> {
+    value
+        .to_u32()
+        .ok_or_else(|| failure(format!("The {} is {}, which does not fit in a u32", what, value)))
+}
+
+fn scalar(value: &Value, what: &str) -> Result<BigUint, FCMCError> {
+    value
+        .scalar()
+        .ok_or_else(|| failure(format!("The {} must be a single value, not {:?}", what, value)))
+}
+
+/// `value` as a value of `declared`, failing if it is out of range or of another shape
+fn conform(value: Value, declared: &Type, what: &str) -> Result<Value, FCMCError> {
+    match (declared, value) {
+        (Type::Array(element, size), Value::Array(elements)) if elements.len() == *size => elements
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| conform(value, element, &format!("{}[{}]", what, index)))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        (Type::Unit, Value::Unit) => Ok(Value::Unit),
+        (Type::Array(..) | Type::Unit, value) => Err(failure(format!(
+            "The {} is {:?}, which is not a {:?}",
+            what, value, declared
+        ))),
+        (declared, value) => {
+            let scalar = scalar(&value, what)?;
+            match declared {
+                Type::Bool if scalar.is_zero() || scalar.is_one() => Ok(Value::Bool(scalar.is_one())),
+                Type::Bool => Err(failure(format!("The {} is {}, which is not a bool", what, scalar))),
+                Type::U32 => Ok(Value::U32(as_u32(scalar, what)?)),
+                _ => Ok(Value::Field(scalar)),
+            }
+        }
+    }
+}
+
+fn failure(message: String) -> FCMCError {
+    FCMCError::VerificationError(message)
+}
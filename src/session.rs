@@ -0,0 +1,148 @@
+//! A compiler that lives across many compilations, for servers compiling user circuits.
+//!
+//! `FCMC::compile` starts from nothing every call. A `CompilerSession` shares three things
+//! between compilations: the rewrite rule files, parsed once when the session is created;
+//! the standard Poseidon parameters of each field, whose round constants are generated on
+//! first use; and the most recent circuits by source, so resubmitting a program returns
+//! the circuit compiled before. Everything else, the standard library gadgets included, is
+//! built again by each compilation.
+//!
+//! A session is `Send + Sync`: share it behind an `Arc` and call `compile` from as many
+//! threads as there are compilations to run. Each compilation still runs on the thread
+//! that calls it. A circuit returned from the cache was compiled without reporting
+//! progress or warnings again.
+
+use crate::optimization::RuleSet;
+use crate::stdlib::ParamsCache;
+use crate::{CancellationToken, CompiledCircuit, FCMCError, FCMC};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Circuits a session keeps unless `with_cache_capacity` says otherwise
+pub const DEFAULT_CACHE_CAPACITY: usize = 64;
+
+/// What a session has done so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    /// Compilations run, failed ones included
+    pub compiled: usize,
+    /// Calls answered from the circuit cache
+    pub cache_hits: usize,
+    pub cached_circuits: usize,
+    /// Poseidon parameter sets generated, one per field and input count used
+    pub poseidon_params: usize,
+}
+
+/// The most recent circuits by source, oldest evicted first
+#[derive(Default)]
+struct CircuitCache {
+    circuits: HashMap<String, Arc<CompiledCircuit>>,
+    order: VecDeque<String>,
+}
+
+pub struct CompilerSession {
+    compiler: FCMC,
+    poseidon: Arc<ParamsCache>,
+    circuits: Mutex<CircuitCache>,
+    capacity: usize,
+    compiled: AtomicUsize,
+    cache_hits: AtomicUsize,
+}
+
+// A session is shared between the threads compiling with it
+const _: fn() = || {
+    fn shared<T: Send + Sync>() {}
+    shared::<CompilerSession>();
+};
+
+impl CompilerSession {
+    /// A session compiling with `compiler`'s options; reads and parses its rewrite rule
+    /// files now, so later edits to them are not seen
+    pub fn new(compiler: FCMC) -> Result<Self, FCMCError> {
+        let mut compiler = compiler;
+        for path in std::mem::take(&mut compiler.rewrite_rules) {
+            let rules = RuleSet::load(&path)?;
+            tracing::debug!("Loaded {} rewrite rule(s) from {}", rules.len(), path.display());
+            compiler = compiler.with_rule_set(rules);
+        }
+        Ok(Self {
+            compiler,
+            poseidon: Arc::new(ParamsCache::new()),
+            circuits: Mutex::new(CircuitCache::default()),
+            capacity: DEFAULT_CACHE_CAPACITY,
+            compiled: AtomicUsize::new(0),
+            cache_hits: AtomicUsize::new(0),
+        })
+    }
+
+    /// Keep at most `capacity` circuits; 0 compiles every call
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn compiler(&self) -> &FCMC {
+        &self.compiler
+    }
+
+    /// Compile `source`, or return the circuit compiled from the same source before.
+    /// Two threads compiling a source neither has seen both compile it.
+    pub fn compile(&self, source: &str) -> Result<Arc<CompiledCircuit>, FCMCError> {
+        self.compile_with(&self.compiler, source)
+    }
+
+    /// `compile`, aborting with `FCMCError::Cancelled` once `token` is cancelled
+    pub fn compile_cancellable(
+        &self,
+        source: &str,
+        token: CancellationToken,
+    ) -> Result<Arc<CompiledCircuit>, FCMCError> {
+        self.compile_with(&self.compiler.clone().with_cancellation(token), source)
+    }
+
+    fn compile_with(&self, compiler: &FCMC, source: &str) -> Result<Arc<CompiledCircuit>, FCMCError> {
+        if let Some(circuit) = self.cached(source) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(circuit);
+        }
+        self.compiled.fetch_add(1, Ordering::Relaxed);
+        let circuit = Arc::new(self.poseidon.enter(|| compiler.compile(source))?);
+        if self.capacity > 0 {
+            let mut cache = self.lock_circuits();
+            if cache.circuits.insert(source.to_string(), circuit.clone()).is_none() {
+                cache.order.push_back(source.to_string());
+            }
+            while cache.order.len() > self.capacity {
+                if let Some(oldest) = cache.order.pop_front() {
+                    cache.circuits.remove(&oldest);
+                }
+            }
+        }
+        Ok(circuit)
+    }
+
+    fn cached(&self, source: &str) -> Option<Arc<CompiledCircuit>> {
+        self.lock_circuits().circuits.get(source).cloned()
+    }
+
+    /// Forget every circuit, keeping the rules and parameters
+    pub fn clear_circuits(&self) {
+        let mut cache = self.lock_circuits();
+        cache.circuits.clear();
+        cache.order.clear();
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        SessionStats {
+            compiled: self.compiled.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cached_circuits: self.lock_circuits().circuits.len(),
+            poseidon_params: self.poseidon.len(),
+        }
+    }
+
+    fn lock_circuits(&self) -> std::sync::MutexGuard<'_, CircuitCache> {
+        self.circuits.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,210 @@
+//! Unsigned big integers of any width, on 64-bit limbs, for arithmetic modulo values the
+//! native field cannot hold: RSA moduli, other curves' fields and group orders.
+//!
+//! In source, `BigUint<N>` is `Field[N]`: `N` limbs, least significant first, each
+//! range checked below 2^64 by the gadgets that take it. Sums and products are exact and
+//! one or `N` limbs wider than their operands; `rem` reduces them. Carries are taken by
+//! decomposing limb sums, as in emulated arithmetic, and the decompositions' equality
+//! constraints bound the limbs, so interval analysis drops range checks on any limb that
+//! an earlier gadget produced or a later one decomposes.
+//!
+//! The IR has no way to supply a quotient as advice, so `rem` is restoring long division:
+//! one conditional subtraction of the modulus per bit of the dividend, about 130
+//! constraints per dividend bit and modulus limb.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::stdlib::emulated::{self, Element, Limb, LIMB_BITS};
+use crate::stdlib::gadget::{Bit, Emitter};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes this module emits
+pub const BIGINT_GADGET: &str = "bigint";
+
+/// Limbs in each of `operands` equal-width operands making up `arguments` limbs
+pub fn limbs_per_operand(name: &str, arguments: usize, operands: usize) -> Result<usize, FCMCError> {
+    if arguments == 0 || arguments % operands != 0 {
+        return Err(FCMCError::SemanticError(format!(
+            "{} takes {} big integers of the same number of limbs, not {} limbs in all",
+            name, operands, arguments
+        )));
+    }
+    Ok(arguments / operands)
+}
+
+/// `a mod m`, failing for a zero modulus as the constraints would
+pub fn rem(a: &BigUint, m: &BigUint) -> Result<BigUint, FCMCError> {
+    if m.is_zero() {
+        return Err(FCMCError::VerificationError(
+            "Big integer remainder by zero".to_string(),
+        ));
+    }
+    Ok(a % m)
+}
+
+/// Emit `a + b` into `graph`, one limb wider than the operands
+pub fn lower_add(graph: &mut IRGraph, a: &[usize], b: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    emulated::check_field()?;
+    let mut emitter = Emitter::new(graph);
+    let (a, b) = (checked(&mut emitter, a), checked(&mut emitter, b));
+    let sum = emit_add(&mut emitter, &a, &b);
+    emitter.finish(BIGINT_GADGET);
+    Ok(sum)
+}
+
+/// Emit `a * b` into `graph`, twice as many limbs as the operands
+pub fn lower_mul(graph: &mut IRGraph, a: &[usize], b: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    emulated::check_field()?;
+    let mut emitter = Emitter::new(graph);
+    let (a, b) = (checked(&mut emitter, a), checked(&mut emitter, b));
+    let product = emit_mul(&mut emitter, &a, &b);
+    emitter.finish(BIGINT_GADGET);
+    Ok(product)
+}
+
+/// Emit `a mod m` into `graph`, as many limbs as the modulus, constraining the modulus
+/// to be nonzero
+pub fn lower_rem(graph: &mut IRGraph, a: &[usize], m: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    emulated::check_field()?;
+    let mut emitter = Emitter::new(graph);
+    let remainder = emit_rem(&mut emitter, a, m);
+    emitter.finish(BIGINT_GADGET);
+    Ok(remainder)
+}
+
+/// Emit `a * b mod m` into `graph`, as many limbs as the operands
+pub fn lower_mul_mod(graph: &mut IRGraph, a: &[usize], b: &[usize], m: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    emulated::check_field()?;
+    let mut emitter = Emitter::new(graph);
+    let (a, b) = (checked(&mut emitter, a), checked(&mut emitter, b));
+    let product = emit_mul(&mut emitter, &a, &b);
+    let remainder = emit_rem(&mut emitter, &product, m);
+    emitter.finish(BIGINT_GADGET);
+    Ok(remainder)
+}
+
+/// The operand on the nodes `limbs`, each range checked below 2^64
+fn checked(emitter: &mut Emitter, limbs: &[usize]) -> Element {
+    for &limb in limbs {
+        emitter.assert_range(limb, LIMB_BITS);
+    }
+    Element::from_limbs(limbs)
+}
+
+fn emit_add(emitter: &mut Emitter, a: &Element, b: &Element) -> Vec<usize> {
+    let columns = a
+        .limbs
+        .iter()
+        .zip(&b.limbs)
+        .map(|(x, y)| Limb {
+            node: emitter.binary(IRNodeType::Add, x.node, y.node),
+            bound: &x.bound + &y.bound,
+        })
+        .collect();
+    let limbs = emulated::normalize(emitter, columns);
+    exact(emitter, limbs, a.limbs.len() + 1)
+}
+
+fn emit_mul(emitter: &mut Emitter, a: &Element, b: &Element) -> Vec<usize> {
+    let mut columns: Vec<Option<Limb>> = vec![None; a.limbs.len() + b.limbs.len() - 1];
+    for (i, x) in a.limbs.iter().enumerate() {
+        for (j, y) in b.limbs.iter().enumerate() {
+            let product = Limb {
+                node: emitter.binary(IRNodeType::Mul, x.node, y.node),
+                bound: &x.bound * &y.bound,
+            };
+            columns[i + j] = Some(emulated::accumulate(emitter, columns[i + j].take(), product));
+        }
+    }
+    let limbs = emulated::normalize(emitter, columns.into_iter().flatten().collect());
+    exact(emitter, limbs, a.limbs.len() + b.limbs.len())
+}
+
+/// The nodes of `count` limbs of a value known to fit in them. Limb bounds overestimate,
+/// so normalizing can leave limbs above `count`; every limb below 2^64 makes the value's
+/// representation unique, so those are zero and are dropped.
+fn exact(emitter: &mut Emitter, mut limbs: Vec<Limb>, count: usize) -> Vec<usize> {
+    limbs.truncate(count);
+    let mut nodes: Vec<usize> = limbs.into_iter().map(|limb| limb.node).collect();
+    while nodes.len() < count {
+        nodes.push(emitter.constant(&BigUint::zero()));
+    }
+    nodes
+}
+
+/// Restoring division from the top bit of `a` down, keeping the remainder as bits. Each
+/// step shifts the next bit in and subtracts `m` limb by limb: `chunk + 2^64 - 1 + carry
+/// - m_i` is below 2^65, its low 64 bits are the difference and its top bit the carry,
+/// set when the limb did not borrow. The difference replaces the remainder unless the
+/// whole subtraction borrowed, so the remainder stays below `m`.
+fn emit_rem(emitter: &mut Emitter, a: &[usize], m: &[usize]) -> Vec<usize> {
+    for &limb in m {
+        emitter.assert_range(limb, LIMB_BITS);
+    }
+    // Limbs below 2^64 cannot wrap when summed, so the sum is zero only for a zero modulus
+    let mut sum = m[0];
+    for &limb in &m[1..] {
+        sum = emitter.binary(IRNodeType::Add, sum, limb);
+    }
+    let zero = emitter.constant(&BigUint::zero());
+    let is_zero = emitter.binary(IRNodeType::Eq, sum, zero);
+    emitter.assert_equal(is_zero, zero);
+
+    let dividend: Vec<Bit> = a
+        .iter()
+        .rev()
+        .flat_map(|&limb| emitter.unpack(limb, LIMB_BITS).into_iter().rev().collect::<Vec<Bit>>())
+        .collect();
+    let offset = emitter.constant(&emulated::limb_mask());
+    let width = LIMB_BITS as usize * m.len();
+    let mut remainder = vec![Bit::Constant(false); width];
+    for bit in dividend {
+        let mut shifted = Vec::with_capacity(width + 1);
+        shifted.push(bit);
+        shifted.extend_from_slice(&remainder);
+
+        let mut difference = Vec::with_capacity(width);
+        let mut carry = Bit::Constant(true);
+        for (index, &limb) in m.iter().enumerate() {
+            let chunk = emitter.pack(&shifted[index * LIMB_BITS as usize..(index + 1) * LIMB_BITS as usize]);
+            let carry_node = emitter.pack(&[carry]);
+            let chunk = emitter.binary(IRNodeType::Add, chunk, carry_node);
+            let chunk = emitter.binary(IRNodeType::Add, chunk, offset);
+            let limb_difference = emitter.binary(IRNodeType::Sub, chunk, limb);
+            let bits = emitter.unpack(limb_difference, LIMB_BITS + 1);
+            difference.extend_from_slice(&bits[..LIMB_BITS as usize]);
+            carry = bits[LIMB_BITS as usize];
+        }
+
+        // shifted >= m: the shifted-out bit covers a final borrow, or there was none
+        let borrowed = emitter.not(carry);
+        let below = emitter.not(shifted[width]);
+        let less = emitter.and(borrowed, below);
+        let Bit::Node(less) = less else {
+            unreachable!("the carry out of a decomposition is a node")
+        };
+        remainder = difference
+            .into_iter()
+            .zip(&shifted[..width])
+            .map(|(difference, &kept)| select_bit(emitter, less, kept, difference))
+            .collect();
+    }
+    remainder
+        .chunks(LIMB_BITS as usize)
+        .map(|limb| emitter.pack(limb))
+        .collect()
+}
+
+/// `condition ? a : b` on bits
+fn select_bit(emitter: &mut Emitter, condition: usize, a: Bit, b: Bit) -> Bit {
+    if a == b {
+        return a;
+    }
+    let mut node = |bit: Bit| match bit {
+        Bit::Node(node) => node,
+        Bit::Constant(value) => emitter.constant(&if value { BigUint::one() } else { BigUint::zero() }),
+    };
+    let (a, b) = (node(a), node(b));
+    Bit::Node(emitter.select(condition, a, b))
+}
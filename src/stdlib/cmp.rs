@@ -0,0 +1,166 @@
+//! Comparisons of field elements as integers, and sorting.
+//!
+//! Ordered comparisons take the operand width in bits, which must be a constant. Every
+//! operand is range checked to that width, so a comparison cannot be fooled by a value
+//! that wraps around the modulus; the range-check passes drop the checks an operand
+//! already carries. Each comparison is a single `Lt` node, `bits + 1` constraints on R1CS,
+//! and `min`, `max` and each comparator of a sort select on it rather than comparing again.
+
+use crate::backend::r1cs::max_comparison_bits;
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field;
+use crate::stdlib::gadget::Emitter;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::Zero;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes this module emits
+pub const CMP_GADGET: &str = "cmp";
+
+/// Fail unless comparisons of `bits`-bit operands can be constrained in the active field
+pub fn check_bits(bits: u32) -> Result<(), FCMCError> {
+    if bits == 0 || bits > max_comparison_bits() {
+        return Err(FCMCError::SemanticError(format!(
+            "Comparisons take operands of 1 to {} bits in the {} field, not {}",
+            max_comparison_bits(),
+            field::active(),
+            bits
+        )));
+    }
+    Ok(())
+}
+
+fn check_operand(value: &BigUint, bits: u32) -> Result<(), FCMCError> {
+    if value.bits() > bits as u64 {
+        return Err(FCMCError::VerificationError(format!(
+            "Comparison operand {} does not fit in {} bits",
+            value, bits
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `a < b`, failing unless both fit in `bits` bits as the constraints would
+pub fn less_than(a: &BigUint, b: &BigUint, bits: u32) -> Result<bool, FCMCError> {
+    check_bits(bits)?;
+    check_operand(a, bits)?;
+    check_operand(b, bits)?;
+    Ok(a < b)
+}
+
+pub fn is_zero(value: &BigUint) -> bool {
+    value.is_zero()
+}
+
+pub fn min(a: &BigUint, b: &BigUint, bits: u32) -> Result<BigUint, FCMCError> {
+    Ok(if less_than(a, b, bits)? { a } else { b }.clone())
+}
+
+pub fn max(a: &BigUint, b: &BigUint, bits: u32) -> Result<BigUint, FCMCError> {
+    Ok(if less_than(a, b, bits)? { b } else { a }.clone())
+}
+
+/// `values` in ascending order, through the same network `lower_sort` emits
+pub fn sort(values: &[BigUint], bits: u32) -> Result<Vec<BigUint>, FCMCError> {
+    check_bits(bits)?;
+    for value in values {
+        check_operand(value, bits)?;
+    }
+    let mut sorted = values.to_vec();
+    for (i, j) in sorting_network(values.len()) {
+        if sorted[j] < sorted[i] {
+            sorted.swap(i, j);
+        }
+    }
+    Ok(sorted)
+}
+
+/// Comparators `(i, j)`, `i < j`, of Batcher's odd-even merge sort on `inputs` wires,
+/// each leaving the smaller value at `i`. Up to eight inputs this is the fewest
+/// comparators any sorting network needs.
+pub fn sorting_network(inputs: usize) -> Vec<(usize, usize)> {
+    let mut comparators = Vec::new();
+    let mut merged = 1;
+    while merged < inputs {
+        let mut distance = merged;
+        while distance >= 1 {
+            let mut start = distance % merged;
+            while start + distance < inputs {
+                for offset in 0..distance.min(inputs - start - distance) {
+                    let (i, j) = (start + offset, start + offset + distance);
+                    if i / (2 * merged) == j / (2 * merged) {
+                        comparators.push((i, j));
+                    }
+                }
+                start += 2 * distance;
+            }
+            distance /= 2;
+        }
+        merged *= 2;
+    }
+    comparators
+}
+
+/// Emit whether `a < b` for `bits`-bit operands into `graph`, returning the boolean node
+pub fn lower_less_than(graph: &mut IRGraph, a: usize, b: usize, bits: u32) -> Result<usize, FCMCError> {
+    check_bits(bits)?;
+    let mut emitter = Emitter::new(graph);
+    let less = checked_less_than(&mut emitter, a, b, bits);
+    emitter.finish(CMP_GADGET);
+    Ok(less)
+}
+
+/// Emit whether `value` is zero into `graph`, returning the boolean node
+pub fn lower_is_zero(graph: &mut IRGraph, value: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    let zero = emitter.constant(&BigUint::zero());
+    let result = emitter.binary(IRNodeType::Eq, value, zero);
+    emitter.finish(CMP_GADGET);
+    result
+}
+
+pub fn lower_min(graph: &mut IRGraph, a: usize, b: usize, bits: u32) -> Result<usize, FCMCError> {
+    check_bits(bits)?;
+    let mut emitter = Emitter::new(graph);
+    let less = checked_less_than(&mut emitter, a, b, bits);
+    let result = emitter.select(less, a, b);
+    emitter.finish(CMP_GADGET);
+    Ok(result)
+}
+
+pub fn lower_max(graph: &mut IRGraph, a: usize, b: usize, bits: u32) -> Result<usize, FCMCError> {
+    check_bits(bits)?;
+    let mut emitter = Emitter::new(graph);
+    let less = checked_less_than(&mut emitter, a, b, bits);
+    let result = emitter.select(less, b, a);
+    emitter.finish(CMP_GADGET);
+    Ok(result)
+}
+
+/// Emit `values` in ascending order into `graph` with `sorting_network`, returning the
+/// sorted nodes. Inputs are range checked once; comparators work on their outputs.
+pub fn lower_sort(graph: &mut IRGraph, values: &[usize], bits: u32) -> Result<Vec<usize>, FCMCError> {
+    check_bits(bits)?;
+    let mut emitter = Emitter::new(graph);
+    for &value in values {
+        emitter.assert_range(value, bits);
+    }
+    let mut sorted = values.to_vec();
+    for (i, j) in sorting_network(values.len()) {
+        let less = emitter.less_than(sorted[j], sorted[i], bits);
+        let (low, high) = (
+            emitter.select(less, sorted[j], sorted[i]),
+            emitter.select(less, sorted[i], sorted[j]),
+        );
+        sorted[i] = low;
+        sorted[j] = high;
+    }
+    emitter.finish(CMP_GADGET);
+    Ok(sorted)
+}
+
+fn checked_less_than(emitter: &mut Emitter, a: usize, b: usize, bits: u32) -> usize {
+    emitter.assert_range(a, bits);
+    emitter.assert_range(b, bits);
+    emitter.less_than(a, b, bits)
+}
@@ -0,0 +1,292 @@
+//! Twisted Edwards curves embedded in the active field: `a x^2 + y^2 = 1 + d x^2 y^2`
+//! with coordinates in the field itself, so point arithmetic is native arithmetic.
+//!
+//! The curve follows `FieldConfig`: Baby Jubjub (EIP-2494) over BN254 and Jubjub over
+//! BLS12-381; the other fields have no embedded curve here. On both curves `a` is a square
+//! and `d` is not, so the addition law is complete on the curve: it has no exceptional
+//! points, its denominators never vanish, and doubling is addition of a point to itself.
+//! Points are not checked to lie on the curve; that is the caller's to constrain.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field::{self, FieldConfig};
+use crate::stdlib::gadget::{Bit, Emitter};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes this module emits
+pub const CURVE_GADGET: &str = "curve";
+
+/// An affine point; the identity is `(0, 1)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub x: BigUint,
+    pub y: BigUint,
+}
+
+impl Point {
+    pub fn identity() -> Self {
+        Self {
+            x: BigUint::zero(),
+            y: BigUint::one(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Curve {
+    pub name: &'static str,
+    pub a: BigUint,
+    pub d: BigUint,
+    /// Generator of the prime-order subgroup, the base of `mul_base`
+    pub base: Point,
+    /// Order of the subgroup `base` generates
+    pub order: BigUint,
+}
+
+impl Curve {
+    /// The curve embedded in `field`
+    pub fn for_field(field: &FieldConfig) -> Result<Self, FCMCError> {
+        // (name, a, d, base x, base y, subgroup order)
+        let (name, a, d, x, y, order) = match field {
+            FieldConfig::Bn254 => (
+                "baby-jubjub",
+                "168700",
+                "168696",
+                "5299619240641551281634865583518297030282874472190772894086521144482721001553",
+                "16950150798460657717958625567821834550301663161624707787222815936182638968203",
+                "2736030358979909402780800718157159386076813972158567259200215660948447373041",
+            ),
+            // d = -10240/10241; the base is 8 times the point of least y with an even x
+            FieldConfig::Bls12_381 => (
+                "jubjub",
+                "-1",
+                "19257038036680949359750312669786877991949435402254120286184196891950884077233",
+                "26425721312295396735536009845259662215154440146657062145727563247428679108070",
+                "33870355149453697655464584064870436861767017640968433840972803788419917420560",
+                "6554484396890773809930967563523245729705921265872317281365359162392183254199",
+            ),
+            _ => {
+                return Err(FCMCError::SemanticError(format!(
+                    "The {} field has no embedded curve; point operations need bn254 or bls12-381",
+                    field
+                )));
+            }
+        };
+        field.enter(|| {
+            let element = |value: &str| field::parse_element(value).expect("valid curve constant");
+            Ok(Self {
+                name,
+                a: element(a),
+                d: element(d),
+                base: Point {
+                    x: element(x),
+                    y: element(y),
+                },
+                order: order.parse().expect("valid curve order"),
+            })
+        })
+    }
+
+    /// The curve embedded in the active field
+    pub fn active() -> Result<Self, FCMCError> {
+        Self::for_field(&field::active())
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        let (xx, yy) = (field::mul(&point.x, &point.x), field::mul(&point.y, &point.y));
+        let left = field::add(&field::mul(&self.a, &xx), &yy);
+        let right = field::add(&BigUint::one(), &field::mul(&self.d, &field::mul(&xx, &yy)));
+        left == right
+    }
+
+    /// `p + q`, failing only for points off the curve where a denominator vanishes
+    pub fn add(&self, p: &Point, q: &Point) -> Result<Point, FCMCError> {
+        let xy = field::mul(&p.x, &q.y);
+        let yx = field::mul(&p.y, &q.x);
+        let t = field::mul(&self.d, &field::mul(&xy, &yx));
+        let x_numerator = field::add(&xy, &yx);
+        let y_numerator = field::sub(&field::mul(&p.y, &q.y), &field::mul(&self.a, &field::mul(&p.x, &q.x)));
+        let divide = |numerator: &BigUint, denominator: BigUint| {
+            field::inverse(&denominator)
+                .map(|inverse| field::mul(numerator, &inverse))
+                .ok_or_else(|| {
+                    FCMCError::VerificationError(format!("Cannot add ({}, {}) and ({}, {})", p.x, p.y, q.x, q.y))
+                })
+        };
+        Ok(Point {
+            x: divide(&x_numerator, field::add(&BigUint::one(), &t))?,
+            y: divide(&y_numerator, field::sub(&BigUint::one(), &t))?,
+        })
+    }
+
+    pub fn double(&self, point: &Point) -> Result<Point, FCMCError> {
+        self.add(point, point)
+    }
+
+    /// Bits of a `mul_base` scalar: the subgroup order's, so every scalar below the order fits
+    pub fn scalar_bits(&self) -> u32 {
+        self.order.bits() as u32
+    }
+
+    /// `scalar * base`, failing unless the scalar fits in `scalar_bits` as the constraints would
+    pub fn mul_base(&self, scalar: &BigUint) -> Result<Point, FCMCError> {
+        if scalar.bits() > self.scalar_bits() as u64 {
+            return Err(FCMCError::VerificationError(format!(
+                "Scalar {} does not fit in the {} bits of a {} scalar",
+                scalar,
+                self.scalar_bits(),
+                self.name
+            )));
+        }
+        self.mul(scalar, &self.base, self.scalar_bits())
+    }
+
+    /// `scalar * point` over the low `bits` bits of the scalar, doubling between bits as
+    /// `lower_mul` does
+    pub fn mul(&self, scalar: &BigUint, point: &Point, bits: u32) -> Result<Point, FCMCError> {
+        let mut result = Point::identity();
+        let mut power = point.clone();
+        for index in 0..bits as u64 {
+            if index > 0 {
+                power = self.double(&power)?;
+            }
+            if scalar.bit(index) {
+                result = self.add(&result, &power)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Check that the base lies on the curve and generates a subgroup of the given order
+    pub fn check(&self) -> Result<(), FCMCError> {
+        let order_times_base = self.mul_base(&self.order)?;
+        if !self.contains(&self.base) || order_times_base != Point::identity() {
+            return Err(FCMCError::VerificationError(format!(
+                "The {} base point is not a point of order {}",
+                self.name, self.order
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Check every embedded curve's constants, each in its own field
+pub fn check_curves() -> Result<(), FCMCError> {
+    for field in [FieldConfig::Bn254, FieldConfig::Bls12_381] {
+        field.enter(|| Curve::for_field(&field)?.check())?;
+    }
+    Ok(())
+}
+
+/// Emit `p + q` into `graph`, with points as `(x, y)` node pairs
+pub fn lower_add(graph: &mut IRGraph, curve: &Curve, p: (usize, usize), q: (usize, usize)) -> (usize, usize) {
+    let mut emitter = Emitter::new(graph);
+    let sum = emit_add(&mut emitter, curve, p, q);
+    emitter.finish(CURVE_GADGET);
+    sum
+}
+
+pub fn lower_double(graph: &mut IRGraph, curve: &Curve, point: (usize, usize)) -> (usize, usize) {
+    lower_add(graph, curve, point, point)
+}
+
+/// Emit `scalar * base` into `graph`. The scalar is decomposed into `scalar_bits` bits,
+/// which also constrains it below `2^scalar_bits`, and each set bit adds a constant
+/// multiple of the base selected by that bit.
+pub fn lower_mul_base(graph: &mut IRGraph, curve: &Curve, scalar: usize) -> Result<(usize, usize), FCMCError> {
+    let mut emitter = Emitter::new(graph);
+    let bits = emitter.unpack(scalar, curve.scalar_bits());
+    let mut result: Option<(usize, usize)> = None;
+    let mut power = curve.base.clone();
+    for bit in bits {
+        let Bit::Node(bit) = bit else {
+            unreachable!("unpacked bits are nodes")
+        };
+        // (bit * x, 1 + bit * (y - 1)) is the power if the bit is set and the identity if not
+        let x = emitter.constant(&power.x);
+        let x = emitter.binary(IRNodeType::Mul, bit, x);
+        let y_offset = emitter.constant(&field::sub(&power.y, &BigUint::one()));
+        let y_offset = emitter.binary(IRNodeType::Mul, bit, y_offset);
+        let one = emitter.constant(&BigUint::one());
+        let y = emitter.binary(IRNodeType::Add, one, y_offset);
+        result = Some(match result {
+            Some(sum) => emit_add(&mut emitter, curve, sum, (x, y)),
+            None => (x, y),
+        });
+        power = curve.double(&power)?;
+    }
+    let result = result.expect("the subgroup order has at least one bit");
+    emitter.finish(CURVE_GADGET);
+    Ok(result)
+}
+
+/// Emit `scalar * point` into `graph`, with the scalar decomposed into `bits` bits, which
+/// also constrains it below `2^bits`. Each bit selects the running power of the point or
+/// the identity, and the power is doubled between bits.
+pub fn lower_mul(
+    graph: &mut IRGraph,
+    curve: &Curve,
+    scalar: usize,
+    point: (usize, usize),
+    bits: u32,
+) -> (usize, usize) {
+    let mut emitter = Emitter::new(graph);
+    let bits = emitter.unpack(scalar, bits);
+    let one = emitter.constant(&BigUint::one());
+    let mut result: Option<(usize, usize)> = None;
+    let mut power = point;
+    for (index, bit) in bits.into_iter().enumerate() {
+        let Bit::Node(bit) = bit else {
+            unreachable!("unpacked bits are nodes")
+        };
+        if index > 0 {
+            power = emit_add(&mut emitter, curve, power, power);
+        }
+        let x = emitter.binary(IRNodeType::Mul, bit, power.0);
+        let y_offset = emitter.binary(IRNodeType::Sub, power.1, one);
+        let y_offset = emitter.binary(IRNodeType::Mul, bit, y_offset);
+        let y = emitter.binary(IRNodeType::Add, one, y_offset);
+        result = Some(match result {
+            Some(sum) => emit_add(&mut emitter, curve, sum, (x, y)),
+            None => (x, y),
+        });
+    }
+    let result = result.expect("scalars have at least one bit");
+    emitter.finish(CURVE_GADGET);
+    result
+}
+
+/// The complete addition law in four multiplications and two divisions:
+/// `x3 = (b + c) / (1 + d t)` and `y3 = (e + a b - c) / (1 - d t)` with `b = x1 y2`,
+/// `c = y1 x2`, `e = (y1 - a x1)(x2 + y2)` and `t = b c`
+fn emit_add(
+    emitter: &mut Emitter,
+    curve: &Curve,
+    (x1, y1): (usize, usize),
+    (x2, y2): (usize, usize),
+) -> (usize, usize) {
+    let a = emitter.constant(&curve.a);
+    let d = emitter.constant(&curve.d);
+    let one = emitter.constant(&BigUint::one());
+
+    let b = emitter.binary(IRNodeType::Mul, x1, y2);
+    let c = emitter.binary(IRNodeType::Mul, y1, x2);
+    let a_x1 = emitter.binary(IRNodeType::Mul, a, x1);
+    let left = emitter.binary(IRNodeType::Sub, y1, a_x1);
+    let right = emitter.binary(IRNodeType::Add, x2, y2);
+    let e = emitter.binary(IRNodeType::Mul, left, right);
+    let t = emitter.binary(IRNodeType::Mul, b, c);
+    let dt = emitter.binary(IRNodeType::Mul, d, t);
+
+    let x_numerator = emitter.binary(IRNodeType::Add, b, c);
+    let x_denominator = emitter.binary(IRNodeType::Add, one, dt);
+    let x3 = emitter.binary(IRNodeType::Div, x_numerator, x_denominator);
+
+    let a_b = emitter.binary(IRNodeType::Mul, a, b);
+    let e_ab = emitter.binary(IRNodeType::Add, e, a_b);
+    let y_numerator = emitter.binary(IRNodeType::Sub, e_ab, c);
+    let y_denominator = emitter.binary(IRNodeType::Sub, one, dt);
+    let y3 = emitter.binary(IRNodeType::Div, y_numerator, y_denominator);
+    (x3, y3)
+}
@@ -0,0 +1,321 @@
+//! ECDSA verification over secp256k1, whose coordinates and scalars are 256-bit integers
+//! that do not fit the native field, so the gadget works in `emulated` arithmetic.
+//!
+//! Every 256-bit value crosses the call boundary as four 64-bit limbs, least significant
+//! first, each range checked. Points are projective and added with the complete formulas
+//! of Renes, Costello and Batina for `a = 0`, so no step divides and the identity needs
+//! no special case. `s^-1 mod n` is `s^(n-2)`, and `u1 G + u2 Q` is one double-and-add
+//! pass over both scalars' bits. Verification comes to several million constraints.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::stdlib::emulated::{self, Element, Modulus, LIMBS, LIMB_BITS};
+use crate::stdlib::gadget::{Bit, Emitter};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_verify_ecdsa` emits
+pub const ECDSA_GADGET: &str = "ecdsa-secp256k1";
+
+const P: &str = "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f";
+const N: &str = "fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141";
+const GX: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+const GY: &str = "483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+/// `y^2 = x^3 + B`
+const B: u32 = 7;
+
+fn hex(value: &str) -> BigUint {
+    BigUint::parse_bytes(value.as_bytes(), 16).expect("valid secp256k1 constant")
+}
+
+/// The base field prime of secp256k1
+pub fn field_prime() -> BigUint {
+    hex(P)
+}
+
+/// The order of secp256k1's generator
+pub fn group_order() -> BigUint {
+    hex(N)
+}
+
+type Affine = Option<(BigUint, BigUint)>;
+
+fn affine_add(p: &Affine, q: &Affine, prime: &BigUint) -> Affine {
+    let ((x1, y1), (x2, y2)) = match (p, q) {
+        (None, point) | (point, None) => return point.clone(),
+        (Some(p), Some(q)) => (p, q),
+    };
+    let inverse = |value: BigUint| value.modpow(&(prime - 2u32), prime);
+    let slope = if x1 == x2 {
+        if (y1 + y2) % prime == BigUint::zero() {
+            return None;
+        }
+        BigUint::from(3u32) * x1 * x1 % prime * inverse(BigUint::from(2u32) * y1 % prime) % prime
+    } else {
+        (y2 + prime - y1) % prime * inverse((x2 + prime - x1) % prime) % prime
+    };
+    let x3 = (&slope * &slope + prime * 2u32 - x1 - x2) % prime;
+    let y3 = (&slope * ((x1 + prime - &x3) % prime) + prime - y1) % prime;
+    Some((x3, y3))
+}
+
+fn affine_mul(scalar: &BigUint, point: &Affine, prime: &BigUint) -> Affine {
+    let mut result = None;
+    for index in (0..scalar.bits()).rev() {
+        result = affine_add(&result, &result, prime);
+        if scalar.bit(index) {
+            result = affine_add(&result, point, prime);
+        }
+    }
+    result
+}
+
+/// Whether `(r, s)` is a valid signature of `message_hash` under the public key `(x, y)`;
+/// coordinates are taken modulo the field prime and the hash modulo the group order
+pub fn verify_ecdsa(x: &BigUint, y: &BigUint, message_hash: &BigUint, r: &BigUint, s: &BigUint) -> bool {
+    let (prime, order) = (field_prime(), group_order());
+    let (x, y) = (x % &prime, y % &prime);
+    let on_curve = (&y * &y) % &prime == (&x * &x * &x + B) % &prime;
+    if !on_curve || r.is_zero() || s.is_zero() || *r >= order || *s >= order {
+        return false;
+    }
+    let w = s.modpow(&(&order - 2u32), &order);
+    let u1 = message_hash % &order * &w % &order;
+    let u2 = r * &w % &order;
+    let generator = Some((hex(GX), hex(GY)));
+    let sum = affine_add(
+        &affine_mul(&u1, &generator, &prime),
+        &affine_mul(&u2, &Some((x, y)), &prime),
+        &prime,
+    );
+    match sum {
+        Some((x, _)) => x % &order == *r,
+        None => false,
+    }
+}
+
+/// Projective `(X, Y, Z)`, standing for `(X / Z, Y / Z)`; the identity is `(0, 1, 0)`
+type Projective = [Element; 3];
+
+/// Emit whether the limbs `signature` (`r` then `s`) are a valid signature of the limbs
+/// `message_hash` under the public key limbs `public_key` (`x` then `y`) into `graph`,
+/// returning the boolean node. Every limb is constrained below 2^64.
+pub fn lower_verify_ecdsa(
+    graph: &mut IRGraph,
+    public_key: &[usize],
+    message_hash: &[usize],
+    signature: &[usize],
+) -> Result<usize, FCMCError> {
+    emulated::check_field()?;
+    if public_key.len() != 2 * LIMBS || message_hash.len() != LIMBS || signature.len() != 2 * LIMBS {
+        return Err(FCMCError::SemanticError(format!(
+            "ECDSA verification takes a public key and a signature of {} limbs and a hash of {}, not {}, {} and {}",
+            2 * LIMBS,
+            LIMBS,
+            public_key.len(),
+            signature.len(),
+            message_hash.len()
+        )));
+    }
+    let (prime, order) = (Modulus::new(field_prime()), Modulus::new(group_order()));
+    let mut emitter = Emitter::new(graph);
+    for &limb in public_key.iter().chain(message_hash).chain(signature) {
+        emitter.assert_range(limb, LIMB_BITS);
+    }
+    let (x, y) = (
+        Element::from_limbs(&public_key[..LIMBS]),
+        Element::from_limbs(&public_key[LIMBS..]),
+    );
+    let hash = Element::from_limbs(message_hash);
+    let (r, s) = (
+        Element::from_limbs(&signature[..LIMBS]),
+        Element::from_limbs(&signature[LIMBS..]),
+    );
+
+    // y^2 = x^3 + 7
+    let y_squared = emulated::mul(&mut emitter, &y, &y, &prime);
+    let x_squared = emulated::mul(&mut emitter, &x, &x, &prime);
+    let x_cubed = emulated::mul(&mut emitter, &x_squared, &x, &prime);
+    let seven = emulated::constant(&mut emitter, &BigUint::from(B));
+    let right = emulated::add(&mut emitter, &x_cubed, &seven);
+    let residue = emulated::sub(&mut emitter, &y_squared, &right, &prime);
+    let on_curve = emulated::is_zero(&mut emitter, &residue, &prime);
+
+    // 0 < r, s < n
+    let mut valid = on_curve;
+    for scalar in [&r, &s] {
+        let below = emulated::less_than_constant(&mut emitter, scalar, &order.value);
+        let zero = emulated::is_zero(&mut emitter, scalar, &order);
+        let nonzero = not(&mut emitter, zero);
+        let in_range = emitter.binary(IRNodeType::And, below, nonzero);
+        valid = emitter.binary(IRNodeType::And, valid, in_range);
+    }
+
+    let w = emulated::pow(&mut emitter, &s, &(&order.value - 2u32), &order);
+    let u1 = emulated::mul(&mut emitter, &hash, &w, &order);
+    let u2 = emulated::mul(&mut emitter, &r, &w, &order);
+    let (u1_bits, u2_bits) = (
+        emulated::bits(&mut emitter, &u1, &order),
+        emulated::bits(&mut emitter, &u2, &order),
+    );
+
+    let one = emulated::constant(&mut emitter, &BigUint::one());
+    let zero = emulated::constant(&mut emitter, &BigUint::zero());
+    let identity: Projective = [zero.clone(), one.clone(), zero];
+    let generator: Projective = [
+        emulated::constant(&mut emitter, &hex(GX)),
+        emulated::constant(&mut emitter, &hex(GY)),
+        one.clone(),
+    ];
+    let key: Projective = [x, y, one];
+    let both = add_points(&mut emitter, &generator, &key, &prime);
+
+    let mut sum = identity.clone();
+    for (&u1_bit, &u2_bit) in u1_bits.iter().zip(&u2_bits).rev() {
+        sum = double_point(&mut emitter, &sum, &prime);
+        let with_key = select_point(&mut emitter, u1_bit, &both, &key);
+        let without_key = select_point(&mut emitter, u1_bit, &generator, &identity);
+        let term = select_point(&mut emitter, u2_bit, &with_key, &without_key);
+        sum = add_points(&mut emitter, &sum, &term, &prime);
+    }
+
+    // x(R) mod n = r, with x(R) = X / Z below p < 2n, is X = r Z or, when r + n < p, X = (r + n) Z
+    let [x_sum, _, z_sum] = sum;
+    let at_infinity = emulated::is_zero(&mut emitter, &z_sum, &prime);
+    let finite = not(&mut emitter, at_infinity);
+    let matches = x_matches(&mut emitter, &x_sum, &z_sum, &r, &prime);
+    let n = emulated::constant(&mut emitter, &order.value);
+    let r_plus_n = emulated::add(&mut emitter, &r, &n);
+    let matches_wrapped = x_matches(&mut emitter, &x_sum, &z_sum, &r_plus_n, &prime);
+    let small = emulated::less_than_constant(&mut emitter, &r, &(&prime.value - &order.value));
+    let wrapped = emitter.binary(IRNodeType::And, small, matches_wrapped);
+    let x_valid = emitter.binary(IRNodeType::Or, matches, wrapped);
+
+    valid = emitter.binary(IRNodeType::And, valid, finite);
+    valid = emitter.binary(IRNodeType::And, valid, x_valid);
+    emitter.finish(ECDSA_GADGET);
+    Ok(valid)
+}
+
+fn not(emitter: &mut Emitter, value: usize) -> usize {
+    match emitter.not(Bit::Node(value)) {
+        Bit::Node(node) => node,
+        Bit::Constant(_) => unreachable!("the negation of a node is a node"),
+    }
+}
+
+/// Whether `x = candidate * z` modulo `prime`
+fn x_matches(emitter: &mut Emitter, x: &Element, z: &Element, candidate: &Element, prime: &Modulus) -> usize {
+    let scaled = emulated::mul(emitter, candidate, z, prime);
+    let difference = emulated::sub(emitter, x, &scaled, prime);
+    emulated::is_zero(emitter, &difference, prime)
+}
+
+fn select_point(emitter: &mut Emitter, condition: usize, a: &Projective, b: &Projective) -> Projective {
+    [0, 1, 2].map(|index| emulated::select(emitter, condition, &a[index], &b[index]))
+}
+
+/// Complete addition for `a = 0`: Algorithm 7 of Renes, Costello and Batina (2016)
+fn add_points(emitter: &mut Emitter, p: &Projective, q: &Projective, prime: &Modulus) -> Projective {
+    let [x1, y1, z1] = p;
+    let [x2, y2, z2] = q;
+    let b3 = emulated::constant(emitter, &BigUint::from(3 * B));
+    let mul = |emitter: &mut Emitter, a: &Element, b: &Element| emulated::mul(emitter, a, b, prime);
+    let sub = |emitter: &mut Emitter, a: &Element, b: &Element| emulated::sub(emitter, a, b, prime);
+    let add = emulated::add;
+
+    let t0 = mul(emitter, x1, x2);
+    let t1 = mul(emitter, y1, y2);
+    let t2 = mul(emitter, z1, z2);
+    let (x1_y1, x2_y2) = (add(emitter, x1, y1), add(emitter, x2, y2));
+    let t3 = mul(emitter, &x1_y1, &x2_y2);
+    let t0_t1 = add(emitter, &t0, &t1);
+    let t3 = sub(emitter, &t3, &t0_t1);
+    let (y1_z1, y2_z2) = (add(emitter, y1, z1), add(emitter, y2, z2));
+    let t4 = mul(emitter, &y1_z1, &y2_z2);
+    let t1_t2 = add(emitter, &t1, &t2);
+    let t4 = sub(emitter, &t4, &t1_t2);
+    let (x1_z1, x2_z2) = (add(emitter, x1, z1), add(emitter, x2, z2));
+    let x3 = mul(emitter, &x1_z1, &x2_z2);
+    let t0_t2 = add(emitter, &t0, &t2);
+    let y3 = sub(emitter, &x3, &t0_t2);
+    let t0_double = add(emitter, &t0, &t0);
+    let t0 = add(emitter, &t0_double, &t0);
+    let t2 = mul(emitter, &t2, &b3);
+    let z3 = add(emitter, &t1, &t2);
+    let t1 = sub(emitter, &t1, &t2);
+    let y3 = mul(emitter, &y3, &b3);
+    let x3 = mul(emitter, &t4, &y3);
+    let t2 = mul(emitter, &t3, &t1);
+    let x3 = sub(emitter, &t2, &x3);
+    let y3 = mul(emitter, &y3, &t0);
+    let t1 = mul(emitter, &t1, &z3);
+    let y3 = add(emitter, &t1, &y3);
+    let t0 = mul(emitter, &t0, &t3);
+    let z3 = mul(emitter, &z3, &t4);
+    let z3 = add(emitter, &z3, &t0);
+    [x3, y3, z3]
+}
+
+/// Doubling for `a = 0`: Algorithm 9 of Renes, Costello and Batina (2016)
+fn double_point(emitter: &mut Emitter, point: &Projective, prime: &Modulus) -> Projective {
+    let [x, y, z] = point;
+    let b3 = emulated::constant(emitter, &BigUint::from(3 * B));
+    let mul = |emitter: &mut Emitter, a: &Element, b: &Element| emulated::mul(emitter, a, b, prime);
+    let add = emulated::add;
+
+    let t0 = mul(emitter, y, y);
+    let z3 = add(emitter, &t0, &t0);
+    let z3 = add(emitter, &z3, &z3);
+    let z3 = add(emitter, &z3, &z3);
+    let t1 = mul(emitter, y, z);
+    let t2 = mul(emitter, z, z);
+    let t2 = mul(emitter, &t2, &b3);
+    let x3 = mul(emitter, &t2, &z3);
+    let y3 = add(emitter, &t0, &t2);
+    let z3 = mul(emitter, &t1, &z3);
+    let t1 = add(emitter, &t2, &t2);
+    let t2 = add(emitter, &t1, &t2);
+    let t0 = emulated::sub(emitter, &t0, &t2, prime);
+    let y3 = mul(emitter, &t0, &y3);
+    let y3 = add(emitter, &x3, &y3);
+    let t1 = mul(emitter, x, y);
+    let x3 = mul(emitter, &t0, &t1);
+    let x3 = add(emitter, &x3, &x3);
+    [x3, y3, z3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Public key `(x, y)`, message hash, `r` and `s`, in hex: a signature of SHA-256("sample")
+    /// under a fixed key, made with an independent implementation
+    const VECTORS: &[(&str, &str, &str, &str, &str)] = &[(
+        "2c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645",
+        "64b95e4fdb6948c0386e189b006a29f686769b011704275e4459822dc3328085",
+        "af2bdbe1aa9b6ec1e2ade1d694f41fc71a831d0268e9891562113d8a62add1bf",
+        "432310e32cb80eb6503a26ce83cc165c783b870845fb8aad6d970889fcd7a6c8",
+        "530128b6b81c548874a6305d93ed071ca6e05074d85863d4056ce89b02bfab69",
+    )];
+
+    #[test]
+    fn accepts_reference_signatures() {
+        for &(x, y, message_hash, r, s) in VECTORS {
+            assert!(verify_ecdsa(&hex(x), &hex(y), &hex(message_hash), &hex(r), &hex(s)));
+        }
+    }
+
+    #[test]
+    fn rejects_a_changed_message_hash() {
+        for &(x, y, message_hash, r, s) in VECTORS {
+            assert!(!verify_ecdsa(
+                &hex(x),
+                &hex(y),
+                &(hex(message_hash) + 1u32),
+                &hex(r),
+                &hex(s)
+            ));
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! EdDSA over the embedded curve with a Poseidon challenge, as circomlib's
+//! `EdDSAPoseidonVerifier` defines it over Baby Jubjub.
+//!
+//! A signature `(R, S)` of the field element `M` under the public key `A` is valid when
+//! `S` is below the subgroup order and `S B = R + h (8 A)` with
+//! `h = Poseidon(R.x, R.y, A.x, A.y, M)`. Multiplying the key by the cofactor keeps a
+//! small-order component of `A` from mattering.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field;
+use crate::stdlib::curve::{self, Curve, Point};
+use crate::stdlib::gadget::Emitter;
+use crate::stdlib::poseidon::{self, PoseidonParams};
+use crate::FCMCError;
+use num_bigint::BigUint;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_verify_eddsa` emits besides the curve
+/// operations' and the hash's
+pub const EDDSA_GADGET: &str = "eddsa";
+
+/// Doublings that multiply the public key by the cofactor 8
+const COFACTOR_DOUBLINGS: usize = 3;
+
+/// Whether `(r, s)` is a valid signature of `message` under `public_key`, failing where
+/// the circuit's constraints would: for `s` wider than the subgroup order, or points for
+/// which the addition law divides by zero
+pub fn verify_eddsa(
+    curve: &Curve,
+    public_key: &Point,
+    message: &BigUint,
+    r: &Point,
+    s: &BigUint,
+) -> Result<bool, FCMCError> {
+    let left = curve.mul_base(s)?;
+    let challenge = poseidon::poseidon(&[
+        r.x.clone(),
+        r.y.clone(),
+        public_key.x.clone(),
+        public_key.y.clone(),
+        message.clone(),
+    ])?;
+    let mut key = public_key.clone();
+    for _ in 0..COFACTOR_DOUBLINGS {
+        key = curve.double(&key)?;
+    }
+    let right = curve.add(r, &curve.mul(&challenge, &key, field::active().bits())?)?;
+    Ok(*s < curve.order && left == right)
+}
+
+/// Emit whether `(r, s)` is a valid signature of `message` under `public_key` into `graph`,
+/// with points as `(x, y)` node pairs, returning the boolean node
+pub fn lower_verify_eddsa(
+    graph: &mut IRGraph,
+    curve: &Curve,
+    public_key: (usize, usize),
+    message: usize,
+    r: (usize, usize),
+    s: usize,
+) -> Result<usize, FCMCError> {
+    let params = PoseidonParams::for_inputs(5)?;
+    let mut emitter = Emitter::new(graph);
+    let left = curve::lower_mul_base(emitter.graph(), curve, s)?;
+    let challenge = poseidon::lower_poseidon(
+        emitter.graph(),
+        &params,
+        &[r.0, r.1, public_key.0, public_key.1, message],
+    )?;
+    let mut key = public_key;
+    for _ in 0..COFACTOR_DOUBLINGS {
+        key = curve::lower_double(emitter.graph(), curve, key);
+    }
+    let scaled = curve::lower_mul(emitter.graph(), curve, challenge, key, field::active().bits());
+    let right = curve::lower_add(emitter.graph(), curve, r, scaled);
+
+    // s is already below 2^scalar_bits from its decomposition in lower_mul_base
+    let order = emitter.constant(&curve.order);
+    let canonical = emitter.less_than(s, order, curve.scalar_bits());
+    let x_equal = emitter.binary(IRNodeType::Eq, left.0, right.0);
+    let y_equal = emitter.binary(IRNodeType::Eq, left.1, right.1);
+    let equal = emitter.binary(IRNodeType::And, x_equal, y_equal);
+    let verified = emitter.binary(IRNodeType::And, canonical, equal);
+    emitter.finish(EDDSA_GADGET);
+    Ok(verified)
+}
@@ -0,0 +1,411 @@
+//! Arithmetic modulo a 256-bit prime other than the native field, on 64-bit limbs.
+//!
+//! An element is a list of limb nodes with a known upper bound on each, standing for
+//! `sum_i limbs[i] * 2^(64 i)` modulo the emulated prime. Sums and differences just add
+//! limbs, growing the bounds; products multiply limb by limb into columns and reduce.
+//! Reduction carries every column into 64-bit limbs by decomposing it, then folds the
+//! limbs above 2^256 back down with `2^256 = c (mod m)`, which converges quickly for a
+//! modulus just below 2^256 such as secp256k1's. It stops at a loosely reduced element:
+//! three 64-bit limbs and a 65-bit top limb, a unique representation below 2^257 that
+//! need not be below the modulus.
+//!
+//! Every bound is an integer bound, kept below the native field's capacity, so no limb
+//! or column ever wraps and every decomposition is unique.
+
+use crate::ir::IRNodeType;
+use crate::optimization::field;
+use crate::stdlib::gadget::{Bit, Emitter};
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// Bits per limb
+pub const LIMB_BITS: u32 = 64;
+
+/// Limbs per loosely reduced element
+pub const LIMBS: usize = 4;
+
+/// Fewest native field bits emulated arithmetic works in: products of loosely reduced
+/// elements have columns up to 2^132
+pub const MIN_FIELD_BITS: u32 = 160;
+
+/// An emulated prime just below `2^(LIMB_BITS * LIMBS)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modulus {
+    pub value: BigUint,
+    /// Limbs of `2^256 - value`, what a unit above the top limb folds down to
+    fold: Vec<BigUint>,
+}
+
+impl Modulus {
+    pub fn new(value: BigUint) -> Self {
+        let excess = (BigUint::one() << (LIMB_BITS as usize * LIMBS)) - &value;
+        let mut fold = limbs_of(&excess, LIMBS);
+        while fold.last().is_some_and(Zero::is_zero) {
+            fold.pop();
+        }
+        Self { value, fold }
+    }
+}
+
+/// `value` as `count` limbs, least significant first
+pub fn limbs_of(value: &BigUint, count: usize) -> Vec<BigUint> {
+    let mask = limb_mask();
+    (0..count)
+        .map(|index| (value >> (LIMB_BITS as usize * index)) & &mask)
+        .collect()
+}
+
+pub(crate) fn limb_mask() -> BigUint {
+    (BigUint::one() << LIMB_BITS) - 1u32
+}
+
+/// Fail unless the active field is wide enough for emulated arithmetic
+pub fn check_field() -> Result<(), FCMCError> {
+    if field::active().bits() < MIN_FIELD_BITS {
+        return Err(FCMCError::SemanticError(format!(
+            "Emulated arithmetic needs a field of at least {} bits, not the {}-bit {} field",
+            MIN_FIELD_BITS,
+            field::active().bits(),
+            field::active()
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Limb {
+    pub node: usize,
+    /// Largest integer value the node can hold
+    pub bound: BigUint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Element {
+    pub limbs: Vec<Limb>,
+}
+
+impl Element {
+    /// The element on the nodes `limbs`, each of which must already be constrained below 2^64
+    pub fn from_limbs(limbs: &[usize]) -> Self {
+        Self {
+            limbs: limbs
+                .iter()
+                .map(|&node| Limb {
+                    node,
+                    bound: limb_mask(),
+                })
+                .collect(),
+        }
+    }
+
+    fn is_loose(&self) -> bool {
+        let (top, rest) = self.limbs.split_last().expect("elements have limbs");
+        self.limbs.len() == LIMBS
+            && rest.iter().all(|limb| limb.bound.bits() <= LIMB_BITS as u64)
+            && top.bound.bits() <= LIMB_BITS as u64 + 1
+    }
+
+    fn bound(&self) -> BigUint {
+        self.limbs
+            .iter()
+            .enumerate()
+            .map(|(index, limb)| &limb.bound << (LIMB_BITS as usize * index))
+            .sum()
+    }
+}
+
+pub(crate) fn constant(emitter: &mut Emitter, value: &BigUint) -> Element {
+    Element {
+        limbs: limbs_of(value, LIMBS)
+            .into_iter()
+            .map(|limb| Limb {
+                node: emitter.constant(&limb),
+                bound: limb,
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn add(emitter: &mut Emitter, a: &Element, b: &Element) -> Element {
+    let count = a.limbs.len().max(b.limbs.len());
+    let limbs = (0..count)
+        .map(|index| match (a.limbs.get(index), b.limbs.get(index)) {
+            (Some(a), Some(b)) => Limb {
+                node: emitter.binary(IRNodeType::Add, a.node, b.node),
+                bound: &a.bound + &b.bound,
+            },
+            (Some(limb), None) | (None, Some(limb)) => limb.clone(),
+            (None, None) => unreachable!("index is below the longer length"),
+        })
+        .collect();
+    Element { limbs }
+}
+
+/// `a - b`, computed as `a + k m - b` for a multiple `k m` whose limbs, borrowing from the
+/// limb above, each cover the bound of `b`'s limb, so no limb goes negative
+pub(crate) fn sub(emitter: &mut Emitter, a: &Element, b: &Element, modulus: &Modulus) -> Element {
+    let bounds: Vec<&BigUint> = b.limbs.iter().map(|limb| &limb.bound).collect();
+    let top = bounds.len() - 1;
+    let doubled = b.bound() * 2u32;
+    let multiple = (&doubled + &modulus.value - 1u32) / &modulus.value * &modulus.value;
+    let low_bounds: BigUint = bounds[..top]
+        .iter()
+        .enumerate()
+        .map(|(index, &bound)| bound << (LIMB_BITS as usize * index))
+        .sum();
+    let rest = &multiple - low_bounds;
+    let spread = limbs_of(&rest, top);
+    let mut offset: Vec<BigUint> = bounds[..top]
+        .iter()
+        .zip(spread)
+        .map(|(&bound, extra)| bound + extra)
+        .collect();
+    offset.push(rest >> (LIMB_BITS as usize * top));
+
+    let offset = Element {
+        limbs: offset
+            .into_iter()
+            .map(|limb| Limb {
+                node: emitter.constant(&limb),
+                bound: limb,
+            })
+            .collect(),
+    };
+    let mut difference = add(emitter, a, &offset);
+    for (limb, subtrahend) in difference.limbs.iter_mut().zip(&b.limbs) {
+        limb.node = emitter.binary(IRNodeType::Sub, limb.node, subtrahend.node);
+    }
+    difference
+}
+
+/// `a * b`, loosely reduced. Inputs are reduced first if their product's columns, with
+/// the carries reduction adds to them, could reach the field's capacity.
+pub(crate) fn mul(emitter: &mut Emitter, a: &Element, b: &Element, modulus: &Modulus) -> Element {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    if column_bound(&a, &b).bits() + 2 > field::active().capacity() as u64 {
+        if !a.is_loose() {
+            a = reduce(emitter, a.limbs, modulus);
+        }
+        if !b.is_loose() {
+            b = reduce(emitter, b.limbs, modulus);
+        }
+    }
+
+    let mut columns: Vec<Option<Limb>> = vec![None; a.limbs.len() + b.limbs.len() - 1];
+    for (i, x) in a.limbs.iter().enumerate() {
+        for (j, y) in b.limbs.iter().enumerate() {
+            let product = Limb {
+                node: emitter.binary(IRNodeType::Mul, x.node, y.node),
+                bound: &x.bound * &y.bound,
+            };
+            columns[i + j] = Some(accumulate(emitter, columns[i + j].take(), product));
+        }
+    }
+    reduce(emitter, columns.into_iter().flatten().collect(), modulus)
+}
+
+fn column_bound(a: &Element, b: &Element) -> BigUint {
+    let mut columns = vec![BigUint::zero(); a.limbs.len() + b.limbs.len() - 1];
+    for (i, x) in a.limbs.iter().enumerate() {
+        for (j, y) in b.limbs.iter().enumerate() {
+            columns[i + j] += &x.bound * &y.bound;
+        }
+    }
+    columns.into_iter().max().unwrap_or_default()
+}
+
+pub(crate) fn accumulate(emitter: &mut Emitter, sum: Option<Limb>, term: Limb) -> Limb {
+    match sum {
+        Some(sum) => Limb {
+            node: emitter.binary(IRNodeType::Add, sum.node, term.node),
+            bound: sum.bound + term.bound,
+        },
+        None => term,
+    }
+}
+
+/// `base^exponent` for a constant exponent, by square-and-multiply from the top bit
+pub(crate) fn pow(emitter: &mut Emitter, base: &Element, exponent: &BigUint, modulus: &Modulus) -> Element {
+    let mut result: Option<Element> = None;
+    for index in (0..exponent.bits()).rev() {
+        if let Some(value) = result.take() {
+            result = Some(mul(emitter, &value, &value, modulus));
+        }
+        if exponent.bit(index) {
+            result = Some(match result {
+                Some(value) => mul(emitter, &value, base, modulus),
+                None => base.clone(),
+            });
+        }
+    }
+    result.unwrap_or_else(|| constant(emitter, &BigUint::one()))
+}
+
+/// `condition ? a : b` limb by limb, for a boolean `condition` node
+pub(crate) fn select(emitter: &mut Emitter, condition: usize, a: &Element, b: &Element) -> Element {
+    let limbs = a
+        .limbs
+        .iter()
+        .zip(&b.limbs)
+        .map(|(a, b)| Limb {
+            node: emitter.select(condition, a.node, b.node),
+            bound: a.bound.clone().max(b.bound.clone()),
+        })
+        .collect();
+    Element { limbs }
+}
+
+/// Carry `columns` into a loosely reduced element congruent to them
+pub(crate) fn reduce(emitter: &mut Emitter, mut columns: Vec<Limb>, modulus: &Modulus) -> Element {
+    loop {
+        let mut limbs = normalize(emitter, columns);
+        while limbs.len() < LIMBS {
+            limbs.push(Limb {
+                node: emitter.constant(&BigUint::zero()),
+                bound: BigUint::zero(),
+            });
+        }
+        if limbs.len() == LIMBS {
+            return Element { limbs };
+        }
+
+        let high = limbs.split_off(LIMBS);
+        let high_bound: BigUint = high
+            .iter()
+            .enumerate()
+            .map(|(index, limb)| &limb.bound << (LIMB_BITS as usize * index))
+            .sum();
+        // At most one unit above 2^256 fits in the top limb's extra bit
+        if high_bound <= BigUint::one() {
+            let top = limbs.pop().expect("four limbs");
+            let weight = emitter.constant(&(BigUint::one() << LIMB_BITS));
+            let carried = emitter.binary(IRNodeType::Mul, high[0].node, weight);
+            limbs.push(Limb {
+                node: emitter.binary(IRNodeType::Add, top.node, carried),
+                bound: top.bound + (&high[0].bound << LIMB_BITS),
+            });
+            return Element { limbs };
+        }
+
+        let mut folded: Vec<Option<Limb>> = limbs.into_iter().map(Some).collect();
+        folded.resize(LIMBS.max(high.len() + modulus.fold.len() - 1), None);
+        for (i, limb) in high.iter().enumerate() {
+            for (j, factor) in modulus.fold.iter().enumerate() {
+                let factor_node = emitter.constant(factor);
+                let term = Limb {
+                    node: emitter.binary(IRNodeType::Mul, limb.node, factor_node),
+                    bound: &limb.bound * factor,
+                };
+                folded[i + j] = Some(accumulate(emitter, folded[i + j].take(), term));
+            }
+        }
+        columns = folded
+            .into_iter()
+            .map(|column| {
+                column.unwrap_or_else(|| Limb {
+                    node: emitter.constant(&BigUint::zero()),
+                    bound: BigUint::zero(),
+                })
+            })
+            .collect();
+    }
+}
+
+/// Carry `columns` into limbs below 2^64, decomposing only the columns that can exceed it
+pub(crate) fn normalize(emitter: &mut Emitter, columns: Vec<Limb>) -> Vec<Limb> {
+    let mut limbs = Vec::with_capacity(columns.len() + 1);
+    let mut columns = columns.into_iter();
+    let mut carry: Option<Limb> = None;
+    loop {
+        let column = match (columns.next(), carry.take()) {
+            (Some(column), carry) => match carry {
+                Some(carry) => accumulate(emitter, Some(column), carry),
+                None => column,
+            },
+            (None, Some(carry)) => carry,
+            (None, None) => return limbs,
+        };
+        if column.bound.bits() <= LIMB_BITS as u64 {
+            limbs.push(column);
+            continue;
+        }
+        let bits = emitter.unpack(column.node, column.bound.bits() as u32);
+        let (low, high) = bits.split_at(LIMB_BITS as usize);
+        limbs.push(Limb {
+            node: emitter.pack(low),
+            bound: limb_mask(),
+        });
+        carry = Some(Limb {
+            node: emitter.pack(high),
+            bound: column.bound >> LIMB_BITS,
+        });
+    }
+}
+
+/// Bits of the integer `value` stands for, least significant first, after reducing it
+/// loosely: 257 bits, constrained to recompose the limbs
+pub(crate) fn bits(emitter: &mut Emitter, value: &Element, modulus: &Modulus) -> Vec<usize> {
+    let value = loose(emitter, value, modulus);
+    let mut result = Vec::with_capacity(LIMBS * LIMB_BITS as usize + 1);
+    for (index, limb) in value.limbs.iter().enumerate() {
+        let width = if index + 1 == LIMBS { LIMB_BITS + 1 } else { LIMB_BITS };
+        result.extend(emitter.unpack(limb.node, width).into_iter().map(|bit| match bit {
+            Bit::Node(node) => node,
+            Bit::Constant(_) => unreachable!("unpacked bits are nodes"),
+        }));
+    }
+    result
+}
+
+/// Boolean node: whether `value` is 0 modulo the emulated prime. A loosely reduced
+/// value below 2^257 that is can only be 0, m or 2m, each with one set of limbs.
+pub(crate) fn is_zero(emitter: &mut Emitter, value: &Element, modulus: &Modulus) -> usize {
+    let value = loose(emitter, value, modulus);
+    let mut any = None;
+    for multiple in 0..3u32 {
+        let mut all = None;
+        for (limb, expected) in value.limbs.iter().zip(limbs_of(&(&modulus.value * multiple), LIMBS)) {
+            let expected = emitter.constant(&expected);
+            let equal = emitter.binary(IRNodeType::Eq, limb.node, expected);
+            all = Some(match all {
+                Some(all) => emitter.binary(IRNodeType::And, all, equal),
+                None => equal,
+            });
+        }
+        let all = all.expect("elements have limbs");
+        any = Some(match any {
+            Some(any) => emitter.binary(IRNodeType::Or, any, all),
+            None => all,
+        });
+    }
+    any.expect("three multiples")
+}
+
+/// Boolean node: whether `value`, whose limbs must each be below 2^64, is below the
+/// constant `bound`, compared lexicographically from the top limb down
+pub(crate) fn less_than_constant(emitter: &mut Emitter, value: &Element, bound: &BigUint) -> usize {
+    let mut less: Option<usize> = None;
+    for (limb, expected) in value.limbs.iter().zip(limbs_of(bound, value.limbs.len())) {
+        let expected = emitter.constant(&expected);
+        let below = emitter.less_than(limb.node, expected, LIMB_BITS);
+        // Lower limbs decide only when every limb above them is equal
+        less = Some(match less {
+            Some(lower) => {
+                let equal = emitter.binary(IRNodeType::Eq, limb.node, expected);
+                let tied = emitter.binary(IRNodeType::And, equal, lower);
+                emitter.binary(IRNodeType::Or, below, tied)
+            }
+            None => below,
+        });
+    }
+    less.expect("elements have limbs")
+}
+
+fn loose(emitter: &mut Emitter, value: &Element, modulus: &Modulus) -> Element {
+    if value.is_loose() {
+        value.clone()
+    } else {
+        reduce(emitter, value.limbs.clone(), modulus)
+    }
+}
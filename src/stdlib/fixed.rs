@@ -0,0 +1,193 @@
+//! Signed fixed-point arithmetic for quantized models.
+//!
+//! In source, `Fixed<I, F>` is a `Field` holding the signed integer `v` for the real
+//! number `v / 2^F`, with `-v` stored as `p - v`. It has `I + F` bits in two's-complement
+//! terms, `I` of them integer bits including the sign, so `v` lies in
+//! `[-2^(I+F-1), 2^(I+F-1))`. The type parameters do not travel with the value, so every
+//! `std::fixed` function takes them as two trailing constants.
+//!
+//! A value is range checked by adding `2^(I+F-1)` and checking the sum below `2^(I+F)`,
+//! which is also how results are checked for overflow. Products are truncated toward
+//! negative infinity: the double-width product is offset to be nonnegative and
+//! decomposed, and its low `F` bits are dropped, so the truncation is constrained exactly
+//! rather than trusted.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field;
+use crate::stdlib::gadget::{Bit, Emitter};
+use crate::FCMCError;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::One;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes this module emits
+pub const FIXED_GADGET: &str = "fixed";
+
+/// The parameters of `Fixed<I, F>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub int_bits: u32,
+    pub frac_bits: u32,
+}
+
+impl Format {
+    /// Fail unless products of the format fit in the active field without wrapping
+    pub fn new(int_bits: u32, frac_bits: u32) -> Result<Self, FCMCError> {
+        let format = Self { int_bits, frac_bits };
+        let capacity = field::active().capacity();
+        if int_bits == 0 || 2 * format.width() > capacity {
+            return Err(FCMCError::SemanticError(format!(
+                "Fixed<{}, {}> needs at least one integer bit and at most {} bits in all in the {} field",
+                int_bits,
+                frac_bits,
+                capacity / 2,
+                field::active()
+            )));
+        }
+        Ok(format)
+    }
+
+    /// Bits of the underlying integer, sign included
+    pub fn width(&self) -> u32 {
+        self.int_bits + self.frac_bits
+    }
+
+    /// `2^(width - 1)`, which makes every value of the format nonnegative
+    fn offset(&self) -> BigUint {
+        BigUint::one() << (self.width() - 1)
+    }
+
+    /// The signed integer the field element `value` holds, failing if it is out of range
+    pub fn decode(&self, value: &BigUint) -> Result<BigInt, FCMCError> {
+        let shifted = field::add(value, &self.offset());
+        if shifted.bits() > self.width() as u64 {
+            return Err(FCMCError::VerificationError(format!(
+                "{} is out of range for {}",
+                value, self
+            )));
+        }
+        Ok(BigInt::from(shifted) - BigInt::from(self.offset()))
+    }
+
+    /// The field element holding the signed integer `value`, failing on overflow
+    pub fn encode(&self, value: &BigInt) -> Result<BigUint, FCMCError> {
+        let shifted = value + BigInt::from(self.offset());
+        match shifted.to_biguint() {
+            Some(shifted) if shifted.bits() <= self.width() as u64 => Ok(field::sub(&shifted, &self.offset())),
+            _ => Err(FCMCError::VerificationError(format!("{} overflows {}", value, self))),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Fixed<{}, {}>", self.int_bits, self.frac_bits)
+    }
+}
+
+/// `a + b`, failing on overflow of the sum; the operands themselves are not range checked
+pub fn add(format: &Format, a: &BigUint, b: &BigUint) -> Result<BigUint, FCMCError> {
+    format.decode(&field::add(a, b))?;
+    Ok(field::add(a, b))
+}
+
+/// `a - b`, failing on overflow of the difference
+pub fn sub(format: &Format, a: &BigUint, b: &BigUint) -> Result<BigUint, FCMCError> {
+    format.decode(&field::sub(a, b))?;
+    Ok(field::sub(a, b))
+}
+
+/// `a * b` truncated toward negative infinity, failing for operands out of range or on
+/// overflow of the result
+pub fn mul(format: &Format, a: &BigUint, b: &BigUint) -> Result<BigUint, FCMCError> {
+    let product = format.decode(a)? * format.decode(b)?;
+    format.encode(&(product >> format.frac_bits))
+}
+
+/// `max(x, 0)`, failing for `x` out of range
+pub fn relu(format: &Format, x: &BigUint) -> Result<BigUint, FCMCError> {
+    let value = format.decode(x)?;
+    Ok(if value.sign() == Sign::Minus {
+        BigUint::default()
+    } else {
+        x.clone()
+    })
+}
+
+/// Whether `a < b` as signed numbers, failing for operands out of range
+pub fn less_than(format: &Format, a: &BigUint, b: &BigUint) -> Result<bool, FCMCError> {
+    Ok(format.decode(a)? < format.decode(b)?)
+}
+
+pub fn lower_add(graph: &mut IRGraph, format: &Format, a: usize, b: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    let sum = emitter.binary(IRNodeType::Add, a, b);
+    checked(&mut emitter, format, sum);
+    emitter.finish(FIXED_GADGET);
+    sum
+}
+
+pub fn lower_sub(graph: &mut IRGraph, format: &Format, a: usize, b: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    let difference = emitter.binary(IRNodeType::Sub, a, b);
+    checked(&mut emitter, format, difference);
+    emitter.finish(FIXED_GADGET);
+    difference
+}
+
+/// Emit the truncated product into `graph`. The operands are range checked, so the
+/// product plus `2^(2 width - 2)` lies in `[0, 2^(2 width - 1)]` and decomposes into
+/// `2 width` bits; dropping the low `F` and removing the offset again leaves the product
+/// shifted right with its sign, which is then checked for overflow.
+pub fn lower_mul(graph: &mut IRGraph, format: &Format, a: usize, b: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    checked(&mut emitter, format, a);
+    checked(&mut emitter, format, b);
+    let width = format.width();
+    let product = emitter.binary(IRNodeType::Mul, a, b);
+    let offset = emitter.constant(&(BigUint::one() << (2 * width - 2)));
+    let shifted = emitter.binary(IRNodeType::Add, product, offset);
+    let bits = emitter.unpack(shifted, 2 * width);
+    let truncated = emitter.pack(&bits[format.frac_bits as usize..]);
+    let offset = emitter.constant(&(BigUint::one() << (2 * width - 2 - format.frac_bits)));
+    let result = emitter.binary(IRNodeType::Sub, truncated, offset);
+    checked(&mut emitter, format, result);
+    emitter.finish(FIXED_GADGET);
+    result
+}
+
+/// Emit `max(x, 0)` into `graph`: the top bit of the offset value is set exactly when
+/// `x` is nonnegative, and the decomposition that yields it also range checks `x`
+pub fn lower_relu(graph: &mut IRGraph, format: &Format, x: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    let shifted = offset(&mut emitter, format, x);
+    let bits = emitter.unpack(shifted, format.width());
+    let Some(&Bit::Node(nonnegative)) = bits.last() else {
+        unreachable!("unpacked bits are nodes")
+    };
+    let result = emitter.binary(IRNodeType::Mul, nonnegative, x);
+    emitter.finish(FIXED_GADGET);
+    result
+}
+
+/// Emit whether `a < b` as signed numbers into `graph`, by comparing the offset values
+pub fn lower_less_than(graph: &mut IRGraph, format: &Format, a: usize, b: usize) -> usize {
+    let mut emitter = Emitter::new(graph);
+    let a = checked(&mut emitter, format, a);
+    let b = checked(&mut emitter, format, b);
+    let less = emitter.less_than(a, b, format.width());
+    emitter.finish(FIXED_GADGET);
+    less
+}
+
+/// `value + 2^(width - 1)`
+fn offset(emitter: &mut Emitter, format: &Format, value: usize) -> usize {
+    let offset = emitter.constant(&format.offset());
+    emitter.binary(IRNodeType::Add, value, offset)
+}
+
+/// Range check `value` to the format, returning its offset node
+fn checked(emitter: &mut Emitter, format: &Format, value: usize) -> usize {
+    let shifted = offset(emitter, format, value);
+    emitter.assert_range(shifted, format.width());
+    shifted
+}
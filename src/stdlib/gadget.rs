@@ -0,0 +1,274 @@
+//! Graph building shared by the gadgets: field arithmetic, and bit-level logic over
+//! words of boolean wires.
+//!
+//! Bits are extracted with `BitDecomposition` nodes carrying `BIT_ATTRIBUTE`, and every
+//! decomposition is tied back to its value by an equality constraint on the recomposed
+//! bits, which also range checks the value. Logic on bits uses `And`, `Xor` and `Not`,
+//! so on targets with lookups the lookup-conversion pass can fold it into tables over
+//! the bits; elsewhere it stays one multiplication per gate.
+
+use crate::ir::{ConstraintType, EdgeType, IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::interpreter::BIT_ATTRIBUTE;
+use crate::optimization::subcircuit::GADGET_ATTRIBUTE;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+
+/// A boolean wire, or a bit known at compile time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bit {
+    Constant(bool),
+    Node(usize),
+}
+
+/// Adds a gadget's nodes to a graph, remembering them so they can be tagged together
+pub(crate) struct Emitter<'a> {
+    graph: &'a mut IRGraph,
+    emitted: Vec<usize>,
+}
+
+impl<'a> Emitter<'a> {
+    pub(crate) fn new(graph: &'a mut IRGraph) -> Self {
+        Self {
+            graph,
+            emitted: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, node_type: IRNodeType, data_type: Type, operands: &[usize], edge: EdgeType) -> usize {
+        let id = self.graph.add_node(node_type, data_type, None);
+        for &operand in operands {
+            self.graph.add_edge(operand, id, edge.clone());
+        }
+        self.emitted.push(id);
+        id
+    }
+
+    pub(crate) fn constant(&mut self, value: &BigUint) -> usize {
+        self.add(
+            IRNodeType::Constant(value.to_string()),
+            Type::Field,
+            &[],
+            EdgeType::DataFlow,
+        )
+    }
+
+    pub(crate) fn binary(&mut self, op: IRNodeType, left: usize, right: usize) -> usize {
+        self.add(op, Type::Field, &[left, right], EdgeType::DataFlow)
+    }
+
+    /// `condition ? a : b`, for a boolean `condition`
+    pub(crate) fn select(&mut self, condition: usize, a: usize, b: usize) -> usize {
+        self.add(IRNodeType::Select, Type::Field, &[condition, a, b], EdgeType::DataFlow)
+    }
+
+    /// Constrain `value` to be 0 or 1
+    pub(crate) fn assert_boolean(&mut self, value: usize) {
+        let square = self.binary(IRNodeType::Mul, value, value);
+        self.assert_equal(square, value);
+    }
+
+    /// Constrain `value` below `2^bits`
+    pub(crate) fn assert_range(&mut self, value: usize, bits: u32) {
+        self.add(
+            IRNodeType::Constraint(ConstraintType::Range { bits }),
+            Type::Bool,
+            &[value],
+            EdgeType::Constraint,
+        );
+    }
+
+    /// Whether `a < b`, for operands below `2^bits`
+    pub(crate) fn less_than(&mut self, a: usize, b: usize, bits: u32) -> usize {
+        let less = self.add(IRNodeType::Lt, Type::Bool, &[a, b], EdgeType::DataFlow);
+        if let Some(node) = self.graph.get_node_mut(less) {
+            node.attributes.insert("bits".to_string(), bits.to_string());
+        }
+        less
+    }
+
+    /// Constrain `left` and `right` to be equal
+    pub(crate) fn assert_equal(&mut self, left: usize, right: usize) {
+        self.add(
+            IRNodeType::Constraint(ConstraintType::Equality),
+            Type::Bool,
+            &[left, right],
+            EdgeType::Constraint,
+        );
+    }
+
+    /// The graph, for emitting another gadget's nodes, which this emitter does not tag
+    pub(crate) fn graph(&mut self) -> &mut IRGraph {
+        self.graph
+    }
+
+    /// Tag every emitted node as part of `gadget`
+    pub(crate) fn finish(self, gadget: &str) {
+        for node_id in self.emitted {
+            if let Some(node) = self.graph.get_node_mut(node_id) {
+                node.attributes.insert(GADGET_ATTRIBUTE.to_string(), gadget.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Constant(a), Bit::Constant(b)) => Bit::Constant(a ^ b),
+            (Bit::Constant(false), bit) | (bit, Bit::Constant(false)) => bit,
+            (Bit::Constant(true), bit) | (bit, Bit::Constant(true)) => self.not(bit),
+            (Bit::Node(a), Bit::Node(b)) => {
+                Bit::Node(self.add(IRNodeType::Xor, Type::Bool, &[a, b], EdgeType::DataFlow))
+            }
+        }
+    }
+
+    pub(crate) fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Constant(a), Bit::Constant(b)) => Bit::Constant(a & b),
+            (Bit::Constant(false), _) | (_, Bit::Constant(false)) => Bit::Constant(false),
+            (Bit::Constant(true), bit) | (bit, Bit::Constant(true)) => bit,
+            (Bit::Node(a), Bit::Node(b)) => {
+                Bit::Node(self.add(IRNodeType::And, Type::Bool, &[a, b], EdgeType::DataFlow))
+            }
+        }
+    }
+
+    pub(crate) fn not(&mut self, a: Bit) -> Bit {
+        match a {
+            Bit::Constant(value) => Bit::Constant(!value),
+            Bit::Node(a) => Bit::Node(self.add(IRNodeType::Not, Type::Bool, &[a], EdgeType::DataFlow)),
+        }
+    }
+
+    pub(crate) fn xor_words(&mut self, a: &[Bit], b: &[Bit]) -> Vec<Bit> {
+        a.iter().zip(b).map(|(&a, &b)| self.xor(a, b)).collect()
+    }
+
+    /// Node holding `sum_i bits[i] * 2^i`
+    pub(crate) fn pack(&mut self, bits: &[Bit]) -> usize {
+        let mut constant = BigUint::zero();
+        let mut sum = None;
+        for (index, &bit) in bits.iter().enumerate() {
+            match bit {
+                Bit::Constant(true) => constant += BigUint::one() << index,
+                Bit::Constant(false) => {}
+                Bit::Node(node) => {
+                    let term = if index == 0 {
+                        node
+                    } else {
+                        let weight = self.constant(&(BigUint::one() << index));
+                        self.binary(IRNodeType::Mul, node, weight)
+                    };
+                    sum = Some(match sum {
+                        Some(sum) => self.binary(IRNodeType::Add, sum, term),
+                        None => term,
+                    });
+                }
+            }
+        }
+        match sum {
+            Some(sum) if constant.is_zero() => sum,
+            Some(sum) => {
+                let constant = self.constant(&constant);
+                self.binary(IRNodeType::Add, sum, constant)
+            }
+            None => self.constant(&constant),
+        }
+    }
+
+    /// The low `bits` bits of `value`, least significant first, constrained to recompose it;
+    /// so `value` is also constrained below `2^bits`
+    pub(crate) fn unpack(&mut self, value: usize, bits: u32) -> Vec<Bit> {
+        let result: Vec<Bit> = (0..bits)
+            .map(|index| {
+                let bit = self.add(IRNodeType::BitDecomposition, Type::Bool, &[value], EdgeType::DataFlow);
+                if let Some(node) = self.graph.get_node_mut(bit) {
+                    node.attributes.insert(BIT_ATTRIBUTE.to_string(), index.to_string());
+                }
+                Bit::Node(bit)
+            })
+            .collect();
+        let recomposed = self.pack(&result);
+        self.assert_equal(recomposed, value);
+        result
+    }
+
+    /// `sum(words) + constant` modulo `2^width`, where every word has `width` bits
+    pub(crate) fn add_words(&mut self, words: &[&[Bit]], constant: u64, width: u32) -> Vec<Bit> {
+        let mut maximum = BigUint::from(constant);
+        let mut sum = self.constant(&BigUint::from(constant));
+        for word in words {
+            maximum += (BigUint::one() << width) - 1u32;
+            let packed = self.pack(word);
+            sum = self.binary(IRNodeType::Add, sum, packed);
+        }
+        let mut bits = self.unpack(sum, maximum.bits().max(width as u64) as u32);
+        bits.truncate(width as usize);
+        bits
+    }
+}
+
+/// Bits of `value`, least significant first
+pub(crate) fn constant_word(value: u64, width: u32) -> Vec<Bit> {
+    (0..width)
+        .map(|index| Bit::Constant((value >> index) & 1 == 1))
+        .collect()
+}
+
+/// `word` rotated right by `amount`
+pub(crate) fn rotate_right(word: &[Bit], amount: usize) -> Vec<Bit> {
+    (0..word.len())
+        .map(|index| word[(index + amount) % word.len()])
+        .collect()
+}
+
+/// `word` rotated left by `amount`
+pub(crate) fn rotate_left(word: &[Bit], amount: usize) -> Vec<Bit> {
+    rotate_right(word, word.len() - amount % word.len())
+}
+
+/// `word` shifted right by `amount`, filling with zeros
+pub(crate) fn shift_right(word: &[Bit], amount: usize) -> Vec<Bit> {
+    (0..word.len())
+        .map(|index| word.get(index + amount).copied().unwrap_or(Bit::Constant(false)))
+        .collect()
+}
+
+/// Running byte gadgets through the reference interpreter
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::optimization::interpreter;
+    use crate::FCMCError;
+    use num_traits::ToPrimitive;
+    use std::collections::HashMap;
+
+    /// The bytes `lower` computes from `message`, checking every constraint it emits holds
+    pub(crate) fn evaluate_bytes(
+        lower: fn(&mut IRGraph, &[usize]) -> Result<Vec<usize>, FCMCError>,
+        message: &[u8],
+    ) -> Vec<u8> {
+        let mut graph = IRGraph::new();
+        let bytes: Vec<usize> = (0..message.len())
+            .map(|i| graph.add_node(IRNodeType::PrivateInput(format!("m{}", i)), Type::Field, None))
+            .collect();
+        let digest = lower(&mut graph, &bytes).unwrap();
+        for (i, &byte) in digest.iter().enumerate() {
+            let out = graph.add_node(IRNodeType::Output(format!("d{}", i)), Type::Field, None);
+            graph.add_edge(byte, out, EdgeType::DataFlow);
+        }
+
+        let inputs: HashMap<String, BigUint> = message
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| (format!("m{}", i), BigUint::from(byte)))
+            .collect();
+        let evaluation = interpreter::evaluate(&graph, &inputs).unwrap();
+        assert!(evaluation.is_satisfied(), "violated: {:?}", evaluation.violated);
+        evaluation
+            .outputs
+            .iter()
+            .map(|(_, value)| value.to_u8().expect("digest byte"))
+            .collect()
+    }
+}
@@ -0,0 +1,230 @@
+//! Keccak-256 over byte arrays, as Ethereum uses it: the original Keccak padding
+//! (`0x01 .. 0x80`), not SHA3-256's.
+//!
+//! As with SHA-256, the padding is constant and each message byte is decomposed into bits.
+//! The permutation works on 25 lanes of 64 bits; rotations are free, so every gate is an
+//! `Xor`, `And` or `Not` of two lane bits.
+
+use crate::ir::IRGraph;
+use crate::stdlib::gadget::{constant_word, rotate_left, Bit, Emitter};
+use crate::FCMCError;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_keccak256` emits
+pub const KECCAK256_GADGET: &str = "keccak256";
+
+/// Message bytes absorbed per permutation
+pub const KECCAK256_RATE_BYTES: usize = 136;
+
+const ROUNDS: usize = 24;
+
+const ROUND_CONSTANTS: [u64; ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Left rotation of lane `x + 5y`
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Bytes in a `message_bytes`-byte message padded to whole blocks
+fn padded(message_bytes: usize) -> usize {
+    (message_bytes + 1).div_ceil(KECCAK256_RATE_BYTES) * KECCAK256_RATE_BYTES
+}
+
+fn padding_byte(message_bytes: usize, index: usize) -> u8 {
+    let first = if index == message_bytes { 0x01 } else { 0 };
+    let last = if index == padded(message_bytes) - 1 { 0x80 } else { 0 };
+    first | last
+}
+
+/// Where `pi` moves lane `x + 5y`
+fn pi(lane: usize) -> usize {
+    let (x, y) = (lane % 5, lane / 5);
+    y + 5 * ((2 * x + 3 * y) % 5)
+}
+
+fn permute(lanes: &mut [u64; 25]) {
+    for constant in ROUND_CONSTANTS {
+        let columns: Vec<u64> = (0..5)
+            .map(|x| (0..5).fold(0, |acc, y| acc ^ lanes[x + 5 * y]))
+            .collect();
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            let x = lane % 5;
+            *value ^= columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+        }
+        let mut moved = [0u64; 25];
+        for (lane, value) in lanes.iter().enumerate() {
+            moved[pi(lane)] = value.rotate_left(ROTATIONS[lane]);
+        }
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            let (x, row) = (lane % 5, lane - lane % 5);
+            *value = moved[lane] ^ (!moved[row + (x + 1) % 5] & moved[row + (x + 2) % 5]);
+        }
+        lanes[0] ^= constant;
+    }
+}
+
+/// The Keccak-256 digest of `message`
+pub fn keccak256(message: &[u8]) -> [u8; 32] {
+    let mut lanes = [0u64; 25];
+    let bytes: Vec<u8> = (0..padded(message.len()))
+        .map(|index| {
+            message
+                .get(index)
+                .copied()
+                .unwrap_or_else(|| padding_byte(message.len(), index))
+        })
+        .collect();
+    for block in bytes.chunks(KECCAK256_RATE_BYTES) {
+        for (lane, chunk) in lanes.iter_mut().zip(block.chunks(8)) {
+            let mut value = [0u8; 8];
+            value.copy_from_slice(chunk);
+            *lane ^= u64::from_le_bytes(value);
+        }
+        permute(&mut lanes);
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, lane) in digest.chunks_mut(8).zip(lanes) {
+        chunk.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+/// Emit the Keccak-256 digest of the byte nodes `message` into `graph`, returning the 32
+/// digest byte nodes. Every message byte is constrained below 256.
+pub fn lower_keccak256(graph: &mut IRGraph, message: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    let mut emitter = Emitter::new(graph);
+    // Message bits, least significant first within each byte and lane
+    let mut bits: Vec<Bit> = Vec::with_capacity(padded(message.len()) * 8);
+    for index in 0..padded(message.len()) {
+        match message.get(index) {
+            Some(&byte) => bits.extend(emitter.unpack(byte, 8)),
+            None => bits.extend(constant_word(padding_byte(message.len(), index) as u64, 8)),
+        }
+    }
+
+    let mut lanes: Vec<Vec<Bit>> = vec![constant_word(0, 64); 25];
+    for block in bits.chunks(KECCAK256_RATE_BYTES * 8) {
+        for (lane, chunk) in lanes.iter_mut().zip(block.chunks(64)) {
+            *lane = emitter.xor_words(lane, chunk);
+        }
+        lanes = permute_bits(&mut emitter, lanes);
+    }
+
+    let mut digest = Vec::with_capacity(32);
+    for lane in &lanes[..4] {
+        for byte in lane.chunks(8) {
+            digest.push(emitter.pack(byte));
+        }
+    }
+    emitter.finish(KECCAK256_GADGET);
+    Ok(digest)
+}
+
+fn permute_bits(emitter: &mut Emitter, mut lanes: Vec<Vec<Bit>>) -> Vec<Vec<Bit>> {
+    for constant in ROUND_CONSTANTS {
+        // theta
+        let columns: Vec<Vec<Bit>> = (0..5)
+            .map(|x| {
+                let mut column = lanes[x].clone();
+                for y in 1..5 {
+                    column = emitter.xor_words(&column, &lanes[x + 5 * y]);
+                }
+                column
+            })
+            .collect();
+        let effects: Vec<Vec<Bit>> = (0..5)
+            .map(|x| emitter.xor_words(&columns[(x + 4) % 5], &rotate_left(&columns[(x + 1) % 5], 1)))
+            .collect();
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            *value = emitter.xor_words(value, &effects[lane % 5]);
+        }
+
+        // rho and pi
+        let mut moved = vec![Vec::new(); 25];
+        for (lane, value) in lanes.iter().enumerate() {
+            moved[pi(lane)] = rotate_left(value, ROTATIONS[lane] as usize);
+        }
+
+        // chi
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            let (x, row) = (lane % 5, lane - lane % 5);
+            let (next, after) = (&moved[row + (x + 1) % 5], &moved[row + (x + 2) % 5]);
+            *value = (0..64)
+                .map(|i| {
+                    let cleared = emitter.not(next[i]);
+                    let masked = emitter.and(cleared, after[i]);
+                    emitter.xor(moved[lane][i], masked)
+                })
+                .collect();
+        }
+
+        // iota
+        lanes[0] = emitter.xor_words(&lanes[0], &constant_word(constant, 64));
+    }
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::gadget::testing::evaluate_bytes;
+
+    /// Messages and their Keccak-256 digests
+    const VECTORS: &[(&str, &str)] = &[
+        ("", "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"),
+        (
+            "abc",
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+        ),
+    ];
+
+    #[test]
+    fn matches_reference() {
+        for (message, expected) in VECTORS {
+            assert_eq!(
+                hex::encode(keccak256(message.as_bytes())),
+                *expected,
+                "keccak256({:?})",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn gadget_matches_native() {
+        for (message, _) in VECTORS {
+            let message = message.as_bytes();
+            assert_eq!(
+                evaluate_bytes(lower_keccak256, message),
+                keccak256(message),
+                "keccak256({:?})",
+                message
+            );
+        }
+    }
+}
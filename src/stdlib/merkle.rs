@@ -0,0 +1,106 @@
+//! Merkle tree membership: whether a leaf and the sibling hashes along its path hash up
+//! to a given root.
+//!
+//! The path runs from the leaf upwards. At each level the index bit says which side the
+//! running node is on: `false` hashes it as the left child, `true` as the right. The tree
+//! is built over any two-to-one `MerkleHash`; `std::merkle::verify` uses Poseidon.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::stdlib::gadget::Emitter;
+use crate::stdlib::poseidon::{self, PoseidonParams};
+use crate::FCMCError;
+use num_bigint::BigUint;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_verify` emits besides the hashes'
+pub const MERKLE_GADGET: &str = "merkle";
+
+/// A two-to-one hash a Merkle tree can be built over
+pub trait MerkleHash {
+    /// Hash of the children `left` and `right`
+    fn hash(&self, left: &BigUint, right: &BigUint) -> Result<BigUint, FCMCError>;
+
+    /// Emit the hash of the nodes `left` and `right` into `graph`, returning the node holding it
+    fn lower(&self, graph: &mut IRGraph, left: usize, right: usize) -> Result<usize, FCMCError>;
+}
+
+/// Poseidon with two inputs, from `PoseidonParams::for_inputs(2)`
+impl MerkleHash for PoseidonParams {
+    fn hash(&self, left: &BigUint, right: &BigUint) -> Result<BigUint, FCMCError> {
+        PoseidonParams::hash(self, &[left.clone(), right.clone()])
+    }
+
+    fn lower(&self, graph: &mut IRGraph, left: usize, right: usize) -> Result<usize, FCMCError> {
+        poseidon::lower_poseidon(graph, self, &[left, right])
+    }
+}
+
+/// The root `leaf` hashes up to along `path`, with `indices` giving its side at each level
+pub fn root<H: MerkleHash>(hash: &H, leaf: &BigUint, path: &[BigUint], indices: &[bool]) -> Result<BigUint, FCMCError> {
+    check_depth(path.len(), indices.len())?;
+    let mut node = leaf.clone();
+    for (sibling, &right) in path.iter().zip(indices) {
+        node = if right {
+            hash.hash(sibling, &node)?
+        } else {
+            hash.hash(&node, sibling)?
+        };
+    }
+    Ok(node)
+}
+
+/// Whether `leaf` hashes up to `expected` along `path` and `indices`
+pub fn verify<H: MerkleHash>(
+    hash: &H,
+    leaf: &BigUint,
+    path: &[BigUint],
+    indices: &[bool],
+    expected: &BigUint,
+) -> Result<bool, FCMCError> {
+    Ok(root(hash, leaf, path, indices)? == *expected)
+}
+
+/// Emit whether the node `leaf` hashes up to the node `expected` along `path` and `indices`
+/// into `graph`, returning the boolean node holding it. Every index is constrained boolean.
+pub fn lower_verify<H: MerkleHash>(
+    graph: &mut IRGraph,
+    hash: &H,
+    leaf: usize,
+    path: &[usize],
+    indices: &[usize],
+    expected: usize,
+) -> Result<usize, FCMCError> {
+    check_depth(path.len(), indices.len())?;
+    let mut emitter = Emitter::new(graph);
+    let mut node = leaf;
+    for (&sibling, &right) in path.iter().zip(indices) {
+        emitter.assert_boolean(right);
+        let left_child = emitter.select(right, sibling, node);
+        let right_child = emitter.select(right, node, sibling);
+        node = hash.lower(emitter.graph(), left_child, right_child)?;
+    }
+    let verified = emitter.binary(IRNodeType::Eq, node, expected);
+    emitter.finish(MERKLE_GADGET);
+    Ok(verified)
+}
+
+/// The tree depth of a `std::merkle::verify` call on `arguments` flattened elements:
+/// the leaf, `depth` siblings, `depth` indices and the root
+pub fn depth_of(arguments: usize) -> Result<usize, FCMCError> {
+    if arguments < 2 || arguments % 2 != 0 {
+        return Err(FCMCError::SemanticError(format!(
+            "Merkle verification takes a leaf, a path and indices of one depth, and a root, not {} elements",
+            arguments
+        )));
+    }
+    Ok((arguments - 2) / 2)
+}
+
+fn check_depth(path: usize, indices: usize) -> Result<(), FCMCError> {
+    if path != indices {
+        return Err(FCMCError::SemanticError(format!(
+            "Merkle path has {} siblings but {} indices",
+            path, indices
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,430 @@
+//! Standard library gadgets, called from source by their `std::` path.
+//!
+//! Each gadget has a native evaluation, used by the reference interpreter, and a lowering
+//! into IR nodes, used by the frontend when it meets a call to the path. Arguments are
+//! flattened to field elements, arrays in order, before either sees them, and an array
+//! result comes back as its elements in order.
+
+pub mod bigint;
+pub mod cmp;
+pub mod curve;
+pub mod ecdsa;
+pub mod eddsa;
+mod emulated;
+pub mod fixed;
+mod gadget;
+pub mod keccak;
+pub mod merkle;
+pub mod poseidon;
+pub mod sha256;
+
+use crate::ir::IRGraph;
+use crate::optimization::constant_value;
+use crate::semantics::Value;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+pub use curve::{Curve, Point};
+pub use fixed::Format;
+pub use keccak::keccak256;
+pub use merkle::MerkleHash;
+pub use poseidon::{poseidon, ParamsCache, PoseidonParams};
+pub use sha256::sha256;
+
+/// `std::hash::poseidon(inputs: Field[N]) -> Field`, for 1 to 16 inputs
+pub const POSEIDON: &str = "std::hash::poseidon";
+
+/// `std::hash::sha256(bytes: Field[N]) -> Field[32]`, every input a byte
+pub const SHA256: &str = "std::hash::sha256";
+
+/// `std::hash::keccak256(bytes: Field[N]) -> Field[32]`, every input a byte
+pub const KECCAK256: &str = "std::hash::keccak256";
+
+/// `std::merkle::verify(leaf: Field, path: Field[DEPTH], indices: Bool[DEPTH], root: Field) -> Bool`,
+/// over two-input Poseidon
+pub const MERKLE_VERIFY: &str = "std::merkle::verify";
+
+/// `std::cmp::less_than(a: Field, b: Field, bits: u32) -> Bool`, for `bits`-bit operands
+pub const LESS_THAN: &str = "std::cmp::less_than";
+
+/// `std::cmp::is_zero(x: Field) -> Bool`
+pub const IS_ZERO: &str = "std::cmp::is_zero";
+
+/// `std::cmp::min(a: Field, b: Field, bits: u32) -> Field`, for `bits`-bit operands
+pub const MIN: &str = "std::cmp::min";
+
+/// `std::cmp::max(a: Field, b: Field, bits: u32) -> Field`, for `bits`-bit operands
+pub const MAX: &str = "std::cmp::max";
+
+/// `std::cmp::sort(values: Field[N], bits: u32) -> Field[N]`, ascending, for `bits`-bit values
+pub const SORT: &str = "std::cmp::sort";
+
+/// `std::curve::add(p: Field[2], q: Field[2]) -> Field[2]` on the active field's embedded curve
+pub const CURVE_ADD: &str = "std::curve::add";
+
+/// `std::curve::double(p: Field[2]) -> Field[2]` on the active field's embedded curve
+pub const CURVE_DOUBLE: &str = "std::curve::double";
+
+/// `std::curve::mul_base(scalar: Field) -> Field[2]`, the scalar times the embedded curve's base
+pub const CURVE_MUL_BASE: &str = "std::curve::mul_base";
+
+/// `std::sig::verify_eddsa(pk: Field[2], msg_hash: Field, sig: Field[3]) -> Bool`, with the
+/// signature as `R.x, R.y, S`, over the embedded curve with a Poseidon challenge
+pub const VERIFY_EDDSA: &str = "std::sig::verify_eddsa";
+
+/// `std::sig::verify_ecdsa(pk: Field[8], msg_hash: Field[4], sig: Field[8]) -> Bool` over
+/// secp256k1, with every 256-bit value as four 64-bit limbs, least significant first: the
+/// key as `x` then `y`, the signature as `r` then `s`
+pub const VERIFY_ECDSA: &str = "std::sig::verify_ecdsa";
+
+/// `std::bigint::add(a: BigUint<N>, b: BigUint<N>) -> BigUint<N + 1>`, where `BigUint<N>`
+/// is `Field[N]` of 64-bit limbs, least significant first
+pub const BIGINT_ADD: &str = "std::bigint::add";
+
+/// `std::bigint::mul(a: BigUint<N>, b: BigUint<N>) -> BigUint<2N>`
+pub const BIGINT_MUL: &str = "std::bigint::mul";
+
+/// `std::bigint::rem(a: BigUint<2N>, m: BigUint<N>) -> BigUint<N>`, for a nonzero `m`
+pub const BIGINT_REM: &str = "std::bigint::rem";
+
+/// `std::bigint::mul_mod(a: BigUint<N>, b: BigUint<N>, m: BigUint<N>) -> BigUint<N>`, for a
+/// nonzero `m`
+pub const BIGINT_MUL_MOD: &str = "std::bigint::mul_mod";
+
+/// `std::fixed::add(a: Fixed<I, F>, b: Fixed<I, F>, I, F) -> Fixed<I, F>`, where
+/// `Fixed<I, F>` is a signed `Field` scaled by `2^F`, with `I` and `F` constants
+pub const FIXED_ADD: &str = "std::fixed::add";
+
+/// `std::fixed::sub(a: Fixed<I, F>, b: Fixed<I, F>, I, F) -> Fixed<I, F>`
+pub const FIXED_SUB: &str = "std::fixed::sub";
+
+/// `std::fixed::mul(a: Fixed<I, F>, b: Fixed<I, F>, I, F) -> Fixed<I, F>`, truncated toward
+/// negative infinity
+pub const FIXED_MUL: &str = "std::fixed::mul";
+
+/// `std::fixed::relu(x: Fixed<I, F>, I, F) -> Fixed<I, F>`
+pub const FIXED_RELU: &str = "std::fixed::relu";
+
+/// `std::fixed::less_than(a: Fixed<I, F>, b: Fixed<I, F>, I, F) -> Bool`, signed
+pub const FIXED_LESS_THAN: &str = "std::fixed::less_than";
+
+/// Whether `name` names a standard library function, or a gadget of a linked package,
+/// rather than one in the program
+pub fn is_std_path(name: &str) -> bool {
+    name.starts_with("std::") || crate::package::is_linked(name)
+}
+
+/// Evaluate the standard library function or linked gadget `name` on `args` in the active
+/// field, or `None` if there is no such function
+pub fn eval_call(name: &str, args: &[BigUint]) -> Option<Result<Value, FCMCError>> {
+    let digest = |hash: fn(&[u8]) -> [u8; 32]| -> Result<Value, FCMCError> {
+        let digest = hash(&bytes(name, args)?);
+        Ok(Value::Array(
+            digest.iter().map(|&byte| Value::Field(BigUint::from(byte))).collect(),
+        ))
+    };
+    match name {
+        POSEIDON => Some(poseidon(args).map(Value::Field)),
+        SHA256 => Some(digest(sha256)),
+        KECCAK256 => Some(digest(keccak256)),
+        MERKLE_VERIFY => Some(eval_merkle_verify(args).map(Value::Bool)),
+        LESS_THAN => Some(comparison(name, args).and_then(|(a, b, bits)| cmp::less_than(a, b, bits).map(Value::Bool))),
+        IS_ZERO => Some(arity(name, args, 1).map(|()| Value::Bool(cmp::is_zero(&args[0])))),
+        MIN => Some(comparison(name, args).and_then(|(a, b, bits)| cmp::min(a, b, bits).map(Value::Field))),
+        MAX => Some(comparison(name, args).and_then(|(a, b, bits)| cmp::max(a, b, bits).map(Value::Field))),
+        SORT => Some(match args.split_last() {
+            Some((bits, values)) => bits_argument(name, bits)
+                .and_then(|bits| cmp::sort(values, bits))
+                .map(|sorted| Value::Array(sorted.into_iter().map(Value::Field).collect())),
+            None => arity(name, args, 1).map(|()| Value::Unit),
+        }),
+        CURVE_ADD => Some(arity(name, args, 4).and_then(|()| {
+            let (p, q) = (point(&args[0..2]), point(&args[2..4]));
+            Curve::active()?.add(&p, &q).map(point_value)
+        })),
+        CURVE_DOUBLE => {
+            Some(arity(name, args, 2).and_then(|()| Curve::active()?.double(&point(args)).map(point_value)))
+        }
+        CURVE_MUL_BASE => {
+            Some(arity(name, args, 1).and_then(|()| Curve::active()?.mul_base(&args[0]).map(point_value)))
+        }
+        VERIFY_EDDSA => Some(arity(name, args, 6).and_then(|()| {
+            let (public_key, r) = (point(&args[0..2]), point(&args[3..5]));
+            eddsa::verify_eddsa(&Curve::active()?, &public_key, &args[2], &r, &args[5]).map(Value::Bool)
+        })),
+        VERIFY_ECDSA => Some(arity(name, args, 20).and_then(|()| {
+            let values = args
+                .chunks(4)
+                .map(|limbs| from_limbs(name, limbs))
+                .collect::<Result<Vec<BigUint>, FCMCError>>()?;
+            let [x, y, hash, r, s] = values.as_slice() else {
+                unreachable!("twenty limbs make five values")
+            };
+            Ok(Value::Bool(ecdsa::verify_ecdsa(x, y, hash, r, s)))
+        })),
+        BIGINT_ADD | BIGINT_MUL | BIGINT_REM | BIGINT_MUL_MOD => Some(eval_bigint(name, args)),
+        FIXED_ADD => {
+            Some(fixed_call(name, args, 2).and_then(|(x, format)| fixed::add(&format, &x[0], &x[1]).map(Value::Field)))
+        }
+        FIXED_SUB => {
+            Some(fixed_call(name, args, 2).and_then(|(x, format)| fixed::sub(&format, &x[0], &x[1]).map(Value::Field)))
+        }
+        FIXED_MUL => {
+            Some(fixed_call(name, args, 2).and_then(|(x, format)| fixed::mul(&format, &x[0], &x[1]).map(Value::Field)))
+        }
+        FIXED_RELU => {
+            Some(fixed_call(name, args, 1).and_then(|(x, format)| fixed::relu(&format, &x[0]).map(Value::Field)))
+        }
+        FIXED_LESS_THAN => Some(
+            fixed_call(name, args, 2).and_then(|(x, format)| fixed::less_than(&format, &x[0], &x[1]).map(Value::Bool)),
+        ),
+        _ => crate::package::eval_call(name, args),
+    }
+}
+
+/// Emit a call to the standard library function or linked gadget `name` on the nodes
+/// `args` into `graph`, returning the nodes holding the result, or `None` if there is no
+/// such function
+pub fn lower_call(graph: &mut IRGraph, name: &str, args: &[usize]) -> Option<Result<Vec<usize>, FCMCError>> {
+    match name {
+        POSEIDON => Some(
+            PoseidonParams::for_inputs(args.len())
+                .and_then(|params| poseidon::lower_poseidon(graph, &params, args))
+                .map(|hash| vec![hash]),
+        ),
+        SHA256 => Some(sha256::lower_sha256(graph, args)),
+        KECCAK256 => Some(keccak::lower_keccak256(graph, args)),
+        MERKLE_VERIFY => Some(lower_merkle_verify(graph, args).map(|verified| vec![verified])),
+        LESS_THAN => Some(
+            lowered_comparison(graph, name, args)
+                .and_then(|(a, b, bits)| cmp::lower_less_than(graph, a, b, bits))
+                .map(|less| vec![less]),
+        ),
+        IS_ZERO => Some(arity(name, args, 1).map(|()| vec![cmp::lower_is_zero(graph, args[0])])),
+        MIN => Some(
+            lowered_comparison(graph, name, args)
+                .and_then(|(a, b, bits)| cmp::lower_min(graph, a, b, bits))
+                .map(|min| vec![min]),
+        ),
+        MAX => Some(
+            lowered_comparison(graph, name, args)
+                .and_then(|(a, b, bits)| cmp::lower_max(graph, a, b, bits))
+                .map(|max| vec![max]),
+        ),
+        SORT => Some(match args.split_last() {
+            Some((&bits, values)) => {
+                constant_bits(graph, name, bits).and_then(|bits| cmp::lower_sort(graph, values, bits))
+            }
+            None => arity(name, args, 1).map(|()| Vec::new()),
+        }),
+        CURVE_ADD => Some(arity(name, args, 4).and_then(|()| {
+            let sum = curve::lower_add(graph, &Curve::active()?, (args[0], args[1]), (args[2], args[3]));
+            Ok(vec![sum.0, sum.1])
+        })),
+        CURVE_DOUBLE => Some(arity(name, args, 2).and_then(|()| {
+            let double = curve::lower_double(graph, &Curve::active()?, (args[0], args[1]));
+            Ok(vec![double.0, double.1])
+        })),
+        CURVE_MUL_BASE => Some(arity(name, args, 1).and_then(|()| {
+            let product = curve::lower_mul_base(graph, &Curve::active()?, args[0])?;
+            Ok(vec![product.0, product.1])
+        })),
+        VERIFY_EDDSA => Some(arity(name, args, 6).and_then(|()| {
+            let curve = Curve::active()?;
+            eddsa::lower_verify_eddsa(graph, &curve, (args[0], args[1]), args[2], (args[3], args[4]), args[5])
+                .map(|verified| vec![verified])
+        })),
+        VERIFY_ECDSA => Some(arity(name, args, 20).and_then(|()| {
+            ecdsa::lower_verify_ecdsa(graph, &args[0..8], &args[8..12], &args[12..20]).map(|verified| vec![verified])
+        })),
+        BIGINT_ADD | BIGINT_MUL | BIGINT_REM | BIGINT_MUL_MOD => Some(lower_bigint(graph, name, args)),
+        FIXED_ADD | FIXED_SUB | FIXED_MUL | FIXED_LESS_THAN => {
+            Some(lowered_fixed_call(graph, name, args, 2).map(|(x, format)| {
+                let lower = match name {
+                    FIXED_ADD => fixed::lower_add,
+                    FIXED_SUB => fixed::lower_sub,
+                    FIXED_MUL => fixed::lower_mul,
+                    _ => fixed::lower_less_than,
+                };
+                vec![lower(graph, &format, x[0], x[1])]
+            }))
+        }
+        FIXED_RELU => Some(
+            lowered_fixed_call(graph, name, args, 1).map(|(x, format)| vec![fixed::lower_relu(graph, &format, x[0])]),
+        ),
+        _ => crate::package::lower_call(graph, name, args),
+    }
+}
+
+fn point(coordinates: &[BigUint]) -> Point {
+    Point {
+        x: coordinates[0].clone(),
+        y: coordinates[1].clone(),
+    }
+}
+
+fn point_value(point: Point) -> Value {
+    Value::Array(vec![Value::Field(point.x), Value::Field(point.y)])
+}
+
+/// The integer whose 64-bit limbs, least significant first, are `limbs`, failing on a
+/// limb of 64 bits or more as the gadget's range checks would
+fn from_limbs(name: &str, limbs: &[BigUint]) -> Result<BigUint, FCMCError> {
+    let mut value = BigUint::default();
+    for (index, limb) in limbs.iter().enumerate().rev() {
+        if limb.bits() > 64 {
+            return Err(FCMCError::VerificationError(format!(
+                "Limb {} of {} is {}, wider than 64 bits",
+                index, name, limb
+            )));
+        }
+        value = (value << 64) + limb;
+    }
+    Ok(value)
+}
+
+/// Limbs per `N` in the arguments of the `std::bigint` function `name`
+fn bigint_width<T>(name: &str, args: &[T]) -> Result<usize, FCMCError> {
+    let operands = if name == BIGINT_ADD || name == BIGINT_MUL { 2 } else { 3 };
+    bigint::limbs_per_operand(name, args.len(), operands)
+}
+
+fn eval_bigint(name: &str, args: &[BigUint]) -> Result<Value, FCMCError> {
+    emulated::check_field()?;
+    let width = bigint_width(name, args)?;
+    let value = |limbs: std::ops::Range<usize>| from_limbs(name, &args[limbs]);
+    let (result, limbs) = match name {
+        BIGINT_ADD => (value(0..width)? + value(width..2 * width)?, width + 1),
+        BIGINT_MUL => (value(0..width)? * value(width..2 * width)?, 2 * width),
+        BIGINT_REM => (
+            bigint::rem(&value(0..2 * width)?, &value(2 * width..3 * width)?)?,
+            width,
+        ),
+        _ => {
+            let product = value(0..width)? * value(width..2 * width)?;
+            (bigint::rem(&product, &value(2 * width..3 * width)?)?, width)
+        }
+    };
+    Ok(Value::Array(
+        emulated::limbs_of(&result, limbs)
+            .into_iter()
+            .map(Value::Field)
+            .collect(),
+    ))
+}
+
+fn lower_bigint(graph: &mut IRGraph, name: &str, args: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    let width = bigint_width(name, args)?;
+    match name {
+        BIGINT_ADD => bigint::lower_add(graph, &args[..width], &args[width..]),
+        BIGINT_MUL => bigint::lower_mul(graph, &args[..width], &args[width..]),
+        BIGINT_REM => bigint::lower_rem(graph, &args[..2 * width], &args[2 * width..]),
+        _ => bigint::lower_mul_mod(graph, &args[..width], &args[width..2 * width], &args[2 * width..]),
+    }
+}
+
+/// The operands and format of a call to a `std::fixed` function of `operands` operands,
+/// whose last two arguments are `I` and `F`
+fn fixed_call<'a>(name: &str, args: &'a [BigUint], operands: usize) -> Result<(&'a [BigUint], Format), FCMCError> {
+    arity(name, args, operands + 2)?;
+    let format = Format::new(
+        bits_argument(name, &args[operands])?,
+        bits_argument(name, &args[operands + 1])?,
+    )?;
+    Ok((&args[..operands], format))
+}
+
+fn lowered_fixed_call<'a>(
+    graph: &IRGraph,
+    name: &str,
+    args: &'a [usize],
+    operands: usize,
+) -> Result<(&'a [usize], Format), FCMCError> {
+    arity(name, args, operands + 2)?;
+    let format = Format::new(
+        constant_bits(graph, name, args[operands])?,
+        constant_bits(graph, name, args[operands + 1])?,
+    )?;
+    Ok((&args[..operands], format))
+}
+
+fn eval_merkle_verify(args: &[BigUint]) -> Result<bool, FCMCError> {
+    let depth = merkle::depth_of(args.len())?;
+    let (leaf, rest) = args.split_first().expect("at least two arguments");
+    let (path, rest) = rest.split_at(depth);
+    let (indices, root) = rest.split_at(depth);
+    let indices = indices
+        .iter()
+        .enumerate()
+        .map(|(level, index)| match index.to_u8() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(FCMCError::VerificationError(format!(
+                "Index {} of {} is {}, not a bool",
+                level, MERKLE_VERIFY, index
+            ))),
+        })
+        .collect::<Result<Vec<bool>, FCMCError>>()?;
+    merkle::verify(&PoseidonParams::for_inputs(2)?, leaf, path, &indices, &root[0])
+}
+
+fn lower_merkle_verify(graph: &mut IRGraph, args: &[usize]) -> Result<usize, FCMCError> {
+    let depth = merkle::depth_of(args.len())?;
+    let (path, indices) = args[1..args.len() - 1].split_at(depth);
+    merkle::lower_verify(
+        graph,
+        &PoseidonParams::for_inputs(2)?,
+        args[0],
+        path,
+        indices,
+        args[args.len() - 1],
+    )
+}
+
+fn arity<T>(name: &str, args: &[T], expected: usize) -> Result<(), FCMCError> {
+    if args.len() != expected {
+        return Err(FCMCError::SemanticError(format!(
+            "{} takes {} arguments, not {}",
+            name,
+            expected,
+            args.len()
+        )));
+    }
+    Ok(())
+}
+
+fn bits_argument(name: &str, bits: &BigUint) -> Result<u32, FCMCError> {
+    bits.to_u32()
+        .ok_or_else(|| FCMCError::SemanticError(format!("{} is not a bit width for {}", bits, name)))
+}
+
+/// The bit width argument `bits` of a lowered call, which must be a constant
+fn constant_bits(graph: &IRGraph, name: &str, bits: usize) -> Result<u32, FCMCError> {
+    let value = constant_value(graph, bits)
+        .ok_or_else(|| FCMCError::SemanticError(format!("The bit width of {} must be a constant", name)))?;
+    bits_argument(name, &value)
+}
+
+/// `a`, `b` and the bit width of a call to `std::cmp::less_than`, `min` or `max`
+fn comparison<'a>(name: &str, args: &'a [BigUint]) -> Result<(&'a BigUint, &'a BigUint, u32), FCMCError> {
+    arity(name, args, 3)?;
+    Ok((&args[0], &args[1], bits_argument(name, &args[2])?))
+}
+
+fn lowered_comparison(graph: &IRGraph, name: &str, args: &[usize]) -> Result<(usize, usize, u32), FCMCError> {
+    arity(name, args, 3)?;
+    Ok((args[0], args[1], constant_bits(graph, name, args[2])?))
+}
+
+/// `args` as bytes, failing on any value above 255 as the gadget's constraints would
+fn bytes(name: &str, args: &[BigUint]) -> Result<Vec<u8>, FCMCError> {
+    args.iter()
+        .enumerate()
+        .map(|(index, value)| {
+            value.to_u8().ok_or_else(|| {
+                FCMCError::VerificationError(format!("Argument {} of {} is {}, not a byte", index, name, value))
+            })
+        })
+        .collect()
+}
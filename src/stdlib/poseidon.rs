@@ -0,0 +1,371 @@
+//! The Poseidon hash, with the parameters circomlib and most BN254 tooling use.
+//!
+//! The state has one element per input plus a capacity element, which starts at zero
+//! and is the output. Each round adds round constants, applies `x^5` to every element
+//! (full rounds) or to the first only (partial rounds), and mixes the state with an MDS
+//! matrix. Round constants and the matrix come from the Grain LFSR of the Poseidon
+//! reference implementation, seeded with the field size, width and round counts, so in
+//! the BN254 field the hash agrees with circomlib's `Poseidon(n)` for every supported `n`;
+//! in other fields it is the same construction with that field's constants.
+
+use crate::ir::{IRGraph, IRNodeType};
+use crate::optimization::field;
+use crate::stdlib::gadget::Emitter;
+use crate::FCMCError;
+use num_bigint::BigUint;
+use num_traits::Zero;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+/// Full rounds of the standard parameters, half before and half after the partial rounds
+pub const POSEIDON_FULL_ROUNDS: usize = 8;
+
+/// Partial rounds of the standard parameters for widths 2 to 17, i.e. 1 to 16 inputs
+pub const POSEIDON_PARTIAL_ROUNDS: [usize; 16] = [56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68];
+
+/// Most inputs the standard parameters cover
+pub const MAX_POSEIDON_INPUTS: usize = POSEIDON_PARTIAL_ROUNDS.len();
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_poseidon` emits
+pub const POSEIDON_GADGET: &str = "poseidon";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoseidonParams {
+    /// State size: inputs plus one capacity element
+    pub width: usize,
+    pub full_rounds: usize,
+    pub partial_rounds: usize,
+    /// `width` constants per round, round after round
+    pub round_constants: Vec<BigUint>,
+    /// `width` x `width`, applied as `state[i] = sum_j mds[i][j] * state[j]`
+    pub mds: Vec<Vec<BigUint>>,
+}
+
+impl PoseidonParams {
+    /// The standard parameters for hashing `inputs` elements of the active field
+    pub fn for_inputs(inputs: usize) -> Result<Self, FCMCError> {
+        if inputs == 0 || inputs > MAX_POSEIDON_INPUTS {
+            return Err(FCMCError::SemanticError(format!(
+                "Poseidon hashes 1 to {} inputs, not {}",
+                MAX_POSEIDON_INPUTS, inputs
+            )));
+        }
+        let generate = || Self::new(inputs + 1, POSEIDON_FULL_ROUNDS, POSEIDON_PARTIAL_ROUNDS[inputs - 1]);
+        match ACTIVE_CACHE.with(|active| active.borrow().clone()) {
+            Some(cache) => cache.get_or_generate(inputs, generate),
+            None => generate(),
+        }
+    }
+
+    /// Parameters of the given width and round counts over the active field, with
+    /// constants generated as the Poseidon reference implementation does
+    pub fn new(width: usize, full_rounds: usize, partial_rounds: usize) -> Result<Self, FCMCError> {
+        if width < 2 || full_rounds % 2 != 0 {
+            return Err(FCMCError::SemanticError(format!(
+                "Poseidon needs a width of at least 2 and an even number of full rounds, not {} and {}",
+                width, full_rounds
+            )));
+        }
+        let modulus = field::modulus();
+        let bits = modulus.bits() as usize;
+        let mut grain = Grain::new(bits, width, full_rounds, partial_rounds);
+
+        // Rejection-sampled, so every constant is uniform in the field
+        let mut round_constants = Vec::with_capacity((full_rounds + partial_rounds) * width);
+        while round_constants.len() < (full_rounds + partial_rounds) * width {
+            let value = grain.bits(bits);
+            if value < modulus {
+                round_constants.push(value);
+            }
+        }
+
+        // Cauchy matrix 1 / (x_i + y_j) over distinct points with no sum of zero
+        let mds = loop {
+            let mut points: Vec<BigUint> = (0..2 * width).map(|_| grain.bits(bits) % &modulus).collect();
+            loop {
+                let mut sorted = points.clone();
+                sorted.sort();
+                sorted.dedup();
+                if sorted.len() == points.len() {
+                    break;
+                }
+                points = (0..2 * width).map(|_| grain.bits(bits) % &modulus).collect();
+            }
+            let (xs, ys) = points.split_at(width);
+            let mds: Option<Vec<Vec<BigUint>>> = xs
+                .iter()
+                .map(|x| ys.iter().map(|y| field::inverse(&field::add(x, y))).collect())
+                .collect();
+            if let Some(mds) = mds {
+                break mds;
+            }
+        };
+
+        Ok(Self {
+            width,
+            full_rounds,
+            partial_rounds,
+            round_constants,
+            mds,
+        })
+    }
+
+    fn is_full_round(&self, round: usize) -> bool {
+        round < self.full_rounds / 2 || round >= self.full_rounds / 2 + self.partial_rounds
+    }
+
+    /// Apply the permutation to `state`, which has `width` elements
+    pub fn permute(&self, state: &mut [BigUint]) {
+        for round in 0..self.full_rounds + self.partial_rounds {
+            for (index, value) in state.iter_mut().enumerate() {
+                *value = field::add(value, &self.round_constants[round * self.width + index]);
+            }
+            let sboxes = if self.is_full_round(round) { self.width } else { 1 };
+            for value in &mut state[..sboxes] {
+                *value = sbox(value);
+            }
+            let mixed: Vec<BigUint> = self
+                .mds
+                .iter()
+                .map(|row| {
+                    row.iter().zip(state.iter()).fold(BigUint::zero(), |acc, (m, value)| {
+                        field::add(&acc, &field::mul(m, value))
+                    })
+                })
+                .collect();
+            state.clone_from_slice(&mixed);
+        }
+    }
+
+    /// Hash `inputs`, of which there must be `width - 1`
+    pub fn hash(&self, inputs: &[BigUint]) -> Result<BigUint, FCMCError> {
+        self.check_inputs(inputs.len())?;
+        let mut state = Vec::with_capacity(self.width);
+        state.push(BigUint::zero());
+        state.extend(inputs.iter().map(|value| value % field::modulus()));
+        self.permute(&mut state);
+        Ok(state.swap_remove(0))
+    }
+
+    fn check_inputs(&self, inputs: usize) -> Result<(), FCMCError> {
+        if inputs + 1 != self.width {
+            return Err(FCMCError::SemanticError(format!(
+                "Poseidon of width {} hashes {} inputs, not {}",
+                self.width,
+                self.width - 1,
+                inputs
+            )));
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// Cache entered on this thread, if any
+    static ACTIVE_CACHE: RefCell<Option<Arc<ParamsCache>>> = const { RefCell::new(None) };
+}
+
+/// Standard parameters already generated, by field modulus and inputs, for compilations
+/// that share them; generating the constants dominates lowering a small hash
+#[derive(Debug, Default)]
+pub struct ParamsCache {
+    params: RwLock<HashMap<(BigUint, usize), PoseidonParams>>,
+}
+
+impl ParamsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parameter sets held
+    pub fn len(&self) -> usize {
+        self.params
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Run `f` with `PoseidonParams::for_inputs` on this thread reading from and adding
+    /// to this cache
+    pub fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let previous = ACTIVE_CACHE.with(|active| active.replace(Some(self.clone())));
+        let result = f();
+        ACTIVE_CACHE.with(|active| *active.borrow_mut() = previous);
+        result
+    }
+
+    fn get_or_generate(
+        &self,
+        inputs: usize,
+        generate: impl FnOnce() -> Result<PoseidonParams, FCMCError>,
+    ) -> Result<PoseidonParams, FCMCError> {
+        let key = (field::modulus(), inputs);
+        if let Some(params) = self
+            .params
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&key)
+        {
+            return Ok(params.clone());
+        }
+        // Generated outside the lock; two threads missing at once both generate the same set
+        let params = generate()?;
+        let mut cached = self.params.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        cached.insert(key, params.clone());
+        Ok(params)
+    }
+}
+
+/// `x^5`
+fn sbox(value: &BigUint) -> BigUint {
+    let square = field::mul(value, value);
+    field::mul(&field::mul(&square, &square), value)
+}
+
+/// The standard Poseidon hash of `inputs` in the active field
+pub fn poseidon(inputs: &[BigUint]) -> Result<BigUint, FCMCError> {
+    PoseidonParams::for_inputs(inputs.len())?.hash(inputs)
+}
+
+/// Emit the hash of the nodes `inputs` into `graph` with `params`, returning the node
+/// holding it. Every emitted node is tagged as part of the `poseidon` gadget.
+pub fn lower_poseidon(graph: &mut IRGraph, params: &PoseidonParams, inputs: &[usize]) -> Result<usize, FCMCError> {
+    params.check_inputs(inputs.len())?;
+    let mut emitter = Emitter::new(graph);
+    let mut state: Vec<usize> = Vec::with_capacity(params.width);
+    state.push(emitter.constant(&BigUint::zero()));
+    state.extend_from_slice(inputs);
+
+    for round in 0..params.full_rounds + params.partial_rounds {
+        for (index, value) in state.iter_mut().enumerate() {
+            let constant = emitter.constant(&params.round_constants[round * params.width + index]);
+            *value = emitter.binary(IRNodeType::Add, *value, constant);
+        }
+        let sboxes = if params.is_full_round(round) { params.width } else { 1 };
+        for value in &mut state[..sboxes] {
+            let square = emitter.binary(IRNodeType::Mul, *value, *value);
+            let fourth = emitter.binary(IRNodeType::Mul, square, square);
+            *value = emitter.binary(IRNodeType::Mul, fourth, *value);
+        }
+        let mut mixed = Vec::with_capacity(params.width);
+        for row in &params.mds {
+            let mut sum = None;
+            for (m, &value) in row.iter().zip(&state) {
+                let m = emitter.constant(m);
+                let term = emitter.binary(IRNodeType::Mul, m, value);
+                sum = Some(match sum {
+                    Some(sum) => emitter.binary(IRNodeType::Add, sum, term),
+                    None => term,
+                });
+            }
+            mixed.push(sum.expect("width is at least 2"));
+        }
+        state = mixed;
+    }
+
+    emitter.finish(POSEIDON_GADGET);
+    Ok(state[0])
+}
+
+/// The Grain LFSR the Poseidon reference implementation draws its constants from
+struct Grain {
+    state: VecDeque<bool>,
+}
+
+impl Grain {
+    fn new(field_bits: usize, width: usize, full_rounds: usize, partial_rounds: usize) -> Self {
+        let mut state = VecDeque::with_capacity(80);
+        // Prime field, x^alpha S-box, then the sizes, then thirty ones
+        let fields = [
+            (1, 2),
+            (0, 4),
+            (field_bits, 12),
+            (width, 12),
+            (full_rounds, 10),
+            (partial_rounds, 10),
+            ((1 << 30) - 1, 30),
+        ];
+        for (value, bits) in fields {
+            for bit in (0..bits).rev() {
+                state.push_back((value >> bit) & 1 == 1);
+            }
+        }
+        let mut grain = Self { state };
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.pop_front();
+        self.state.push_back(bit);
+        bit
+    }
+
+    /// Next output bit: of each pair of steps, the second is output if the first is 1
+    fn bit(&mut self) -> bool {
+        loop {
+            let keep = self.step();
+            let bit = self.step();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    /// The next `bits` output bits as an integer, most significant first
+    fn bits(&mut self, bits: usize) -> BigUint {
+        let mut value = BigUint::zero();
+        for _ in 0..bits {
+            value <<= 1;
+            if self.bit() {
+                value += 1u32;
+            }
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::field::FieldConfig;
+
+    fn hash(inputs: &[u64]) -> String {
+        let inputs: Vec<BigUint> = inputs.iter().map(|&input| BigUint::from(input)).collect();
+        FieldConfig::Bn254.enter(|| poseidon(&inputs)).unwrap().to_string()
+    }
+
+    /// Hashes from circomlib's `poseidon` in the BN254 field
+    #[test]
+    fn matches_circomlib_on_bn254() {
+        assert_eq!(
+            hash(&[1]),
+            "18586133768512220936620570745912940619677854269274689475585506675881198879027"
+        );
+        assert_eq!(
+            hash(&[1, 2]),
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+        );
+        assert_eq!(
+            hash(&[1, 2, 3, 4]),
+            "18821383157269793795438455681495246036402687001665670618754263018637548127333"
+        );
+        assert_eq!(
+            hash(&[1, 2, 0, 0]),
+            "1294238303953011295368028792578855414304792618679093646952555481669458132119"
+        );
+        assert_eq!(
+            hash(&[1, 2, 0, 0, 0]),
+            "1018317224307729531995786483840663576608797660851238720571059489595066344487"
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! SHA-256 (FIPS 180-4) over byte arrays.
+//!
+//! The message length is known when the circuit is built, so the padding is constant
+//! and only the message bytes are wires. Each byte is decomposed into bits, which also
+//! constrains it below 256; the compression function then works on 32-bit words of bits,
+//! with additions modulo 2^32 done on packed values and decomposed once per sum.
+
+use crate::ir::IRGraph;
+use crate::stdlib::gadget::{constant_word, rotate_right, shift_right, Bit, Emitter};
+use crate::FCMCError;
+
+/// Value of `GADGET_ATTRIBUTE` on the nodes `lower_sha256` emits
+pub const SHA256_GADGET: &str = "sha256";
+
+/// Message bytes per compression
+pub const SHA256_BLOCK_BYTES: usize = 64;
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98,
+    0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+    0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8,
+    0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819,
+    0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+    0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+    0xc67178f2,
+];
+
+/// Bytes in a `message_bytes`-byte message padded to whole blocks: a 1 bit, zeros, and
+/// the bit length as 64 big-endian bits
+fn padded(message_bytes: usize) -> usize {
+    (message_bytes + 9).div_ceil(SHA256_BLOCK_BYTES) * SHA256_BLOCK_BYTES
+}
+
+fn padding_byte(message_bytes: usize, index: usize) -> u8 {
+    let total = padded(message_bytes);
+    let length = (message_bytes as u64 * 8).to_be_bytes();
+    if index == message_bytes {
+        0x80
+    } else if index >= total - 8 {
+        length[index - (total - 8)]
+    } else {
+        0
+    }
+}
+
+/// The SHA-256 digest of `message`
+pub fn sha256(message: &[u8]) -> [u8; 32] {
+    let mut state = INITIAL_STATE;
+    let bytes: Vec<u8> = (0..padded(message.len()))
+        .map(|index| {
+            message
+                .get(index)
+                .copied()
+                .unwrap_or_else(|| padding_byte(message.len(), index))
+        })
+        .collect();
+    for block in bytes.chunks(SHA256_BLOCK_BYTES) {
+        let mut schedule = [0u32; 64];
+        for (t, word) in block.chunks(4).enumerate() {
+            schedule[t] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for t in 16..64 {
+            let s0 = schedule[t - 15].rotate_right(7) ^ schedule[t - 15].rotate_right(18) ^ (schedule[t - 15] >> 3);
+            let s1 = schedule[t - 2].rotate_right(17) ^ schedule[t - 2].rotate_right(19) ^ (schedule[t - 2] >> 10);
+            schedule[t] = schedule[t - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[t - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for t in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(ROUND_CONSTANTS[t])
+                .wrapping_add(schedule[t]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (chunk, word) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Emit the SHA-256 digest of the byte nodes `message` into `graph`, returning the 32
+/// digest byte nodes. Every message byte is constrained below 256.
+pub fn lower_sha256(graph: &mut IRGraph, message: &[usize]) -> Result<Vec<usize>, FCMCError> {
+    let mut emitter = Emitter::new(graph);
+    // Message bits, most significant first within each byte as SHA-256 reads them
+    let mut bits: Vec<Bit> = Vec::with_capacity(padded(message.len()) * 8);
+    for index in 0..padded(message.len()) {
+        let byte = match message.get(index) {
+            Some(&byte) => emitter.unpack(byte, 8),
+            None => constant_word(padding_byte(message.len(), index) as u64, 8),
+        };
+        bits.extend(byte.into_iter().rev());
+    }
+
+    let mut state: Vec<Vec<Bit>> = INITIAL_STATE
+        .iter()
+        .map(|&word| constant_word(word as u64, 32))
+        .collect();
+    for block in bits.chunks(SHA256_BLOCK_BYTES * 8) {
+        state = compress(&mut emitter, &state, block);
+    }
+
+    let mut digest = Vec::with_capacity(32);
+    for word in &state {
+        for byte in (0..4).rev() {
+            digest.push(emitter.pack(&word[8 * byte..8 * byte + 8]));
+        }
+    }
+    emitter.finish(SHA256_GADGET);
+    Ok(digest)
+}
+
+/// One compression of the 512 block bits, most significant first, into the eight state
+/// words, least significant bit first
+fn compress(emitter: &mut Emitter, state: &[Vec<Bit>], block: &[Bit]) -> Vec<Vec<Bit>> {
+    let mut schedule: Vec<Vec<Bit>> = block
+        .chunks(32)
+        .map(|word| word.iter().rev().copied().collect())
+        .collect();
+    for t in 16..64 {
+        let s0 = xor3(
+            emitter,
+            &rotate_right(&schedule[t - 15], 7),
+            &rotate_right(&schedule[t - 15], 18),
+            &shift_right(&schedule[t - 15], 3),
+        );
+        let s1 = xor3(
+            emitter,
+            &rotate_right(&schedule[t - 2], 17),
+            &rotate_right(&schedule[t - 2], 19),
+            &shift_right(&schedule[t - 2], 10),
+        );
+        let word = emitter.add_words(&[&schedule[t - 16], &s0, &schedule[t - 7], &s1], 0, 32);
+        schedule.push(word);
+    }
+
+    let mut vars = state.to_vec();
+    for t in 0..64 {
+        let (a, b, c, e, f, g) = (&vars[0], &vars[1], &vars[2], &vars[4], &vars[5], &vars[6]);
+        let s1 = xor3(emitter, &rotate_right(e, 6), &rotate_right(e, 11), &rotate_right(e, 25));
+        // g ^ (e & (f ^ g)) is e ? f : g
+        let choice: Vec<Bit> = (0..32)
+            .map(|i| {
+                let differ = emitter.xor(f[i], g[i]);
+                let picked = emitter.and(e[i], differ);
+                emitter.xor(g[i], picked)
+            })
+            .collect();
+        let s0 = xor3(emitter, &rotate_right(a, 2), &rotate_right(a, 13), &rotate_right(a, 22));
+        // (a & b) ^ (c & (a ^ b)) is the majority of a, b and c
+        let majority: Vec<Bit> = (0..32)
+            .map(|i| {
+                let both = emitter.and(a[i], b[i]);
+                let either = emitter.xor(a[i], b[i]);
+                let third = emitter.and(c[i], either);
+                emitter.xor(both, third)
+            })
+            .collect();
+
+        // T1 is added to both d and T2, so each sum is decomposed once
+        let t1: [&[Bit]; 4] = [&vars[7], &s1, &choice, &schedule[t]];
+        let k = ROUND_CONSTANTS[t] as u64;
+        let new_e = emitter.add_words(&[&vars[3], t1[0], t1[1], t1[2], t1[3]], k, 32);
+        let new_a = emitter.add_words(&[t1[0], t1[1], t1[2], t1[3], &s0, &majority], k, 32);
+        vars.pop();
+        vars.insert(0, new_a);
+        vars[4] = new_e;
+    }
+
+    state
+        .iter()
+        .zip(&vars)
+        .map(|(word, value)| emitter.add_words(&[word, value], 0, 32))
+        .collect()
+}
+
+fn xor3(emitter: &mut Emitter, a: &[Bit], b: &[Bit], c: &[Bit]) -> Vec<Bit> {
+    let ab = emitter.xor_words(a, b);
+    emitter.xor_words(&ab, c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stdlib::gadget::testing::evaluate_bytes;
+
+    /// Digests from FIPS 180-4's examples
+    const VECTORS: &[(&str, &str)] = &[
+        ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+        (
+            "abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+    ];
+
+    #[test]
+    fn matches_fips_180_4() {
+        for (message, expected) in VECTORS {
+            assert_eq!(
+                hex::encode(sha256(message.as_bytes())),
+                *expected,
+                "sha256({:?})",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn gadget_matches_native() {
+        for (message, _) in VECTORS {
+            let message = message.as_bytes();
+            assert_eq!(
+                evaluate_bytes(lower_sha256, message),
+                sha256(message),
+                "sha256({:?})",
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Golden-file snapshot tests for compiled circuits.
+//!
+//! A source file is compiled and its constraint system written out in a canonical text
+//! form: a header with the target and sizes, then the backend's JSON with sorted keys,
+//! one value per line. The text is compared with a golden file committed next to the
+//! test, and a mismatch reports a line diff, so a change in circuit size or semantics
+//! shows up in code review. Running with `FCMC_UPDATE_GOLDEN=1` rewrites the golden files
+//! instead.
+//!
+//! `check_against_reference` tests a source file against an equivalent circuit built with
+//! another toolchain, such as a circom build of a standard SHA-256 or Poseidon gadget: on
+//! random inputs both must accept or both reject, and agree on the outputs.
+
+use crate::backend::export::circom::{read_r1cs, read_wtns};
+use crate::backend::CircuitBackend;
+use crate::ir::{IRGraph, IRNodeType};
+use crate::language::ast::Type;
+use crate::optimization::soundness::random_value;
+use crate::{CompiledCircuit, FCMCError, InputMap, FCMC};
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable that makes `check_golden` write the golden file instead of comparing
+pub const UPDATE_GOLDEN_ENV: &str = "FCMC_UPDATE_GOLDEN";
+
+/// Unchanged lines shown around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+/// Changed regions with more line pairs than this are shown as a whole instead of aligned
+const MAX_ALIGNED_CELLS: usize = 4_000_000;
+
+/// Random input assignments `assert_matches_reference` compares on
+pub const DEFAULT_REFERENCE_SAMPLES: usize = 16;
+
+/// The canonical text form of `compiled`'s constraint system
+pub fn snapshot(compiled: &CompiledCircuit) -> Result<String, FCMCError> {
+    let circuit = compiled.circuit.backend();
+    let body = serde_json::to_string_pretty(&circuit.to_json())
+        .map_err(|e| FCMCError::BackendError(format!("Failed to serialize circuit: {}", e)))?;
+    Ok(format!(
+        "# target: {:?}\n# field: {}\n# constraints: {}\n# wires: {}\n{}\n",
+        circuit.target(),
+        compiled.field,
+        circuit.constraint_count(),
+        circuit.wire_count(),
+        body
+    ))
+}
+
+/// Compile `source_path` with `compiler` and compare its snapshot with `golden_path`,
+/// failing with a diff if they differ; with `FCMC_UPDATE_GOLDEN` set, write the snapshot
+/// to `golden_path` instead
+pub fn check_golden(
+    compiler: &FCMC,
+    source_path: impl AsRef<Path>,
+    golden_path: impl AsRef<Path>,
+) -> Result<(), FCMCError> {
+    let source_path = source_path.as_ref();
+    let golden_path = golden_path.as_ref();
+    let source = std::fs::read_to_string(source_path)
+        .map_err(|e| FCMCError::VerificationError(format!("Failed to read {}: {}", source_path.display(), e)))?;
+    let actual = snapshot(&compiler.compile(&source)?)?;
+
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        return std::fs::write(golden_path, actual)
+            .map_err(|e| FCMCError::VerificationError(format!("Failed to write {}: {}", golden_path.display(), e)));
+    }
+    let expected = std::fs::read_to_string(golden_path).map_err(|e| {
+        FCMCError::VerificationError(format!(
+            "Failed to read {}: {} (run with {}=1 to create it)",
+            golden_path.display(),
+            e,
+            UPDATE_GOLDEN_ENV
+        ))
+    })?;
+    if expected == actual {
+        return Ok(());
+    }
+    Err(FCMCError::VerificationError(format!(
+        "{} no longer matches {} (run with {}=1 to accept the change):\n{}",
+        source_path.display(),
+        golden_path.display(),
+        UPDATE_GOLDEN_ENV,
+        diff(&expected, &actual)
+    )))
+}
+
+/// `check_golden` with the default compiler, panicking with the diff on a mismatch
+pub fn assert_golden(source_path: impl AsRef<Path>, golden_path: impl AsRef<Path>) {
+    if let Err(e) = check_golden(&FCMC::new(), source_path, golden_path) {
+        panic!("{}", e);
+    }
+}
+
+/// A circuit built by another toolchain: its circom `.r1cs` file and the command that
+/// computes its `.wtns` witness. `{input}` and `{witness}` in the command stand for the
+/// input JSON file to read and the witness file to write, as with circom's generated
+/// `node generate_witness.js circuit.wasm {input} {witness}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceCircuit {
+    pub r1cs: PathBuf,
+    pub witness_command: Vec<String>,
+}
+
+impl ReferenceCircuit {
+    pub fn new(r1cs: impl Into<PathBuf>, witness_command: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            r1cs: r1cs.into(),
+            witness_command: witness_command.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The reference's witness for `inputs`, or `None` if it rejects them
+    fn witness(&self, inputs: &InputMap, dir: &Path) -> Result<Option<Vec<BigUint>>, FCMCError> {
+        let input_path = dir.join("input.json");
+        let witness_path = dir.join("witness.wtns");
+        std::fs::write(&input_path, input_json(inputs).to_string())
+            .map_err(|e| FCMCError::VerificationError(format!("Failed to write {}: {}", input_path.display(), e)))?;
+        let _ = std::fs::remove_file(&witness_path);
+
+        let arguments: Vec<String> = self
+            .witness_command
+            .iter()
+            .map(|argument| {
+                argument
+                    .replace("{input}", &input_path.to_string_lossy())
+                    .replace("{witness}", &witness_path.to_string_lossy())
+            })
+            .collect();
+        let (program, arguments) = arguments
+            .split_first()
+            .ok_or_else(|| FCMCError::VerificationError("The reference has no witness command".to_string()))?;
+        let status = Command::new(program).args(arguments).output().map_err(|e| {
+            FCMCError::VerificationError(format!(
+                "Failed to run the reference witness command {}: {}",
+                program, e
+            ))
+        })?;
+        // Witness generators fail on inputs their constraints reject
+        if !status.status.success() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(&witness_path)
+            .map_err(|e| FCMCError::VerificationError(format!("Failed to read {}: {}", witness_path.display(), e)))?;
+        Ok(Some(read_wtns(&bytes)?.1))
+    }
+}
+
+/// What a source file was checked against its reference on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReferenceReport {
+    /// Assignments both accepted with the same outputs
+    pub accepted: usize,
+    /// Assignments both rejected
+    pub rejected: usize,
+}
+
+/// Compile `source_path` with `compiler` and compare it with `reference` on `samples`
+/// random inputs. Inputs are drawn from each input's declared type; an array input
+/// `xs[0]`, `xs[1]`, ... is passed to the reference as the JSON array `xs`. The
+/// reference's public outputs are compared, in order, with the circuit's outputs.
+pub fn check_against_reference(
+    compiler: &FCMC,
+    source_path: impl AsRef<Path>,
+    reference: &ReferenceCircuit,
+    samples: usize,
+) -> Result<ReferenceReport, FCMCError> {
+    let source_path = source_path.as_ref();
+    let source = std::fs::read_to_string(source_path)
+        .map_err(|e| FCMCError::VerificationError(format!("Failed to read {}: {}", source_path.display(), e)))?;
+    let compiled = compiler.compile(&source)?;
+    let bytes = std::fs::read(&reference.r1cs)
+        .map_err(|e| FCMCError::VerificationError(format!("Failed to read {}: {}", reference.r1cs.display(), e)))?;
+    let r1cs = read_r1cs(&bytes)?;
+    if r1cs.modulus != compiled.in_field(crate::optimization::field::modulus) {
+        return Err(FCMCError::VerificationError(format!(
+            "{} is over another field than {} ({})",
+            reference.r1cs.display(),
+            compiled.field,
+            source_path.display()
+        )));
+    }
+
+    let dir = std::env::temp_dir().join(format!("fcmc-reference-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| FCMCError::VerificationError(format!("Failed to create {}: {}", dir.display(), e)))?;
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let mut rng = StdRng::seed_from_u64(hasher.finish());
+    let mut report = ReferenceReport::default();
+    let result = (0..samples).try_for_each(|_| {
+        let inputs = compiled.in_field(|| sample_inputs(&compiled.ir, &mut rng));
+        let expected = reference
+            .witness(&inputs, &dir)?
+            .filter(|witness| compiled.in_field(|| r1cs.is_satisfied(witness)));
+        match (expected, compiled.compute_witness(&inputs)) {
+            (Some(expected), Ok(witness)) => {
+                let outputs: Vec<BigUint> = witness.outputs.into_iter().map(|(_, value)| value).collect();
+                if outputs != r1cs.outputs(&expected) {
+                    return Err(mismatch(
+                        source_path,
+                        &inputs,
+                        &format!("outputs {:?} instead of {:?}", outputs, r1cs.outputs(&expected)),
+                    ));
+                }
+                report.accepted += 1;
+            }
+            (None, Err(_)) => report.rejected += 1,
+            (Some(_), Err(e)) => {
+                return Err(mismatch(
+                    source_path,
+                    &inputs,
+                    &format!("rejects what the reference accepts: {}", e),
+                ));
+            }
+            (None, Ok(_)) => {
+                return Err(mismatch(source_path, &inputs, "accepts what the reference rejects"));
+            }
+        }
+        Ok(())
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    result.map(|_| report)
+}
+
+/// `check_against_reference` with the default compiler and sample count, panicking on a
+/// difference
+pub fn assert_matches_reference(source_path: impl AsRef<Path>, reference: &ReferenceCircuit) {
+    if let Err(e) = check_against_reference(&FCMC::new(), source_path, reference, DEFAULT_REFERENCE_SAMPLES) {
+        panic!("{}", e);
+    }
+}
+
+fn mismatch(source_path: &Path, inputs: &InputMap, detail: &str) -> FCMCError {
+    let mut assignment: Vec<String> = inputs
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    assignment.sort();
+    FCMCError::VerificationError(format!(
+        "{} differs from its reference on inputs [{}]: {}",
+        source_path.display(),
+        assignment.join(", "),
+        detail
+    ))
+}
+
+/// A random value for each input of `graph` within its declared type or `bits` attribute
+fn sample_inputs(graph: &IRGraph, rng: &mut StdRng) -> InputMap {
+    graph
+        .live_nodes()
+        .filter_map(|node| {
+            let name = match &node.node_type {
+                IRNodeType::Input(name) | IRNodeType::PrivateInput(name) => name,
+                _ => return None,
+            };
+            let bits = node.attributes.get("bits").and_then(|bits| bits.parse::<u32>().ok());
+            let value = match (&node.data_type, bits) {
+                (Type::Bool, _) => BigUint::from(rng.gen_bool(0.5) as u8),
+                (_, Some(bits)) if bits < 64 => BigUint::from(rng.gen_range(0..1u64 << bits)),
+                (Type::U32, _) => BigUint::from(rng.gen::<u32>()),
+                _ => random_value(rng),
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// `inputs` as circom input JSON: decimal strings, with `xs[i]` gathered into an array `xs`
+fn input_json(inputs: &InputMap) -> Value {
+    let mut sorted: Vec<(&String, &BigUint)> = inputs.iter().collect();
+    sorted.sort();
+    let mut arrays: Map<String, Value> = Map::new();
+    let mut elements: Vec<(String, usize, String)> = Vec::new();
+    for (name, value) in sorted {
+        let element = name
+            .strip_suffix(']')
+            .and_then(|name| name.split_once('['))
+            .and_then(|(base, index)| Some((base, index.parse::<usize>().ok()?)));
+        match element {
+            Some((base, index)) => elements.push((base.to_string(), index, value.to_string())),
+            None => {
+                arrays.insert(name.clone(), Value::String(value.to_string()));
+            }
+        }
+    }
+    elements.sort_by_key(|(base, index, _)| (base.clone(), *index));
+    for (base, _, value) in elements {
+        let entry = arrays.entry(base).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(values) = entry {
+            values.push(Value::String(value));
+        }
+    }
+    Value::Object(arrays)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line diff of `expected` against `actual`: `-` lines only in the golden file, `+` lines
+/// only in the new snapshot, each run of changes under an `@@ line N @@` header with a
+/// few unchanged lines around it
+pub fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    let lines = align(&old, &new);
+
+    // Ranges of entries to show, each change with its context, overlapping ranges merged
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Same(_)) {
+            continue;
+        }
+        let start = index.saturating_sub(DIFF_CONTEXT);
+        let end = (index + DIFF_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    // Line number in the golden file of each entry
+    let mut old_lines = Vec::with_capacity(lines.len());
+    let mut old_line = 1;
+    for line in &lines {
+        old_lines.push(old_line);
+        if !matches!(line, Line::Added(_)) {
+            old_line += 1;
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end) in hunks {
+        out.push_str(&format!("@@ line {} @@\n", old_lines[start]));
+        for line in &lines[start..end] {
+            match line {
+                Line::Same(text) => out.push_str(&format!("  {}\n", text)),
+                Line::Removed(text) => out.push_str(&format!("- {}\n", text)),
+                Line::Added(text) => out.push_str(&format!("+ {}\n", text)),
+            }
+        }
+    }
+    out
+}
+
+/// Longest-common-subsequence alignment of the lines between the common prefix and suffix
+fn align<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines: Vec<Line> = old[..prefix].iter().map(|line| Line::Same(line)).collect();
+    if old_middle.len() * new_middle.len() > MAX_ALIGNED_CELLS {
+        lines.extend(old_middle.iter().map(|line| Line::Removed(line)));
+        lines.extend(new_middle.iter().map(|line| Line::Added(line)));
+    } else {
+        // common[i][j]: length of the LCS of old_middle[i..] and new_middle[j..]
+        let mut common = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+        for i in (0..old_middle.len()).rev() {
+            for j in (0..new_middle.len()).rev() {
+                common[i][j] = if old_middle[i] == new_middle[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_middle.len() || j < new_middle.len() {
+            if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+                lines.push(Line::Same(old_middle[i]));
+                i += 1;
+                j += 1;
+            } else if j == new_middle.len() || (i < old_middle.len() && common[i + 1][j] >= common[i][j + 1]) {
+                lines.push(Line::Removed(old_middle[i]));
+                i += 1;
+            } else {
+                lines.push(Line::Added(new_middle[j]));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|line| Line::Same(line)));
+    lines
+}
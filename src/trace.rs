@@ -0,0 +1,122 @@
+//! Step-through evaluation of a circuit's constraints, for debugging a failing proof.
+//!
+//! The IR is evaluated on the given inputs and its constraints, range checks and lookups
+//! are then visited in source order, each step holding whether the check held, the
+//! values of the wires it reads and the assertion it comes from. A debugger walks the
+//! steps, or jumps to the first that fails.
+
+use crate::backend::ConstraintTag;
+use crate::ir::{IRNodeType, SourceSpan};
+use crate::optimization::interpreter::evaluate;
+use crate::witness::{check_input_names, operand_values};
+use crate::{CompiledCircuit, FCMCError, InputMap};
+use num_bigint::BigUint;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// IR node of the check
+    pub node: usize,
+    /// Source position, gadget and message of the assertion the check comes from
+    pub tag: ConstraintTag,
+    pub holds: bool,
+    /// Name and value of each wire the check reads
+    pub values: Vec<(String, BigUint)>,
+}
+
+impl TraceStep {
+    pub fn describe(&self) -> String {
+        let location = self
+            .tag
+            .span
+            .map(|span| span.to_string())
+            .unwrap_or_else(|| format!("node {}", self.node));
+        let origin = self
+            .tag
+            .message
+            .clone()
+            .or_else(|| self.tag.gadget.clone().map(|gadget| format!("in {}", gadget)))
+            .map(|origin| format!(" ({})", origin))
+            .unwrap_or_default();
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+        format!(
+            "{}{}: {} with {}",
+            location,
+            origin,
+            if self.holds { "holds" } else { "FAILS" },
+            values.join(", ")
+        )
+    }
+}
+
+/// Every check of a circuit on one input assignment, in source order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConstraintTrace {
+    pub steps: Vec<TraceStep>,
+    /// Output name and value, in node order
+    pub outputs: Vec<(String, BigUint)>,
+}
+
+impl ConstraintTrace {
+    pub fn first_failure(&self) -> Option<&TraceStep> {
+        self.steps.iter().find(|step| !step.holds)
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.first_failure().is_none()
+    }
+
+    /// The steps coming from source line `line`
+    pub fn at_line(&self, line: usize) -> impl Iterator<Item = &TraceStep> {
+        self.steps
+            .iter()
+            .filter(move |step| step.tag.span.map_or(false, |span| span.line == line))
+    }
+}
+
+impl IntoIterator for ConstraintTrace {
+    type Item = TraceStep;
+    type IntoIter = std::vec::IntoIter<TraceStep>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.steps.into_iter()
+    }
+}
+
+pub(crate) fn trace(compiled: &CompiledCircuit, inputs: &InputMap) -> Result<ConstraintTrace, FCMCError> {
+    check_input_names(compiled, inputs)?;
+    let graph = &compiled.ir;
+    let evaluation = compiled.in_field(|| evaluate(graph, inputs))?;
+    let violated: HashSet<usize> = evaluation.violated.iter().copied().collect();
+
+    // Checks without a source position come last, in node order
+    let mut checks: Vec<(Option<SourceSpan>, usize)> = graph
+        .live_nodes()
+        .filter(|node| {
+            matches!(
+                node.node_type,
+                IRNodeType::Constraint(_) | IRNodeType::RangeCheck | IRNodeType::Lookup
+            )
+        })
+        .map(|node| (graph.span(node.id), node.id))
+        .collect();
+    checks.sort_by_key(|&(span, node_id)| (span.is_none(), span, node_id));
+
+    let steps = checks
+        .into_iter()
+        .map(|(_, node_id)| TraceStep {
+            node: node_id,
+            tag: ConstraintTag::of(graph, node_id).unwrap_or_default(),
+            holds: !violated.contains(&node_id),
+            values: operand_values(graph, &evaluation, node_id),
+        })
+        .collect();
+    Ok(ConstraintTrace {
+        steps,
+        outputs: evaluation.outputs,
+    })
+}
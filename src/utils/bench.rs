@@ -0,0 +1,3 @@
+//! Corpus benchmarks with a size-regression baseline, re-exported from `crate::bench`
+
+pub use crate::bench::*;
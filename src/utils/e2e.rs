@@ -0,0 +1,3 @@
+//! Groth16 compile, prove and verify for integration tests, re-exported from `crate::e2e`
+
+pub use crate::e2e::*;
@@ -0,0 +1,3 @@
+//! Differential fuzzing of the compiler, re-exported from `crate::fuzz`
+
+pub use crate::fuzz::*;
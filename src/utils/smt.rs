@@ -0,0 +1,12 @@
+//! SMT-LIB export of a compiled circuit's constraints for auditors; the encoding lives in
+//! `crate::backend::export::smt`.
+
+use crate::{CompiledCircuit, FCMCError};
+
+pub use crate::backend::export::smt::{smt_source, InputSorts};
+
+/// SMT-LIB encoding of the rank-1 constraints `circuit` lowers to, every input a field
+/// element; `CompiledCircuit::export_smt` takes the input sorts
+pub fn export(circuit: &CompiledCircuit) -> Result<String, FCMCError> {
+    circuit.export_smt(InputSorts::Field)
+}
@@ -0,0 +1,3 @@
+//! Golden-file snapshots of compiled circuits, re-exported from `crate::testing`
+
+pub use crate::testing::*;
@@ -0,0 +1,95 @@
+//! JavaScript bindings for running the compiler in a browser, with the `wasm` feature.
+//!
+//! Build for `wasm32-unknown-unknown` without default features, so nothing starts
+//! threads, and run `wasm-bindgen` or `wasm-pack` on the result:
+//!
+//! ```sh
+//! wasm-pack build --target web -- --no-default-features --features wasm
+//! ```
+//!
+//! Circuits live in the wasm heap as `WasmCircuit` objects; `toBytes` and `fromBytes`
+//! move them through the FCMC binary format instead of the filesystem. Inputs and
+//! witnesses are JSON, with field elements as decimal strings.
+
+use crate::backend::CircuitBackend;
+use crate::optimization::field::FieldConfig;
+use crate::{artifact, witness, CompiledCircuit, TargetSystem, FCMC};
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+/// A compiled circuit
+#[wasm_bindgen]
+pub struct WasmCircuit {
+    compiled: CompiledCircuit,
+}
+
+/// Compile `source` for `target` (`"r1cs"` if omitted) at `optimizationLevel` (2 if
+/// omitted) over `field` (the target's default if omitted)
+#[wasm_bindgen(js_name = compileSource)]
+pub fn compile_source(
+    source: &str,
+    target: Option<String>,
+    optimization_level: Option<u8>,
+    field: Option<String>,
+) -> Result<WasmCircuit, JsError> {
+    let mut compiler = FCMC::new();
+    if let Some(target) = target {
+        compiler = compiler.with_target(target.parse::<TargetSystem>()?);
+    }
+    if let Some(level) = optimization_level {
+        compiler = compiler.with_optimization_level(level);
+    }
+    if let Some(field) = field {
+        compiler = compiler.with_field(field.parse::<FieldConfig>()?);
+    }
+    Ok(WasmCircuit {
+        compiled: compiler.compile(source)?,
+    })
+}
+
+#[wasm_bindgen]
+impl WasmCircuit {
+    /// A circuit from the bytes `toBytes` returned
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmCircuit, JsError> {
+        Ok(Self {
+            compiled: artifact::decode(bytes)?,
+        })
+    }
+
+    /// The circuit in FCMC's binary format, as `fcmc compile` writes it
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        artifact::encode(&self.compiled)
+    }
+
+    #[wasm_bindgen(getter, js_name = constraintCount)]
+    pub fn constraint_count(&self) -> usize {
+        self.compiled.circuit.constraint_count()
+    }
+
+    /// The circuit in its target's JSON form
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        self.compiled.in_field(|| self.compiled.circuit.to_json()).to_string()
+    }
+
+    /// The witness for the inputs in the JSON object `inputs`, as JSON with every wire
+    /// under `witness` and the outputs by name under `outputs`
+    #[wasm_bindgen(js_name = computeWitness)]
+    pub fn compute_witness(&self, inputs: &str) -> Result<String, JsError> {
+        let inputs: Value = serde_json::from_str(inputs)?;
+        let inputs = self.compiled.in_field(|| witness::inputs_from_json(&inputs))?;
+        let witness = self.compiled.compute_witness(&inputs)?;
+        let outputs: serde_json::Map<String, Value> = witness
+            .outputs
+            .iter()
+            .map(|(name, value)| (name.clone(), json!(value.to_string())))
+            .collect();
+        Ok(json!({
+            "witness": witness.values.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "outputs": outputs,
+        })
+        .to_string())
+    }
+}
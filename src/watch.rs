@@ -0,0 +1,169 @@
+//! Incremental recompilation, for watch mode.
+//!
+//! The pipeline inlines and optimizes a program as a whole, so the unit of reuse is the
+//! compiled circuit. Each function, each top-level constraint and the property
+//! annotations are fingerprinted from their parsed form, and a change to the source files
+//! recompiles only when a fingerprint the entry point can reach has changed: an edit to a
+//! comment, or to a function nothing the entry point calls uses, keeps the circuit as it
+//! was without optimizing or lowering again.
+
+use crate::backend::CircuitBackend;
+use crate::frontend::parse_source;
+use crate::language::ast::Program;
+use crate::optimization::{extract_properties, CallGraph};
+use crate::platform::Instant;
+use crate::{CompiledCircuit, FCMCError, FCMC};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Key of the fingerprint covering the top-level constraints and property annotations
+const GLOBALS: &str = "constraints and properties";
+
+/// What a call to `recompile` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recompilation {
+    /// Functions whose fingerprints changed, and `GLOBALS` if the constraints or
+    /// properties did, in name order
+    pub changed: Vec<String>,
+    /// Whether the circuit was compiled again rather than kept
+    pub recompiled: bool,
+    pub constraints: usize,
+    /// Constraints in the circuit before, `None` for the first compilation
+    pub previous_constraints: Option<usize>,
+    pub elapsed: Duration,
+}
+
+/// A compiler that keeps its last circuit and the fingerprints it was compiled from
+pub struct IncrementalCompiler {
+    compiler: FCMC,
+    /// The entry point's file, then files appended to it, as `Project` assembles them
+    files: Vec<PathBuf>,
+    sources: HashMap<PathBuf, String>,
+    fingerprints: HashMap<String, u64>,
+    reachable: HashSet<String>,
+    circuit: Option<CompiledCircuit>,
+}
+
+impl IncrementalCompiler {
+    pub fn new(compiler: FCMC, files: Vec<PathBuf>) -> Self {
+        Self {
+            compiler,
+            files,
+            sources: HashMap::new(),
+            fingerprints: HashMap::new(),
+            reachable: HashSet::new(),
+            circuit: None,
+        }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn compiler(&self) -> &FCMC {
+        &self.compiler
+    }
+
+    /// The last circuit compiled, `None` before the first successful compilation
+    pub fn circuit(&self) -> Option<&CompiledCircuit> {
+        self.circuit.as_ref()
+    }
+
+    /// Compile every file from scratch
+    pub fn compile(&mut self) -> Result<Recompilation, FCMCError> {
+        self.sources.clear();
+        self.fingerprints.clear();
+        self.reachable.clear();
+        let files = self.files.clone();
+        self.recompile(&files)
+    }
+
+    /// Read `changed_files` again and recompile if that changed anything the entry point
+    /// reaches. Files that are not part of the program are ignored. On an error the
+    /// previous circuit and fingerprints are kept, so the next call compares against them.
+    pub fn recompile(&mut self, changed_files: &[PathBuf]) -> Result<Recompilation, FCMCError> {
+        let start = Instant::now();
+        let mut sources = self.sources.clone();
+        for path in changed_files.iter().filter(|path| self.files.contains(path)) {
+            sources.insert(path.clone(), read(path)?);
+        }
+        let mut source = String::new();
+        for (index, path) in self.files.iter().enumerate() {
+            if index > 0 {
+                source.push('\n');
+            }
+            match sources.get(path) {
+                Some(text) => source.push_str(text),
+                None => source.push_str(&read(path)?),
+            }
+        }
+
+        let (stripped, properties) = extract_properties(&source)?;
+        let program = parse_source(&stripped)?;
+        let mut fingerprints: HashMap<String, u64> = program
+            .functions
+            .iter()
+            .map(|function| (function.name.clone(), fingerprint(function)))
+            .collect();
+        fingerprints.insert(GLOBALS.to_string(), fingerprint(&(&program.constraints, &properties)));
+        let reachable = reachable(&program);
+
+        let mut changed: Vec<String> = reachable
+            .union(&self.reachable)
+            .filter(|item| self.fingerprints.get(*item) != fingerprints.get(*item))
+            .cloned()
+            .collect();
+        changed.sort();
+
+        let previous_constraints = self.circuit.as_ref().map(|circuit| circuit.circuit.constraint_count());
+        let recompiled = self.circuit.is_none() || !changed.is_empty();
+        if recompiled {
+            self.circuit = Some(self.compiler.compile(&source)?);
+        }
+        for path in &self.files {
+            if let Some(text) = sources.remove(path) {
+                self.sources.insert(path.clone(), text);
+            }
+        }
+        self.fingerprints = fingerprints;
+        self.reachable = reachable;
+
+        Ok(Recompilation {
+            changed,
+            recompiled,
+            constraints: self
+                .circuit
+                .as_ref()
+                .map_or(0, |circuit| circuit.circuit.constraint_count()),
+            previous_constraints,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+/// The entry point, every function it calls directly or not, and `GLOBALS`
+fn reachable(program: &Program) -> HashSet<String> {
+    let calls = CallGraph::from_program(program);
+    let mut reachable = HashSet::from([GLOBALS.to_string()]);
+    let mut stack = vec![program.entry_point.clone()];
+    while let Some(function) = stack.pop() {
+        if reachable.insert(function.clone()) {
+            stack.extend(calls.callees(&function).iter().cloned());
+        }
+    }
+    reachable
+}
+
+fn fingerprint(item: &impl std::fmt::Debug) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", item).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn read(path: &Path) -> Result<String, FCMCError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| FCMCError::ParseError(format!("Failed to read {}: {}", path.display(), e)))
+}
@@ -0,0 +1,154 @@
+//! Witness computation from named inputs.
+//!
+//! The IR is evaluated over the circuit's field, which both computes every node and checks
+//! the source's own assertions, then the backend's witness program fills in every wire and
+//! each constraint row is checked. Either failure names the first constraint that does
+//! not hold, where in the source it comes from, and the values it saw.
+//!
+//! Named inputs can also be read from a JSON object in circom's input file layout.
+
+use crate::backend::{CircuitBackend, ConstraintTag};
+use crate::ir::IRGraph;
+use crate::optimization::field;
+use crate::optimization::interpreter::{evaluate, input_names, Evaluation};
+use crate::{CompiledCircuit, FCMCError};
+use num_bigint::BigUint;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Values of the circuit's inputs, public and private, by name
+pub type InputMap = HashMap<String, BigUint>;
+
+/// A full assignment of the circuit's wires
+#[derive(Debug, Clone, PartialEq)]
+pub struct Witness {
+    /// Every wire in witness order, the constant one first
+    pub values: Vec<BigUint>,
+    /// Output name and value, in node order
+    pub outputs: Vec<(String, BigUint)>,
+}
+
+impl Witness {
+    pub fn output(&self, name: &str) -> Option<&BigUint> {
+        self.outputs
+            .iter()
+            .find(|(output, _)| output == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Inputs from a JSON object shaped like circom's input files: each input by name, an
+/// array input as an array of its elements (`xs` gives `xs[0]`, `xs[1]` and so on), and
+/// each value a number, a boolean, or a string holding a decimal integer, possibly
+/// negative, or a `0x` hexadecimal one. Values are reduced into the active field.
+pub fn inputs_from_json(json: &Value) -> Result<InputMap, FCMCError> {
+    let object = json
+        .as_object()
+        .ok_or_else(|| FCMCError::ParseError("Inputs must be an object of values by name".to_string()))?;
+    let mut inputs = InputMap::new();
+    for (name, value) in object {
+        add_input(&mut inputs, name.clone(), value)?;
+    }
+    Ok(inputs)
+}
+
+fn add_input(inputs: &mut InputMap, name: String, value: &Value) -> Result<(), FCMCError> {
+    let element = match value {
+        Value::Array(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                add_input(inputs, format!("{}[{}]", name, index), element)?;
+            }
+            return Ok(());
+        }
+        Value::Bool(value) => Some(BigUint::from(*value as u8)),
+        Value::Number(number) => field::parse_element(&number.to_string()),
+        Value::String(text) => match text.strip_prefix("0x") {
+            Some(hex) => BigUint::parse_bytes(hex.as_bytes(), 16).map(|value| value % field::modulus()),
+            None => field::parse_element(text),
+        },
+        _ => None,
+    };
+    let element = element
+        .ok_or_else(|| FCMCError::ParseError(format!("Input '{}' is {}, not an integer", name, value)))?;
+    inputs.insert(name, element);
+    Ok(())
+}
+
+pub(crate) fn compute_witness(compiled: &CompiledCircuit, inputs: &InputMap) -> Result<Witness, FCMCError> {
+    check_input_names(compiled, inputs)?;
+    let evaluation = compiled.in_field(|| evaluate(&compiled.ir, inputs))?;
+    if let Some(&node_id) = evaluation.violated.first() {
+        let values = operand_values(&compiled.ir, &evaluation, node_id);
+        let tag = ConstraintTag::of(&compiled.ir, node_id).unwrap_or_default();
+        return Err(unsatisfied(&format!("node {}", node_id), tag, &values));
+    }
+
+    let values = compiled.circuit.assign(&evaluation.values)?;
+    if !compiled.circuit.is_satisfied(&values) {
+        return Err(match compiled.circuit.unsatisfied_row(&values) {
+            Some((row, conflicting)) => {
+                let tag = compiled.circuit.constraint_tags().get(row).copied().flatten().cloned();
+                unsatisfied(&format!("row {}", row), tag.unwrap_or_default(), &conflicting)
+            }
+            None => FCMCError::VerificationError("Inputs do not satisfy the circuit's constraints".to_string()),
+        });
+    }
+
+    Ok(Witness {
+        values,
+        outputs: evaluation.outputs,
+    })
+}
+
+/// Refuse inputs the circuit does not have, which are most likely misspelt
+pub(crate) fn check_input_names(compiled: &CompiledCircuit, inputs: &InputMap) -> Result<(), FCMCError> {
+    let known = input_names(&compiled.ir);
+    let mut unknown: Vec<&String> = inputs.keys().filter(|name| !known.contains(name)).collect();
+    unknown.sort();
+    match unknown.first() {
+        Some(name) => Err(FCMCError::VerificationError(format!("The circuit has no input '{}'", name))),
+        None => Ok(()),
+    }
+}
+
+/// Name and value of each operand of `node_id`, named by its label where it has one
+pub(crate) fn operand_values(graph: &IRGraph, evaluation: &Evaluation, node_id: usize) -> Vec<(String, BigUint)> {
+    graph
+        .get_predecessors(node_id)
+        .into_iter()
+        .map(|operand| {
+            let name = graph
+                .get_node(operand)
+                .and_then(|node| node.label.clone())
+                .unwrap_or_else(|| format!("node {}", operand));
+            let value = evaluation.values.get(&operand).cloned().unwrap_or_default();
+            (name, value)
+        })
+        .collect()
+}
+
+/// The error for the failed constraint `what`, with where it comes from and the values it read
+fn unsatisfied(what: &str, tag: ConstraintTag, values: &[(String, BigUint)]) -> FCMCError {
+    let context: Vec<String> = tag
+        .span
+        .map(|span| span.to_string())
+        .into_iter()
+        .chain(tag.gadget.map(|gadget| format!("in {}", gadget)))
+        .chain(tag.message)
+        .collect();
+    let context = if context.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", context.join(", "))
+    };
+    let values: Vec<String> = values
+        .iter()
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect();
+    FCMCError::VerificationError(format!(
+        "Constraint {}{} is not satisfied: {}",
+        what,
+        context,
+        values.join(", ")
+    ))
+}
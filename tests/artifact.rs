@@ -0,0 +1,42 @@
+//! A circuit saved with `CompiledCircuit::save` loads back without recompiling
+
+use fcmc_compiler::backend::CircuitBackend;
+use fcmc_compiler::{artifact, CompiledCircuit, InputMap, FCMC};
+use num_bigint::BigUint;
+
+#[test]
+fn saved_circuit_loads_unchanged() {
+    let circuit = FCMC::new()
+        .compile(
+            "fn main(x: field, y: field) -> field {
+                let z: field = x * y + 3;
+                return z * z + x;
+            }",
+        )
+        .unwrap();
+    let inputs: InputMap = [("x", 5u8), ("y", 7u8)]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), BigUint::from(value)))
+        .collect();
+    let path = std::env::temp_dir().join(format!("fcmc-artifact-{}.fcmc", std::process::id()));
+    circuit.save(&path).unwrap();
+    let loaded = CompiledCircuit::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    let loaded = loaded.unwrap();
+
+    assert_eq!(artifact::encode(&loaded), artifact::encode(&circuit));
+    assert_eq!(loaded.signature(), circuit.signature());
+    assert_eq!(loaded.stats.optimization_ratio(), circuit.stats.optimization_ratio());
+    let witness = loaded.compute_witness(&inputs).unwrap();
+    assert_eq!(witness.values, circuit.compute_witness(&inputs).unwrap().values);
+    assert!(loaded.circuit.is_satisfied(&witness.values));
+}
+
+#[test]
+fn truncated_artifact_is_rejected() {
+    let circuit = FCMC::new()
+        .compile("fn main(x: field) -> field { return x * x; }")
+        .unwrap();
+    let bytes = artifact::encode(&circuit);
+    assert!(artifact::decode(&bytes[..bytes.len() / 2]).is_err());
+}
@@ -0,0 +1,52 @@
+//! Every built-in backend accepts the witness FCMC computes for a small circuit and
+//! rejects it once a wire is tampered with
+
+use fcmc_compiler::backend::{CircuitBackend, TargetSystem};
+use fcmc_compiler::{InputMap, FCMC};
+use num_bigint::BigUint;
+
+const SOURCE: &str = "fn main(x: field, y: field) -> field {
+    let z: field = x * y + 3;
+    return z * z + x;
+}";
+
+fn check_target(target: TargetSystem) {
+    let circuit = FCMC::new().with_target(target.clone()).compile(SOURCE).unwrap();
+    let inputs: InputMap = [("x", 5u8), ("y", 7u8)]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), BigUint::from(value)))
+        .collect();
+    let witness = circuit.compute_witness(&inputs).unwrap();
+    assert_eq!(witness.outputs[0].1, BigUint::from(38u32 * 38 + 5), "{:?}", target);
+    assert!(circuit.circuit.is_satisfied(&witness.values), "{:?}", target);
+
+    // Wire 1 is the output in every backend's witness order
+    let mut tampered = witness.values.clone();
+    tampered[1] = (&tampered[1] + 1u8) % circuit.field.modulus();
+    assert!(!circuit.circuit.is_satisfied(&tampered), "{:?}", target);
+}
+
+#[test]
+fn plonk() {
+    check_target(TargetSystem::Plonk);
+}
+
+#[test]
+fn air() {
+    check_target(TargetSystem::AIR);
+}
+
+#[test]
+fn acir() {
+    check_target(TargetSystem::ACIR);
+}
+
+#[test]
+fn plonky2() {
+    check_target(TargetSystem::Plonky2);
+}
+
+#[test]
+fn ccs() {
+    check_target(TargetSystem::CCS);
+}
@@ -0,0 +1,15 @@
+//! Every program of the benchmark corpus compiles
+
+use fcmc_compiler::backend::CircuitBackend;
+use fcmc_compiler::bench::CORPUS;
+use fcmc_compiler::FCMC;
+
+#[test]
+fn corpus_compiles() {
+    for (name, source) in CORPUS {
+        let circuit = FCMC::new()
+            .compile(source)
+            .unwrap_or_else(|e| panic!("{}: {}", name, e));
+        assert!(circuit.circuit.constraint_count() > 0, "{} has no constraints", name);
+    }
+}
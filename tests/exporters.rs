@@ -0,0 +1,117 @@
+//! The files each exporter writes for one small circuit, checked against the circuit
+//! they were generated from
+
+use fcmc_compiler::backend::export::plan::WitnessPlan;
+use fcmc_compiler::backend::export::{c, circom, gnark, halo2, rust, wasm};
+use fcmc_compiler::backend::CircuitBackend;
+use fcmc_compiler::{CompiledCircuit, InputMap, TargetSystem, FCMC};
+use num_bigint::BigUint;
+
+const SOURCE: &str = "fn main(x: field, y: field) -> field {
+    let z: field = x * y + 3;
+    return z * z + x;
+}";
+
+fn compile(target: TargetSystem) -> CompiledCircuit {
+    FCMC::new().with_target(target).compile(SOURCE).unwrap()
+}
+
+fn inputs() -> InputMap {
+    [("x", 5u8), ("y", 7u8)]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), BigUint::from(value)))
+        .collect()
+}
+
+#[test]
+fn circom_files_read_back() {
+    let circuit = compile(TargetSystem::R1CS);
+    let r1cs = circuit.circuit.as_r1cs().unwrap();
+    let read = circom::read_r1cs(&circom::r1cs_bytes(r1cs)).unwrap();
+    assert_eq!(read.modulus, circuit.field.modulus());
+    assert_eq!(read.wires, r1cs.wires.len());
+    assert_eq!(read.public_outputs, 1);
+    assert_eq!(read.public_inputs + read.private_inputs, 2);
+    assert_eq!(read.constraints, r1cs.constraints);
+
+    let witness = circuit.compute_witness(&inputs()).unwrap();
+    assert!(circuit.in_field(|| read.is_satisfied(&witness.values)));
+    let wtns = circom::wtns_bytes(&witness.values, &read.modulus);
+    assert_eq!(circom::read_wtns(&wtns).unwrap(), (read.modulus, witness.values));
+
+    let sym = circom::sym_file(r1cs);
+    assert_eq!(sym.lines().count(), r1cs.wires.len() - 1);
+    assert!(sym.starts_with(&format!("1,1,0,main.{}\n", r1cs.wire_names[1])));
+}
+
+#[test]
+fn gnark_package_asserts_every_row() {
+    let circuit = compile(TargetSystem::R1CS);
+    let r1cs = circuit.circuit.as_r1cs().unwrap();
+    let source = gnark::gnark_source(r1cs, "square").unwrap();
+    assert!(source.starts_with("// Code generated by FCMC. DO NOT EDIT.\n"));
+    assert!(source.contains("\npackage square\n"));
+    assert!(source.contains("const Curve = ecc."));
+    assert!(source.contains(&format!("const Wires = {}\n", r1cs.wires.len())));
+    let public = format!(
+        "\tPublic  [{}]frontend.Variable `gnark:\",public\"`\n",
+        r1cs.public_count()
+    );
+    assert!(source.contains(&public));
+    assert_eq!(
+        source.matches("\tapi.AssertIsEqual(api.Mul(").count(),
+        r1cs.constraints.len()
+    );
+}
+
+#[test]
+fn halo2_module_lists_every_row() {
+    let circuit = compile(TargetSystem::Halo2);
+    let plonk = circuit.circuit.as_plonk().unwrap();
+    let module = halo2::halo2_module(plonk, &circuit.ir, "square");
+    assert!(module.starts_with("//! halo2 circuit `square`, generated by FCMC. Do not edit.\n"));
+    assert!(module.contains(&format!("pub const VARIABLES: usize = {};\n", plonk.variables.len())));
+    let rows = plonk.gates.len();
+    assert!(module.contains(&format!("pub const ROWS: [[usize; 3]; {}] = [\n", rows)));
+    assert!(module.contains(&format!("pub const SELECTORS: [[&str; 5]; {}] = [\n", rows)));
+    for gate in &plonk.gates {
+        assert!(module.contains(&format!("    {:?},\n", gate.wires)));
+    }
+}
+
+#[test]
+fn c_calculator_declares_and_defines_the_entry_point() {
+    let circuit = compile(TargetSystem::R1CS);
+    let plan = WitnessPlan::new(&circuit).unwrap();
+    let header = c::witness_header(&plan, "square");
+    assert!(header.contains("#ifndef SQUARE_H\n#define SQUARE_H\n"));
+    assert!(header.contains(&format!("#define SQUARE_WIRES {}\n", plan.wires.len())));
+    assert!(header.contains("#define SQUARE_INPUTS 2\n"));
+    assert!(header.contains(" *   0: x\n *   1: y\n"));
+    assert!(header.contains("int square_compute_witness(const fcmc_fe *inputs, fcmc_fe *witness);\n"));
+
+    let source = c::witness_source(&plan, "square");
+    assert!(source.contains("\nint square_compute_witness(const fcmc_fe *inputs, fcmc_fe *w)\n{"));
+    assert!(source.contains("= inputs[0];"));
+    assert!(source.contains("= inputs[1];"));
+}
+
+#[test]
+fn rust_module_exposes_inputs_and_the_witness() {
+    let circuit = compile(TargetSystem::R1CS);
+    let plan = WitnessPlan::new(&circuit).unwrap();
+    let module = rust::witness_module(&plan, "square");
+    assert!(module.contains(&format!("\npub const WIRES: usize = {};\n", plan.wires.len())));
+    assert!(module.contains("pub struct Inputs<F> {\n    /// `x`\n    pub x: F,\n    /// `y`\n    pub y: F,\n}\n"));
+    assert!(module.contains("pub fn compute_witness<F: PrimeField>(inputs: &Inputs<F>) -> Vec<F> {\n"));
+    assert!(module.contains("inputs.x") && module.contains("inputs.y"));
+}
+
+#[test]
+fn wasm_calculator_is_a_module() {
+    let circuit = compile(TargetSystem::R1CS);
+    let plan = WitnessPlan::new(&circuit).unwrap();
+    let bytes = wasm::witness_wasm(&plan);
+    assert_eq!(&bytes[..8], b"\0asm\x01\0\0\0");
+    assert_eq!(bytes, wasm::witness_wasm(&WitnessPlan::new(&circuit).unwrap()));
+}
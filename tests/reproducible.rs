@@ -0,0 +1,45 @@
+//! Compiling the same source twice, on any thread, saves to the same bytes
+
+use fcmc_compiler::{artifact, FCMC};
+
+/// Programs without standard library calls, so the test exercises only the core pipeline
+const SOURCES: &[&str] = &[
+    include_str!("../benches/corpus/polynomial.zk"),
+    "fn main(x: field, y: field, z: field) -> field {
+        let a: field = x * y + x * y;
+        let b: field = (x + y) * (x - y);
+        for i in 0..4 {
+            a = a * z + b;
+        }
+        return a + b;
+    }",
+];
+
+fn compiler() -> FCMC {
+    FCMC::new().with_optimization_level(3).with_reproducible(true)
+}
+
+#[test]
+fn compiling_twice_gives_identical_bytes() {
+    for source in SOURCES {
+        let first = artifact::encode(&compiler().compile(source).unwrap());
+        let second = std::thread::scope(|scope| {
+            scope
+                .spawn(|| artifact::encode(&compiler().compile(source).unwrap()))
+                .join()
+                .unwrap()
+        });
+        assert_eq!(first, second);
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn thread_pool_size_does_not_change_the_bytes() {
+    for source in SOURCES {
+        let first = artifact::encode(&compiler().compile(source).unwrap());
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+        let second = pool.install(|| artifact::encode(&compiler().compile(source).unwrap()));
+        assert_eq!(first, second);
+    }
+}
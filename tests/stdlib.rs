@@ -0,0 +1,278 @@
+//! Standard library calls compiled through `FCMC::compile`, with the witness checked
+//! against each gadget's native evaluation
+
+use fcmc_compiler::backend::CircuitBackend;
+use fcmc_compiler::optimization::field::FieldConfig;
+use fcmc_compiler::stdlib::{self, Curve, PoseidonParams};
+use fcmc_compiler::{InputMap, FCMC};
+use num_bigint::BigUint;
+
+/// Compile `source` over BN254, whose `main` passes its parameters in order and then the
+/// literals `constants` to the call `name`; check the circuit accepts `inputs` and
+/// outputs what the native evaluation gives, and return the outputs
+fn assert_matches_native(name: &str, source: &str, inputs: &[(&str, BigUint)], constants: &[u64]) -> Vec<BigUint> {
+    let circuit = FCMC::new().with_field(FieldConfig::Bn254).compile(source).unwrap();
+    let input_map: InputMap = inputs
+        .iter()
+        .map(|(input, value)| (input.to_string(), value.clone()))
+        .collect();
+    let witness = circuit.compute_witness(&input_map).unwrap();
+    assert!(circuit.circuit.is_satisfied(&witness.values));
+
+    let mut args: Vec<BigUint> = inputs.iter().map(|(_, value)| value.clone()).collect();
+    args.extend(constants.iter().map(|&constant| BigUint::from(constant)));
+    let expected = circuit
+        .in_field(|| stdlib::eval_call(name, &args))
+        .unwrap()
+        .unwrap()
+        .field_elements();
+    let actual: Vec<BigUint> = witness.outputs.into_iter().map(|(_, value)| value).collect();
+    assert_eq!(actual, expected, "{}", name);
+    actual
+}
+
+fn small(inputs: &[(&'static str, u64)]) -> Vec<(&'static str, BigUint)> {
+    inputs
+        .iter()
+        .map(|&(input, value)| (input, BigUint::from(value)))
+        .collect()
+}
+
+/// The four 64-bit limbs, least significant first, of the 256-bit hex value `hex`
+fn limbs(hex: &str) -> Vec<BigUint> {
+    let value = BigUint::parse_bytes(hex.as_bytes(), 16).unwrap();
+    (0..4)
+        .map(|i| (&value >> (64 * i)) % (BigUint::from(1u8) << 64))
+        .collect()
+}
+
+#[test]
+fn poseidon() {
+    assert_matches_native(
+        stdlib::POSEIDON,
+        "fn main(a: field, b: field, c: field) -> field {
+            return std::hash::poseidon([a, b, c]);
+        }",
+        &small(&[("a", 1), ("b", 2), ("c", 3)]),
+        &[],
+    );
+}
+
+#[test]
+fn sha256() {
+    let digest = assert_matches_native(
+        stdlib::SHA256,
+        "fn main(a: field, b: field, c: field) -> [field; 32] {
+            return std::hash::sha256([a, b, c]);
+        }",
+        &small(&[("a", b'a' as u64), ("b", b'b' as u64), ("c", b'c' as u64)]),
+        &[],
+    );
+    let bytes: Vec<u8> = digest.iter().map(|byte| byte.to_bytes_le()[0]).collect();
+    assert_eq!(bytes, stdlib::sha256(b"abc"));
+}
+
+#[test]
+fn keccak256() {
+    let digest = assert_matches_native(
+        stdlib::KECCAK256,
+        "fn main(a: field, b: field, c: field) -> [field; 32] {
+            return std::hash::keccak256([a, b, c]);
+        }",
+        &small(&[("a", b'a' as u64), ("b", b'b' as u64), ("c", b'c' as u64)]),
+        &[],
+    );
+    let bytes: Vec<u8> = digest.iter().map(|byte| byte.to_bytes_le()[0]).collect();
+    assert_eq!(bytes, stdlib::keccak256(b"abc"));
+}
+
+#[test]
+fn merkle_verify() {
+    let (leaf, path) = (BigUint::from(5u8), [BigUint::from(6u8), BigUint::from(7u8)]);
+    let root = FieldConfig::Bn254
+        .enter(|| stdlib::merkle::root(&PoseidonParams::for_inputs(2).unwrap(), &leaf, &path, &[true, false]).unwrap());
+    let source = "fn main(leaf: field, p0: field, p1: field, i0: bool, i1: bool, root: field) -> bool {
+        return std::merkle::verify(leaf, [p0, p1], [i0, i1], root);
+    }";
+    let mut inputs = vec![("leaf", leaf), ("p0", path[0].clone()), ("p1", path[1].clone())];
+    inputs.extend(small(&[("i0", 1), ("i1", 0)]));
+    inputs.push(("root", root));
+    assert_eq!(
+        assert_matches_native(stdlib::MERKLE_VERIFY, source, &inputs, &[]),
+        [BigUint::from(1u8)]
+    );
+}
+
+#[test]
+fn comparisons() {
+    let pair = small(&[("a", 70000), ("b", 5)]);
+    for (name, call) in [
+        (stdlib::LESS_THAN, "std::cmp::less_than"),
+        (stdlib::MIN, "std::cmp::min"),
+        (stdlib::MAX, "std::cmp::max"),
+    ] {
+        let source = format!("fn main(a: field, b: field) -> field {{ return {}(a, b, 32); }}", call);
+        assert_matches_native(name, &source, &pair, &[32]);
+    }
+    assert_matches_native(
+        stdlib::IS_ZERO,
+        "fn main(x: field) -> bool { return std::cmp::is_zero(x); }",
+        &small(&[("x", 0)]),
+        &[],
+    );
+    assert_matches_native(
+        stdlib::SORT,
+        "fn main(a: field, b: field, c: field) -> [field; 3] { return std::cmp::sort([a, b, c], 16); }",
+        &small(&[("a", 9), ("b", 2), ("c", 4)]),
+        &[16],
+    );
+}
+
+#[test]
+fn curve_operations() {
+    let (p, q) = FieldConfig::Bn254.enter(|| {
+        let curve = Curve::active().unwrap();
+        (
+            curve.mul_base(&BigUint::from(2u8)).unwrap(),
+            curve.mul_base(&BigUint::from(3u8)).unwrap(),
+        )
+    });
+    assert_matches_native(
+        stdlib::CURVE_MUL_BASE,
+        "fn main(s: field) -> [field; 2] { return std::curve::mul_base(s); }",
+        &small(&[("s", 7)]),
+        &[],
+    );
+    assert_matches_native(
+        stdlib::CURVE_ADD,
+        "fn main(px: field, py: field, qx: field, qy: field) -> [field; 2] {
+            return std::curve::add([px, py], [qx, qy]);
+        }",
+        &[("px", p.x.clone()), ("py", p.y.clone()), ("qx", q.x), ("qy", q.y)],
+        &[],
+    );
+    assert_matches_native(
+        stdlib::CURVE_DOUBLE,
+        "fn main(px: field, py: field) -> [field; 2] { return std::curve::double([px, py]); }",
+        &[("px", p.x), ("py", p.y)],
+        &[],
+    );
+}
+
+#[test]
+fn verify_eddsa() {
+    // Sign with a fixed key and nonce: S = k + h * 8 * secret
+    let (public_key, message, r, s) = FieldConfig::Bn254.enter(|| {
+        let curve = Curve::active().unwrap();
+        let (secret, nonce, message) = (BigUint::from(12345u32), BigUint::from(6789u32), BigUint::from(42u8));
+        let public_key = curve.mul_base(&secret).unwrap();
+        let r = curve.mul_base(&nonce).unwrap();
+        let challenge = stdlib::poseidon(&[
+            r.x.clone(),
+            r.y.clone(),
+            public_key.x.clone(),
+            public_key.y.clone(),
+            message.clone(),
+        ])
+        .unwrap();
+        let s = (nonce + challenge * 8u8 * secret) % &curve.order;
+        (public_key, message, r, s)
+    });
+    let source = "fn main(ax: field, ay: field, m: field, rx: field, ry: field, s: field) -> bool {
+        return std::sig::verify_eddsa([ax, ay], m, [rx, ry, s]);
+    }";
+    let inputs = [
+        ("ax", public_key.x),
+        ("ay", public_key.y),
+        ("m", message),
+        ("rx", r.x),
+        ("ry", r.y),
+        ("s", s),
+    ];
+    assert_eq!(
+        assert_matches_native(stdlib::VERIFY_EDDSA, source, &inputs, &[]),
+        [BigUint::from(1u8)]
+    );
+}
+
+#[test]
+fn verify_ecdsa() {
+    // A signature of SHA-256("sample") under a fixed key, made with an independent
+    // implementation: the key x then y, the hash, and the signature r then s
+    let values: Vec<BigUint> = [
+        "2c8c31fc9f990c6b55e3865a184a4ce50e09481f2eaeb3e60ec1cea13a6ae645",
+        "64b95e4fdb6948c0386e189b006a29f686769b011704275e4459822dc3328085",
+        "af2bdbe1aa9b6ec1e2ade1d694f41fc71a831d0268e9891562113d8a62add1bf",
+        "432310e32cb80eb6503a26ce83cc165c783b870845fb8aad6d970889fcd7a6c8",
+        "530128b6b81c548874a6305d93ed071ca6e05074d85863d4056ce89b02bfab69",
+    ]
+    .into_iter()
+    .flat_map(limbs)
+    .collect();
+    let names: Vec<String> = (0..values.len()).map(|i| format!("l{}", i)).collect();
+    let params: Vec<String> = names.iter().map(|name| format!("{}: field", name)).collect();
+    let source = format!(
+        "fn main({}) -> bool {{ return std::sig::verify_ecdsa([{}], [{}], [{}]); }}",
+        params.join(", "),
+        names[..8].join(", "),
+        names[8..12].join(", "),
+        names[12..].join(", ")
+    );
+    let inputs: Vec<(&str, BigUint)> = names.iter().map(String::as_str).zip(values).collect();
+    assert_eq!(
+        assert_matches_native(stdlib::VERIFY_ECDSA, &source, &inputs, &[]),
+        [BigUint::from(1u8)]
+    );
+}
+
+#[test]
+fn bigint() {
+    let max = u64::MAX;
+    assert_matches_native(
+        stdlib::BIGINT_ADD,
+        "fn main(a0: field, a1: field, b0: field, b1: field) -> [field; 3] {
+            return std::bigint::add([a0, a1], [b0, b1]);
+        }",
+        &small(&[("a0", max), ("a1", max), ("b0", 1), ("b1", 2)]),
+        &[],
+    );
+    assert_matches_native(
+        stdlib::BIGINT_MUL_MOD,
+        "fn main(a0: field, a1: field, b0: field, b1: field, m0: field, m1: field) -> [field; 2] {
+            return std::bigint::mul_mod([a0, a1], [b0, b1], [m0, m1]);
+        }",
+        &small(&[
+            ("a0", max),
+            ("a1", 3),
+            ("b0", 12345),
+            ("b1", 5),
+            ("m0", 97),
+            ("m1", 1 << 40),
+        ]),
+        &[],
+    );
+}
+
+#[test]
+fn fixed_point() {
+    // Q8.8: 3.5 and 1.25
+    let pair = small(&[("a", 3 * 256 + 128), ("b", 256 + 64)]);
+    for (name, call) in [
+        (stdlib::FIXED_ADD, "std::fixed::add"),
+        (stdlib::FIXED_SUB, "std::fixed::sub"),
+        (stdlib::FIXED_MUL, "std::fixed::mul"),
+        (stdlib::FIXED_LESS_THAN, "std::fixed::less_than"),
+    ] {
+        let source = format!(
+            "fn main(a: field, b: field) -> field {{ return {}(a, b, 8, 8); }}",
+            call
+        );
+        assert_matches_native(name, &source, &pair, &[8, 8]);
+    }
+    assert_matches_native(
+        stdlib::FIXED_RELU,
+        "fn main(x: field) -> field { return std::fixed::relu(x, 8, 8); }",
+        &small(&[("x", 5 * 256)]),
+        &[8, 8],
+    );
+}
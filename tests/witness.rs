@@ -0,0 +1,47 @@
+//! `CompiledCircuit::compute_witness` accepts inputs the constraints hold for and reports
+//! the constraint that fails otherwise
+
+use fcmc_compiler::backend::CircuitBackend;
+use fcmc_compiler::{FCMCError, InputMap, FCMC};
+use num_bigint::BigUint;
+
+const SOURCE: &str = "fn main(x: field, y: field) -> field {
+    assert x * y == 12;
+    return x + y;
+}";
+
+fn inputs(values: &[(&str, u64)]) -> InputMap {
+    values
+        .iter()
+        .map(|&(name, value)| (name.to_string(), BigUint::from(value)))
+        .collect()
+}
+
+#[test]
+fn satisfying_inputs_give_a_witness() {
+    let circuit = FCMC::new().compile(SOURCE).unwrap();
+    let witness = circuit.compute_witness(&inputs(&[("x", 3), ("y", 4)])).unwrap();
+    assert_eq!(witness.outputs, [("return".to_string(), BigUint::from(7u8))]);
+    assert!(circuit.circuit.is_satisfied(&witness.values));
+}
+
+#[test]
+fn failed_assertion_is_reported() {
+    let circuit = FCMC::new().compile(SOURCE).unwrap();
+    match circuit.compute_witness(&inputs(&[("x", 3), ("y", 5)])) {
+        Err(FCMCError::VerificationError(message)) => assert!(message.contains("is not satisfied"), "{}", message),
+        other => panic!(
+            "expected an unsatisfied constraint, got {:?}",
+            other.map(|witness| witness.outputs)
+        ),
+    }
+}
+
+#[test]
+fn unknown_input_is_refused() {
+    let circuit = FCMC::new().compile(SOURCE).unwrap();
+    let error = circuit
+        .compute_witness(&inputs(&[("x", 3), ("y", 4), ("z", 0)]))
+        .unwrap_err();
+    assert!(error.to_string().contains("no input 'z'"), "{}", error);
+}